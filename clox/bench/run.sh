@@ -0,0 +1,17 @@
+#!/bin/sh
+# Builds clox once with switch dispatch and once with THREADED_DISPATCH, and
+# prints the seconds each takes on every benchmark.
+set -e
+cd "$(dirname "$0")/.."
+
+build=$(mktemp -d)
+trap 'rm -rf "$build"' EXIT
+gcc -O3 -o "$build/switch" src/*.c
+gcc -O3 -DTHREADED_DISPATCH -o "$build/threaded" src/*.c
+
+printf "%-16s %10s %10s\n" benchmark switch threaded
+for bench in bench/*.lox; do
+  switch=$("$build/switch" "$bench" | tail -n 1)
+  threaded=$("$build/threaded" "$bench" | tail -n 1)
+  printf "%-16s %10.3f %10.3f\n" "$(basename "$bench" .lox)" "$switch" "$threaded"
+done
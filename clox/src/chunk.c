@@ -15,12 +15,14 @@ void initChunk(Chunk* chunk) {
   chunk->lines = NULL;
 
   initValueArray(&chunk->constants);
+  chunk->hits = NULL;
 }
 
 void freeChunk(Chunk* chunk) {
   FREE_ARRAY(uint8_t, chunk->code, chunk->capacity);
-  FREE_ARRAY(int, chunk->lines, chunk->lineCapacity);
+  FREE_ARRAY(LineStart, chunk->lines, chunk->lineCapacity);
   freeValueArray(&chunk->constants);
+  if (chunk->hits != NULL) FREE_ARRAY(uint64_t, chunk->hits, chunk->capacity);
   initChunk(chunk);
 }
 
@@ -31,23 +33,22 @@ void writeChunk(Chunk* chunk, uint8_t byte, int line) {
     chunk->code = GROW_ARRAY(uint8_t, chunk->code, oldCapacity, chunk->capacity);
   }
 
-  if (chunk->lineCount > 0 && chunk->lines[chunk->lineCount - 2] == line) {
-    chunk->lines[chunk->lineCount - 1]++;
-  } else {
-    if (chunk->lineCapacity < chunk->lineCount + 2) {
-      int oldCapacity = chunk->lineCapacity;
-      chunk->lineCapacity = GROW_CAPACITY(oldCapacity);
-      chunk->lines = GROW_ARRAY(int, chunk->lines, oldCapacity, chunk->lineCapacity);
-    }
+  chunk->code[chunk->count] = byte;
+  chunk->count++;
 
-    chunk->lines[chunk->lineCount] = line;
-    chunk->lines[chunk->lineCount + 1] = 1;
+  if (chunk->lineCount > 0 && chunk->lines[chunk->lineCount - 1].line == line) {
+    return;
+  }
 
-    chunk->lineCount = chunk->lineCount + 2;
+  if (chunk->lineCapacity < chunk->lineCount + 1) {
+    int oldCapacity = chunk->lineCapacity;
+    chunk->lineCapacity = GROW_CAPACITY(oldCapacity);
+    chunk->lines = GROW_ARRAY(LineStart, chunk->lines, oldCapacity, chunk->lineCapacity);
   }
 
-  chunk->code[chunk->count] = byte;
-  chunk->count++;
+  LineStart* lineStart = &chunk->lines[chunk->lineCount++];
+  lineStart->offset = chunk->count - 1;
+  lineStart->line = line;
 }
 
 void writeConstant(Chunk* chunk, Value value, int line) {
@@ -64,6 +65,11 @@ void writeConstant(Chunk* chunk, Value value, int line) {
 }
 
 int addConstant(Chunk* chunk, Value value) {
+  // Strings are interned, so a repeated name or literal finds its slot here.
+  for (int i = 0; i < chunk->constants.count; i++) {
+    if (valuesEqual(chunk->constants.values[i], value)) return i;
+  }
+
   push(value);
   writeValueArray(&chunk->constants, value);
   pop();
@@ -71,18 +77,24 @@ int addConstant(Chunk* chunk, Value value) {
 }
 
 /*
-  Lines are stored in a dynamic array in the following way:
-
-  {line, count, line, count, line, count}
+  Lines are stored run-length encoded, one LineStart for each run of bytes on
+  the same line, in order of offset. The run holding `index` is the last one
+  starting at or before it, found by binary search.
 */
 int getLine(Chunk* chunk, int index) {
-  int sum = 0;
-  for (int i = 1; i < chunk->lineCount; i = i + 2) {
-    sum = sum + chunk->lines[i];
-
-    if (index < sum) return chunk->lines[i - 1];
+  if (index < 0 || index >= chunk->count) return -1;
+
+  int start = 0;
+  int end = chunk->lineCount - 1;
+  while (start < end) {
+    int mid = start + (end - start + 1) / 2;
+    if (chunk->lines[mid].offset <= index) {
+      start = mid;
+    } else {
+      end = mid - 1;
+    }
   }
 
-  return -1;
+  return chunk->lines[start].line;
 }
 
@@ -10,6 +10,8 @@ typedef enum {
   OP_NIL,
   OP_TRUE,
   OP_FALSE,
+  OP_ZERO,
+  OP_ONE,
   OP_POP,
   OP_GET_LOCAL,
   OP_SET_LOCAL,
@@ -43,16 +45,34 @@ typedef enum {
   OP_CLASS,
   OP_INHERIT,
   OP_METHOD,
+  // Makes the constant operand of the next instruction three bytes.
+  OP_WIDE,
+  // Superinstructions, fused from the instructions they stand for by
+  // fuseSuperinstructions().
+  OP_ADD_CONSTANT,
+  OP_ADD_LOCALS,
+  OP_INCREMENT,
+  OP_EQUAL_JUMP,
+  OP_GREATER_JUMP,
+  OP_LESS_JUMP,
 } OpCode;
 
+// The first instruction of a run of instructions all on `line`.
+typedef struct {
+  int offset;
+  int line;
+} LineStart;
+
 typedef struct {
   int count;
   int capacity;
   uint8_t* code;
   int lineCount;
   int lineCapacity;
-  int* lines;
+  LineStart* lines;
   ValueArray constants;
+  // Times each instruction ran, by offset, when the VM counts hotspots.
+  uint64_t* hits;
 } Chunk;
 
 void initChunk(Chunk* chunk);
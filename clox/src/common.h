@@ -7,6 +7,11 @@
 
 #define NAN_BOXING
 
+// Dispatches instructions by jumping through a table of label addresses, a
+// GCC and Clang extension, rather than through a switch. bench/run.sh times
+// both.
+// #define THREADED_DISPATCH
+
 // #define DEBUG_PRINT_CODE
 // #define DEBUG_TRACE_EXECUTION
 
@@ -1,9 +1,11 @@
 #include <stdio.h>
+#include <stdlib.h>
 
 #include "debug.h"
 #include "chunk.h"
 #include "object.h"
 #include "value.h"
+#include "vm.h"
 
 void disassembleChunk(Chunk* chunk, const char* name) {
   printf("== %s ==\n", name);
@@ -13,13 +15,30 @@ void disassembleChunk(Chunk* chunk, const char* name) {
   }
 }
 
+// Set by OP_WIDE for the instruction after it.
+static bool wide = false;
+
+// Reads the constant operand at `offset` and moves past it.
+static int readConstant(Chunk* chunk, int* offset) {
+  uint8_t* code = chunk->code + *offset;
+  if (!wide) {
+    *offset += 1;
+    return code[0];
+  }
+
+  wide = false;
+  *offset += 3;
+  return code[0] | (code[1] << 8) | (code[2] << 16);
+}
+
 static int constantInstruction(const char* name, Chunk* chunk, int offset) {
-  uint8_t constant = chunk->code[offset + 1];
+  offset++;
+  int constant = readConstant(chunk, &offset);
   printf("%-16s %4d '", name, constant);
   printValue(chunk->constants.values[constant]);
   printf("'\n");
 
-  return offset + 2;
+  return offset;
 }
 
 static int longConstantInstruction(const char* name, Chunk* chunk, int offset) {
@@ -32,13 +51,22 @@ static int longConstantInstruction(const char* name, Chunk* chunk, int offset) {
   return offset + 4;
 }
 
+static int globalInstruction(const char* name, Chunk* chunk, int offset) {
+  uint16_t slot = (uint16_t)(chunk->code[offset + 1] << 8);
+  slot |= chunk->code[offset + 2];
+  printf("%-16s %4d '%s'\n", name, slot,
+         AS_STRING(vm.globalNames.values[slot])->chars);
+  return offset + 3;
+}
+
 static int invokeInstruction(const char* name, Chunk* chunk, int offset) {
-  uint8_t constant = chunk->code[offset + 1];
-  uint8_t argCount = chunk->code[offset + 2];
+  offset++;
+  int constant = readConstant(chunk, &offset);
+  uint8_t argCount = chunk->code[offset++];
   printf("%-16s (%d args) %4d '", name, argCount, constant);
   printValue(chunk->constants.values[constant]);
   printf("'\n");
-  return offset + 3;
+  return offset;
 }
 
 static int simpleInstruction(const char* name, int offset) {
@@ -52,6 +80,11 @@ static int byteInstruction(const char* name, Chunk* chunk, int offset) {
   return offset + 2;
 }
 
+static int twoByteInstruction(const char* name, Chunk* chunk, int offset) {
+  printf("%-16s %4d %4d\n", name, chunk->code[offset + 1], chunk->code[offset + 2]);
+  return offset + 3;
+}
+
 static int jumpInstruction(const char* name, int sign, Chunk* chunk, int offset) {
   uint16_t jump = (uint16_t)(chunk->code[offset + 1] << 8);
   jump |= chunk->code[offset + 2];
@@ -80,6 +113,10 @@ int disassembleInstruction(Chunk* chunk, int offset) {
       return simpleInstruction("OP_TRUE", offset);
     case OP_FALSE:
       return simpleInstruction("OP_FALSE", offset);
+    case OP_ZERO:
+      return simpleInstruction("OP_ZERO", offset);
+    case OP_ONE:
+      return simpleInstruction("OP_ONE", offset);
     case OP_POP:
       return simpleInstruction("OP_POP", offset);
     case OP_GET_LOCAL:
@@ -87,11 +124,11 @@ int disassembleInstruction(Chunk* chunk, int offset) {
     case OP_SET_LOCAL:
       return byteInstruction("OP_SET_LOCAL", chunk, offset);
     case OP_GET_GLOBAL:
-      return constantInstruction("OP_GET_GLOBAL", chunk, offset);
+      return globalInstruction("OP_GET_GLOBAL", chunk, offset);
     case OP_DEFINE_GLOBAL:
-      return constantInstruction("OP_DEFINE_GLOBAL", chunk, offset);
+      return globalInstruction("OP_DEFINE_GLOBAL", chunk, offset);
     case OP_SET_GLOBAL:
-      return constantInstruction("OP_SET_GLOBAL", chunk, offset);
+      return globalInstruction("OP_SET_GLOBAL", chunk, offset);
     case OP_GET_UPVALUE:
       return byteInstruction("OP_GET_UPVALUE", chunk, offset);
     case OP_SET_UPVALUE:
@@ -136,7 +173,7 @@ int disassembleInstruction(Chunk* chunk, int offset) {
       return invokeInstruction("OP_SUPER_INVOKE", chunk, offset);
     case OP_CLOSURE: {
         offset++;
-        uint8_t constant = chunk->code[offset++];
+        int constant = readConstant(chunk, &offset);
         printf("%-16s %4d ", "OP_CLOSURE", constant);
         printValue(chunk->constants.values[constant]);
         printf("\n");
@@ -161,9 +198,79 @@ int disassembleInstruction(Chunk* chunk, int offset) {
       return simpleInstruction("OP_INHERIT", offset);
     case OP_METHOD:
       return constantInstruction("OP_METHOD", chunk, offset);
+    case OP_WIDE:
+      wide = true;
+      return simpleInstruction("OP_WIDE", offset);
+    case OP_ADD_CONSTANT:
+      return constantInstruction("OP_ADD_CONSTANT", chunk, offset);
+    case OP_ADD_LOCALS:
+      return twoByteInstruction("OP_ADD_LOCALS", chunk, offset);
+    case OP_INCREMENT:
+      return simpleInstruction("OP_INCREMENT", offset);
+    case OP_EQUAL_JUMP:
+      return jumpInstruction("OP_EQUAL_JUMP", 1, chunk, offset);
+    case OP_GREATER_JUMP:
+      return jumpInstruction("OP_GREATER_JUMP", 1, chunk, offset);
+    case OP_LESS_JUMP:
+      return jumpInstruction("OP_LESS_JUMP", 1, chunk, offset);
     default:
       printf("Unknown opcode %d\n", instruction);
       return offset + 1;
   }
 }
 
+static void printSourceLine(const char* source, int line) {
+  const char* start = source;
+  for (int i = 1; i < line && *start != '\0'; start++) {
+    if (*start == '\n') i++;
+  }
+  while (*start == ' ' || *start == '\t') start++;
+
+  int length = 0;
+  while (start[length] != '\0' && start[length] != '\n') length++;
+  fprintf(stderr, "%.*s", length, start);
+}
+
+/*
+  Adds up the instructions every function ran, line by line, and prints the
+  `limit` busiest lines of `source`, most executed first, to stderr so they
+  don't mix with the script's output.
+*/
+void printHotspots(const char* source, int limit) {
+  int lineCount = 1;
+  for (Obj* object = vm.objects; object != NULL; object = object->next) {
+    if (object->type != OBJ_FUNCTION) continue;
+    Chunk* chunk = &((ObjFunction*)object)->chunk;
+    for (int i = 0; i < chunk->lineCount; i++) {
+      if (chunk->lines[i].line >= lineCount) lineCount = chunk->lines[i].line + 1;
+    }
+  }
+
+  // Not through the GC, which would free the functions that finished.
+  uint64_t* hits = calloc(lineCount, sizeof(uint64_t));
+  if (hits == NULL) exit(1);
+  for (Obj* object = vm.objects; object != NULL; object = object->next) {
+    if (object->type != OBJ_FUNCTION) continue;
+    Chunk* chunk = &((ObjFunction*)object)->chunk;
+    if (chunk->hits == NULL) continue;
+    for (int offset = 0; offset < chunk->count; offset++) {
+      hits[getLine(chunk, offset)] += chunk->hits[offset];
+    }
+  }
+
+  fprintf(stderr, "== hotspots ==\n");
+  for (int shown = 0; shown < limit; shown++) {
+    int busiest = 0;
+    for (int line = 1; line < lineCount; line++) {
+      if (hits[line] > hits[busiest]) busiest = line;
+    }
+    if (hits[busiest] == 0) break;
+
+    fprintf(stderr, "%6d %14llu  ", busiest, (unsigned long long)hits[busiest]);
+    printSourceLine(source, busiest);
+    fprintf(stderr, "\n");
+    hits[busiest] = 0;
+  }
+
+  free(hits);
+}
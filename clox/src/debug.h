@@ -7,4 +7,6 @@ void disassembleChunk(Chunk* chunk, const char* name);
 
 int disassembleInstruction(Chunk* chunk, int offset);
 
+void printHotspots(const char* source, int limit);
+
 #endif
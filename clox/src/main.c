@@ -51,25 +51,47 @@ static char* readFile(const char* path) {
   return buffer;
 }
 
-static void runFile(const char* path) {
+static void runFile(const char* path, int hotspots) {
   char* source = readFile(path);
+  vm.hotspots = hotspots > 0;
   InterpretResult result = interpret(source);
+  if (hotspots > 0) printHotspots(source, hotspots);
   free(source);
 
   if (result == INTERPRET_COMPILE_ERROR) exit(65);
   if (result == INTERPRET_RUNTIME_ERROR) exit(70);
 }
 
+static void usage() {
+  fprintf(stderr, "Usage: clox [--hotspots[=count]] [--no-superinstructions] [path]\n");
+  exit(64);
+}
+
 int main(int argc, const char* argv[]) {
   initVM();
 
-  if (argc == 1) {
+  // How many of the most executed lines to show after the run, if any.
+  int hotspots = 0;
+  int arg = 1;
+  for (; arg < argc && strncmp(argv[arg], "--", 2) == 0; arg++) {
+    if (strcmp(argv[arg], "--hotspots") == 0) {
+      hotspots = 10;
+    } else if (strcmp(argv[arg], "--no-superinstructions") == 0) {
+      vm.superinstructions = false;
+    } else if (strncmp(argv[arg], "--hotspots=", 11) == 0) {
+      hotspots = atoi(argv[arg] + 11);
+      if (hotspots <= 0) usage();
+    } else {
+      usage();
+    }
+  }
+
+  if (arg == argc && hotspots == 0) {
     repl();
-  } else if (argc == 2) {
-    runFile(argv[1]);
+  } else if (arg == argc - 1) {
+    runFile(argv[arg], hotspots);
   } else {
-    fprintf(stderr, "Usage: clox [path]\n");
-    exit(64);
+    usage();
   }
   
   freeVM();
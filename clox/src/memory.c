@@ -182,7 +182,8 @@ static void markRoots() {
     markObject((Obj*)upvalue);       
   }
 
-  markTable(&vm.globals);
+  markTable(&vm.globalSlots);
+  markArray(&vm.globalValues);
   markCompilerRoots();
   markObject((Obj*)vm.initString);
 }
@@ -0,0 +1,215 @@
+#include <stdlib.h>
+
+#include "chunk.h"
+#include "memory.h"
+#include "object.h"
+#include "peephole.h"
+
+int instructionLength(Chunk* chunk, int offset) {
+  switch (chunk->code[offset]) {
+    case OP_CONSTANT_LONG:
+      return 4;
+    case OP_JUMP:
+    case OP_JUMP_IF_FALSE:
+    case OP_LOOP:
+    case OP_INVOKE:
+    case OP_SUPER_INVOKE:
+    case OP_GET_GLOBAL:
+    case OP_DEFINE_GLOBAL:
+    case OP_SET_GLOBAL:
+    case OP_ADD_LOCALS:
+    case OP_EQUAL_JUMP:
+    case OP_GREATER_JUMP:
+    case OP_LESS_JUMP:
+      return 3;
+    case OP_CONSTANT:
+    case OP_GET_LOCAL:
+    case OP_SET_LOCAL:
+    case OP_GET_UPVALUE:
+    case OP_SET_UPVALUE:
+    case OP_GET_PROPERTY:
+    case OP_SET_PROPERTY:
+    case OP_GET_SUPER:
+    case OP_CALL:
+    case OP_CLASS:
+    case OP_METHOD:
+    case OP_ADD_CONSTANT:
+      return 2;
+    case OP_CLOSURE: {
+        ObjFunction* function =
+          AS_FUNCTION(chunk->constants.values[chunk->code[offset + 1]]);
+        return 2 + 2 * function->upvalueCount;
+      }
+    case OP_WIDE: {
+        // The prefix, then the instruction with two more operand bytes.
+        uint8_t* code = chunk->code + offset + 1;
+        if (code[0] != OP_CLOSURE) {
+          return 1 + instructionLength(chunk, offset + 1) + 2;
+        }
+
+        int constant = code[1] | (code[2] << 8) | (code[3] << 16);
+        ObjFunction* function = AS_FUNCTION(chunk->constants.values[constant]);
+        return 5 + 2 * function->upvalueCount;
+      }
+    default:
+      return 1;
+  }
+}
+
+static bool isJump(uint8_t instruction) {
+  switch (instruction) {
+    case OP_JUMP:
+    case OP_JUMP_IF_FALSE:
+    case OP_LOOP:
+    case OP_EQUAL_JUMP:
+    case OP_GREATER_JUMP:
+    case OP_LESS_JUMP:
+      return true;
+    default:
+      return false;
+  }
+}
+
+static int jumpTarget(Chunk* chunk, int offset) {
+  int jump = (chunk->code[offset + 1] << 8) | chunk->code[offset + 2];
+  if (chunk->code[offset] == OP_LOOP) return offset + 3 - jump;
+  return offset + 3 + jump;
+}
+
+// The superinstruction the instructions at `offset` fuse into, and how many
+// of them, or -1 if they don't fuse.
+static int fusion(Chunk* chunk, int offset, bool* isTarget, int* fused) {
+  uint8_t* code = chunk->code;
+  int next = offset + instructionLength(chunk, offset);
+  if (next >= chunk->count || isTarget[next]) return -1;
+
+  switch (code[offset]) {
+    case OP_CONSTANT:
+      if (code[next] != OP_ADD) break;
+      *fused = 2;
+      return OP_ADD_CONSTANT;
+    case OP_ONE:
+      if (code[next] != OP_ADD) break;
+      *fused = 2;
+      return OP_INCREMENT;
+    case OP_GET_LOCAL: {
+        int last = next + 2;
+        if (code[next] != OP_GET_LOCAL || last >= chunk->count ||
+            isTarget[last] || code[last] != OP_ADD) {
+          break;
+        }
+        *fused = 3;
+        return OP_ADD_LOCALS;
+      }
+    case OP_EQUAL:
+    case OP_GREATER:
+    case OP_LESS:
+      if (code[next] != OP_JUMP_IF_FALSE) break;
+      *fused = 2;
+      return code[offset] == OP_EQUAL ? OP_EQUAL_JUMP
+           : code[offset] == OP_GREATER ? OP_GREATER_JUMP
+           : OP_LESS_JUMP;
+    default:
+      break;
+  }
+
+  return -1;
+}
+
+/*
+  Rewrites common sequences of instructions as single superinstructions:
+
+    OP_CONSTANT k, OP_ADD                  -> OP_ADD_CONSTANT k
+    OP_ONE, OP_ADD                         -> OP_INCREMENT
+    OP_GET_LOCAL a, OP_GET_LOCAL b, OP_ADD -> OP_ADD_LOCALS a b
+    OP_LESS, OP_JUMP_IF_FALSE j            -> OP_LESS_JUMP j
+
+  and likewise OP_GREATER and OP_EQUAL. A sequence only fuses if nothing
+  jumps into the middle of it. The code shrinks, so every jump is moved to
+  the new offset of its target.
+*/
+void fuseSuperinstructions(Chunk* chunk) {
+  bool* isTarget = calloc(chunk->count + 1, sizeof(bool));
+  int* newOffset = malloc(sizeof(int) * (chunk->count + 1));
+  if (isTarget == NULL || newOffset == NULL) exit(1);
+  // Instructions fused into the one before them have no offset of their own.
+  for (int i = 0; i <= chunk->count; i++) newOffset[i] = -1;
+
+  for (int offset = 0; offset < chunk->count;
+       offset += instructionLength(chunk, offset)) {
+    if (isJump(chunk->code[offset])) {
+      int target = jumpTarget(chunk, offset);
+      if (target >= 0 && target <= chunk->count) isTarget[target] = true;
+    }
+  }
+
+  Chunk fused;
+  initChunk(&fused);
+
+  for (int offset = 0; offset < chunk->count;) {
+    int line = getLine(chunk, offset);
+    int length = instructionLength(chunk, offset);
+    int count = 1;
+    int superinstruction = fusion(chunk, offset, isTarget, &count);
+    newOffset[offset] = fused.count;
+
+    if (superinstruction == -1) {
+      for (int i = 0; i < length; i++) {
+        writeChunk(&fused, chunk->code[offset + i], line);
+      }
+      offset += length;
+      continue;
+    }
+
+    writeChunk(&fused, superinstruction, line);
+    switch (superinstruction) {
+      case OP_INCREMENT:
+        break;
+      case OP_ADD_CONSTANT:
+        writeChunk(&fused, chunk->code[offset + 1], line);
+        break;
+      case OP_ADD_LOCALS:
+        writeChunk(&fused, chunk->code[offset + 1], line);
+        writeChunk(&fused, chunk->code[offset + 3], line);
+        break;
+      default:
+        // Jumps keep their old offset until every target has moved.
+        writeChunk(&fused, chunk->code[offset + 2], line);
+        writeChunk(&fused, chunk->code[offset + 3], line);
+        break;
+    }
+
+    for (int i = 0; i < count; i++) {
+      offset += instructionLength(chunk, offset);
+    }
+  }
+  newOffset[chunk->count] = fused.count;
+  // The constants are shared, for the lengths of OP_CLOSURE instructions.
+  fused.constants = chunk->constants;
+
+  // Walk the old and new code together to point the jumps at their targets.
+  int offset = 0;
+  for (int at = 0; at < fused.count; at += instructionLength(&fused, at)) {
+    while (newOffset[offset] != at) offset += instructionLength(chunk, offset);
+    if (!isJump(fused.code[at])) continue;
+
+    int jumpFrom = isJump(chunk->code[offset]) ? offset
+                 : offset + instructionLength(chunk, offset);
+    int target = newOffset[jumpTarget(chunk, jumpFrom)];
+    int jump = fused.code[at] == OP_LOOP ? at + 3 - target : target - at - 3;
+    fused.code[at + 1] = (jump >> 8) & 0xff;
+    fused.code[at + 2] = jump & 0xff;
+  }
+
+  FREE_ARRAY(uint8_t, chunk->code, chunk->capacity);
+  FREE_ARRAY(LineStart, chunk->lines, chunk->lineCapacity);
+  chunk->code = fused.code;
+  chunk->count = fused.count;
+  chunk->capacity = fused.capacity;
+  chunk->lines = fused.lines;
+  chunk->lineCount = fused.lineCount;
+  chunk->lineCapacity = fused.lineCapacity;
+
+  free(isTarget);
+  free(newOffset);
+}
@@ -0,0 +1,10 @@
+#ifndef clox_peephole_h
+#define clox_peephole_h
+
+#include "chunk.h"
+
+int instructionLength(Chunk* chunk, int offset);
+
+void fuseSuperinstructions(Chunk* chunk);
+
+#endif
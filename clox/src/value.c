@@ -1,3 +1,4 @@
+#include <math.h>
 #include <stdlib.h>
 #include <stdio.h>
 #include <string.h>
@@ -29,6 +30,53 @@ void writeValueArray(ValueArray* array, Value value) {
   array->count++;
 }
 
+// Prints the shortest digits that read back as the same number, laid out the
+// way jlox prints numbers: positional between 1e-5 and 1e16, exponent outside.
+static void printNumber(double number) {
+  if (number != number) {
+    printf("NaN");
+    return;
+  }
+  if (isinf(number)) {
+    printf(number > 0 ? "inf" : "-inf");
+    return;
+  }
+
+  char buffer[32];
+  for (int precision = 0; precision < 17; precision++) {
+    snprintf(buffer, sizeof(buffer), "%.*e", precision, number);
+    if (strtod(buffer, NULL) == number) break;
+  }
+
+  char* cursor = buffer;
+  if (*cursor == '-') {
+    putchar('-');
+    cursor++;
+  }
+  char digits[20];
+  int length = 0;
+  for (; *cursor != 'e'; cursor++) {
+    if (*cursor != '.') digits[length++] = *cursor;
+  }
+  digits[length] = '\0';
+  int point = atoi(cursor + 1) + 1;
+
+  if (point >= length && point <= 16) {
+    printf("%s", digits);
+    for (int i = length; i < point; i++) putchar('0');
+  } else if (point > 0 && point <= 16) {
+    printf("%.*s.%s", point, digits, digits + point);
+  } else if (point > -5 && point <= 0) {
+    printf("0.");
+    for (int i = point; i < 0; i++) putchar('0');
+    printf("%s", digits);
+  } else if (length == 1) {
+    printf("%se%d", digits, point - 1);
+  } else {
+    printf("%c.%se%d", digits[0], digits + 1, point - 1);
+  }
+}
+
 void printValue(Value value) {
 #ifdef NAN_BOXING
   if (IS_BOOL(value)) {
@@ -36,7 +84,7 @@ void printValue(Value value) {
   } else if (IS_NIL(value)) {
     printf("nil");
   } else if (IS_NUMBER(value)) {
-    printf("%g", AS_NUMBER(value));
+    printNumber(AS_NUMBER(value));
   } else if (IS_OBJ(value)) {
     printObject(value);
   }
@@ -46,8 +94,9 @@ void printValue(Value value) {
       printf(AS_BOOL(value) ? "true" : "false");
       break;
     case VAL_NIL: printf("nil"); break;
-    case VAL_NUMBER: printf("%g", AS_NUMBER(value)); break;
+    case VAL_NUMBER: printNumber(AS_NUMBER(value)); break;
     case VAL_OBJ: printObject(value); break;
+    case VAL_UNDEFINED: break;
   }
 #endif
 }
@@ -49,7 +49,8 @@ static void runtimeError(const char* format, ...) {
 static void defineNative(const char* name, NativeFn function) {
   push(OBJ_VAL(copyString(name, (int)strlen(name))));
   push(OBJ_VAL(newNative(function)));
-  tableSet(&vm.globals, AS_STRING(vm.stack[0]), vm.stack[1]);
+  int slot = globalSlot(AS_STRING(vm.stack[0]));
+  vm.globalValues.values[slot] = vm.stack[1];
   pop();
   pop();
 }
@@ -64,22 +65,41 @@ void initVM() {
   vm.grayCapacity = 0;
   vm.grayStack = NULL;
 
-  initTable(&vm.globals);
+  initTable(&vm.globalSlots);
+  initValueArray(&vm.globalNames);
+  initValueArray(&vm.globalValues);
   initTable(&vm.strings);
 
   vm.initString = NULL;
   vm.initString = copyString("init", 4);
+  vm.hotspots = false;
+  vm.superinstructions = true;
 
   defineNative("clock", clockNative);
 }
 
 void freeVM() {
-  freeTable(&vm.globals);
+  freeTable(&vm.globalSlots);
+  freeValueArray(&vm.globalNames);
+  freeValueArray(&vm.globalValues);
   freeTable(&vm.strings);
   vm.initString = NULL;
   freeObjects();
 }
 
+/* The slot of the global `name`, added undefined if it has none yet. */
+int globalSlot(ObjString* name) {
+  Value slot;
+  if (tableGet(&vm.globalSlots, name, &slot)) return (int)AS_NUMBER(slot);
+
+  push(OBJ_VAL(name));
+  writeValueArray(&vm.globalNames, OBJ_VAL(name));
+  writeValueArray(&vm.globalValues, UNDEFINED_VAL);
+  tableSet(&vm.globalSlots, name, NUMBER_VAL(vm.globalValues.count - 1));
+  pop();
+  return vm.globalValues.count - 1;
+}
+
 void push(Value value) {
   *vm.stackTop = value;
   vm.stackTop++;
@@ -105,6 +125,12 @@ static bool call(ObjClosure* closure, int argCount) {
     return false;
   }
   
+  Chunk* chunk = &closure->function->chunk;
+  if (vm.hotspots && chunk->hits == NULL) {
+    chunk->hits = ALLOCATE(uint64_t, chunk->capacity);
+    memset(chunk->hits, 0, sizeof(uint64_t) * chunk->capacity);
+  }
+
   CallFrame* frame = &vm.frames[vm.frameCount++];
   frame->closure = closure;
   frame->ip = closure->function->chunk.code;
@@ -251,18 +277,69 @@ static void concatenate() {
   pop();
   push(OBJ_VAL(result));
 }
+static bool add() {
+  if (IS_STRING(peek(0)) && IS_STRING(peek(1))) {
+    concatenate();
+  } else if (IS_NUMBER(peek(0)) && IS_NUMBER(peek(1))) {
+    double b = AS_NUMBER(pop());
+    double a = AS_NUMBER(pop());
+    push(NUMBER_VAL(a + b));
+  } else {
+    runtimeError("Operands must be two numbers or two strings.");
+    return false;
+  }
+  return true;
+}
+
+/* Traces and counts the instruction at `ip`, as the build and flags ask. */
+static inline void beginInstruction(CallFrame* frame, uint8_t* ip) {
+#ifdef DEBUG_TRACE_EXECUTION
+  printf("          ");
+  for (Value* slot = vm.stack; slot < vm.stackTop; slot++) {
+    printf("[ ");
+    printValue(*slot);
+    printf(" ]");
+  }
+  printf("\n");
+  disassembleInstruction(&frame->closure->function->chunk,
+                         (int)(ip - frame->closure->function->chunk.code));
+#endif
+  if (vm.hotspots) {
+    Chunk* chunk = &frame->closure->function->chunk;
+    chunk->hits[ip - chunk->code]++;
+  }
+}
+
 static InterpretResult run() {
   CallFrame* frame = &vm.frames[vm.frameCount - 1];
   register uint8_t* ip = frame->ip;
+  // Set by OP_WIDE for the instruction after it.
+  bool wide = false;
 
 #define READ_BYTE() (*ip++)
 
-#define READ_CONSTANT() (frame->closure->function->chunk.constants.values[READ_BYTE()])
+#define READ_INDEX() \
+  (wide ? (wide = false, ip += 3, ip[-3] | (ip[-2] << 8) | (ip[-1] << 16)) \
+        : READ_BYTE())
+
+#define READ_CONSTANT() (frame->closure->function->chunk.constants.values[READ_INDEX()])
 
 #define READ_SHORT() \
   (ip += 2, (uint16_t)((ip[-2] << 8) | ip[-1]))
 
 #define READ_STRING() AS_STRING(READ_CONSTANT())
+#ifdef THREADED_DISPATCH
+#define CASE(opcode) handle_##opcode
+#define DISPATCH() \
+  do { \
+    beginInstruction(frame, ip); \
+    goto *dispatchTable[READ_BYTE()]; \
+  } while (false)
+#else
+#define CASE(opcode) case opcode
+#define DISPATCH() break
+#endif
+
 #define BINARY_OP(valueType, op) \
   do { \
     if (!IS_NUMBER(peek(0)) || !IS_NUMBER(peek(1))) { \
@@ -275,77 +352,129 @@ static InterpretResult run() {
     push(valueType(a op b)); \
   } while (false)
 
+#ifdef THREADED_DISPATCH
+  static void* dispatchTable[] = {
+    [OP_CONSTANT] = &&CASE(OP_CONSTANT),
+    [OP_CONSTANT_LONG] = &&CASE(OP_CONSTANT_LONG),
+    [OP_NIL] = &&CASE(OP_NIL),
+    [OP_TRUE] = &&CASE(OP_TRUE),
+    [OP_FALSE] = &&CASE(OP_FALSE),
+    [OP_ZERO] = &&CASE(OP_ZERO),
+    [OP_ONE] = &&CASE(OP_ONE),
+    [OP_POP] = &&CASE(OP_POP),
+    [OP_GET_LOCAL] = &&CASE(OP_GET_LOCAL),
+    [OP_GET_GLOBAL] = &&CASE(OP_GET_GLOBAL),
+    [OP_SET_LOCAL] = &&CASE(OP_SET_LOCAL),
+    [OP_DEFINE_GLOBAL] = &&CASE(OP_DEFINE_GLOBAL),
+    [OP_SET_GLOBAL] = &&CASE(OP_SET_GLOBAL),
+    [OP_GET_UPVALUE] = &&CASE(OP_GET_UPVALUE),
+    [OP_SET_UPVALUE] = &&CASE(OP_SET_UPVALUE),
+    [OP_GET_PROPERTY] = &&CASE(OP_GET_PROPERTY),
+    [OP_SET_PROPERTY] = &&CASE(OP_SET_PROPERTY),
+    [OP_GET_SUPER] = &&CASE(OP_GET_SUPER),
+    [OP_EQUAL] = &&CASE(OP_EQUAL),
+    [OP_GREATER] = &&CASE(OP_GREATER),
+    [OP_LESS] = &&CASE(OP_LESS),
+    [OP_ADD] = &&CASE(OP_ADD),
+    [OP_SUBTRACT] = &&CASE(OP_SUBTRACT),
+    [OP_MULTIPLY] = &&CASE(OP_MULTIPLY),
+    [OP_DIVIDE] = &&CASE(OP_DIVIDE),
+    [OP_NOT] = &&CASE(OP_NOT),
+    [OP_NEGATE] = &&CASE(OP_NEGATE),
+    [OP_PRINT] = &&CASE(OP_PRINT),
+    [OP_JUMP] = &&CASE(OP_JUMP),
+    [OP_JUMP_IF_FALSE] = &&CASE(OP_JUMP_IF_FALSE),
+    [OP_LOOP] = &&CASE(OP_LOOP),
+    [OP_CALL] = &&CASE(OP_CALL),
+    [OP_INVOKE] = &&CASE(OP_INVOKE),
+    [OP_SUPER_INVOKE] = &&CASE(OP_SUPER_INVOKE),
+    [OP_CLOSURE] = &&CASE(OP_CLOSURE),
+    [OP_CLOSE_UPVALUE] = &&CASE(OP_CLOSE_UPVALUE),
+    [OP_RETURN] = &&CASE(OP_RETURN),
+    [OP_CLASS] = &&CASE(OP_CLASS),
+    [OP_INHERIT] = &&CASE(OP_INHERIT),
+    [OP_METHOD] = &&CASE(OP_METHOD),
+    [OP_WIDE] = &&CASE(OP_WIDE),
+    [OP_ADD_CONSTANT] = &&CASE(OP_ADD_CONSTANT),
+    [OP_ADD_LOCALS] = &&CASE(OP_ADD_LOCALS),
+    [OP_INCREMENT] = &&CASE(OP_INCREMENT),
+    [OP_EQUAL_JUMP] = &&CASE(OP_EQUAL_JUMP),
+    [OP_GREATER_JUMP] = &&CASE(OP_GREATER_JUMP),
+    [OP_LESS_JUMP] = &&CASE(OP_LESS_JUMP),
+  };
+
+  DISPATCH();
+#else
   for (;;) {
-#ifdef DEBUG_TRACE_EXECUTION
-    printf("          ");
-    for (Value* slot = vm.stack; slot < vm.stackTop; slot++) {
-      printf("[ ");
-      printValue(*slot);
-      printf(" ]");
-    }
-    printf("\n");
-    disassembleInstruction(&frame->closure->function->chunk,
-                           (int)(frame->ip - frame->closure->function->chunk.code));
+    beginInstruction(frame, ip);
+    switch (READ_BYTE()) {
 #endif
-    uint8_t instruction;
-    switch (instruction = READ_BYTE()) {
-      case OP_CONSTANT: {
+      CASE(OP_CONSTANT): {
           Value constant = READ_CONSTANT();
           push(constant);
-          break;
+          DISPATCH();
         }
-      case OP_NIL: push(NIL_VAL); break;
-      case OP_TRUE: push(BOOL_VAL(true)); break;
-      case OP_FALSE: push(BOOL_VAL(false)); break;
-      case OP_POP: pop(); break;
-      case OP_GET_LOCAL: {
+      CASE(OP_CONSTANT_LONG): {
+          int index = ip[0] | (ip[1] << 8) | (ip[2] << 16);
+          ip += 3;
+          push(frame->closure->function->chunk.constants.values[index]);
+          DISPATCH();
+        }
+      CASE(OP_NIL): push(NIL_VAL); DISPATCH();
+      CASE(OP_TRUE): push(BOOL_VAL(true)); DISPATCH();
+      CASE(OP_FALSE): push(BOOL_VAL(false)); DISPATCH();
+      CASE(OP_ZERO): push(NUMBER_VAL(0)); DISPATCH();
+      CASE(OP_ONE): push(NUMBER_VAL(1)); DISPATCH();
+      CASE(OP_POP): pop(); DISPATCH();
+      CASE(OP_GET_LOCAL): {
           uint8_t slot = READ_BYTE();
           push(frame->slots[slot]);
-          break;
+          DISPATCH();
         }
-      case OP_GET_GLOBAL: {
-          ObjString* name = READ_STRING();
-          Value value;
-          if (!tableGet(&vm.globals, name, &value)) {
+      CASE(OP_GET_GLOBAL): {
+          uint16_t slot = READ_SHORT();
+          Value value = vm.globalValues.values[slot];
+          if (IS_UNDEFINED(value)) {
             frame->ip = ip;
-            runtimeError("Undefined variable '%s'.", name->chars);
+            runtimeError("Undefined variable '%s'.",
+                         AS_STRING(vm.globalNames.values[slot])->chars);
             return INTERPRET_RUNTIME_ERROR;
           }
           push(value);
-          break;
+          DISPATCH();
         }
-      case OP_SET_LOCAL: {
+      CASE(OP_SET_LOCAL): {
           uint8_t slot = READ_BYTE();
           frame->slots[slot] = peek(0);
-          break;
+          DISPATCH();
         }
-      case OP_DEFINE_GLOBAL: {
-          ObjString* name = READ_STRING();
-          tableSet(&vm.globals, name, peek(0));
-          pop();
-          break;
+      CASE(OP_DEFINE_GLOBAL): {
+          uint16_t slot = READ_SHORT();
+          vm.globalValues.values[slot] = pop();
+          DISPATCH();
         }
-      case OP_SET_GLOBAL: {
-          ObjString* name = READ_STRING();
-          if (tableSet(&vm.globals, name, peek(0))) {
-            tableDelete(&vm.globals, name);
+      CASE(OP_SET_GLOBAL): {
+          uint16_t slot = READ_SHORT();
+          if (IS_UNDEFINED(vm.globalValues.values[slot])) {
             frame->ip = ip;
-            runtimeError("Undefined variable '%s'.", name->chars);
+            runtimeError("Undefined variable '%s'.",
+                         AS_STRING(vm.globalNames.values[slot])->chars);
             return INTERPRET_RUNTIME_ERROR;
           }
-          break;
+          vm.globalValues.values[slot] = peek(0);
+          DISPATCH();
         }
-      case OP_GET_UPVALUE: {
+      CASE(OP_GET_UPVALUE): {
           uint8_t slot = READ_BYTE();
           push(*frame->closure->upvalues[slot]->location);
-          break;
+          DISPATCH();
         }
-      case OP_SET_UPVALUE: {
+      CASE(OP_SET_UPVALUE): {
           uint8_t slot = READ_BYTE();
           *frame->closure->upvalues[slot]->location = peek(0);
-          break;
+          DISPATCH();
         }
-      case OP_GET_PROPERTY: {
+      CASE(OP_GET_PROPERTY): {
           if (!IS_INSTANCE(peek(0))) {
             frame->ip = ip;
             runtimeError("Only instances have properties.");
@@ -359,15 +488,15 @@ static InterpretResult run() {
           if (tableGet(&instance->fields, name, &value)) {
             pop(); // Instance.
             push(value);
-            break;
+            DISPATCH();
           }
 
           if (!bindMethod(instance->klass, name)) {
             return INTERPRET_RUNTIME_ERROR;
           }
-          break;
+          DISPATCH();
         }
-      case OP_SET_PROPERTY: {
+      CASE(OP_SET_PROPERTY): {
           if (!IS_INSTANCE(peek(1))) {
             frame->ip = ip;
             runtimeError("Only instances have fields.");
@@ -379,75 +508,65 @@ static InterpretResult run() {
           Value value = pop();
           pop();
           push(value);
-          break;
+          DISPATCH();
         }
-      case OP_GET_SUPER: {
+      CASE(OP_GET_SUPER): {
           ObjString* name = READ_STRING();
           ObjClass* superclass = AS_CLASS(pop());
 
           if (!bindMethod(superclass, name)) {
             return INTERPRET_COMPILE_ERROR;
           }
-          break;
+          DISPATCH();
         }
-      case OP_EQUAL: {
+      CASE(OP_EQUAL): {
           Value b = pop();
           Value a = pop();
           push(BOOL_VAL(valuesEqual(a, b)));
-          break;
-        }
-      case OP_GREATER:  BINARY_OP(BOOL_VAL, >); break;
-      case OP_LESS:     BINARY_OP(BOOL_VAL, <); break;
-      case OP_ADD: {
-          if (IS_STRING(peek(0)) && IS_STRING(peek(1))) {
-            concatenate();
-          } else if (IS_NUMBER(peek(0)) && IS_NUMBER(peek(1))) {
-            double b = AS_NUMBER(pop());
-            double a = AS_NUMBER(pop());
-            push(NUMBER_VAL(a + b));
-          } else {
-            frame->ip = ip;
-            runtimeError("Operands must be two numbers or two strings.");
-            return INTERPRET_RUNTIME_ERROR;
-          }
-          break;
+          DISPATCH();
         }
-      case OP_SUBTRACT: BINARY_OP(NUMBER_VAL, -); break;
-      case OP_MULTIPLY: BINARY_OP(NUMBER_VAL, *); break;
-      case OP_DIVIDE:   BINARY_OP(NUMBER_VAL, /); break;
-      case OP_NOT:
+      CASE(OP_GREATER):  BINARY_OP(BOOL_VAL, >); DISPATCH();
+      CASE(OP_LESS):     BINARY_OP(BOOL_VAL, <); DISPATCH();
+      CASE(OP_ADD):
+        frame->ip = ip;
+        if (!add()) return INTERPRET_RUNTIME_ERROR;
+        DISPATCH();
+      CASE(OP_SUBTRACT): BINARY_OP(NUMBER_VAL, -); DISPATCH();
+      CASE(OP_MULTIPLY): BINARY_OP(NUMBER_VAL, *); DISPATCH();
+      CASE(OP_DIVIDE):   BINARY_OP(NUMBER_VAL, /); DISPATCH();
+      CASE(OP_NOT):
         *(vm.stackTop - 1) = BOOL_VAL(isFalsey(*(vm.stackTop - 1)));
-        break;
-      case OP_NEGATE: {
+        DISPATCH();
+      CASE(OP_NEGATE): {
           if (!IS_NUMBER(peek(0))) {
             frame->ip = ip;
             runtimeError("Operand must be a number.");
             return INTERPRET_RUNTIME_ERROR;
           }
           *(vm.stackTop - 1) = NUMBER_VAL(-AS_NUMBER(*(vm.stackTop - 1)));
-          break;
+          DISPATCH();
         }
-      case OP_PRINT: {
+      CASE(OP_PRINT): {
           printValue(pop());
           printf("\n");
-          break;
+          DISPATCH();
         }
-      case OP_JUMP: {
+      CASE(OP_JUMP): {
           uint16_t offset = READ_SHORT();
           ip += offset;
-          break;
+          DISPATCH();
         }
-      case OP_JUMP_IF_FALSE: {
+      CASE(OP_JUMP_IF_FALSE): {
           uint16_t offset = READ_SHORT();
           if (isFalsey(peek(0))) ip += offset;
-          break;
+          DISPATCH();
         }
-      case OP_LOOP: {
+      CASE(OP_LOOP): {
           uint16_t offset = READ_SHORT();
           ip -= offset;
-          break;
+          DISPATCH();
         }
-      case OP_CALL: {
+      CASE(OP_CALL): {
           int argCount = READ_BYTE();
           frame->ip = ip;
           if (!callValue(peek(argCount), argCount)) {
@@ -455,28 +574,32 @@ static InterpretResult run() {
           }
           frame = &vm.frames[vm.frameCount - 1];
           ip = frame->ip;
-          break;
+          DISPATCH();
         }
-      case OP_INVOKE: {
+      CASE(OP_INVOKE): {
           ObjString* method = READ_STRING();
           int argCount = READ_BYTE();
+          frame->ip = ip;
           if (!invoke(method, argCount)) {
             return INTERPRET_RUNTIME_ERROR;
           }
           frame = &vm.frames[vm.frameCount - 1];
-          break;
+          ip = frame->ip;
+          DISPATCH();
         }
-      case OP_SUPER_INVOKE: {
+      CASE(OP_SUPER_INVOKE): {
           ObjString* method = READ_STRING();
           int argCount = READ_BYTE();
           ObjClass* superclass = AS_CLASS(pop());
+          frame->ip = ip;
           if (!invokeFromClass(superclass, method, argCount)) {
             return INTERPRET_RUNTIME_ERROR;
           }
           frame = &vm.frames[vm.frameCount - 1];
-          break;
+          ip = frame->ip;
+          DISPATCH();
         }
-      case OP_CLOSURE: {
+      CASE(OP_CLOSURE): {
           ObjFunction* function = AS_FUNCTION(READ_CONSTANT());
           ObjClosure* closure = newClosure(function);
           push(OBJ_VAL(closure));
@@ -489,13 +612,13 @@ static InterpretResult run() {
               closure->upvalues[i] = frame->closure->upvalues[index];
             }
           }
-          break;
+          DISPATCH();
         }
-      case OP_CLOSE_UPVALUE:
+      CASE(OP_CLOSE_UPVALUE):
         closeUpvalues(vm.stackTop - 1);
         pop();
-        break;
-      case OP_RETURN: {
+        DISPATCH();
+      CASE(OP_RETURN): {
           Value result = pop();
           closeUpvalues(frame->slots);
           vm.frameCount--;
@@ -508,12 +631,12 @@ static InterpretResult run() {
           push(result);
           frame = &vm.frames[vm.frameCount - 1];
           ip = frame->ip;
-          break;
+          DISPATCH();
         }
-      case OP_CLASS:
+      CASE(OP_CLASS):
         push(OBJ_VAL(newClass(READ_STRING())));
-        break;
-      case OP_INHERIT: {
+        DISPATCH();
+      CASE(OP_INHERIT): {
           Value superclass = peek(1);
           if (!IS_CLASS(superclass)) {
             frame->ip = ip;
@@ -525,18 +648,80 @@ static InterpretResult run() {
           tableAddAll(&AS_CLASS(superclass)->methods,
                       &subclass->methods);
           pop(); // Subclass.
-          break;
+          DISPATCH();
         }
-      case OP_METHOD:
+      CASE(OP_METHOD):
         defineMethod(READ_STRING());
-        break;
+        DISPATCH();
+      CASE(OP_WIDE):
+        wide = true;
+        DISPATCH();
+      CASE(OP_ADD_CONSTANT): {
+          Value b = READ_CONSTANT();
+          if (IS_NUMBER(b) && IS_NUMBER(peek(0))) {
+            vm.stackTop[-1] = NUMBER_VAL(AS_NUMBER(vm.stackTop[-1]) + AS_NUMBER(b));
+            DISPATCH();
+          }
+          push(b);
+          frame->ip = ip;
+          if (!add()) return INTERPRET_RUNTIME_ERROR;
+          DISPATCH();
+        }
+      CASE(OP_ADD_LOCALS): {
+          Value a = frame->slots[READ_BYTE()];
+          Value b = frame->slots[READ_BYTE()];
+          if (IS_NUMBER(a) && IS_NUMBER(b)) {
+            push(NUMBER_VAL(AS_NUMBER(a) + AS_NUMBER(b)));
+            DISPATCH();
+          }
+          push(a);
+          push(b);
+          frame->ip = ip;
+          if (!add()) return INTERPRET_RUNTIME_ERROR;
+          DISPATCH();
+        }
+      CASE(OP_INCREMENT):
+        if (IS_NUMBER(peek(0))) {
+          vm.stackTop[-1] = NUMBER_VAL(AS_NUMBER(vm.stackTop[-1]) + 1);
+          DISPATCH();
+        }
+        push(NUMBER_VAL(1));
+        frame->ip = ip;
+        if (!add()) return INTERPRET_RUNTIME_ERROR;
+        DISPATCH();
+      CASE(OP_EQUAL_JUMP): {
+          uint16_t offset = READ_SHORT();
+          Value b = pop();
+          Value a = pop();
+          bool equal = valuesEqual(a, b);
+          push(BOOL_VAL(equal));
+          if (!equal) ip += offset;
+          DISPATCH();
+        }
+      CASE(OP_GREATER_JUMP): {
+          uint16_t offset = READ_SHORT();
+          BINARY_OP(BOOL_VAL, >);
+          if (isFalsey(peek(0))) ip += offset;
+          DISPATCH();
+        }
+      CASE(OP_LESS_JUMP): {
+          uint16_t offset = READ_SHORT();
+          BINARY_OP(BOOL_VAL, <);
+          if (isFalsey(peek(0))) ip += offset;
+          DISPATCH();
+        }
+#ifndef THREADED_DISPATCH
     }
   }
+#endif
 
 #undef BINARY_OP
+#undef DISPATCH
+#undef CASE
 #undef READ_STRING
 #undef READ_SHORT
 #undef READ_CONSTANT
+#undef READ_INDEX
 #undef READ_BYTE
 }
 
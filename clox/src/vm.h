@@ -19,10 +19,17 @@ typedef struct {
   int frameCount;
   Value stack[STACK_MAX];
   Value* stackTop;
-  Table globals;
+  // Global variables by slot, handed out by the compiler the first time it
+  // sees a name. Slots persist, so code compiled before a global is defined,
+  // as in the REPL, finds it once it is.
+  Table globalSlots;
+  ValueArray globalNames;
+  ValueArray globalValues;
   Table strings;
   ObjString* initString;
   ObjUpvalue* openUpvalues;
+  bool hotspots;
+  bool superinstructions;
 
   size_t bytesAllocated;
   size_t nextGC;
@@ -46,6 +53,8 @@ void freeVM();
 
 InterpretResult interpret(const char* source);
 
+int globalSlot(ObjString* name);
+
 void push(Value value);
 
 Value pop();
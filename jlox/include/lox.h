@@ -0,0 +1,73 @@
+/*
+ * C interface to the jlox interpreter; see src/ffi.rs.
+ *
+ * Link against the `jlox` cdylib (libjlox.so, libjlox.dylib or jlox.dll).
+ * Handles returned by these functions are owned by the caller and freed
+ * with lox_free and lox_value_free. The arguments a native callback
+ * receives are borrowed for the duration of the call, and the handle it
+ * returns (NULL for nil) is taken over by the interpreter.
+ */
+
+#ifndef LOX_H
+#define LOX_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define LOX_OK 0
+#define LOX_COMPILE_ERROR 65
+#define LOX_RUNTIME_ERROR 70
+
+typedef struct LoxVm LoxVm;
+typedef struct LoxValue LoxValue;
+
+typedef enum LoxType {
+    LOX_TYPE_NIL = 0,
+    LOX_TYPE_BOOL = 1,
+    LOX_TYPE_NUMBER = 2,
+    LOX_TYPE_STRING = 3,
+    /* Functions, classes, instances, collections and host values. */
+    LOX_TYPE_OTHER = 4,
+} LoxType;
+
+typedef LoxValue *(*LoxNativeFn)(void *user_data, const LoxValue *const *args, size_t count);
+
+LoxVm *lox_new(void);
+void lox_free(LoxVm *vm);
+
+/* Returns LOX_OK, LOX_COMPILE_ERROR or LOX_RUNTIME_ERROR. */
+int lox_run(LoxVm *vm, const char *source);
+/* NULL after a successful run; valid until the next lox_run or lox_free. */
+const char *lox_get_error(const LoxVm *vm);
+
+int lox_register_fn(LoxVm *vm, const char *name, size_t arity, LoxNativeFn callback,
+                    void *user_data);
+/* NULL if `name` isn't defined. */
+LoxValue *lox_get_global(const LoxVm *vm, const char *name);
+int lox_set_global(LoxVm *vm, const char *name, const LoxValue *value);
+
+LoxValue *lox_value_nil(void);
+LoxValue *lox_value_bool(bool value);
+LoxValue *lox_value_number(double value);
+/* NULL if `text` isn't utf-8. */
+LoxValue *lox_value_string(const char *text);
+/* Returned from a native callback, fails the call with `message`. */
+LoxValue *lox_value_error(const char *message);
+void lox_value_free(LoxValue *value);
+
+LoxType lox_value_type(const LoxValue *value);
+bool lox_value_as_bool(const LoxValue *value);
+/* NaN if the value isn't a number. */
+double lox_value_as_number(const LoxValue *value);
+/* NULL if the value isn't a string; valid as long as the handle. */
+const char *lox_value_as_string(const LoxValue *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LOX_H */
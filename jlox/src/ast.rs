@@ -1,11 +1,24 @@
-use std::fmt::Display;
-use std::rc::Rc;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    mem::{self, ManuallyDrop},
+    ptr::read,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        LazyLock, Mutex,
+    },
+};
 
-use crate::{token::Token, types::Number};
+use crate::{
+    stack,
+    token::Token,
+    types::{Gc, Number},
+};
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Expr {
     Assign {
+        id: NodeId,
         name: Token,
         value: Box<Expr>,
     },
@@ -14,10 +27,13 @@ pub enum Expr {
         op: Token,
         right: Box<Expr>,
     },
+    /// `callee(arguments)`. A call written as a pipeline stage, `x |> f(a)`
+    /// or `x |> f`, is `piped`, with the piped value as the first argument.
     Call {
         callee: Box<Expr>,
         paren: Token,
         arguments: Vec<Expr>,
+        piped: bool,
     },
     Get {
         object: Box<Expr>,
@@ -26,73 +42,231 @@ pub enum Expr {
     Grouping {
         ex: Box<Expr>,
     },
+    /// `object[index]`, an element of a list or the value of a map's key.
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    /// `#[a, b]`, a frozen list.
+    List {
+        bracket: Token,
+        elements: Vec<Expr>,
+    },
     Literal(Literal),
     Logical {
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
     },
+    /// `#{key: value, ...}`, a frozen map. Later entries replace earlier
+    /// ones with an equal key.
+    Map {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+    /// `match subject { pattern if guard => body, ... }`, the value of the
+    /// first arm whose pattern matches and whose guard holds.
+    Match {
+        keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
     Set {
         object: Box<Expr>,
         name: Token,
         value: Box<Expr>,
     },
+    /// `object[index] = value`. Lists and maps are frozen, so this always
+    /// fails at runtime.
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    /// `super.method`, or `super(Ancestor).method` to start looking from
+    /// an ancestor further up than the superclass.
     Super {
+        id: NodeId,
         keyword: Token,
+        ancestor: Option<Token>,
         method: Token,
     },
     This {
+        id: NodeId,
         keyword: Token,
     },
+    /// `(a, b, c)`: a comma inside parentheses makes a tuple, not a group.
+    Tuple {
+        elements: Vec<Expr>,
+    },
     Unary {
         op: Token,
         right: Box<Expr>,
     },
     Variable {
+        id: NodeId,
         name: Token,
     },
 }
 
+#[derive(PartialEq, Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Pattern {
+    /// `_`, matching anything.
+    Wildcard,
+    /// A name, matching anything and binding it for the guard and body.
+    Binding(Token),
+    /// A number, string, `true`, `false` or `nil`, compared with `==`.
+    Literal(Literal),
+    /// `low..high`, numbers from `low` up to but not including `high`.
+    Range { low: Number, high: Number },
+    /// `(a, b)`, a tuple of the same size whose elements all match.
+    Tuple(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// The names the pattern binds, left to right.
+    pub fn bindings(&self) -> Vec<&Token> {
+        match self {
+            Self::Binding(name) => vec![name],
+            Self::Tuple(patterns) => patterns.iter().flat_map(Pattern::bindings).collect(),
+            Self::Wildcard | Self::Literal(_) | Self::Range { .. } => Vec::new(),
+        }
+    }
+
+    fn shift(&mut self, lines: usize) {
+        match self {
+            Self::Binding(name) => name.shift(lines),
+            Self::Tuple(patterns) => patterns.iter_mut().for_each(|p| p.shift(lines)),
+            Self::Wildcard | Self::Literal(_) | Self::Range { .. } => (),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Wildcard => write!(f, "_"),
+            Self::Binding(name) => write!(f, "{}", name.lexeme),
+            Self::Literal(literal) => write!(f, "{literal}"),
+            Self::Range { low, high } => write!(f, "(.. {low} {high})"),
+            Self::Tuple(patterns) => {
+                write!(f, "(tuple")?;
+                for pattern in patterns {
+                    write!(f, " {pattern}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum Literal {
     Number(Number),
     String(String),
+    // Boxed so a `Literal` is no bigger than a `String`.
+    Bytes(Box<[u8]>),
     True,
     False,
     Nil,
 }
 
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{n}"),
+            Self::String(s) => write!(f, "\"{s}\""),
+            Self::Bytes(bytes) => write!(f, "b\"{}\"", crate::bytes::escape(bytes)),
+            Self::True => write!(f, "true"),
+            Self::False => write!(f, "false"),
+            Self::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+/// A stable S-expression dump of the tree, e.g. `(+ 1 (group (* 2 x)))`,
+/// used by the parser's snapshot tests.
 impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            Self::Assign { name, value } => f.write_fmt(format_args!("{name} = {value}")),
-            Self::Binary { left, op, right } => {
-                f.write_fmt(format_args!("({} {} {})", left, op, right))
+            Self::Assign { name, value, .. } => write!(f, "(= {} {value})", name.lexeme),
+            Self::Binary { left, op, right } | Self::Logical { left, op, right } => {
+                write!(f, "({} {left} {right})", op.lexeme)
             }
             Self::Call {
-                callee,
-                paren: _,
-                arguments,
-            } => f.write_fmt(format_args!("{callee}({arguments:?})")),
-            Self::Get { object, name } => f.write_fmt(format_args!("{object}.{name}")),
-            Self::Grouping { ex } => f.write_fmt(format_args!("({})", ex)),
-            Self::Literal(Literal::Number(n)) => n.fmt(f),
-            Self::Literal(Literal::String(s)) => s.fmt(f),
-            Self::Literal(Literal::True) => true.fmt(f),
-            Self::Literal(Literal::False) => false.fmt(f),
-            Self::Literal(Literal::Nil) => f.write_str("nil"),
-            Self::Logical { left, op, right } => {
-                f.write_fmt(format_args!("({} {} {})", left, op, right))
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {callee}")?;
+                for argument in arguments {
+                    write!(f, " {argument}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Get { object, name } => write!(f, "(. {object} {})", name.lexeme),
+            Self::Grouping { ex } => write!(f, "(group {ex})"),
+            Self::Index { object, index, .. } => write!(f, "([] {object} {index})"),
+            Self::List { elements, .. } => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {element}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Literal(literal) => write!(f, "{literal}"),
+            Self::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (key, value) in entries {
+                    write!(f, " (: {key} {value})")?;
+                }
+                write!(f, ")")
+            }
+            Self::Match { subject, arms, .. } => {
+                write!(f, "(match {subject}")?;
+                for arm in arms {
+                    write!(f, " (=> {}", arm.pattern)?;
+                    if let Some(guard) = &arm.guard {
+                        write!(f, " (if {guard})")?;
+                    }
+                    write!(f, " {})", arm.body)?;
+                }
+                write!(f, ")")
             }
             Self::Set {
                 object,
                 name,
                 value,
-            } => f.write_fmt(format_args!("{}.{} = {}", object, name, value)),
-            Self::Super { keyword, method } => f.write_fmt(format_args!("{keyword}.{method}")),
-            Self::This { keyword: _ } => f.write_str("this"),
-            Self::Unary { op, right } => f.write_fmt(format_args!("({}{})", op, right)),
-            Self::Variable { name } => f.write_fmt(format_args!("{}", name)),
+            } => write!(f, "(.= {object} {} {value})", name.lexeme),
+            Self::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => write!(f, "([]= {object} {index} {value})"),
+            Self::Super {
+                ancestor: Some(ancestor),
+                method,
+                ..
+            } => write!(f, "(super {} {})", ancestor.lexeme, method.lexeme),
+            Self::Super { method, .. } => write!(f, "(super {})", method.lexeme),
+            Self::This { .. } => write!(f, "this"),
+            Self::Tuple { elements } => {
+                write!(f, "(tuple")?;
+                for element in elements {
+                    write!(f, " {element}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Unary { op, right } => write!(f, "({} {right})", op.lexeme),
+            Self::Variable { name, .. } => write!(f, "{}", name.lexeme),
         }
     }
 }
@@ -100,136 +274,478 @@ impl Display for Expr {
 pub trait ExprVisitor<T> {
     type E;
 
-    fn evaluate(&mut self, expr: Expr) -> Result<Rc<T>, Self::E> {
-        match expr {
-            Expr::Assign { name, value } => self.visit_assign_expr(name, value),
-            Expr::Binary { left, op, right } => self.visit_binary_expr(left, op, right),
-            Expr::Call {
-                callee,
-                paren,
-                arguments,
-            } => self.visit_call_expr(callee, paren, arguments),
-            Expr::Get { object, name } => self.visit_get_expr(object, name),
-            Expr::Grouping { ex } => self.visit_grouping_expr(ex),
-            Expr::Literal(literal) => self.visit_literal_expr(literal),
-            Expr::Logical { left, op, right } => self.visit_logical_expr(left, op, right),
-            Expr::Set {
-                object,
-                name,
-                value,
-            } => self.visit_set_expr(object, name, value),
-            Expr::Super { keyword, method } => self.visit_super_expr(keyword, method),
-            Expr::This { keyword } => self.visit_this_expr(keyword),
-            Expr::Unary { op, right } => self.visit_unary_expr(op, right),
-            Expr::Variable { name } => self.visit_variable_expr(name),
+    /// Runs before each expression is visited, so visitors that recurse on
+    /// the native stack can refuse to go deeper. Does nothing by default.
+    fn enter_expr(&mut self) -> Result<(), Self::E> {
+        Ok(())
+    }
+
+    fn evaluate(&mut self, expr: Expr) -> Result<Gc<T>, Self::E> {
+        self.enter_expr()?;
+        // `Expr` drops its own children, so patterns can't move its fields
+        // out. Each arm reads the fields out instead, and the emptied
+        // `expr` is never dropped.
+        let expr = ManuallyDrop::new(expr);
+        // SAFETY: every field that owns memory is read exactly once, and
+        // `expr` isn't used again after the match.
+        unsafe {
+            match &*expr {
+                Expr::Assign { id, name, value } => {
+                    self.visit_assign_expr(*id, read(name), read(value))
+                }
+                Expr::Binary { left, op, right } => {
+                    self.visit_binary_expr(read(left), read(op), read(right))
+                }
+                Expr::Call {
+                    callee,
+                    paren,
+                    arguments,
+                    ..
+                } => self.visit_call_expr(read(callee), read(paren), read(arguments)),
+                Expr::Get { object, name } => self.visit_get_expr(read(object), read(name)),
+                Expr::Grouping { ex } => self.visit_grouping_expr(read(ex)),
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => self.visit_index_expr(read(object), read(bracket), read(index)),
+                Expr::List { bracket, elements } => {
+                    self.visit_list_expr(read(bracket), read(elements))
+                }
+                Expr::Literal(literal) => self.visit_literal_expr(read(literal)),
+                Expr::Logical { left, op, right } => {
+                    self.visit_logical_expr(read(left), read(op), read(right))
+                }
+                Expr::Map { brace, entries } => self.visit_map_expr(read(brace), read(entries)),
+                Expr::Match {
+                    keyword,
+                    subject,
+                    arms,
+                } => self.visit_match_expr(read(keyword), read(subject), read(arms)),
+                Expr::Set {
+                    object,
+                    name,
+                    value,
+                } => self.visit_set_expr(read(object), read(name), read(value)),
+                Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    value,
+                } => {
+                    self.visit_set_index_expr(read(object), read(bracket), read(index), read(value))
+                }
+                Expr::Super {
+                    id,
+                    keyword,
+                    ancestor,
+                    method,
+                } => self.visit_super_expr(*id, read(keyword), read(ancestor), read(method)),
+                Expr::This { id, keyword } => self.visit_this_expr(*id, read(keyword)),
+                Expr::Tuple { elements } => self.visit_tuple_expr(read(elements)),
+                Expr::Unary { op, right } => self.visit_unary_expr(read(op), read(right)),
+                Expr::Variable { id, name } => self.visit_variable_expr(*id, read(name)),
+            }
         }
     }
 
-    fn visit_assign_expr(&mut self, name: Token, value: Box<Expr>) -> Result<Rc<T>, Self::E>;
+    fn visit_assign_expr(
+        &mut self,
+        id: NodeId,
+        name: Token,
+        value: Box<Expr>,
+    ) -> Result<Gc<T>, Self::E>;
     fn visit_binary_expr(
         &mut self,
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
-    ) -> Result<Rc<T>, Self::E>;
+    ) -> Result<Gc<T>, Self::E>;
     fn visit_call_expr(
         &mut self,
         callee: Box<Expr>,
         paren: Token,
         arguments: Vec<Expr>,
-    ) -> Result<Rc<T>, Self::E>;
-    fn visit_get_expr(&mut self, object: Box<Expr>, name: Token) -> Result<Rc<T>, Self::E>;
-    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Rc<T>, Self::E>;
-    fn visit_literal_expr(&mut self, literal: Literal) -> Result<Rc<T>, Self::E>;
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_get_expr(&mut self, object: Box<Expr>, name: Token) -> Result<Gc<T>, Self::E>;
+    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Gc<T>, Self::E>;
+    fn visit_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_list_expr(&mut self, bracket: Token, elements: Vec<Expr>) -> Result<Gc<T>, Self::E>;
+    fn visit_literal_expr(&mut self, literal: Literal) -> Result<Gc<T>, Self::E>;
     fn visit_logical_expr(
         &mut self,
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
-    ) -> Result<Rc<T>, Self::E>;
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_map_expr(
+        &mut self,
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_match_expr(
+        &mut self,
+        keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    ) -> Result<Gc<T>, Self::E>;
     fn visit_set_expr(
         &mut self,
         object: Box<Expr>,
         name: Token,
         value: Box<Expr>,
-    ) -> Result<Rc<T>, Self::E>;
-    fn visit_super_expr(&mut self, keyword: Token, method: Token) -> Result<Rc<T>, Self::E>;
-    fn visit_this_expr(&mut self, keyword: Token) -> Result<Rc<T>, Self::E>;
-    fn visit_unary_expr(&mut self, op: Token, right: Box<Expr>) -> Result<Rc<T>, Self::E>;
-    fn visit_variable_expr(&mut self, name: Token) -> Result<Rc<T>, Self::E>;
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_set_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_super_expr(
+        &mut self,
+        id: NodeId,
+        keyword: Token,
+        ancestor: Option<Token>,
+        method: Token,
+    ) -> Result<Gc<T>, Self::E>;
+    fn visit_this_expr(&mut self, id: NodeId, keyword: Token) -> Result<Gc<T>, Self::E>;
+    fn visit_tuple_expr(&mut self, elements: Vec<Expr>) -> Result<Gc<T>, Self::E>;
+    fn visit_unary_expr(&mut self, op: Token, right: Box<Expr>) -> Result<Gc<T>, Self::E>;
+    fn visit_variable_expr(&mut self, id: NodeId, name: Token) -> Result<Gc<T>, Self::E>;
+}
+
+/// Identifies a statement, so tools such as the debugger and profilers can
+/// keep data about it. Unique within the process; copies of a tree share them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StmtId(u32);
+
+static NEXT_STMT_ID: AtomicU32 = AtomicU32::new(1);
+
+impl StmtId {
+    pub(crate) fn fresh() -> Self {
+        Self(NEXT_STMT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Identifies an expression that refers to a variable: a variable, an
+/// assignment, `this` or `super`. The parser hands them out, and the stages
+/// after it key their side tables by them, as the interpreter does the
+/// scope depths the resolver finds. Unique within the process, like
+/// [`StmtId`].
+#[derive(Debug, Clone, Copy)]
+pub struct NodeId(u32);
+
+static NEXT_NODE_ID: AtomicU32 = AtomicU32::new(1);
+
+impl NodeId {
+    /// For expressions the host makes up rather than parses, which are
+    /// never resolved.
+    pub const NONE: Self = Self(0);
+
+    pub(crate) fn fresh() -> Self {
+        Self(NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Ids don't take part, as with [`Boundary`]: trees parsed from the same
+/// source are equal. Tell nodes apart with a [`NodeMap`].
+impl PartialEq for NodeId {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// A side table with an entry per node.
+#[derive(Debug, Clone)]
+pub struct NodeMap<V>(HashMap<u32, V>);
+
+impl<V> NodeMap<V> {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub fn insert(&mut self, id: NodeId, value: V) -> Option<V> {
+        self.0.insert(id.0, value)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&V> {
+        self.0.get(&id.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl<V> Default for NodeMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An interned name: equal names get the same id, so tables keyed by name
+/// hash and compare a number. Names are kept for the life of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+
+#[derive(Default)]
+struct Symbols {
+    ids: HashMap<&'static str, SymbolId>,
+    names: Vec<&'static str>,
+}
+
+static SYMBOLS: LazyLock<Mutex<Symbols>> = LazyLock::new(Mutex::default);
+
+impl SymbolId {
+    pub fn intern(name: &str) -> Self {
+        let mut symbols = SYMBOLS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&id) = symbols.ids.get(name) {
+            return id;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let id = Self(symbols.names.len() as u32);
+        symbols.names.push(name);
+        symbols.ids.insert(name, id);
+        id
+    }
+
+    pub fn name(self) -> &'static str {
+        SYMBOLS.lock().unwrap_or_else(|e| e.into_inner()).names[self.0 as usize]
+    }
+}
+
+impl Display for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A statement's entry in the statement-boundary table: the lines it spans.
+/// Execution pauses at boundaries, one per statement, and loops add one for
+/// their header on every iteration.
+#[derive(Debug, Clone, Copy)]
+pub struct Boundary {
+    pub id: StmtId,
+    pub line: usize,
+    pub end_line: usize,
+    /// The byte offset where the statement's last token ends.
+    pub end: usize,
+}
+
+impl Boundary {
+    /// For statements the host makes up rather than parses, which tools skip.
+    pub const NONE: Self = Self {
+        id: StmtId(0),
+        line: 0,
+        end_line: 0,
+        end: 0,
+    };
+
+    pub fn is_none(&self) -> bool {
+        self.id == Self::NONE.id
+    }
+}
+
+/// Ids and offsets don't take part: trees parsed from the same source are
+/// equal.
+impl PartialEq for Boundary {
+    fn eq(&self, other: &Self) -> bool {
+        (self.line, self.end_line) == (other.line, other.end_line)
+    }
+}
+
+/// The annotations of a function, as in `fun add(a: Number, b) -> Number`:
+/// a type name or `None` per parameter, and the return type's. What the
+/// names mean is up to [`typecheck`](crate::typecheck).
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Signature {
+    pub params: Vec<Option<Token>>,
+    pub returns: Option<Token>,
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Stmt {
     Block {
         statements: Vec<Stmt>,
+        boundary: Boundary,
+    },
+    Break {
+        keyword: Token,
+        boundary: Boundary,
     },
     Class {
         name: Token,
         superclass: Option<Expr>,
         methods: Vec<Stmt>,
+        doc: Option<String>,
+        boundary: Boundary,
+    },
+    Continue {
+        keyword: Token,
+        boundary: Boundary,
+    },
+    /// `var (a, b) = tuple;`, declaring one variable per element of a tuple
+    /// or list.
+    Destructure {
+        paren: Token,
+        names: Vec<Token>,
+        initializer: Expr,
+        boundary: Boundary,
     },
     Expression {
         expr: Expr,
+        boundary: Boundary,
+    },
+    /// A `for` loop. Each iteration gets a fresh copy of the variables the
+    /// initializer declares, so closures made in the body keep the values
+    /// of their own iteration.
+    For {
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+        boundary: Boundary,
     },
     Function {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
+        doc: Option<String>,
+        /// Its type annotations, if it has any.
+        signature: Option<Box<Signature>>,
+        boundary: Boundary,
     },
     If {
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
+        boundary: Boundary,
+    },
+    Import {
+        keyword: Token,
+        path: String,
+        boundary: Boundary,
     },
     Print {
         expr: Expr,
+        boundary: Boundary,
     },
     Return {
         keyword: Token,
         value: Option<Expr>,
+        boundary: Boundary,
     },
     Var {
         name: Token,
+        /// `var n: Number`, the name of its type.
+        annotation: Option<Token>,
         initializer: Option<Expr>,
+        boundary: Boundary,
     },
     While {
         condition: Expr,
         body: Box<Stmt>,
+        boundary: Boundary,
     },
 }
 
+/// How executing a statement finished. Visitors that run statements use
+/// `StmtVisitor<Flow<V>>`, with `V` the values returned from functions, so
+/// `return`, `break` and `continue` unwind through the enclosing statements
+/// as ordinary results rather than as errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow<V> {
+    Normal,
+    Return(V),
+    Break,
+    Continue,
+}
+
 pub trait StmtVisitor<T> {
     type E;
 
+    /// Like [`ExprVisitor::enter_expr`], before each statement.
+    fn enter_stmt(&mut self) -> Result<(), Self::E> {
+        Ok(())
+    }
+
     fn execute(&mut self, stmt: Stmt) -> Result<T, Self::E> {
+        self.enter_stmt()?;
         match stmt {
-            Stmt::Block { statements } => self.visit_block_stmt(statements),
+            Stmt::Block { statements, .. } => self.visit_block_stmt(statements),
+            Stmt::Break { keyword, .. } => self.visit_break_stmt(keyword),
             Stmt::Class {
                 name,
                 superclass,
                 methods,
-            } => self.visit_class_stmt(name, superclass, methods),
-            Stmt::Expression { expr } => self.visit_expression_stmt(expr),
-            Stmt::Function { name, params, body } => self.visit_function_stmt(name, params, body),
+                doc,
+                ..
+            } => self.visit_class_stmt(name, superclass, methods, doc),
+            Stmt::Continue { keyword, .. } => self.visit_continue_stmt(keyword),
+            Stmt::Destructure {
+                paren,
+                names,
+                initializer,
+                ..
+            } => self.visit_destructure_stmt(paren, names, initializer),
+            Stmt::Expression { expr, .. } => self.visit_expression_stmt(expr),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => self.visit_for_stmt(initializer, condition, increment, body),
+            Stmt::Function {
+                name,
+                params,
+                body,
+                doc,
+                signature,
+                ..
+            } => self.visit_function_stmt(name, params, body, doc, signature),
             Stmt::If {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => self.visit_if_stmt(condition, then_branch, else_branch),
-            Stmt::Print { expr } => self.visit_print_stmt(expr),
-            Stmt::Return { keyword, value } => self.visit_return_stmt(keyword, value),
-            Stmt::Var { name, initializer } => self.visit_var_stmt(name, initializer),
-            Stmt::While { condition, body } => self.visit_while_stmt(condition, body),
+            Stmt::Import { keyword, path, .. } => self.visit_import_stmt(keyword, path),
+            Stmt::Print { expr, .. } => self.visit_print_stmt(expr),
+            Stmt::Return { keyword, value, .. } => self.visit_return_stmt(keyword, value),
+            Stmt::Var {
+                name,
+                annotation,
+                initializer,
+                ..
+            } => self.visit_var_stmt(name, annotation, initializer),
+            Stmt::While {
+                condition, body, ..
+            } => self.visit_while_stmt(condition, body),
         }
     }
 
     fn visit_block_stmt(&mut self, statements: Vec<Stmt>) -> Result<T, Self::E>;
+    fn visit_break_stmt(&mut self, keyword: Token) -> Result<T, Self::E>;
     fn visit_class_stmt(
         &mut self,
         name: Token,
         superclass: Option<Expr>,
         methods: Vec<Stmt>,
+        doc: Option<String>,
+    ) -> Result<T, Self::E>;
+    fn visit_continue_stmt(&mut self, keyword: Token) -> Result<T, Self::E>;
+    fn visit_destructure_stmt(
+        &mut self,
+        paren: Token,
+        names: Vec<Token>,
+        initializer: Expr,
     ) -> Result<T, Self::E>;
     fn visit_expression_stmt(&mut self, expr: Expr) -> Result<T, Self::E>;
     fn visit_function_stmt(
@@ -237,6 +753,15 @@ pub trait StmtVisitor<T> {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
+        doc: Option<String>,
+        signature: Option<Box<Signature>>,
+    ) -> Result<T, Self::E>;
+    fn visit_for_stmt(
+        &mut self,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
     ) -> Result<T, Self::E>;
     fn visit_if_stmt(
         &mut self,
@@ -244,8 +769,552 @@ pub trait StmtVisitor<T> {
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     ) -> Result<T, Self::E>;
+    fn visit_import_stmt(&mut self, keyword: Token, path: String) -> Result<T, Self::E>;
     fn visit_print_stmt(&mut self, expr: Expr) -> Result<T, Self::E>;
     fn visit_return_stmt(&mut self, keyword: Token, value: Option<Expr>) -> Result<T, Self::E>;
-    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> Result<T, Self::E>;
+    fn visit_var_stmt(
+        &mut self,
+        name: Token,
+        annotation: Option<Token>,
+        initializer: Option<Expr>,
+    ) -> Result<T, Self::E>;
     fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Result<T, Self::E>;
 }
+
+/// The statement counterpart of the [`Expr`] dump. Nested statements go on
+/// their own lines, indented two spaces per level.
+impl Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.dump(f, 0)
+    }
+}
+
+impl Stmt {
+    fn dump(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let child = |f: &mut std::fmt::Formatter<'_>, stmt: &Stmt| {
+            write!(f, "\n{}", "  ".repeat(depth + 1))?;
+            stmt.dump(f, depth + 1)
+        };
+        let doc = |f: &mut std::fmt::Formatter<'_>, doc: &Option<String>| match doc {
+            Some(doc) => write!(f, "\n{}(doc {doc:?})", "  ".repeat(depth + 1)),
+            None => Ok(()),
+        };
+
+        match self {
+            Self::Block { statements, .. } => {
+                write!(f, "(block")?;
+                statements.iter().try_for_each(|stmt| child(f, stmt))?;
+            }
+            Self::Break { .. } => write!(f, "(break")?,
+            Self::Class {
+                name,
+                superclass,
+                methods,
+                doc: comment,
+                ..
+            } => {
+                write!(f, "(class {}", name.lexeme)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " {superclass}")?;
+                }
+                doc(f, comment)?;
+                methods.iter().try_for_each(|method| child(f, method))?;
+            }
+            Self::Continue { .. } => write!(f, "(continue")?,
+            Self::Destructure {
+                names, initializer, ..
+            } => {
+                let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+                write!(f, "(var ({}) {initializer}", names.join(" "))?;
+            }
+            Self::Expression { expr, .. } => write!(f, "(; {expr}")?,
+            Self::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                write!(f, "(for")?;
+                match initializer {
+                    Some(initializer) => child(f, initializer)?,
+                    None => write!(f, "\n{}_", "  ".repeat(depth + 1))?,
+                }
+                for expr in [condition, increment] {
+                    match expr {
+                        Some(expr) => write!(f, "\n{}{expr}", "  ".repeat(depth + 1))?,
+                        None => write!(f, "\n{}_", "  ".repeat(depth + 1))?,
+                    }
+                }
+                child(f, body)?;
+            }
+            Self::Function {
+                name,
+                params,
+                body,
+                doc: comment,
+                ..
+            } => {
+                let params: Vec<&str> = params.iter().map(|p| p.lexeme.as_str()).collect();
+                write!(f, "(fun {} ({})", name.lexeme, params.join(" "))?;
+                doc(f, comment)?;
+                body.iter().try_for_each(|stmt| child(f, stmt))?;
+            }
+            Self::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                write!(f, "(if {condition}")?;
+                child(f, then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    child(f, else_branch)?;
+                }
+            }
+            Self::Import { path, .. } => write!(f, "(import \"{path}\"")?,
+            Self::Print { expr, .. } => write!(f, "(print {expr}")?,
+            Self::Return { value, .. } => match value {
+                Some(value) => write!(f, "(return {value}")?,
+                None => write!(f, "(return")?,
+            },
+            Self::Var {
+                name, initializer, ..
+            } => match initializer {
+                Some(initializer) => write!(f, "(var {} {initializer}", name.lexeme)?,
+                None => write!(f, "(var {}", name.lexeme)?,
+            },
+            Self::While {
+                condition, body, ..
+            } => {
+                write!(f, "(while {condition}")?;
+                child(f, body)?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// Dropping a tree recurses into its children, which a chain like
+/// `1 + 1 + ... + 1` makes as deep as it is long. Once the stack runs low
+/// the rest of the tree is taken apart on the heap instead.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        if !stack::exhausted() {
+            return;
+        }
+        let mut pending: Vec<Expr> = self.children_mut().into_iter().map(mem::take).collect();
+        while let Some(mut expr) = pending.pop() {
+            pending.extend(expr.children_mut().into_iter().map(mem::take));
+        }
+    }
+}
+
+/// `nil`, what [`mem::take`] leaves behind. `Expr` drops its own children,
+/// so that is how to move a field out of one.
+impl Default for Expr {
+    fn default() -> Self {
+        Self::Literal(Literal::Nil)
+    }
+}
+
+impl Expr {
+    /// The expressions directly nested in this one, in source order, match
+    /// guards included.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Self::Assign { value: ex, .. }
+            | Self::Get { object: ex, .. }
+            | Self::Grouping { ex }
+            | Self::Unary { right: ex, .. } => vec![ex],
+            Self::Binary { left, right, .. }
+            | Self::Logical { left, right, .. }
+            | Self::Index {
+                object: left,
+                index: right,
+                ..
+            }
+            | Self::Set {
+                object: left,
+                value: right,
+                ..
+            } => vec![left, right],
+            Self::Call {
+                callee, arguments, ..
+            } => [&**callee].into_iter().chain(arguments).collect(),
+            Self::List { elements, .. } | Self::Tuple { elements } => elements.iter().collect(),
+            Self::Map { entries, .. } => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+            Self::Match { subject, arms, .. } => [&**subject]
+                .into_iter()
+                .chain(
+                    arms.iter()
+                        .flat_map(|arm| arm.guard.iter().chain([&arm.body])),
+                )
+                .collect(),
+            Self::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => vec![object, index, value],
+            Self::Literal(_) | Self::Super { .. } | Self::This { .. } | Self::Variable { .. } => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Like [`Expr::children`], for taking them apart.
+    fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Self::Assign { value: ex, .. }
+            | Self::Get { object: ex, .. }
+            | Self::Grouping { ex }
+            | Self::Unary { right: ex, .. } => vec![ex],
+            Self::Binary { left, right, .. }
+            | Self::Logical { left, right, .. }
+            | Self::Index {
+                object: left,
+                index: right,
+                ..
+            }
+            | Self::Set {
+                object: left,
+                value: right,
+                ..
+            } => vec![left, right],
+            Self::Call {
+                callee, arguments, ..
+            } => [&mut **callee].into_iter().chain(arguments).collect(),
+            Self::List { elements, .. } | Self::Tuple { elements } => elements.iter_mut().collect(),
+            Self::Map { entries, .. } => entries.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+            Self::Match { subject, arms, .. } => [&mut **subject]
+                .into_iter()
+                .chain(
+                    arms.iter_mut()
+                        .flat_map(|arm| arm.guard.iter_mut().chain([&mut arm.body])),
+                )
+                .collect(),
+            Self::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => vec![object, index, value],
+            Self::Literal(_) | Self::Super { .. } | Self::This { .. } | Self::Variable { .. } => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Moves every token in the expression `lines` lines down, for trees
+    /// parsed from a fragment of a larger source.
+    pub(crate) fn shift(&mut self, lines: usize) {
+        match self {
+            Self::Assign { name, value, .. } => {
+                name.shift(lines);
+                value.shift(lines);
+            }
+            Self::Binary { left, op, right } | Self::Logical { left, op, right } => {
+                left.shift(lines);
+                op.shift(lines);
+                right.shift(lines);
+            }
+            Self::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => {
+                callee.shift(lines);
+                paren.shift(lines);
+                arguments.iter_mut().for_each(|arg| arg.shift(lines));
+            }
+            Self::Get { object, name } => {
+                object.shift(lines);
+                name.shift(lines);
+            }
+            Self::Grouping { ex } => ex.shift(lines),
+            Self::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                object.shift(lines);
+                bracket.shift(lines);
+                index.shift(lines);
+            }
+            Self::List { bracket, elements } => {
+                bracket.shift(lines);
+                elements.iter_mut().for_each(|e| e.shift(lines));
+            }
+            Self::Literal(_) => (),
+            Self::Map { brace, entries } => {
+                brace.shift(lines);
+                for (key, value) in entries {
+                    key.shift(lines);
+                    value.shift(lines);
+                }
+            }
+            Self::Match {
+                keyword,
+                subject,
+                arms,
+            } => {
+                keyword.shift(lines);
+                subject.shift(lines);
+                for arm in arms {
+                    arm.pattern.shift(lines);
+                    if let Some(guard) = &mut arm.guard {
+                        guard.shift(lines);
+                    }
+                    arm.body.shift(lines);
+                }
+            }
+            Self::Set {
+                object,
+                name,
+                value,
+            } => {
+                object.shift(lines);
+                name.shift(lines);
+                value.shift(lines);
+            }
+            Self::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                object.shift(lines);
+                bracket.shift(lines);
+                index.shift(lines);
+                value.shift(lines);
+            }
+            Self::Super {
+                keyword,
+                ancestor,
+                method,
+                ..
+            } => {
+                keyword.shift(lines);
+                if let Some(ancestor) = ancestor {
+                    ancestor.shift(lines);
+                }
+                method.shift(lines);
+            }
+            Self::This { keyword, .. } => keyword.shift(lines),
+            Self::Tuple { elements } => elements.iter_mut().for_each(|e| e.shift(lines)),
+            Self::Unary { op, right } => {
+                op.shift(lines);
+                right.shift(lines);
+            }
+            Self::Variable { name, .. } => name.shift(lines),
+        }
+    }
+}
+
+impl Stmt {
+    /// The statement's entry in the statement-boundary table.
+    pub fn boundary(&self) -> Boundary {
+        match self {
+            Self::Block { boundary, .. }
+            | Self::Break { boundary, .. }
+            | Self::Class { boundary, .. }
+            | Self::Continue { boundary, .. }
+            | Self::Destructure { boundary, .. }
+            | Self::Expression { boundary, .. }
+            | Self::For { boundary, .. }
+            | Self::Function { boundary, .. }
+            | Self::If { boundary, .. }
+            | Self::Import { boundary, .. }
+            | Self::Print { boundary, .. }
+            | Self::Return { boundary, .. }
+            | Self::Var { boundary, .. }
+            | Self::While { boundary, .. } => *boundary,
+        }
+    }
+
+    pub(crate) fn set_boundary(&mut self, to: Boundary) {
+        match self {
+            Self::Block { boundary, .. }
+            | Self::Break { boundary, .. }
+            | Self::Class { boundary, .. }
+            | Self::Continue { boundary, .. }
+            | Self::Destructure { boundary, .. }
+            | Self::Expression { boundary, .. }
+            | Self::For { boundary, .. }
+            | Self::Function { boundary, .. }
+            | Self::If { boundary, .. }
+            | Self::Import { boundary, .. }
+            | Self::Print { boundary, .. }
+            | Self::Return { boundary, .. }
+            | Self::Var { boundary, .. }
+            | Self::While { boundary, .. } => *boundary = to,
+        }
+    }
+
+    /// The statements directly nested in this one, in source order.
+    pub fn children(&self) -> Vec<&Stmt> {
+        match self {
+            Self::Block { statements, .. }
+            | Self::Function {
+                body: statements, ..
+            } => statements.iter().collect(),
+            Self::Class { methods, .. } => methods.iter().collect(),
+            Self::For {
+                initializer, body, ..
+            } => initializer
+                .iter()
+                .map(Box::as_ref)
+                .chain([&**body])
+                .collect(),
+            Self::If {
+                then_branch,
+                else_branch,
+                ..
+            } => [&**then_branch]
+                .into_iter()
+                .chain(else_branch.as_deref())
+                .collect(),
+            Self::While { body, .. } => vec![body],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The expressions directly in this statement, not in the statements
+    /// nested in it, in source order.
+    pub fn expressions(&self) -> Vec<&Expr> {
+        match self {
+            Self::Class { superclass, .. } => superclass.iter().collect(),
+            Self::Destructure { initializer, .. } => vec![initializer],
+            Self::Expression { expr, .. } | Self::Print { expr, .. } => vec![expr],
+            Self::For {
+                condition,
+                increment,
+                ..
+            } => condition.iter().chain(increment).collect(),
+            Self::If { condition, .. } | Self::While { condition, .. } => vec![condition],
+            Self::Return { value, .. } => value.iter().collect(),
+            Self::Var { initializer, .. } => initializer.iter().collect(),
+            Self::Block { .. }
+            | Self::Break { .. }
+            | Self::Continue { .. }
+            | Self::Function { .. }
+            | Self::Import { .. } => Vec::new(),
+        }
+    }
+
+    /// Moves every token in the statement `lines` lines down.
+    pub(crate) fn shift(&mut self, lines: usize) {
+        let boundary = self.boundary();
+        if !boundary.is_none() {
+            self.set_boundary(Boundary {
+                line: boundary.line + lines,
+                end_line: boundary.end_line + lines,
+                ..boundary
+            });
+        }
+
+        match self {
+            Self::Block { statements, .. } => statements.iter_mut().for_each(|s| s.shift(lines)),
+            Self::Break { keyword, .. } | Self::Continue { keyword, .. } => keyword.shift(lines),
+            Self::Destructure {
+                paren,
+                names,
+                initializer,
+                ..
+            } => {
+                paren.shift(lines);
+                names.iter_mut().for_each(|name| name.shift(lines));
+                initializer.shift(lines);
+            }
+            Self::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                name.shift(lines);
+                if let Some(superclass) = superclass {
+                    superclass.shift(lines);
+                }
+                methods.iter_mut().for_each(|m| m.shift(lines));
+            }
+            Self::Expression { expr, .. } | Self::Print { expr, .. } => expr.shift(lines),
+            Self::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                if let Some(initializer) = initializer {
+                    initializer.shift(lines);
+                }
+                for expr in [condition, increment].into_iter().flatten() {
+                    expr.shift(lines);
+                }
+                body.shift(lines);
+            }
+            Self::Function {
+                name, params, body, ..
+            } => {
+                name.shift(lines);
+                params.iter_mut().for_each(|p| p.shift(lines));
+                body.iter_mut().for_each(|s| s.shift(lines));
+            }
+            Self::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                condition.shift(lines);
+                then_branch.shift(lines);
+                if let Some(else_branch) = else_branch {
+                    else_branch.shift(lines);
+                }
+            }
+            Self::Import { keyword, .. } => keyword.shift(lines),
+            Self::Return { keyword, value, .. } => {
+                keyword.shift(lines);
+                if let Some(value) = value {
+                    value.shift(lines);
+                }
+            }
+            Self::Var {
+                name, initializer, ..
+            } => {
+                name.shift(lines);
+                if let Some(initializer) = initializer {
+                    initializer.shift(lines);
+                }
+            }
+            Self::While {
+                condition, body, ..
+            } => {
+                condition.shift(lines);
+                body.shift(lines);
+            }
+        }
+    }
+}
+
+/// The statement-boundary table of `statements`: the boundary of every
+/// statement in them, nested ones included, in source order.
+pub fn boundary_table(statements: &[Stmt]) -> Vec<Boundary> {
+    fn walk(stmt: &Stmt, table: &mut Vec<Boundary>) {
+        if !stmt.boundary().is_none() {
+            table.push(stmt.boundary());
+        }
+        for child in stmt.children() {
+            walk(child, table);
+        }
+    }
+
+    let mut table = Vec::new();
+    for stmt in statements {
+        walk(stmt, &mut table);
+    }
+    table
+}
@@ -0,0 +1,281 @@
+//! Binary data: byte strings written `b"GIF\x89"`, read from files with
+//! `readBytes` and converted to and from text with `encode` and `decode`.
+//!
+//! Bytes index like lists, `data[0]` being a number from 0 to 255, and are
+//! frozen too. Unlike strings they can hold anything a file does.
+
+use std::fmt::Write;
+
+use crate::{
+    functions::Callable,
+    host::IntoLox,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    tasks::Message,
+    types::{Gc, Number},
+};
+
+/// The source form of `bytes` without the `b"..."` around it: printable
+/// ASCII as is, everything else (and `"` and `\`) escaped.
+pub fn escape(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            0x20..=0x7e => text.push(byte as char),
+            _ => {
+                let _ = write!(text, "\\x{byte:02x}");
+            }
+        }
+    }
+    text
+}
+
+/// The bytes a literal's source stands for, or `None` for an escape other
+/// than `\xNN`, `\"` and `\\`.
+pub fn unescape(source: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(source.len());
+    let mut rest = source.iter();
+    while let Some(&byte) = rest.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        match rest.next()? {
+            b'x' => {
+                let digits = [*rest.next()?, *rest.next()?];
+                let digits = std::str::from_utf8(&digits).ok()?;
+                bytes.push(u8::from_str_radix(digits, 16).ok()?);
+            }
+            &escaped @ (b'"' | b'\\') => bytes.push(escaped),
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+/// A text encoding `encode` and `decode` understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Latin1,
+    Ascii,
+}
+
+impl Encoding {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Some(Self::Latin1),
+            "ascii" | "us-ascii" => Some(Self::Ascii),
+            _ => None,
+        }
+    }
+
+    fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        let limit = match self {
+            Self::Utf8 => return Ok(text.as_bytes().to_vec()),
+            Self::Latin1 => 0xff,
+            Self::Ascii => 0x7f,
+        };
+
+        text.chars()
+            .map(|c| match u8::try_from(c) {
+                Ok(byte) if byte <= limit => Ok(byte),
+                _ => Err(format!("{c:?} can't be encoded as {self}.")),
+            })
+            .collect()
+    }
+
+    fn decode(self, bytes: &[u8]) -> Result<String, String> {
+        match self {
+            Self::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|e| format!("invalid utf-8 at byte {}.", e.utf8_error().valid_up_to())),
+            Self::Latin1 => Ok(bytes.iter().map(|&byte| byte as char).collect()),
+            Self::Ascii => match bytes.iter().position(|byte| !byte.is_ascii()) {
+                Some(i) => Err(format!("byte {i} ({:#04x}) is not ascii.", bytes[i])),
+                None => Ok(bytes.iter().map(|&byte| byte as char).collect()),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "utf-8",
+            Self::Latin1 => "latin-1",
+            Self::Ascii => "ascii",
+        })
+    }
+}
+
+fn native_error(name: &str, msg: String) -> Error {
+    Error::NativeError {
+        name: name.to_string(),
+        msg,
+    }
+}
+
+/// The encoding named by the optional argument at `index`, utf-8 without it.
+fn encoding(native: &str, arguments: &[Gc<Object>], index: usize) -> Result<Encoding, Error> {
+    match arguments.get(index).map(|argument| &**argument) {
+        None => Ok(Encoding::Utf8),
+        Some(Object::String(name)) => Encoding::from_name(name).ok_or_else(|| {
+            native_error(
+                native,
+                format!("unknown encoding {name:?}, expected utf-8, latin-1 or ascii."),
+            )
+        }),
+        Some(other) => Err(native_error(
+            native,
+            format!("the encoding must be a string, got {other}."),
+        )),
+    }
+}
+
+/// `encode(string, encoding)`: the bytes of `string`. The encoding is
+/// optional and defaults to utf-8.
+pub struct Encode;
+
+impl Callable for Encode {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn optional(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let Object::String(text) = &*arguments[0] else {
+            return Err(native_error(
+                "encode",
+                format!("expected a string, got {}.", arguments[0]),
+            ));
+        };
+
+        let bytes = encoding("encode", &arguments, 1)?
+            .encode(text)
+            .map_err(|msg| native_error("encode", msg))?;
+        Ok(Gc::new(Object::Bytes(bytes)))
+    }
+}
+
+/// `decode(bytes, encoding)`: the text `bytes` encode. The encoding is
+/// optional and defaults to utf-8.
+pub struct Decode;
+
+impl Callable for Decode {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn optional(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let Object::Bytes(bytes) = &*arguments[0] else {
+            return Err(native_error(
+                "decode",
+                format!("expected bytes, got {}.", arguments[0]),
+            ));
+        };
+
+        let text = encoding("decode", &arguments, 1)?
+            .decode(bytes)
+            .map_err(|msg| native_error("decode", msg))?;
+        interpreter.check_string_length("decode", text.len())?;
+        Ok(Gc::new(Object::String(text)))
+    }
+}
+
+/// `readBytes(path)`: the contents of a file. Requires the `read`
+/// capability.
+pub struct ReadBytes;
+
+impl Callable for ReadBytes {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        interpreter.check_native("readBytes")?;
+
+        let Object::String(path) = &*arguments[0] else {
+            return Err(native_error(
+                "readBytes",
+                format!("the path must be a string, got {}.", arguments[0]),
+            ));
+        };
+
+        let bytes = interpreter.inputs_mut().provide("readBytes", || {
+            let bytes =
+                std::fs::read(path).map_err(|e| native_error("readBytes", e.to_string()))?;
+            Ok(Message::Bytes(bytes))
+        })?;
+        Ok(Gc::new(bytes.into_lox()))
+    }
+}
+
+/// `writeBytes(path, data)`: replaces the contents of a file with bytes, or
+/// with a string as utf-8, and returns the number of bytes written. Requires
+/// the `write` capability.
+pub struct WriteBytes;
+
+impl Callable for WriteBytes {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        interpreter.check_native("writeBytes")?;
+
+        let Object::String(path) = &*arguments[0] else {
+            return Err(native_error(
+                "writeBytes",
+                format!("the path must be a string, got {}.", arguments[0]),
+            ));
+        };
+        let data = match &*arguments[1] {
+            Object::Bytes(bytes) => bytes.as_slice(),
+            Object::String(text) => text.as_bytes(),
+            other => {
+                return Err(native_error(
+                    "writeBytes",
+                    format!("expected bytes or a string, got {other}."),
+                ))
+            }
+        };
+
+        std::fs::write(path, data).map_err(|e| native_error("writeBytes", e.to_string()))?;
+        Ok(Gc::new(Object::Number(Number(data.len() as f64))))
+    }
+}
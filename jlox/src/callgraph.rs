@@ -0,0 +1,231 @@
+//! `jlox callgraph`: which functions and methods call which, read from the
+//! source without running it.
+//!
+//! Calls are matched by name, so the graph is a best guess where Lox only
+//! decides at runtime:
+//!
+//! - `f()` calls every function named `f`, and `Point()` calls
+//!   `Point.init` if the class has one;
+//! - `this.m()` calls `m` as the enclosing class has it, its own or
+//!   inherited, and `super.m()` calls it as the superclass has it;
+//! - `object.m()` calls every method named `m`, since any of them might be
+//!   the one `object` has.
+//!
+//! Calls of natives, and of anything that isn't a name or a method, aren't
+//! edges. Top-level code calls from `<script>`.
+//!
+//! ```
+//! use jlox::{callgraph::CallGraph, parse_source};
+//!
+//! let statements = parse_source("fun f() { g(); } fun g() {} f();").unwrap();
+//! let graph = CallGraph::extract(&statements);
+//! assert_eq!(graph.to_dot(), "digraph calls {
+//!   \"f\";
+//!   \"g\";
+//!   \"f\" -> \"g\";
+//!   \"<script>\" -> \"f\";
+//! }
+//! ");
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Expr, Stmt};
+
+/// The caller of top-level code.
+pub const SCRIPT: &str = "<script>";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallGraph {
+    /// Every function, and every method as `Class.method`, in source order.
+    pub functions: Vec<String>,
+    /// Caller and callee, each edge once, in the order the calls appear.
+    pub edges: Vec<(String, String)>,
+}
+
+impl CallGraph {
+    pub fn extract(statements: &[Stmt]) -> Self {
+        let mut extractor = Extractor::default();
+        for stmt in statements {
+            extractor.declare(stmt, None);
+        }
+        for stmt in statements {
+            extractor.walk(stmt, SCRIPT, None);
+        }
+        extractor.graph
+    }
+
+    /// The graph in Graphviz's DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        for function in &self.functions {
+            dot.push_str(&format!("  \"{function}\";\n"));
+        }
+        for (caller, callee) in &self.edges {
+            dot.push_str(&format!("  \"{caller}\" -> \"{callee}\";\n"));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[derive(Default)]
+struct Extractor {
+    graph: CallGraph,
+    /// Functions that aren't methods. Functions sharing a name in different
+    /// scopes are one node.
+    functions: HashSet<String>,
+    /// Methods by name, each as `Class.method`.
+    methods: HashMap<String, Vec<String>>,
+    /// Every class, with the name of its superclass if it has one.
+    classes: HashMap<String, Option<String>>,
+}
+
+impl Extractor {
+    /// Records the functions and classes declared in `stmt` or nested in it.
+    /// `class` names the class whose methods these are.
+    fn declare(&mut self, stmt: &Stmt, class: Option<&str>) {
+        match stmt {
+            Stmt::Function { name, body, .. } => {
+                let function = match class {
+                    Some(class) => {
+                        let method = format!("{class}.{}", name.lexeme);
+                        self.methods
+                            .entry(name.lexeme.clone())
+                            .or_default()
+                            .push(method.clone());
+                        method
+                    }
+                    None => {
+                        self.functions.insert(name.lexeme.clone());
+                        name.lexeme.clone()
+                    }
+                };
+                if !self.graph.functions.contains(&function) {
+                    self.graph.functions.push(function);
+                }
+                for stmt in body {
+                    self.declare(stmt, None);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let superclass = match superclass {
+                    Some(Expr::Variable { name, .. }) => Some(name.lexeme.clone()),
+                    _ => None,
+                };
+                self.classes.insert(name.lexeme.clone(), superclass);
+                for method in methods {
+                    self.declare(method, Some(&name.lexeme));
+                }
+            }
+            _ => {
+                for child in stmt.children() {
+                    self.declare(child, None);
+                }
+            }
+        }
+    }
+
+    /// Adds the calls `caller` makes in `stmt`. `this` is an instance of
+    /// `class`, if `stmt` is in a method.
+    fn walk(&mut self, stmt: &Stmt, caller: &str, class: Option<&str>) {
+        match stmt {
+            // A nested function keeps the method's `this`.
+            Stmt::Function { name, body, .. } => {
+                for stmt in body {
+                    self.walk(stmt, &name.lexeme, class);
+                }
+            }
+            Stmt::Class { name, methods, .. } => {
+                for method in methods {
+                    if let Stmt::Function {
+                        name: method, body, ..
+                    } = method
+                    {
+                        let caller = format!("{}.{}", name.lexeme, method.lexeme);
+                        for stmt in body {
+                            self.walk(stmt, &caller, Some(&name.lexeme));
+                        }
+                    }
+                }
+            }
+            _ => {
+                for expr in stmt.expressions() {
+                    self.calls(expr, caller, class);
+                }
+                for child in stmt.children() {
+                    self.walk(child, caller, class);
+                }
+            }
+        }
+    }
+
+    fn calls(&mut self, expr: &Expr, caller: &str, class: Option<&str>) {
+        if let Expr::Call { callee, .. } = expr {
+            for callee in self.callees(callee, class) {
+                let edge = (caller.to_owned(), callee);
+                if !self.graph.edges.contains(&edge) {
+                    self.graph.edges.push(edge);
+                }
+            }
+        }
+        for child in expr.children() {
+            self.calls(child, caller, class);
+        }
+    }
+
+    /// What calling `callee` might run.
+    fn callees(&self, callee: &Expr, class: Option<&str>) -> Vec<String> {
+        match callee {
+            Expr::Variable { name, .. } if self.classes.contains_key(&name.lexeme) => {
+                self.method(&name.lexeme, "init").into_iter().collect()
+            }
+            Expr::Variable { name, .. } if self.functions.contains(&name.lexeme) => {
+                vec![name.lexeme.clone()]
+            }
+            Expr::Get { object, name } => {
+                let own = match (&**object, class) {
+                    (Expr::This { .. }, Some(class)) => self.method(class, &name.lexeme),
+                    _ => None,
+                };
+                match own {
+                    Some(method) => vec![method],
+                    None => self.methods.get(&name.lexeme).cloned().unwrap_or_default(),
+                }
+            }
+            Expr::Super {
+                ancestor: Some(ancestor),
+                method,
+                ..
+            } => self
+                .method(&ancestor.lexeme, &method.lexeme)
+                .into_iter()
+                .collect(),
+            Expr::Super { method, .. } => class
+                .and_then(|class| self.classes.get(class)?.as_deref())
+                .and_then(|superclass| self.method(superclass, &method.lexeme))
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The method `name` of `class`, its own or inherited.
+    fn method(&self, class: &str, name: &str) -> Option<String> {
+        let mut class = class;
+        // A bounded walk, in case a class inherits from itself indirectly.
+        for _ in 0..=self.classes.len() {
+            let method = format!("{class}.{name}");
+            if self.graph.functions.contains(&method) {
+                return Some(method);
+            }
+            class = self.classes.get(class)?.as_deref()?;
+        }
+        None
+    }
+}
@@ -0,0 +1,88 @@
+//! Running a script without a console, for graders and differential testing:
+//! [`run_captured`] feeds it input from a string and hands back everything it
+//! printed along with how it ended.
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{limits::Limits, stats::Stats, streams::OutputStream, Lox, LoxError};
+
+/// The outcome of [`run_captured`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunReport {
+    pub stdout: String,
+    pub stderr: String,
+    /// Every error, in the order they happened: at most one from the script
+    /// itself, then any from the callbacks it scheduled.
+    pub diagnostics: Vec<String>,
+    /// What `jlox` would exit with: 0, 65 if the source didn't compile or 70
+    /// if it failed while running.
+    pub exit_status: i32,
+    pub stats: Stats,
+}
+
+/// Collects what a script writes.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `source` on a fresh interpreter with `stdin` as its input and
+/// `limits`, like `jlox` runs a file, without touching the console. The
+/// interpreter has no capabilities. Tasks it spawns share its limits, and
+/// what they print is captured too once they finish or run out of time.
+pub fn run_captured(source: &str, stdin: &str, limits: Limits) -> RunReport {
+    let (stdout, stderr) = (Capture::default(), Capture::default());
+    let mut lox = Lox::new();
+    lox.set_limits(limits);
+    lox.redirect_input(io::Cursor::new(stdin.to_owned()));
+    lox.redirect_output(OutputStream::Stdout, stdout.clone());
+    lox.redirect_output(OutputStream::Stderr, stderr.clone());
+
+    let mut diagnostics = Vec::new();
+    let exit_status = match lox.execute(source) {
+        Ok(()) => {
+            let mut status = 0;
+            while let Err(err) = lox.interpreter.borrow_mut().run_events(true) {
+                diagnostics.push(LoxError::Runtime(err).to_string());
+                status = 70;
+            }
+            status
+        }
+        Err(err @ LoxError::Runtime(_)) => {
+            diagnostics.push(err.to_string());
+            70
+        }
+        Err(err) => {
+            diagnostics.push(err.to_string());
+            65
+        }
+    };
+
+    lox.interpreter.borrow_mut().wait_for_tasks();
+
+    RunReport {
+        stdout: stdout.text(),
+        stderr: stderr.text(),
+        diagnostics,
+        exit_status,
+        stats: lox.stats(),
+    }
+}
@@ -1,39 +1,97 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{collections::HashMap, fmt::Display};
 
 use crate::{
     functions::{Callable, LoxFunction},
     interpreter::Interpreter,
     object::Object,
     token::Token,
+    types::{Gc, GcCell},
 };
 
 #[derive(Debug, Clone)]
 pub struct Class {
     name: String,
-    superclass: Option<Rc<RefCell<Class>>>,
+    superclass: Option<Gc<GcCell<Class>>>,
     methods: HashMap<String, LoxFunction>,
+    doc: Option<String>,
+    /// Whether the class itself defines `onSet`, so writes to the fields
+    /// of instances of classes that don't skip looking for it.
+    on_set: bool,
 }
 
 impl Class {
     pub fn new(
         name: String,
-        superclass: Option<Rc<RefCell<Class>>>,
+        superclass: Option<Gc<GcCell<Class>>>,
         methods: HashMap<String, LoxFunction>,
     ) -> Self {
         Self {
             name,
+            on_set: methods.contains_key("onSet"),
             superclass,
             methods,
+            doc: None,
         }
     }
 
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
+    }
+
+    /// The doc comment written above the class, if any.
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn superclass(&self) -> Option<&Gc<GcCell<Class>>> {
+        self.superclass.as_ref()
+    }
+
+    pub fn methods(&self) -> &HashMap<String, LoxFunction> {
+        &self.methods
+    }
+
+    /// Whether the class or a superclass defines `onSet(name, old, new)`,
+    /// called after each write to a field of an instance.
+    pub fn observes_sets(&self) -> bool {
+        self.on_set
+            || self
+                .superclass
+                .as_ref()
+                .is_some_and(|superclass| superclass.borrow().observes_sets())
+    }
+
+    /// Makes an instance of `klass` and runs its initializer. The instance
+    /// shares `klass`, so it sees changes to the class such as a reload.
+    pub fn instantiate(
+        klass: &Gc<GcCell<Class>>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, crate::interpreter::Error> {
+        let instance = Gc::new(GcCell::new(Instance::new(klass.clone())));
+        interpreter.track_instance(&instance);
+
+        let initializer = klass.borrow().find_method("init");
+
+        if let Some(init) = initializer {
+            init.bind(instance.clone()).call(interpreter, arguments)?;
+        }
+
+        Ok(Gc::new(Object::Instance(instance)))
+    }
+
     pub fn find_method(&self, name: &str) -> Option<LoxFunction> {
         if let Some(method) = self.methods.get(name) {
-            return Some(method.clone());
+            Some(method.clone())
         } else if let Some(superclass) = &self.superclass {
-            return superclass.borrow().find_method(name);
+            superclass.borrow().find_method(name)
         } else {
-            return None;
+            None
         }
     }
 }
@@ -60,57 +118,56 @@ impl Callable for Class {
     fn call(
         &self,
         interpreter: &mut Interpreter,
-        arguments: Vec<Rc<Object>>,
-    ) -> Result<Rc<Object>, Self::E> {
-        let instance = Rc::new(RefCell::new(Instance::new(Rc::new(RefCell::new(
-            self.clone(),
-        )))));
-
-        let initializer = self.find_method("init");
-
-        if let Some(init) = initializer {
-            init.bind(instance.clone()).call(interpreter, arguments)?;
-        }
-
-        Ok(Rc::new(Object::Instance(instance)))
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Self::E> {
+        Class::instantiate(&Gc::new(GcCell::new(self.clone())), interpreter, arguments)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Instance {
-    klass: Rc<RefCell<Class>>,
-    fields: HashMap<String, Rc<Object>>,
+    klass: Gc<GcCell<Class>>,
+    fields: HashMap<String, Gc<Object>>,
 }
 
 impl Instance {
-    pub fn new(klass: Rc<RefCell<Class>>) -> Self {
+    pub fn new(klass: Gc<GcCell<Class>>) -> Self {
         Self {
             klass,
             fields: HashMap::new(),
         }
     }
 
-    pub fn get(&self, name: Token) -> Result<Rc<Object>, crate::interpreter::Error> {
+    pub fn get(&self, name: Token) -> Result<Gc<Object>, crate::interpreter::Error> {
         if self.fields.contains_key(&name.lexeme) {
             return Ok(self.fields.get(&name.lexeme).unwrap().clone());
         }
 
         if let Some(method) = self.klass.borrow().find_method(&name.lexeme) {
-            return Ok(Rc::new(Object::Function(Rc::new(
-                method.bind(Rc::new(RefCell::new(self.clone()))),
+            return Ok(Gc::new(Object::Function(Gc::new(
+                method.bind(Gc::new(GcCell::new(self.clone()))),
             ))));
         }
 
         Err(crate::interpreter::Error::UndefinedProperty { name: name.lexeme })
     }
 
-    pub fn set(&mut self, name: Token, value: Rc<Object>) {
+    pub fn class(&self) -> &Gc<GcCell<Class>> {
+        &self.klass
+    }
+
+    /// The fields set on the instance, by name.
+    pub fn fields(&self) -> &HashMap<String, Gc<Object>> {
+        &self.fields
+    }
+
+    pub fn set(&mut self, name: Token, value: Gc<Object>) {
         self.fields.insert(name.lexeme, value);
     }
 }
 
 impl Display for Instance {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} instance", self.klass.borrow().to_string())
+        write!(f, "{} instance", self.klass.borrow())
     }
 }
@@ -0,0 +1,168 @@
+//! The line format of replay traces and snapshots.
+//!
+//! A file starts with a header line naming the format and its version, then
+//! holds one entry per value. An entry's first line is `kind name`, followed
+//! by the value for nil, booleans, numbers and bytes (escaped like a bytes
+//! literal). Strings and source code give the number of lines that follow
+//! and hold the text; tuples, lists and maps give their number of elements
+//! or pairs, each an entry of its own.
+
+use std::fmt::{self, Formatter};
+
+use crate::{bytes, snapshot::Entry};
+
+/// A file that doesn't follow the format. Lines count from 1.
+#[derive(Debug)]
+pub(crate) struct Malformed {
+    pub line: usize,
+    pub msg: String,
+}
+
+/// The entries of `source`, which has to start with `header`, each with the
+/// line it starts on.
+pub(crate) fn parse(source: &str, header: &str) -> Result<Vec<(usize, String, Entry)>, Malformed> {
+    let mut lines = source.split_inclusive('\n').enumerate();
+
+    match lines.next() {
+        Some((_, first)) if first.trim_end() == header => (),
+        _ => return Err(malformed(0, &format!("missing '{header}' header"))),
+    }
+
+    let mut entries = Vec::new();
+    while let Some((line, text)) = lines.next() {
+        let (name, entry) = parse_entry(&mut lines, line, text)?;
+        entries.push((line + 1, name.to_string(), entry));
+    }
+
+    Ok(entries)
+}
+
+/// Writes `header`, then every entry.
+pub(crate) fn write<'a>(
+    f: &mut Formatter<'_>,
+    header: &str,
+    entries: impl IntoIterator<Item = &'a (String, Entry)>,
+) -> fmt::Result {
+    writeln!(f, "{header}")?;
+
+    for (name, entry) in entries {
+        write_entry(f, name, entry)?;
+    }
+
+    Ok(())
+}
+
+type Lines<'a> = std::iter::Enumerate<std::str::SplitInclusive<'a, char>>;
+
+fn malformed(line: usize, msg: &str) -> Malformed {
+    Malformed {
+        line: line + 1,
+        msg: msg.to_string(),
+    }
+}
+
+/// Parses the entry whose first line is `text`, the `line`th one, taking
+/// the lines of its payload or elements from `lines`.
+fn parse_entry<'a>(
+    lines: &mut Lines<'a>,
+    line: usize,
+    text: &'a str,
+) -> Result<(&'a str, Entry), Malformed> {
+    let mut fields = text.trim_end_matches('\n').splitn(3, ' ');
+    let (Some(kind), Some(name)) = (fields.next(), fields.next()) else {
+        return Err(malformed(line, "expected '<kind> <name>'"));
+    };
+    let rest = fields.next();
+    let count = || -> Result<usize, Malformed> {
+        rest.unwrap_or_default()
+            .parse()
+            .map_err(|_| malformed(line, "invalid count"))
+    };
+    let next = |lines: &mut Lines<'a>| -> Result<Entry, Malformed> {
+        let Some((element, text)) = lines.next() else {
+            return Err(malformed(line, "truncated entry"));
+        };
+        Ok(parse_entry(lines, element, text)?.1)
+    };
+
+    let entry = match (kind, rest) {
+        ("nil", None) => Entry::Nil,
+        ("bool", Some("true")) => Entry::Bool(true),
+        ("bool", Some("false")) => Entry::Bool(false),
+        ("number", Some(n)) => Entry::Number(
+            n.parse()
+                .map_err(|_| malformed(line, "invalid number literal"))?,
+        ),
+        ("bytes", Some(b)) => Entry::Bytes(
+            bytes::unescape(b.as_bytes())
+                .ok_or_else(|| malformed(line, "invalid bytes literal"))?,
+        ),
+        ("string" | "fun" | "class", Some(_)) => {
+            let mut payload = String::new();
+            for _ in 0..count()? {
+                let Some((_, text)) = lines.next() else {
+                    return Err(malformed(line, "truncated entry"));
+                };
+                payload.push_str(text);
+            }
+            // The payload is always terminated by a newline that isn't part
+            // of the value.
+            payload.pop();
+
+            match kind {
+                "string" => Entry::String(payload),
+                "fun" => Entry::Function(payload),
+                _ => Entry::Class(payload),
+            }
+        }
+        ("tuple" | "list", Some(_)) => {
+            let elements = (0..count()?)
+                .map(|_| next(lines))
+                .collect::<Result<_, _>>()?;
+            match kind {
+                "tuple" => Entry::Tuple(elements),
+                _ => Entry::List(elements),
+            }
+        }
+        ("map", Some(_)) => Entry::Map(
+            (0..count()?)
+                .map(|_| Ok((next(lines)?, next(lines)?)))
+                .collect::<Result<_, Malformed>>()?,
+        ),
+        _ => return Err(malformed(line, &format!("unknown entry kind '{kind}'"))),
+    };
+
+    Ok((name, entry))
+}
+
+/// Writes `entry` as the parser reads it: one line naming it, then the lines
+/// of a payload or one entry per element.
+fn write_entry(f: &mut Formatter<'_>, name: &str, entry: &Entry) -> fmt::Result {
+    let kind = entry.kind();
+    match entry {
+        Entry::Nil => writeln!(f, "{kind} {name}"),
+        Entry::Bool(b) => writeln!(f, "{kind} {name} {b}"),
+        Entry::Number(n) => writeln!(f, "{kind} {name} {n}"),
+        Entry::Bytes(b) => writeln!(f, "{kind} {name} {}", bytes::escape(b)),
+        Entry::String(payload) | Entry::Function(payload) | Entry::Class(payload) => {
+            let count = payload.split('\n').count();
+            writeln!(f, "{kind} {name} {count}")?;
+            writeln!(f, "{payload}")
+        }
+        Entry::Tuple(elements) | Entry::List(elements) => {
+            writeln!(f, "{kind} {name} {}", elements.len())?;
+            for (i, element) in elements.iter().enumerate() {
+                write_entry(f, &i.to_string(), element)?;
+            }
+            Ok(())
+        }
+        Entry::Map(entries) => {
+            writeln!(f, "{kind} {name} {}", entries.len())?;
+            for (key, value) in entries {
+                write_entry(f, "key", key)?;
+                write_entry(f, "value", value)?;
+            }
+            Ok(())
+        }
+    }
+}
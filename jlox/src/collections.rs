@@ -0,0 +1,528 @@
+//! Frozen collections: lists written `#[1, 2, 3]` and maps written
+//! `#{"a": 1}`. Neither can change once built, so they can be shared freely
+//! and used as map keys themselves.
+//!
+//! Map keys are hashed by value: nil, booleans, numbers, strings, bytes and tuples,
+//! lists and maps of those. Instances are hashed and compared through their
+//! `hashCode()` and `equals(other)` methods, or by identity without them, so
+//! looking a key up may call back into the interpreter; see
+//! [`Interpreter::hash_key`] and [`Interpreter::keys_equal`]. Functions,
+//! classes and host values can't be keys.
+
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap},
+    convert::Infallible,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    class::Instance,
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    types::{Gc, GcCell, Number},
+};
+
+/// The hash of `value` as a map key, or `None` if it can't be one. Plain
+/// data only: instances have no hash here.
+pub fn hash(value: &Object) -> Option<u64> {
+    let Ok(hash) = hash_with(value, &mut |_| Ok::<_, Infallible>(None));
+    hash
+}
+
+/// The hash of `value` as a map key, with `instance` hashing the instances
+/// in it, or `None` if it can't be one.
+pub fn hash_with<E>(
+    value: &Object,
+    instance: &mut impl FnMut(&Gc<GcCell<Instance>>) -> Result<Option<u64>, E>,
+) -> Result<Option<u64>, E> {
+    let mut hasher = DefaultHasher::new();
+    Ok(hash_into(value, &mut hasher, instance)?.map(|()| hasher.finish()))
+}
+
+fn hash_into<E>(
+    value: &Object,
+    state: &mut impl Hasher,
+    instance: &mut impl FnMut(&Gc<GcCell<Instance>>) -> Result<Option<u64>, E>,
+) -> Result<Option<()>, E> {
+    std::mem::discriminant(value).hash(state);
+    match value {
+        Object::Nil => (),
+        Object::Bool(b) => b.hash(state),
+        // `0 == -0`, so both have to hash the same.
+        Object::Number(n) if *n == Number(0.0) => 0.0f64.to_bits().hash(state),
+        Object::Number(n) => n.0.to_bits().hash(state),
+        Object::String(s) => s.hash(state),
+        Object::Bytes(bytes) => bytes.hash(state),
+        Object::Tuple(elements) | Object::List(elements) => {
+            elements.len().hash(state);
+            for element in elements {
+                if hash_into(element, state, instance)?.is_none() {
+                    return Ok(None);
+                }
+            }
+        }
+        // Equal maps can list their entries in any order.
+        Object::Map(map) => {
+            let mut combined = 0u64;
+            for (key, value) in map.iter() {
+                let mut entry = DefaultHasher::new();
+                if hash_into(key, &mut entry, instance)?.is_none()
+                    || hash_into(value, &mut entry, instance)?.is_none()
+                {
+                    return Ok(None);
+                }
+                combined = combined.wrapping_add(entry.finish());
+            }
+            map.len().hash(state);
+            combined.hash(state);
+        }
+        Object::Instance(inst) => match instance(inst)? {
+            Some(hash) => hash.hash(state),
+            None => return Ok(None),
+        },
+        Object::Function(_) | Object::Class(_) | Object::UserData(_) => return Ok(None),
+    }
+    Ok(Some(()))
+}
+
+/// An immutable map, iterated in the order its keys were first inserted.
+#[derive(Default, Clone)]
+pub struct Map {
+    entries: Vec<(Gc<Object>, Gc<Object>)>,
+    buckets: HashMap<u64, Vec<usize>>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds plain data `key`, replacing the value of an equal key already
+    /// present. Gives the key back if it has no hash.
+    pub(crate) fn insert(&mut self, key: Gc<Object>, value: Gc<Object>) -> Result<(), Gc<Object>> {
+        let Some(hash) = hash(&key) else {
+            return Err(key);
+        };
+
+        let Ok(()) = self.insert_hashed(hash, key.clone(), value, |k| {
+            Ok::<_, Infallible>(**k == *key)
+        });
+        Ok(())
+    }
+
+    /// Adds `key` under its `hash`, replacing the value of the key already
+    /// present that `eq` says is the same. Only used while building a map.
+    pub(crate) fn insert_hashed<E>(
+        &mut self,
+        hash: u64,
+        key: Gc<Object>,
+        value: Gc<Object>,
+        eq: impl FnMut(&Gc<Object>) -> Result<bool, E>,
+    ) -> Result<(), E> {
+        match self.find(hash, eq)? {
+            Some(i) => self.entries[i].1 = value,
+            None => {
+                self.buckets
+                    .entry(hash)
+                    .or_default()
+                    .push(self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// The value of plain data `key`.
+    pub fn get(&self, key: &Object) -> Option<&Gc<Object>> {
+        let Ok(value) = self.get_hashed(hash(key)?, |k| Ok::<_, Infallible>(**k == *key));
+        value
+    }
+
+    /// The value of the key with `hash` that `eq` says is the one looked for.
+    pub fn get_hashed<E>(
+        &self,
+        hash: u64,
+        eq: impl FnMut(&Gc<Object>) -> Result<bool, E>,
+    ) -> Result<Option<&Gc<Object>>, E> {
+        Ok(self.find(hash, eq)?.map(|i| &self.entries[i].1))
+    }
+
+    fn find<E>(
+        &self,
+        hash: u64,
+        mut eq: impl FnMut(&Gc<Object>) -> Result<bool, E>,
+    ) -> Result<Option<usize>, E> {
+        for &i in self.buckets.get(&hash).into_iter().flatten() {
+            if eq(&self.entries[i].0)? {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Gc<Object>, &Gc<Object>)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl std::fmt::Debug for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Maps are equal when they have equal keys with equal values, in any order.
+/// This is `==`, so maps keyed by instances are never equal; see
+/// [`Interpreter::keys_equal`] for the comparison map lookups use.
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key).is_some_and(|other| **other == **value))
+    }
+}
+
+/// `len(x)`: the number of elements of a list, tuple or map, or of bytes in
+/// a string or bytes value.
+pub struct Len;
+
+impl Callable for Len {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let len = match &*arguments[0] {
+            Object::List(elements) | Object::Tuple(elements) => elements.len(),
+            Object::Map(map) => map.len(),
+            Object::String(s) => s.len(),
+            Object::Bytes(bytes) => bytes.len(),
+            other => {
+                return Err(native_error(
+                    "len",
+                    format!("expected a list, tuple, map, string or bytes, got {other}."),
+                ))
+            }
+        };
+        Ok(Gc::new(Object::Number(Number(len as f64))))
+    }
+}
+
+/// `has(collection, x)`: whether a map has the key `x` or a list contains
+/// `x`, comparing the way map keys are compared.
+pub struct Has;
+
+impl Callable for Has {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let found = match &*arguments[0] {
+            Object::Map(map) => interpreter.map_get(map, &arguments[1])?.is_some(),
+            Object::List(elements) => {
+                let mut found = false;
+                for element in elements {
+                    if interpreter.keys_equal(element, &arguments[1])? {
+                        found = true;
+                        break;
+                    }
+                }
+                found
+            }
+            other => {
+                return Err(native_error(
+                    "has",
+                    format!("expected a list or map, got {other}."),
+                ))
+            }
+        };
+        Ok(interpreter.bool(found))
+    }
+}
+
+/// `slice(x, start, end)`: the elements of a list, or the bytes, from
+/// `start` up to but not including `end`. Without `end`, up to the end.
+pub struct Slice;
+
+impl Callable for Slice {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn optional(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let len = match &*arguments[0] {
+            Object::List(elements) => elements.len(),
+            Object::Bytes(bytes) => bytes.len(),
+            other => {
+                return Err(native_error(
+                    "slice",
+                    format!("expected a list or bytes, got {other}."),
+                ))
+            }
+        };
+
+        let bound = |argument: &Gc<Object>| match &**argument {
+            Object::Number(n) => n.as_int().and_then(|i| usize::try_from(i).ok()),
+            _ => None,
+        };
+        let start = bound(&arguments[1]);
+        let end = arguments.get(2).map_or(Some(len), bound);
+        let (Some(start), Some(end)) = (start, end) else {
+            return Err(native_error(
+                "slice",
+                "start and end must be whole numbers.".to_string(),
+            ));
+        };
+        if start > end || end > len {
+            return Err(native_error(
+                "slice",
+                format!("{start}..{end} is out of range for a length of {len}."),
+            ));
+        }
+
+        Ok(Gc::new(match &*arguments[0] {
+            Object::Bytes(bytes) => Object::Bytes(bytes[start..end].to_vec()),
+            Object::List(elements) => Object::List(elements[start..end].to_vec()),
+            _ => unreachable!("checked above"),
+        }))
+    }
+}
+
+fn native_error(name: &str, msg: String) -> Error {
+    Error::NativeError {
+        name: name.to_string(),
+        msg,
+    }
+}
+
+/// The elements of `value`, which `native` requires to be a list.
+fn list_argument<'a>(native: &str, value: &'a Object) -> Result<&'a [Gc<Object>], Error> {
+    match value {
+        Object::List(elements) => Ok(elements),
+        other => Err(native_error(
+            native,
+            format!("expected a list, got {other}."),
+        )),
+    }
+}
+
+fn list(elements: Vec<Gc<Object>>) -> Gc<Object> {
+    Gc::new(Object::List(elements))
+}
+
+// The natives below call back into Lox. An error raised by a callback is
+// returned as it is, so it still points at the line in the callback.
+
+/// `map(list, fn)`: a new list of `fn(element)` for every element.
+pub struct MapList;
+
+impl Callable for MapList {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let elements = list_argument("map", &arguments[0])?
+            .iter()
+            .map(|element| interpreter.call_value(arguments[1].clone(), vec![element.clone()]))
+            .collect::<Result<_, _>>()?;
+        Ok(list(elements))
+    }
+}
+
+/// `filter(list, fn)`: a new list of the elements for which `fn(element)`
+/// is truthy.
+pub struct Filter;
+
+impl Callable for Filter {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let mut kept = Vec::new();
+        for element in list_argument("filter", &arguments[0])? {
+            let keep = interpreter.call_value(arguments[1].clone(), vec![element.clone()])?;
+            if interpreter.is_truthy(&keep)? {
+                kept.push(element.clone());
+            }
+        }
+        Ok(list(kept))
+    }
+}
+
+/// `reduce(list, fn, init)`: folds the list from the left, starting from
+/// `init`, with `fn(accumulator, element)`.
+pub struct Reduce;
+
+impl Callable for Reduce {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let mut accumulator = arguments[2].clone();
+        for element in list_argument("reduce", &arguments[0])? {
+            accumulator =
+                interpreter.call_value(arguments[1].clone(), vec![accumulator, element.clone()])?;
+        }
+        Ok(accumulator)
+    }
+}
+
+/// `sort(list, comparator)`: a new list in the order `comparator(a, b)`
+/// gives, a negative number when `a` goes first, positive when `b` does
+/// and zero when either can. Without a comparator numbers and strings sort
+/// ascending and instances by their `compareTo(other)` method, which
+/// answers like a comparator.
+///
+/// The sort is a merge sort: stable, so elements that compare as equal
+/// keep their order, and it makes O(n log n) comparisons.
+pub struct Sort;
+
+impl Callable for Sort {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn optional(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let elements = list_argument("sort", &arguments[0])?;
+        let sorted = match arguments.get(1) {
+            Some(comparator) => merge_sort(elements, &mut |a, b| {
+                let order =
+                    interpreter.call_value(comparator.clone(), vec![a.clone(), b.clone()])?;
+                ordering(&order, || "the comparator".to_string())
+            })?,
+            None => merge_sort(elements, &mut |a, b| natural_order(interpreter, a, b))?,
+        };
+        Ok(list(sorted))
+    }
+}
+
+/// How `sort` orders elements without a comparator.
+fn natural_order(
+    interpreter: &mut Interpreter,
+    a: &Gc<Object>,
+    b: &Gc<Object>,
+) -> Result<Ordering, Error> {
+    match (&**a, &**b) {
+        (Object::Number(x), Object::Number(y)) => Ok(x.partial_cmp(y).unwrap_or(Ordering::Equal)),
+        (Object::String(x), Object::String(y)) => Ok(x.cmp(y)),
+        (Object::Instance(instance), _) => {
+            match interpreter.call_protocol(instance, "compareTo", vec![b.clone()])? {
+                Some(order) => ordering(&order, || {
+                    format!("{}.compareTo()", instance.borrow().class().borrow().name())
+                }),
+                None => Err(native_error(
+                    "sort",
+                    format!("{a} has no compareTo(other) method, pass a comparator to sort it."),
+                )),
+            }
+        }
+        _ => Err(native_error(
+            "sort",
+            format!("can't compare {a} with {b}, pass a comparator to sort them."),
+        )),
+    }
+}
+
+/// The order a comparator's `result` stands for. NaN counts as a tie.
+fn ordering(result: &Object, what: impl FnOnce() -> String) -> Result<Ordering, Error> {
+    match result {
+        Object::Number(n) => Ok(n.0.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+        other => Err(native_error(
+            "sort",
+            format!("{} must return a number, got {other}.", what()),
+        )),
+    }
+}
+
+/// A merge sort, stable and fine with a comparison that fails or isn't a
+/// consistent order, which [`slice::sort_by`] doesn't allow.
+fn merge_sort<E>(
+    items: &[Gc<Object>],
+    compare: &mut impl FnMut(&Gc<Object>, &Gc<Object>) -> Result<Ordering, E>,
+) -> Result<Vec<Gc<Object>>, E> {
+    if items.len() <= 1 {
+        return Ok(items.to_vec());
+    }
+
+    let (left, right) = items.split_at(items.len() / 2);
+    let mut left = merge_sort(left, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(items.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Ties take from the left, which keeps equal elements in order.
+        let next = match compare(a, b)? {
+            Ordering::Greater => right.next(),
+            _ => left.next(),
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
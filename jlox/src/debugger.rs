@@ -0,0 +1,448 @@
+//! `jlox --debug script.lox`: a line debugger for scripts.
+//!
+//! It starts paused at the first statement and reads commands at a `(debug)`
+//! prompt:
+//!
+//! - `step` (`s`) runs to the next statement, entering calls.
+//! - `next` (`n`) runs to the next statement in the current call or one of
+//!   its callers.
+//! - `finish` (`f`) runs until the current call returns.
+//! - `continue` (`c`) runs to the next breakpoint.
+//! - `break N` (`b N`) and `delete N` (`d N`) add and remove a breakpoint at
+//!   line `N`; `break` alone lists them. With `break N if condition`,
+//!   execution only stops there while the condition holds.
+//! - `watch expr` (`w expr`) stops whenever the value of `expr` changes and
+//!   shows the old and new values; `unwatch N` removes the `N`th watch.
+//! - `print expr` (`p expr`) shows the value of `expr`.
+//! - `list` (`l`) shows the lines around the current one.
+//! - `quit` (`q`) lets the script run to the end without stopping.
+//!
+//! Execution stops where the interpreter reaches an entry of the
+//! statement-boundary table, so a `for` loop stops at its header before every
+//! increment and stepping over a method call never lands inside it. A line
+//! holding several statements is stopped at once.
+//!
+//! When a runtime error ends the script, the debugger stops where it was
+//! raised for a post-mortem: `where` (`bt`) shows the calls that were in
+//! progress, `up` and `down` move between them, `print` and `list` work in
+//! the selected one, `captures` shows the variables its function closes
+//! over and `quit` ends the script.
+//!
+//! Conditions and expressions are evaluated where execution stopped, with
+//! [`Interpreter::evaluate_here`]. A watch is checked before every statement
+//! and skipped where its variables aren't in scope.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    ast::{boundary_table, Boundary, Expr},
+    interpreter::{DebugHook, Error, Failure, Interpreter},
+    parse_source,
+    parser::Parser,
+    scanner::Scanner,
+};
+
+/// Where to stop next, besides breakpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Step,
+    /// At a call depth up to this one.
+    Next(usize),
+    /// At a call depth below this one.
+    Finish(usize),
+    Continue,
+}
+
+/// An expression as typed and as parsed.
+struct Source {
+    text: String,
+    expr: Expr,
+}
+
+struct Watch {
+    source: Source,
+    /// The value when last checked, if its variables were in scope.
+    value: Option<String>,
+}
+
+pub struct Debugger<R, W> {
+    lines: Vec<String>,
+    /// Lines with a statement on them, from the statement-boundary table.
+    statement_lines: BTreeSet<usize>,
+    /// Breakpoints by line, with their conditions.
+    breakpoints: BTreeMap<usize, Option<Source>>,
+    watches: Vec<Watch>,
+    mode: Mode,
+    /// Set by `quit` or once commands run out: the script runs on as if
+    /// there were no debugger.
+    detached: bool,
+    /// Where execution last stopped.
+    stopped: Option<(Boundary, usize)>,
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// A debugger for `source` that reads commands from `input` and reports
+    /// to `output`.
+    pub fn new(source: &str, input: R, output: W) -> Self {
+        let table = parse_source(source)
+            .map(|statements| boundary_table(&statements))
+            .unwrap_or_default();
+
+        Self {
+            lines: source.lines().map(str::to_owned).collect(),
+            statement_lines: table.iter().map(|boundary| boundary.line).collect(),
+            breakpoints: BTreeMap::new(),
+            watches: Vec::new(),
+            mode: Mode::Step,
+            detached: false,
+            stopped: None,
+            input,
+            output,
+        }
+    }
+
+    fn should_stop(
+        &mut self,
+        interpreter: &mut Interpreter,
+        boundary: Boundary,
+        depth: usize,
+    ) -> io::Result<bool> {
+        if self.detached {
+            return Ok(false);
+        }
+        let watched = self.check_watches(interpreter)?;
+
+        let wanted = match self.mode {
+            Mode::Step => true,
+            Mode::Next(at) => depth <= at,
+            Mode::Finish(at) => depth < at,
+            Mode::Continue => false,
+        };
+
+        // Statements sharing a line with the last stop are part of it, but a
+        // loop header coming around again is a new stop.
+        let same_line = self.stopped.is_some_and(|(stopped, at)| {
+            stopped.line == boundary.line && at == depth && stopped.id != boundary.id
+        });
+        if same_line {
+            return Ok(watched);
+        }
+
+        Ok(watched || wanted || self.hits_breakpoint(interpreter, boundary.line)?)
+    }
+
+    /// Whether there is a breakpoint at `line` whose condition, if any,
+    /// holds. A condition that fails counts as holding.
+    fn hits_breakpoint(&mut self, interpreter: &mut Interpreter, line: usize) -> io::Result<bool> {
+        let Some(breakpoint) = self.breakpoints.get(&line) else {
+            return Ok(false);
+        };
+        let Some(condition) = breakpoint else {
+            return Ok(true);
+        };
+
+        let holds = interpreter
+            .evaluate_here(condition.expr.clone())
+            .and_then(|value| interpreter.is_truthy(&value));
+        match holds {
+            Ok(holds) => Ok(holds),
+            Err(err) => {
+                let text = &condition.text;
+                writeln!(self.output, "Error in '{text}' at line {line}: {err}")?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Updates the watches, reporting those whose value changed.
+    fn check_watches(&mut self, interpreter: &mut Interpreter) -> io::Result<bool> {
+        let mut changed = false;
+        for (number, watch) in self.watches.iter_mut().enumerate() {
+            let Ok(value) = interpreter.evaluate_here(watch.source.expr.clone()) else {
+                continue;
+            };
+            let value = value.to_string();
+            if let Some(old) = &watch.value {
+                if *old != value {
+                    let text = &watch.source.text;
+                    writeln!(
+                        self.output,
+                        "Watch {}: {text} changed from {old} to {value}.",
+                        number + 1
+                    )?;
+                    changed = true;
+                }
+            }
+            watch.value = Some(value);
+        }
+        Ok(changed)
+    }
+
+    /// Reads commands until one resumes execution.
+    fn prompt(
+        &mut self,
+        interpreter: &mut Interpreter,
+        boundary: Boundary,
+        depth: usize,
+    ) -> io::Result<()> {
+        self.show(boundary.line)?;
+
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                self.detached = true;
+                return Ok(());
+            }
+
+            let line = line.trim();
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            match (command, rest) {
+                ("" | "s" | "step", "") => self.mode = Mode::Step,
+                ("n" | "next", "") => self.mode = Mode::Next(depth),
+                ("f" | "finish", "") => self.mode = Mode::Finish(depth),
+                ("c" | "continue", "") => self.mode = Mode::Continue,
+                ("q" | "quit", "") => self.detached = true,
+                ("b" | "break", "") => {
+                    for (line, condition) in &self.breakpoints {
+                        match condition {
+                            Some(condition) => writeln!(
+                                self.output,
+                                "Breakpoint at line {line} if {}.",
+                                condition.text
+                            )?,
+                            None => writeln!(self.output, "Breakpoint at line {line}.")?,
+                        }
+                    }
+                    continue;
+                }
+                ("b" | "break", rest) => {
+                    self.add_breakpoint(rest)?;
+                    continue;
+                }
+                ("d" | "delete", rest) => {
+                    match rest.parse() {
+                        Ok(line) if self.breakpoints.remove(&line).is_some() => (),
+                        _ => writeln!(self.output, "No breakpoint at line {rest}.")?,
+                    }
+                    continue;
+                }
+                ("w" | "watch", "") => {
+                    for (number, watch) in self.watches.iter().enumerate() {
+                        let value = watch.value.as_deref().unwrap_or("unavailable");
+                        let text = &watch.source.text;
+                        writeln!(self.output, "Watch {}: {text} = {value}", number + 1)?;
+                    }
+                    continue;
+                }
+                ("w" | "watch", rest) => {
+                    if let Some(source) = self.parse(rest)? {
+                        let value = interpreter.evaluate_here(source.expr.clone());
+                        let value = value.ok().map(|value| value.to_string());
+                        let shown = value.as_deref().unwrap_or("unavailable");
+                        let number = self.watches.len() + 1;
+                        writeln!(self.output, "Watch {number}: {rest} = {shown}")?;
+                        self.watches.push(Watch { source, value });
+                    }
+                    continue;
+                }
+                ("unwatch", rest) => {
+                    match rest.parse::<usize>() {
+                        Ok(number) if (1..=self.watches.len()).contains(&number) => {
+                            self.watches.remove(number - 1);
+                        }
+                        _ => writeln!(self.output, "No watch {rest}.")?,
+                    }
+                    continue;
+                }
+                ("p" | "print", rest) if !rest.is_empty() => {
+                    if let Some(source) = self.parse(rest)? {
+                        match interpreter.evaluate_here(source.expr) {
+                            Ok(value) => writeln!(self.output, "{value}")?,
+                            Err(err) => writeln!(self.output, "Error: {err}")?,
+                        }
+                    }
+                    continue;
+                }
+                ("l" | "list", "") => {
+                    self.list(boundary.line)?;
+                    continue;
+                }
+                _ => {
+                    writeln!(
+                        self.output,
+                        "Commands: step, next, finish, continue, break [line [if condition]], \
+                         delete line, watch [expr], unwatch n, print expr, list, quit."
+                    )?;
+                    continue;
+                }
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Reads post-mortem commands until one ends the session.
+    fn post_mortem(
+        &mut self,
+        interpreter: &mut Interpreter,
+        error: &Error,
+        failure: &Failure,
+    ) -> io::Result<()> {
+        let frames = failure.frames();
+        let mut selected = 0;
+        writeln!(self.output, "Error: {error}")?;
+        self.show(frames[selected].boundary().line)?;
+
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            let line = line.trim();
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            match (command, rest) {
+                ("q" | "quit" | "c" | "continue", "") => return Ok(()),
+                ("bt" | "where", "") => {
+                    for (number, frame) in frames.iter().enumerate() {
+                        let marker = if number == selected { "->" } else { "  " };
+                        let (name, line) = (frame.name(), frame.boundary().line);
+                        writeln!(self.output, "{marker} #{number} {name} at line {line}")?;
+                    }
+                }
+                ("up", "") if selected + 1 < frames.len() => {
+                    selected += 1;
+                    self.show(frames[selected].boundary().line)?;
+                }
+                ("down", "") if selected > 0 => {
+                    selected -= 1;
+                    self.show(frames[selected].boundary().line)?;
+                }
+                ("up" | "down", "") => writeln!(self.output, "No frame {command} from here.")?,
+                ("p" | "print", rest) if !rest.is_empty() => {
+                    if let Some(source) = self.parse(rest)? {
+                        match frames[selected].evaluate(interpreter, source.expr) {
+                            Ok(value) => writeln!(self.output, "{value}")?,
+                            Err(err) => writeln!(self.output, "Error: {err}")?,
+                        }
+                    }
+                }
+                ("l" | "list", "") => self.list(frames[selected].boundary().line)?,
+                ("captures", "") => {
+                    let captures = frames[selected].captures();
+                    if captures.is_empty() {
+                        writeln!(self.output, "No captured variables.")?;
+                    }
+                    for (name, value) in captures {
+                        writeln!(self.output, "{name} = {value}")?;
+                    }
+                }
+                _ => writeln!(
+                    self.output,
+                    "Commands: where, up, down, print expr, list, captures, quit."
+                )?,
+            }
+        }
+    }
+
+    /// `break N` or `break N if condition`.
+    fn add_breakpoint(&mut self, spec: &str) -> io::Result<()> {
+        let (line, condition) = match spec.split_once(" if ") {
+            Some((line, condition)) => (line.trim(), Some(condition.trim())),
+            None => (spec, None),
+        };
+        let Ok(line) = line.parse::<usize>() else {
+            return writeln!(self.output, "Expect a line number after 'break'.");
+        };
+        let Some(&line) = self.statement_lines.range(line..).next() else {
+            return writeln!(self.output, "No statement at or after line {line}.");
+        };
+
+        let condition = match condition {
+            Some(condition) => match self.parse(condition)? {
+                Some(condition) => Some(condition),
+                None => return Ok(()),
+            },
+            None => None,
+        };
+        writeln!(self.output, "Breakpoint at line {line}.")?;
+        self.breakpoints.insert(line, condition);
+        Ok(())
+    }
+
+    /// `text` parsed as an expression, or `None` after reporting why it
+    /// isn't one.
+    fn parse(&mut self, text: &str) -> io::Result<Option<Source>> {
+        match Parser::new(Scanner::new(text)).parse_expression() {
+            Ok(expr) => Ok(Some(Source {
+                text: text.to_owned(),
+                expr,
+            })),
+            Err(err) => {
+                writeln!(self.output, "{err}")?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn show(&mut self, line: usize) -> io::Result<()> {
+        let text = self.lines.get(line - 1).map_or("", |text| text.trim());
+        writeln!(self.output, "[line {line}] {text}")
+    }
+
+    /// The five lines before and after `line`, marking it.
+    fn list(&mut self, line: usize) -> io::Result<()> {
+        let first = line.saturating_sub(5).max(1);
+        for (at, text) in self.lines.iter().enumerate().skip(first - 1).take(11) {
+            let marker = if at + 1 == line { "->" } else { "  " };
+            writeln!(self.output, "{marker} {:>4} {text}", at + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead, W: Write> DebugHook for Debugger<R, W> {
+    fn before_statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        boundary: Boundary,
+        depth: usize,
+    ) {
+        let result = self
+            .should_stop(interpreter, boundary, depth)
+            .and_then(|stop| {
+                if stop {
+                    self.stopped = Some((boundary, depth));
+                    self.prompt(interpreter, boundary, depth)?;
+                }
+                Ok(())
+            });
+
+        if result.is_err() {
+            // Nobody is listening anymore: let the script finish.
+            self.detached = true;
+        }
+    }
+
+    fn on_error(&mut self, interpreter: &mut Interpreter, error: &Error, failure: &Failure) {
+        if self.detached {
+            return;
+        }
+        let _ = self.post_mortem(interpreter, error, failure);
+    }
+}
@@ -0,0 +1,139 @@
+//! Dialects: Lox as spelled by a particular course or fork.
+//!
+//! A [`Dialect`] holds the keyword table the scanner uses. The standard one
+//! is the book's; others rename keywords, say `fn` for `fun`, or drop them so
+//! their spelling is an ordinary identifier. Later chapters of the book, and
+//! many forks, make `print` such an identifier, naming a function instead of
+//! starting a statement:
+//!
+//! ```
+//! use jlox::{dialect::Dialect, token::TokenType};
+//!
+//! let dialect = Dialect::default().with_keyword(TokenType::Fun, "fn");
+//! assert_eq!(dialect.keyword("fn"), Some(TokenType::Fun));
+//! assert_eq!(dialect.keyword("fun"), None);
+//!
+//! let dialect = Dialect::default().with_print_function();
+//! assert_eq!(dialect.keyword("print"), None);
+//! ```
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use phf::phf_map;
+
+use crate::token::TokenType;
+
+type TT = TokenType;
+
+static KEYWORDS: phf::Map<&'static str, TT> = phf_map! {
+    "and" => TT::And,
+    "break" => TT::Break,
+    "class" => TT::Class,
+    "continue" => TT::Continue,
+    "else" => TT::Else,
+    "false" => TT::False,
+    "for" => TT::For,
+    "fun" => TT::Fun,
+    "if" => TT::If,
+    "import" => TT::Import,
+    "match" => TT::Match,
+    "nil" => TT::Nil,
+    "or" => TT::Or,
+    "print" => TT::Print,
+    "return" => TT::Return,
+    "super" => TT::Super,
+    "this" => TT::This,
+    "true" => TT::True,
+    "var" => TT::Var,
+    "while" => TT::While,
+};
+
+static STANDARD: LazyLock<Dialect> = LazyLock::new(Dialect::default);
+
+/// The keyword `word` is in standard Lox, e.g. to name the keyword a
+/// dialect respells.
+pub fn standard_keyword(word: &str) -> Option<TokenType> {
+    KEYWORDS.get(word).copied()
+}
+
+/// The language a source is scanned and parsed as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialect {
+    /// Spelling of every keyword.
+    keywords: HashMap<String, TokenType>,
+    print_function: bool,
+    semicolon_inference: bool,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            keywords: KEYWORDS
+                .entries()
+                .map(|(spelling, keyword)| (spelling.to_string(), *keyword))
+                .collect(),
+            print_function: false,
+            semicolon_inference: false,
+        }
+    }
+}
+
+impl Dialect {
+    /// Standard Lox, which [`Scanner::new`](crate::scanner::Scanner::new)
+    /// scans.
+    pub fn standard() -> &'static Self {
+        &STANDARD
+    }
+
+    /// The keyword spelled `word`, or `None` if it is an identifier.
+    pub fn keyword(&self, word: &str) -> Option<TokenType> {
+        self.keywords.get(word).copied()
+    }
+
+    /// Spells `keyword` as `spelling` instead of its current spelling, which
+    /// becomes an identifier. `spelling` only takes effect if it scans as an
+    /// identifier.
+    pub fn with_keyword(mut self, keyword: TokenType, spelling: &str) -> Self {
+        self.keywords.retain(|_, other| *other != keyword);
+        self.keywords.insert(spelling.to_owned(), keyword);
+        self
+    }
+
+    /// Drops `keyword`, so its spelling is an identifier.
+    pub fn without_keyword(mut self, keyword: TokenType) -> Self {
+        self.keywords.retain(|_, other| *other != keyword);
+        self
+    }
+
+    /// Replaces the `print` statement with a variadic `print` function:
+    /// `print(a, b);` prints `a` and `b` on one line.
+    pub fn with_print_function(mut self) -> Self {
+        self.print_function = true;
+        self.without_keyword(TokenType::Print)
+    }
+
+    /// Whether sessions in this dialect define the `print` function.
+    pub fn print_function(&self) -> bool {
+        self.print_function
+    }
+
+    /// Lets a line break end a statement that is complete without its `;`,
+    /// as can a `}` or the end of input:
+    ///
+    /// ```text
+    /// var greeting = "hi"
+    /// write(greeting)
+    /// ```
+    ///
+    /// As in JavaScript, a line that can continue the expression before it
+    /// does: one starting with `(`, `[`, `.` or an operator joins the line
+    /// above, and `return` alone on its line returns `nil`.
+    pub fn with_semicolon_inference(mut self) -> Self {
+        self.semicolon_inference = true;
+        self
+    }
+
+    pub fn semicolon_inference(&self) -> bool {
+        self.semicolon_inference
+    }
+}
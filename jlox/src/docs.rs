@@ -0,0 +1,335 @@
+//! `jlox doc`: API documentation generated from the `///` comments of the
+//! top-level functions and classes in Lox sources.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::{
+    ast::{Expr, Stmt},
+    parse_source, parser, program,
+};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    /// Reported as `jlox` reports the parse errors of the files it runs.
+    #[error("{}", program::parse_diagnostic(path, error, true))]
+    Parse { path: PathBuf, error: parser::Error },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// A documented function or method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDoc {
+    pub name: String,
+    pub params: Vec<String>,
+    pub doc: Option<String>,
+}
+
+/// A documented class and its methods, in declaration order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDoc {
+    pub name: String,
+    pub superclass: Option<String>,
+    pub doc: Option<String>,
+    pub methods: Vec<FunctionDoc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Function(FunctionDoc),
+    Class(ClassDoc),
+}
+
+/// The documented items of one source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    /// Path relative to the documented root, e.g. `geometry/point.lox`.
+    pub path: PathBuf,
+    pub items: Vec<Item>,
+}
+
+fn function_doc(stmt: &Stmt) -> Option<FunctionDoc> {
+    let Stmt::Function {
+        name, params, doc, ..
+    } = stmt
+    else {
+        return None;
+    };
+
+    Some(FunctionDoc {
+        name: name.lexeme.clone(),
+        params: params.iter().map(|p| p.lexeme.clone()).collect(),
+        doc: doc.clone(),
+    })
+}
+
+/// Collects the top-level functions and classes of `statements`.
+pub fn collect(statements: &[Stmt]) -> Vec<Item> {
+    statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Function { .. } => function_doc(stmt).map(Item::Function),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+                ..
+            } => Some(Item::Class(ClassDoc {
+                name: name.lexeme.clone(),
+                superclass: match superclass {
+                    Some(Expr::Variable { name, .. }) => Some(name.lexeme.clone()),
+                    _ => None,
+                },
+                doc: doc.clone(),
+                methods: methods.iter().filter_map(function_doc).collect(),
+            })),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses every `.lox` file under `root` (or `root` itself if it is a file),
+/// sorted by path.
+pub fn scan(root: &Path) -> Result<Vec<Module>, Error> {
+    let mut modules = Vec::new();
+    for file in sources(root)? {
+        let source = fs::read_to_string(&file).map_err(io_error(&file))?;
+        let statements = parse_source(&source).map_err(|error| Error::Parse {
+            path: file.clone(),
+            error,
+        })?;
+
+        let path = match file.strip_prefix(root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+            _ => PathBuf::from(file.file_name().unwrap_or(file.as_os_str())),
+        };
+
+        modules.push(Module {
+            path,
+            items: collect(&statements),
+        });
+    }
+
+    Ok(modules)
+}
+
+/// The `.lox` files under `root`, or `root` itself if it is a file, sorted.
+pub(crate) fn sources(root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    if root.is_dir() {
+        lox_files(root, &mut files).map_err(io_error(root))?;
+    } else {
+        files.push(root.to_path_buf());
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> Error {
+    let path = path.to_path_buf();
+    move |error| Error::Io { path, error }
+}
+
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            lox_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes one page per module plus an index to `out`, returning the pages
+/// written.
+pub fn generate(root: &Path, out: &Path, format: Format) -> Result<Vec<PathBuf>, Error> {
+    let modules = scan(root)?;
+
+    let mut written = Vec::new();
+    for module in &modules {
+        let page = out.join(module.path.with_extension(format.extension()));
+        if let Some(parent) = page.parent() {
+            fs::create_dir_all(parent).map_err(io_error(parent))?;
+        }
+        fs::write(&page, render(module, format)).map_err(io_error(&page))?;
+        written.push(page);
+    }
+
+    fs::create_dir_all(out).map_err(io_error(out))?;
+    let index = out.join(format!("index.{}", format.extension()));
+    fs::write(&index, render_index(&modules, format)).map_err(io_error(&index))?;
+    written.push(index);
+
+    Ok(written)
+}
+
+fn signature(function: &FunctionDoc) -> String {
+    format!("{}({})", function.name, function.params.join(", "))
+}
+
+fn class_heading(class: &ClassDoc) -> String {
+    match &class.superclass {
+        Some(superclass) => format!("class {} > {superclass}", class.name),
+        None => format!("class {}", class.name),
+    }
+}
+
+/// Renders the page for one module.
+pub fn render(module: &Module, format: Format) -> String {
+    match format {
+        Format::Markdown => markdown(module),
+        Format::Html => html(module),
+    }
+}
+
+fn markdown(module: &Module) -> String {
+    let mut page = format!("# {}\n", module.path.display());
+
+    let doc = |page: &mut String, doc: &Option<String>| {
+        if let Some(doc) = doc {
+            page.push_str(&format!("\n{doc}\n"));
+        }
+    };
+
+    for item in &module.items {
+        match item {
+            Item::Function(function) => {
+                page.push_str(&format!("\n## `fun {}`\n", signature(function)));
+                doc(&mut page, &function.doc);
+            }
+            Item::Class(class) => {
+                page.push_str(&format!("\n## `{}`\n", class_heading(class)));
+                doc(&mut page, &class.doc);
+
+                for method in &class.methods {
+                    page.push_str(&format!("\n### `{}.{}`\n", class.name, signature(method)));
+                    doc(&mut page, &method.doc);
+                }
+            }
+        }
+    }
+
+    page
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html(module: &Module) -> String {
+    let title = escape(&module.path.display().to_string());
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    let doc = |page: &mut String, doc: &Option<String>| {
+        if let Some(doc) = doc {
+            for paragraph in doc.split("\n\n") {
+                page.push_str(&format!("<p>{}</p>\n", escape(paragraph)));
+            }
+        }
+    };
+
+    for item in &module.items {
+        match item {
+            Item::Function(function) => {
+                page.push_str(&format!(
+                    "<h2 id=\"{}\"><code>fun {}</code></h2>\n",
+                    escape(&function.name),
+                    escape(&signature(function))
+                ));
+                doc(&mut page, &function.doc);
+            }
+            Item::Class(class) => {
+                page.push_str(&format!(
+                    "<h2 id=\"{}\"><code>{}</code></h2>\n",
+                    escape(&class.name),
+                    escape(&class_heading(class))
+                ));
+                doc(&mut page, &class.doc);
+
+                for method in &class.methods {
+                    page.push_str(&format!(
+                        "<h3 id=\"{}.{}\"><code>{}.{}</code></h3>\n",
+                        escape(&class.name),
+                        escape(&method.name),
+                        escape(&class.name),
+                        escape(&signature(method))
+                    ));
+                    doc(&mut page, &method.doc);
+                }
+            }
+        }
+    }
+
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+/// Renders the index page linking every module.
+pub fn render_index(modules: &[Module], format: Format) -> String {
+    let link = |module: &Module| {
+        module
+            .path
+            .with_extension(format.extension())
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+
+    match format {
+        Format::Markdown => {
+            let mut page = "# API documentation\n\n".to_string();
+            for module in modules {
+                page.push_str(&format!(
+                    "- [{}]({})\n",
+                    module.path.display(),
+                    link(module)
+                ));
+            }
+            page
+        }
+        Format::Html => {
+            let mut page = "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>API documentation</title></head>\n<body>\n<h1>API documentation</h1>\n<ul>\n".to_string();
+            for module in modules {
+                page.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    escape(&link(module)),
+                    escape(&module.path.display().to_string())
+                ));
+            }
+            page.push_str("</ul>\n</body>\n</html>\n");
+            page
+        }
+    }
+}
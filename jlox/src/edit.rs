@@ -0,0 +1,87 @@
+//! Changes to source text, as the tools that rewrite scripts make them:
+//! `jlox fmt` and `jlox lint --fix`.
+//!
+//! ```
+//! use jlox::edit::{apply, Edit};
+//!
+//! let edits = [Edit::replace(4..5, "_x"), Edit::insert(9, ";")];
+//! assert_eq!(apply("var x = 1", &edits), ("var _x = 1;".to_string(), 2));
+//! ```
+
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{docs, parser};
+
+/// The bytes in `span` replaced by `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn replace(span: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            span,
+            text: text.into(),
+        }
+    }
+
+    pub fn insert(at: usize, text: impl Into<String>) -> Self {
+        Self::replace(at..at, text)
+    }
+}
+
+/// `source` with `edits` made, and how many were. An edit overlapping one
+/// made already, or outside `source`, is skipped.
+pub fn apply(source: &str, edits: &[Edit]) -> (String, usize) {
+    let mut edits: Vec<&Edit> = edits.iter().collect();
+    edits.sort_by_key(|edit| (edit.span.start, edit.span.end));
+
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+    let mut made = 0;
+    for edit in edits {
+        let Range { start, end } = edit.span;
+        if start < copied || end > source.len() || start > end {
+            continue;
+        }
+        if !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+            continue;
+        }
+        result.push_str(&source[copied..start]);
+        result.push_str(&edit.text);
+        copied = end;
+        made += 1;
+    }
+    result.push_str(&source[copied..]);
+    (result, made)
+}
+
+/// Runs `rewrite` over every `.lox` file under `root`, returning the files
+/// it changes. They're written back unless `check` is set.
+pub fn rewrite_files(
+    root: &Path,
+    check: bool,
+    rewrite: impl Fn(&str) -> Result<String, parser::Error>,
+) -> Result<Vec<PathBuf>, docs::Error> {
+    let mut changed = Vec::new();
+    for file in docs::sources(root)? {
+        let source = fs::read_to_string(&file).map_err(docs::io_error(&file))?;
+        let rewritten = rewrite(&source).map_err(|error| docs::Error::Parse {
+            path: file.clone(),
+            error,
+        })?;
+        if rewritten != source {
+            if !check {
+                fs::write(&file, rewritten).map_err(docs::io_error(&file))?;
+            }
+            changed.push(file);
+        }
+    }
+    Ok(changed)
+}
@@ -1,9 +1,8 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::{cell::Cell, collections::HashMap};
 
 use crate::object::Object;
 use crate::token::Token;
+use crate::types::{Gc, GcCell};
 
 use thiserror::Error;
 
@@ -16,25 +15,60 @@ pub enum Error {
     EnclosingError,
 }
 
+thread_local! {
+    static CREATED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Environments created on this thread so far, for [`crate::stats`].
+pub fn created() -> usize {
+    CREATED.with(Cell::get)
+}
+
 #[derive(Debug)]
 pub struct Environment {
-    pub values: HashMap<String, Rc<Object>>,
-    pub enclosing: Option<Rc<RefCell<Environment>>>,
+    pub values: HashMap<String, Gc<Object>>,
+    pub enclosing: Option<Gc<GcCell<Environment>>>,
+    /// Bumped by every definition and by assignments replacing a function
+    /// or class, so call sites can cache the callees they find here.
+    version: u64,
 }
 
 impl Environment {
-    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
+    pub fn new(enclosing: Option<Gc<GcCell<Environment>>>) -> Self {
+        Self::reusing(HashMap::new(), enclosing)
+    }
+
+    /// An environment keeping its variables in `values`, an empty map
+    /// whose capacity is left from an earlier one.
+    pub fn reusing(
+        values: HashMap<String, Gc<Object>>,
+        enclosing: Option<Gc<GcCell<Environment>>>,
+    ) -> Self {
+        debug_assert!(values.is_empty());
+        CREATED.with(|created| created.set(created.get() + 1));
         Self {
-            values: HashMap::new(),
+            values,
             enclosing,
+            version: 0,
         }
     }
 
-    pub fn define(&mut self, name: String, value: Rc<Object>) {
+    pub fn define(&mut self, name: String, value: Gc<Object>) {
+        self.version += 1;
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: &str) -> Result<Rc<Object>, Error> {
+    pub fn undefine(&mut self, name: &str) -> Option<Gc<Object>> {
+        self.version += 1;
+        self.values.remove(name)
+    }
+
+    /// Changes whenever a callee found here may have been replaced.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, name: &str) -> Result<Gc<Object>, Error> {
         if self.values.contains_key(name) {
             return Ok(self.values.get(name).unwrap().clone());
         }
@@ -47,9 +81,12 @@ impl Environment {
             name: name.to_string(),
         })
     }
-    pub fn assign(&mut self, name: Token, value: Rc<Object>) -> Result<(), Error> {
-        if self.values.contains_key(&name.lexeme) {
-            self.values.insert(name.lexeme, value);
+    pub fn assign(&mut self, name: Token, value: Gc<Object>) -> Result<(), Error> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            if matches!(**slot, Object::Function(_) | Object::Class(_)) {
+                self.version += 1;
+            }
+            *slot = value;
             return Ok(());
         }
 
@@ -61,16 +98,17 @@ impl Environment {
         Err(Error::UndefinedVariable { name: name.lexeme })
     }
 
-    pub fn get_at(&self, distance: usize, name: &str) -> Result<Rc<Object>, Error> {
+    pub fn get_at(&self, distance: usize, name: &str) -> Result<Gc<Object>, Error> {
         if distance == 0 {
-            return Ok(self.get(name)?);
+            self.get(name)
         } else {
             let ancestor = self.ancestor(distance)?;
-            return Ok(ancestor.borrow_mut().get(name)?);
+            let value = ancestor.borrow_mut().get(name);
+            value
         }
     }
 
-    fn ancestor(&self, distance: usize) -> Result<Rc<RefCell<Self>>, Error> {
+    fn ancestor(&self, distance: usize) -> Result<Gc<GcCell<Self>>, Error> {
         if let Some(enclosing) = &self.enclosing {
             let mut env = enclosing.clone();
 
@@ -93,7 +131,7 @@ impl Environment {
         &mut self,
         distance: usize,
         name: Token,
-        value: Rc<Object>,
+        value: Gc<Object>,
     ) -> Result<(), Error> {
         if distance == 0 {
             self.assign(name, value)?;
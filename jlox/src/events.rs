@@ -0,0 +1,210 @@
+//! Deferred callbacks and asynchronous natives.
+//!
+//! Scripts never block on host work: an async native takes a callback as its
+//! last argument and returns immediately. The host finishes the work wherever
+//! it likes (a thread pool, a tokio task) and hands the result to the
+//! [`Completion`], which is `Send`. The interpreter runs the callback as
+//! `callback(error, value)` the next time its event loop is pumped.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
+
+use crate::{
+    functions::Callable,
+    host::IntoLox,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    types::{host_fn, Gc, MaybeSync},
+};
+
+type Resolution = Result<Box<dyn FnOnce() -> Object + Send>, String>;
+
+/// The handle an async native uses to report its result. Dropping it without
+/// resolving reports an error to the script.
+pub struct Completion {
+    id: usize,
+    sender: Option<Sender<(usize, Resolution)>>,
+}
+
+impl Completion {
+    pub fn resolve<T: IntoLox + Send + 'static>(mut self, value: T) {
+        self.send(Ok(Box::new(move || value.into_lox())));
+    }
+
+    pub fn reject(mut self, msg: impl Into<String>) {
+        self.send(Err(msg.into()));
+    }
+
+    fn send(&mut self, resolution: Resolution) {
+        if let Some(sender) = self.sender.take() {
+            // The interpreter may already be gone, in which case nobody is
+            // waiting for the result.
+            let _ = sender.send((self.id, resolution));
+        }
+    }
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        self.send(Err("native finished without a result.".to_string()));
+    }
+}
+
+/// Callbacks that are ready to run, and those still waiting on a
+/// [`Completion`].
+pub struct EventLoop {
+    ready: VecDeque<(Gc<Object>, Vec<Gc<Object>>)>,
+    waiting: HashMap<usize, Gc<Object>>,
+    next_id: usize,
+    sender: Sender<(usize, Resolution)>,
+    receiver: Mutex<Receiver<(usize, Resolution)>>,
+}
+
+impl EventLoop {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Self {
+            ready: VecDeque::new(),
+            waiting: HashMap::new(),
+            next_id: 0,
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// Queues `callback(arguments...)` to run on the next turn of the loop.
+    pub fn defer(&mut self, callback: Gc<Object>, arguments: Vec<Gc<Object>>) {
+        self.ready.push_back((callback, arguments));
+    }
+
+    /// Registers `callback` to run once the returned completion is resolved.
+    pub fn completion(&mut self, callback: Gc<Object>) -> Completion {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiting.insert(id, callback);
+
+        Completion {
+            id,
+            sender: Some(self.sender.clone()),
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.ready.is_empty() && self.waiting.is_empty()
+    }
+
+    /// Takes the next callback to run. With `wait`, blocks until an
+    /// outstanding completion resolves if nothing is ready yet.
+    pub fn next(&mut self, wait: bool) -> Option<(Gc<Object>, Vec<Gc<Object>>)> {
+        let receiver = self.receiver.lock().expect("event loop lock poisoned.");
+        let mut resolved: Vec<_> = receiver.try_iter().collect();
+        if wait && resolved.is_empty() && self.ready.is_empty() && !self.waiting.is_empty() {
+            resolved.extend(receiver.recv().ok());
+        }
+        drop(receiver);
+
+        for (id, resolution) in resolved {
+            let Some(callback) = self.waiting.remove(&id) else {
+                continue;
+            };
+            let arguments = match resolution {
+                Ok(value) => vec![Gc::new(Object::Nil), Gc::new(value())],
+                Err(msg) => vec![Gc::new(Object::String(msg)), Gc::new(Object::Nil)],
+            };
+            self.ready.push_back((callback, arguments));
+        }
+
+        self.ready.pop_front()
+    }
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn expect_callable(native: &str, value: &Gc<Object>) -> Result<(), Error> {
+    match &**value {
+        Object::Function(_) | Object::Class(_) => Ok(()),
+        other => Err(Error::NativeError {
+            name: native.to_string(),
+            msg: format!("expected a function, got {other}."),
+        }),
+    }
+}
+
+/// `defer(fn)`: runs `fn()` after the current script (or callback) finishes.
+pub struct Defer;
+
+impl Callable for Defer {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        mut arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let callback = arguments.remove(0);
+        expect_callable("defer", &callback)?;
+
+        interpreter.events_mut().defer(callback, Vec::new());
+        Ok(interpreter.nil())
+    }
+}
+
+host_fn!(AsyncFn = Fn(&mut Interpreter, Vec<Gc<Object>>, Completion) -> Result<(), Error>);
+
+/// A host function whose result arrives later through a [`Completion`].
+/// Scripts pass a `fun (error, value)` callback after the regular arguments.
+pub struct AsyncNative {
+    name: String,
+    arity: usize,
+    function: AsyncFn,
+}
+
+impl AsyncNative {
+    pub fn new<F>(name: &str, arity: usize, function: F) -> Self
+    where
+        F: Fn(&mut Interpreter, Vec<Gc<Object>>, Completion) -> Result<(), Error>
+            + MaybeSync
+            + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl Callable for AsyncNative {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        self.arity + 1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        mut arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let callback = arguments.pop().unwrap_or_default();
+        expect_callable(&self.name, &callback)?;
+
+        let completion = interpreter.events_mut().completion(callback);
+        (self.function)(interpreter, arguments, completion)?;
+        Ok(interpreter.nil())
+    }
+}
@@ -0,0 +1,398 @@
+//! A C ABI for embedding the interpreter from C, C++ or any language with a
+//! foreign function interface. `include/lox.h` declares it.
+//!
+//! Everything goes through opaque handles: a `LoxVm` is a session and a
+//! `LoxValue` one runtime value. Handles returned to the caller are owned by
+//! it and released with `lox_free` and `lox_value_free`. The arguments a
+//! native callback receives are borrowed for the duration of the call, and
+//! the handle it returns is taken over by the interpreter.
+
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    ptr,
+};
+
+use crate::{
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    types::{Gc, Number},
+    Lox, LoxError,
+};
+
+/// `lox_run` succeeded.
+pub const LOX_OK: c_int = 0;
+/// The source didn't scan, parse or resolve, as exit code 65 of `jlox`.
+pub const LOX_COMPILE_ERROR: c_int = 65;
+/// The source failed while running, as exit code 70 of `jlox`.
+pub const LOX_RUNTIME_ERROR: c_int = 70;
+
+/// An interpreter session and the message of its last error.
+pub struct LoxVm {
+    lox: Lox,
+    error: Option<CString>,
+}
+
+impl LoxVm {
+    fn fail(&mut self, code: c_int, message: impl ToString) -> c_int {
+        self.error = CString::new(message.to_string().replace('\0', " ")).ok();
+        code
+    }
+}
+
+/// A value handed across the boundary. Strings keep a NUL-terminated copy
+/// so `lox_value_as_string` can lend it out.
+pub struct LoxValue {
+    object: Gc<Object>,
+    text: Option<CString>,
+    /// Set by `lox_value_error`: returned from a native, it fails the call.
+    error: Option<String>,
+}
+
+impl LoxValue {
+    fn new(object: Gc<Object>) -> Self {
+        let text = match &*object {
+            Object::String(s) => CString::new(s.as_str()).ok(),
+            _ => None,
+        };
+        Self {
+            object,
+            text,
+            error: None,
+        }
+    }
+
+    fn into_raw(self) -> *mut LoxValue {
+        Box::into_raw(Box::new(self))
+    }
+}
+
+/// What a `LoxValue` holds.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoxType {
+    Nil = 0,
+    Bool = 1,
+    Number = 2,
+    String = 3,
+    /// Functions, classes, instances, collections and host values.
+    Other = 4,
+}
+
+/// A native function implemented in C. `args` holds `count` borrowed
+/// handles; the returned handle (NULL for nil) is owned by the interpreter.
+pub type LoxNativeFn = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    args: *const *const LoxValue,
+    count: usize,
+) -> *mut LoxValue;
+
+struct CNative {
+    name: String,
+    arity: usize,
+    callback: LoxNativeFn,
+    user_data: *mut c_void,
+}
+
+// SAFETY: the embedder registering a callback is responsible for it and its
+// user data being usable from whichever thread runs the interpreter.
+#[cfg(feature = "sync")]
+unsafe impl Send for CNative {}
+#[cfg(feature = "sync")]
+unsafe impl Sync for CNative {}
+
+impl Callable for CNative {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let arguments: Vec<LoxValue> = arguments.into_iter().map(LoxValue::new).collect();
+        let pointers: Vec<*const LoxValue> = arguments.iter().map(ptr::from_ref).collect();
+
+        // SAFETY: the pointers stay valid until `arguments` is dropped, after
+        // the call, and the callback's contract is the embedder's.
+        let result = unsafe { (self.callback)(self.user_data, pointers.as_ptr(), pointers.len()) };
+        if result.is_null() {
+            return Ok(interpreter.nil());
+        }
+
+        // SAFETY: non-NULL results are handles made by a `lox_value_*`
+        // constructor, which the interpreter now owns.
+        let result = unsafe { Box::from_raw(result) };
+        match result.error {
+            Some(msg) => Err(Error::NativeError {
+                name: self.name.clone(),
+                msg,
+            }),
+            None => Ok(result.object),
+        }
+    }
+}
+
+/// A string argument, or `None` if it is NULL or not utf-8.
+///
+/// # Safety
+///
+/// `text` must be NULL or point to a NUL-terminated string.
+unsafe fn str_argument<'a>(text: *const c_char) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    CStr::from_ptr(text).to_str().ok()
+}
+
+/// Creates a session. Free it with `lox_free`.
+#[no_mangle]
+pub extern "C" fn lox_new() -> *mut LoxVm {
+    Box::into_raw(Box::new(LoxVm {
+        lox: Lox::new(),
+        error: None,
+    }))
+}
+
+/// Frees a session made by `lox_new`. NULL is ignored.
+///
+/// # Safety
+///
+/// `vm` must be NULL or a session from `lox_new` not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lox_free(vm: *mut LoxVm) {
+    if !vm.is_null() {
+        drop(Box::from_raw(vm));
+    }
+}
+
+/// Runs `source` and any callbacks it schedules. Returns `LOX_OK`,
+/// `LOX_COMPILE_ERROR` or `LOX_RUNTIME_ERROR`; `lox_get_error` describes
+/// the failure.
+///
+/// # Safety
+///
+/// `vm` must be a live session and `source` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_run(vm: *mut LoxVm, source: *const c_char) -> c_int {
+    let vm = &mut *vm;
+    vm.error = None;
+
+    let Some(source) = str_argument(source) else {
+        return vm.fail(LOX_COMPILE_ERROR, "Source must be a utf-8 string.");
+    };
+
+    match vm.lox.execute(source) {
+        Ok(()) => (),
+        Err(err @ LoxError::Runtime(_)) => return vm.fail(LOX_RUNTIME_ERROR, err),
+        Err(err) => return vm.fail(LOX_COMPILE_ERROR, err),
+    }
+
+    let events = vm.lox.interpreter.borrow_mut().run_events(true);
+    if let Err(err) = events {
+        return vm.fail(LOX_RUNTIME_ERROR, LoxError::Runtime(err));
+    }
+
+    LOX_OK
+}
+
+/// The message of the last failed `lox_run`, or NULL if it succeeded. The
+/// string stays valid until the next `lox_run` or `lox_free`.
+///
+/// # Safety
+///
+/// `vm` must be a live session.
+#[no_mangle]
+pub unsafe extern "C" fn lox_get_error(vm: *const LoxVm) -> *const c_char {
+    (*vm)
+        .error
+        .as_ref()
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// Defines the global function `name`, calling `callback` with `user_data`
+/// and `arity` arguments. Returns `LOX_OK`, or -1 if `name` isn't utf-8.
+///
+/// # Safety
+///
+/// `vm` must be a live session and `name` a NUL-terminated string.
+/// `callback` is called with `user_data` for as long as the session lives.
+#[no_mangle]
+pub unsafe extern "C" fn lox_register_fn(
+    vm: *mut LoxVm,
+    name: *const c_char,
+    arity: usize,
+    callback: LoxNativeFn,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(name) = str_argument(name) else {
+        return -1;
+    };
+
+    (*vm).lox.define_native(
+        name,
+        CNative {
+            name: name.to_owned(),
+            arity,
+            callback,
+            user_data,
+        },
+    );
+    LOX_OK
+}
+
+/// The global `name`, or NULL if it isn't defined.
+///
+/// # Safety
+///
+/// `vm` must be a live session and `name` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_get_global(vm: *const LoxVm, name: *const c_char) -> *mut LoxValue {
+    let Some(name) = str_argument(name) else {
+        return ptr::null_mut();
+    };
+
+    match (*vm).lox.interpreter.borrow().get_global(name) {
+        Some(value) => LoxValue::new(value).into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Defines or overwrites the global `name` with a copy of `value`.
+///
+/// # Safety
+///
+/// `vm` must be a live session, `name` a NUL-terminated string and `value`
+/// a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn lox_set_global(
+    vm: *mut LoxVm,
+    name: *const c_char,
+    value: *const LoxValue,
+) -> c_int {
+    let Some(name) = str_argument(name) else {
+        return -1;
+    };
+
+    (*vm)
+        .lox
+        .interpreter
+        .borrow_mut()
+        .define_host_global(name, (*value).object.clone());
+    LOX_OK
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_nil() -> *mut LoxValue {
+    LoxValue::new(Gc::new(Object::Nil)).into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_bool(value: bool) -> *mut LoxValue {
+    LoxValue::new(Gc::new(Object::Bool(value))).into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_number(value: f64) -> *mut LoxValue {
+    LoxValue::new(Gc::new(Object::Number(Number(value)))).into_raw()
+}
+
+/// A copy of `text`, or NULL if it isn't utf-8.
+///
+/// # Safety
+///
+/// `text` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_string(text: *const c_char) -> *mut LoxValue {
+    match str_argument(text) {
+        Some(text) => LoxValue::new(Gc::new(Object::String(text.to_owned()))).into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// A value that, returned from a native callback, fails the call with
+/// `message` as a runtime error.
+///
+/// # Safety
+///
+/// `message` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_error(message: *const c_char) -> *mut LoxValue {
+    let message = if message.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(message).to_string_lossy().into_owned()
+    };
+
+    LoxValue {
+        error: Some(message),
+        ..LoxValue::new(Gc::new(Object::Nil))
+    }
+    .into_raw()
+}
+
+/// Frees a handle the caller owns. NULL is ignored.
+///
+/// # Safety
+///
+/// `value` must be NULL or an owned handle not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_free(value: *mut LoxValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_type(value: *const LoxValue) -> LoxType {
+    match &*(*value).object {
+        Object::Nil => LoxType::Nil,
+        Object::Bool(_) => LoxType::Bool,
+        Object::Number(_) => LoxType::Number,
+        Object::String(_) => LoxType::String,
+        _ => LoxType::Other,
+    }
+}
+
+/// The boolean `value` holds, or false for any other type.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_bool(value: *const LoxValue) -> bool {
+    matches!(*(*value).object, Object::Bool(true))
+}
+
+/// The number `value` holds, or NaN for any other type.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_number(value: *const LoxValue) -> f64 {
+    match *(*value).object {
+        Object::Number(n) => n.0,
+        _ => f64::NAN,
+    }
+}
+
+/// The string `value` holds, or NULL for any other type or a string with a
+/// NUL byte in it. Valid as long as the handle.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_string(value: *const LoxValue) -> *const c_char {
+    (*value)
+        .text
+        .as_ref()
+        .map_or(ptr::null(), |text| text.as_ptr())
+}
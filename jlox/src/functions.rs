@@ -1,28 +1,52 @@
 use std::{
-    cell::RefCell,
+    collections::HashSet,
     fmt::Display,
-    rc::Rc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
-    ast::Stmt,
+    ast::{Expr, Flow, Stmt, StmtId},
     class::Instance,
+    collections::Map,
     environment::Environment,
-    interpreter::{Error, Interpreter},
+    host::IntoLox,
+    interpreter::{Error, Interpreter, Slots},
     object::Object,
+    tasks::Message,
+    types::{Gc, GcCell, MaybeSync},
 };
 
-pub trait Callable {
+pub trait Callable: MaybeSync {
     type E;
 
     fn call(
         &self,
         interpreter: &mut Interpreter,
-        arguments: Vec<Rc<Object>>,
-    ) -> Result<Rc<Object>, Self::E>;
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Self::E>;
 
     fn arity(&self) -> usize;
+
+    /// How many of the last parameters callers may leave out. Only natives
+    /// have optional parameters; they see fewer arguments when they are.
+    fn optional(&self) -> usize {
+        0
+    }
+
+    /// Whether callers may pass any number of arguments after the first
+    /// `arity()`. Only natives are variadic; they see every argument.
+    fn variadic(&self) -> bool {
+        false
+    }
+
+    /// The doc comment written above the declaration, if any.
+    fn doc(&self) -> Option<&str> {
+        None
+    }
+
+    fn as_lox_function(&self) -> Option<&LoxFunction> {
+        None
+    }
 }
 
 impl std::fmt::Debug for dyn Callable<E = Error> {
@@ -42,14 +66,17 @@ impl Callable for Clock {
 
     fn call(
         &self,
-        _interpreter: &mut Interpreter,
-        _arguments: Vec<Rc<Object>>,
-    ) -> Result<Rc<Object>, Error> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        Ok(Rc::new(Object::Number(now as f64)))
+        interpreter: &mut Interpreter,
+        _arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let now = interpreter.inputs_mut().provide("clock", || {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+            Ok(Message::Number(now as f64))
+        })?;
+        Ok(Gc::new(now.into_lox()))
     }
 }
 
@@ -59,21 +86,83 @@ impl std::fmt::Debug for Clock {
     }
 }
 
+/// `doc(value)`: the documentation of a function or class, or nil.
+pub struct Doc;
+
+impl Callable for Doc {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        match interpreter.doc_of(&arguments[0]) {
+            Some(doc) => Ok(Gc::new(Object::String(doc))),
+            None => Ok(interpreter.nil()),
+        }
+    }
+}
+
+/// `closureInfo(fn)`: a map from the names of the variables a function
+/// declared in Lox captures to their current values.
+pub struct ClosureInfo;
+
+impl Callable for ClosureInfo {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let function = match &*arguments[0] {
+            Object::Function(function) => function.as_lox_function(),
+            _ => None,
+        };
+        let Some(function) = function else {
+            return Err(Error::NativeError {
+                name: "closureInfo".to_owned(),
+                msg: format!("expected a function declared in Lox, got {}.", arguments[0]),
+            });
+        };
+
+        let mut captures = Map::new();
+        for (name, value) in function.captures() {
+            // Strings always hash.
+            let _ = captures.insert(Gc::new(Object::String(name)), value);
+        }
+        Ok(Gc::new(Object::Map(captures)))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LoxFunction {
     name: String,
-    closure: Rc<RefCell<Environment>>,
+    closure: Gc<GcCell<Environment>>,
     params: Vec<String>,
-    body: Rc<Vec<Stmt>>,
+    body: Gc<Vec<Stmt>>,
     is_initializer: bool,
+    doc: Option<Gc<str>>,
+    /// The declaration, when the resolver found the parameters can be kept
+    /// in slots.
+    slotted: Option<StmtId>,
 }
 
 impl LoxFunction {
     pub fn new(
         name: String,
-        closure: Rc<RefCell<Environment>>,
+        closure: Gc<GcCell<Environment>>,
         params: Vec<String>,
-        body: Rc<Vec<Stmt>>,
+        body: Gc<Vec<Stmt>>,
         is_initializer: bool,
     ) -> Self {
         Self {
@@ -82,19 +171,102 @@ impl LoxFunction {
             params,
             body,
             is_initializer,
+            doc: None,
+            slotted: None,
         }
     }
 
-    pub fn bind(&self, instance: Rc<RefCell<Instance>>) -> Self {
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc.map(Gc::from);
+        self
+    }
+
+    pub(crate) fn with_slots(mut self, declaration: Option<StmtId>) -> Self {
+        self.slotted = declaration;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &[Stmt] {
+        &self.body
+    }
+
+    pub fn closure(&self) -> &Gc<GcCell<Environment>> {
+        &self.closure
+    }
+
+    /// The variables of enclosing scopes the body refers to, with their
+    /// current values, innermost scope first and sorted by name within
+    /// each. Globals aren't captured, as they're looked up when used; a
+    /// method's `this` is.
+    pub fn captures(&self) -> Vec<(String, Gc<Object>)> {
+        let mut names = HashSet::new();
+        for stmt in self.body.iter() {
+            referenced(stmt, &mut names);
+        }
+        // Parameters and declarations at the top of the body shadow
+        // whatever they're named after.
+        for name in self
+            .params
+            .iter()
+            .map(String::as_str)
+            .chain(self.body.iter().flat_map(|stmt| match stmt {
+                Stmt::Var { name, .. } | Stmt::Function { name, .. } | Stmt::Class { name, .. } => {
+                    vec![name.lexeme.as_str()]
+                }
+                Stmt::Destructure { names, .. } => {
+                    names.iter().map(|n| n.lexeme.as_str()).collect()
+                }
+                _ => Vec::new(),
+            }))
+        {
+            names.remove(name);
+        }
+
+        let mut captures = Vec::new();
+        let mut scope = Some(self.closure.clone());
+        while let Some(current) = scope {
+            let current = current.borrow();
+            // The global scope is the only one without an enclosing scope.
+            if current.enclosing.is_none() {
+                break;
+            }
+            let mut values: Vec<(String, Gc<Object>)> = current
+                .values
+                .iter()
+                .filter(|(name, _)| names.remove(name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            values.sort_by(|(a, _), (b, _)| a.cmp(b));
+            captures.extend(values);
+            scope = current.enclosing.clone();
+        }
+        captures
+    }
+
+    /// The body, which every closure of the declaration shares.
+    pub fn shared_body(&self) -> &Gc<Vec<Stmt>> {
+        &self.body
+    }
+
+    pub fn is_initializer(&self) -> bool {
+        self.is_initializer
+    }
+
+    pub fn bind(&self, instance: Gc<GcCell<Instance>>) -> Self {
         let mut environment = Environment::new(Some(self.closure.clone()));
-        environment.define("this".to_string(), Rc::new(Object::Instance(instance)));
-        Self::new(
-            self.name.clone(),
-            Rc::new(RefCell::new(environment)),
-            self.params.clone(),
-            self.body.clone(),
-            self.is_initializer,
-        )
+        environment.define("this".to_string(), Gc::new(Object::Instance(instance)));
+        Self {
+            closure: Gc::new(GcCell::new(environment)),
+            ..self.clone()
+        }
     }
 }
 
@@ -105,16 +277,36 @@ impl Callable for LoxFunction {
         self.params.len()
     }
 
+    fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    fn as_lox_function(&self) -> Option<&LoxFunction> {
+        Some(self)
+    }
+
     fn call(
         &self,
         interpreter: &mut Interpreter,
-        arguments: Vec<Rc<Object>>,
-    ) -> Result<Rc<Object>, Error> {
-        let environment = Rc::new(RefCell::new(Environment::new(Some(
-            (&self.closure).clone(),
-        ))));
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        if let Some(declaration) = self.slotted.filter(|_| interpreter.slots_enabled()) {
+            // No scope of its own: the body only uses globals and its
+            // parameters, and it's never an initializer.
+            let slots = Slots::new(declaration, arguments);
+            let flow =
+                interpreter.execute_in_slots(slots, self.body.clone(), self.closure.clone())?;
+            return match flow {
+                Flow::Return(value) => Ok(value),
+                Flow::Normal | Flow::Break | Flow::Continue => Ok(interpreter.nil()),
+            };
+        }
 
-        // println!("Before: {environment:?}");
+        let environment = interpreter.call_scope(self.closure.clone());
+        let line = interpreter.line();
+        interpreter.track_scope(&environment, line, || {
+            format!("the scope of a call to '{}'", self.name)
+        });
 
         for (i, arg) in arguments.into_iter().enumerate() {
             environment
@@ -122,30 +314,28 @@ impl Callable for LoxFunction {
                 .define(self.params[i].to_owned(), arg);
         }
 
-        // println!("After: {environment:?}");
-
-        match interpreter.execute_block(self.body.clone(), environment) {
-            Ok(_) => {
-                if self.is_initializer {
-                    self.closure
-                        .borrow()
-                        .get_at(0, "this")
-                        .map_err(|e| Error::EnvironmentError { error: e })
-                } else {
-                    Ok(Rc::new(Object::Nil))
-                }
-            }
-            Err(Error::Return { value }) => {
-                if self.is_initializer {
-                    self.closure
-                        .borrow()
-                        .get_at(0, "this")
-                        .map_err(|e| Error::EnvironmentError { error: e })
-                } else {
-                    Ok(value)
-                }
-            }
-            Err(e) => Err(e),
+        let flow = match self.slotted {
+            // Hides the slots of a call to the same function further out.
+            Some(_) => interpreter.execute_in_slots(
+                Slots::default(),
+                self.body.clone(),
+                environment.clone(),
+            ),
+            None => interpreter.execute_block(self.body.clone(), environment.clone()),
+        }?;
+        interpreter.recycle_scope(environment);
+        if self.is_initializer {
+            return self
+                .closure
+                .borrow()
+                .get_at(0, "this")
+                .map_err(|e| Error::EnvironmentError { error: e });
+        }
+
+        match flow {
+            Flow::Return(value) => Ok(value),
+            // The resolver keeps `break` and `continue` inside loops.
+            Flow::Normal | Flow::Break | Flow::Continue => Ok(interpreter.nil()),
         }
     }
 }
@@ -155,3 +345,32 @@ impl Display for LoxFunction {
         write!(f, "<fn {}>", &self.name)
     }
 }
+
+/// Adds the names of the variables `stmt` reads or assigns, nested
+/// functions included, to `names`.
+fn referenced<'a>(stmt: &'a Stmt, names: &mut HashSet<&'a str>) {
+    fn expression<'a>(expr: &'a Expr, names: &mut HashSet<&'a str>) {
+        match expr {
+            Expr::Variable { name, .. } | Expr::Assign { name, .. } => {
+                names.insert(&name.lexeme);
+            }
+            Expr::This { .. } => {
+                names.insert("this");
+            }
+            Expr::Super { .. } => {
+                names.insert("super");
+            }
+            _ => {}
+        }
+        for child in expr.children() {
+            expression(child, names);
+        }
+    }
+
+    for expr in stmt.expressions() {
+        expression(expr, names);
+    }
+    for child in stmt.children() {
+        referenced(child, names);
+    }
+}
@@ -0,0 +1,656 @@
+//! Exposing Rust types to Lox as classes.
+//!
+//! A [`ClassBuilder`] describes how scripts construct a `T`, which fields are
+//! readable (and writable) properties and which methods can be called. Argument
+//! and return values are converted through [`FromLox`] and [`IntoLox`].
+//!
+//! ```ignore
+//! let point = ClassBuilder::<Point>::new("Point")
+//!     .constructor(|(x, y): (f64, f64)| Point { x, y })
+//!     .field("x", |p| p.x, |p, x: f64| p.x = x)
+//!     .method("length", |p, (): ()| (p.x * p.x + p.y * p.y).sqrt())
+//!     .build();
+//! lox.register_class(point);
+//! ```
+//!
+//! [`native_fn!`](crate::native_fn) converts the arguments of native
+//! functions the same way.
+
+use std::{any::TypeId, collections::HashMap, fmt::Display, marker::PhantomData};
+
+use crate::{
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    token::Token,
+    types::{host_fn, AnyValue, Gc, GcCell, MaybeSync, Number},
+};
+
+/// Conversion from a Lox value into a Rust argument.
+pub trait FromLox: Sized {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String>;
+}
+
+/// Conversion from a Rust return value into a Lox value.
+pub trait IntoLox {
+    fn into_lox(self) -> Object;
+}
+
+impl FromLox for f64 {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String> {
+        match &**value {
+            Object::Number(n) => Ok(n.0),
+            other => Err(format!("expected a number, got {other}")),
+        }
+    }
+}
+
+impl FromLox for bool {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String> {
+        match &**value {
+            Object::Bool(b) => Ok(*b),
+            other => Err(format!("expected a boolean, got {other}")),
+        }
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String> {
+        match &**value {
+            Object::String(s) => Ok(s.clone()),
+            other => Err(format!("expected a string, got {other}")),
+        }
+    }
+}
+
+impl FromLox for Gc<Object> {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String> {
+        match &**value {
+            Object::Nil => Ok(None),
+            _ => T::from_lox(value).map(Some),
+        }
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> Object {
+        Object::Number(Number(self))
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> Object {
+        Object::Bool(self)
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> Object {
+        Object::String(self)
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> Object {
+        Object::String(self.to_owned())
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> Object {
+        Object::Nil
+    }
+}
+
+impl IntoLox for Object {
+    fn into_lox(self) -> Object {
+        self
+    }
+}
+
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> Object {
+        self.map_or(Object::Nil, IntoLox::into_lox)
+    }
+}
+
+/// A tuple of [`FromLox`] arguments with a fixed arity.
+pub trait FromArgs: Sized {
+    const ARITY: usize;
+
+    fn from_args(arguments: &[Gc<Object>]) -> Result<Self, String>;
+}
+
+macro_rules! impl_from_args {
+    ($arity:expr; $($ty:ident $index:tt),*) => {
+        impl<$($ty: FromLox),*> FromArgs for ($($ty,)*) {
+            const ARITY: usize = $arity;
+
+            #[allow(unused_variables)]
+            fn from_args(arguments: &[Gc<Object>]) -> Result<Self, String> {
+                Ok(($($ty::from_lox(&arguments[$index])
+                    .map_err(|e| format!("argument {}: {e}", $index + 1))?,)*))
+            }
+        }
+    };
+}
+
+impl_from_args!(0;);
+impl_from_args!(1; A 0);
+impl_from_args!(2; A 0, B 1);
+impl_from_args!(3; A 0, B 1, C 2);
+impl_from_args!(4; A 0, B 1, C 2, D 3);
+impl_from_args!(5; A 0, B 1, C 2, D 3, E 4);
+impl_from_args!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+
+/// Conversion from a Lox value into an argument of a
+/// [`native_fn!`](crate::native_fn), which unlike [`FromLox`] may borrow
+/// from the value, as `&str` does.
+pub trait FromArg<'a>: Sized {
+    fn from_arg(value: &'a Gc<Object>) -> Result<Self, String>;
+}
+
+impl<T: FromLox> FromArg<'_> for T {
+    fn from_arg(value: &Gc<Object>) -> Result<Self, String> {
+        T::from_lox(value)
+    }
+}
+
+impl<'a> FromArg<'a> for &'a str {
+    fn from_arg(value: &'a Gc<Object>) -> Result<Self, String> {
+        match &**value {
+            Object::String(s) => Ok(s),
+            other => Err(format!("expected a string, got {other}")),
+        }
+    }
+}
+
+/// The `index`th argument of the native `name`, converted for its body.
+#[doc(hidden)]
+pub fn native_arg<'a, T: FromArg<'a>>(
+    name: &str,
+    arguments: &'a [Gc<Object>],
+    index: usize,
+) -> Result<T, Error> {
+    T::from_arg(&arguments[index]).map_err(|e| Error::NativeError {
+        name: name.to_owned(),
+        msg: format!("argument {}: {e}", index + 1),
+    })
+}
+
+/// Defines a native function as a unit struct implementing [`Callable`],
+/// with one parameter per typed argument. Arguments are converted through
+/// [`FromArg`] before the body runs, and the body returns a [`IntoLox`]
+/// value or a message; a failed conversion or a message is a runtime error
+/// naming the function.
+///
+/// ```
+/// use jlox::{native_fn, Lox, Value};
+///
+/// native_fn!(Repeat = "repeat", |_interpreter, (text: &str, times: f64)| {
+///     if times < 0.0 {
+///         return Err("times can't be negative".to_owned());
+///     }
+///     Ok(text.repeat(times as usize))
+/// });
+///
+/// let mut lox = Lox::new();
+/// lox.define_native("repeat", Repeat);
+/// assert_eq!(
+///     lox.eval_expression("repeat(\"ab\", 2)").unwrap(),
+///     Value::String("abab".to_owned())
+/// );
+/// assert!(lox.eval_expression("repeat(2, \"ab\")").is_err());
+/// ```
+#[macro_export]
+macro_rules! native_fn {
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident = $lox_name:literal,
+        |$interpreter:pat_param, ($($arg:ident : $ty:ty),* $(,)?)| $body:expr $(,)?
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $crate::functions::Callable for $name {
+            type E = $crate::interpreter::Error;
+
+            fn arity(&self) -> usize {
+                <[&str]>::len(&[$(stringify!($arg)),*])
+            }
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn call(
+                &self,
+                interpreter: &mut $crate::interpreter::Interpreter,
+                arguments: ::std::vec::Vec<$crate::types::Gc<$crate::object::Object>>,
+            ) -> ::std::result::Result<
+                $crate::types::Gc<$crate::object::Object>,
+                $crate::interpreter::Error,
+            > {
+                let mut index = 0;
+                $(
+                    let $arg: $ty = $crate::host::native_arg($lox_name, &arguments, index)?;
+                    index += 1;
+                )*
+                let $interpreter: &mut $crate::interpreter::Interpreter = interpreter;
+                let result = (|| -> ::std::result::Result<_, ::std::string::String> { $body })();
+                result
+                    .map(|value| {
+                        $crate::types::Gc::new($crate::host::IntoLox::into_lox(value))
+                    })
+                    .map_err(|msg| $crate::interpreter::Error::NativeError {
+                        name: $lox_name.to_owned(),
+                        msg,
+                    })
+            }
+        }
+    };
+}
+
+host_fn!(Constructor = Fn(&[Gc<Object>]) -> Result<Box<AnyValue>, String>);
+host_fn!(Getter = Fn(&AnyValue) -> Object);
+host_fn!(Setter = Fn(&mut AnyValue, &Gc<Object>) -> Result<(), String>);
+host_fn!(Method = Fn(&mut Interpreter, &mut AnyValue, Vec<Gc<Object>>) -> Result<Gc<Object>, Error>);
+
+/// A class whose instances wrap a Rust value. It also serves as the method
+/// table of [`UserData`] values handed out by natives.
+pub struct HostClass {
+    name: String,
+    type_name: &'static str,
+    /// The type of every instance's value, which getters and methods rely on.
+    type_id: TypeId,
+    constructor: Option<(usize, Constructor)>,
+    getters: HashMap<String, Getter>,
+    setters: HashMap<String, Setter>,
+    methods: HashMap<String, (usize, Method)>,
+}
+
+impl HostClass {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wraps an existing Rust value so it can be handed to a script. Fails
+    /// unless `T` is the type the class was built for.
+    pub fn instantiate<T: MaybeSync + 'static>(self: &Gc<Self>, value: T) -> Result<Object, Error> {
+        let data = UserData::with_class(self.clone(), value)?;
+        Ok(Object::UserData(Gc::new(data)))
+    }
+
+    fn check_type<T: 'static>(&self) -> Result<(), Error> {
+        if TypeId::of::<T>() == self.type_id {
+            return Ok(());
+        }
+
+        Err(Error::NativeError {
+            name: self.name.clone(),
+            msg: format!(
+                "instances hold {}, not {}.",
+                self.type_name,
+                std::any::type_name::<T>()
+            ),
+        })
+    }
+}
+
+impl std::fmt::Debug for HostClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native class {}>", self.name)
+    }
+}
+
+pub struct ClassBuilder<T> {
+    class: HostClass,
+    marker: PhantomData<T>,
+}
+
+fn downcast<T: 'static>(value: &AnyValue) -> &T {
+    value
+        .downcast_ref()
+        .expect("Host instance holds a value of another type.")
+}
+
+fn downcast_mut<T: 'static>(value: &mut AnyValue) -> &mut T {
+    value
+        .downcast_mut()
+        .expect("Host instance holds a value of another type.")
+}
+
+impl<T: MaybeSync + 'static> ClassBuilder<T> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            class: HostClass {
+                name: name.to_string(),
+                type_name: std::any::type_name::<T>(),
+                type_id: TypeId::of::<T>(),
+                constructor: None,
+                getters: HashMap::new(),
+                setters: HashMap::new(),
+                methods: HashMap::new(),
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// Makes the class callable from Lox. Without a constructor, instances can
+    /// only be created by the host through [`HostClass::instantiate`].
+    pub fn constructor<A, F>(mut self, constructor: F) -> Self
+    where
+        A: FromArgs,
+        F: Fn(A) -> T + MaybeSync + 'static,
+    {
+        self.class.constructor = Some((
+            A::ARITY,
+            Box::new(move |args| Ok(Box::new(constructor(A::from_args(args)?)))),
+        ));
+        self
+    }
+
+    /// A read-only property.
+    pub fn getter<R, G>(mut self, name: &str, getter: G) -> Self
+    where
+        R: IntoLox,
+        G: Fn(&T) -> R + MaybeSync + 'static,
+    {
+        self.class.getters.insert(
+            name.to_string(),
+            Box::new(move |value| getter(downcast(value)).into_lox()),
+        );
+        self
+    }
+
+    /// A property that scripts can both read and assign.
+    pub fn field<R, V, G, S>(self, name: &str, getter: G, setter: S) -> Self
+    where
+        R: IntoLox,
+        V: FromLox,
+        G: Fn(&T) -> R + MaybeSync + 'static,
+        S: Fn(&mut T, V) + MaybeSync + 'static,
+    {
+        let mut builder = self.getter(name, getter);
+        builder.class.setters.insert(
+            name.to_string(),
+            Box::new(move |value, new| {
+                setter(downcast_mut(value), V::from_lox(new)?);
+                Ok(())
+            }),
+        );
+        builder
+    }
+
+    pub fn method<A, R, F>(self, name: &str, method: F) -> Self
+    where
+        A: FromArgs,
+        R: IntoLox,
+        F: Fn(&mut T, A) -> R + MaybeSync + 'static,
+    {
+        self.try_method(name, move |value, args| Ok(method(value, args)))
+    }
+
+    /// A method that can fail with a message reported as a runtime error.
+    pub fn try_method<A, R, F>(self, name: &str, method: F) -> Self
+    where
+        A: FromArgs,
+        R: IntoLox,
+        F: Fn(&mut T, A) -> Result<R, String> + MaybeSync + 'static,
+    {
+        let qualified = format!("{}.{name}", self.class.name);
+
+        self.native_method(name, A::ARITY, move |_, value, args| {
+            let result = A::from_args(&args).and_then(|args| method(value, args));
+
+            result
+                .map(|value| Gc::new(value.into_lox()))
+                .map_err(|msg| Error::NativeError {
+                    name: qualified.clone(),
+                    msg,
+                })
+        })
+    }
+
+    /// A method working directly on Lox values, with access to the
+    /// interpreter (e.g. for permission checks).
+    pub fn native_method<F>(mut self, name: &str, arity: usize, method: F) -> Self
+    where
+        F: Fn(&mut Interpreter, &mut T, Vec<Gc<Object>>) -> Result<Gc<Object>, Error>
+            + MaybeSync
+            + 'static,
+    {
+        self.class.methods.insert(
+            name.to_string(),
+            (
+                arity,
+                Box::new(move |interpreter, value, args| {
+                    method(interpreter, downcast_mut(value), args)
+                }),
+            ),
+        );
+        self
+    }
+
+    pub fn build(self) -> Gc<HostClass> {
+        Gc::new(self.class)
+    }
+}
+
+/// An opaque host value held by a script. Lox code can only pass it around,
+/// unless it carries a [`HostClass`] exposing properties and methods.
+pub struct UserData {
+    type_name: &'static str,
+    class: Option<Gc<HostClass>>,
+    value: GcCell<Box<AnyValue>>,
+}
+
+impl UserData {
+    pub fn new<T: MaybeSync + 'static>(value: T) -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            class: None,
+            value: GcCell::new(Box::new(value)),
+        }
+    }
+
+    /// A value exposed through `class`, which must have been built for `T`.
+    pub fn with_class<T: MaybeSync + 'static>(
+        class: Gc<HostClass>,
+        value: T,
+    ) -> Result<Self, Error> {
+        class.check_type::<T>()?;
+        Ok(Self {
+            class: Some(class),
+            ..Self::new(value)
+        })
+    }
+
+    /// The Rust type name of the wrapped value.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn class(&self) -> Option<&Gc<HostClass>> {
+        self.class.as_ref()
+    }
+
+    /// Whether the wrapped value is a `T`. It's `false` while one of the
+    /// value's methods is running.
+    pub fn is<T: 'static>(&self) -> bool {
+        self.value.try_borrow().is_ok_and(|value| value.is::<T>())
+    }
+
+    /// Runs `f` on the wrapped value if it is a `T` and none of its methods
+    /// is running.
+    pub fn with<T: 'static, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.value.try_borrow().ok()?.downcast_ref().map(f)
+    }
+
+    pub fn with_mut<T: 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.value.try_borrow_mut().ok()?.downcast_mut().map(f)
+    }
+
+    /// Runs `f` on an argument received by a native, reporting a type error
+    /// attributed to `native` when it holds anything other than a `T`.
+    pub fn expect<T: 'static, R>(
+        value: &Object,
+        native: &str,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<R, Error> {
+        let data = match value {
+            Object::UserData(data) => data.with(f),
+            _ => None,
+        };
+
+        data.ok_or_else(|| Error::NativeError {
+            name: native.to_string(),
+            msg: format!("expected {}, got {value}.", std::any::type_name::<T>()),
+        })
+    }
+
+    pub fn get(data: &Gc<UserData>, name: Token) -> Result<Gc<Object>, Error> {
+        let Some(class) = &data.class else {
+            return Err(Error::PropertyAccessError { name });
+        };
+
+        if let Some(getter) = class.getters.get(&name.lexeme) {
+            let Ok(value) = data.value.try_borrow() else {
+                return Err(busy(class, &name.lexeme));
+            };
+            return Ok(Gc::new(getter(&**value)));
+        }
+
+        if class.methods.contains_key(&name.lexeme) {
+            return Ok(Gc::new(Object::Function(Gc::new(BoundHostMethod {
+                data: data.clone(),
+                class: class.clone(),
+                name: name.lexeme,
+            }))));
+        }
+
+        Err(Error::UndefinedProperty { name: name.lexeme })
+    }
+
+    pub fn set(&self, name: Token, value: Gc<Object>) -> Result<(), Error> {
+        let Some(class) = &self.class else {
+            return Err(Error::FieldAccessError { name });
+        };
+
+        let native_error = |msg| Error::NativeError {
+            name: format!("{}.{}", class.name, name.lexeme),
+            msg,
+        };
+
+        let Some(setter) = class.setters.get(&name.lexeme) else {
+            return Err(native_error("property is read-only.".to_string()));
+        };
+
+        let Ok(mut data) = self.value.try_borrow_mut() else {
+            return Err(busy(class, &name.lexeme));
+        };
+        setter(&mut **data, &value).map_err(native_error)
+    }
+}
+
+impl std::fmt::Debug for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Display for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.class {
+            Some(class) => write!(f, "{} instance", class.name),
+            None => write!(f, "<userdata {}>", self.type_name),
+        }
+    }
+}
+
+/// The callable registered under a host class's name.
+pub struct HostConstructor {
+    class: Gc<HostClass>,
+}
+
+impl HostConstructor {
+    pub fn new(class: Gc<HostClass>) -> Self {
+        Self { class }
+    }
+}
+
+impl Callable for HostConstructor {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        self.class
+            .constructor
+            .as_ref()
+            .map_or(0, |(arity, _)| *arity)
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let native_error = |msg| Error::NativeError {
+            name: self.class.name.clone(),
+            msg,
+        };
+
+        let Some((_, constructor)) = &self.class.constructor else {
+            return Err(native_error(
+                "class can't be constructed from Lox.".to_string(),
+            ));
+        };
+
+        let value = constructor(&arguments).map_err(native_error)?;
+
+        Ok(Gc::new(Object::UserData(Gc::new(UserData {
+            type_name: self.class.type_name,
+            class: Some(self.class.clone()),
+            value: GcCell::new(value),
+        }))))
+    }
+}
+
+/// A method that calls back into Lox may reach its own object again. Using
+/// the object from there is an error rather than a second borrow.
+fn busy(class: &HostClass, name: &str) -> Error {
+    Error::NativeError {
+        name: format!("{}.{name}", class.name),
+        msg: "object is in use by one of its methods.".to_string(),
+    }
+}
+
+struct BoundHostMethod {
+    data: Gc<UserData>,
+    class: Gc<HostClass>,
+    name: String,
+}
+
+impl Callable for BoundHostMethod {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        self.class.methods[&self.name].0
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let (_, method) = &self.class.methods[&self.name];
+
+        let Ok(mut value) = self.data.value.try_borrow_mut() else {
+            return Err(busy(&self.class, &self.name));
+        };
+
+        method(interpreter, &mut **value, arguments)
+    }
+}
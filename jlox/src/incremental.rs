@@ -0,0 +1,185 @@
+//! Incremental parsing for tools that parse the same file again after small
+//! edits, such as watch mode or an editor.
+//!
+//! Source is split into top-level declarations and each one is parsed on its
+//! own, cached under its text. After an edit only the declarations whose text
+//! changed are parsed again. Cached trees are kept as if their declaration
+//! started on line 1 and are shifted to their real line when reused, so
+//! inserting lines above a declaration doesn't invalidate it.
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    ast::Stmt,
+    dialect::Dialect,
+    limits::Limits,
+    parser::{self, Parser},
+    scanner::Scanner,
+    token::TokenType,
+};
+
+/// A top-level declaration (or statement) of a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Byte range in the source.
+    pub span: Range<usize>,
+    /// Line the chunk starts on.
+    pub line: usize,
+}
+
+/// Splits `source` after every `;` or `}` that closes a top-level
+/// declaration. A chunk starts at its first token (doc comments included),
+/// so blank lines and plain comments between declarations are left out.
+/// The `}` of a top-level `match` or map literal closes an expression, so a
+/// statement with one only ends at its `;`.
+pub fn split(source: &str, dialect: &Dialect) -> Vec<Chunk> {
+    let mut scanner = Scanner::new(source).with_dialect(dialect);
+    let mut chunks = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    let mut pending = None;
+    let mut in_expression = false;
+
+    let mut line = 1;
+    let mut counted = 0;
+    let mut push = |chunks: &mut Vec<Chunk>, span: Range<usize>| {
+        line += source[counted..span.start].matches('\n').count();
+        counted = span.start;
+        chunks.push(Chunk { span, line });
+    };
+
+    while let Some(token) = scanner.next() {
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+
+        // `if (c) {} else {}` is still one statement.
+        if let Some(end) = pending.take() {
+            if token.token_type != TokenType::Else {
+                if let Some(start) = start.take() {
+                    push(&mut chunks, start..end);
+                }
+                in_expression = false;
+            }
+        }
+        start.get_or_insert(scanner.span().start);
+
+        match token.token_type {
+            TokenType::Match | TokenType::Pound if depth == 0 => in_expression = true,
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => (),
+        }
+
+        if depth == 0
+            && (token.token_type == TokenType::Semicolon
+                || (token.token_type == TokenType::RightBrace && !in_expression))
+        {
+            pending = Some(scanner.span().end);
+        }
+    }
+
+    if let Some(start) = start {
+        push(&mut chunks, start..pending.unwrap_or(source.len()));
+    }
+
+    chunks
+}
+
+/// Parses sources declaration by declaration, reusing the trees of
+/// declarations that didn't change since the previous call.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    entries: HashMap<String, Vec<Stmt>>,
+    reparsed: usize,
+    limits: Limits,
+    dialect: Dialect,
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses `source` into the same statements [`Parser`] would produce.
+    /// Declarations that are no longer present are dropped from the cache.
+    pub fn parse(&mut self, source: &str) -> Result<Vec<Stmt>, parser::Error> {
+        let mut statements = Vec::new();
+        let mut used: HashMap<String, Vec<Stmt>> = HashMap::new();
+        self.reparsed = 0;
+
+        for chunk in split(source, &self.dialect) {
+            let text = &source[chunk.span];
+
+            let parsed = match self.entries.remove(text) {
+                Some(parsed) => parsed,
+                None => match used.get(text) {
+                    Some(parsed) => parsed.clone(),
+                    None => {
+                        self.reparsed += 1;
+                        tracing::trace!(line = chunk.line, "parse cache miss");
+                        match Parser::new(Scanner::new(text).with_dialect(&self.dialect))
+                            .with_dialect(&self.dialect)
+                            .with_limits(self.limits)
+                            .parse()
+                        {
+                            Ok(parsed) => parsed,
+                            Err(mut error) => {
+                                error.shift(chunk.line - 1);
+                                self.entries.extend(used);
+                                return Err(error);
+                            }
+                        }
+                    }
+                },
+            };
+
+            statements.extend(parsed.iter().cloned().map(|mut stmt| {
+                stmt.shift(chunk.line - 1);
+                stmt
+            }));
+            used.insert(text.to_owned(), parsed);
+        }
+
+        self.entries = used;
+        Ok(statements)
+    }
+
+    /// How many declarations the last [`ParseCache::parse`] had to parse
+    /// rather than take from the cache.
+    pub fn reparsed(&self) -> usize {
+        self.reparsed
+    }
+
+    /// Number of cached declarations.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Parses with `limits` from now on. Cached trees were checked against
+    /// the old limits, so the cache is cleared.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.clear();
+    }
+
+    pub fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    /// Parses as `dialect` from now on, clearing the cache.
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+        self.clear();
+    }
+}
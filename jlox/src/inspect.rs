@@ -0,0 +1,208 @@
+//! `inspect(value, depth)`: a value written out for people, as the REPL
+//! shows results.
+//!
+//! Strings are quoted and instances list their fields. Lists, tuples, maps
+//! and instances holding only plain values fit on one line; anything deeper
+//! gets a line per element, indented by two spaces:
+//!
+//! ```text
+//! #{
+//!   "origin": Point { x: 0, y: 0 },
+//!   "tags": #["a", "b"],
+//! }
+//! ```
+//!
+//! Below `depth` levels, collections show as `#[...]`, `#{...}`, `(...)` or
+//! `Point {...}`. An instance that contains itself, directly or not, shows
+//! as `<circular>` where it comes around again.
+
+use crate::{
+    class::Instance,
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    types::{Gc, GcCell},
+};
+
+/// How deep the REPL and `inspect` without a depth go.
+pub const DEFAULT_DEPTH: usize = 4;
+
+/// `value` written out, showing `depth` levels of nesting.
+pub fn inspect(value: &Gc<Object>, depth: usize) -> String {
+    let mut inspector = Inspector {
+        max_depth: depth,
+        path: Vec::new(),
+        out: String::new(),
+    };
+    inspector.write(value, 0);
+    inspector.out
+}
+
+struct Inspector {
+    max_depth: usize,
+    /// The instances being written, outermost first.
+    path: Vec<Gc<GcCell<Instance>>>,
+    out: String,
+}
+
+enum Kind {
+    List,
+    Tuple,
+    Map,
+    Instance(String),
+}
+
+/// What comes before an element.
+enum Key<'a> {
+    None,
+    Field(&'a str),
+    Value(&'a Gc<Object>),
+}
+
+impl Inspector {
+    fn write(&mut self, value: &Gc<Object>, depth: usize) {
+        match &**value {
+            Object::String(s) => self.out.push_str(&format!("\"{s}\"")),
+            Object::List(elements) => {
+                let elements = elements.iter().map(|e| (Key::None, e)).collect();
+                self.write_compound(Kind::List, elements, depth);
+            }
+            Object::Tuple(elements) => {
+                let elements = elements.iter().map(|e| (Key::None, e)).collect();
+                self.write_compound(Kind::Tuple, elements, depth);
+            }
+            Object::Map(map) => {
+                let entries = map.iter().map(|(k, v)| (Key::Value(k), v)).collect();
+                self.write_compound(Kind::Map, entries, depth);
+            }
+            Object::Instance(instance) => {
+                if self.path.iter().any(|seen| Gc::ptr_eq(seen, instance)) {
+                    self.out.push_str("<circular>");
+                    return;
+                }
+
+                let borrowed = instance.borrow();
+                let class = borrowed.class().borrow().name().to_owned();
+                let mut fields: Vec<(String, Gc<Object>)> = borrowed
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                drop(borrowed);
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+                self.path.push(instance.clone());
+                let fields = fields.iter().map(|(name, value)| (Key::Field(name), value));
+                self.write_compound(Kind::Instance(class), fields.collect(), depth);
+                self.path.pop();
+            }
+            _ => self.out.push_str(&value.to_string()),
+        }
+    }
+
+    fn write_compound(&mut self, kind: Kind, elements: Vec<(Key, &Gc<Object>)>, depth: usize) {
+        let (open, close) = match &kind {
+            Kind::List => ("#[".to_owned(), "]"),
+            Kind::Tuple => ("(".to_owned(), ")"),
+            Kind::Map => ("#{".to_owned(), "}"),
+            Kind::Instance(class) => (format!("{class} {{"), "}"),
+        };
+        self.out.push_str(&open);
+        if elements.is_empty() {
+            self.out.push_str(close);
+            return;
+        }
+        if depth >= self.max_depth {
+            self.out.push_str("...");
+            self.out.push_str(close);
+            return;
+        }
+
+        let flat = elements.iter().all(|(_, value)| !is_compound(value));
+        let one = elements.len() == 1;
+        let padding = if matches!(kind, Kind::Instance(_)) && flat {
+            " "
+        } else {
+            ""
+        };
+
+        self.out.push_str(padding);
+        for (i, (key, value)) in elements.into_iter().enumerate() {
+            if !flat {
+                self.out.push('\n');
+                self.out.push_str(&"  ".repeat(depth + 1));
+            } else if i > 0 {
+                self.out.push_str(", ");
+            }
+
+            match key {
+                Key::None => (),
+                Key::Field(name) => self.out.push_str(&format!("{name}: ")),
+                Key::Value(key) => {
+                    self.write(key, depth + 1);
+                    self.out.push_str(": ");
+                }
+            }
+            self.write(value, depth + 1);
+
+            // Every element of a multi-line value ends with a comma, and so
+            // does the element of a tuple of one.
+            if !flat || (one && matches!(kind, Kind::Tuple)) {
+                self.out.push(',');
+            }
+        }
+        if !flat {
+            self.out.push('\n');
+            self.out.push_str(&"  ".repeat(depth));
+        }
+        self.out.push_str(padding);
+        self.out.push_str(close);
+    }
+}
+
+fn is_compound(value: &Object) -> bool {
+    match value {
+        Object::List(elements) | Object::Tuple(elements) => !elements.is_empty(),
+        Object::Map(map) => !map.is_empty(),
+        Object::Instance(_) => true,
+        _ => false,
+    }
+}
+
+/// `inspect(value, depth)`: `value` written out as the REPL shows it, with
+/// `depth` levels of nesting, or [`DEFAULT_DEPTH`] without it.
+pub struct Inspect;
+
+impl Callable for Inspect {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn optional(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let depth = match arguments.get(1).map(|depth| &**depth) {
+            None => Some(DEFAULT_DEPTH),
+            Some(Object::Number(n)) => n.as_int().and_then(|i| usize::try_from(i).ok()),
+            Some(_) => None,
+        };
+        let Some(depth) = depth else {
+            return Err(Error::NativeError {
+                name: "inspect".to_string(),
+                msg: "depth must be a whole number of at least 0.".to_string(),
+            });
+        };
+
+        let text = inspect(&arguments[0], depth);
+        interpreter.check_string_length("inspect", text.len())?;
+        Ok(Gc::new(Object::String(text)))
+    }
+}
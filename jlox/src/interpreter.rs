@@ -1,170 +1,1622 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 use thiserror::Error;
 
-use crate::ast::{Expr, ExprVisitor, Literal, Stmt, StmtVisitor};
-use crate::class::Class;
-use crate::environment::Environment;
-use crate::functions::{Callable, Clock, LoxFunction};
+use crate::ast::{
+    Boundary, Expr, ExprVisitor, Flow, Literal, MatchArm, NodeId, NodeMap, Pattern, Signature,
+    Stmt, StmtId, StmtVisitor,
+};
+use crate::class::{Class, Instance};
+use crate::collections::{self, Map};
+use crate::dialect::Dialect;
+use crate::environment::{self, Environment};
+use crate::events::{AsyncNative, Completion, EventLoop};
+use crate::functions::{Callable, LoxFunction};
+use crate::host::{HostClass, HostConstructor, UserData};
+use crate::leaks::{self, Leak};
+use crate::limits::Limits;
+use crate::math::{self, MathMode};
+use crate::natives::{NativeInfo, Registry, BUILTINS, HOST_MODULE};
 use crate::object::Object;
+use crate::permissions::{Capability, Permissions};
+use crate::replay::Inputs;
+use crate::settings::Settings;
+use crate::stack;
+use crate::stats::{CountingAllocator, Stats};
+use crate::streams::{self, OutputStream, Streams};
+use crate::tasks::{Message, TaskGroup, TaskOutput};
 use crate::token::{Token, TokenType};
-
-pub type Number = f64;
+use crate::types::{host_fn, Gc, GcCell, MaybeSync, Number};
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Unsupported operation between {op} and {right:?}")]
-    UnsupportedUnaryOp { op: Token, right: Rc<Object> },
+    UnsupportedUnaryOp { op: Token, right: Gc<Object> },
 
     #[error("Unsupported addition between {left:?} and {right:?}")]
-    UnsupportedAddOp { left: Rc<Object>, right: Rc<Object> },
+    UnsupportedAddOp { left: Gc<Object>, right: Gc<Object> },
 
     #[error("Unsupported operation: {left:?} {op} {right:?}")]
     UnsupportedBinaryOp {
-        left: Rc<Object>,
+        left: Gc<Object>,
+        op: Token,
+        right: Gc<Object>,
+    },
+
+    #[error("Cast conversion failed: {value} is not a number")]
+    NaN { value: String },
+
+    #[error("Division by zero")]
+    ZeroDivision,
+
+    #[error("Variable '{}' is read before it is assigned [line {}].", .name.lexeme, .name.line())]
+    Uninitialized { name: Token },
+
+    #[error("{left} {} {right} is {result} [line {}]", .op.lexeme, .op.line())]
+    NonFiniteResult {
+        left: Number,
         op: Token,
-        right: Rc<Object>,
+        right: Number,
+        result: Number,
+    },
+
+    #[error("Environment error: {error:?}")]
+    EnvironmentError { error: crate::environment::Error },
+
+    #[error("Object is not callable: {obj:?}")]
+    NotCallable { obj: Gc<Object> },
+
+    #[error("Expected {arity} arguments but got {size} in call to '{callee}'{}", at_line(.line))]
+    ArityError {
+        callee: String,
+        arity: usize,
+        size: usize,
+        /// Line of the call's closing parenthesis, unless the host made the call.
+        line: Option<usize>,
+    },
+
+    #[error("{name} Only instances have properties.")]
+    PropertyAccessError { name: Token },
+
+    #[error("{name} Tuple of {size} elements has no such field.")]
+    TupleIndex { name: Token, size: usize },
+
+    #[error("Only tuples and lists can be destructured, got {value} [line {}].", paren.line())]
+    NotDestructurable { paren: Token, value: Gc<Object> },
+
+    #[error("Expected {expected} values to destructure but got {got} [line {}].", paren.line())]
+    UnpackArity {
+        paren: Token,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("No match arm for {value} [line {}].", keyword.line())]
+    NoMatch { keyword: Token, value: Gc<Object> },
+
+    #[error("Only lists and maps can be indexed, got {value} [line {}].", bracket.line())]
+    NotIndexable { bracket: Token, value: Gc<Object> },
+
+    #[error("{kind} index must be a whole number below {len}, got {index} [line {}].", bracket.line())]
+    IndexOutOfRange {
+        bracket: Token,
+        kind: &'static str,
+        index: Gc<Object>,
+        len: usize,
+    },
+
+    #[error("Key {key} is not in the map [line {}].", bracket.line())]
+    MissingKey { bracket: Token, key: Gc<Object> },
+
+    #[error("{key} can't be a map key [line {}].", brace.line())]
+    UnhashableKey { brace: Token, key: Gc<Object> },
+
+    #[error("Can't modify {value}, lists, maps and bytes are frozen [line {}].", bracket.line())]
+    Frozen { bracket: Token, value: Gc<Object> },
+
+    #[error("{class}.hashCode() must return a number, got {value}.")]
+    BadHashCode { class: String, value: Gc<Object> },
+
+    #[error("Undefined property '{name}'")]
+    UndefinedProperty { name: String },
+
+    #[error("{name} Only instances have fields.")]
+    FieldAccessError { name: Token },
+
+    #[error("{stmt:?} is not a function statement.")]
+    MethodNotFunction { stmt: Box<Stmt> },
+
+    #[error("{name}: Superclass must be a class.")]
+    SuperClassNotClass { name: Token },
+
+    #[error("'{}' is not an ancestor of this class [line {}].", ancestor.lexeme, ancestor.line())]
+    NotAnAncestor { ancestor: Token },
+
+    #[error("{name}: {msg}")]
+    NativeError { name: String, msg: String },
+
+    #[error("Stack overflow.")]
+    StackOverflow,
+
+    #[error("Time limit of {limit:?} exceeded.")]
+    TimeLimit { limit: std::time::Duration },
+
+    #[error("Memory limit of {limit} bytes exceeded.")]
+    MemoryLimit { limit: usize },
+
+    #[error("{op} Strings can't be longer than {limit} bytes.")]
+    StringTooLong { op: Token, limit: usize },
+
+    #[error("{native}: requires the '{capability}' capability (run with --allow-{capability}).")]
+    PermissionDenied {
+        native: String,
+        capability: Capability,
     },
 
-    #[error("Cast conversion failed: {value} is not a number")]
-    NaN { value: String },
+    #[error("'import' is only supported when running a file or source through Lox [line {}].", keyword.line())]
+    ImportUnsupported { keyword: Token },
+}
+
+/// How a statement run by the interpreter finished.
+pub type ControlFlow = Flow<Gc<Object>>;
+
+/// `left + right` for a string `left`. Nothing else holds an intermediate
+/// like `a + b` in `a + b + c`, so its buffer is grown in place.
+fn concat(left: Gc<Object>, right: &str) -> Gc<Object> {
+    match Gc::try_unwrap(left) {
+        Ok(Object::String(mut s)) => {
+            s.push_str(right);
+            Gc::new(Object::String(s))
+        }
+        Ok(other) => Gc::new(Object::String(format!("{other}{right}"))),
+        Err(left) => Gc::new(Object::String(format!("{left}{right}"))),
+    }
+}
+
+/// Whether `value` matches `pattern`, collecting what the pattern binds.
+fn match_pattern(
+    pattern: &Pattern,
+    value: &Gc<Object>,
+    bindings: &mut Vec<(String, Gc<Object>)>,
+) -> bool {
+    match (pattern, &**value) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Binding(name), _) => {
+            bindings.push((name.lexeme.clone(), value.clone()));
+            true
+        }
+        (Pattern::Literal(Literal::Number(n)), Object::Number(m)) => n == m,
+        (Pattern::Literal(Literal::String(s)), Object::String(t)) => s == t,
+        (Pattern::Literal(Literal::Bytes(a)), Object::Bytes(b)) => **a == **b,
+        (Pattern::Literal(Literal::True), Object::Bool(b)) => *b,
+        (Pattern::Literal(Literal::False), Object::Bool(b)) => !*b,
+        (Pattern::Literal(Literal::Nil), Object::Nil) => true,
+        (Pattern::Range { low, high }, Object::Number(n)) => low <= n && n < high,
+        (Pattern::Tuple(patterns), Object::Tuple(elements)) => {
+            patterns.len() == elements.len()
+                && patterns
+                    .iter()
+                    .zip(elements)
+                    .all(|(pattern, element)| match_pattern(pattern, element, bindings))
+        }
+        _ => false,
+    }
+}
+
+fn at_line(line: &Option<usize>) -> String {
+    match line {
+        Some(line) => format!(" [line {line}]"),
+        None => ".".to_string(),
+    }
+}
+
+impl Object {
+    /// `nil` and `false` are falsey, everything else is truthy. Instances can
+    /// override this through [`Interpreter::is_truthy`].
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Nil => false,
+            Self::Bool(b) => *b,
+            _ => true,
+        }
+    }
+
+    pub fn n(&self) -> Result<Number, Error> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            _ => Err(Error::NaN {
+                value: self.to_string(),
+            }),
+        }
+    }
+}
+
+/// How top-level declarations are scoped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TopLevel {
+    /// Every top-level `var`, `fun` and `class` defines a global, as the
+    /// REPL needs so later lines can use it.
+    #[default]
+    Interactive,
+    /// A file's top level runs like the body of an implicit `main`: its
+    /// declarations are locals, read through resolved slots instead of by
+    /// name, and are gone once the file finishes. As in any block, a
+    /// function can only call functions declared before it. Only the file
+    /// being run is affected, not the files it imports.
+    Script,
+}
+
+/// Watches execution from the host, e.g. [`crate::debugger::Debugger`].
+pub trait DebugHook {
+    /// Called before each statement on the statement-boundary table runs, and
+    /// at a loop's header again before every iteration after the first.
+    /// `depth` is the number of calls in progress. The hook is unset while
+    /// it runs, so it can evaluate code without being called back.
+    fn before_statement(&mut self, interpreter: &mut Interpreter, boundary: Boundary, depth: usize);
+
+    /// Called when `error` is about to end a run, with the calls that were
+    /// in progress where it was raised.
+    fn on_error(&mut self, _interpreter: &mut Interpreter, _error: &Error, _failure: &Failure) {}
+}
+
+/// A call in progress, with what its caller was running.
+#[derive(Clone)]
+struct Call {
+    callee: Gc<Object>,
+    environment: Gc<GcCell<Environment>>,
+    boundary: Boundary,
+}
+
+/// Where a runtime error was raised, kept for post-mortem debugging.
+pub struct Failure {
+    frames: Vec<Frame>,
+}
+
+/// A function that was running when an error was raised, or the script.
+pub struct Frame {
+    name: String,
+    /// The function called, or `None` for the script.
+    callee: Option<Gc<Object>>,
+    boundary: Boundary,
+    environment: Gc<GcCell<Environment>>,
+}
+
+impl Failure {
+    /// The innermost frame, where the error was raised, first.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+}
+
+impl Frame {
+    /// The function's name, or `script` for the top level.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The statement that was running in this frame.
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// The variables the frame's function captures, as
+    /// [`LoxFunction::captures`] gives them, or none for the script and
+    /// natives.
+    pub fn captures(&self) -> Vec<(String, Gc<Object>)> {
+        match self.callee.as_deref() {
+            Some(Object::Function(function)) => function
+                .as_lox_function()
+                .map(LoxFunction::captures)
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Evaluates `expr` in this frame's environment, like
+    /// [`Interpreter::evaluate_here`].
+    pub fn evaluate(&self, interpreter: &mut Interpreter, expr: Expr) -> Result<Gc<Object>, Error> {
+        let previous = std::mem::replace(&mut interpreter.environment, self.environment.clone());
+        let result = interpreter.evaluate_here(expr);
+        interpreter.environment = previous;
+        result
+    }
+}
+
+host_fn!(Hook = DebugHook);
+
+/// How many emptied variable maps are kept for calls to reuse.
+const SCOPE_POOL_SIZE: usize = 64;
+
+/// The capacity above which a map is dropped instead of pooled, so a call
+/// with many locals doesn't hold on to its memory for good.
+const MAX_POOLED_CAPACITY: usize = 32;
+
+/// The most parameters a function can keep in slots.
+pub(crate) const MAX_SLOTS: usize = 8;
+
+/// The arguments of the call running now, when its function keeps its
+/// parameters in slots instead of a scope of its own. The resolver allows
+/// that for functions that declare nothing and capture nothing, so only
+/// their own body ever refers to the parameters.
+#[derive(Default)]
+pub(crate) struct Slots {
+    /// The declaration of the function. `None` while any other call runs.
+    owner: Option<StmtId>,
+    values: [Option<Gc<Object>>; MAX_SLOTS],
+}
+
+impl Slots {
+    pub fn new(owner: StmtId, arguments: Vec<Gc<Object>>) -> Self {
+        let mut slots = Self {
+            owner: Some(owner),
+            ..Self::default()
+        };
+        for (slot, argument) in slots.values.iter_mut().zip(arguments) {
+            *slot = Some(argument);
+        }
+        slots
+    }
+}
+
+pub struct Interpreter {
+    globals: Gc<GcCell<Environment>>,
+    /// How many scopes out each resolved local is, by the expression
+    /// naming it.
+    locals: NodeMap<usize>,
+    /// The functions that keep their parameters in slots, by declaration,
+    /// with their names.
+    slotted: HashMap<StmtId, String>,
+    /// Which function's slot each reference to a slotted parameter reads.
+    slot_refs: NodeMap<(StmtId, usize)>,
+    slots: Slots,
+    /// The global function or class each call site naming one called last,
+    /// with the globals' version then.
+    call_sites: NodeMap<(u64, Gc<Object>)>,
+    /// The instances whose `onSet` is running, by address.
+    setting: HashSet<usize>,
+    environment: Gc<GcCell<Environment>>,
+    permissions: Permissions,
+    limits: Limits,
+    math: MathMode,
+    /// Whether `print` writes values as Lox spells them, `3` rather than
+    /// `Number(3.0)`, as the VM does.
+    print_display: bool,
+    settings: Settings,
+    top_level: TopLevel,
+    /// The dialect of the session, which decides how code the interpreter
+    /// parses itself, such as snapshots and tasks, treats `print`.
+    dialect: Dialect,
+    streams: Streams,
+    natives: Registry,
+    /// Globals defined by the host rather than by scripts, kept by `reset`.
+    host_globals: HashMap<String, Gc<Object>>,
+    /// The names every interpreter starts with: builtin natives, math
+    /// constants and the standard streams.
+    builtins: HashSet<String>,
+    events: EventLoop,
+    inputs: Inputs,
+    call_depth: usize,
+    debug_hook: Option<Hook>,
+    /// The boundary of the statement that started running last.
+    boundary: Boundary,
+    /// Innermost last.
+    calls_in_progress: Vec<Call>,
+    /// Set once the error a statement failed with has been captured in
+    /// `failure`, so the statements it unwinds through leave it alone.
+    unwinding: bool,
+    failure: Option<Failure>,
+    /// Set by [`Interpreter::evaluate_here`]: variables are found by name
+    /// through the current environment, as the resolver hasn't seen them.
+    by_name: bool,
+    /// When the current run started and the thread's heap bytes then, for
+    /// [`Limits::max_duration`] and [`Limits::max_memory`].
+    run_started: Instant,
+    bytes_before_run: isize,
+    /// The run's share of the heap its task group holds, as last reported.
+    bytes_reported: isize,
+    /// The tasks spawned from this run, or the run this task belongs to.
+    tasks: TaskGroup,
+    task_output: TaskOutput,
+    statements: usize,
+    calls: usize,
+    /// `environment::created()` when the interpreter was made.
+    environments_before: usize,
+    /// What has been made since [`Interpreter::enable_leak_check`].
+    leaks: Option<leaks::Registry>,
+    /// The emptied variable maps of the scopes of finished calls, for the
+    /// next calls to fill instead of allocating their own.
+    scope_pool: Vec<HashMap<String, Gc<Object>>>,
+    nil: Gc<Object>,
+    true_value: Gc<Object>,
+    false_value: Gc<Object>,
+    /// The value of variables declared without an initializer when
+    /// [`Settings::forbid_uninitialized_reads`] is on: a `nil` of its own,
+    /// told apart by address.
+    uninitialized: Gc<Object>,
+    #[cfg(feature = "jit")]
+    jit: Option<crate::jit::Jit>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let environments_before = environment::created();
+        let globals = Gc::new(GcCell::new(Environment::new(None)));
+
+        let mut interpreter = Self {
+            globals: globals.clone(),
+            locals: NodeMap::new(),
+            slotted: HashMap::new(),
+            slot_refs: NodeMap::new(),
+            slots: Slots::default(),
+            call_sites: NodeMap::new(),
+            setting: HashSet::new(),
+            environment: globals,
+            permissions: Permissions::new(),
+            limits: Limits::default(),
+            math: MathMode::default(),
+            print_display: false,
+            settings: Settings::default(),
+            top_level: TopLevel::default(),
+            dialect: Dialect::default(),
+            streams: Streams::default(),
+            natives: Registry::new(),
+            host_globals: HashMap::new(),
+            builtins: HashSet::new(),
+            events: EventLoop::new(),
+            inputs: Inputs::live(),
+            call_depth: 0,
+            debug_hook: None,
+            boundary: Boundary::NONE,
+            calls_in_progress: Vec::new(),
+            unwinding: false,
+            failure: None,
+            by_name: false,
+            run_started: Instant::now(),
+            bytes_before_run: CountingAllocator::thread_bytes(),
+            bytes_reported: 0,
+            tasks: TaskGroup::default(),
+            task_output: TaskOutput::default(),
+            statements: 0,
+            calls: 0,
+            environments_before,
+            leaks: None,
+            scope_pool: Vec::new(),
+            nil: Gc::new(Object::Nil),
+            true_value: Gc::new(Object::Bool(true)),
+            false_value: Gc::new(Object::Bool(false)),
+            #[cfg(feature = "jit")]
+            jit: None,
+            uninitialized: Gc::new(Object::Nil),
+        };
+
+        for builtin in BUILTINS {
+            let native = (builtin.make)();
+            interpreter
+                .natives
+                .register(builtin.info().with_arity(native.arity(), native.variadic()));
+            interpreter.define_host_global(builtin.name, Gc::new(Object::Function(native)));
+        }
+
+        for (name, value) in math::CONSTANTS {
+            interpreter.define_host_global(name, Gc::new(Object::Number(Number(*value))));
+        }
+
+        for (name, stream) in streams::globals() {
+            interpreter.define_host_global(name, Gc::new(stream));
+        }
+
+        interpreter.builtins = interpreter.host_globals.keys().cloned().collect();
+        interpreter
+    }
+
+    /// Whether `value` is still the builtin every interpreter binds to
+    /// `name`, rather than something a script or the host put there.
+    pub fn is_builtin(&self, name: &str, value: &Gc<Object>) -> bool {
+        self.builtins.contains(name)
+            && self
+                .host_globals
+                .get(name)
+                .is_some_and(|builtin| Gc::ptr_eq(builtin, value))
+    }
+
+    /// The names of the globals the host defined: natives, classes and
+    /// values.
+    pub fn host_globals(&self) -> impl Iterator<Item = &str> {
+        self.host_globals.keys().map(String::as_str)
+    }
+
+    /// Defines or overwrites the global `name` on behalf of the host, so
+    /// `reset` brings it back.
+    pub fn define_host_global(&mut self, name: &str, value: Gc<Object>) {
+        self.host_globals.insert(name.to_owned(), value.clone());
+        self.globals.borrow_mut().define(name.to_owned(), value);
+    }
+
+    /// Forgets everything scripts defined, leaving only the natives,
+    /// classes and values the host defined. Pending callbacks are dropped too;
+    /// permissions and replay inputs are kept.
+    pub fn reset(&mut self) {
+        let mut globals = Environment::new(None);
+        for (name, value) in &self.host_globals {
+            globals.define(name.clone(), value.clone());
+        }
+
+        self.globals = Gc::new(GcCell::new(globals));
+        self.environment = self.globals.clone();
+        self.locals.clear();
+        self.slotted.clear();
+        self.slot_refs.clear();
+        self.slots = Slots::default();
+        self.call_sites.clear();
+        self.events = EventLoop::new();
+        self.call_depth = 0;
+        self.calls_in_progress.clear();
+    }
+
+    /// Statements executed, calls made and environments created by this
+    /// interpreter. Allocation counts are left for the caller to fill in.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            statements: self.statements,
+            calls: self.calls,
+            environments: environment::created() - self.environments_before,
+            ..Stats::default()
+        }
+    }
+
+    /// Registers the scopes and instances made from now on, for
+    /// [`Interpreter::leaks`].
+    pub fn enable_leak_check(&mut self) {
+        self.leaks.get_or_insert_with(leaks::Registry::default);
+    }
+
+    /// The scopes and instances made since the leak check was enabled that
+    /// only cycles keep alive. Meant for when the program has finished;
+    /// while a call runs, its scope can't be reached from the globals.
+    pub fn leaks(&self) -> Vec<Leak> {
+        self.leaks
+            .as_ref()
+            .map_or_else(Vec::new, |registry| registry.leaks(&self.globals))
+    }
+
+    /// Registers `scope`, made at `line` for what `what` describes, with
+    /// the leak check if it's on.
+    pub(crate) fn track_scope(
+        &mut self,
+        scope: &Gc<GcCell<Environment>>,
+        line: usize,
+        what: impl FnOnce() -> String,
+    ) {
+        if let Some(registry) = &mut self.leaks {
+            registry.scope(scope, what(), line);
+        }
+    }
+
+    /// The line of the statement running now.
+    pub(crate) fn line(&self) -> usize {
+        self.boundary.line
+    }
+
+    /// A scope for a call, enclosed by `enclosing`, with a pooled variable
+    /// map if there is one.
+    pub(crate) fn call_scope(
+        &mut self,
+        enclosing: Gc<GcCell<Environment>>,
+    ) -> Gc<GcCell<Environment>> {
+        let values = self.scope_pool.pop().unwrap_or_default();
+        Gc::new(GcCell::new(Environment::reusing(values, Some(enclosing))))
+    }
+
+    /// Takes back the scope of a call that has returned. Unless something
+    /// still holds it, like a closure declared in the call, its variables
+    /// are dropped and the map goes back to the pool.
+    pub(crate) fn recycle_scope(&mut self, scope: Gc<GcCell<Environment>>) {
+        if self.scope_pool.len() >= SCOPE_POOL_SIZE {
+            return;
+        }
+        let Ok(scope) = Gc::try_unwrap(scope) else {
+            return;
+        };
+        let mut values = scope.into_inner().values;
+        if values.capacity() <= MAX_POOLED_CAPACITY {
+            values.clear();
+            self.scope_pool.push(values);
+        }
+    }
+
+    /// Registers `instance` with the leak check if it's on.
+    pub(crate) fn track_instance(&mut self, instance: &Gc<GcCell<Instance>>) {
+        if let Some(registry) = &mut self.leaks {
+            registry.instance(instance, self.boundary.line);
+        }
+    }
+
+    /// The shared `nil` value. Use it instead of allocating a new one.
+    pub fn nil(&self) -> Gc<Object> {
+        self.nil.clone()
+    }
+
+    /// The shared `true` or `false` value.
+    pub fn bool(&self, value: bool) -> Gc<Object> {
+        if value {
+            self.true_value.clone()
+        } else {
+            self.false_value.clone()
+        }
+    }
+
+    /// Defines a host function as a global.
+    pub fn define_native(&mut self, name: &str, native: impl Callable<E = Error> + 'static) {
+        self.define_native_with(NativeInfo::new(name, HOST_MODULE), native);
+    }
+
+    /// Defines a host function as a global, described by `info`.
+    pub fn define_native_with(
+        &mut self,
+        info: NativeInfo,
+        native: impl Callable<E = Error> + 'static,
+    ) {
+        let name = info.name().to_owned();
+        self.natives
+            .register(info.with_arity(native.arity(), native.variadic()));
+        self.define_host_global(&name, Gc::new(Object::Function(Gc::new(native))));
+    }
+
+    /// Metadata for every native defined in this interpreter.
+    pub fn natives(&self) -> &Registry {
+        &self.natives
+    }
+
+    /// Documentation for `value`: the doc comment of a Lox function or class,
+    /// or the registry entry of a native.
+    pub fn doc_of(&self, value: &Gc<Object>) -> Option<String> {
+        match &**value {
+            Object::Function(function) => match function.doc() {
+                Some(doc) => Some(doc.to_owned()),
+                None => self
+                    .native_info(value)
+                    .map(|native| native.doc().to_owned())
+                    .filter(|doc| !doc.is_empty()),
+            },
+            Object::Class(klass) => klass.borrow().doc().map(str::to_owned),
+            _ => None,
+        }
+    }
+
+    /// The registry entry of the native `value`, if it is one still defined
+    /// under its registered name.
+    fn native_info(&self, value: &Gc<Object>) -> Option<&NativeInfo> {
+        self.natives.iter().find(|native| {
+            self.globals
+                .borrow()
+                .values
+                .get(native.name())
+                .is_some_and(|global| Gc::ptr_eq(global, value))
+        })
+    }
+
+    /// The name `callee` was declared or registered with, for error messages.
+    fn callee_name(&self, callee: &Gc<Object>) -> String {
+        match &**callee {
+            Object::Function(function) => match function.as_lox_function() {
+                Some(function) => function.name().to_owned(),
+                None => self
+                    .native_info(callee)
+                    .map_or("<native fn>".to_owned(), |native| native.name().to_owned()),
+            },
+            Object::Class(klass) => klass.borrow().name().to_owned(),
+            _ => callee.to_string(),
+        }
+    }
+
+    /// The value of the global `name`, if it is defined.
+    pub fn get_global(&self, name: &str) -> Option<Gc<Object>> {
+        self.globals.borrow().values.get(name).cloned()
+    }
+
+    /// Defines or overwrites the global `name`, e.g. to pass configuration
+    /// to a script before running it.
+    pub fn set_global(&mut self, name: &str, value: Gc<Object>) {
+        self.globals.borrow_mut().define(name.to_owned(), value);
+    }
+
+    /// Fails unless the capability the registry lists for `native` has been
+    /// granted. Natives without a registered capability always pass.
+    pub fn check_native(&self, native: &str) -> Result<(), Error> {
+        match self.natives.get(native).and_then(NativeInfo::capability) {
+            Some(capability) => self.permissions.check(capability, native),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if `callee` is a native registered with a capability that
+    /// hasn't been granted. Only natives that need one are looked up, so
+    /// calls to the others stay cheap.
+    fn check_capability(&self, callee: &Gc<Object>) -> Result<(), Error> {
+        let globals = self.globals.borrow();
+        for native in self.natives.iter() {
+            let Some(capability) = native.capability() else {
+                continue;
+            };
+            if globals
+                .values
+                .get(native.name())
+                .is_some_and(|global| Gc::ptr_eq(global, callee))
+            {
+                return self.permissions.check(capability, native.name());
+            }
+        }
+        Ok(())
+    }
+
+    /// Defines a host function that completes asynchronously. Scripts call
+    /// it with a trailing `fun (error, value)` callback, run once
+    /// `function` resolves its [`Completion`].
+    pub fn define_async_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&mut Interpreter, Vec<Gc<Object>>, Completion) -> Result<(), Error>
+            + MaybeSync
+            + 'static,
+    {
+        self.define_native(name, AsyncNative::new(name, arity, function));
+    }
+
+    /// Defines a Rust-backed class as a global, callable by its name.
+    pub fn register_class(&mut self, class: Gc<HostClass>) {
+        let name = class.name().to_owned();
+        self.define_host_global(
+            &name,
+            Gc::new(Object::Function(Gc::new(HostConstructor::new(class)))),
+        );
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    pub fn permissions_mut(&mut self) -> &mut Permissions {
+        &mut self.permissions
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Fails if `native` would return a string of `len` bytes, longer than
+    /// [`Limits::max_string_length`] allows.
+    pub fn check_string_length(&self, native: &str, len: usize) -> Result<(), Error> {
+        let limit = self.limits.max_string_length;
+        if len > limit {
+            return Err(Error::NativeError {
+                name: native.to_string(),
+                msg: format!("strings can't be longer than {limit} bytes."),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn math_mode(&self) -> MathMode {
+        self.math
+    }
+
+    pub fn set_math_mode(&mut self, mode: MathMode) {
+        self.math = mode;
+    }
+
+    pub fn print_display(&self) -> bool {
+        self.print_display
+    }
+
+    /// Has `print` write values through `Display` instead of `Debug`.
+    pub fn set_print_display(&mut self, display: bool) {
+        self.print_display = display;
+    }
+
+    pub fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    /// Records the session's dialect and defines the natives it expects,
+    /// removing those an earlier dialect defined that this one doesn't.
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        if dialect.print_function() {
+            let info = NativeInfo::new("print", "streams")
+                .with_doc("Prints its arguments, separated by spaces, and a newline.");
+            self.define_native_with(info, streams::Print);
+        } else if let Some(print) = self.host_globals.remove("print") {
+            self.natives.remove("print");
+            let mut globals = self.globals.borrow_mut();
+            if globals
+                .values
+                .get("print")
+                .is_some_and(|value| Gc::ptr_eq(value, &print))
+            {
+                globals.undefine("print");
+            }
+        }
+        self.dialect = dialect;
+    }
+
+    /// Runs hot functions that only compute with numbers as machine code
+    /// from now on; see [`jit`](crate::jit).
+    #[cfg(feature = "jit")]
+    pub fn enable_jit(&mut self) -> Result<(), crate::jit::Unavailable> {
+        self.jit = Some(crate::jit::Jit::new()?);
+        Ok(())
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    /// Sends what scripts print or write to `stream` to `writer` instead of
+    /// the console.
+    pub fn redirect_output(
+        &mut self,
+        stream: OutputStream,
+        writer: impl std::io::Write + MaybeSync + 'static,
+    ) {
+        self.streams.set(stream, Some(Box::new(writer)));
+    }
+
+    /// Undoes [`Interpreter::redirect_output`].
+    pub fn restore_output(&mut self, stream: OutputStream) {
+        self.streams.set(stream, None);
+    }
+
+    /// Makes `stdin` read from `reader` instead of the console.
+    pub fn redirect_input(&mut self, reader: impl std::io::BufRead + MaybeSync + 'static) {
+        self.streams.set_input(Some(Box::new(reader)));
+    }
+
+    /// Undoes [`Interpreter::redirect_input`].
+    pub fn restore_input(&mut self) {
+        self.streams.set_input(None);
+    }
+
+    /// Calls `hook` at every statement from now on.
+    pub fn set_debug_hook(&mut self, hook: impl DebugHook + MaybeSync + 'static) {
+        self.debug_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_debug_hook(&mut self) {
+        self.debug_hook = None;
+    }
+
+    /// Tells the debug hook, if there is one, that `boundary` is about to run.
+    fn reach(&mut self, boundary: Boundary) {
+        if boundary.is_none() {
+            return;
+        }
+        if let Some(mut hook) = self.debug_hook.take() {
+            hook.before_statement(self, boundary, self.call_depth);
+            self.debug_hook.get_or_insert(hook);
+        }
+    }
+
+    /// Evaluates `expr`, which the resolver hasn't seen, where execution is,
+    /// e.g. for a debugger paused in a [`DebugHook`]: variables are looked up
+    /// by name through the current environment and its enclosing ones. The
+    /// environment, call depth and statistics are left as they were; the
+    /// effects of `expr` itself, such as assignments, are not undone.
+    pub fn evaluate_here(&mut self, expr: Expr) -> Result<Gc<Object>, Error> {
+        let saved = (
+            self.environment.clone(),
+            self.call_depth,
+            self.boundary,
+            self.by_name,
+            self.statements,
+            self.calls,
+            self.unwinding,
+            self.failure.take(),
+        );
+        self.by_name = true;
+        let result = self.evaluate(expr);
+        (
+            self.environment,
+            self.call_depth,
+            self.boundary,
+            self.by_name,
+            self.statements,
+            self.calls,
+            self.unwinding,
+            self.failure,
+        ) = saved;
+        result
+    }
+
+    pub(crate) fn streams_mut(&mut self) -> &mut Streams {
+        &mut self.streams
+    }
+
+    pub(crate) fn task_output_mut(&mut self) -> &mut TaskOutput {
+        &mut self.task_output
+    }
+
+    /// Writes out what tasks sent back since last time.
+    pub(crate) fn forward_task_output(&mut self) {
+        for (stream, data) in self.task_output.received() {
+            let _ = self.streams.write_all(stream, &data);
+        }
+    }
+
+    /// Waits for the tasks spawned from this run, until they have all
+    /// finished or the run is out of time, and writes out what they printed.
+    pub fn wait_for_tasks(&mut self) {
+        let deadline = self
+            .limits
+            .max_duration
+            .map(|limit| self.run_started + limit);
+        self.tasks.wait(deadline);
+        self.forward_task_output();
+    }
+
+    pub(crate) fn run_started(&self) -> Instant {
+        self.run_started
+    }
+
+    pub(crate) fn task_group(&self) -> &TaskGroup {
+        &self.tasks
+    }
+
+    /// Makes this a task of the run that started at `started`, sharing its
+    /// deadline and, through `group`, its memory limit.
+    pub(crate) fn join_run(&mut self, started: Instant, group: TaskGroup) {
+        self.run_started = started;
+        self.tasks = group;
+    }
+
+    /// Takes back what this task reported to its group, once it's done.
+    pub(crate) fn leave_run(&mut self) {
+        self.tasks.report(-self.bytes_reported);
+        self.bytes_reported = 0;
+    }
+
+    /// Reads input for `native` from `stdin`, or from the recording when
+    /// replaying one.
+    pub(crate) fn read_input(
+        &mut self,
+        native: &str,
+        read: impl FnOnce(&mut Streams) -> Result<Message, Error>,
+    ) -> Result<Message, Error> {
+        let streams = &mut self.streams;
+        self.inputs.provide(native, || read(streams))
+    }
+
+    pub fn top_level(&self) -> TopLevel {
+        self.top_level
+    }
+
+    pub fn set_top_level(&mut self, top_level: TopLevel) {
+        self.top_level = top_level;
+    }
+
+    pub fn inputs(&self) -> &Inputs {
+        &self.inputs
+    }
+
+    pub fn inputs_mut(&mut self) -> &mut Inputs {
+        &mut self.inputs
+    }
+
+    pub fn events_mut(&mut self) -> &mut EventLoop {
+        &mut self.events
+    }
+
+    /// Runs deferred and completed callbacks until none is ready. With
+    /// `wait`, keeps going until no async native is outstanding either.
+    pub fn run_events(&mut self, wait: bool) -> Result<(), Error> {
+        while let Some((callback, arguments)) = self.events.next(wait) {
+            self.call_value(callback, arguments)?;
+        }
+        Ok(())
+    }
+
+    /// Calls the global `name` with `args`, or returns `None` if it isn't
+    /// defined, for optional hooks such as a game's `update(dt)`. Nothing
+    /// is parsed or resolved and the callee's scope reuses a pooled map, so
+    /// once warm a call allocates only for its arguments and what its body
+    /// does; `tests/hook_allocations.rs` holds it to that.
+    pub fn call_if_exists(
+        &mut self,
+        name: &str,
+        args: Vec<Gc<Object>>,
+    ) -> Result<Option<Gc<Object>>, Error> {
+        match self.get_global(name) {
+            Some(callee) => self.call_value(callee, args).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn call_value(
+        &mut self,
+        callee: Gc<Object>,
+        args: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        self.call_at(callee, args, None)
+    }
+
+    /// Calls `callee` for a call expression, reporting arity errors at `line`.
+    fn call_at(
+        &mut self,
+        callee: Gc<Object>,
+        args: Vec<Gc<Object>>,
+        line: Option<usize>,
+    ) -> Result<Gc<Object>, Error> {
+        if self.call_depth >= self.limits.max_call_depth || stack::exhausted() {
+            return Err(Error::StackOverflow);
+        }
+
+        tracing::debug!(callee = %callee, arguments = args.len(), depth = self.call_depth, "call");
+        self.call_depth += 1;
+        self.calls += 1;
+        self.calls_in_progress.push(Call {
+            callee: callee.clone(),
+            environment: self.environment.clone(),
+            boundary: self.boundary,
+        });
+        let result = self.call_unchecked(callee, args, line);
+        self.calls_in_progress.pop();
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_unchecked(
+        &mut self,
+        callee: Gc<Object>,
+        args: Vec<Gc<Object>>,
+        line: Option<usize>,
+    ) -> Result<Gc<Object>, Error> {
+        match &*callee {
+            Object::Function(f) => {
+                let required = f.arity() - f.optional();
+                let expected = match f.variadic() {
+                    true => args.len().max(required),
+                    false => args.len().clamp(required, f.arity()),
+                };
+                self.check_arity(&callee, expected, args.len(), line)?;
+                #[cfg(feature = "jit")]
+                if let Some(result) = self.call_compiled(f.as_ref(), &args) {
+                    return Ok(result);
+                }
+                if f.as_lox_function().is_none() {
+                    self.check_capability(&callee)?;
+                }
+                f.call(self, args)
+            }
+            Object::Class(klass) => {
+                self.check_arity(&callee, klass.borrow().arity(), args.len(), line)?;
+                Class::instantiate(klass, self, args)
+            }
+            _ => Err(Error::NotCallable { obj: callee }),
+        }
+    }
+
+    /// Calls the machine code for `f`, if the JIT has some and nothing
+    /// the tree-walker does for a call is needed.
+    #[cfg(feature = "jit")]
+    fn call_compiled(
+        &mut self,
+        f: &dyn Callable<E = Error>,
+        args: &[Gc<Object>],
+    ) -> Option<Gc<Object>> {
+        let function = f.as_lox_function()?;
+        if self.math == MathMode::Strict
+            || self.limits.max_duration.is_some()
+            || self.debug_hook.is_some()
+        {
+            return None;
+        }
+        let depth = self.limits.max_call_depth.saturating_sub(self.call_depth);
+        let result = self.jit.as_mut()?.call(function, args, depth)?;
+        Some(Gc::new(Object::Number(Number(result))))
+    }
+
+    fn check_arity(
+        &self,
+        callee: &Gc<Object>,
+        arity: usize,
+        size: usize,
+        line: Option<usize>,
+    ) -> Result<(), Error> {
+        if arity == size {
+            return Ok(());
+        }
+
+        Err(Error::ArityError {
+            callee: self.callee_name(callee),
+            arity,
+            size,
+            line,
+        })
+    }
+
+    /// Calls the method `name` of `instance` with `args`, or returns `None`
+    /// if its class doesn't define one. Used for the methods the interpreter
+    /// itself looks for, like `isTruthy()` and `hashCode()`.
+    pub fn call_protocol(
+        &mut self,
+        instance: &Gc<GcCell<Instance>>,
+        name: &str,
+        args: Vec<Gc<Object>>,
+    ) -> Result<Option<Gc<Object>>, Error> {
+        let method = instance.borrow().class().borrow().find_method(name);
+        let Some(method) = method else {
+            return Ok(None);
+        };
+
+        let method = Gc::new(Object::Function(Gc::new(method.bind(instance.clone()))));
+        self.call_value(method, args).map(Some)
+    }
+
+    /// Truthiness as scripts see it: instances whose class defines
+    /// `isTruthy()` decide for themselves, everything else follows
+    /// [`Object::is_truthy`]. The method's result is not consulted again.
+    pub fn is_truthy(&mut self, value: &Gc<Object>) -> Result<bool, Error> {
+        let Object::Instance(instance) = &**value else {
+            return Ok(value.is_truthy());
+        };
+
+        match self.call_protocol(instance, "isTruthy", Vec::new())? {
+            Some(result) => Ok(result.is_truthy()),
+            None => Ok(true),
+        }
+    }
+
+    /// The hash of `key` as a map key, or `None` if it can't be one. An
+    /// instance whose class defines `hashCode()` is hashed by the number it
+    /// returns, any other instance by identity.
+    pub fn hash_key(&mut self, key: &Object) -> Result<Option<u64>, Error> {
+        collections::hash_with(key, &mut |instance| self.hash_instance(instance))
+    }
+
+    fn hash_instance(&mut self, instance: &Gc<GcCell<Instance>>) -> Result<Option<u64>, Error> {
+        let Some(value) = self.call_protocol(instance, "hashCode", Vec::new())? else {
+            return Ok(Some(Gc::as_ptr(instance) as usize as u64));
+        };
 
-    #[error("Division by zero")]
-    ZeroDivision,
+        match &*value {
+            Object::Number(_) => Ok(collections::hash(&value)),
+            _ => Err(Error::BadHashCode {
+                class: instance.borrow().class().borrow().name().to_owned(),
+                value,
+            }),
+        }
+    }
 
-    #[error("Environment error: {error:?}")]
-    EnvironmentError { error: crate::environment::Error },
+    /// Whether `a` and `b` are the same map key. An instance whose class
+    /// defines `equals(other)` decides by the truthiness of its result, any
+    /// other instance is only the same as itself. Tuples, lists and maps
+    /// compare their contents this way, everything else with `==`.
+    pub fn keys_equal(&mut self, a: &Gc<Object>, b: &Gc<Object>) -> Result<bool, Error> {
+        match (&**a, &**b) {
+            (Object::Instance(instance), _) => {
+                match self.call_protocol(instance, "equals", vec![b.clone()])? {
+                    Some(result) => self.is_truthy(&result),
+                    None => {
+                        Ok(matches!(&**b, Object::Instance(other) if Gc::ptr_eq(instance, other)))
+                    }
+                }
+            }
+            (Object::Tuple(xs), Object::Tuple(ys)) | (Object::List(xs), Object::List(ys)) => {
+                if xs.len() != ys.len() {
+                    return Ok(false);
+                }
+                for (x, y) in xs.iter().zip(ys) {
+                    if !self.keys_equal(x, y)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (Object::Map(xs), Object::Map(ys)) => {
+                if xs.len() != ys.len() {
+                    return Ok(false);
+                }
+                for (key, value) in xs.iter() {
+                    match self.map_get(ys, key)? {
+                        Some(other) if self.keys_equal(value, other)? => (),
+                        _ => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(a == b),
+        }
+    }
 
-    #[error("Object is not callable: {obj:?}")]
-    NotCallable { obj: Rc<Object> },
+    /// The value of `key` in `map`, using [`Interpreter::hash_key`] and
+    /// [`Interpreter::keys_equal`].
+    pub fn map_get<'m>(
+        &mut self,
+        map: &'m Map,
+        key: &Gc<Object>,
+    ) -> Result<Option<&'m Gc<Object>>, Error> {
+        let Some(hash) = self.hash_key(key)? else {
+            return Ok(None);
+        };
+        map.get_hashed(hash, |k| self.keys_equal(k, key))
+    }
 
-    #[error("Expected {arity} arguments but got {size}.")]
-    ArityError { arity: usize, size: usize },
+    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), Error> {
+        let _span = tracing::debug_span!("interpret", statements = statements.len()).entered();
+        self.run_started = Instant::now();
+        self.bytes_before_run = CountingAllocator::thread_bytes();
+        self.leave_run();
+        self.failure = None;
+        for statement in statements {
+            if let Err(error) = self.run_statement(statement) {
+                self.report_failure(&error);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
 
-    #[error("Forgot to handle return statement, this should not happen.")]
-    Return { value: Rc<Object> },
+    /// Hands the state captured where `error` was raised to the debug hook.
+    fn report_failure(&mut self, error: &Error) {
+        let Some(failure) = self.failure.take() else {
+            return;
+        };
+        if let Some(mut hook) = self.debug_hook.take() {
+            hook.on_error(self, error, &failure);
+            self.debug_hook.get_or_insert(hook);
+        }
+    }
 
-    #[error("{name} Only instances have properties.")]
-    PropertyAccessError { name: Token },
+    /// The frames of the calls in progress, for [`Failure`].
+    fn capture_failure(&self) -> Failure {
+        let mut frames = Vec::with_capacity(self.calls_in_progress.len() + 1);
+        let (mut boundary, mut environment) = (self.boundary, self.environment.clone());
+        for call in self.calls_in_progress.iter().rev() {
+            frames.push(Frame {
+                name: self.callee_name(&call.callee),
+                callee: Some(call.callee.clone()),
+                boundary,
+                environment,
+            });
+            (boundary, environment) = (call.boundary, call.environment.clone());
+        }
+        frames.push(Frame {
+            name: "script".to_owned(),
+            callee: None,
+            boundary,
+            environment,
+        });
+        Failure { frames }
+    }
 
-    #[error("Undefined property '{name}'")]
-    UndefinedProperty { name: String },
+    fn run_for(
+        &mut self,
+        header: Boundary,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+    ) -> Result<ControlFlow, Error> {
+        if let Some(initializer) = initializer {
+            self.run_statement(*initializer)?;
+        }
 
-    #[error("{name} Only instances have fields.")]
-    FieldAccessError { name: Token },
+        loop {
+            if let Some(condition) = &condition {
+                let value = self.evaluate(condition.clone())?;
+                if !self.is_truthy(&value)? {
+                    return Ok(Flow::Normal);
+                }
+            }
 
-    #[error("{stmt:?} is not a function statement.")]
-    MethodNotFunction { stmt: Stmt },
+            match self.run_statement(*body.clone())? {
+                Flow::Break => return Ok(Flow::Normal),
+                flow @ Flow::Return(_) => return Ok(flow),
+                Flow::Normal | Flow::Continue => {}
+            }
+            self.reach(header);
 
-    #[error("{name}: Superclass must be a class.")]
-    SuperClassNotClass { name: Token },
-}
+            let iteration = self.environment.borrow();
+            let mut next = Environment::new(iteration.enclosing.clone());
+            for (name, value) in &iteration.values {
+                next.define(name.clone(), value.clone());
+            }
+            drop(iteration);
+            self.environment = Gc::new(GcCell::new(next));
+            let environment = self.environment.clone();
+            self.track_scope(&environment, header.line, || {
+                "a for loop's scope".to_owned()
+            });
 
-impl Object {
-    pub fn is_truthy(&self) -> bool {
-        match self {
-            Self::Nil => false,
-            Self::Bool(b) => *b,
-            _ => true,
+            if let Some(increment) = &increment {
+                self.evaluate(increment.clone())?;
+            }
         }
     }
 
-    pub fn n(&self) -> Result<f64, Error> {
-        match self {
-            Self::Number(n) => Ok(*n),
-            _ => Err(Error::NaN {
-                value: self.to_string(),
-            }),
+    /// The arm's body, unless its guard doesn't hold.
+    fn run_arm(&mut self, guard: Option<Expr>, body: Expr) -> Result<Option<Gc<Object>>, Error> {
+        if let Some(guard) = guard {
+            let holds = self.evaluate(guard)?;
+            if !self.is_truthy(&holds)? {
+                return Ok(None);
+            }
         }
-    }
-}
 
-pub struct Interpreter {
-    globals: Rc<RefCell<Environment>>,
-    locals: HashMap<Token, usize>,
-    environment: Rc<RefCell<Environment>>,
-}
-
-impl Interpreter {
-    pub fn new() -> Self {
-        let globals = Rc::new(RefCell::new(Environment::new(None)));
+        self.evaluate(body).map(Some)
+    }
 
-        (*globals).borrow_mut().define(
-            "clock".to_owned(),
-            Rc::new(Object::Function(Rc::new(Clock {}))),
-        );
+    /// Executes `stmt`, counting it for [`Interpreter::stats`].
+    fn run_statement(&mut self, stmt: Stmt) -> Result<ControlFlow, Error> {
+        self.check_budget()?;
+        self.forward_task_output();
+        self.statements += 1;
+        let boundary = stmt.boundary();
+        if !boundary.is_none() {
+            self.boundary = boundary;
+        }
+        self.reach(boundary);
+        self.unwinding = false;
 
-        Self {
-            globals: globals.clone(),
-            locals: HashMap::new(),
-            environment: globals,
+        let result = self.execute(stmt);
+        if result.is_err() && !self.unwinding && self.debug_hook.is_some() {
+            self.unwinding = true;
+            self.failure = Some(self.capture_failure());
         }
+        result
     }
 
-    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), Error> {
-        for statement in statements {
-            self.execute(statement)?;
+    /// Fails once the current run, with the memory held by the tasks it
+    /// spawned, is over its time or memory limit.
+    pub(crate) fn check_budget(&mut self) -> Result<(), Error> {
+        if let Some(limit) = self.limits.max_duration {
+            if self.run_started.elapsed() > limit {
+                return Err(Error::TimeLimit { limit });
+            }
+        }
+        if let Some(limit) = self.limits.max_memory {
+            let used = CountingAllocator::thread_bytes() - self.bytes_before_run;
+            let total = self.tasks.report(used - self.bytes_reported);
+            self.bytes_reported = used;
+            if total > limit as isize {
+                return Err(Error::MemoryLimit { limit });
+            }
         }
         Ok(())
     }
 
+    /// Runs `statements` in `environment`, stopping early at a `return`,
+    /// `break` or `continue`.
     pub fn execute_block(
         &mut self,
-        statements: Rc<Vec<Stmt>>,
-        environment: Rc<RefCell<Environment>>,
-    ) -> Result<(), Error> {
-        let previous = (&self.environment).clone();
-        // println!("Before: {previous:?}");
+        statements: Gc<Vec<Stmt>>,
+        environment: Gc<GcCell<Environment>>,
+    ) -> Result<ControlFlow, Error> {
+        let previous = self.environment.clone();
+        tracing::trace!(
+            variables = environment.borrow().values.len(),
+            "push environment"
+        );
         self.environment = environment;
 
+        let mut result = Ok(Flow::Normal);
         for stmt in statements.iter() {
-            if let Err(return_type) = self.execute(stmt.clone()) {
-                self.environment = previous;
-                return Err(return_type);
+            result = self.run_statement(stmt.clone());
+            if !matches!(result, Ok(Flow::Normal)) {
+                break;
             }
         }
 
-        // println!("After: {previous:?}");
+        tracing::trace!("pop environment");
         self.environment = previous;
 
-        Ok(())
+        result
     }
 
-    pub fn copy_globals(&mut self) -> Rc<RefCell<Environment>> {
+    pub fn copy_globals(&self) -> Gc<GcCell<Environment>> {
         self.globals.clone()
     }
 
-    pub fn resolve(&mut self, name: &Token, depth: usize) {
-        self.locals.insert(name.clone(), depth);
+    pub fn resolve(&mut self, id: NodeId, depth: usize) {
+        self.locals.insert(id, depth);
     }
 
-    fn look_up_variable(&mut self, name: Token) -> Result<Rc<Object>, Error> {
-        if let Some(distance) = self.locals.get(&name) {
-            match self
-                .environment
+    /// Has the function `name` declared by the statement `owner` keep its
+    /// parameters in slots, `references` naming the slot each reads.
+    pub fn resolve_slots(&mut self, owner: StmtId, name: &str, references: Vec<(NodeId, usize)>) {
+        self.slotted.insert(owner, name.to_owned());
+        for (id, slot) in references {
+            self.slot_refs.insert(id, (owner, slot));
+        }
+    }
+
+    /// The statement running now, if it declares a function named `name`
+    /// that keeps its parameters in slots.
+    fn slotted_function(&self, name: &str) -> Option<StmtId> {
+        let id = self.boundary.id;
+        (self.slotted.get(&id).map(String::as_str) == Some(name)).then_some(id)
+    }
+
+    /// Whether calls may keep parameters in slots. A debugger reads them
+    /// from the scopes of the calls in progress, so not while one is on.
+    pub(crate) fn slots_enabled(&self) -> bool {
+        self.debug_hook.is_none()
+    }
+
+    /// Runs the body of a call like [`Interpreter::execute_block`], with
+    /// `slots` in place of those of the call around it.
+    pub(crate) fn execute_in_slots(
+        &mut self,
+        slots: Slots,
+        statements: Gc<Vec<Stmt>>,
+        environment: Gc<GcCell<Environment>>,
+    ) -> Result<ControlFlow, Error> {
+        let previous = std::mem::replace(&mut self.slots, slots);
+        let result = self.execute_block(statements, environment);
+        self.slots = previous;
+        result
+    }
+
+    /// The slot of the call running now that `id` refers to, if any.
+    fn slot(&mut self, id: NodeId) -> Option<&mut Option<Gc<Object>>> {
+        let owner = self.slots.owner?;
+        match self.slot_refs.get(id) {
+            Some(&(of, slot)) if of == owner => Some(&mut self.slots.values[slot]),
+            _ => None,
+        }
+    }
+
+    /// The global a call site names, from the site's cache while the
+    /// globals' version says it can't have been replaced.
+    fn global_callee(&mut self, id: NodeId, name: Token) -> Result<Gc<Object>, Error> {
+        let version = self.globals.borrow().version();
+        if let Some((cached, callee)) = self.call_sites.get(id) {
+            if *cached == version {
+                return Ok(callee.clone());
+            }
+        }
+
+        let callee = self.look_up_variable(id, name)?;
+        if matches!(*callee, Object::Function(_) | Object::Class(_)) {
+            self.call_sites.insert(id, (version, callee.clone()));
+        }
+        Ok(callee)
+    }
+
+    fn arguments(&mut self, arguments: Vec<Expr>) -> Result<Vec<Gc<Object>>, Error> {
+        let mut args: Vec<Gc<Object>> = Vec::new();
+
+        for argument in arguments {
+            args.push(self.evaluate(argument)?)
+        }
+
+        Ok(args)
+    }
+
+    /// The property `name` of `obj`.
+    fn property(&self, obj: &Gc<Object>, name: Token) -> Result<Gc<Object>, Error> {
+        match &**obj {
+            Object::Instance(inst) => inst.borrow().get(name),
+            Object::UserData(data) => UserData::get(data, name),
+            Object::Tuple(elements) => name
+                .lexeme
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| elements.get(index).cloned())
+                .ok_or(Error::TupleIndex {
+                    name,
+                    size: elements.len(),
+                }),
+            _ => Err(Error::PropertyAccessError { name }),
+        }
+    }
+
+    /// The `onMissing(name, args)` method of `obj`'s class bound to `obj`,
+    /// when `obj` is an instance of a class defining one. It answers for
+    /// the properties and methods the instance doesn't have.
+    fn missing_handler(&self, obj: &Gc<Object>) -> Option<Gc<Object>> {
+        let Object::Instance(instance) = &**obj else {
+            return None;
+        };
+        let handler = instance
+            .borrow()
+            .class()
+            .borrow()
+            .find_method("onMissing")?;
+        Some(Gc::new(Object::Function(Gc::new(
+            handler.bind(instance.clone()),
+        ))))
+    }
+
+    /// The `onSet(name, old, new)` method of `instance`'s class bound to
+    /// it, when the class opts into hearing of writes to fields by defining
+    /// one. Writes made while that instance's `onSet` runs don't call it
+    /// again.
+    fn set_handler(&self, instance: &Gc<GcCell<Instance>>) -> Option<Gc<Object>> {
+        if self.setting.contains(&(Gc::as_ptr(instance) as usize)) {
+            return None;
+        }
+        let instance_ref = instance.borrow();
+        let class = instance_ref.class().borrow();
+        if !class.observes_sets() {
+            return None;
+        }
+        let handler = class.find_method("onSet")?;
+        Some(Gc::new(Object::Function(Gc::new(
+            handler.bind(instance.clone()),
+        ))))
+    }
+
+    /// Calls `handler` for the missing `name`, with the arguments of the
+    /// call as a list, or `nil` when the property is only read.
+    fn call_missing(
+        &mut self,
+        handler: Gc<Object>,
+        name: String,
+        args: Gc<Object>,
+        line: usize,
+    ) -> Result<Gc<Object>, Error> {
+        let name = Gc::new(Object::String(name));
+        self.call_at(handler, vec![name, args], Some(line))
+    }
+
+    fn look_up_variable(&mut self, id: NodeId, name: Token) -> Result<Gc<Object>, Error> {
+        let value = if self.by_name {
+            self.environment.borrow().get(&name.lexeme)
+        } else if let Some(Some(value)) = self.slot(id) {
+            Ok(value.clone())
+        } else if let Some(distance) = self.locals.get(id) {
+            self.environment
                 .borrow_mut()
                 .get_at(*distance, &name.lexeme)
-            {
-                Ok(something) => return Ok(something),
-                Err(e) => return Err(Error::EnvironmentError { error: e }),
-            }
         } else {
-            match self.globals.borrow_mut().get(&name.lexeme) {
-                Ok(something) => return Ok(something),
-                Err(e) => return Err(Error::EnvironmentError { error: e }),
-            }
+            self.globals.borrow_mut().get(&name.lexeme)
+        };
+
+        let value = value.map_err(|e| Error::EnvironmentError { error: e })?;
+        if self.settings.forbid_uninitialized_reads && Gc::ptr_eq(&value, &self.uninitialized) {
+            return Err(Error::Uninitialized { name });
         }
+        Ok(value)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl ExprVisitor<Object> for Interpreter {
     type E = Error;
 
-    fn visit_assign_expr(&mut self, name: Token, value: Box<Expr>) -> Result<Rc<Object>, Self::E> {
+    fn enter_expr(&mut self) -> Result<(), Self::E> {
+        match stack::exhausted() {
+            true => Err(Error::StackOverflow),
+            false => Ok(()),
+        }
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        id: NodeId,
+        name: Token,
+        value: Box<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
         let val = self.evaluate(*value)?;
 
-        if let Some(distance) = self.locals.get(&name) {
+        if self.by_name {
+            if let Err(e) = self.environment.borrow_mut().assign(name, val.clone()) {
+                return Err(Error::EnvironmentError { error: e });
+            }
+        } else if let Some(slot) = self.slot(id) {
+            *slot = Some(val.clone());
+        } else if let Some(distance) = self.locals.get(id) {
             if let Err(e) = self
                 .environment
                 .borrow_mut()
@@ -186,37 +1638,37 @@ impl ExprVisitor<Object> for Interpreter {
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
-    ) -> Result<Rc<Object>, Error> {
+    ) -> Result<Gc<Object>, Error> {
         let l = self.evaluate(*left)?;
         let r = self.evaluate(*right)?;
 
         match op.token_type {
-            TokenType::Minus => Ok(Rc::new(Object::Number(l.n()? - r.n()?))),
-            TokenType::Slash => {
-                let divisor = r.n()?;
-                if divisor == 0.0 {
-                    return Err(Error::ZeroDivision);
-                }
-
-                Ok(Rc::new(Object::Number(l.n()? / divisor)))
+            TokenType::Minus | TokenType::Slash | TokenType::Star => {
+                let result = self.math.binary(l.n()?, &op, r.n()?)?;
+                Ok(Gc::new(Object::Number(result)))
             }
-            TokenType::Star => Ok(Rc::new(Object::Number(l.n()? * r.n()?))),
 
             TokenType::Plus => match (&*l, &*r) {
-                (Object::Number(n), Object::Number(m)) => Ok(Rc::new(Object::Number(n + m))),
+                (Object::Number(n), Object::Number(m)) => {
+                    Ok(Gc::new(Object::Number(self.math.binary(*n, &op, *m)?)))
+                }
                 (Object::String(s), Object::String(t)) => {
-                    Ok(Rc::new(Object::String(format!("{s}{t}"))))
+                    let limit = self.limits.max_string_length;
+                    if s.len() + t.len() > limit {
+                        return Err(Error::StringTooLong { op, limit });
+                    }
+                    Ok(concat(l, t))
                 }
                 (_, _) => Err(Error::UnsupportedAddOp { left: l, right: r }),
             },
 
-            TokenType::Greater => Ok(Rc::new(Object::Bool(l.n()? > r.n()?))),
-            TokenType::GreaterEqual => Ok(Rc::new(Object::Bool(l.n()? >= r.n()?))),
-            TokenType::Less => Ok(Rc::new(Object::Bool(l.n()? < r.n()?))),
-            TokenType::LessEqual => Ok(Rc::new(Object::Bool(l.n()? <= r.n()?))),
+            TokenType::Greater => Ok(self.bool(l.n()? > r.n()?)),
+            TokenType::GreaterEqual => Ok(self.bool(l.n()? >= r.n()?)),
+            TokenType::Less => Ok(self.bool(l.n()? < r.n()?)),
+            TokenType::LessEqual => Ok(self.bool(l.n()? <= r.n()?)),
 
-            TokenType::BangEqual => Ok(Rc::new(Object::Bool(!(l == r)))),
-            TokenType::EqualEqual => Ok(Rc::new(Object::Bool(l == r))),
+            TokenType::BangEqual => Ok(self.bool(!(l == r))),
+            TokenType::EqualEqual => Ok(self.bool(l == r)),
 
             _ => Err(Error::UnsupportedBinaryOp {
                 left: l,
@@ -229,60 +1681,121 @@ impl ExprVisitor<Object> for Interpreter {
     fn visit_call_expr(
         &mut self,
         callee: Box<Expr>,
-        _paren: Token,
+        paren: Token,
         arguments: Vec<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
-        let callee = self.evaluate(*callee)?;
-
-        let mut args: Vec<Rc<Object>> = Vec::new();
-
-        for argument in arguments {
-            args.push(self.evaluate(argument)?)
-        }
-
-        match &*callee {
-            Object::Function(f) => {
-                if f.arity() != args.len() {
-                    return Err(Error::ArityError {
-                        arity: f.arity(),
-                        size: args.len(),
-                    });
-                }
-                f.call(self, args)
+    ) -> Result<Gc<Object>, Self::E> {
+        let mut callee = *callee;
+        let callee = match &mut callee {
+            Expr::Variable { id, name } if !self.by_name && self.locals.get(*id).is_none() => {
+                self.global_callee(*id, std::mem::take(name))?
             }
-            Object::Class(klass) => {
-                if klass.borrow().arity() != args.len() {
-                    return Err(Error::ArityError {
-                        arity: klass.borrow().arity(),
-                        size: args.len(),
-                    });
+            Expr::Get { object, name } => {
+                let object = self.evaluate(std::mem::take(object))?;
+                match self.property(&object, std::mem::take(name)) {
+                    Err(Error::UndefinedProperty { name }) => {
+                        let Some(handler) = self.missing_handler(&object) else {
+                            return Err(Error::UndefinedProperty { name });
+                        };
+                        let args = self.arguments(arguments)?;
+                        let args = Gc::new(Object::List(args));
+                        return self.call_missing(handler, name, args, paren.line());
+                    }
+                    result => result?,
                 }
-                klass.borrow().call(self, args)
             }
-            _ => Err(Error::NotCallable { obj: callee }),
-        }
+            _ => self.evaluate(callee)?,
+        };
+
+        let args = self.arguments(arguments)?;
+        self.call_at(callee, args, Some(paren.line()))
     }
 
-    fn visit_get_expr(&mut self, object: Box<Expr>, name: Token) -> Result<Rc<Object>, Self::E> {
+    fn visit_get_expr(&mut self, object: Box<Expr>, name: Token) -> Result<Gc<Object>, Self::E> {
         let obj = self.evaluate(*object)?;
+        let line = name.line();
 
-        match &*obj {
-            Object::Instance(inst) => inst.borrow().get(name),
-            _ => Err(Error::PropertyAccessError { name }),
+        match self.property(&obj, name) {
+            Err(Error::UndefinedProperty { name }) => match self.missing_handler(&obj) {
+                Some(handler) => self.call_missing(handler, name, self.nil(), line),
+                None => Err(Error::UndefinedProperty { name }),
+            },
+            result => result,
         }
     }
 
-    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Rc<Object>, Error> {
+    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Gc<Object>, Error> {
         self.evaluate(*expr)
     }
 
-    fn visit_literal_expr(&mut self, literal: Literal) -> Result<Rc<Object>, Error> {
+    fn visit_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
+        let object = self.evaluate(*object)?;
+        let index = self.evaluate(*index)?;
+
+        let position = match &*index {
+            Object::Number(n) => n.as_int().and_then(|i| usize::try_from(i).ok()),
+            _ => None,
+        };
+
+        match &*object {
+            Object::List(elements) => {
+                position
+                    .and_then(|i| elements.get(i).cloned())
+                    .ok_or(Error::IndexOutOfRange {
+                        bracket,
+                        kind: "List",
+                        index,
+                        len: elements.len(),
+                    })
+            }
+            Object::Bytes(bytes) => position
+                .and_then(|i| bytes.get(i))
+                .map(|&byte| Gc::new(Object::Number(Number(byte.into()))))
+                .ok_or(Error::IndexOutOfRange {
+                    bracket,
+                    kind: "Bytes",
+                    index,
+                    len: bytes.len(),
+                }),
+            Object::Map(map) => self
+                .map_get(map, &index)?
+                .cloned()
+                .ok_or(Error::MissingKey {
+                    bracket,
+                    key: index,
+                }),
+            _ => Err(Error::NotIndexable {
+                bracket,
+                value: object,
+            }),
+        }
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        _bracket: Token,
+        elements: Vec<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
+        let elements = elements
+            .into_iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Gc::new(Object::List(elements)))
+    }
+
+    fn visit_literal_expr(&mut self, literal: Literal) -> Result<Gc<Object>, Error> {
         match literal {
-            Literal::Nil => Ok(Rc::new(Object::Nil)),
-            Literal::True => Ok(Rc::new(Object::Bool(true))),
-            Literal::False => Ok(Rc::new(Object::Bool(false))),
-            Literal::Number(n) => Ok(Rc::new(Object::Number(n))),
-            Literal::String(s) => Ok(Rc::new(Object::String(s))),
+            Literal::Nil => Ok(self.nil()),
+            Literal::True => Ok(self.bool(true)),
+            Literal::False => Ok(self.bool(false)),
+            Literal::Number(n) => Ok(Gc::new(Object::Number(n))),
+            Literal::String(s) => Ok(Gc::new(Object::String(s))),
+            Literal::Bytes(bytes) => Ok(Gc::new(Object::Bytes(bytes.into()))),
         }
     }
 
@@ -291,15 +1804,15 @@ impl ExprVisitor<Object> for Interpreter {
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
+    ) -> Result<Gc<Object>, Self::E> {
         let left = self.evaluate(*left)?;
 
         if op.token_type == TokenType::Or {
-            if left.is_truthy() {
+            if self.is_truthy(&left)? {
                 return Ok(left);
             }
         } else {
-            if !left.is_truthy() {
+            if !self.is_truthy(&left)? {
                 return Ok(left);
             }
         }
@@ -307,28 +1820,130 @@ impl ExprVisitor<Object> for Interpreter {
         self.evaluate(*right)
     }
 
+    fn visit_map_expr(
+        &mut self,
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    ) -> Result<Gc<Object>, Self::E> {
+        let mut map = Map::new();
+        for (key, value) in entries {
+            let key = self.evaluate(key)?;
+            let value = self.evaluate(value)?;
+            let Some(hash) = self.hash_key(&key)? else {
+                return Err(Error::UnhashableKey {
+                    brace: brace.clone(),
+                    key,
+                });
+            };
+            map.insert_hashed(hash, key.clone(), value, |k| self.keys_equal(k, &key))?;
+        }
+
+        Ok(Gc::new(Object::Map(map)))
+    }
+
+    fn visit_match_expr(
+        &mut self,
+        keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    ) -> Result<Gc<Object>, Self::E> {
+        let value = self.evaluate(*subject)?;
+
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !match_pattern(&arm.pattern, &value, &mut bindings) {
+                continue;
+            }
+
+            let previous = self.environment.clone();
+            let mut environment = Environment::new(Some(previous.clone()));
+            for (name, value) in bindings {
+                environment.define(name, value);
+            }
+            self.environment = Gc::new(GcCell::new(environment));
+
+            let result = self.run_arm(arm.guard, arm.body);
+            self.environment = previous;
+            if let Some(value) = result? {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::NoMatch { keyword, value })
+    }
+
+    /// The object is evaluated before the value, so in a chain like
+    /// `a.x = b.y = f()` the targets' objects are evaluated left to right,
+    /// then `f()`, then the fields are set right to left. A target that is
+    /// not an instance fails before its value is evaluated.
     fn visit_set_expr(
         &mut self,
         object: Box<Expr>,
         name: Token,
         value: Box<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
+    ) -> Result<Gc<Object>, Self::E> {
         let obj = self.evaluate(*object)?;
 
         match &*obj {
             Object::Instance(inst) => {
                 let val = self.evaluate(*value)?;
+                let Some(handler) = self.set_handler(inst) else {
+                    inst.borrow_mut().set(name, val.clone());
+                    return Ok(val);
+                };
+
+                let old = inst.borrow().fields().get(&name.lexeme).cloned();
+                let old = old.unwrap_or_else(|| self.nil());
+                let (field, line) = (Gc::new(Object::String(name.lexeme.clone())), name.line());
                 inst.borrow_mut().set(name, val.clone());
+                let address = Gc::as_ptr(inst) as usize;
+                self.setting.insert(address);
+                let result = self.call_at(handler, vec![field, old, val.clone()], Some(line));
+                self.setting.remove(&address);
+                result.map(|_| val)
+            }
+            Object::UserData(data) => {
+                let val = self.evaluate(*value)?;
+                data.set(name, val.clone())?;
                 Ok(val)
             }
             _ => Err(Error::FieldAccessError { name }),
         }
     }
 
-    fn visit_super_expr(&mut self, keyword: Token, method: Token) -> Result<Rc<Object>, Self::E> {
+    /// Lists and maps are frozen, so this always fails, before the index or
+    /// value is evaluated.
+    fn visit_set_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        bracket: Token,
+        _index: Box<Expr>,
+        _value: Box<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
+        let object = self.evaluate(*object)?;
+
+        match &*object {
+            Object::List(_) | Object::Map(_) | Object::Bytes(_) => Err(Error::Frozen {
+                bracket,
+                value: object,
+            }),
+            _ => Err(Error::NotIndexable {
+                bracket,
+                value: object,
+            }),
+        }
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        id: NodeId,
+        _keyword: Token,
+        ancestor: Option<Token>,
+        method: Token,
+    ) -> Result<Gc<Object>, Self::E> {
         let distance = *self
             .locals
-            .get(&keyword)
+            .get(id)
             .expect("Expect keyword to be in locals.");
 
         let superclass = self
@@ -351,47 +1966,76 @@ impl ExprVisitor<Object> for Interpreter {
             unreachable!()
         };
 
-        let m = superclass.borrow().find_method(&method.lexeme);
+        // The superclass or the ancestor above it with that name.
+        let mut start = superclass.clone();
+        if let Some(ancestor) = ancestor {
+            while start.borrow().name() != ancestor.lexeme {
+                let above = start.borrow().superclass().cloned();
+                start = above.ok_or(Error::NotAnAncestor {
+                    ancestor: ancestor.clone(),
+                })?;
+            }
+        }
+
+        let m = start.borrow().find_method(&method.lexeme);
         let Some(method) = m else {
             return Err(Error::UndefinedProperty {
                 name: method.lexeme,
             });
         };
 
-        Ok(Rc::new(Object::Function(Rc::new(
+        Ok(Gc::new(Object::Function(Gc::new(
             method.bind(object.clone()),
         ))))
     }
 
-    fn visit_this_expr(&mut self, keyword: Token) -> Result<Rc<Object>, Self::E> {
-        self.look_up_variable(keyword)
+    fn visit_this_expr(&mut self, id: NodeId, keyword: Token) -> Result<Gc<Object>, Self::E> {
+        self.look_up_variable(id, keyword)
+    }
+
+    fn visit_tuple_expr(&mut self, elements: Vec<Expr>) -> Result<Gc<Object>, Self::E> {
+        let elements = elements
+            .into_iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<_, _>>()?;
+        Ok(Gc::new(Object::Tuple(elements)))
     }
 
-    fn visit_variable_expr(&mut self, name: Token) -> Result<Rc<Object>, Self::E> {
-        self.look_up_variable(name)
+    fn visit_variable_expr(&mut self, id: NodeId, name: Token) -> Result<Gc<Object>, Self::E> {
+        self.look_up_variable(id, name)
     }
 
-    fn visit_unary_expr(&mut self, op: Token, right: Box<Expr>) -> Result<Rc<Object>, Error> {
+    fn visit_unary_expr(&mut self, op: Token, right: Box<Expr>) -> Result<Gc<Object>, Error> {
         let r = self.evaluate(*right)?;
 
         match op.token_type {
-            TokenType::Minus => Ok(Rc::new(Object::Number(-r.n()?))),
-            TokenType::Bang => Ok(Rc::new(Object::Bool(!r.is_truthy()))),
+            TokenType::Minus => Ok(Gc::new(Object::Number(-r.n()?))),
+            TokenType::Bang => {
+                let truthy = self.is_truthy(&r)?;
+                Ok(self.bool(!truthy))
+            }
             _ => Err(Error::UnsupportedUnaryOp { op, right: r }),
         }
     }
 }
 
-impl StmtVisitor<()> for Interpreter {
+impl StmtVisitor<ControlFlow> for Interpreter {
     type E = Error;
 
-    fn visit_block_stmt(&mut self, statements: Vec<Stmt>) -> Result<(), Self::E> {
-        let reference = (&self.environment).clone();
-        self.execute_block(
-            Rc::new(statements),
-            Rc::new(RefCell::new(Environment::new(Some(reference)))),
-        )?;
-        Ok(())
+    fn enter_stmt(&mut self) -> Result<(), Self::E> {
+        self.enter_expr()
+    }
+
+    fn visit_block_stmt(&mut self, statements: Vec<Stmt>) -> Result<ControlFlow, Self::E> {
+        let reference = self.environment.clone();
+        let environment = Gc::new(GcCell::new(Environment::new(Some(reference))));
+        let line = self.boundary.line;
+        self.track_scope(&environment, line, || "a block's scope".to_owned());
+        self.execute_block(Gc::new(statements), environment)
+    }
+
+    fn visit_break_stmt(&mut self, _keyword: Token) -> Result<ControlFlow, Self::E> {
+        Ok(Flow::Break)
     }
 
     fn visit_class_stmt(
@@ -399,7 +2043,8 @@ impl StmtVisitor<()> for Interpreter {
         name: Token,
         superclass: Option<Expr>,
         methods: Vec<Stmt>,
-    ) -> Result<(), Self::E> {
+        doc: Option<String>,
+    ) -> Result<ControlFlow, Self::E> {
         let sklass = if let Some(sclass) = superclass {
             let superclass = self.evaluate(sclass)?;
             if let Object::Class(klass) = &*superclass {
@@ -413,32 +2058,43 @@ impl StmtVisitor<()> for Interpreter {
 
         self.environment
             .borrow_mut()
-            .define(name.lexeme.clone(), Rc::new(Object::Nil));
+            .define(name.lexeme.clone(), self.nil());
 
         if let Some(superclass) = &sklass {
             let mut environment = Environment::new(Some(self.environment.clone()));
             environment.define(
                 "super".to_string(),
-                Rc::new(Object::Class(superclass.clone())),
+                Gc::new(Object::Class(superclass.clone())),
             );
-            self.environment = Rc::new(RefCell::new(environment));
+            self.environment = Gc::new(GcCell::new(environment));
         }
 
         let mut methods_map = HashMap::new();
 
         for method in methods {
             match method {
-                Stmt::Function { name, params, body } => {
+                Stmt::Function {
+                    name,
+                    params,
+                    body,
+                    doc,
+                    ..
+                } => {
                     let function = LoxFunction::new(
                         name.lexeme.clone(),
                         self.environment.clone(),
                         params.into_iter().map(|e| e.lexeme).collect(),
-                        Rc::new(body),
+                        Gc::new(body),
                         &name.lexeme == "init",
-                    );
+                    )
+                    .with_doc(doc);
                     methods_map.insert(name.lexeme, function);
                 }
-                _ => return Err(Error::MethodNotFunction { stmt: method }),
+                _ => {
+                    return Err(Error::MethodNotFunction {
+                        stmt: Box::new(method),
+                    })
+                }
             };
         }
 
@@ -452,22 +2108,51 @@ impl StmtVisitor<()> for Interpreter {
             self.environment = enclosing;
         }
 
-        let klass = Class::new(name.lexeme.clone(), sklass, methods_map);
+        let klass = Class::new(name.lexeme.clone(), sklass, methods_map).with_doc(doc);
 
         if let Err(e) = self
             .environment
             .borrow_mut()
-            .assign(name, Rc::new(Object::Class(Rc::new(RefCell::new(klass)))))
+            .assign(name, Gc::new(Object::Class(Gc::new(GcCell::new(klass)))))
         {
             return Err(Error::EnvironmentError { error: e });
         };
 
-        Ok(())
+        Ok(Flow::Normal)
+    }
+
+    fn visit_continue_stmt(&mut self, _keyword: Token) -> Result<ControlFlow, Self::E> {
+        Ok(Flow::Continue)
+    }
+
+    fn visit_destructure_stmt(
+        &mut self,
+        paren: Token,
+        names: Vec<Token>,
+        initializer: Expr,
+    ) -> Result<ControlFlow, Self::E> {
+        let value = self.evaluate(initializer)?;
+        let (Object::Tuple(elements) | Object::List(elements)) = &*value else {
+            return Err(Error::NotDestructurable { paren, value });
+        };
+        if elements.len() != names.len() {
+            return Err(Error::UnpackArity {
+                paren,
+                expected: names.len(),
+                got: elements.len(),
+            });
+        }
+
+        let mut environment = self.environment.borrow_mut();
+        for (name, element) in names.into_iter().zip(elements) {
+            environment.define(name.lexeme, element.clone());
+        }
+        Ok(Flow::Normal)
     }
 
-    fn visit_expression_stmt(&mut self, expr: Expr) -> Result<(), Error> {
+    fn visit_expression_stmt(&mut self, expr: Expr) -> Result<ControlFlow, Error> {
         self.evaluate(expr)?;
-        Ok(())
+        Ok(Flow::Normal)
     }
 
     fn visit_function_stmt(
@@ -475,19 +2160,43 @@ impl StmtVisitor<()> for Interpreter {
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
-    ) -> Result<(), Self::E> {
+        doc: Option<String>,
+        _signature: Option<Box<Signature>>,
+    ) -> Result<ControlFlow, Self::E> {
         let function = LoxFunction::new(
             name.lexeme.clone(),
-            (&self.environment).clone(),
+            self.environment.clone(),
             params.into_iter().map(|t| t.lexeme).collect(),
-            Rc::new(body),
+            Gc::new(body),
             false,
-        );
+        )
+        .with_doc(doc)
+        .with_slots(self.slotted_function(&name.lexeme));
 
         self.environment
             .borrow_mut()
-            .define(name.lexeme, Rc::new(Object::Function(Rc::new(function))));
-        Ok(())
+            .define(name.lexeme, Gc::new(Object::Function(Gc::new(function))));
+        Ok(Flow::Normal)
+    }
+
+    /// Runs the loop in an environment holding the initializer's variables.
+    /// Before each increment that environment is swapped for a fresh copy,
+    /// so closures created by the body keep the values of their iteration.
+    fn visit_for_stmt(
+        &mut self,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+    ) -> Result<ControlFlow, Self::E> {
+        let previous = self.environment.clone();
+        self.environment = Gc::new(GcCell::new(Environment::new(Some(previous.clone()))));
+        let (environment, line) = (self.environment.clone(), self.boundary.line);
+        self.track_scope(&environment, line, || "a for loop's scope".to_owned());
+
+        let result = self.run_for(self.boundary, initializer, condition, increment, body);
+        self.environment = previous;
+        result
     }
 
     fn visit_if_stmt(
@@ -495,47 +2204,91 @@ impl StmtVisitor<()> for Interpreter {
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
-    ) -> Result<(), Self::E> {
-        if self.evaluate(condition)?.is_truthy() {
-            self.execute(*then_branch)?;
+    ) -> Result<ControlFlow, Self::E> {
+        let condition = self.evaluate(condition)?;
+        if self.is_truthy(&condition)? {
+            self.run_statement(*then_branch)
         } else if let Some(bexpr) = else_branch {
-            self.execute(*bexpr)?;
+            self.run_statement(*bexpr)
+        } else {
+            Ok(Flow::Normal)
         }
+    }
 
-        Ok(())
+    /// Imports are loaded by [`crate::program::Program`] before the
+    /// importing file runs, which then drops them, so one that gets here
+    /// was never loaded.
+    fn visit_import_stmt(&mut self, keyword: Token, _path: String) -> Result<ControlFlow, Self::E> {
+        Err(Error::ImportUnsupported { keyword })
     }
 
-    fn visit_print_stmt(&mut self, expr: Expr) -> Result<(), Error> {
+    fn visit_print_stmt(&mut self, expr: Expr) -> Result<ControlFlow, Error> {
         let value = self.evaluate(expr)?;
-        println!("{value:?}");
-        Ok(())
+        let text = match self.print_display {
+            true => format!("{value}\n"),
+            false => format!("{value:?}\n"),
+        };
+        self.streams
+            .write_all(OutputStream::Stdout, text.as_bytes())
+            .map_err(|e| Error::NativeError {
+                name: "print".to_string(),
+                msg: e.to_string(),
+            })?;
+        Ok(Flow::Normal)
     }
 
-    fn visit_return_stmt(&mut self, _keyword: Token, value: Option<Expr>) -> Result<(), Self::E> {
-        let mut val: Rc<Object> = Rc::new(Object::Nil);
+    fn visit_return_stmt(
+        &mut self,
+        _keyword: Token,
+        value: Option<Expr>,
+    ) -> Result<ControlFlow, Self::E> {
+        let mut val: Gc<Object> = self.nil();
 
         if let Some(a) = value {
             val = self.evaluate(a)?;
         }
 
-        Err(Error::Return { value: val })
+        Ok(Flow::Return(val))
     }
 
-    fn visit_var_stmt(&mut self, name: Token, initializer: Option<Expr>) -> Result<(), Self::E> {
-        let mut value = Rc::new(Object::Nil);
-        if let Some(expr) = initializer {
-            value = self.evaluate(expr)?;
-        }
+    fn visit_var_stmt(
+        &mut self,
+        name: Token,
+        _annotation: Option<Token>,
+        initializer: Option<Expr>,
+    ) -> Result<ControlFlow, Self::E> {
+        let value = match initializer {
+            Some(expr) => self.evaluate(expr)?,
+            None if self.settings.forbid_uninitialized_reads => self.uninitialized.clone(),
+            None => self.nil(),
+        };
 
         self.environment.borrow_mut().define(name.lexeme, value);
-        Ok(())
+        Ok(Flow::Normal)
     }
 
-    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Result<(), Self::E> {
-        while self.evaluate(condition.clone())?.is_truthy() {
-            self.execute(*body.clone())?;
+    fn visit_while_stmt(
+        &mut self,
+        condition: Expr,
+        body: Box<Stmt>,
+    ) -> Result<ControlFlow, Self::E> {
+        let header = self.boundary;
+        for iteration in 0.. {
+            if iteration > 0 {
+                self.reach(header);
+            }
+            let value = self.evaluate(condition.clone())?;
+            if !self.is_truthy(&value)? {
+                break;
+            }
+
+            match self.run_statement(*body.clone())? {
+                Flow::Break => break,
+                flow @ Flow::Return(_) => return Ok(flow),
+                Flow::Normal | Flow::Continue => {}
+            }
         }
 
-        Ok(())
+        Ok(Flow::Normal)
     }
 }
@@ -0,0 +1,901 @@
+//! `jlox transpile --js`: a script as JavaScript, to run in a browser or
+//! under Node without the interpreter, with a source map pointing back at
+//! the script.
+//!
+//! Lox maps onto JavaScript almost statement for statement:
+//!
+//! - classes become ES classes, and `init` a method that returns `this`;
+//!   calling a class by its name becomes `new Point().init(x, y)`;
+//! - functions keep their names, except that ones nested in methods become
+//!   arrow functions so `this` still means the instance;
+//! - `var` is `let`, so `for` loops give each iteration its own variables
+//!   like Lox does;
+//! - `print` is `console.log`, `nil` is `null`, `==` is `===` and `clock()`
+//!   is `Date.now()`.
+//!
+//! Conditions, `!`, `and` and `or` use Lox's truthiness through a few
+//! `$`-prefixed helpers, defined at the top of the output when it needs
+//! them. Names JavaScript reserves get a trailing `_`.
+//!
+//! The operators are JavaScript's: adding a string to a number, dividing by
+//! zero or reading a missing field doesn't fail, and calls don't check how
+//! many arguments they get. A class is only constructed when it's called by
+//! its own name. Lists, maps, tuples, `match`, imports and destructuring
+//! are refused, with the line they're on.
+//!
+//! ```
+//! use jlox::{javascript, parse_source};
+//!
+//! let statements = parse_source("fun square(x) { return x * x; }\nprint square(3);").unwrap();
+//! let js = javascript::to_js(&statements, "square.lox").unwrap();
+//! assert!(js.code.contains("function square(x) {\n  return x * x;\n}"));
+//! assert!(js.code.contains("console.log(square(3));"));
+//! assert_eq!(js.lines.last(), Some(&Some(2)));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{Expr, Literal, Stmt},
+    token::{Token, TokenType},
+    transpile::Error,
+};
+
+/// Names a Lox variable can't keep: JavaScript's reserved words, and the
+/// globals the generated code uses.
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "case",
+    "catch",
+    "console",
+    "const",
+    "Date",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "function",
+    "implements",
+    "in",
+    "Infinity",
+    "instanceof",
+    "interface",
+    "let",
+    "NaN",
+    "new",
+    "null",
+    "Object",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "switch",
+    "throw",
+    "try",
+    "typeof",
+    "undefined",
+    "void",
+    "with",
+    "yield",
+];
+
+/// The helpers the generated code can call, in the order they're defined.
+const HELPERS: &[(&str, &str)] = &[
+    (
+        "$truthy",
+        "const $truthy = (value) => value != null && value !== false;",
+    ),
+    (
+        "$or",
+        "const $or = (left, right) => ($truthy(left) ? left : right());",
+    ),
+    (
+        "$and",
+        "const $and = (left, right) => ($truthy(left) ? right() : left);",
+    ),
+    (
+        "$get",
+        "const $get = (object, name) => {\n  const value = object[name];\n  return typeof value === \"function\" && !Object.hasOwn(object, name)\n    ? value.bind(object)\n    : value;\n};",
+    ),
+];
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A script as JavaScript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaScript {
+    pub code: String,
+    /// For each line of `code`, the line of the script it came from.
+    pub lines: Vec<Option<usize>>,
+    source: String,
+}
+
+impl JavaScript {
+    /// A version 3 source map for `code` saved as `file`, mapping the start
+    /// of each line to the start of its line in the script.
+    pub fn source_map(&self, file: &str) -> String {
+        let mut mappings = String::new();
+        let mut previous = 0;
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                mappings.push(';');
+            }
+            if let Some(line) = line {
+                let line = *line as i64 - 1;
+                // Column 0, the first source, `line`, column 0.
+                for field in [0, 0, line - previous, 0] {
+                    vlq(&mut mappings, field);
+                }
+                previous = line;
+            }
+        }
+        format!(
+            "{{\"version\":3,\"file\":{},\"sources\":[{}],\"names\":[],\"mappings\":\"{mappings}\"}}\n",
+            quoted(file),
+            quoted(&self.source)
+        )
+    }
+}
+
+/// Appends `value` as a base64 VLQ, the encoding of source map fields.
+fn vlq(out: &mut String, value: i64) {
+    let mut rest = if value < 0 {
+        (-value << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = rest & 31;
+        rest >>= 5;
+        if rest > 0 {
+            digit |= 32;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if rest == 0 {
+            break;
+        }
+    }
+}
+
+/// `s` as a string literal, valid as both JavaScript and JSON.
+fn quoted(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn ident(name: &Token) -> String {
+    if RESERVED.contains(&name.lexeme.as_str()) {
+        format!("{}_", name.lexeme)
+    } else {
+        name.lexeme.clone()
+    }
+}
+
+/// A field or method name, which JavaScript only restricts when it would
+/// name the class constructor.
+fn property(name: &Token) -> String {
+    if name.lexeme == "constructor" {
+        "constructor_".to_owned()
+    } else {
+        name.lexeme.clone()
+    }
+}
+
+/// Whether `expr` always evaluates to a boolean, so JavaScript's operators
+/// treat it like Lox's.
+fn boolean(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(Literal::True | Literal::False) => true,
+        Expr::Binary { op, .. } => matches!(
+            op.token_type,
+            TokenType::EqualEqual
+                | TokenType::BangEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+        ),
+        Expr::Unary { op, .. } => op.token_type == TokenType::Bang,
+        Expr::Grouping { ex } => boolean(ex),
+        Expr::Logical { left, right, .. } => boolean(left) && boolean(right),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Class {
+    superclass: Option<String>,
+    init: bool,
+}
+
+/// What the script declares anywhere, which decides how calls and property
+/// reads are written.
+#[derive(Default)]
+struct Declarations {
+    classes: HashMap<String, Class>,
+    methods: HashSet<String>,
+    names: HashSet<String>,
+}
+
+impl Declarations {
+    fn collect(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let superclass = match superclass {
+                    Some(Expr::Variable { name, .. }) => Some(name.lexeme.clone()),
+                    _ => None,
+                };
+                let init = methods.iter().any(
+                    |method| matches!(method, Stmt::Function { name, .. } if name.lexeme == "init"),
+                );
+                self.classes
+                    .insert(name.lexeme.clone(), Class { superclass, init });
+                self.names.insert(name.lexeme.clone());
+                for method in methods {
+                    if let Stmt::Function {
+                        name, params, body, ..
+                    } = method
+                    {
+                        self.methods.insert(name.lexeme.clone());
+                        self.names
+                            .extend(params.iter().map(|param| param.lexeme.clone()));
+                        body.iter().for_each(|stmt| self.collect(stmt));
+                    }
+                }
+                return;
+            }
+            Stmt::Function { name, params, .. } => {
+                self.names.insert(name.lexeme.clone());
+                self.names
+                    .extend(params.iter().map(|param| param.lexeme.clone()));
+            }
+            Stmt::Var { name, .. } => {
+                self.names.insert(name.lexeme.clone());
+            }
+            _ => {}
+        }
+        for child in stmt.children() {
+            self.collect(child);
+        }
+    }
+
+    /// Whether constructing `class` runs an `init`, its own or inherited.
+    fn initialized(&self, class: &str) -> bool {
+        let mut class = self.classes.get(class);
+        // Bounded, in case a script makes a class its own ancestor.
+        for _ in 0..self.classes.len() {
+            match class {
+                Some(Class { init: true, .. }) => return true,
+                Some(Class {
+                    superclass: Some(superclass),
+                    ..
+                }) => class = self.classes.get(superclass),
+                _ => return false,
+            }
+        }
+        false
+    }
+}
+
+struct Emitter {
+    declarations: Declarations,
+    out: String,
+    lines: Vec<Option<usize>>,
+    indent: usize,
+    /// The line of the statement being written.
+    source_line: usize,
+    helpers: HashSet<&'static str>,
+    /// The names declared in each enclosing block, innermost last.
+    scopes: Vec<HashSet<String>>,
+    functions: Vec<Kind>,
+    /// Loops enclosing the current statement within the current function.
+    loops: usize,
+    /// For each enclosing class, whether it has a superclass.
+    classes: Vec<bool>,
+    /// Whether the last statement written was a declaration, which gets a
+    /// blank line after it.
+    spaced: bool,
+}
+
+/// `statements` as JavaScript. `source` names the script in the header
+/// comment and the source map.
+pub fn to_js(statements: &[Stmt], source: &str) -> Result<JavaScript, Error> {
+    let mut declarations = Declarations::default();
+    statements
+        .iter()
+        .for_each(|stmt| declarations.collect(stmt));
+    let mut emitter = Emitter {
+        declarations,
+        out: String::new(),
+        lines: Vec::new(),
+        indent: 0,
+        source_line: 1,
+        helpers: HashSet::new(),
+        scopes: vec![HashSet::new()],
+        functions: Vec::new(),
+        loops: 0,
+        classes: Vec::new(),
+        spaced: false,
+    };
+    for stmt in statements {
+        emitter.statement(stmt)?;
+    }
+
+    let mut code =
+        format!("// Transpiled from {source} by `jlox transpile --js`.\n\"use strict\";\n\n");
+    for (name, helper) in HELPERS {
+        if emitter.helpers.contains(name) {
+            code.push_str(helper);
+            code.push('\n');
+        }
+    }
+    if !emitter.helpers.is_empty() {
+        code.push('\n');
+    }
+    let mut lines = vec![None; code.lines().count()];
+    code.push_str(&emitter.out);
+    lines.extend(emitter.lines);
+    Ok(JavaScript {
+        code,
+        lines,
+        source: source.to_owned(),
+    })
+}
+
+impl Emitter {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.source_line,
+            message: message.into(),
+        }
+    }
+
+    fn unsupported(&self, what: &str) -> Error {
+        self.error(format!("Can't transpile {what}."))
+    }
+
+    fn line(&mut self, text: &str) {
+        if text.is_empty() {
+            self.lines.push(None);
+        } else {
+            self.out.push_str(&"  ".repeat(self.indent));
+            self.out.push_str(text);
+            self.lines.push(Some(self.source_line));
+        }
+        self.out.push('\n');
+    }
+
+    fn helper(&mut self, name: &'static str) -> &'static str {
+        if name != "$get" {
+            self.helpers.insert("$truthy");
+        }
+        self.helpers.insert(name);
+        name
+    }
+
+    fn doc(&mut self, doc: Option<&str>) {
+        let Some(doc) = doc else { return };
+        let lines: Vec<&str> = doc.lines().collect();
+        if let [line] = lines[..] {
+            self.line(&format!("/** {line} */"));
+            return;
+        }
+        self.line("/**");
+        for line in lines {
+            self.line(format!(" * {line}").trim_end());
+        }
+        self.line(" */");
+    }
+
+    /// Declares `name` in the current block, returning its JavaScript name
+    /// and whether the block already declared it, in which case the
+    /// declaration has to be written as an assignment.
+    fn declare(&mut self, name: &Token) -> (String, bool) {
+        let scope = self.scopes.last_mut().expect("a block is open");
+        (ident(name), !scope.insert(name.lexeme.clone()))
+    }
+
+    fn block(&mut self, statements: &[Stmt], declared: HashSet<String>) -> Result<(), Error> {
+        let line = self.source_line;
+        self.indent += 1;
+        self.scopes.push(declared);
+        self.spaced = false;
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+        self.scopes.pop();
+        self.source_line = line;
+        self.spaced = false;
+        self.indent -= 1;
+        Ok(())
+    }
+
+    /// The body of a loop or branch, which is always written as a block.
+    fn body(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        match stmt {
+            Stmt::Block { statements, .. } => self.block(statements, HashSet::new()),
+            _ => self.block(std::slice::from_ref(stmt), HashSet::new()),
+        }
+    }
+
+    fn function(
+        &mut self,
+        head: &str,
+        params: &[Token],
+        body: &[Stmt],
+        kind: Kind,
+        tail: &str,
+    ) -> Result<(), Error> {
+        if body.is_empty() && kind != Kind::Initializer {
+            self.line(&format!("{head}{tail}"));
+            return Ok(());
+        }
+        let line = self.source_line;
+        self.line(head);
+        self.functions.push(kind);
+        let loops = std::mem::take(&mut self.loops);
+        let declared = params.iter().map(|param| param.lexeme.clone()).collect();
+        self.indent += 1;
+        self.scopes.push(declared);
+        self.spaced = false;
+        for stmt in body {
+            self.statement(stmt)?;
+        }
+        self.source_line = line;
+        if kind == Kind::Initializer && !matches!(body.last(), Some(Stmt::Return { .. })) {
+            self.line("return this;");
+        }
+        self.scopes.pop();
+        self.indent -= 1;
+        self.loops = loops;
+        self.functions.pop();
+        self.line(tail);
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        self.source_line = stmt.boundary().line;
+        let declaration = matches!(stmt, Stmt::Class { .. } | Stmt::Function { .. });
+        if (declaration || self.spaced) && !self.out.is_empty() && !self.out.ends_with("{\n") {
+            self.line("");
+        }
+
+        match stmt {
+            Stmt::Block { statements, .. } => {
+                self.line("{");
+                self.block(statements, HashSet::new())?;
+                self.line("}");
+            }
+            Stmt::Break { keyword, .. } | Stmt::Continue { keyword, .. } if self.loops == 0 => {
+                return Err(self.error(format!("Can't use '{}' outside of a loop.", keyword.lexeme)))
+            }
+            Stmt::Break { .. } => self.line("break;"),
+            Stmt::Continue { .. } => self.line("continue;"),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+                ..
+            } => {
+                self.doc(doc.as_deref());
+                let extends = match superclass {
+                    Some(superclass) => format!(" extends {}", self.expression(superclass)?),
+                    None => String::new(),
+                };
+                let (name, redeclared) = self.declare(name);
+                let (head, tail) = if redeclared {
+                    (format!("{name} = class {name}{extends} {{"), "};")
+                } else {
+                    (format!("class {name}{extends} {{"), "}")
+                };
+                if methods.is_empty() {
+                    self.line(&format!("{head}{tail}"));
+                    self.spaced = true;
+                    return Ok(());
+                }
+                self.line(&head);
+
+                self.classes.push(superclass.is_some());
+                self.indent += 1;
+                for (i, method) in methods.iter().enumerate() {
+                    let Stmt::Function {
+                        name,
+                        params,
+                        body,
+                        doc,
+                        boundary,
+                        ..
+                    } = method
+                    else {
+                        continue;
+                    };
+                    self.source_line = boundary.line;
+                    if i > 0 {
+                        self.line("");
+                    }
+                    self.doc(doc.as_deref());
+                    let kind = if name.lexeme == "init" {
+                        Kind::Initializer
+                    } else {
+                        Kind::Method
+                    };
+                    let head = format!("{}({}) {{", property(name), self.params(params));
+                    self.function(&head, params, body, kind, "}")?;
+                }
+                self.indent -= 1;
+                self.classes.pop();
+
+                self.line(tail);
+            }
+            Stmt::Destructure { .. } => return Err(self.unsupported("destructuring")),
+            Stmt::Expression { expr, .. } => {
+                let expr = self.expression(expr)?;
+                self.line(&format!("{expr};"));
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(HashSet::new());
+                let mut head = "for (".to_owned();
+                match initializer.as_deref() {
+                    Some(Stmt::Var {
+                        name, initializer, ..
+                    }) => {
+                        let value = self.initializer(initializer.as_ref())?;
+                        let (name, _) = self.declare(name);
+                        head.push_str(&format!("let {name} = {value}"));
+                    }
+                    Some(Stmt::Expression { expr, .. }) => head.push_str(&self.expression(expr)?),
+                    _ => {}
+                }
+                head.push(';');
+                if let Some(condition) = condition {
+                    head.push(' ');
+                    head.push_str(&self.condition(condition)?);
+                }
+                head.push(';');
+                if let Some(increment) = increment {
+                    head.push(' ');
+                    head.push_str(&self.expression(increment)?);
+                }
+                self.line(&format!("{head}) {{"));
+                self.loops += 1;
+                self.body(body)?;
+                self.loops -= 1;
+                self.line("}");
+                self.scopes.pop();
+            }
+            Stmt::Function {
+                name,
+                params,
+                body,
+                doc,
+                ..
+            } => {
+                self.doc(doc.as_deref());
+                let params_list = self.params(params);
+                let (name, redeclared) = self.declare(name);
+                // Inside a class, `this` has to keep meaning the instance.
+                let arrow = !self.classes.is_empty();
+                let (head, tail) = match (arrow, redeclared) {
+                    (true, true) => (format!("{name} = ({params_list}) => {{"), "};"),
+                    (true, false) => (format!("let {name} = ({params_list}) => {{"), "};"),
+                    (false, true) => (format!("{name} = function {name}({params_list}) {{"), "};"),
+                    (false, false) => (format!("function {name}({params_list}) {{"), "}"),
+                };
+                self.function(&head, params, body, Kind::Function, tail)?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.condition(condition)?;
+                self.line(&format!("if ({condition}) {{"));
+                self.body(then_branch)?;
+                let mut else_branch = else_branch.as_deref();
+                while let Some(branch) = else_branch {
+                    if let Stmt::If {
+                        condition,
+                        then_branch,
+                        else_branch: next,
+                        boundary,
+                    } = branch
+                    {
+                        self.source_line = boundary.line;
+                        let condition = self.condition(condition)?;
+                        self.line(&format!("}} else if ({condition}) {{"));
+                        self.body(then_branch)?;
+                        else_branch = next.as_deref();
+                    } else {
+                        self.line("} else {");
+                        self.body(branch)?;
+                        else_branch = None;
+                    }
+                }
+                self.line("}");
+            }
+            Stmt::Import { .. } => return Err(self.unsupported("imports")),
+            Stmt::Print { expr, .. } => {
+                let expr = self.expression(expr)?;
+                self.line(&format!("console.log({expr});"));
+            }
+            Stmt::Return { value, .. } => match (self.functions.last(), value) {
+                (None, _) => return Err(self.error("Can't return from top-level code.")),
+                (Some(Kind::Initializer), Some(_)) => {
+                    return Err(self.error("Can't return a value from an initializer."))
+                }
+                (Some(Kind::Initializer), None) => self.line("return this;"),
+                (_, None) => self.line("return;"),
+                (_, Some(value)) => {
+                    let value = self.expression(value)?;
+                    self.line(&format!("return {value};"));
+                }
+            },
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = self.initializer(initializer.as_ref())?;
+                let (name, redeclared) = self.declare(name);
+                if redeclared {
+                    self.line(&format!("{name} = {value};"));
+                } else {
+                    self.line(&format!("let {name} = {value};"));
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let condition = self.condition(condition)?;
+                self.line(&format!("while ({condition}) {{"));
+                self.loops += 1;
+                self.body(body)?;
+                self.loops -= 1;
+                self.line("}");
+            }
+        }
+
+        self.spaced = declaration;
+        Ok(())
+    }
+
+    fn params(&self, params: &[Token]) -> String {
+        params.iter().map(ident).collect::<Vec<_>>().join(", ")
+    }
+
+    fn initializer(&mut self, initializer: Option<&Expr>) -> Result<String, Error> {
+        match initializer {
+            Some(initializer) => self.expression(initializer),
+            None => Ok("null".to_owned()),
+        }
+    }
+
+    /// Whether `name` is the native `clock`, which nothing in the script
+    /// redeclares.
+    fn clock(&self, name: &Token) -> bool {
+        name.lexeme == "clock" && !self.declarations.names.contains("clock")
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<String, Error> {
+        Ok(match expr {
+            Expr::Assign { name, value, .. } => {
+                format!("{} = {}", ident(name), self.expression(value)?)
+            }
+            Expr::Binary { left, op, right } => {
+                let nil = |expr: &Expr| matches!(expr, Expr::Literal(Literal::Nil));
+                // `== null` also holds for `undefined`, which is what a
+                // function without a `return` gives.
+                let loose = nil(left) || nil(right);
+                let operator = match op.token_type {
+                    TokenType::EqualEqual if loose => "==",
+                    TokenType::BangEqual if loose => "!=",
+                    TokenType::EqualEqual => "===",
+                    TokenType::BangEqual => "!==",
+                    _ => &op.lexeme,
+                };
+                format!(
+                    "{} {operator} {}",
+                    self.expression(left)?,
+                    self.expression(right)?
+                )
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let mut args = Vec::new();
+                for argument in arguments {
+                    args.push(self.expression(argument)?);
+                }
+                let args = args.join(", ");
+                match &**callee {
+                    Expr::Variable { name, .. } if self.clock(name) => "Date.now()".to_owned(),
+                    Expr::Variable { name, .. } if self.declarations.initialized(&name.lexeme) => {
+                        format!("new {}().init({args})", ident(name))
+                    }
+                    Expr::Variable { name, .. }
+                        if self.declarations.classes.contains_key(&name.lexeme) =>
+                    {
+                        format!("new {}({args})", ident(name))
+                    }
+                    Expr::Get { object, name } => {
+                        format!("{}.{}({args})", self.expression(object)?, property(name))
+                    }
+                    Expr::Super {
+                        ancestor: Some(ancestor),
+                        method,
+                        ..
+                    } => {
+                        self.check_super()?;
+                        let method = property(method);
+                        let this = if args.is_empty() { "this" } else { "this, " };
+                        format!("{}.prototype.{method}.call({this}{args})", ident(ancestor))
+                    }
+                    Expr::Super { method, .. } => {
+                        self.check_super()?;
+                        format!("super.{}({args})", property(method))
+                    }
+                    callee => format!("{}({args})", self.expression(callee)?),
+                }
+            }
+            Expr::Get { object, name } => {
+                let object = self.expression(object)?;
+                if self.declarations.methods.contains(&name.lexeme) {
+                    let get = self.helper("$get");
+                    format!("{get}({object}, {})", quoted(&property(name)))
+                } else {
+                    format!("{object}.{}", property(name))
+                }
+            }
+            Expr::Grouping { ex } => format!("({})", self.expression(ex)?),
+            Expr::Index { .. } | Expr::SetIndex { .. } => return Err(self.unsupported("indexing")),
+            Expr::List { .. } => return Err(self.unsupported("lists")),
+            Expr::Literal(literal) => match literal {
+                Literal::Number(n) if n.0.is_infinite() => "Infinity".to_owned(),
+                Literal::Number(n) => format!("{}", n.0),
+                Literal::String(s) => quoted(s),
+                Literal::True => "true".to_owned(),
+                Literal::False => "false".to_owned(),
+                Literal::Nil => "null".to_owned(),
+                Literal::Bytes(_) => return Err(self.unsupported("byte strings")),
+            },
+            Expr::Logical { left, op, right } if boolean(left) && boolean(right) => {
+                let operator = if op.token_type == TokenType::Or {
+                    "||"
+                } else {
+                    "&&"
+                };
+                format!(
+                    "{} {operator} {}",
+                    self.expression(left)?,
+                    self.expression(right)?
+                )
+            }
+            Expr::Logical { left, op, right } => {
+                let helper = self.helper(if op.token_type == TokenType::Or {
+                    "$or"
+                } else {
+                    "$and"
+                });
+                format!(
+                    "{helper}({}, () => {})",
+                    self.expression(left)?,
+                    self.expression(right)?
+                )
+            }
+            Expr::Map { .. } => return Err(self.unsupported("maps")),
+            Expr::Match { .. } => return Err(self.unsupported("'match'")),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => format!(
+                "{}.{} = {}",
+                self.expression(object)?,
+                property(name),
+                self.expression(value)?
+            ),
+            Expr::Super {
+                ancestor: Some(ancestor),
+                method,
+                ..
+            } => {
+                self.check_super()?;
+                format!(
+                    "{}.prototype.{}.bind(this)",
+                    ident(ancestor),
+                    property(method)
+                )
+            }
+            Expr::Super { method, .. } => {
+                self.check_super()?;
+                format!("super.{}.bind(this)", property(method))
+            }
+            Expr::This { .. } if self.classes.is_empty() => {
+                return Err(self.error("Can't use 'this' outside of a class."))
+            }
+            Expr::This { .. } => "this".to_owned(),
+            Expr::Tuple { .. } => return Err(self.unsupported("tuples")),
+            Expr::Unary { op, right } if op.token_type == TokenType::Bang => {
+                format!("!{}", self.condition(right)?)
+            }
+            Expr::Unary { right, .. } => {
+                let right = self.expression(right)?;
+                // Not `--`, which is a decrement.
+                let space = if right.starts_with('-') { " " } else { "" };
+                format!("-{space}{right}")
+            }
+            Expr::Variable { name, .. } if self.clock(name) => "Date.now".to_owned(),
+            Expr::Variable { name, .. } => ident(name),
+        })
+    }
+
+    fn check_super(&self) -> Result<(), Error> {
+        match self.classes.last() {
+            None => Err(self.error("Can't use 'super' outside of a class.")),
+            Some(false) => Err(self.error("Can't use 'super' in a class with no superclass.")),
+            Some(true) => Ok(()),
+        }
+    }
+
+    /// `expr` as a JavaScript boolean, for `if`, loops and `!`.
+    fn condition(&mut self, expr: &Expr) -> Result<String, Error> {
+        match expr {
+            Expr::Logical { left, op, right } => {
+                let operator = if op.token_type == TokenType::Or {
+                    "||"
+                } else {
+                    "&&"
+                };
+                Ok(format!(
+                    "{} {operator} {}",
+                    self.condition(left)?,
+                    self.condition(right)?
+                ))
+            }
+            Expr::Grouping { ex } if matches!(**ex, Expr::Logical { .. }) => {
+                Ok(format!("({})", self.condition(ex)?))
+            }
+            _ if boolean(expr) => self.expression(expr),
+            _ => {
+                let truthy = self.helper("$truthy");
+                Ok(format!("{truthy}({})", self.expression(expr)?))
+            }
+        }
+    }
+}
@@ -0,0 +1,707 @@
+//! `--jit`: compiles hot functions that only compute with numbers to machine
+//! code with Cranelift, and calls that instead of walking their bodies.
+//!
+//! A function is compiled on its [`HOT_CALLS`]th call, from its syntax tree,
+//! if everything in it fits the subset this tier knows:
+//!
+//! - parameters, and local variables initialized with numbers;
+//! - number literals, `true` and `false`, arithmetic, comparisons, `!`,
+//!   `and` and `or` on booleans, and assignment to locals;
+//! - `if`, `while` and `for` with boolean conditions, `break`, `continue`
+//!   and `return` of a number;
+//! - calls of the function itself by its name.
+//!
+//! Anything else (globals, captured variables, strings, `print`, other
+//! calls) keeps the function in the tree-walker for good. The compiled code
+//! has no side effects, so when it meets something it can't finish (a call
+//! with arguments that aren't all numbers, division by zero, falling off the
+//! end to return `nil`, recursion deeper than the interpreter allows) it
+//! bails, and the call runs again in the tree-walker, which does what Lox
+//! does. A function that bails [`MAX_BAILS`] times isn't compiled code's
+//! business anymore.
+//!
+//! Compiled calls, including the recursive ones inside them, skip the
+//! interpreter's bookkeeping, so `--stats` doesn't count them and the
+//! debugger can't stop in them. The interpreter doesn't use compiled code
+//! under `--strict-math`, a time limit or a debug hook.
+
+use std::collections::HashMap;
+
+use cranelift_codegen::{
+    ir::{
+        condcodes::{FloatCC, IntCC},
+        types, AbiParam, Block, FuncRef, InstBuilder, MemFlags, StackSlotData, StackSlotKind,
+        Value,
+    },
+    settings::{self, Configurable},
+    Context,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+use crate::{
+    ast::{Expr, Literal, Stmt},
+    functions::LoxFunction,
+    object::Object,
+    token::TokenType,
+    types::Gc,
+};
+
+/// Calls a function takes before it is compiled.
+pub const HOT_CALLS: usize = 100;
+
+/// Bails a compiled function may take before calls stop trying it.
+pub const MAX_BAILS: usize = 10;
+
+/// Compiled code: the arguments, how many more calls deep it may go, and a
+/// flag it sets to bail. The result means nothing when it bails.
+type Code = unsafe extern "C" fn(*const f64, i64, *mut u8) -> f64;
+
+#[derive(thiserror::Error, Debug)]
+#[error("the JIT can't run here: {0}")]
+pub struct Unavailable(String);
+
+enum Entry {
+    Counting(usize),
+    Compiled {
+        code: Code,
+        /// Whether the code calls the function by its name, which has to
+        /// still mean this function for it to be right.
+        calls_itself: bool,
+        bails: usize,
+    },
+    Unsupported,
+}
+
+pub struct Jit {
+    module: JITModule,
+    context: Context,
+    builder_context: FunctionBuilderContext,
+    /// Keyed by the address of the function's body, which every closure
+    /// and bound method made from one declaration shares. The body is kept
+    /// so the address can't be reused for another.
+    functions: HashMap<*const Stmt, (Gc<Vec<Stmt>>, Entry)>,
+    compiled: usize,
+}
+
+// SAFETY: nothing in a `Jit` is reachable through a shared reference, so
+// the interpreter's lock serializes every use of it, and the compiled code
+// and the memory it lives in aren't tied to the thread that made them.
+#[cfg(feature = "sync")]
+unsafe impl Send for Jit {}
+#[cfg(feature = "sync")]
+unsafe impl Sync for Jit {}
+
+impl Jit {
+    pub fn new() -> Result<Self, Unavailable> {
+        let mut flags = settings::builder();
+        let options = [
+            ("use_colocated_libcalls", "false"),
+            ("is_pic", "false"),
+            ("opt_level", "speed"),
+        ];
+        for (name, value) in options {
+            flags
+                .set(name, value)
+                .map_err(|e| Unavailable(e.to_string()))?;
+        }
+        let isa = cranelift_native::builder()
+            .map_err(|e| Unavailable(e.to_owned()))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| Unavailable(e.to_string()))?;
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+
+        Ok(Self {
+            context: module.make_context(),
+            module,
+            builder_context: FunctionBuilderContext::new(),
+            functions: HashMap::new(),
+            compiled: 0,
+        })
+    }
+
+    /// Runs `function` compiled, if it is and `arguments` are all numbers,
+    /// allowing `depth` more nested calls. `None` means the caller has to
+    /// run it instead.
+    pub fn call(
+        &mut self,
+        function: &LoxFunction,
+        arguments: &[Gc<Object>],
+        depth: usize,
+    ) -> Option<f64> {
+        if function.is_initializer() {
+            return None;
+        }
+        let key = function.body().as_ptr();
+        let (_, entry) = self
+            .functions
+            .entry(key)
+            .or_insert_with(|| (function.shared_body().clone(), Entry::Counting(0)));
+        if let Entry::Counting(calls) = entry {
+            *calls += 1;
+            if *calls < HOT_CALLS {
+                return None;
+            }
+            let entry = match self.compile(function) {
+                Some((code, calls_itself)) => Entry::Compiled {
+                    code,
+                    calls_itself,
+                    bails: 0,
+                },
+                None => Entry::Unsupported,
+            };
+            self.functions.get_mut(&key).expect("counted above").1 = entry;
+        }
+
+        let Some((
+            _,
+            Entry::Compiled {
+                code,
+                calls_itself,
+                bails,
+            },
+        )) = self.functions.get_mut(&key)
+        else {
+            return None;
+        };
+        if *calls_itself && !names_itself(function) {
+            return None;
+        }
+        let arguments = arguments
+            .iter()
+            .map(|argument| match &**argument {
+                Object::Number(n) => Some(n.0),
+                _ => None,
+            })
+            .collect::<Option<Vec<f64>>>()?;
+
+        let mut bailed = 0u8;
+        // SAFETY: the code was compiled for this function, which takes
+        // `arguments.len()` parameters, as the interpreter has checked.
+        let result = unsafe { code(arguments.as_ptr(), depth as i64, &mut bailed) };
+        if bailed == 0 {
+            return Some(result);
+        }
+
+        *bails += 1;
+        if *bails >= MAX_BAILS {
+            self.functions.get_mut(&key).expect("run above").1 = Entry::Unsupported;
+        }
+        None
+    }
+
+    /// The machine code for `function`, and whether it calls itself, or
+    /// `None` if it steps outside what this tier compiles.
+    fn compile(&mut self, function: &LoxFunction) -> Option<(Code, bool)> {
+        let pointer = self.module.target_config().pointer_type();
+        self.module.clear_context(&mut self.context);
+        let signature = &mut self.context.func.signature;
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(types::I64));
+        signature.params.push(AbiParam::new(pointer));
+        signature.returns.push(AbiParam::new(types::F64));
+
+        let name = format!("lox{}", self.compiled);
+        self.compiled += 1;
+        let id = self
+            .module
+            .declare_function(&name, Linkage::Local, &self.context.func.signature)
+            .ok()?;
+
+        let calls_itself = Translator::translate(
+            &mut self.module,
+            &mut self.context,
+            &mut self.builder_context,
+            id,
+            function,
+        )?;
+
+        self.module.define_function(id, &mut self.context).ok()?;
+        self.module.clear_context(&mut self.context);
+        self.module.finalize_definitions().ok()?;
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the function was declared with `Code`'s signature.
+        Some((
+            unsafe { std::mem::transmute::<*const u8, Code>(code) },
+            calls_itself,
+        ))
+    }
+}
+
+/// Whether `function`'s name, looked up from where it was declared, is
+/// still `function`.
+fn names_itself(function: &LoxFunction) -> bool {
+    let Ok(named) = function.closure().borrow().get(function.name()) else {
+        return false;
+    };
+    match &*named {
+        Object::Function(f) => f
+            .as_lox_function()
+            .is_some_and(|f| f.body().as_ptr() == function.body().as_ptr()),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    Bool,
+}
+
+/// Marks what compiled code can't do. Translation stops there.
+struct Unsupported;
+
+type Translated<T> = Result<T, Unsupported>;
+
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    /// Innermost last.
+    scopes: Vec<HashMap<String, (Variable, Type)>>,
+    variables: u32,
+    /// Where `continue` and `break` go, innermost loop last.
+    loops: Vec<(Block, Block)>,
+    name: String,
+    arity: usize,
+    own: FuncRef,
+    calls_itself: bool,
+    depth: Value,
+    bail: Value,
+}
+
+impl<'a> Translator<'a> {
+    fn translate(
+        module: &mut JITModule,
+        context: &'a mut Context,
+        builder_context: &'a mut FunctionBuilderContext,
+        id: FuncId,
+        function: &LoxFunction,
+    ) -> Option<bool> {
+        let own = module.declare_func_in_func(id, &mut context.func);
+        let mut builder = FunctionBuilder::new(&mut context.func, builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let [arguments, depth, bail] = builder.block_params(entry) else {
+            unreachable!("compiled code takes three parameters");
+        };
+        let (arguments, depth, bail) = (*arguments, *depth, *bail);
+
+        let mut translator = Translator {
+            builder,
+            scopes: vec![HashMap::new()],
+            variables: 0,
+            loops: Vec::new(),
+            name: function.name().to_owned(),
+            arity: function.params().len(),
+            own,
+            calls_itself: false,
+            depth,
+            bail,
+        };
+
+        // Too deep already: let the interpreter report it.
+        let too_deep = translator
+            .builder
+            .ins()
+            .icmp_imm(IntCC::SignedLessThanOrEqual, depth, 0);
+        translator.bail_if(too_deep);
+
+        for (i, param) in function.params().iter().enumerate() {
+            let value = translator.builder.ins().load(
+                types::F64,
+                MemFlags::trusted(),
+                arguments,
+                (i * 8) as i32,
+            );
+            translator.declare(param, Type::Number, value);
+        }
+
+        let result = translator.block(function.body());
+        if result.is_err() {
+            return None;
+        }
+        // Falling off the end returns `nil`.
+        translator.bail_here();
+        translator.builder.seal_all_blocks();
+        translator.builder.finalize();
+        Some(translator.calls_itself)
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, value: Value) {
+        let variable = Variable::from_u32(self.variables);
+        self.variables += 1;
+        let clif = match ty {
+            Type::Number => types::F64,
+            Type::Bool => types::I8,
+        };
+        self.builder.declare_var(variable, clif);
+        self.builder.def_var(variable, value);
+        self.scopes
+            .last_mut()
+            .expect("there is always a scope")
+            .insert(name.to_owned(), (variable, ty));
+    }
+
+    fn lookup(&self, name: &str) -> Option<(Variable, Type)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    /// Sets the bail flag and returns.
+    fn bail_here(&mut self) {
+        let one = self.builder.ins().iconst(types::I8, 1);
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), one, self.bail, 0);
+        let zero = self.builder.ins().f64const(0.0);
+        self.builder.ins().return_(&[zero]);
+        self.unreachable();
+    }
+
+    fn bail_if(&mut self, condition: Value) {
+        let bail = self.builder.create_block();
+        let next = self.builder.create_block();
+        self.builder.ins().brif(condition, bail, &[], next, &[]);
+        self.builder.switch_to_block(bail);
+        self.bail_here();
+        self.builder.switch_to_block(next);
+    }
+
+    /// Starts a block nothing jumps to, for the code after a `return`,
+    /// `break` or `continue`.
+    fn unreachable(&mut self) {
+        let block = self.builder.create_block();
+        self.builder.switch_to_block(block);
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Translated<()> {
+        self.scopes.push(HashMap::new());
+        let result = statements.iter().try_for_each(|stmt| self.statement(stmt));
+        self.scopes.pop();
+        result
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Translated<()> {
+        match stmt {
+            Stmt::Block { statements, .. } => self.block(statements),
+            Stmt::Expression { expr, .. } => self.expression(expr).map(|_| ()),
+            Stmt::Var {
+                name,
+                initializer: Some(initializer),
+                ..
+            } => {
+                let (value, ty) = self.expression(initializer)?;
+                if ty != Type::Number {
+                    return Err(Unsupported);
+                }
+                self.declare(&name.lexeme, ty, value);
+                Ok(())
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.condition(condition)?;
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge = self.builder.create_block();
+                self.builder
+                    .ins()
+                    .brif(condition, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.statement(then_branch)?;
+                self.builder.ins().jump(merge, &[]);
+
+                self.builder.switch_to_block(else_block);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.builder.ins().jump(merge, &[]);
+
+                self.builder.switch_to_block(merge);
+                Ok(())
+            }
+            Stmt::While {
+                condition, body, ..
+            } => self.looping(Some(condition), None, body),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(HashMap::new());
+                let result = initializer
+                    .as_deref()
+                    .map_or(Ok(()), |initializer| self.statement(initializer))
+                    .and_then(|()| self.looping(condition.as_ref(), increment.as_ref(), body));
+                self.scopes.pop();
+                result
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {
+                let &(next, exit) = self.loops.last().ok_or(Unsupported)?;
+                let target = match stmt {
+                    Stmt::Break { .. } => exit,
+                    _ => next,
+                };
+                self.builder.ins().jump(target, &[]);
+                self.unreachable();
+                Ok(())
+            }
+            Stmt::Return {
+                value: Some(value), ..
+            } => {
+                let (value, ty) = self.expression(value)?;
+                if ty != Type::Number {
+                    return Err(Unsupported);
+                }
+                self.builder.ins().return_(&[value]);
+                self.unreachable();
+                Ok(())
+            }
+            _ => Err(Unsupported),
+        }
+    }
+
+    /// A loop, with `increment` run before every test of `condition` but
+    /// the first, where `continue` goes.
+    fn looping(
+        &mut self,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Translated<()> {
+        let header = self.builder.create_block();
+        let next = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit = self.builder.create_block();
+
+        self.builder.ins().jump(header, &[]);
+        self.builder.switch_to_block(header);
+        match condition {
+            Some(condition) => {
+                let condition = self.condition(condition)?;
+                self.builder
+                    .ins()
+                    .brif(condition, body_block, &[], exit, &[]);
+            }
+            None => {
+                self.builder.ins().jump(body_block, &[]);
+            }
+        }
+
+        self.builder.switch_to_block(body_block);
+        self.loops.push((next, exit));
+        let result = self.statement(body);
+        self.loops.pop();
+        result?;
+        self.builder.ins().jump(next, &[]);
+
+        self.builder.switch_to_block(next);
+        if let Some(increment) = increment {
+            self.expression(increment)?;
+        }
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.switch_to_block(exit);
+        Ok(())
+    }
+
+    fn condition(&mut self, expr: &Expr) -> Translated<Value> {
+        match self.expression(expr)? {
+            (value, Type::Bool) => Ok(value),
+            (_, Type::Number) => Err(Unsupported),
+        }
+    }
+
+    fn number(&mut self, expr: &Expr) -> Translated<Value> {
+        match self.expression(expr)? {
+            (value, Type::Number) => Ok(value),
+            (_, Type::Bool) => Err(Unsupported),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Translated<(Value, Type)> {
+        match expr {
+            Expr::Literal(Literal::Number(n)) => {
+                Ok((self.builder.ins().f64const(n.0), Type::Number))
+            }
+            Expr::Literal(Literal::True) => {
+                Ok((self.builder.ins().iconst(types::I8, 1), Type::Bool))
+            }
+            Expr::Literal(Literal::False) => {
+                Ok((self.builder.ins().iconst(types::I8, 0), Type::Bool))
+            }
+            Expr::Grouping { ex } => self.expression(ex),
+            Expr::Variable { name, .. } => {
+                let (variable, ty) = self.lookup(&name.lexeme).ok_or(Unsupported)?;
+                Ok((self.builder.use_var(variable), ty))
+            }
+            Expr::Assign { name, value, .. } => {
+                let (variable, ty) = self.lookup(&name.lexeme).ok_or(Unsupported)?;
+                let (value, value_ty) = self.expression(value)?;
+                if value_ty != ty {
+                    return Err(Unsupported);
+                }
+                self.builder.def_var(variable, value);
+                Ok((value, ty))
+            }
+            Expr::Unary { op, right } => match op.token_type {
+                TokenType::Minus => {
+                    let right = self.number(right)?;
+                    Ok((self.builder.ins().fneg(right), Type::Number))
+                }
+                TokenType::Bang => {
+                    let right = self.condition(right)?;
+                    Ok((self.builder.ins().bxor_imm(right, 1), Type::Bool))
+                }
+                _ => Err(Unsupported),
+            },
+            Expr::Binary { left, op, right } => self.binary(left, op.token_type, right),
+            Expr::Logical { left, op, right } => {
+                let left = self.condition(left)?;
+                let right_block = self.builder.create_block();
+                let merge = self.builder.create_block();
+                self.builder.append_block_param(merge, types::I8);
+                match op.token_type {
+                    TokenType::And => {
+                        self.builder
+                            .ins()
+                            .brif(left, right_block, &[], merge, &[left])
+                    }
+                    _ => self
+                        .builder
+                        .ins()
+                        .brif(left, merge, &[left], right_block, &[]),
+                };
+
+                self.builder.switch_to_block(right_block);
+                let right = self.condition(right)?;
+                self.builder.ins().jump(merge, &[right]);
+
+                self.builder.switch_to_block(merge);
+                Ok((self.builder.block_params(merge)[0], Type::Bool))
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let Expr::Variable { name, .. } = &**callee else {
+                    return Err(Unsupported);
+                };
+                if name.lexeme != self.name
+                    || self.lookup(&name.lexeme).is_some()
+                    || arguments.len() != self.arity
+                {
+                    return Err(Unsupported);
+                }
+                self.calls_itself = true;
+                self.call_itself(arguments)
+            }
+            _ => Err(Unsupported),
+        }
+    }
+
+    fn binary(&mut self, left: &Expr, op: TokenType, right: &Expr) -> Translated<(Value, Type)> {
+        let (left, left_ty) = self.expression(left)?;
+        let (right, right_ty) = self.expression(right)?;
+
+        if let (TokenType::EqualEqual | TokenType::BangEqual, Type::Bool, Type::Bool) =
+            (op, left_ty, right_ty)
+        {
+            let cc = match op {
+                TokenType::EqualEqual => IntCC::Equal,
+                _ => IntCC::NotEqual,
+            };
+            return Ok((self.builder.ins().icmp(cc, left, right), Type::Bool));
+        }
+        if (left_ty, right_ty) != (Type::Number, Type::Number) {
+            return Err(Unsupported);
+        }
+
+        match op {
+            TokenType::Plus => Ok((self.builder.ins().fadd(left, right), Type::Number)),
+            TokenType::Minus => Ok((self.builder.ins().fsub(left, right), Type::Number)),
+            TokenType::Star => Ok((self.builder.ins().fmul(left, right), Type::Number)),
+            TokenType::Slash => {
+                // Division by zero is an error the interpreter reports.
+                let zero = self.builder.ins().f64const(0.0);
+                let by_zero = self.builder.ins().fcmp(FloatCC::Equal, right, zero);
+                self.bail_if(by_zero);
+                Ok((self.builder.ins().fdiv(left, right), Type::Number))
+            }
+            TokenType::Greater => Ok((
+                self.builder.ins().fcmp(FloatCC::GreaterThan, left, right),
+                Type::Bool,
+            )),
+            TokenType::GreaterEqual => Ok((
+                self.builder
+                    .ins()
+                    .fcmp(FloatCC::GreaterThanOrEqual, left, right),
+                Type::Bool,
+            )),
+            TokenType::Less => Ok((
+                self.builder.ins().fcmp(FloatCC::LessThan, left, right),
+                Type::Bool,
+            )),
+            TokenType::LessEqual => Ok((
+                self.builder
+                    .ins()
+                    .fcmp(FloatCC::LessThanOrEqual, left, right),
+                Type::Bool,
+            )),
+            TokenType::EqualEqual => Ok((
+                self.builder.ins().fcmp(FloatCC::Equal, left, right),
+                Type::Bool,
+            )),
+            TokenType::BangEqual => Ok((
+                self.builder.ins().fcmp(FloatCC::NotEqual, left, right),
+                Type::Bool,
+            )),
+            _ => Err(Unsupported),
+        }
+    }
+
+    /// Calls the compiled function recursively, bailing out of this call too
+    /// if that one bails.
+    fn call_itself(&mut self, arguments: &[Expr]) -> Translated<(Value, Type)> {
+        let size = (arguments.len().max(1) * 8) as u32;
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            size,
+            3,
+        ));
+        for (i, argument) in arguments.iter().enumerate() {
+            let value = self.number(argument)?;
+            self.builder.ins().stack_store(value, slot, (i * 8) as i32);
+        }
+
+        let pointer = self.builder.func.dfg.value_type(self.bail);
+        let arguments = self.builder.ins().stack_addr(pointer, slot, 0);
+        let depth = self.builder.ins().iadd_imm(self.depth, -1);
+        let call = self
+            .builder
+            .ins()
+            .call(self.own, &[arguments, depth, self.bail]);
+        let result = self.builder.inst_results(call)[0];
+
+        let bailed = self
+            .builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), self.bail, 0);
+        let bail = self.builder.create_block();
+        let next = self.builder.create_block();
+        self.builder.ins().brif(bailed, bail, &[], next, &[]);
+        self.builder.switch_to_block(bail);
+        let zero = self.builder.ins().f64const(0.0);
+        self.builder.ins().return_(&[zero]);
+        self.builder.switch_to_block(next);
+        Ok((result, Type::Number))
+    }
+}
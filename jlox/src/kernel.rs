@@ -0,0 +1,514 @@
+//! A Jupyter kernel, so notebooks can run Lox: `jlox kernel install` registers
+//! it and Jupyter then starts `jlox kernel -f <connection file>`.
+//!
+//! Cells run one after another in the same session, so later cells see what
+//! earlier ones defined. A cell that is a single expression shows its value,
+//! like the REPL would; what cells print is streamed back as it happens.
+//!
+//! Messages follow the Jupyter messaging protocol 5.3 over [`crate::zmtp`].
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::{
+    streams::OutputStream,
+    zmtp::{Multipart, Socket, SocketType},
+    Lox,
+};
+
+const PROTOCOL_VERSION: &str = "5.3";
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// Everything that can go wrong starting or running the kernel.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid connection file: {0}")]
+    Connection(#[from] serde_json::Error),
+
+    #[error("Unsupported {0}.")]
+    Unsupported(String),
+}
+
+/// The connection file Jupyter writes before starting a kernel.
+#[derive(Debug)]
+struct ConnectionInfo {
+    transport: String,
+    ip: String,
+    shell_port: u16,
+    iopub_port: u16,
+    stdin_port: u16,
+    control_port: u16,
+    hb_port: u16,
+    key: String,
+    signature_scheme: String,
+}
+
+impl ConnectionInfo {
+    fn read(path: &Path) -> Result<Self, Error> {
+        let info: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let text = |name: &str| info[name].as_str().unwrap_or_default().to_string();
+        let port = |name: &str| {
+            info[name]
+                .as_u64()
+                .and_then(|port| u16::try_from(port).ok())
+                .ok_or_else(|| Error::Unsupported(format!("{name} {}", info[name])))
+        };
+
+        Ok(Self {
+            transport: text("transport"),
+            ip: text("ip"),
+            shell_port: port("shell_port")?,
+            iopub_port: port("iopub_port")?,
+            stdin_port: port("stdin_port")?,
+            control_port: port("control_port")?,
+            hb_port: port("hb_port")?,
+            key: text("key"),
+            signature_scheme: text("signature_scheme"),
+        })
+    }
+}
+
+/// Signs and checks messages with the connection's key. An empty key turns
+/// signing off.
+#[derive(Clone)]
+struct Signer {
+    key: Vec<u8>,
+}
+
+impl Signer {
+    fn sign(&self, parts: &[Vec<u8>]) -> Vec<u8> {
+        if self.key.is_empty() {
+            return Vec::new();
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("any key size works");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+            .into_bytes()
+    }
+}
+
+/// A decoded message: who sent it and its four JSON parts.
+#[derive(Debug)]
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Value,
+    content: Value,
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header["msg_type"].as_str().unwrap_or_default()
+    }
+}
+
+/// Sends messages on behalf of the kernel's session.
+#[derive(Clone)]
+struct Sender {
+    signer: Signer,
+    session: String,
+    counter: Arc<AtomicU64>,
+}
+
+impl Sender {
+    fn header(&self, msg_type: &str) -> Value {
+        let id = self.counter.fetch_add(1, Ordering::Relaxed);
+        json!({
+            "msg_id": format!("{}-{id}", self.session),
+            "session": self.session,
+            "username": "jlox",
+            "date": timestamp(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        })
+    }
+
+    fn send(
+        &self,
+        socket: &Socket,
+        identities: Vec<Vec<u8>>,
+        parent: &Value,
+        msg_type: &str,
+        content: Value,
+    ) -> io::Result<()> {
+        let parts: Vec<Vec<u8>> = [self.header(msg_type), parent.clone(), json!({}), content]
+            .iter()
+            .map(|part| part.to_string().into_bytes())
+            .collect();
+
+        let mut message = identities;
+        message.push(DELIMITER.to_vec());
+        message.push(self.signer.sign(&parts));
+        message.extend(parts);
+        socket.send(message)
+    }
+}
+
+/// Where a cell's output goes: `stream` messages on IOPub, one per write.
+struct StreamWriter {
+    name: &'static str,
+    iopub: Socket,
+    sender: Sender,
+    /// The header of the request being run.
+    parent: Arc<Mutex<Value>>,
+}
+
+impl Write for StreamWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let parent = self.parent.lock().unwrap().clone();
+        let text = String::from_utf8_lossy(data);
+        let content = json!({ "name": self.name, "text": text });
+        self.sender
+            .send(&self.iopub, vec![], &parent, "stream", content)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Channel {
+    Shell,
+    Control,
+}
+
+/// A running kernel and its session.
+pub struct Kernel {
+    lox: Lox,
+    signer: Signer,
+    sender: Sender,
+    shell: Socket,
+    control: Socket,
+    iopub: Socket,
+    requests: mpsc::Receiver<(Channel, Multipart)>,
+    parent: Arc<Mutex<Value>>,
+    execution_count: u64,
+}
+
+impl Kernel {
+    /// Binds the sockets the connection file at `path` names.
+    pub fn start(path: &Path) -> Result<Self, Error> {
+        let info = ConnectionInfo::read(path)?;
+        if info.transport != "tcp" {
+            return Err(Error::Unsupported(format!(
+                "transport {:?}",
+                info.transport
+            )));
+        }
+        if !matches!(info.signature_scheme.as_str(), "hmac-sha256" | "") {
+            return Err(Error::Unsupported(format!(
+                "signature scheme {:?}",
+                info.signature_scheme
+            )));
+        }
+
+        let (tx, requests) = mpsc::channel();
+        let address = |port| (info.ip.as_str(), port);
+        let forward = |channel: fn() -> Channel| {
+            let tx = tx.clone();
+            move |message| {
+                let _ = tx.send((channel(), message));
+                None
+            }
+        };
+
+        let shell = Socket::bind(
+            address(info.shell_port),
+            SocketType::Router,
+            forward(|| Channel::Shell),
+        )?;
+        let control = Socket::bind(
+            address(info.control_port),
+            SocketType::Router,
+            forward(|| Channel::Control),
+        )?;
+        let iopub = Socket::bind(address(info.iopub_port), SocketType::Pub, |_| None)?;
+        // Cells can't read input yet, but clients connect to it anyway.
+        Socket::bind(address(info.stdin_port), SocketType::Router, |_| None)?;
+        Socket::bind(address(info.hb_port), SocketType::Rep, Some)?;
+
+        let signer = Signer {
+            key: info.key.into_bytes(),
+        };
+        let sender = Sender {
+            signer: signer.clone(),
+            session: format!("jlox-{}", std::process::id()),
+            counter: Arc::default(),
+        };
+        let parent = Arc::new(Mutex::new(json!({})));
+
+        let mut lox = Lox::new();
+        for (stream, name) in [
+            (OutputStream::Stdout, "stdout"),
+            (OutputStream::Stderr, "stderr"),
+        ] {
+            lox.redirect_output(
+                stream,
+                StreamWriter {
+                    name,
+                    iopub: iopub.clone(),
+                    sender: sender.clone(),
+                    parent: parent.clone(),
+                },
+            );
+        }
+
+        Ok(Self {
+            lox,
+            signer,
+            sender,
+            shell,
+            control,
+            iopub,
+            requests,
+            parent,
+            execution_count: 0,
+        })
+    }
+
+    /// Answers requests until a client asks the kernel to shut down.
+    pub fn run(&mut self) -> Result<(), Error> {
+        while let Ok((channel, message)) = self.requests.recv() {
+            let socket = match channel {
+                Channel::Shell => self.shell.clone(),
+                Channel::Control => self.control.clone(),
+            };
+            let Some(request) = self.decode(message) else {
+                continue;
+            };
+
+            *self.parent.lock().unwrap() = request.header.clone();
+            self.status("busy")?;
+            let done = self.handle(&socket, request)?;
+            self.status("idle")?;
+            if done {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits off the identities and checks the signature, dropping
+    /// messages that don't verify.
+    fn decode(&self, mut message: Multipart) -> Option<Message> {
+        let delimiter = message.iter().position(|part| part == DELIMITER)?;
+        let mut parts = message.split_off(delimiter + 1);
+        message.pop();
+        if parts.len() < 5 {
+            return None;
+        }
+
+        let signature = parts.remove(0);
+        parts.truncate(4);
+        if signature != self.signer.sign(&parts) {
+            return None;
+        }
+
+        let mut parts = parts.iter().map(|part| serde_json::from_slice(part).ok());
+        let header = parts.next()??;
+        let content = parts.nth(2)??;
+        Some(Message {
+            identities: message,
+            header,
+            content,
+        })
+    }
+
+    fn status(&self, state: &str) -> io::Result<()> {
+        self.publish("status", json!({ "execution_state": state }))
+    }
+
+    fn publish(&self, msg_type: &str, content: Value) -> io::Result<()> {
+        let parent = self.parent.lock().unwrap().clone();
+        self.sender
+            .send(&self.iopub, vec![], &parent, msg_type, content)
+    }
+
+    /// Answers `request`; true if the kernel should stop.
+    fn handle(&mut self, socket: &Socket, request: Message) -> io::Result<bool> {
+        let (reply_type, content) = match request.msg_type() {
+            "kernel_info_request" => ("kernel_info_reply", kernel_info()),
+            "execute_request" => ("execute_reply", self.execute(&request.content)?),
+            "comm_info_request" => ("comm_info_reply", json!({ "status": "ok", "comms": {} })),
+            "shutdown_request" => {
+                let restart = request.content["restart"].as_bool().unwrap_or(false);
+                let content = json!({ "status": "ok", "restart": restart });
+                self.sender.send(
+                    socket,
+                    request.identities,
+                    &request.header,
+                    "shutdown_reply",
+                    content,
+                )?;
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+
+        self.sender.send(
+            socket,
+            request.identities,
+            &request.header,
+            reply_type,
+            content,
+        )?;
+        Ok(false)
+    }
+
+    /// Runs a cell and returns the content of its `execute_reply`.
+    fn execute(&mut self, request: &Value) -> io::Result<Value> {
+        let code = request["code"].as_str().unwrap_or_default();
+        if !request["silent"].as_bool().unwrap_or(false) {
+            self.execution_count += 1;
+        }
+        let count = self.execution_count;
+        self.publish(
+            "execute_input",
+            json!({ "code": code, "execution_count": count }),
+        )?;
+
+        let result = if self.lox.is_expression(code) {
+            self.lox.eval_expression(code).map(Some)
+        } else {
+            self.lox.execute(code).map(|()| None)
+        };
+        self.lox.wait_for_events();
+
+        match result {
+            Ok(value) => {
+                if let Some(value) = value.filter(|value| *value != crate::Value::Nil) {
+                    self.publish(
+                        "execute_result",
+                        json!({
+                            "execution_count": count,
+                            "data": { "text/plain": value.to_string() },
+                            "metadata": {},
+                        }),
+                    )?;
+                }
+                Ok(json!({
+                    "status": "ok",
+                    "execution_count": count,
+                    "payload": [],
+                    "user_expressions": {},
+                }))
+            }
+            Err(err) => {
+                let error = json!({
+                    "ename": "Error",
+                    "evalue": err.to_string(),
+                    "traceback": [err.to_string()],
+                });
+                self.publish("error", error.clone())?;
+
+                let mut reply = error;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(count);
+                Ok(reply)
+            }
+        }
+    }
+}
+
+fn kernel_info() -> Value {
+    json!({
+        "status": "ok",
+        "protocol_version": PROTOCOL_VERSION,
+        "implementation": "jlox",
+        "implementation_version": env!("CARGO_PKG_VERSION"),
+        "language_info": {
+            "name": "lox",
+            "version": env!("CARGO_PKG_VERSION"),
+            "mimetype": "text/x-lox",
+            "file_extension": ".lox",
+        },
+        "banner": "Lox, from Crafting Interpreters",
+        "help_links": [],
+    })
+}
+
+/// The current time as ISO 8601 in UTC.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let (days, seconds) = (now.as_secs() / 86400, now.as_secs() % 86400);
+
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        now.subsec_micros()
+    )
+}
+
+/// Where Jupyter looks for kernels: `$JUPYTER_DATA_DIR/kernels`, or the
+/// per-user data directory of the platform.
+fn kernels_dir() -> Result<PathBuf, Error> {
+    let data = match std::env::var_os("JUPYTER_DATA_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => std::env::var_os("APPDATA")
+            .map(|dir| PathBuf::from(dir).join("jupyter"))
+            .ok_or_else(|| Error::Unsupported("system without %APPDATA%".to_string()))?,
+        None => {
+            let home = std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .ok_or_else(|| Error::Unsupported("system without $HOME".to_string()))?;
+            if cfg!(target_os = "macos") {
+                home.join("Library/Jupyter")
+            } else {
+                home.join(".local/share/jupyter")
+            }
+        }
+    };
+    Ok(data.join("kernels"))
+}
+
+/// Registers the running executable as the `lox` kernel and returns the
+/// directory of its spec.
+pub fn install() -> Result<PathBuf, Error> {
+    let exe = std::env::current_exe()?;
+    let dir = kernels_dir()?.join("lox");
+    std::fs::create_dir_all(&dir)?;
+
+    let spec = json!({
+        "argv": [exe, "kernel", "-f", "{connection_file}"],
+        "display_name": "Lox",
+        "language": "lox",
+    });
+    std::fs::write(dir.join("kernel.json"), format!("{spec:#}\n"))?;
+    Ok(dir)
+}
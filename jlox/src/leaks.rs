@@ -0,0 +1,125 @@
+//! `--leak-check`: reports the scopes and instances that reference
+//! counting can't free because they refer to themselves.
+//!
+//! Values are shared through [`Gc`], so a cycle, like a function declared
+//! in another one that keeps the scope of the call holding it, or an
+//! instance with a field pointing back at it, outlives everything else.
+//! With the check on, the interpreter registers the scopes of calls,
+//! blocks and loops and every instance as it makes them, along with the
+//! line that made them. At the end of the program, whatever is still alive
+//! but can't be reached from the globals is only kept by a cycle.
+
+use std::{collections::HashSet, fmt::Display};
+
+use crate::{
+    class::Instance,
+    environment::Environment,
+    object::Object,
+    types::{Gc, GcCell, GcWeak},
+    visit::{Edge, ValueVisitor},
+};
+
+/// A scope or instance that outlived the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    /// What it is, e.g. `the scope of a call to 'make'`.
+    pub what: String,
+    /// The line of the statement that made it.
+    pub line: usize,
+}
+
+impl Display for Leak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Leak: {} made at line {} is kept alive only by a cycle.",
+            self.what, self.line
+        )
+    }
+}
+
+/// Everything made since the check was turned on.
+#[derive(Default)]
+pub(crate) struct Registry {
+    scopes: Vec<(GcWeak<GcCell<Environment>>, Leak)>,
+    instances: Vec<(GcWeak<GcCell<Instance>>, Leak)>,
+    /// How many entries there may be before the dead ones are dropped.
+    prune_at: usize,
+}
+
+impl Registry {
+    pub fn scope(&mut self, scope: &Gc<GcCell<Environment>>, what: String, line: usize) {
+        self.prune();
+        self.scopes
+            .push((Gc::downgrade(scope), Leak { what, line }));
+    }
+
+    pub fn instance(&mut self, instance: &Gc<GcCell<Instance>>, line: usize) {
+        self.prune();
+        let class = instance.borrow().class().borrow().name().to_owned();
+        let what = format!("a {class} instance");
+        self.instances
+            .push((Gc::downgrade(instance), Leak { what, line }));
+    }
+
+    /// What is still alive but can't be reached from `globals`, in the
+    /// order it was made: scopes first, then instances.
+    pub fn leaks(&self, globals: &Gc<GcCell<Environment>>) -> Vec<Leak> {
+        let mut reach = Reach::default();
+        reach.scopes.insert(Gc::as_ptr(globals) as usize);
+        // The globals as one value, so what they share is walked once.
+        let values = globals.borrow().values.values().cloned().collect();
+        Object::Tuple(values).visit(&mut reach);
+
+        let scopes = self.scopes.iter().filter(|(scope, _)| {
+            scope.strong_count() > 0 && !reach.scopes.contains(&(scope.as_ptr() as usize))
+        });
+        let instances = self.instances.iter().filter(|(instance, _)| {
+            instance.strong_count() > 0 && !reach.instances.contains(&(instance.as_ptr() as usize))
+        });
+        scopes
+            .map(|(_, leak)| leak.clone())
+            .chain(instances.map(|(_, leak)| leak.clone()))
+            .collect()
+    }
+
+    /// Drops the entries of what has been freed once there are twice as
+    /// many as there were alive last time.
+    fn prune(&mut self) {
+        if self.scopes.len() + self.instances.len() < self.prune_at {
+            return;
+        }
+        self.scopes.retain(|(scope, _)| scope.strong_count() > 0);
+        self.instances
+            .retain(|(instance, _)| instance.strong_count() > 0);
+        self.prune_at = (2 * (self.scopes.len() + self.instances.len())).max(1024);
+    }
+}
+
+/// The addresses of the scopes and instances reached.
+#[derive(Default)]
+struct Reach {
+    scopes: HashSet<usize>,
+    instances: HashSet<usize>,
+}
+
+impl ValueVisitor for Reach {
+    fn enter(&mut self, _edge: Edge<'_>, value: &Object) -> bool {
+        match value {
+            Object::Instance(instance) => {
+                self.instances.insert(Gc::as_ptr(instance) as usize);
+            }
+            Object::Function(function) => {
+                let mut scope = function
+                    .as_lox_function()
+                    .map(|function| function.closure().clone());
+                while let Some(current) = scope {
+                    self.scopes.insert(Gc::as_ptr(&current) as usize);
+                    scope = current.borrow().enclosing.clone();
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+}
@@ -0,0 +1,776 @@
+use std::{
+    fs,
+    io::{self, Error, Result, Write},
+};
+
+pub mod ast;
+pub mod bytes;
+pub mod callgraph;
+pub mod capture;
+pub mod class;
+mod codec;
+pub mod collections;
+pub mod debugger;
+pub mod dialect;
+pub mod docs;
+pub mod edit;
+pub mod environment;
+pub mod events;
+pub mod ffi;
+pub mod functions;
+pub mod host;
+pub mod incremental;
+pub mod inspect;
+pub mod interpreter;
+pub mod javascript;
+#[cfg(feature = "jit")]
+pub mod jit;
+#[cfg(feature = "jupyter")]
+pub mod kernel;
+pub mod leaks;
+pub mod limits;
+pub mod lint;
+pub mod math;
+pub mod metrics;
+pub mod natives;
+pub mod net;
+pub mod object;
+pub mod parser;
+pub mod permissions;
+pub mod printer;
+pub mod program;
+pub mod replay;
+pub mod resolver;
+pub mod runtime;
+pub mod scanner;
+pub mod selftest;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod serve;
+pub mod settings;
+pub mod snapshot;
+pub mod stack;
+pub mod stats;
+pub mod streams;
+pub mod strings;
+pub mod tasks;
+pub mod token;
+pub mod transpile;
+pub mod typecheck;
+pub mod types;
+pub mod visit;
+pub mod visualize;
+#[cfg(feature = "jupyter")]
+pub mod zmtp;
+
+use ast::ExprVisitor;
+pub use capture::{run_captured, RunReport};
+use dialect::Dialect;
+use events::Completion;
+use incremental::ParseCache;
+use interpreter::{Interpreter, TopLevel};
+use limits::Limits;
+use math::MathMode;
+use natives::NativeInfo;
+use object::Object;
+use parser::Parser;
+use permissions::{Capability, Permissions};
+use program::Program;
+use replay::{Inputs, Trace};
+use resolver::Resolver;
+use scanner::Scanner;
+use settings::Settings;
+use snapshot::Snapshot;
+use stats::{CountingAllocator, Stats};
+pub use types::Value;
+use types::{Gc, GcCell, MaybeSync};
+
+/// Everything that can go wrong running a piece of source.
+#[derive(thiserror::Error, Debug)]
+pub enum LoxError {
+    #[error("{0}")]
+    Parse(#[from] parser::Error),
+
+    #[error("{0}")]
+    Resolve(#[from] resolver::Error),
+
+    #[error("Error: {0}")]
+    Runtime(#[from] interpreter::Error),
+
+    #[error("{0}")]
+    Program(program::Report),
+}
+
+/// How many results the REPL keeps: `_` holds the last, `_2` the one
+/// before and so on.
+const HISTORY: usize = 3;
+
+/// An interpreter session. With the `sync` feature it is `Send + Sync` and can
+/// be moved to another thread (natives and host classes must be thread-safe
+/// too).
+pub struct Lox {
+    interpreter: Gc<GcCell<Interpreter>>,
+    parse_cache: ParseCache,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self {
+            interpreter: Gc::new(GcCell::new(Interpreter::new())),
+            parse_cache: ParseCache::new(),
+        }
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        native: impl functions::Callable<E = interpreter::Error> + 'static,
+    ) {
+        self.interpreter.borrow_mut().define_native(name, native);
+    }
+
+    /// Like [`Lox::define_native`], with documentation and a required
+    /// capability that show up in `:help`. The capability is checked before
+    /// every call, so the native itself doesn't need to.
+    pub fn define_native_with(
+        &mut self,
+        info: NativeInfo,
+        native: impl functions::Callable<E = interpreter::Error> + 'static,
+    ) {
+        self.interpreter
+            .borrow_mut()
+            .define_native_with(info, native);
+    }
+
+    /// Metadata for every native defined in this session.
+    pub fn natives(&self) -> Vec<NativeInfo> {
+        self.interpreter
+            .borrow()
+            .natives()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn define_async_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(
+                &mut Interpreter,
+                Vec<Gc<Object>>,
+                Completion,
+            ) -> std::result::Result<(), interpreter::Error>
+            + MaybeSync
+            + 'static,
+    {
+        self.interpreter
+            .borrow_mut()
+            .define_async_native(name, arity, function);
+    }
+
+    pub fn register_class(&mut self, class: Gc<host::HostClass>) {
+        self.interpreter.borrow_mut().register_class(class);
+    }
+
+    pub fn grant(&mut self, capability: Capability) {
+        self.interpreter
+            .borrow_mut()
+            .permissions_mut()
+            .grant(capability);
+    }
+
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        *self.interpreter.borrow_mut().permissions_mut() = permissions;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.interpreter.borrow().limits()
+    }
+
+    /// Applies `limits` to everything parsed and run from now on.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.borrow_mut().set_limits(limits);
+        self.parse_cache.set_limits(limits);
+    }
+
+    /// See [`Interpreter::redirect_output`].
+    pub fn redirect_output(
+        &mut self,
+        stream: streams::OutputStream,
+        writer: impl io::Write + MaybeSync + 'static,
+    ) {
+        self.interpreter
+            .borrow_mut()
+            .redirect_output(stream, writer);
+    }
+
+    pub fn restore_output(&mut self, stream: streams::OutputStream) {
+        self.interpreter.borrow_mut().restore_output(stream);
+    }
+
+    /// See [`Interpreter::redirect_input`].
+    pub fn redirect_input(&mut self, reader: impl io::BufRead + MaybeSync + 'static) {
+        self.interpreter.borrow_mut().redirect_input(reader);
+    }
+
+    pub fn restore_input(&mut self) {
+        self.interpreter.borrow_mut().restore_input();
+    }
+
+    /// See [`Interpreter::set_debug_hook`].
+    pub fn set_debug_hook(&mut self, hook: impl interpreter::DebugHook + MaybeSync + 'static) {
+        self.interpreter.borrow_mut().set_debug_hook(hook);
+    }
+
+    pub fn clear_debug_hook(&mut self) {
+        self.interpreter.borrow_mut().clear_debug_hook();
+    }
+
+    pub fn top_level(&self) -> TopLevel {
+        self.interpreter.borrow().top_level()
+    }
+
+    /// Chooses whether the top level of what runs from now on defines
+    /// globals or locals; see [`TopLevel`].
+    pub fn set_top_level(&mut self, top_level: TopLevel) {
+        self.interpreter.borrow_mut().set_top_level(top_level);
+    }
+
+    pub fn dialect(&self) -> &Dialect {
+        self.parse_cache.dialect()
+    }
+
+    /// Scans and parses everything from now on as `dialect`, imports
+    /// included, and defines the natives it expects.
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.interpreter.borrow_mut().set_dialect(dialect.clone());
+        self.parse_cache.set_dialect(dialect);
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.interpreter.borrow().settings()
+    }
+
+    /// Turns the checks of `settings` on or off for what runs from now on.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.interpreter.borrow_mut().set_settings(settings);
+    }
+
+    /// Chooses whether arithmetic that overflows or gives NaN is an error;
+    /// see [`MathMode`].
+    pub fn set_math_mode(&mut self, mode: MathMode) {
+        self.interpreter.borrow_mut().set_math_mode(mode);
+    }
+
+    /// See [`Interpreter::set_print_display`].
+    pub fn set_print_display(&mut self, display: bool) {
+        self.interpreter.borrow_mut().set_print_display(display);
+    }
+
+    /// See [`Interpreter::enable_jit`].
+    #[cfg(feature = "jit")]
+    pub fn enable_jit(&mut self) -> std::result::Result<(), jit::Unavailable> {
+        self.interpreter.borrow_mut().enable_jit()
+    }
+
+    /// Writes the current global environment to `path`.
+    pub fn save_snapshot(&mut self, path: &str) -> std::result::Result<(), snapshot::Error> {
+        let snapshot = Snapshot::capture(&mut self.interpreter.borrow_mut());
+        fs::write(path, snapshot.to_string())?;
+        Ok(())
+    }
+
+    /// Restores globals previously written with [`Lox::save_snapshot`].
+    pub fn load_snapshot(&mut self, path: &str) -> std::result::Result<(), snapshot::Error> {
+        let snapshot = Snapshot::parse(&fs::read_to_string(path)?)?;
+        snapshot.restore(&self.interpreter)
+    }
+
+    /// Starts recording the external inputs of this session.
+    pub fn start_recording(&mut self) {
+        *self.interpreter.borrow_mut().inputs_mut() = Inputs::record();
+    }
+
+    /// Writes the inputs recorded since [`Lox::start_recording`] to `path`.
+    pub fn save_recording(&mut self, path: &str) -> std::result::Result<(), replay::Error> {
+        let interpreter = self.interpreter.borrow();
+        let trace = interpreter
+            .inputs()
+            .trace()
+            .ok_or(replay::Error::NotRecording)?;
+        fs::write(path, trace.to_string())?;
+        Ok(())
+    }
+
+    /// Serves external inputs from a trace written by [`Lox::save_recording`].
+    pub fn replay(&mut self, path: &str) -> std::result::Result<(), replay::Error> {
+        let trace = Trace::parse(&fs::read_to_string(path)?)?;
+        *self.interpreter.borrow_mut().inputs_mut() = Inputs::replay(trace);
+        Ok(())
+    }
+
+    /// Scans, parses, resolves and runs `source`, returning the first error.
+    /// Imports need the `read` capability.
+    pub fn execute(&mut self, source: &str) -> std::result::Result<(), LoxError> {
+        let mut statements = self.parse(source)?;
+
+        // Outside of a file, imports are relative to the working directory.
+        let imports: Vec<&str> = statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                ast::Stmt::Import { path, .. } => Some(path.as_str()),
+                _ => None,
+            })
+            .collect();
+        if !imports.is_empty() {
+            let program = {
+                let interpreter = self.interpreter.borrow();
+                let permissions = interpreter.permissions();
+                permissions.check(Capability::Read, "import")?;
+                Program::load_imports(imports, self.dialect().clone(), permissions)
+            };
+            self.run_files(&program, false)?;
+        }
+        statements.retain(|stmt| !matches!(stmt, ast::Stmt::Import { .. }));
+
+        let statements = self.scope_top_level(statements);
+        self.resolve(&statements)?;
+        self.interpret(statements)
+    }
+
+    /// Runs `source` in a scope of its own: it can read and assign globals,
+    /// but its declarations are gone once it finishes. Imports aren't loaded,
+    /// so running one is an error.
+    pub fn run_isolated(&mut self, source: &str) -> std::result::Result<(), LoxError> {
+        let statements = vec![ast::Stmt::Block {
+            statements: self.parse(source)?,
+            boundary: ast::Boundary::NONE,
+        }];
+        self.resolve(&statements)?;
+        self.interpret(statements)
+    }
+
+    /// An owned copy of the global `name`, if it is defined.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter
+            .borrow()
+            .get_global(name)
+            .map(|value| value.to_value())
+    }
+
+    /// Defines or overwrites the global `name` with a host value. Like
+    /// natives, it survives [`Lox::reset`].
+    pub fn set_global(&mut self, name: &str, value: impl host::IntoLox) {
+        self.interpreter
+            .borrow_mut()
+            .define_host_global(name, Gc::new(value.into_lox()));
+    }
+
+    /// Calls the global function `name`, or returns `Ok(None)` if scripts
+    /// didn't define it; see [`Interpreter::call_if_exists`]. Arguments are
+    /// made with [`host::IntoLox`], e.g. `vec![dt.into_lox()]`.
+    pub fn call_if_exists(
+        &mut self,
+        name: &str,
+        arguments: Vec<Object>,
+    ) -> std::result::Result<Option<Value>, LoxError> {
+        let arguments = arguments.into_iter().map(Gc::new).collect();
+        let result = self
+            .interpreter
+            .borrow_mut()
+            .call_if_exists(name, arguments)?;
+        Ok(result.map(|value| value.to_value()))
+    }
+
+    /// Clears every global scripts defined; see [`Interpreter::reset`].
+    pub fn reset(&mut self) {
+        self.interpreter.borrow_mut().reset();
+    }
+
+    /// What this session has done so far. Allocation counts cover the whole
+    /// process and stay zero unless [`CountingAllocator`] is installed.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            peak_live_objects: CountingAllocator::peak_live(),
+            allocations: CountingAllocator::allocations(),
+            ..self.interpreter.borrow().stats()
+        }
+    }
+
+    /// Registers the scopes and instances made from now on, to report the
+    /// ones only cycles keep alive with [`Lox::leaks`].
+    pub fn enable_leak_check(&mut self) {
+        self.interpreter.borrow_mut().enable_leak_check();
+    }
+
+    /// What only cycles keep alive; see [`leaks`].
+    pub fn leaks(&self) -> Vec<leaks::Leak> {
+        self.interpreter.borrow().leaks()
+    }
+
+    /// Evaluates a single expression (a trailing `;` is optional) against the
+    /// session's globals and returns an owned copy of its value.
+    pub fn eval_expression(&mut self, source: &str) -> std::result::Result<Value, LoxError> {
+        Ok(self.evaluate_source(source)?.to_value())
+    }
+
+    fn evaluate_source(&mut self, source: &str) -> std::result::Result<Gc<Object>, LoxError> {
+        let expr = Parser::new(Scanner::new(source).with_dialect(self.dialect()))
+            .with_dialect(self.dialect())
+            .with_limits(self.limits())
+            .parse_expression()?;
+        Resolver::new(self.interpreter.clone()).resolve_expression(&expr)?;
+
+        Ok(self.interpreter.borrow_mut().evaluate(expr)?)
+    }
+
+    /// Whether `source` is a single expression, optionally followed by `;`.
+    pub(crate) fn is_expression(&self, source: &str) -> bool {
+        Parser::new(Scanner::new(source).with_dialect(self.dialect()))
+            .with_dialect(self.dialect())
+            .parse_expression()
+            .is_ok()
+    }
+
+    /// Binds `value` to `_`, moving the earlier results along to `_2`, `_3`
+    /// and so on, as the REPL does with the value of each expression.
+    fn remember(&mut self, value: Gc<Object>) {
+        let name = |n: usize| {
+            if n == 1 {
+                "_".to_owned()
+            } else {
+                format!("_{n}")
+            }
+        };
+        let mut interpreter = self.interpreter.borrow_mut();
+        for n in (2..=HISTORY).rev() {
+            if let Some(previous) = interpreter.get_global(&name(n - 1)) {
+                interpreter.set_global(&name(n), previous);
+            }
+        }
+        interpreter.set_global("_", value);
+    }
+
+    /// Runs every file of `program`, each after the files it imports. Nothing
+    /// runs if any file has a diagnostic.
+    pub fn run_program(&mut self, program: &Program) -> std::result::Result<(), LoxError> {
+        self.run_files(program, true)
+    }
+
+    /// Runs `program`, applying the [`TopLevel`] mode to its entry files
+    /// if `entries_are_scripts` and never to the files they import.
+    fn run_files(
+        &mut self,
+        program: &Program,
+        entries_are_scripts: bool,
+    ) -> std::result::Result<(), LoxError> {
+        if !program.report().is_empty() {
+            return Err(LoxError::Program(program.report().clone()));
+        }
+
+        let forbidden = program.strict_errors(&self.interpreter.borrow());
+        if !forbidden.is_empty() {
+            return Err(LoxError::Program(program::Report {
+                diagnostics: forbidden,
+            }));
+        }
+
+        let settings = self.interpreter.borrow().settings();
+        let mismatches = program.type_mismatches();
+        if settings.forbid_type_mismatches && !mismatches.is_empty() {
+            return Err(LoxError::Program(program::Report {
+                diagnostics: mismatches,
+            }));
+        }
+
+        {
+            let mut interpreter = self.interpreter.borrow_mut();
+            let mut warnings = mismatches;
+            if settings.warn_undefined_globals {
+                warnings.extend(program.undefined_globals(&interpreter));
+            }
+            for warning in warnings {
+                let warning = format!("Warning: {warning}\n");
+                let _ = interpreter
+                    .streams_mut()
+                    .write_all(streams::OutputStream::Stderr, warning.as_bytes());
+            }
+        }
+
+        for file in program.files() {
+            // The program already loaded what these import.
+            let mut statements: Vec<_> = file
+                .statements()
+                .unwrap_or_default()
+                .iter()
+                .filter(|stmt| !matches!(stmt, ast::Stmt::Import { .. }))
+                .cloned()
+                .collect();
+            if entries_are_scripts && program.entries().iter().any(|entry| entry == file.path()) {
+                statements = self.scope_top_level(statements);
+            }
+            self.resolve(&statements)?;
+            self.interpret(statements)?;
+        }
+
+        Ok(())
+    }
+
+    /// Hot reloading: re-reads the file at `path` and runs only its top-level
+    /// function and class declarations, so they replace the live ones while
+    /// every other global keeps its value. A class that is already defined
+    /// is updated in place, so existing instances get the new methods;
+    /// closures and bound methods taken before the reload keep the old code.
+    /// Returns the names redefined.
+    pub fn reload(&mut self, path: &str) -> std::result::Result<Vec<String>, LoxError> {
+        let program = self.load([path]);
+        if !program.report().is_empty() {
+            return Err(LoxError::Program(program.report().clone()));
+        }
+        let file = program
+            .file(path)
+            .expect("Expect the entry file to be loaded.");
+
+        let mut names = vec![];
+        for statement in file.statements().unwrap_or_default() {
+            let name = match statement {
+                ast::Stmt::Function { name, .. } | ast::Stmt::Class { name, .. } => {
+                    name.lexeme.clone()
+                }
+                _ => continue,
+            };
+
+            let old = self.interpreter.borrow().get_global(&name);
+            let statements = vec![statement.clone()];
+            self.resolve(&statements)?;
+            self.interpret(statements)?;
+
+            if let Some(old) = old {
+                let mut interpreter = self.interpreter.borrow_mut();
+                let new = interpreter.get_global(&name);
+                if let (Object::Class(old_class), Some(Object::Class(new_class))) =
+                    (&*old, new.as_deref())
+                {
+                    let updated = new_class.borrow().clone();
+                    *old_class.borrow_mut() = updated;
+                    interpreter.set_global(&name, old.clone());
+                }
+            }
+            names.push(name);
+        }
+
+        Ok(names)
+    }
+
+    /// Reads `entries` and their imports with this interpreter's permissions.
+    fn load(&self, entries: impl IntoIterator<Item = impl AsRef<std::path::Path>>) -> Program {
+        let interpreter = self.interpreter.borrow();
+        Program::load_with(entries, self.dialect().clone(), interpreter.permissions())
+    }
+
+    /// In [`TopLevel::Script`] mode, wraps `statements` in a block so their
+    /// declarations are locals.
+    fn scope_top_level(&self, statements: Vec<ast::Stmt>) -> Vec<ast::Stmt> {
+        match self.top_level() {
+            TopLevel::Interactive => statements,
+            TopLevel::Script => vec![ast::Stmt::Block {
+                statements,
+                boundary: ast::Boundary::NONE,
+            }],
+        }
+    }
+
+    /// First stage: turns source into statements. Declarations unchanged
+    /// since the previous call are taken from the parse cache.
+    pub fn parse(&mut self, source: &str) -> std::result::Result<Vec<ast::Stmt>, LoxError> {
+        Ok(self.parse_cache.parse(source)?)
+    }
+
+    /// Second stage: binds every variable use to its declaration.
+    pub fn resolve(&mut self, statements: &[ast::Stmt]) -> std::result::Result<(), LoxError> {
+        let _span = tracing::debug_span!("resolve").entered();
+        Resolver::new(self.interpreter.clone()).resolve(statements)?;
+        Ok(())
+    }
+
+    /// Last stage: runs resolved statements.
+    pub fn interpret(&mut self, statements: Vec<ast::Stmt>) -> std::result::Result<(), LoxError> {
+        self.interpreter.borrow_mut().interpret(statements)?;
+        Ok(())
+    }
+
+    pub fn parse_cache(&self) -> &ParseCache {
+        &self.parse_cache
+    }
+
+    pub fn run(&mut self, bytes: String) -> std::result::Result<(), parser::Error> {
+        match self.execute(&bytes) {
+            Err(LoxError::Parse(err)) => return Err(err),
+            Err(err) => eprintln!("{err}"),
+            Ok(()) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Runs deferred and completed callbacks without blocking. Returns `true`
+    /// while async natives are still outstanding, so embedders can call it
+    /// again from their own loop.
+    pub fn poll_events(&mut self) -> bool {
+        self.pump_events(false);
+        !self.interpreter.borrow_mut().events_mut().is_idle()
+    }
+
+    /// Runs callbacks until every deferred call and async native is done.
+    pub fn wait_for_events(&mut self) {
+        self.pump_events(true);
+    }
+
+    fn pump_events(&mut self, wait: bool) {
+        while let Err(err) = self.interpreter.borrow_mut().run_events(wait) {
+            eprintln!("Error: {err}");
+        }
+    }
+
+    pub fn run_file(&mut self, path: String) -> Result<()> {
+        match self.run_program(&self.load([path])) {
+            Err(LoxError::Program(report)) => {
+                eprintln!("{report}");
+                return Err(Error::from_raw_os_error(65));
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                return Err(Error::from_raw_os_error(70));
+            }
+            Ok(()) => (),
+        }
+        self.wait_for_events();
+
+        Ok(())
+    }
+
+    pub fn run_prompt(&mut self) -> Result<()> {
+        // Doc comments are held back until the declaration they document.
+        let mut docs = String::new();
+
+        while let Some(line) = prompt()? {
+            if let Some(topic) = line.trim().strip_prefix(":help") {
+                self.help(topic.trim());
+                continue;
+            }
+
+            if line.trim() == ":reset" {
+                self.reset();
+                continue;
+            }
+
+            if let Some(path) = line.trim().strip_prefix(":reload") {
+                match self.reload(path.trim()) {
+                    Ok(names) => println!("Reloaded {}.", names.join(", ")),
+                    Err(err) => eprintln!("{err}"),
+                }
+                continue;
+            }
+
+            if line.trim_start().starts_with("///") {
+                docs.push_str(&line);
+                continue;
+            }
+
+            let source = std::mem::take(&mut docs) + &line;
+            if self.is_expression(&source) {
+                // A lone expression shows its value, unless it is nil.
+                match self.evaluate_source(&source) {
+                    Ok(value) if matches!(*value, Object::Nil) => (),
+                    Ok(value) => {
+                        println!("{}", inspect::inspect(&value, inspect::DEFAULT_DEPTH));
+                        self.remember(value);
+                    }
+                    Err(err) => eprintln!("{err}"),
+                }
+            } else if let Err(err) = self.run(source) {
+                eprintln!("Error: {err}");
+            }
+            self.wait_for_events();
+        }
+
+        Ok(())
+    }
+
+    /// `:help` lists every native, `:help name` describes a native or a
+    /// global function or class.
+    fn help(&self, topic: &str) {
+        let interpreter = self.interpreter.borrow();
+        let natives = interpreter.natives();
+
+        if topic.is_empty() {
+            for native in natives.iter() {
+                println!("{}", native.signature());
+            }
+            return;
+        }
+
+        match natives.get(topic) {
+            Some(native) => {
+                println!("{}", native.signature());
+                if !native.doc().is_empty() {
+                    println!("    {}", native.doc());
+                }
+            }
+            None => match interpreter.get_global(topic) {
+                Some(value) => {
+                    match &*value {
+                        Object::Function(f) => match f.as_lox_function() {
+                            Some(f) => println!("fun {}({})", f.name(), f.params().join(", ")),
+                            None => println!("{topic}({})", f.arity()),
+                        },
+                        Object::Class(_) => println!("class {topic}"),
+                        _ => println!("{value}"),
+                    }
+                    if let Some(doc) = interpreter.doc_of(&value) {
+                        for line in doc.lines() {
+                            println!("    {line}");
+                        }
+                    }
+                }
+                None => println!("No help for '{topic}'."),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Lox>();
+};
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fuzzing entry point: parses arbitrary text. Never panics.
+pub fn parse_source(source: &str) -> std::result::Result<Vec<ast::Stmt>, parser::Error> {
+    Parser::new(Scanner::new(source)).parse()
+}
+
+/// Fuzzing entry point: runs arbitrary text in a fresh session without any
+/// capabilities. Never panics; hostile input only ever produces an error.
+pub fn run_source(source: &str) -> std::result::Result<(), LoxError> {
+    let mut lox = Lox::new();
+    lox.execute(source)?;
+    lox.interpreter.borrow_mut().run_events(true)?;
+    Ok(())
+}
+
+fn prompt() -> Result<Option<String>> {
+    let mut line = String::new();
+    print!("> ");
+    io::stdout().flush()?;
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(line))
+}
@@ -0,0 +1,48 @@
+//! Language limits shared by the parser and the interpreter. Embedders can
+//! tighten them for untrusted scripts, or loosen them with care.
+
+use std::time::Duration;
+
+/// Bounds a script must stay within. Going over one is a parse or runtime
+/// error at the offending token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Most arguments a call may pass and parameters a function may declare.
+    pub max_arguments: usize,
+    /// Deepest the parser lets expressions and statements nest, so that
+    /// `((((...))))` is a parse error instead of overflowing the stack of the
+    /// parser or of the passes that walk the tree after it. Each link of a
+    /// chain such as `a.b.c`, `f(1)(2)`, `x |> f |> g` or `1 + 2 + 3` counts
+    /// as a level too, as it wraps the tree in one more node. On a small
+    /// stack the parser gives up sooner, with the same kind of error.
+    pub max_nesting: usize,
+    /// Deepest chain of nested calls before a script is stopped. How deep a
+    /// script can go also depends on how much each call nests, so runs stop
+    /// with the same error when the thread's stack is nearly used up, see
+    /// [`crate::stack`].
+    pub max_call_depth: usize,
+    /// Longest string, in bytes, that `+` and natives such as `inspect` or
+    /// `StringBuilder.append` may build.
+    pub max_string_length: usize,
+    /// Longest a run may take, from the start of
+    /// [`Interpreter::interpret`](crate::interpreter::Interpreter::interpret)
+    /// through the callbacks it schedules. Checked before every statement.
+    pub max_duration: Option<Duration>,
+    /// Most heap bytes a run may hold at once, counted on the thread it runs
+    /// on. Checked before every statement, and only in programs that install
+    /// [`CountingAllocator`](crate::stats::CountingAllocator).
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_arguments: 255,
+            max_nesting: 200,
+            max_call_depth: 255,
+            max_string_length: 1 << 30,
+            max_duration: None,
+            max_memory: None,
+        }
+    }
+}
@@ -0,0 +1,522 @@
+//! `jlox lint`: likely mistakes that are valid Lox, found without running it.
+//!
+//! The checks are flow-insensitive: they look at what the tree contains, not
+//! at the order it runs in. A local counts as read if anything reads it, so
+//! a store is only reported when no read of its variable exists at all.
+//!
+//! Mechanical lints come with a [`Fix`], which `jlox lint --fix` makes:
+//! an unused local gets a leading `_`, and an assignment used as a
+//! condition becomes `==`. [`fix_source`] also puts in a `;` wherever the
+//! parser wanted one.
+//!
+//! ```
+//! use jlox::{lint::{lint, Rule}, parse_source};
+//!
+//! let statements = parse_source("fun f(a) { var unused = a; if (nil) return a == a; }").unwrap();
+//! let rules: Vec<_> = lint(&statements).into_iter().map(|lint| lint.rule).collect();
+//! assert_eq!(rules, [Rule::DeadStore, Rule::ConstantCondition, Rule::SelfComparison]);
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Expr, Literal, Stmt},
+    docs::{self, Error},
+    edit::{self, Edit},
+    parser::{self, Parser},
+    program::Diagnostic,
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// A value stored in a local that nothing reads. Locals whose name
+    /// starts with `_` are meant to be unused, and aren't reported.
+    DeadStore,
+    /// An `if`, `while` or `for` condition that is a literal.
+    ConstantCondition,
+    /// `x == x`, `x < x` and the like, which don't depend on `x`.
+    SelfComparison,
+    /// `if (x = 1)`, most likely meant as `==`. Parentheses around the
+    /// assignment say it's meant.
+    AssignmentInCondition,
+}
+
+impl Rule {
+    /// The name warnings are tagged with, e.g. `dead-store`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DeadStore => "dead-store",
+            Self::ConstantCondition => "constant-condition",
+            Self::SelfComparison => "self-comparison",
+            Self::AssignmentInCondition => "assignment-in-condition",
+        }
+    }
+}
+
+/// One warning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub rule: Rule,
+    pub line: usize,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+/// A mechanical change to the source that resolves a lint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    /// What it does, e.g. "Rename to '_unused'".
+    pub title: String,
+    /// Edits to the source the statements were parsed from.
+    pub edits: Vec<Edit>,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule.name())
+    }
+}
+
+/// The warnings about `statements`, by line. The tree doesn't keep the `=`
+/// of an assignment, so only [`lint_source`] can fix one in a condition.
+pub fn lint(statements: &[Stmt]) -> Vec<Lint> {
+    Linter::default().run(statements)
+}
+
+/// The warnings about `source`, with every fix there is for them.
+pub fn lint_source(source: &str) -> Result<Vec<Lint>, parser::Error> {
+    let tokens: Vec<Token> = Scanner::new(source).collect();
+    let statements = Parser::new(tokens.clone()).parse()?;
+    let linter = Linter {
+        tokens: &tokens,
+        ..Linter::default()
+    };
+    Ok(linter.run(&statements))
+}
+
+/// `source` with the fix for every lint made, and a `;` put in wherever
+/// the parser wanted one. Other parse errors are returned.
+pub fn fix_source(source: &str) -> Result<String, parser::Error> {
+    let mut source = source.to_owned();
+    loop {
+        match lint_source(&source) {
+            Ok(lints) => {
+                let edits: Vec<Edit> = lints
+                    .into_iter()
+                    .filter_map(|lint| lint.fix)
+                    .flat_map(|fix| fix.edits)
+                    .collect();
+                return Ok(edit::apply(&source, &edits).0);
+            }
+            Err(error) => {
+                let edit = missing_semicolon(&source, &error).ok_or(error)?;
+                source = edit::apply(&source, &[edit]).0;
+            }
+        }
+    }
+}
+
+/// A `;` right after the token before the one the parser wanted it at,
+/// if that's what `error` is about.
+fn missing_semicolon(source: &str, error: &parser::Error) -> Option<Edit> {
+    let (msg, at) = match error {
+        parser::Error::Bad { token, msg } => (msg, token.span().start),
+        parser::Error::UnexpectedEof { msg, .. } => (msg, source.len()),
+        _ => return None,
+    };
+    if !msg.starts_with("Expect ';'") {
+        return None;
+    }
+
+    let previous = Scanner::new(source)
+        .take_while(|token| token.span().start < at && token.token_type != TokenType::EOF)
+        .filter(|token| token.token_type != TokenType::DocComment)
+        .last()?;
+    // Already one there, so another wouldn't help.
+    if previous.token_type == TokenType::Semicolon {
+        return None;
+    }
+    Some(Edit::insert(previous.span().end, ";"))
+}
+
+/// Makes the fixes in every `.lox` file under `root`, returning the files
+/// that changed.
+pub fn fix(root: &Path) -> Result<Vec<PathBuf>, Error> {
+    edit::rewrite_files(root, false, fix_source)
+}
+
+/// Lints every `.lox` file under `root` (or `root` itself if it is a file),
+/// sorted by path.
+pub fn check(root: &Path) -> Result<Vec<Diagnostic>, Error> {
+    let files = docs::sources(root)?;
+
+    let mut diagnostics = Vec::new();
+    for file in files {
+        let source = fs::read_to_string(&file).map_err(docs::io_error(&file))?;
+        let lints = lint_source(&source).map_err(|error| Error::Parse {
+            path: file.clone(),
+            error,
+        })?;
+
+        diagnostics.extend(lints.into_iter().map(|lint| Diagnostic {
+            path: file.clone(),
+            line: Some(lint.line),
+            message: lint.to_string(),
+        }));
+    }
+
+    Ok(diagnostics)
+}
+
+/// A local variable and what happens to it.
+struct Local {
+    name: String,
+    /// Lines of the initializer and assignments that give it a value.
+    stores: Vec<usize>,
+    /// Where it's declared and assigned.
+    spans: Vec<Range<usize>>,
+    read: bool,
+}
+
+#[derive(Default)]
+struct Linter<'a> {
+    /// The tokens of the source, when linting one.
+    tokens: &'a [Token],
+    /// Innermost last, each naming its locals by index into `locals`. Empty
+    /// at the top level, whose variables other files may read.
+    scopes: Vec<HashMap<String, usize>>,
+    locals: Vec<Local>,
+    /// Every name declared, which a renamed local mustn't take.
+    names: HashSet<String>,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn run(mut self, statements: &[Stmt]) -> Vec<Lint> {
+        self.statements(statements);
+
+        for local in &self.locals {
+            if local.read || local.name.starts_with('_') {
+                continue;
+            }
+            let renamed = format!("_{}", local.name);
+            let mut fix = (!self.names.contains(&renamed)).then(|| Fix {
+                title: format!("Rename to '{renamed}'"),
+                edits: local
+                    .spans
+                    .iter()
+                    .map(|span| Edit::replace(span.clone(), renamed.clone()))
+                    .collect(),
+            });
+            for &line in &local.stores {
+                self.lints.push(Lint {
+                    rule: Rule::DeadStore,
+                    line,
+                    message: format!("Value stored in '{}' is never read.", local.name),
+                    fix: fix.take(),
+                });
+            }
+        }
+
+        self.lints.sort_by_key(|lint| (lint.line, lint.rule));
+        self.lints
+    }
+
+    fn declare(&mut self, name: &Token) {
+        self.names.insert(name.lexeme.clone());
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        scope.insert(name.lexeme.clone(), self.locals.len());
+        self.locals.push(Local {
+            name: name.lexeme.clone(),
+            stores: Vec::new(),
+            spans: vec![name.span()],
+            read: false,
+        });
+    }
+
+    fn lookup(&mut self, name: &Token) -> Option<&mut Local> {
+        let index = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))?;
+        Some(&mut self.locals[*index])
+    }
+
+    fn store(&mut self, name: &Token) {
+        if let Some(local) = self.lookup(name) {
+            local.stores.push(name.line());
+            if !local.spans.contains(&name.span()) {
+                local.spans.push(name.span());
+            }
+        }
+    }
+
+    fn scoped(&mut self, walk: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        walk(self);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        // Literals keep no token, so constant conditions are reported at
+        // their statement.
+        let line = stmt.boundary().line;
+        match stmt {
+            Stmt::Block { statements, .. } => self.scoped(|linter| linter.statements(statements)),
+            Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Import { .. } => (),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                self.declare(name);
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                for method in methods {
+                    if let Stmt::Function { params, body, .. } = method {
+                        self.function(params, body);
+                    }
+                }
+            }
+            Stmt::Destructure {
+                names, initializer, ..
+            } => {
+                self.expression(initializer);
+                for name in names {
+                    self.declare(name);
+                    self.store(name);
+                }
+            }
+            Stmt::Expression { expr, .. } | Stmt::Print { expr, .. } => self.expression(expr),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => self.scoped(|linter| {
+                if let Some(initializer) = initializer {
+                    linter.statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    linter.condition(condition, true, line);
+                }
+                linter.statement(body);
+                if let Some(increment) = increment {
+                    linter.expression(increment);
+                }
+            }),
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                self.declare(name);
+                self.function(params, body);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.condition(condition, false, line);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(name);
+                if initializer.is_some() {
+                    self.store(name);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.condition(condition, true, line);
+                self.statement(body);
+            }
+        }
+    }
+
+    fn function(&mut self, params: &[Token], body: &[Stmt]) {
+        self.scoped(|linter| {
+            params.iter().for_each(|param| linter.declare(param));
+            linter.statements(body);
+        });
+    }
+
+    /// Checks the condition of an `if` or, with `looping`, a loop. `while
+    /// (true)` is the usual loop that ends with `break`, so it is fine.
+    fn condition(&mut self, condition: &Expr, looping: bool, line: usize) {
+        let mut inner = condition;
+        while let Expr::Grouping { ex } = inner {
+            inner = ex;
+        }
+        match inner {
+            Expr::Literal(Literal::True) if looping => (),
+            Expr::Literal(literal) => self.lints.push(Lint {
+                rule: Rule::ConstantCondition,
+                line,
+                message: format!("Condition is always {}.", truthiness(literal)),
+                fix: None,
+            }),
+            _ => (),
+        }
+        if let Expr::Assign { name, .. } = condition {
+            self.lints.push(Lint {
+                rule: Rule::AssignmentInCondition,
+                line: name.line(),
+                message: format!("'{}' is assigned in a condition.", name.lexeme),
+                fix: self.equals_after(name).map(|equals| Fix {
+                    title: "Compare with '=='".to_owned(),
+                    edits: vec![Edit::replace(equals, "==")],
+                }),
+            });
+        }
+        self.expression(condition);
+    }
+
+    /// Where the `=` after `name` is, if the tokens are known.
+    fn equals_after(&self, name: &Token) -> Option<Range<usize>> {
+        let next = self
+            .tokens
+            .partition_point(|token| token.span().start < name.span().end);
+        let equals = self.tokens.get(next)?;
+        (equals.token_type == TokenType::Equal).then(|| equals.span())
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign { name, value, .. } => {
+                self.expression(value);
+                self.store(name);
+            }
+            Expr::Binary { left, op, right } => {
+                self.compare(left, op, right);
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                arguments.iter().for_each(|arg| self.expression(arg));
+            }
+            Expr::Get { object, .. } => self.expression(object),
+            Expr::Grouping { ex } => self.expression(ex),
+            Expr::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expr::List { elements, .. } | Expr::Tuple { elements } => {
+                elements.iter().for_each(|element| self.expression(element));
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expr::Match { subject, arms, .. } => {
+                self.expression(subject);
+                for arm in arms {
+                    self.scoped(|linter| {
+                        for name in arm.pattern.bindings() {
+                            linter.declare(name);
+                        }
+                        if let Some(guard) = &arm.guard {
+                            linter.expression(guard);
+                        }
+                        linter.expression(&arm.body);
+                    });
+                }
+            }
+            Expr::Set { object, value, .. } => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+            Expr::Unary { right, .. } => self.expression(right),
+            Expr::Variable { name, .. } => {
+                if let Some(local) = self.lookup(name) {
+                    local.read = true;
+                }
+            }
+            Expr::Literal(_) | Expr::Super { .. } | Expr::This { .. } => (),
+        }
+    }
+
+    fn compare(&mut self, left: &Expr, op: &Token, right: &Expr) {
+        use TokenType::*;
+
+        if !matches!(
+            op.token_type,
+            EqualEqual | BangEqual | Less | LessEqual | Greater | GreaterEqual
+        ) {
+            return;
+        }
+        if let (Expr::Variable { name: left, .. }, Expr::Variable { name: right, .. }) =
+            (left, right)
+        {
+            if left.lexeme == right.lexeme {
+                self.lints.push(Lint {
+                    rule: Rule::SelfComparison,
+                    line: op.line(),
+                    message: format!("'{}' is compared with itself.", left.lexeme),
+                    fix: None,
+                });
+            }
+        }
+    }
+}
+
+/// How a literal condition tests.
+fn truthiness(literal: &Literal) -> &'static str {
+    match literal {
+        Literal::False | Literal::Nil => "false",
+        _ => "true",
+    }
+}
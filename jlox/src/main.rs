@@ -1,107 +1,743 @@
 use std::{
-    cell::RefCell,
-    env, fs,
-    io::{self, Error, Result, Write},
-    rc::Rc,
+    env,
+    io::{self, BufReader, Error, Result},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
 };
 
-pub mod ast;
-pub mod class;
-pub mod environment;
-pub mod functions;
-pub mod interpreter;
-pub mod object;
-pub mod parser;
-pub mod resolver;
-pub mod scanner;
-pub mod token;
-pub mod types;
+use jlox::{
+    callgraph::CallGraph,
+    debugger::Debugger,
+    dialect,
+    docs::{self, Format},
+    interpreter::TopLevel,
+    javascript, lint,
+    math::MathMode,
+    metrics,
+    permissions::Capability,
+    printer, selftest,
+    serve::{self, Config},
+    settings::Settings,
+    stats::CountingAllocator,
+    transpile, typecheck,
+    visualize::Tree,
+    Lox,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn usage() -> Error {
+    eprintln!(
+        "Usage: jlox [--allow-read] [--allow-write] [--allow-net] [--allow-env] [--allow-run] [--allow-all]"
+    );
+    eprintln!(
+        "            [--load-snapshot file] [--save-snapshot file] [--record file] [--replay file]"
+    );
+    eprintln!("            [--script-scope] [--strict] [--strict-math] [--print-display] [--keyword word=spelling]");
+    eprintln!("            [--print-function] [--infer-semicolons] [--warn-undefined] [--typed] [--stats] [--leak-check] [--debug] [script]");
+    #[cfg(feature = "jit")]
+    eprintln!("       jlox --jit [flag...] [script]");
+    eprintln!("       jlox ast <file> [--dot | --d2 | --html]");
+    eprintln!("       jlox callgraph <file> [--dot]");
+    eprintln!("       jlox doc <path> [-o dir] [--html]");
+    eprintln!("       jlox fmt <path>... [--check]");
+    eprintln!("       jlox lint [--fix] <path>...");
+    eprintln!("       jlox metrics <path> [--json]");
+    eprintln!("       jlox selftest [--vm path] [--generate n] [--seed s] [corpus...]");
+    eprintln!("       jlox serve [--host h] [--port p] [--time-limit ms] [--memory-limit mb]");
+    eprintln!("       jlox transpile <file> [--js] [-o out.rs | -o out.js]");
+    eprintln!("       jlox types <file>");
+    #[cfg(feature = "jupyter")]
+    eprintln!("       jlox kernel (install | -f connection-file)");
+    Error::from_raw_os_error(64)
+}
+
+fn ast_usage() -> Error {
+    eprintln!("Usage: jlox ast <file> [--dot | --d2 | --html]");
+    Error::from_raw_os_error(64)
+}
+
+/// `jlox ast game.lox --dot | dot -Tsvg`: the parse tree of a script, as
+/// its S-expression dump, a Graphviz or D2 graph, or an HTML page.
+fn ast(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut path = None;
+    let mut format = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--dot" | "--d2" | "--html" if format.is_none() => format = Some(arg),
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(ast_usage()),
+        }
+    }
+
+    let path = path.ok_or_else(ast_usage)?;
+    let source = std::fs::read_to_string(&path).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(74)
+    })?;
+    let statements = jlox::parse_source(&source).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(65)
+    })?;
+
+    let tree = Tree::build(&statements);
+    match format.as_deref() {
+        Some("--dot") => print!("{}", tree.to_dot()),
+        Some("--d2") => print!("{}", tree.to_d2()),
+        Some(_) => print!("{}", tree.to_html(&path)),
+        None => statements.iter().for_each(|stmt| println!("{stmt}")),
+    }
+    Ok(())
+}
+
+fn callgraph_usage() -> Error {
+    eprintln!("Usage: jlox callgraph <file> [--dot]");
+    Error::from_raw_os_error(64)
+}
+
+/// `jlox callgraph game.lox --dot | dot -Tsvg`: the calls between the
+/// functions and methods of a script, as `caller -> callee` lines or as a
+/// Graphviz graph.
+fn callgraph(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut path = None;
+    let mut dot = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(callgraph_usage()),
+        }
+    }
+
+    let path = path.ok_or_else(callgraph_usage)?;
+    let source = std::fs::read_to_string(&path).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(74)
+    })?;
+    let statements = jlox::parse_source(&source).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(65)
+    })?;
 
-use interpreter::Interpreter;
-use parser::Parser;
-use resolver::Resolver;
-use scanner::Scanner;
+    let graph = CallGraph::extract(&statements);
+    if dot {
+        print!("{}", graph.to_dot());
+    } else {
+        for (caller, callee) in &graph.edges {
+            println!("{caller} -> {callee}");
+        }
+    }
+    Ok(())
+}
 
-pub struct Lox {
-    interpreter: Rc<RefCell<Interpreter>>,
+fn doc_usage() -> Error {
+    eprintln!("Usage: jlox doc <path> [-o dir] [--html]");
+    Error::from_raw_os_error(64)
 }
 
-impl Lox {
-    pub fn new() -> Self {
-        Self {
-            interpreter: Rc::new(RefCell::new(Interpreter::new())),
+/// `jlox doc src/ -o docs/`: writes API documentation for the `.lox` files
+/// under `path`.
+fn doc(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut root = None;
+    let mut out = PathBuf::from("docs");
+    let mut format = Format::Markdown;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--out" => out = args.next().ok_or_else(doc_usage)?.into(),
+            "--html" => format = Format::Html,
+            "--markdown" => format = Format::Markdown,
+            _ if root.is_none() && !arg.starts_with('-') => root = Some(PathBuf::from(arg)),
+            _ => return Err(doc_usage()),
+        }
+    }
+
+    let root = root.ok_or_else(doc_usage)?;
+    match docs::generate(&root, &out, format) {
+        Ok(pages) => {
+            println!("Wrote {} pages to {}", pages.len(), out.display());
+            Ok(())
+        }
+        Err(err @ docs::Error::Parse { .. }) => {
+            eprintln!("{err}");
+            Err(Error::from_raw_os_error(65))
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            Err(Error::from_raw_os_error(74))
         }
     }
+}
+
+fn fmt_usage() -> Error {
+    eprintln!("Usage: jlox fmt <path>... [--check]");
+    Error::from_raw_os_error(64)
+}
 
-    pub fn run(&mut self, bytes: String) -> std::result::Result<(), parser::Error> {
-        let mut scanner = Scanner::new(&bytes);
-        let tokens = scanner.scan_tokens();
-        // println!("{tokens:?}");
-        let mut parser = Parser::new(tokens);
+/// `jlox fmt src/`: reprints the `.lox` files under each path in the
+/// standard layout, keeping their comments. With `--check` it only lists
+/// the files that would change, and exits with 1 if there are any.
+fn fmt(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ if arg.starts_with('-') => return Err(fmt_usage()),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(fmt_usage());
+    }
 
-        let statements = parser.parse()?;
+    let mut unformatted = 0;
+    for path in paths {
+        match printer::format_files(path.as_ref(), check) {
+            Ok(changed) => {
+                for file in &changed {
+                    println!("{}", file.display());
+                }
+                unformatted += changed.len();
+            }
+            Err(err @ docs::Error::Parse { .. }) => {
+                eprintln!("{err}");
+                return Err(Error::from_raw_os_error(65));
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                return Err(Error::from_raw_os_error(74));
+            }
+        }
+    }
 
-        // println!("{statements:?}");
+    if check && unformatted > 0 {
+        return Err(Error::from_raw_os_error(1));
+    }
+    Ok(())
+}
 
-        let mut resolver = Resolver::new(self.interpreter.clone());
+fn lint_usage() -> Error {
+    eprintln!("Usage: jlox lint [--fix] <path>...");
+    Error::from_raw_os_error(64)
+}
 
-        if let Err(e) = resolver.resolve(&statements) {
-            eprintln!("{e}");
-            return Ok(());
+/// `jlox lint src/`: warns about dead stores, constant conditions and
+/// self-comparisons in the `.lox` files under each path. Exits with 1 if
+/// there were any. `--fix` first makes the fixes there are, naming each
+/// file it changes, and then reports what's left.
+fn lint(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut fix = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--fix" => fix = true,
+            _ if arg.starts_with('-') => return Err(lint_usage()),
+            _ => paths.push(arg),
         }
+    }
+    if paths.is_empty() {
+        return Err(lint_usage());
+    }
 
-        if let Err(err) = self.interpreter.borrow_mut().interpret(statements) {
-            eprintln!("Error: {err}");
+    let mut warnings = 0;
+    for path in paths {
+        let fixed = if fix {
+            lint::fix(path.as_ref())
+        } else {
+            Ok(Vec::new())
+        };
+        let checked = fixed.and_then(|fixed| {
+            for file in &fixed {
+                eprintln!("Fixed {}", file.display());
+            }
+            lint::check(path.as_ref())
+        });
+
+        match checked {
+            Ok(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    println!("{diagnostic}");
+                }
+                warnings += diagnostics.len();
+            }
+            Err(err @ docs::Error::Parse { .. }) => {
+                eprintln!("{err}");
+                return Err(Error::from_raw_os_error(65));
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                return Err(Error::from_raw_os_error(74));
+            }
         }
+    }
 
-        Ok(())
+    if warnings > 0 {
+        return Err(Error::from_raw_os_error(1));
     }
+    Ok(())
+}
+
+fn metrics_usage() -> Error {
+    eprintln!("Usage: jlox metrics <path> [--json]");
+    Error::from_raw_os_error(64)
+}
 
-    pub fn run_file(&mut self, path: String) -> Result<()> {
-        let bytes = fs::read_to_string(path)?;
-        if let Err(_err) = self.run(bytes) {
-            eprintln!("{:?}", _err);
+/// `jlox metrics src/`: statement counts, nesting depth and cyclomatic
+/// complexity of every function in the `.lox` files under `path`.
+fn metrics(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut root = None;
+    let mut json = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if root.is_none() && !arg.starts_with('-') => root = Some(PathBuf::from(arg)),
+            _ => return Err(metrics_usage()),
+        }
+    }
+
+    let root = root.ok_or_else(metrics_usage)?;
+    match metrics::scan(&root) {
+        Ok(files) if json => print!("{}", metrics::render_json(&files)),
+        Ok(files) => print!("{}", metrics::render_text(&files)),
+        Err(err @ docs::Error::Parse { .. }) => {
+            eprintln!("{err}");
             return Err(Error::from_raw_os_error(65));
         }
+        Err(err) => {
+            eprintln!("{err}");
+            return Err(Error::from_raw_os_error(74));
+        }
+    }
+    Ok(())
+}
+
+fn selftest_usage() -> Error {
+    eprintln!("Usage: jlox selftest [--vm path] [--generate n] [--seed s] [corpus...]");
+    Error::from_raw_os_error(64)
+}
+
+/// `jlox selftest --vm ../clox/clox tests/corpus --generate 100`: runs every
+/// corpus file and generated program through this interpreter and the VM and
+/// reports where they disagree.
+fn selftest(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut vm = PathBuf::from("clox");
+    let mut generate = 0;
+    let mut seed = 1;
+    let mut programs = Vec::new();
+
+    let number = |arg: Option<String>| -> Result<u64> {
+        arg.and_then(|n| n.parse().ok()).ok_or_else(selftest_usage)
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--vm" => vm = args.next().ok_or_else(selftest_usage)?.into(),
+            "--generate" => generate = number(args.next())?,
+            "--seed" => seed = number(args.next())?,
+            _ if !arg.starts_with('-') => programs.extend(selftest::corpus(arg.as_ref())?),
+            _ => return Err(selftest_usage()),
+        }
+    }
 
-        Ok(())
+    if programs.is_empty() && generate == 0 {
+        return Err(selftest_usage());
     }
 
-    pub fn run_prompt(&mut self) -> Result<()> {
-        loop {
-            if let Err(err) = self.run(prompt()?) {
-                eprintln!("Error: {err}");
+    let jlox = env::current_exe()?;
+    let scratch = env::temp_dir().join(format!("jlox-selftest-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch)?;
+    for seed in seed..seed + generate {
+        let path = scratch.join(format!("seed-{seed}.lox"));
+        std::fs::write(&path, selftest::generate(seed))?;
+        programs.push(path);
+    }
+
+    let mut mismatches = 0;
+    for program in &programs {
+        if let Some(mismatch) = selftest::compare(&jlox, &vm, program)? {
+            println!("{mismatch}");
+            mismatches += 1;
+        }
+    }
+
+    println!("{} programs, {mismatches} mismatches", programs.len());
+    if mismatches > 0 {
+        // Keep the generated programs around to reproduce the mismatches.
+        println!("Generated programs are in {}", scratch.display());
+        return Err(Error::from_raw_os_error(1));
+    }
+
+    std::fs::remove_dir_all(&scratch)?;
+    Ok(())
+}
+
+fn serve_usage() -> Error {
+    eprintln!(
+        "Usage: jlox serve [--host h] [--port p] [--time-limit ms] [--memory-limit mb] \
+         [--max-connections n]"
+    );
+    Error::from_raw_os_error(64)
+}
+
+/// `jlox serve --port 8080`: runs scripts POSTed to `/run` in a sandbox; see
+/// [`serve`].
+fn serve(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut config = Config::default();
+
+    let number = |arg: Option<String>| -> Result<u64> {
+        arg.and_then(|n| n.parse().ok()).ok_or_else(serve_usage)
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => config.host = args.next().ok_or_else(serve_usage)?,
+            "--port" => {
+                config.port = number(args.next())?.try_into().map_err(|_| serve_usage())?;
+            }
+            "--time-limit" => {
+                config.limits.max_duration = Some(Duration::from_millis(number(args.next())?));
+            }
+            "--memory-limit" => {
+                config.limits.max_memory = Some(number(args.next())? as usize * 1024 * 1024);
+            }
+            "--max-connections" => config.max_connections = number(args.next())? as usize,
+            _ => return Err(serve_usage()),
+        }
+    }
+
+    println!("Serving on http://{}:{}/run", config.host, config.port);
+    serve::serve(config)
+}
+
+fn types_usage() -> Error {
+    eprintln!("Usage: jlox types <file>");
+    Error::from_raw_os_error(64)
+}
+
+/// `jlox types game.lox`: the types inferred for the top-level variables
+/// and functions of a script, annotated or not, then the places it uses a
+/// value as a type it can't be. Exits with 1 if there were any.
+fn types(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(types_usage()),
+        }
+    }
+
+    let path = path.ok_or_else(types_usage)?;
+    let source = std::fs::read_to_string(&path).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(74)
+    })?;
+    let statements = jlox::parse_source(&source).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(65)
+    })?;
+
+    let inference = typecheck::infer(&statements);
+    for declaration in &inference.declarations {
+        println!("{path}:{}: {declaration}", declaration.line());
+    }
+    for mismatch in &inference.mismatches {
+        println!("{path}:{}: {}", mismatch.line, mismatch.message);
+    }
+
+    if !inference.mismatches.is_empty() {
+        return Err(Error::from_raw_os_error(1));
+    }
+    Ok(())
+}
+
+fn transpile_usage() -> Error {
+    eprintln!("Usage: jlox transpile <file> [--js] [-o out.rs | -o out.js]");
+    Error::from_raw_os_error(64)
+}
+
+/// `jlox transpile game.lox -o game.rs`: the script as a Rust program
+/// built on `jlox::runtime`, written to stdout without `-o`. With `--js`, or
+/// an output ending in `.js`, it's JavaScript instead, and `-o game.js` also
+/// writes its source map to `game.js.map`.
+fn transpile(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut path = None;
+    let mut out: Option<String> = None;
+    let mut js = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--out" => out = Some(args.next().ok_or_else(transpile_usage)?),
+            "--js" => js = true,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(transpile_usage()),
+        }
+    }
+
+    let path = path.ok_or_else(transpile_usage)?;
+    let source = std::fs::read_to_string(&path).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(74)
+    })?;
+    let statements = jlox::parse_source(&source).map_err(|err| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(65)
+    })?;
+
+    let name = Path::new(&path)
+        .file_name()
+        .map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+    let refused = |err: transpile::Error| {
+        eprintln!("{path}: {err}");
+        Error::from_raw_os_error(65)
+    };
+    let write = |out: &str, contents: String| {
+        std::fs::write(out, contents).map_err(|err| {
+            eprintln!("{out}: {err}");
+            Error::from_raw_os_error(74)
+        })
+    };
+
+    if js || out.as_ref().is_some_and(|out| out.ends_with(".js")) {
+        let js = javascript::to_js(&statements, &name).map_err(refused)?;
+        match out {
+            Some(out) => {
+                let file = Path::new(&out)
+                    .file_name()
+                    .map_or(out.clone(), |name| name.to_string_lossy().into_owned());
+                write(&format!("{out}.map"), js.source_map(&file))?;
+                write(
+                    &out,
+                    format!("{}//# sourceMappingURL={file}.map\n", js.code),
+                )?;
             }
+            None => print!("{}", js.code),
         }
+        return Ok(());
+    }
+
+    let rust = transpile::to_rust(&statements, &name).map_err(refused)?;
+    match out {
+        Some(out) => write(&out, rust)?,
+        None => print!("{rust}"),
     }
+    Ok(())
 }
 
-fn prompt() -> Result<String> {
-    let mut line = String::new();
-    print!("> ");
-    io::stdout().flush()?;
-    io::stdin().read_line(&mut line)?;
+/// `jlox kernel install` registers the Jupyter kernel, and Jupyter starts it
+/// with `jlox kernel -f connection.json`.
+#[cfg(feature = "jupyter")]
+fn kernel(mut args: impl Iterator<Item = String>) -> Result<()> {
+    use jlox::kernel::{self, Kernel};
 
-    Ok(line)
+    let result = match (args.next().as_deref(), args.next(), args.next()) {
+        (Some("install"), None, None) => kernel::install().map(|dir| {
+            println!("Installed the Lox kernel in {}", dir.display());
+        }),
+        (Some("-f"), Some(path), None) => {
+            Kernel::start(path.as_ref()).and_then(|mut kernel| kernel.run())
+        }
+        _ => return Err(usage()),
+    };
+
+    result.map_err(|err| {
+        eprintln!("{err}");
+        Error::from_raw_os_error(74)
+    })
 }
 
-fn main() -> Result<()> {
-    let mut args = env::args();
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // Errors with a raw code (usage, script and I/O failures) have been
+        // reported already and only set the exit status.
+        Err(err) => match err.raw_os_error() {
+            Some(code) => ExitCode::from(code as u8),
+            None => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
 
-    let _program_name = args.next();
+fn run() -> Result<()> {
+    // `RUST_LOG=jlox=debug` times each stage and shows every call, `trace`
+    // adds environment pushes and pops.
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let mut args = env::args().skip(1).peekable();
+
+    if args.next_if_eq("ast").is_some() {
+        return ast(args);
+    }
+
+    if args.next_if_eq("callgraph").is_some() {
+        return callgraph(args);
+    }
+
+    if args.next_if_eq("doc").is_some() {
+        return doc(args);
+    }
+
+    if args.next_if_eq("fmt").is_some() {
+        return fmt(args);
+    }
+    if args.next_if_eq("lint").is_some() {
+        return lint(args);
+    }
+
+    if args.next_if_eq("metrics").is_some() {
+        return metrics(args);
+    }
+
+    if args.next_if_eq("selftest").is_some() {
+        return selftest(args);
+    }
+
+    if args.next_if_eq("serve").is_some() {
+        return serve(args);
+    }
+
+    if args.next_if_eq("transpile").is_some() {
+        return transpile(args);
+    }
+
+    if args.next_if_eq("types").is_some() {
+        return types(args);
+    }
+
+    #[cfg(feature = "jupyter")]
+    if args.next_if_eq("kernel").is_some() {
+        return kernel(args);
+    }
 
     let mut program = Lox::new();
+    let mut save_snapshot = None;
+    let mut record = None;
+    let mut stats = false;
+    let mut debug = false;
+
+    while let Some(flag) = args.next_if(|arg| arg.starts_with("--")) {
+        match flag.as_str() {
+            "--allow-all" => Capability::ALL
+                .into_iter()
+                .for_each(|cap| program.grant(cap)),
+            "--load-snapshot" => {
+                let path = args.next().ok_or_else(usage)?;
+                if let Err(err) = program.load_snapshot(&path) {
+                    eprintln!("{err}");
+                    return Err(Error::from_raw_os_error(65));
+                }
+            }
+            "--save-snapshot" => save_snapshot = Some(args.next().ok_or_else(usage)?),
+            "--record" => {
+                record = Some(args.next().ok_or_else(usage)?);
+                program.start_recording();
+            }
+            "--stats" => stats = true,
+            "--leak-check" => program.enable_leak_check(),
+            "--debug" => debug = true,
+            "--script-scope" => program.set_top_level(TopLevel::Script),
+            "--strict" => program.set_settings(Settings {
+                warn_undefined_globals: program.settings().warn_undefined_globals,
+                forbid_type_mismatches: program.settings().forbid_type_mismatches,
+                ..Settings::strict()
+            }),
+            "--warn-undefined" => program.set_settings(Settings {
+                warn_undefined_globals: true,
+                ..program.settings()
+            }),
+            "--typed" => program.set_settings(Settings {
+                forbid_type_mismatches: true,
+                ..program.settings()
+            }),
+            "--strict-math" => program.set_math_mode(MathMode::Strict),
+            "--print-display" => program.set_print_display(true),
+            #[cfg(feature = "jit")]
+            "--jit" => {
+                if let Err(err) = program.enable_jit() {
+                    eprintln!("{err}");
+                }
+            }
+            "--print-function" => {
+                let dialect = program.dialect().clone().with_print_function();
+                program.set_dialect(dialect);
+            }
+            "--infer-semicolons" => {
+                let dialect = program.dialect().clone().with_semicolon_inference();
+                program.set_dialect(dialect);
+            }
+            "--keyword" => {
+                let rename = args.next().ok_or_else(usage)?;
+                let (word, spelling) = rename.split_once('=').ok_or_else(usage)?;
+                let keyword = dialect::standard_keyword(word).ok_or_else(usage)?;
+                let dialect = program.dialect().clone().with_keyword(keyword, spelling);
+                program.set_dialect(dialect);
+            }
+            "--replay" => {
+                let path = args.next().ok_or_else(usage)?;
+                if let Err(err) = program.replay(&path) {
+                    eprintln!("{err}");
+                    return Err(Error::from_raw_os_error(65));
+                }
+            }
+            _ => match Capability::from_flag(&flag) {
+                Some(capability) => program.grant(capability),
+                None => return Err(usage()),
+            },
+        }
+    }
 
     if let Some(source_path) = args.next() {
-        if let Some(_) = args.next() {
-            eprintln!("Usage: jlox [script]");
-            return Err(Error::from_raw_os_error(64));
+        if args.next().is_some() {
+            return Err(usage());
         };
 
+        if debug {
+            // The script keeps stdout; the debugger talks on stderr.
+            let source = std::fs::read_to_string(&source_path).unwrap_or_default();
+            program.set_debug_hook(Debugger::new(
+                &source,
+                BufReader::new(io::stdin()),
+                io::stderr(),
+            ));
+        }
         program.run_file(source_path)?;
     } else {
         program.run_prompt()?;
     };
 
+    if stats {
+        eprintln!("{}", program.stats());
+    }
+
+    for leak in program.leaks() {
+        eprintln!("{leak}");
+    }
+
+    if let Some(path) = record {
+        if let Err(err) = program.save_recording(&path) {
+            eprintln!("{err}");
+            return Err(Error::from_raw_os_error(74));
+        }
+    }
+
+    if let Some(path) = save_snapshot {
+        if let Err(err) = program.save_snapshot(&path) {
+            eprintln!("{err}");
+            return Err(Error::from_raw_os_error(74));
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,193 @@
+//! Numeric globals: the `nan` and `infinity` constants and the natives that
+//! classify and format numbers.
+//!
+//! Numbers are IEEE 754 doubles and compare as such: `nan == nan` is false
+//! and `nan != nan` is true, so `isNaN` is the only reliable test for it.
+//!
+//! `print` and string conversion use the shortest form that reads back as
+//! the same number; see [`Number`]'s `Display`. `toFixed` and `toPrecision`
+//! round to a given number of digits instead. All of them write `.` as the
+//! decimal point whatever the locale.
+
+use crate::{
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    token::{Token, TokenType},
+    types::{Gc, Number},
+};
+
+/// How arithmetic treats results that aren't finite numbers. Division by
+/// zero is an error either way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MathMode {
+    /// Results are what IEEE 754 says: `1e308 * 10` is `inf`.
+    #[default]
+    Ieee,
+    /// `--strict-math`: an operation on finite numbers that overflows to an
+    /// infinity, or one on numbers that aren't NaN that gives NaN, such as
+    /// `infinity - infinity`, is an error naming the operands and line, to
+    /// find where a bad value first appears.
+    Strict,
+}
+
+impl MathMode {
+    /// `left op right` for `+`, `-`, `*` and `/`.
+    pub fn binary(self, left: Number, op: &Token, right: Number) -> Result<Number, Error> {
+        let result = match op.token_type {
+            TokenType::Plus => left + right,
+            TokenType::Minus => left - right,
+            TokenType::Star => left * right,
+            TokenType::Slash if right == Number(0.0) => return Err(Error::ZeroDivision),
+            TokenType::Slash => left / right,
+            _ => unreachable!("{} is not arithmetic", op.lexeme),
+        };
+
+        let overflowed = result.is_infinite() && left.is_finite() && right.is_finite();
+        let undefined = result.is_nan() && !left.is_nan() && !right.is_nan();
+        if self == Self::Strict && (overflowed || undefined) {
+            return Err(Error::NonFiniteResult {
+                left,
+                op: op.clone(),
+                right,
+                result,
+            });
+        }
+        Ok(result)
+    }
+}
+
+/// Most digits `toFixed` and `toPrecision` produce after the point.
+const MAX_DIGITS: usize = 100;
+
+/// The arguments of `toFixed` and `toPrecision`: a number and a digit count
+/// in `min..=MAX_DIGITS`.
+fn number_and_digits(
+    name: &str,
+    arguments: &[Gc<Object>],
+    min: usize,
+) -> Result<(f64, usize), Error> {
+    let error = |msg: String| Error::NativeError {
+        name: name.to_string(),
+        msg,
+    };
+    let Object::Number(Number(x)) = *arguments[0] else {
+        return Err(error(format!("expected a number, got {}.", arguments[0])));
+    };
+    let digits = match &*arguments[1] {
+        Object::Number(n) => n.as_int().and_then(|i| usize::try_from(i).ok()),
+        _ => None,
+    };
+    match digits {
+        Some(digits) if (min..=MAX_DIGITS).contains(&digits) => Ok((x, digits)),
+        _ => Err(error(format!(
+            "digits must be a whole number from {min} to {MAX_DIGITS}."
+        ))),
+    }
+}
+
+/// `toFixed(x, digits)`: `x` rounded to `digits` digits after the point,
+/// e.g. `toFixed(2.345, 1)` is `"2.3"`. Rounds the exact binary value, so
+/// `toFixed(0.125, 2)` is `"0.12"` but `toFixed(0.135, 2)` is `"0.14"`.
+pub struct ToFixed;
+
+impl Callable for ToFixed {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let (x, digits) = number_and_digits("toFixed", &arguments, 0)?;
+        let text = format!("{x:.digits$}");
+        interpreter.check_string_length("toFixed", text.len())?;
+        Ok(Gc::new(Object::String(text)))
+    }
+}
+
+/// `toPrecision(x, digits)`: `x` rounded to `digits` significant digits,
+/// with an exponent if it would otherwise need zeros past them or more than
+/// six after the point, e.g. `toPrecision(123.456, 4)` is `"123.5"` and
+/// `toPrecision(123456, 2)` is `"1.2e5"`.
+pub struct ToPrecision;
+
+impl Callable for ToPrecision {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let (x, digits) = number_and_digits("toPrecision", &arguments, 1)?;
+        if !x.is_finite() {
+            return Ok(Gc::new(Object::String(Number(x).to_string())));
+        }
+
+        // The exponent after rounding, which may carry into a new digit.
+        let scientific = format!("{x:.*e}", digits - 1);
+        let (mantissa, exponent) = scientific.split_once('e').expect("formatted with {:e}");
+        let exponent: i64 = exponent.parse().expect("formatted with {:e}");
+
+        let text = if exponent < -6 || exponent >= digits as i64 {
+            format!("{mantissa}e{exponent}")
+        } else {
+            let decimals = (digits as i64 - 1 - exponent).max(0) as usize;
+            format!("{x:.decimals$}")
+        };
+        interpreter.check_string_length("toPrecision", text.len())?;
+        Ok(Gc::new(Object::String(text)))
+    }
+}
+
+/// Number constants every interpreter starts with.
+pub const CONSTANTS: &[(&str, f64)] = &[("nan", f64::NAN), ("infinity", f64::INFINITY)];
+
+/// `isNaN(x)`: whether `x` is the number NaN. Other values are not.
+pub struct IsNaN;
+
+impl Callable for IsNaN {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let nan = matches!(*arguments[0], Object::Number(n) if n.is_nan());
+        Ok(interpreter.bool(nan))
+    }
+}
+
+/// `isFinite(x)`: whether `x` is a number other than NaN or an infinity.
+pub struct IsFinite;
+
+impl Callable for IsFinite {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let finite = matches!(*arguments[0], Object::Number(n) if n.is_finite());
+        Ok(interpreter.bool(finite))
+    }
+}
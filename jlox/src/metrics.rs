@@ -0,0 +1,207 @@
+//! `jlox metrics`: how big and how tangled each function of a Lox codebase
+//! is, for code review.
+//!
+//! For every function and method, nested ones included, this counts:
+//!
+//! - statements, not counting blocks, and counting a nested function or
+//!   class as one statement, since it is measured on its own;
+//! - nesting depth, how many `if`, `while` and `for` statements enclose its
+//!   deepest statement, itself included;
+//! - cyclomatic complexity, one more than its decision points: each `if`,
+//!   `while`, `for`, `and` and `or`, each `match` arm after the first, and
+//!   each arm's guard.
+
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Expr, Stmt},
+    docs::{self, Error},
+    parse_source,
+    serve::json_string,
+};
+
+/// The measurements of one function or method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    /// The function's name, or `Class.method` for a method.
+    pub name: String,
+    pub line: usize,
+    pub statements: usize,
+    pub depth: usize,
+    pub complexity: usize,
+}
+
+impl Display for FunctionMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} statements, depth {}, complexity {}",
+            self.name, self.statements, self.depth, self.complexity
+        )
+    }
+}
+
+/// The functions of one source file, in source order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetrics {
+    pub path: PathBuf,
+    pub functions: Vec<FunctionMetrics>,
+}
+
+/// Measures every function and method in `statements`.
+pub fn measure(statements: &[Stmt]) -> Vec<FunctionMetrics> {
+    let mut functions = Vec::new();
+    for stmt in statements {
+        collect(stmt, None, &mut functions);
+    }
+    functions
+}
+
+/// Measures the `.lox` files under `root` (or `root` itself if it is a
+/// file), sorted by path.
+pub fn scan(root: &Path) -> Result<Vec<FileMetrics>, Error> {
+    let mut files = Vec::new();
+    for path in docs::sources(root)? {
+        let source = fs::read_to_string(&path).map_err(docs::io_error(&path))?;
+        let statements = parse_source(&source).map_err(|error| Error::Parse {
+            path: path.clone(),
+            error,
+        })?;
+        files.push(FileMetrics {
+            functions: measure(&statements),
+            path,
+        });
+    }
+    Ok(files)
+}
+
+/// One line per function, `path:line: name: ...`.
+pub fn render_text(files: &[FileMetrics]) -> String {
+    let mut out = String::new();
+    for file in files {
+        for function in &file.functions {
+            out.push_str(&format!(
+                "{}:{}: {function}\n",
+                file.path.display(),
+                function.line
+            ));
+        }
+    }
+    out
+}
+
+/// An array with an object per file, each with its `path` and `functions`.
+pub fn render_json(files: &[FileMetrics]) -> String {
+    let files: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let functions: Vec<String> = file
+                .functions
+                .iter()
+                .map(|function| {
+                    format!(
+                        "{{\"name\": {}, \"line\": {}, \"statements\": {}, \"depth\": {}, \"complexity\": {}}}",
+                        json_string(&function.name),
+                        function.line,
+                        function.statements,
+                        function.depth,
+                        function.complexity
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"path\": {}, \"functions\": [{}]}}",
+                json_string(&file.path.to_string_lossy()),
+                functions.join(", ")
+            )
+        })
+        .collect();
+    format!("[{}]\n", files.join(", "))
+}
+
+/// Measures the functions declared in `stmt` or in the statements nested in
+/// it. `class` names the class whose methods these are.
+fn collect(stmt: &Stmt, class: Option<&str>, functions: &mut Vec<FunctionMetrics>) {
+    match stmt {
+        Stmt::Function { name, body, .. } => {
+            let mut metrics = FunctionMetrics {
+                name: match class {
+                    Some(class) => format!("{class}.{}", name.lexeme),
+                    None => name.lexeme.clone(),
+                },
+                line: name.line(),
+                statements: 0,
+                depth: 0,
+                complexity: 1,
+            };
+            for stmt in body {
+                metrics.count(stmt, 0);
+            }
+            functions.push(metrics);
+
+            for stmt in body {
+                collect(stmt, None, functions);
+            }
+        }
+        Stmt::Class { name, methods, .. } => {
+            for method in methods {
+                collect(method, Some(&name.lexeme), functions);
+            }
+        }
+        _ => {
+            for child in stmt.children() {
+                collect(child, None, functions);
+            }
+        }
+    }
+}
+
+impl FunctionMetrics {
+    /// Adds `stmt`, nested in `depth` branches or loops, to the counts.
+    fn count(&mut self, stmt: &Stmt, depth: usize) {
+        match stmt {
+            Stmt::Block { .. } => (),
+            Stmt::Function { .. } | Stmt::Class { .. } => {
+                self.statements += 1;
+                return;
+            }
+            _ => self.statements += 1,
+        }
+
+        let mut depth = depth;
+        if matches!(
+            stmt,
+            Stmt::If { .. } | Stmt::While { .. } | Stmt::For { .. }
+        ) {
+            depth += 1;
+            self.complexity += 1;
+            self.depth = self.depth.max(depth);
+        }
+
+        for expr in stmt.expressions() {
+            self.decisions(expr);
+        }
+        for child in stmt.children() {
+            self.count(child, depth);
+        }
+    }
+
+    /// Adds the decision points in `expr` to the complexity.
+    fn decisions(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Logical { .. } => self.complexity += 1,
+            Expr::Match { arms, .. } => {
+                self.complexity += arms.len().saturating_sub(1);
+                self.complexity += arms.iter().filter(|arm| arm.guard.is_some()).count();
+            }
+            _ => (),
+        }
+        for child in expr.children() {
+            self.decisions(child);
+        }
+    }
+}
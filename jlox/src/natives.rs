@@ -0,0 +1,344 @@
+//! Metadata for the native functions defined as globals.
+//!
+//! Every native is described once, here or by the embedder that defines it,
+//! so the REPL's `:help`, editor tooling and permission checks all read the
+//! same name, arity, module, documentation and required capability.
+
+use crate::{
+    bytes::{Decode, Encode, ReadBytes, WriteBytes},
+    collections::{Filter, Has, Len, MapList, Reduce, Slice, Sort},
+    events::Defer,
+    functions::{Callable, Clock, ClosureInfo, Doc},
+    host::HostConstructor,
+    inspect::Inspect,
+    interpreter::Error,
+    math::{IsFinite, IsNaN, ToFixed, ToPrecision},
+    net::TcpConnect,
+    permissions::Capability,
+    streams::WriteOut,
+    strings::StringBuilder,
+    tasks::{NewChannel, Spawn},
+    types::Gc,
+};
+
+/// Module natives defined through [`crate::Lox::define_native`] belong to.
+pub const HOST_MODULE: &str = "host";
+
+/// Describes a native function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeInfo {
+    name: String,
+    arity: usize,
+    variadic: bool,
+    module: String,
+    doc: String,
+    capability: Option<Capability>,
+}
+
+impl NativeInfo {
+    pub fn new(name: &str, module: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            arity: 0,
+            variadic: false,
+            module: module.to_owned(),
+            doc: String::new(),
+            capability: None,
+        }
+    }
+
+    pub fn with_doc(mut self, doc: &str) -> Self {
+        self.doc = doc.to_owned();
+        self
+    }
+
+    /// Marks the native as needing `capability` before it may run.
+    pub fn requires(mut self, capability: Capability) -> Self {
+        self.capability = Some(capability);
+        self
+    }
+
+    pub(crate) fn with_arity(mut self, arity: usize, variadic: bool) -> Self {
+        self.arity = arity;
+        self.variadic = variadic;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of arguments scripts pass, taken from the native itself.
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Whether scripts may pass more arguments than [`NativeInfo::arity`].
+    pub fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn doc(&self) -> &str {
+        &self.doc
+    }
+
+    pub fn capability(&self) -> Option<Capability> {
+        self.capability
+    }
+
+    /// One line summary, e.g. `tcpConnect(2)  [net, needs --allow-net]`.
+    pub fn signature(&self) -> String {
+        let arity = match self.variadic {
+            true => format!("{}+", self.arity),
+            false => self.arity.to_string(),
+        };
+        match self.capability {
+            Some(cap) => format!(
+                "{}({arity})  [{}, needs --allow-{cap}]",
+                self.name, self.module
+            ),
+            None => format!("{}({arity})  [{}]", self.name, self.module),
+        }
+    }
+}
+
+/// The natives defined in an interpreter, in definition order.
+#[derive(Debug, Default, Clone)]
+pub struct Registry {
+    natives: Vec<NativeInfo>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `info`, replacing an earlier native of the same name.
+    pub fn register(&mut self, info: NativeInfo) {
+        match self.natives.iter_mut().find(|n| n.name == info.name) {
+            Some(existing) => *existing = info,
+            None => self.natives.push(info),
+        }
+    }
+
+    /// Forgets the native `name`, if it was registered.
+    pub fn remove(&mut self, name: &str) {
+        self.natives.retain(|n| n.name != name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NativeInfo> {
+        self.natives.iter().find(|n| n.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NativeInfo> {
+        self.natives.iter()
+    }
+
+    /// The natives gated behind `capability`.
+    pub fn requiring(&self, capability: Capability) -> impl Iterator<Item = &NativeInfo> {
+        self.natives
+            .iter()
+            .filter(move |n| n.capability == Some(capability))
+    }
+}
+
+/// A native every interpreter starts with.
+pub struct Builtin {
+    pub name: &'static str,
+    pub module: &'static str,
+    pub doc: &'static str,
+    pub capability: Option<Capability>,
+    pub make: fn() -> Gc<dyn Callable<E = Error>>,
+}
+
+impl Builtin {
+    pub fn info(&self) -> NativeInfo {
+        let info = NativeInfo::new(self.name, self.module).with_doc(self.doc);
+        match self.capability {
+            Some(cap) => info.requires(cap),
+            None => info,
+        }
+    }
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "clock",
+        module: "time",
+        doc: "Milliseconds since the Unix epoch.",
+        capability: None,
+        make: || Gc::new(Clock),
+    },
+    Builtin {
+        name: "doc",
+        module: "docs",
+        doc: "The doc comment of a function or class, or nil if it has none.",
+        capability: None,
+        make: || Gc::new(Doc),
+    },
+    Builtin {
+        name: "inspect",
+        module: "docs",
+        doc: "`value` written out with nested values indented, down to `depth` levels (4 if left out).",
+        capability: None,
+        make: || Gc::new(Inspect),
+    },
+    Builtin {
+        name: "closureInfo",
+        module: "docs",
+        doc: "A map from the names of the variables a function captures from enclosing scopes to their current values.",
+        capability: None,
+        make: || Gc::new(ClosureInfo),
+    },
+    Builtin {
+        name: "len",
+        module: "collections",
+        doc: "The number of elements of a list, tuple or map, or of bytes in a string.",
+        capability: None,
+        make: || Gc::new(Len),
+    },
+    Builtin {
+        name: "has",
+        module: "collections",
+        doc: "Whether a map has the key `x` or a list contains an element equal to `x`.",
+        capability: None,
+        make: || Gc::new(Has),
+    },
+    Builtin {
+        name: "slice",
+        module: "collections",
+        doc: "The elements of a list, or the bytes, from `start` up to `end` (exclusive, optional).",
+        capability: None,
+        make: || Gc::new(Slice),
+    },
+    Builtin {
+        name: "map",
+        module: "collections",
+        doc: "A new list of `fn(element)` for every element of `list`.",
+        capability: None,
+        make: || Gc::new(MapList),
+    },
+    Builtin {
+        name: "filter",
+        module: "collections",
+        doc: "A new list of the elements of `list` for which `fn(element)` is truthy.",
+        capability: None,
+        make: || Gc::new(Filter),
+    },
+    Builtin {
+        name: "reduce",
+        module: "collections",
+        doc: "Folds `list` from the left with `fn(accumulator, element)`, starting from `init`.",
+        capability: None,
+        make: || Gc::new(Reduce),
+    },
+    Builtin {
+        name: "sort",
+        module: "collections",
+        doc: "A new list sorted by `comparator(a, b)`, negative when `a` goes first, or without one by value and `compareTo(other)`. Stable.",
+        capability: None,
+        make: || Gc::new(Sort),
+    },
+    Builtin {
+        name: "isNaN",
+        module: "math",
+        doc: "Whether `x` is the number NaN. Use it instead of `x == nan`, which is always false.",
+        capability: None,
+        make: || Gc::new(IsNaN),
+    },
+    Builtin {
+        name: "isFinite",
+        module: "math",
+        doc: "Whether `x` is a number other than NaN, infinity and -infinity.",
+        capability: None,
+        make: || Gc::new(IsFinite),
+    },
+    Builtin {
+        name: "toFixed",
+        module: "math",
+        doc: "`x` as a string rounded to `digits` digits after the decimal point.",
+        capability: None,
+        make: || Gc::new(ToFixed),
+    },
+    Builtin {
+        name: "toPrecision",
+        module: "math",
+        doc: "`x` as a string rounded to `digits` significant digits.",
+        capability: None,
+        make: || Gc::new(ToPrecision),
+    },
+    Builtin {
+        name: "defer",
+        module: "events",
+        doc: "Calls `callback` with no arguments once the current script finishes.",
+        capability: None,
+        make: || Gc::new(Defer),
+    },
+    Builtin {
+        name: "encode",
+        module: "bytes",
+        doc: "The bytes of a string in `encoding`: utf-8 (the default), latin-1 or ascii.",
+        capability: None,
+        make: || Gc::new(Encode),
+    },
+    Builtin {
+        name: "decode",
+        module: "bytes",
+        doc: "The string that bytes encode in `encoding`: utf-8 (the default), latin-1 or ascii.",
+        capability: None,
+        make: || Gc::new(Decode),
+    },
+    Builtin {
+        name: "readBytes",
+        module: "bytes",
+        doc: "The contents of the file at `path` as bytes.",
+        capability: Some(Capability::Read),
+        make: || Gc::new(ReadBytes),
+    },
+    Builtin {
+        name: "writeBytes",
+        module: "bytes",
+        doc: "Replaces the file at `path` with bytes or a utf-8 string; returns the bytes written.",
+        capability: Some(Capability::Write),
+        make: || Gc::new(WriteBytes),
+    },
+    Builtin {
+        name: "write",
+        module: "streams",
+        doc: "Prints the text of `x` without a newline and flushes stdout.",
+        capability: None,
+        make: || Gc::new(WriteOut),
+    },
+    Builtin {
+        name: "StringBuilder",
+        module: "strings",
+        doc: "A growable string: `append(x)` adds the text of `x`, `toString()` returns it.",
+        capability: None,
+        make: || Gc::new(HostConstructor::new(StringBuilder::class())),
+    },
+    Builtin {
+        name: "spawn",
+        module: "tasks",
+        doc: "Runs a top-level function without parameters on its own thread and returns its Task.",
+        capability: None,
+        make: || Gc::new(Spawn::new()),
+    },
+    Builtin {
+        name: "channel",
+        module: "tasks",
+        doc: "Creates a Channel for sending nil, booleans, numbers and strings between tasks.",
+        capability: None,
+        make: || Gc::new(NewChannel::new()),
+    },
+    Builtin {
+        name: "tcpConnect",
+        module: "net",
+        doc: "Opens a TCP connection to `host` and `port` and returns a Socket.",
+        capability: Some(Capability::Net),
+        make: || Gc::new(TcpConnect::new()),
+    },
+];
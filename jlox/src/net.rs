@@ -0,0 +1,146 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+use crate::{
+    functions::Callable,
+    host::{ClassBuilder, HostClass, IntoLox},
+    interpreter::{Error, Interpreter},
+    object::Object,
+    permissions::Capability,
+    tasks::Message,
+    types::{Gc, Number},
+};
+
+const RECV_BUFFER_SIZE: usize = 4096;
+
+fn net_error(name: &str, error: std::io::Error) -> Error {
+    Error::NativeError {
+        name: name.to_string(),
+        msg: error.to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct Socket {
+    peer: String,
+    stream: Option<TcpStream>,
+}
+
+impl Socket {
+    pub fn connect(host: &str, port: u16) -> std::io::Result<Self> {
+        let stream = TcpStream::connect((host, port))?;
+
+        Ok(Self {
+            peer: format!("{host}:{port}"),
+            stream: Some(stream),
+        })
+    }
+
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+
+    fn stream(&mut self, name: &str) -> Result<&mut TcpStream, Error> {
+        self.stream.as_mut().ok_or_else(|| Error::NativeError {
+            name: name.to_string(),
+            msg: format!("socket {} is closed.", self.peer),
+        })
+    }
+
+    /// The method table of socket handles returned by `tcpConnect`.
+    pub fn class() -> Gc<HostClass> {
+        ClassBuilder::<Socket>::new("Socket")
+            .getter("peer", |socket| socket.peer.clone())
+            .native_method("send", 1, |interpreter, socket, arguments| {
+                interpreter.permissions().check(Capability::Net, "send")?;
+
+                let data = arguments[0].to_string();
+                socket
+                    .stream("send")?
+                    .write_all(data.as_bytes())
+                    .map_err(|e| net_error("send", e))?;
+                Ok(Gc::new(Object::Number(Number(data.len() as f64))))
+            })
+            .native_method("recv", 0, |interpreter, socket, _| {
+                interpreter.permissions().check(Capability::Net, "recv")?;
+
+                let data = interpreter.inputs_mut().provide("recv", || {
+                    let mut buffer = [0; RECV_BUFFER_SIZE];
+                    let read = socket
+                        .stream("recv")?
+                        .read(&mut buffer)
+                        .map_err(|e| net_error("recv", e))?;
+                    Ok(Message::String(
+                        String::from_utf8_lossy(&buffer[..read]).into_owned(),
+                    ))
+                })?;
+                if let Message::String(data) = &data {
+                    interpreter.check_string_length("recv", data.len())?;
+                }
+                Ok(Gc::new(data.into_lox()))
+            })
+            .native_method("close", 0, |interpreter, socket, _| {
+                socket.stream = None;
+                Ok(interpreter.nil())
+            })
+            .build()
+    }
+}
+
+/// `tcpConnect(host, port)`: opens a TCP connection and returns a socket
+/// handle with `send(data)`, `recv()` and `close()` methods. Requires the
+/// `net` capability.
+pub struct TcpConnect {
+    class: Gc<HostClass>,
+}
+
+impl TcpConnect {
+    pub fn new() -> Self {
+        Self {
+            class: Socket::class(),
+        }
+    }
+}
+
+impl Default for TcpConnect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Callable for TcpConnect {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        interpreter.check_native("tcpConnect")?;
+
+        let Object::String(host) = &*arguments[0] else {
+            return Err(Error::NativeError {
+                name: "tcpConnect".to_string(),
+                msg: format!("host must be a string, got {}.", arguments[0]),
+            });
+        };
+
+        let number = arguments[1].n()?;
+        let Some(port) = number.as_int().and_then(|port| u16::try_from(port).ok()) else {
+            return Err(Error::NativeError {
+                name: "tcpConnect".to_string(),
+                msg: format!("{number} is not a valid port."),
+            });
+        };
+
+        let socket = Socket::connect(host, port).map_err(|e| net_error("tcpConnect", e))?;
+
+        Ok(Gc::new(self.class.instantiate(socket)?))
+    }
+}
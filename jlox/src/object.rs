@@ -1,9 +1,12 @@
 use crate::{
     class::{Class, Instance},
+    collections::Map,
     functions::Callable,
+    host::UserData,
+    types::{Gc, GcCell, Number, Value},
 };
 
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::fmt::Display;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,11 +20,57 @@ pub enum Object {
     #[default]
     Nil,
     Bool(bool),
-    Number(f64),
+    Number(Number),
     String(String),
-    Function(Rc<dyn Callable<E = crate::interpreter::Error>>),
-    Class(Rc<RefCell<Class>>),
-    Instance(Rc<RefCell<Instance>>),
+    /// Binary data, `b"..."`, read with `bytes[i]`.
+    Bytes(Vec<u8>),
+    Function(Gc<dyn Callable<E = crate::interpreter::Error>>),
+    Class(Gc<GcCell<Class>>),
+    Instance(Gc<GcCell<Instance>>),
+    UserData(Gc<UserData>),
+    /// An immutable, fixed-size sequence, read with `t.0`, `t.1`, ...
+    Tuple(Vec<Gc<Object>>),
+    /// A frozen list, `#[a, b]`, read with `list[i]`.
+    List(Vec<Gc<Object>>),
+    /// A frozen map, `#{key: value}`, read with `map[key]`.
+    Map(Map),
+}
+
+impl Object {
+    /// An owned copy of the value that doesn't keep any shared object alive.
+    pub fn to_value(&self) -> Value {
+        match self {
+            Self::Nil => Value::Nil,
+            Self::Bool(b) => Value::Bool(*b),
+            Self::Number(n) => Value::Number(n.0),
+            Self::String(s) => Value::String(s.clone()),
+            Self::Bytes(bytes) => Value::Bytes(bytes.clone()),
+            Self::Function(function) => Value::Function {
+                name: function.as_lox_function().map(|f| f.name().to_owned()),
+                arity: function.arity(),
+            },
+            Self::Class(klass) => Value::Class {
+                name: klass.borrow().name().to_owned(),
+            },
+            Self::Instance(instance) => Value::Instance {
+                class: instance.borrow().class().borrow().name().to_owned(),
+            },
+            Self::UserData(data) => Value::Host {
+                name: data.to_string(),
+            },
+            Self::Tuple(elements) => {
+                Value::Tuple(elements.iter().map(|element| element.to_value()).collect())
+            }
+            Self::List(elements) => {
+                Value::List(elements.iter().map(|element| element.to_value()).collect())
+            }
+            Self::Map(map) => Value::Map(
+                map.iter()
+                    .map(|(key, value)| (key.to_value(), value.to_value()))
+                    .collect(),
+            ),
+        }
+    }
 }
 
 impl Display for Object {
@@ -31,13 +80,69 @@ impl Display for Object {
             Self::Bool(b) => write!(f, "{}", b),
             Self::Number(n) => write!(f, "{}", n),
             Self::String(s) => write!(f, "{}", s),
+            Self::Bytes(bytes) => write!(f, "b\"{}\"", crate::bytes::escape(bytes)),
             Self::Function(func) => write!(f, "{:?}", func),
-            Self::Class(klass) => write!(f, "{}", klass.borrow().to_string()),
-            Self::Instance(inst) => write!(f, "{}", inst.borrow().to_string()),
+            Self::Class(klass) => write!(f, "{}", klass.borrow()),
+            Self::Instance(inst) => write!(f, "{}", inst.borrow()),
+            Self::UserData(data) => write!(f, "{data}"),
+            Self::Tuple(elements) => write_tuple(f, elements),
+            Self::List(elements) => write_list(f, elements),
+            Self::Map(map) => write_map(f, map.iter()),
+        }
+    }
+}
+
+/// `(a, b)`, with a trailing comma for a tuple of one, as it is written.
+pub(crate) fn write_tuple<T: Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    elements: &[T],
+) -> std::fmt::Result {
+    write!(f, "(")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{element}")?;
+    }
+    if elements.len() == 1 {
+        write!(f, ",")?;
+    }
+    write!(f, ")")
+}
+
+/// `#[a, b]`.
+pub(crate) fn write_list<T: Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    elements: &[T],
+) -> std::fmt::Result {
+    write!(f, "#[")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{element}")?;
+    }
+    write!(f, "]")
+}
+
+/// `#{key: value, ...}`.
+pub(crate) fn write_map<K: Display, V: Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    entries: impl Iterator<Item = (K, V)>,
+) -> std::fmt::Result {
+    write!(f, "#{{")?;
+    for (i, (key, value)) in entries.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
         }
+        write!(f, "{key}: {value}")?;
     }
+    write!(f, "}}")
 }
 
+/// Lox equality. Numbers follow IEEE 754, so NaN is not equal to anything,
+/// itself included. That is also why `Object` must never implement `Eq`:
+/// `Gc<Object>` would then compare a shared NaN equal to itself by pointer.
 impl PartialEq for Object {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -45,6 +150,9 @@ impl PartialEq for Object {
             (Self::Number(a), Self::Number(b)) => a == b,
             (Self::Bool(a), Self::Bool(b)) => a == b,
             (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            (Self::Tuple(a), Self::Tuple(b)) | (Self::List(a), Self::List(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
             _ => false,
         }
     }
@@ -1,11 +1,14 @@
 use crate::{
-    ast::{Expr, Literal, Stmt},
+    ast::{Boundary, Expr, Literal, MatchArm, NodeId, Pattern, Signature, Stmt, StmtId},
+    dialect::Dialect,
+    limits::Limits,
+    stack,
     token::{
         Token,
         TokenType::{self, *},
     },
 };
-use std::string::String;
+use std::{mem, string::String};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,67 +16,289 @@ pub enum Error {
     #[error("{msg} at {token}")]
     Bad { token: Token, msg: String },
 
+    #[error("[line {line}] Error at end: {msg}")]
+    UnexpectedEof { line: usize, msg: String },
+
     #[error("Invalid assignment target {token}.")]
     InvalidAssignment { token: Token },
 
-    #[error("Maximum limit of arguments achieved.")]
-    MaxArgs,
+    #[error("Can't have more than {limit} {what}. at {token}")]
+    TooMany {
+        token: Token,
+        what: &'static str,
+        limit: usize,
+    },
+
+    #[error("{what} nested too deeply (more than {limit} levels). at {token}")]
+    TooDeep {
+        token: Token,
+        what: &'static str,
+        limit: usize,
+    },
+}
+
+impl Error {
+    /// Moves the reported position `lines` lines down.
+    pub(crate) fn shift(&mut self, lines: usize) {
+        match self {
+            Self::Bad { token, .. }
+            | Self::InvalidAssignment { token }
+            | Self::TooMany { token, .. }
+            | Self::TooDeep { token, .. } => token.shift(lines),
+            Self::UnexpectedEof { line, .. } => *line += lines,
+        }
+    }
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// `t.0`, a positional field of a tuple. The scanner reads `t.0.1` as `t`,
+/// `.` and the number `0.1`, which is split back into `t.0` and `.1`.
+fn tuple_index(object: Expr, index: Token) -> Expr {
+    index
+        .lexeme
+        .split('.')
+        .fold(object, |object, field| Expr::Get {
+            object: Box::new(object),
+            name: Token::new(Identifier, field, None, index.line()),
+        })
+}
+
 fn variant_eq(a: &TokenType, b: &TokenType) -> bool {
     std::mem::discriminant(a) == std::mem::discriminant(b)
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+/// How tightly an operator binds, loosest first, as in clox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    None,
+    Assignment, // =
+    Pipeline,   // |>
+    Or,         // or
+    And,        // and
+    Equality,   // == !=
+    Comparison, // < > <= >=
+    Term,       // + -
+    Factor,     // * /
+    Unary,      // ! -
+    Call,       // . () []
+}
+
+impl Precedence {
+    /// One level tighter, where the right operand of a left-associative
+    /// operator is parsed.
+    fn tighter(self) -> Self {
+        use Precedence::*;
+        match self {
+            None => Assignment,
+            Assignment => Pipeline,
+            Pipeline => Or,
+            Or => And,
+            And => Equality,
+            Equality => Comparison,
+            Comparison => Term,
+            Term => Factor,
+            Factor => Unary,
+            Unary | Call => Call,
+        }
+    }
+}
+
+/// Parses an expression starting with the token just consumed.
+type Prefix<'a> = fn(&mut Parser<'a>, Token) -> Result<Expr>;
+/// Parses the rest of an expression whose left operand is parsed and whose
+/// operator was just consumed.
+type Infix<'a> = fn(&mut Parser<'a>, Expr, Token) -> Result<Expr>;
+
+/// What a token does at the start of an expression and after an operand.
+struct Rule<'a> {
+    prefix: Option<Prefix<'a>>,
+    infix: Option<Infix<'a>>,
+    /// How tightly the token binds as an infix operator.
+    precedence: Precedence,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+impl<'a> Rule<'a> {
+    const NONE: Self = Self::new(None, None, Precedence::None);
+
+    const fn new(
+        prefix: Option<Prefix<'a>>,
+        infix: Option<Infix<'a>>,
+        precedence: Precedence,
+    ) -> Self {
+        Self {
+            prefix,
+            infix,
+            precedence,
+        }
+    }
+}
+
+/// The parse rule of every token type. Adding an operator is adding a row.
+fn rule<'a>(token_type: &TokenType) -> Rule<'a> {
+    use Precedence as P;
+    match token_type {
+        LeftParen => Rule::new(Some(Parser::grouping), Some(Parser::call), P::Call),
+        LeftBracket => Rule::new(None, Some(Parser::index), P::Call),
+        Dot => Rule::new(None, Some(Parser::dot), P::Call),
+        Minus => Rule::new(Some(Parser::unary), Some(Parser::binary), P::Term),
+        Plus => Rule::new(None, Some(Parser::binary), P::Term),
+        Slash | Star => Rule::new(None, Some(Parser::binary), P::Factor),
+        Bang => Rule::new(Some(Parser::unary), None, P::None),
+        BangEqual | EqualEqual => Rule::new(None, Some(Parser::binary), P::Equality),
+        Greater | GreaterEqual | Less | LessEqual => {
+            Rule::new(None, Some(Parser::binary), P::Comparison)
+        }
+        Equal => Rule::new(None, Some(Parser::assignment), P::Assignment),
+        Pipe => Rule::new(None, Some(Parser::pipeline), P::Pipeline),
+        Or => Rule::new(None, Some(Parser::logical), P::Or),
+        And => Rule::new(None, Some(Parser::logical), P::And),
+        Identifier => Rule::new(Some(Parser::variable), None, P::None),
+        String | Bytes | Number | False | True | Nil => {
+            Rule::new(Some(Parser::literal), None, P::None)
+        }
+        This => Rule::new(Some(Parser::this), None, P::None),
+        Super => Rule::new(Some(Parser::super_method), None, P::None),
+        Match => Rule::new(Some(Parser::match_expression), None, P::None),
+        Pound => Rule::new(Some(Parser::collection), None, P::None),
+        _ => Rule::NONE,
+    }
+}
+
+pub struct Parser<'a> {
+    /// The tokens after `current`, scanned as the parser asks for them.
+    tokens: Box<dyn Iterator<Item = Token> + 'a>,
+    /// The one token of lookahead, EOF once the input is used up.
+    current: Token,
+    /// The doc comment written right before `current`, if any.
+    doc: Option<String>,
+    /// The last token consumed.
+    previous: Option<Token>,
+    consumed: usize,
+    limits: Limits,
+    /// How many expressions and statements enclose the current token.
+    depth: usize,
+    /// Whether a line break may stand in for a `;`, see
+    /// [`Dialect::with_semicolon_inference`].
+    infer_semicolons: bool,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: impl IntoIterator<Item = Token> + 'a) -> Self {
+        let mut parser = Self {
+            tokens: Box::new(tokens.into_iter()),
+            current: Token::new(EOF, "", None, 1),
+            doc: None,
+            previous: None,
+            consumed: 0,
+            limits: Limits::default(),
+            depth: 0,
+            infer_semicolons: false,
+        };
+        parser.pull();
+        parser
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Parses the grammar of `dialect`. Its keywords are the scanner's
+    /// business.
+    pub fn with_dialect(mut self, dialect: &Dialect) -> Self {
+        self.infer_semicolons = dialect.semicolon_inference();
+        self
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>> {
+        let span = tracing::debug_span!("parse", tokens = tracing::field::Empty).entered();
         let mut statements: Vec<Stmt> = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            // Imports are only allowed at the top level of a file.
+            let stmt = if self.check(&Import) {
+                self.located(Self::import_declaration)
+            } else {
+                self.declaration()
+            };
+            statements.push(stmt?);
         }
 
+        span.record("tokens", self.consumed);
         Ok(statements)
     }
 
+    /// Parses a single expression, optionally followed by `;`, and nothing
+    /// else.
+    pub fn parse_expression(&mut self) -> Result<Expr> {
+        let expr = self.expression()?;
+        self.match_token(&[Semicolon]);
+
+        if self.peek().is_some() {
+            return Err(self.error("Expect end of expression."));
+        }
+
+        Ok(expr)
+    }
+
     fn declaration(&mut self) -> Result<Stmt> {
         let res = if self.check(&Class) {
-            self.advance();
-            self.class_declaration()
+            let doc = self.take_doc();
+            self.located(|parser| {
+                parser.advance();
+                parser.class_declaration(doc)
+            })
         } else if self.check(&Fun) {
-            self.advance();
-            self.function("function")
+            let doc = self.take_doc();
+            self.located(|parser| {
+                parser.advance();
+                parser.function("function", doc)
+            })
         } else if self.check(&Var) {
-            self.advance();
-            self.var_declaration()
+            self.located(|parser| {
+                parser.advance();
+                parser.var_declaration()
+            })
         } else {
             self.statement()
         };
 
         match res {
-            Ok(stmt) => return Ok(stmt),
+            Ok(stmt) => Ok(stmt),
             Err(err) => {
                 self.synchronize();
-                return Err(err);
+                Err(err)
             }
         }
     }
 
-    fn class_declaration(&mut self) -> Result<Stmt> {
+    fn import_declaration(&mut self) -> Result<Stmt> {
+        let keyword = self.consume(Import, "Expect 'import'.")?;
+        let path = match self.match_token(&[String]).and_then(|token| token.literal) {
+            Some(Literal::String(path)) => path,
+            _ => return Err(self.error("Expect module path string after 'import'.")),
+        };
+        self.end_statement("Expect ';' after import.")?;
+
+        Ok(Stmt::Import {
+            keyword,
+            path,
+            boundary: Boundary::NONE,
+        })
+    }
+
+    /// The doc comment written right before the current token, if any.
+    fn take_doc(&mut self) -> Option<String> {
+        self.doc.take()
+    }
+
+    fn class_declaration(&mut self, doc: Option<String>) -> Result<Stmt> {
         let name = self.consume(Identifier, "Expect class name.")?;
 
         let superclass = if self.check(&Greater) {
             self.advance();
             Some(Expr::Variable {
+                id: NodeId::fresh(),
                 name: self.consume(Identifier, "Expect superclass name.")?,
             })
         } else {
@@ -85,7 +310,8 @@ impl Parser {
         let mut methods = Vec::new();
 
         while !self.check(&RightBrace) && !self.is_at_end() {
-            methods.push(self.function("method")?);
+            let doc = self.take_doc();
+            methods.push(self.located(|parser| parser.function("method", doc))?);
         }
 
         self.consume(RightBrace, "Expect '}' after class body.")?;
@@ -94,10 +320,67 @@ impl Parser {
             name,
             superclass,
             methods,
+            doc,
+            boundary: Boundary::NONE,
         })
     }
 
+    /// Gives the statement `parse` reads from the current token on its entry in
+    /// the statement-boundary table. Ids are handed out before parsing, so they
+    /// follow the source order of the statements' first tokens.
+    fn located(&mut self, parse: impl FnOnce(&mut Self) -> Result<Stmt>) -> Result<Stmt> {
+        let id = StmtId::fresh();
+        let line = self.current.line();
+        let mut stmt = parse(self)?;
+        let (end_line, end) = self
+            .previous
+            .as_ref()
+            .map_or((line, 0), |token| (token.line(), token.span().end));
+        stmt.set_boundary(Boundary {
+            id,
+            line,
+            end_line: end_line.max(line),
+            end,
+        });
+        Ok(stmt)
+    }
+
     fn statement(&mut self) -> Result<Stmt> {
+        self.nested("Statement", |parser| {
+            parser.located(Self::unlocated_statement)
+        })
+    }
+
+    /// Runs `parse` one level deeper, failing once past
+    /// [`Limits::max_nesting`] rather than recursing until the stack runs out.
+    fn nested<T>(
+        &mut self,
+        what: &'static str,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        if self.depth >= self.limits.max_nesting || stack::exhausted() {
+            return Err(self.too_deep(what));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Reports going over `max_nesting`, or running low on stack before it.
+    fn too_deep(&self, what: &'static str) -> Error {
+        match self.peek() {
+            Some(token) if self.depth >= self.limits.max_nesting => Error::TooDeep {
+                token: token.clone(),
+                what,
+                limit: self.limits.max_nesting,
+            },
+            _ => self.error(&format!("{what} nested too deeply.")),
+        }
+    }
+
+    fn unlocated_statement(&mut self) -> Result<Stmt> {
         if self.check(&For) {
             self.advance();
             return self.for_statement();
@@ -109,14 +392,28 @@ impl Parser {
         }
 
         if self.check(&Print) {
-            // println!("1) There is a print!");
             self.advance();
             return self.print_statement();
         }
 
-        if self.check(&Return) {
-            self.advance();
-            return self.return_statement();
+        if let Some(keyword) = self.match_token(&[Return]) {
+            return self.return_statement(keyword);
+        }
+
+        if let Some(keyword) = self.match_token(&[Break]) {
+            self.end_statement("Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break {
+                keyword,
+                boundary: Boundary::NONE,
+            });
+        }
+
+        if let Some(keyword) = self.match_token(&[Continue]) {
+            self.end_statement("Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue {
+                keyword,
+                boundary: Boundary::NONE,
+            });
         }
 
         if self.check(&While) {
@@ -128,6 +425,16 @@ impl Parser {
             self.advance();
             return Ok(Stmt::Block {
                 statements: self.block()?,
+                boundary: Boundary::NONE,
+            });
+        }
+
+        // A lone `;` does nothing, like an empty block.
+        if self.check(&Semicolon) {
+            self.advance();
+            return Ok(Stmt::Block {
+                statements: Vec::new(),
+                boundary: Boundary::NONE,
             });
         }
 
@@ -143,9 +450,9 @@ impl Parser {
             initializer = None;
         } else if self.check(&Var) {
             self.advance();
-            initializer = Some(self.var_declaration()?);
+            initializer = Some(self.located(Self::var_declaration)?);
         } else {
-            initializer = Some(self.expression_statement()?);
+            initializer = Some(self.located(Self::expression_statement)?);
         }
 
         let mut condition: Option<Expr> = None;
@@ -160,27 +467,15 @@ impl Parser {
         }
         self.consume(RightParen, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
-
-        if let Some(expr) = increment {
-            body = Stmt::Block {
-                statements: vec![body, Stmt::Expression { expr }],
-            };
-        }
+        let body = self.statement()?;
 
-        let condition = condition.unwrap_or(Expr::Literal(Literal::True));
-        body = Stmt::While {
+        Ok(Stmt::For {
+            initializer: initializer.map(Box::new),
             condition,
+            increment,
             body: Box::new(body),
-        };
-
-        if let Some(init) = initializer {
-            body = Stmt::Block {
-                statements: vec![init, body],
-            };
-        }
-
-        Ok(body)
+            boundary: Boundary::NONE,
+        })
     }
 
     fn if_statement(&mut self) -> Result<Stmt> {
@@ -191,6 +486,7 @@ impl Parser {
         let then_branch = Box::new(self.statement()?);
         let mut else_branch: Option<Box<Stmt>> = None;
         if self.check(&Else) {
+            self.advance();
             else_branch = Some(Box::new(self.statement()?));
         }
 
@@ -198,31 +494,42 @@ impl Parser {
             condition,
             then_branch,
             else_branch,
+            boundary: Boundary::NONE,
         })
     }
 
     fn print_statement(&mut self) -> Result<Stmt> {
         let value = self.expression()?;
-        // println!("2) Value is: {value:?}");
-        self.consume(Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print { expr: value })
+        self.end_statement("Expect ';' after value.")?;
+        Ok(Stmt::Print {
+            expr: value,
+            boundary: Boundary::NONE,
+        })
     }
 
-    fn return_statement(&mut self) -> Result<Stmt> {
-        let keyword = self.previous().clone();
+    fn return_statement(&mut self, keyword: Token) -> Result<Stmt> {
         let mut value: Option<Expr> = None;
 
-        if !self.check(&Semicolon) {
+        if !self.check(&Semicolon) && !self.inferred_end() {
             value = Some(self.expression()?);
         }
 
-        self.consume(Semicolon, "Expect ';' after return value.")?;
+        self.end_statement("Expect ';' after return value.")?;
 
-        Ok(Stmt::Return { keyword, value })
+        Ok(Stmt::Return {
+            keyword,
+            value,
+            boundary: Boundary::NONE,
+        })
     }
 
     fn var_declaration(&mut self) -> Result<Stmt> {
+        if let Some(paren) = self.match_token(&[LeftParen]) {
+            return self.destructure(paren);
+        }
+
         let name = self.consume(Identifier, "Expect variable name.")?;
+        let annotation = self.annotation(Colon)?;
 
         let initializer = if self.check(&Equal) {
             self.advance();
@@ -231,8 +538,35 @@ impl Parser {
             None
         };
 
-        self.consume(Semicolon, "Expect ';' after variable declaration.")?;
-        Ok(Stmt::Var { name, initializer })
+        self.end_statement("Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var {
+            name,
+            annotation,
+            initializer,
+            boundary: Boundary::NONE,
+        })
+    }
+
+    fn destructure(&mut self, paren: Token) -> Result<Stmt> {
+        let mut names = Vec::new();
+        loop {
+            names.push(self.consume(Identifier, "Expect variable name.")?);
+            if self.match_token(&[Comma]).is_none() {
+                break;
+            }
+        }
+        self.consume(RightParen, "Expect ')' after variable names.")?;
+
+        self.consume(Equal, "Expect '=' after variable names.")?;
+        let initializer = self.expression()?;
+        self.end_statement("Expect ';' after variable declaration.")?;
+
+        Ok(Stmt::Destructure {
+            paren,
+            names,
+            initializer,
+            boundary: Boundary::NONE,
+        })
     }
 
     fn while_statement(&mut self) -> Result<Stmt> {
@@ -241,28 +575,41 @@ impl Parser {
         self.consume(RightParen, "Expect ')' after condition.")?;
         let body = Box::new(self.statement()?);
 
-        Ok(Stmt::While { condition, body })
+        Ok(Stmt::While {
+            condition,
+            body,
+            boundary: Boundary::NONE,
+        })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt> {
         let expr = self.expression()?;
-        self.consume(Semicolon, "Expect ';' after expression.")?;
-        Ok(Stmt::Expression { expr })
+        self.end_statement("Expect ';' after expression.")?;
+        Ok(Stmt::Expression {
+            expr,
+            boundary: Boundary::NONE,
+        })
     }
 
-    fn function(&mut self, kind: &str) -> Result<Stmt> {
+    fn function(&mut self, kind: &str, doc: Option<String>) -> Result<Stmt> {
+        self.nested("Function", |parser| parser.unnested_function(kind, doc))
+    }
+
+    fn unnested_function(&mut self, kind: &str, doc: Option<String>) -> Result<Stmt> {
         let name = self.consume(Identifier, &format!("Expect {kind} name."))?;
         self.consume(LeftParen, &format!("Expect '(' after {kind} name."))?;
 
         let mut parameters: Vec<Token> = Vec::new();
+        let mut signature = Signature::default();
 
         if !self.check(&RightParen) {
             loop {
-                if parameters.len() >= 255 {
-                    return Err(Error::MaxArgs);
+                if parameters.len() >= self.limits.max_arguments {
+                    return Err(self.too_many("parameters"));
                 }
 
                 parameters.push(self.consume(Identifier, "Expect parameter name.")?);
+                signature.params.push(self.annotation(Colon)?);
 
                 if !self.check(&Comma) {
                     break;
@@ -273,14 +620,28 @@ impl Parser {
         }
 
         self.consume(RightParen, "Expect ')' after parameters.")?;
+        signature.returns = self.annotation(ThinArrow)?;
         self.consume(LeftBrace, &format!("Expect '{{' before {kind} body."))?;
 
+        let annotated = signature.returns.is_some() || signature.params.iter().any(Option::is_some);
         let body = self.block()?;
-        return Ok(Stmt::Function {
+        Ok(Stmt::Function {
             name,
             params: parameters,
             body,
-        });
+            doc,
+            signature: annotated.then(|| Box::new(signature)),
+            boundary: Boundary::NONE,
+        })
+    }
+
+    /// The type name after `marker`, the `:` or `->` that starts an
+    /// annotation, if there is one.
+    fn annotation(&mut self, marker: TokenType) -> Result<Option<Token>> {
+        if self.match_token(&[marker]).is_none() {
+            return Ok(None);
+        }
+        Ok(Some(self.consume(Identifier, "Expect type name.")?))
     }
 
     fn block(&mut self) -> Result<Vec<Stmt>> {
@@ -295,202 +656,340 @@ impl Parser {
     }
 
     fn expression(&mut self) -> Result<Expr> {
-        self.assignment()
+        self.parse_precedence(Precedence::Assignment)
     }
 
-    fn assignment(&mut self) -> Result<Expr> {
-        let expr = self.or()?;
-
-        if self.check(&Equal) {
-            let equals = self.advance().clone();
-            let value = self.assignment()?;
+    /// An expression whose operators all bind at least as tightly as
+    /// `precedence`: the prefix rule of its first token, then infix rules for
+    /// as long as the next operator binds tightly enough.
+    fn parse_precedence(&mut self, precedence: Precedence) -> Result<Expr> {
+        self.nested("Expression", |parser| {
+            let Some(prefix) = parser
+                .peek()
+                .and_then(|token| rule(&token.token_type).prefix)
+            else {
+                return Err(parser.error("Expect expression."));
+            };
+            let token = parser.advance().cloned().expect("peeked a prefix");
+            let expr = prefix(parser, token)?;
+
+            // Each operator wraps `expr` in one more node, so links of a
+            // chain like `a.b.c` or `1 + 2 + 3` count as levels of nesting.
+            let depth = parser.depth;
+            let result = parser.infixes(precedence, expr);
+            parser.depth = depth;
+            result
+        })
+    }
 
-            match expr {
-                Expr::Variable { name } => {
-                    return Ok(Expr::Assign {
-                        name,
-                        value: Box::new(value),
-                    })
-                }
-                Expr::Get { object, name } => {
-                    return Ok(Expr::Set {
-                        object,
-                        name,
-                        value: Box::new(value),
-                    })
-                }
-                _ => return Err(Error::InvalidAssignment { token: equals }),
+    fn infixes(&mut self, precedence: Precedence, mut expr: Expr) -> Result<Expr> {
+        while let Some(infix) = self
+            .peek()
+            .map(|token| rule(&token.token_type))
+            .filter(|rule| precedence <= rule.precedence)
+            .and_then(|rule| rule.infix)
+        {
+            if self.depth >= self.limits.max_nesting {
+                return Err(self.too_deep("Expression"));
             }
-        }
+            self.depth += 1;
 
+            let operator = self.advance().cloned().expect("peeked an operator");
+            expr = infix(self, expr, operator)?;
+        }
         Ok(expr)
     }
 
-    fn or(&mut self) -> Result<Expr> {
-        let mut expr = self.and()?;
+    fn binary(&mut self, left: Expr, op: Token) -> Result<Expr> {
+        let right = self.parse_precedence(rule(&op.token_type).precedence.tighter())?;
+        Ok(Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        })
+    }
 
-        while self.check(&Or) {
-            let op = self.advance().clone();
-            let right = self.and()?;
-            expr = Expr::Logical {
-                left: Box::new(expr),
-                op,
-                right: Box::new(right),
-            };
-        }
+    fn logical(&mut self, left: Expr, op: Token) -> Result<Expr> {
+        let right = self.parse_precedence(rule(&op.token_type).precedence.tighter())?;
+        Ok(Expr::Logical {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        })
+    }
 
-        Ok(expr)
+    fn unary(&mut self, op: Token) -> Result<Expr> {
+        let right = self.parse_precedence(Precedence::Unary)?;
+        Ok(Expr::Unary {
+            op,
+            right: Box::new(right),
+        })
     }
 
-    fn and(&mut self) -> Result<Expr> {
-        let mut expr = self.equality()?;
+    /// Right-associative, so `a = b = c` assigns `c` to both.
+    fn assignment(&mut self, target: Expr, equals: Token) -> Result<Expr> {
+        let value = Box::new(self.parse_precedence(Precedence::Assignment)?);
 
-        while self.check(&And) {
-            let op = self.advance().clone();
-            let right = self.equality()?;
-            expr = Expr::Logical {
-                left: Box::new(expr),
-                op,
-                right: Box::new(right),
-            };
+        let mut target = target;
+        match &mut target {
+            Expr::Variable { id, name } => Ok(Expr::Assign {
+                id: *id,
+                name: mem::take(name),
+                value,
+            }),
+            Expr::Get { object, name } => Ok(Expr::Set {
+                object: mem::take(object),
+                name: mem::take(name),
+                value,
+            }),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => Ok(Expr::SetIndex {
+                object: mem::take(object),
+                bracket: mem::take(bracket),
+                index: mem::take(index),
+                value,
+            }),
+            _ => Err(Error::InvalidAssignment { token: equals }),
         }
-
-        Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr> {
-        let mut expr = self.comparison()?;
-
-        // println!("3) Expression: {expr:?}");
-
-        while self.eval_tokens(&[BangEqual, EqualEqual]) {
-            let operator = self.previous().clone();
-            let right = self.comparison()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                op: operator,
-                right: Box::new(right),
-            };
+    /// `x |> f(a)` is sugar for `f(x, a)` and `x |> f` for `f(x)`, marked as
+    /// piped so the printer can write it back as it was. It binds
+    /// looser than every other operator but assignment, so the stage on the
+    /// right is a call or property chain and `a + b |> f` pipes the sum.
+    fn pipeline(&mut self, input: Expr, pipe: Token) -> Result<Expr> {
+        let mut stage = self.parse_precedence(Precedence::Call)?;
+        match &mut stage {
+            Expr::Call {
+                paren,
+                arguments,
+                piped,
+                ..
+            } => {
+                if arguments.len() >= self.limits.max_arguments {
+                    return Err(Error::TooMany {
+                        token: mem::take(paren),
+                        what: "arguments",
+                        limit: self.limits.max_arguments,
+                    });
+                }
+                arguments.insert(0, input);
+                *piped = true;
+                Ok(stage)
+            }
+            _ => Ok(Expr::Call {
+                callee: Box::new(stage),
+                paren: pipe,
+                arguments: vec![input],
+                piped: true,
+            }),
         }
-
-        Ok(expr)
     }
 
-    fn eval_tokens(&mut self, types: &[TokenType]) -> bool {
-        for ty in types.iter() {
-            if self.check(ty) {
-                self.advance();
-                return true;
-            }
+    fn dot(&mut self, object: Expr, _dot: Token) -> Result<Expr> {
+        if let Some(index) = self.match_token(&[Number]) {
+            return Ok(tuple_index(object, index));
         }
 
-        false
+        let name = self.consume(Identifier, "Expect property name after '.'.")?;
+        Ok(Expr::Get {
+            object: Box::new(object),
+            name,
+        })
     }
 
-    fn check(&self, ty: &TokenType) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        variant_eq(&self.peek().token_type, ty)
+    fn index(&mut self, object: Expr, _bracket: Token) -> Result<Expr> {
+        let index = self.expression()?;
+        let bracket = self.consume(RightBracket, "Expect ']' after index.")?;
+        Ok(Expr::Index {
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+        })
     }
 
-    fn advance(&mut self) -> &Token {
-        if !self.is_at_end() {
-            self.current += 1;
+    fn literal(&mut self, token: Token) -> Result<Expr> {
+        match token.token_type {
+            False => Ok(Expr::Literal(Literal::False)),
+            True => Ok(Expr::Literal(Literal::True)),
+            Nil => Ok(Expr::Literal(Literal::Nil)),
+            _ => match token.literal {
+                Some(literal) => Ok(Expr::Literal(literal)),
+                None => Err(Error::Bad {
+                    token,
+                    msg: "Expect literal value.".to_owned(),
+                }),
+            },
         }
-
-        self.previous()
     }
 
-    fn is_at_end(&self) -> bool {
-        self.peek().token_type == EOF
+    fn variable(&mut self, name: Token) -> Result<Expr> {
+        Ok(Expr::Variable {
+            id: NodeId::fresh(),
+            name,
+        })
     }
 
-    fn peek(&self) -> &Token {
-        self.tokens.get(self.current).unwrap()
+    fn this(&mut self, keyword: Token) -> Result<Expr> {
+        Ok(Expr::This {
+            id: NodeId::fresh(),
+            keyword,
+        })
     }
 
-    fn previous(&self) -> &Token {
-        self.tokens.get(self.current - 1).unwrap()
+    /// `super.method` or `super(Ancestor).method`.
+    fn super_method(&mut self, keyword: Token) -> Result<Expr> {
+        let ancestor = if self.check(&LeftParen) {
+            self.advance();
+            let ancestor = self.consume(Identifier, "Expect ancestor class name.")?;
+            self.consume(RightParen, "Expect ')' after ancestor class name.")?;
+            Some(ancestor)
+        } else {
+            None
+        };
+        self.consume(Dot, "Expect '.' after 'super'.")?;
+        let method = self.consume(Identifier, "Expect superclass method name.")?;
+        Ok(Expr::Super {
+            id: NodeId::fresh(),
+            keyword,
+            ancestor,
+            method,
+        })
     }
 
-    fn comparison(&mut self) -> Result<Expr> {
-        let mut expr = self.term()?;
-
-        // println!("4) Expression: {expr:?}");
+    /// `(a)`, or a tuple: `(a,)` is a tuple of one, and a trailing comma is
+    /// allowed after more.
+    fn grouping(&mut self, _paren: Token) -> Result<Expr> {
+        let expr = self.expression()?;
+        if self.match_token(&[Comma]).is_none() {
+            self.consume(RightParen, "Expect ')' after expression.")?;
+            return Ok(Expr::Grouping { ex: Box::new(expr) });
+        }
 
-        while self.eval_tokens(&[Greater, GreaterEqual, Less, LessEqual]) {
-            let operator = self.previous().clone();
-            let right = self.term()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                op: operator,
-                right: Box::new(right),
+        let mut elements = vec![expr];
+        while !self.check(&RightParen) {
+            elements.push(self.expression()?);
+            if self.match_token(&[Comma]).is_none() {
+                break;
             }
         }
-
-        Ok(expr)
+        self.consume(RightParen, "Expect ')' after tuple elements.")?;
+        Ok(Expr::Tuple { elements })
     }
 
-    fn term(&mut self) -> Result<Expr> {
-        let mut expr = self.factor()?;
+    /// Consumes the current token if it has one of the given types.
+    fn match_token(&mut self, types: &[TokenType]) -> Option<Token> {
+        if types.iter().any(|ty| self.check(ty)) {
+            return self.advance().cloned();
+        }
 
-        // println!("5) Expr: {expr:?}");
+        None
+    }
 
-        while self.eval_tokens(&[Minus, Plus]) {
-            let operator = self.previous().clone();
-            let right = self.factor()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                op: operator,
-                right: Box::new(right),
-            }
+    fn check(&self, ty: &TokenType) -> bool {
+        self.peek()
+            .is_some_and(|token| variant_eq(&token.token_type, ty))
+    }
+
+    /// Consumes and returns the current token, or `None` at the end of input.
+    fn advance(&mut self) -> Option<&Token> {
+        if self.is_at_end() {
+            return None;
         }
 
-        Ok(expr)
+        let token = self.pull();
+        self.consumed += 1;
+        Some(self.previous.insert(token))
     }
 
-    fn factor(&mut self) -> Result<Expr> {
-        let mut expr = self.unary()?;
+    /// Replaces `current` with the next token of the stream, returning the
+    /// old one, and collects the doc comment lines scanned on the way. When
+    /// the stream runs out without an EOF, one is made on the last line.
+    fn pull(&mut self) -> Token {
+        let mut lines: Vec<String> = Vec::new();
+        let next = loop {
+            match self.tokens.next() {
+                Some(Token {
+                    token_type: DocComment,
+                    literal,
+                    ..
+                }) => {
+                    if let Some(Literal::String(line)) = literal {
+                        lines.push(line);
+                    }
+                }
+                Some(token) => break token,
+                None => break Token::new(EOF, "", None, self.current.line()),
+            }
+        };
 
-        // println!("6) Expr: {expr:?}");
+        self.doc = (!lines.is_empty()).then(|| lines.join("\n"));
+        mem::replace(&mut self.current, next)
+    }
 
-        while self.eval_tokens(&[Slash, Star]) {
-            let operator = self.previous().clone();
-            let right = self.unary()?;
-            expr = Expr::Binary {
-                left: Box::new(expr),
-                op: operator,
-                right: Box::new(right),
-            }
-        }
+    fn is_at_end(&self) -> bool {
+        self.peek().is_none()
+    }
 
-        Ok(expr)
+    /// The current token, or `None` once only EOF is left.
+    fn peek(&self) -> Option<&Token> {
+        Some(&self.current).filter(|token| token.token_type != EOF)
     }
 
-    fn unary(&mut self) -> Result<Expr> {
-        if self.eval_tokens(&[Bang, Minus]) {
-            let operator = self.previous().clone();
-            let right = self.unary()?;
-            return Ok(Expr::Unary {
-                op: operator,
-                right: Box::new(right),
-            });
+    /// An error at the current token, or at the end of input. At a token
+    /// the scanner couldn't make sense of, the error is the scanner's.
+    fn error(&self, msg: &str) -> Error {
+        match self.peek() {
+            Some(
+                token @ Token {
+                    token_type: TokenType::Error,
+                    literal: Some(Literal::String(msg)),
+                    ..
+                },
+            ) => Error::Bad {
+                token: token.clone(),
+                msg: msg.clone(),
+            },
+            Some(token) => Error::Bad {
+                token: token.clone(),
+                msg: msg.to_owned(),
+            },
+            None => Error::UnexpectedEof {
+                line: self.current.line(),
+                msg: msg.to_owned(),
+            },
         }
+    }
 
-        self.call()
+    /// Reported at the first argument or parameter over the limit.
+    fn too_many(&self, what: &'static str) -> Error {
+        let limit = self.limits.max_arguments;
+        match self.peek() {
+            Some(token) => Error::TooMany {
+                token: token.clone(),
+                what,
+                limit,
+            },
+            None => self.error(&format!("Can't have more than {limit} {what}.")),
+        }
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+    fn call(&mut self, callee: Expr, _paren: Token) -> Result<Expr> {
         let mut arguments: Vec<Expr> = Vec::new();
 
         if !self.check(&RightParen) {
-            arguments.push(self.expression()?);
-            while self.eval_tokens(&[Comma]) {
-                if arguments.len() >= 255 {
-                    return Err(Error::MaxArgs);
+            loop {
+                if arguments.len() >= self.limits.max_arguments {
+                    return Err(self.too_many("arguments"));
                 }
                 arguments.push(self.expression()?);
+
+                if self.match_token(&[Comma]).is_none() {
+                    break;
+                }
             }
         }
 
@@ -500,103 +999,180 @@ impl Parser {
             callee: Box::new(callee),
             paren,
             arguments,
+            piped: false,
         })
     }
 
-    fn call(&mut self) -> Result<Expr> {
-        let mut expr = self.primary()?;
+    /// `#[a, b]` or `#{key: value}`, with an optional trailing comma.
+    fn collection(&mut self, pound: Token) -> Result<Expr> {
+        if let Some(bracket) = self.match_token(&[LeftBracket]) {
+            let mut elements = Vec::new();
+            while !self.check(&RightBracket) && !self.is_at_end() {
+                elements.push(self.expression()?);
+                if self.match_token(&[Comma]).is_none() {
+                    break;
+                }
+            }
+            self.consume(RightBracket, "Expect ']' after list elements.")?;
+            return Ok(Expr::List { bracket, elements });
+        }
 
-        loop {
-            if self.eval_tokens(&[LeftParen]) {
-                expr = self.finish_call(expr)?;
-            } else if self.eval_tokens(&[Dot]) {
-                let name = self.consume(Identifier, "Expect property name after '.'.")?;
-                expr = Expr::Get {
-                    object: Box::new(expr),
-                    name,
-                };
-            } else {
+        let Some(brace) = self.match_token(&[LeftBrace]) else {
+            return Err(Error::Bad {
+                token: pound,
+                msg: "Expect '[' or '{' after '#'.".to_owned(),
+            });
+        };
+        let mut entries = Vec::new();
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            let key = self.expression()?;
+            self.consume(Colon, "Expect ':' after map key.")?;
+            entries.push((key, self.expression()?));
+            if self.match_token(&[Comma]).is_none() {
                 break;
             }
         }
-
-        Ok(expr)
+        self.consume(RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::Map { brace, entries })
     }
 
-    fn primary(&mut self) -> Result<Expr> {
-        // println!("Token from self.advance(): {:?}", self.advance());
-        self.advance();
+    fn match_expression(&mut self, keyword: Token) -> Result<Expr> {
+        let subject = self.expression()?;
+        self.consume(LeftBrace, "Expect '{' after match subject.")?;
 
-        let prev = self.previous();
+        let mut arms = Vec::new();
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            let guard = match self.match_token(&[If]) {
+                Some(_) => Some(self.expression()?),
+                None => None,
+            };
+            self.consume(Arrow, "Expect '=>' after pattern.")?;
+            let body = self.expression()?;
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
 
-        match prev.token_type {
-            False => Ok(Expr::Literal(Literal::False)),
-            True => Ok(Expr::Literal(Literal::True)),
-            Nil => Ok(Expr::Literal(Literal::Nil)),
-            Super => {
-                let keyword = prev.clone();
-                self.consume(Dot, "Expect '.' after 'super'.")?;
-                let method = self.consume(Identifier, "Expect superclass method name.")?;
-                Ok(Expr::Super { keyword, method })
+            if self.match_token(&[Comma]).is_none() {
+                break;
             }
-            This => Ok(Expr::This {
-                keyword: prev.clone(),
-            }),
-            Number => {
-                let n = prev
-                    .clone()
-                    .literal
-                    .expect("Number token does not have a literal");
-                Ok(Expr::Literal(n))
+        }
+        self.consume(RightBrace, "Expect '}' after match arms.")?;
+
+        Ok(Expr::Match {
+            keyword,
+            subject: Box::new(subject),
+            arms,
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern> {
+        if let Some(name) = self.match_token(&[Identifier]) {
+            return Ok(match name.lexeme.as_str() {
+                "_" => Pattern::Wildcard,
+                _ => Pattern::Binding(name),
+            });
+        }
+
+        if self.match_token(&[LeftParen]).is_some() {
+            let first = self.pattern()?;
+            if self.match_token(&[Comma]).is_none() {
+                self.consume(RightParen, "Expect ')' after pattern.")?;
+                return Ok(first);
             }
-            String => {
-                let s = prev
-                    .clone()
-                    .literal
-                    .expect("String token does not have a literal");
-                Ok(Expr::Literal(s))
+
+            let mut patterns = vec![first];
+            while !self.check(&RightParen) {
+                patterns.push(self.pattern()?);
+                if self.match_token(&[Comma]).is_none() {
+                    break;
+                }
             }
-            Identifier => Ok(Expr::Variable { name: prev.clone() }),
-            LeftParen => {
-                let expr = self.expression()?;
-                self.consume(RightParen, "Expect ')' after expression.")?;
-                return Ok(Expr::Grouping { ex: Box::new(expr) });
+            self.consume(RightParen, "Expect ')' after tuple pattern.")?;
+            return Ok(Pattern::Tuple(patterns));
+        }
+
+        if self.check(&Minus) || self.check(&Number) {
+            let low = self.number_pattern()?;
+            if self.match_token(&[DotDot]).is_none() {
+                return Ok(Pattern::Literal(Literal::Number(low)));
             }
-            other => {
-                println!("Token found that is wrong: {other}");
-                return Err(Error::Bad {
-                    token: self.peek().clone(),
-                    msg: "Expect expression.".to_owned(),
-                });
+            let high = self.number_pattern()?;
+            return Ok(Pattern::Range { low, high });
+        }
+
+        match self.match_token(&[String, Bytes, True, False, Nil]) {
+            Some(token) => Ok(Pattern::Literal(match token.token_type {
+                True => Literal::True,
+                False => Literal::False,
+                Nil => Literal::Nil,
+                _ => token.literal.unwrap_or(Literal::Nil),
+            })),
+            None => Err(self.error("Expect pattern.")),
+        }
+    }
+
+    /// A number in a pattern, which may be negative.
+    fn number_pattern(&mut self) -> Result<crate::types::Number> {
+        let negative = self.match_token(&[Minus]).is_some();
+        let token = self.consume(Number, "Expect number after '-'.")?;
+        let Some(Literal::Number(n)) = token.literal else {
+            return Err(Error::Bad {
+                token,
+                msg: "Expect literal value.".to_owned(),
+            });
+        };
+
+        Ok(if negative { -n } else { n })
+    }
+
+    /// Consumes the `;` ending a statement, or accepts its absence where
+    /// [`Parser::inferred_end`] says the statement ends anyway.
+    fn end_statement(&mut self, message: &str) -> Result<()> {
+        if self.match_token(&[Semicolon]).is_some() || self.inferred_end() {
+            return Ok(());
+        }
+
+        Err(self.error(message))
+    }
+
+    /// With semicolon inference, whether the statement so far ends before the
+    /// current token: at a line break, a `}` or the end of input.
+    fn inferred_end(&self) -> bool {
+        if !self.infer_semicolons {
+            return false;
+        }
+
+        let previous = self.previous.as_ref();
+        match self.peek() {
+            None => true,
+            Some(token) => {
+                token.token_type == RightBrace
+                    || previous.is_some_and(|previous| previous.line() < token.line())
             }
         }
     }
 
     fn consume(&mut self, ty: TokenType, message: &str) -> Result<Token> {
-        if self.check(&ty) {
-            return Ok(self.advance().clone());
+        match self.match_token(&[ty]) {
+            Some(token) => Ok(token),
+            None => Err(self.error(message)),
         }
-
-        Err(Error::Bad {
-            token: self.peek().clone(),
-            msg: message.to_owned(),
-        })
     }
 
     fn synchronize(&mut self) {
-        self.advance();
-
-        while !self.is_at_end() {
-            if self.previous().token_type == Semicolon {
+        while let Some(token) = self.advance() {
+            if token.token_type == Semicolon {
                 return;
             }
 
-            match self.peek().token_type {
-                Class | Fun | Var | For | If | While | Print | Return => return,
-                _ => (),
+            if let Some(Class | Fun | Var | For | If | While | Print | Return) =
+                self.peek().map(|token| token.token_type)
+            {
+                return;
             }
-
-            self.advance();
         }
     }
 }
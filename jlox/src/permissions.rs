@@ -0,0 +1,87 @@
+use std::{collections::HashSet, fmt::Display};
+
+use crate::interpreter::Error;
+
+/// A privilege a native function needs before it may touch the outside
+/// world. Scripts start with none of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Read,
+    Write,
+    Net,
+    Env,
+    Run,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Read,
+        Capability::Write,
+        Capability::Net,
+        Capability::Env,
+        Capability::Run,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Net => "net",
+            Self::Env => "env",
+            Self::Run => "run",
+        }
+    }
+
+    /// Maps a command line flag such as `--allow-net` to its capability.
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        let name = flag.strip_prefix("--allow-")?;
+        Self::ALL.into_iter().find(|cap| cap.name() == name)
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Permissions {
+    granted: HashSet<Capability>,
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Self {
+            granted: Capability::ALL.into_iter().collect(),
+        }
+    }
+
+    pub fn grant(&mut self, capability: Capability) {
+        self.granted.insert(capability);
+    }
+
+    pub fn revoke(&mut self, capability: Capability) {
+        self.granted.remove(&capability);
+    }
+
+    pub fn is_granted(&self, capability: Capability) -> bool {
+        self.granted.contains(&capability)
+    }
+
+    /// Called by privileged natives before doing any work.
+    pub fn check(&self, capability: Capability, native: &str) -> Result<(), Error> {
+        if self.is_granted(capability) {
+            return Ok(());
+        }
+
+        Err(Error::PermissionDenied {
+            native: native.to_string(),
+            capability,
+        })
+    }
+}
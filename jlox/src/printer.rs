@@ -0,0 +1,662 @@
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Expr, ExprVisitor, Literal, MatchArm, NodeId, Pattern, Signature, Stmt, StmtVisitor},
+    docs, edit,
+    parser::{self, Parser},
+    scanner::Scanner,
+    token::{Token, TokenType, Trivia},
+    types::Gc,
+};
+
+const INDENT: &str = "    ";
+
+/// Turns AST nodes back into Lox source that parses to the same tree.
+#[derive(Default)]
+pub struct Printer {
+    depth: usize,
+    /// Comments and blank lines still to place, in source order.
+    trivia: VecDeque<Trivia>,
+    /// The last line of the statement being printed, where a block in it
+    /// closes.
+    end_line: usize,
+}
+
+/// `source` pretty-printed, keeping its comments and blank lines.
+pub fn format_source(source: &str) -> Result<String, parser::Error> {
+    let (tokens, trivia): (Vec<Token>, Vec<Vec<Trivia>>) =
+        Scanner::new(source).with_trivia().unzip();
+    let statements = Parser::new(tokens).parse()?;
+    Ok(Printer::with_trivia(trivia.into_iter().flatten()).print_program(statements))
+}
+
+/// Formats the `.lox` files under `root`, returning the ones that weren't
+/// formatted already. They're rewritten unless `check` is set.
+pub fn format_files(root: &Path, check: bool) -> Result<Vec<PathBuf>, docs::Error> {
+    edit::rewrite_files(root, check, format_source)
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A printer that puts `trivia` back between the statements it prints,
+    /// by the lines they were on.
+    pub fn with_trivia(trivia: impl IntoIterator<Item = Trivia>) -> Self {
+        Self {
+            trivia: trivia.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// A whole script, one statement per line.
+    pub fn print_program(&mut self, statements: Vec<Stmt>) -> String {
+        let mut source = String::new();
+        for (i, stmt) in statements.into_iter().enumerate() {
+            self.statement(stmt, &mut source, i == 0);
+        }
+        let first = source.is_empty();
+        self.comments(usize::MAX, &mut source, first);
+        source
+    }
+
+    pub fn print_expr(&mut self, mut expr: Expr) -> String {
+        if let Expr::Call {
+            callee,
+            paren,
+            arguments,
+            piped: true,
+        } = &mut expr
+        {
+            return self.print_pipeline(
+                std::mem::take(callee),
+                std::mem::take(paren),
+                std::mem::take(arguments),
+            );
+        }
+
+        let Ok(source) = self.evaluate(expr);
+        (*source).clone()
+    }
+
+    /// A piped call as it was written, `x |> f` when the stage had no
+    /// parentheses and `x |> f(a)` when it did.
+    fn print_pipeline(&mut self, callee: Expr, paren: Token, mut arguments: Vec<Expr>) -> String {
+        let input = self.print_expr(arguments.remove(0));
+        let callee = self.print_expr(callee);
+        if paren.token_type == TokenType::Pipe {
+            return format!("{input} |> {callee}");
+        }
+
+        let arguments: Vec<String> = arguments
+            .into_iter()
+            .map(|arg| self.print_expr(arg))
+            .collect();
+        format!("{input} |> {callee}({})", arguments.join(", "))
+    }
+
+    pub fn print_stmt(&mut self, stmt: Stmt) -> String {
+        self.end_line = stmt.boundary().end_line;
+        let Ok(source) = self.execute(stmt);
+        source
+    }
+
+    pub fn print_function(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &[Stmt],
+        doc: Option<&str>,
+    ) -> String {
+        format!(
+            "{}fun {}",
+            self.doc_comment(doc),
+            self.function_body(name, params, "", body)
+        )
+    }
+
+    /// `///` lines for `doc`, each followed by the current indentation.
+    fn doc_comment(&self, doc: Option<&str>) -> String {
+        let Some(doc) = doc else {
+            return String::new();
+        };
+
+        doc.split('\n')
+            .map(|line| match line {
+                "" => format!("///\n{}", self.indent()),
+                line => format!("/// {line}\n{}", self.indent()),
+            })
+            .collect()
+    }
+
+    fn indent(&self) -> String {
+        INDENT.repeat(self.depth)
+    }
+
+    /// Writes `stmt` on its own line, after the comments before it and with
+    /// the comment that trails it.
+    fn statement(&mut self, stmt: Stmt, source: &mut String, first: bool) {
+        let boundary = stmt.boundary();
+        if self.comments(boundary.line, source, first) {
+            source.push('\n');
+        }
+        source.push_str(&self.indent());
+        source.push_str(&self.print_stmt(stmt));
+        self.trailing(boundary.end, source);
+        source.push('\n');
+    }
+
+    /// Writes the comments from before `line` on lines of their own. A run
+    /// of blank lines in the source becomes one, except at the start of a
+    /// block; returns whether one is due before what's on `line`.
+    fn comments(&mut self, line: usize, source: &mut String, mut first: bool) -> bool {
+        let mut blank = false;
+        while self.trivia.front().is_some_and(|trivia| match trivia {
+            Trivia::Blank { line: at } => *at <= line,
+            Trivia::Comment { line: at, .. } => *at < line,
+        }) {
+            match self.trivia.pop_front() {
+                Some(Trivia::Comment { text, .. }) => {
+                    if blank && !first {
+                        source.push('\n');
+                    }
+                    source.push_str(&self.indent());
+                    source.push_str(&text);
+                    source.push('\n');
+                    (blank, first) = (false, false);
+                }
+                _ => blank = true,
+            }
+        }
+        blank && !first
+    }
+
+    /// Appends the comment right after the code ending at byte `end`, if
+    /// there is one. A statement nested in another on the same line isn't
+    /// followed by it, so it's left for the enclosing statement.
+    fn trailing(&mut self, end: usize, source: &mut String) {
+        if let Some(Trivia::Comment {
+            trailing: true,
+            after,
+            ..
+        }) = self.trivia.front()
+        {
+            if *after == end {
+                if let Some(Trivia::Comment { text, .. }) = self.trivia.pop_front() {
+                    source.push(' ');
+                    source.push_str(&text);
+                }
+            }
+        }
+    }
+
+    /// `returns` is the ` -> Type` of an annotated function, or empty.
+    fn function_body(
+        &mut self,
+        name: &str,
+        params: &[String],
+        returns: &str,
+        body: &[Stmt],
+    ) -> String {
+        format!(
+            "{name}({}){returns} {}",
+            params.join(", "),
+            self.block(body.to_vec())
+        )
+    }
+
+    fn block(&mut self, statements: Vec<Stmt>) -> String {
+        let end_line = self.end_line;
+        let commented = self
+            .trivia
+            .iter()
+            .take_while(|trivia| trivia.line() < end_line)
+            .any(|trivia| matches!(trivia, Trivia::Comment { .. }));
+        if statements.is_empty() && !commented {
+            return "{}".to_string();
+        }
+
+        self.depth += 1;
+        let mut source = "{\n".to_string();
+        let first = statements.is_empty();
+        for (i, stmt) in statements.into_iter().enumerate() {
+            self.statement(stmt, &mut source, i == 0);
+        }
+        self.comments(end_line, &mut source, first);
+        self.depth -= 1;
+
+        source.push_str(&self.indent());
+        source.push('}');
+        source
+    }
+}
+
+fn print_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Binding(name) => name.lexeme.clone(),
+        Pattern::Literal(literal) => literal.to_string(),
+        Pattern::Range { low, high } => format!("{low}..{high}"),
+        Pattern::Tuple(patterns) => {
+            let patterns: Vec<String> = patterns.iter().map(print_pattern).collect();
+            match patterns.as_slice() {
+                [pattern] => format!("({pattern},)"),
+                _ => format!("({})", patterns.join(", ")),
+            }
+        }
+    }
+}
+
+impl ExprVisitor<String> for Printer {
+    type E = Infallible;
+
+    fn visit_assign_expr(
+        &mut self,
+        _id: NodeId,
+        name: Token,
+        value: Box<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!(
+            "{} = {}",
+            name.lexeme,
+            self.print_expr(*value)
+        )))
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        left: Box<Expr>,
+        op: Token,
+        right: Box<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!(
+            "{} {} {}",
+            self.print_expr(*left),
+            op.lexeme,
+            self.print_expr(*right)
+        )))
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        callee: Box<Expr>,
+        _paren: Token,
+        arguments: Vec<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        let arguments: Vec<String> = arguments
+            .into_iter()
+            .map(|arg| self.print_expr(arg))
+            .collect();
+
+        Ok(Gc::new(format!(
+            "{}({})",
+            self.print_expr(*callee),
+            arguments.join(", ")
+        )))
+    }
+
+    fn visit_get_expr(&mut self, object: Box<Expr>, name: Token) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!(
+            "{}.{}",
+            self.print_expr(*object),
+            name.lexeme
+        )))
+    }
+
+    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!("({})", self.print_expr(*expr))))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        _bracket: Token,
+        index: Box<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!(
+            "{}[{}]",
+            self.print_expr(*object),
+            self.print_expr(*index)
+        )))
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        _bracket: Token,
+        elements: Vec<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        let elements: Vec<String> = elements.into_iter().map(|e| self.print_expr(e)).collect();
+        Ok(Gc::new(format!("#[{}]", elements.join(", "))))
+    }
+
+    fn visit_literal_expr(&mut self, literal: Literal) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(match literal {
+            Literal::Number(n) => n.to_string(),
+            Literal::String(s) => format!("\"{s}\""),
+            Literal::Bytes(bytes) => format!("b\"{}\"", crate::bytes::escape(&bytes)),
+            Literal::True => "true".to_string(),
+            Literal::False => "false".to_string(),
+            Literal::Nil => "nil".to_string(),
+        }))
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: Box<Expr>,
+        op: Token,
+        right: Box<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        self.visit_binary_expr(left, op, right)
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        _brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    ) -> Result<Gc<String>, Self::E> {
+        let entries: Vec<String> = entries
+            .into_iter()
+            .map(|(key, value)| format!("{}: {}", self.print_expr(key), self.print_expr(value)))
+            .collect();
+        Ok(Gc::new(format!("#{{{}}}", entries.join(", "))))
+    }
+
+    fn visit_match_expr(
+        &mut self,
+        _keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    ) -> Result<Gc<String>, Self::E> {
+        let arms: Vec<String> = arms
+            .into_iter()
+            .map(|arm| {
+                let guard = arm.guard.map_or(String::new(), |guard| {
+                    format!(" if {}", self.print_expr(guard))
+                });
+                format!(
+                    "{}{guard} => {}",
+                    print_pattern(&arm.pattern),
+                    self.print_expr(arm.body)
+                )
+            })
+            .collect();
+
+        Ok(Gc::new(format!(
+            "match {} {{ {} }}",
+            self.print_expr(*subject),
+            arms.join(", ")
+        )))
+    }
+
+    fn visit_set_expr(
+        &mut self,
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!(
+            "{}.{} = {}",
+            self.print_expr(*object),
+            name.lexeme,
+            self.print_expr(*value)
+        )))
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        _bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    ) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!(
+            "{}[{}] = {}",
+            self.print_expr(*object),
+            self.print_expr(*index),
+            self.print_expr(*value)
+        )))
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        _id: NodeId,
+        _keyword: Token,
+        ancestor: Option<Token>,
+        method: Token,
+    ) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(match ancestor {
+            Some(ancestor) => format!("super({}).{}", ancestor.lexeme, method.lexeme),
+            None => format!("super.{}", method.lexeme),
+        }))
+    }
+
+    fn visit_this_expr(&mut self, _id: NodeId, _keyword: Token) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new("this".to_string()))
+    }
+
+    fn visit_tuple_expr(&mut self, elements: Vec<Expr>) -> Result<Gc<String>, Self::E> {
+        let elements: Vec<String> = elements.into_iter().map(|e| self.print_expr(e)).collect();
+        Ok(Gc::new(match elements.as_slice() {
+            [element] => format!("({element},)"),
+            _ => format!("({})", elements.join(", ")),
+        }))
+    }
+
+    fn visit_unary_expr(&mut self, op: Token, right: Box<Expr>) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(format!("{}{}", op.lexeme, self.print_expr(*right))))
+    }
+
+    fn visit_variable_expr(&mut self, _id: NodeId, name: Token) -> Result<Gc<String>, Self::E> {
+        Ok(Gc::new(name.lexeme))
+    }
+}
+
+impl StmtVisitor<String> for Printer {
+    type E = Infallible;
+
+    fn visit_block_stmt(&mut self, statements: Vec<Stmt>) -> Result<String, Self::E> {
+        Ok(self.block(statements))
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Stmt>,
+        doc: Option<String>,
+    ) -> Result<String, Self::E> {
+        let end_line = self.end_line;
+        let mut source = format!("{}class {}", self.doc_comment(doc.as_deref()), name.lexeme);
+        if let Some(superclass) = superclass {
+            source.push_str(&format!(" > {}", self.print_expr(superclass)));
+        }
+
+        if methods.is_empty() {
+            source.push_str(" {}");
+            return Ok(source);
+        }
+
+        source.push_str(" {\n");
+        self.depth += 1;
+        for (i, method) in methods.into_iter().enumerate() {
+            if let Stmt::Function {
+                name,
+                params,
+                body,
+                doc,
+                signature,
+                boundary,
+            } = method
+            {
+                if self.comments(boundary.line, &mut source, i == 0) {
+                    source.push('\n');
+                }
+                let (params, returns) = annotated(params, signature);
+                source.push_str(&self.indent());
+                source.push_str(&self.doc_comment(doc.as_deref()));
+                self.end_line = boundary.end_line;
+                source.push_str(&self.function_body(&name.lexeme, &params, &returns, &body));
+                self.trailing(boundary.end, &mut source);
+                source.push('\n');
+            }
+        }
+        self.comments(end_line, &mut source, false);
+        self.depth -= 1;
+        source.push_str(&self.indent());
+        source.push('}');
+
+        Ok(source)
+    }
+
+    fn visit_expression_stmt(&mut self, expr: Expr) -> Result<String, Self::E> {
+        Ok(format!("{};", self.print_expr(expr)))
+    }
+
+    fn visit_for_stmt(
+        &mut self,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+    ) -> Result<String, Self::E> {
+        let initializer = match initializer {
+            Some(initializer) => self.print_stmt(*initializer),
+            None => ";".to_string(),
+        };
+        let condition = condition.map_or(String::new(), |c| format!(" {}", self.print_expr(c)));
+        let increment = increment.map_or(String::new(), |i| format!(" {}", self.print_expr(i)));
+
+        Ok(format!(
+            "for ({initializer}{condition};{increment}) {}",
+            self.print_stmt(*body)
+        ))
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+        doc: Option<String>,
+        signature: Option<Box<Signature>>,
+    ) -> Result<String, Self::E> {
+        let (params, returns) = annotated(params, signature);
+        Ok(format!(
+            "{}fun {}",
+            self.doc_comment(doc.as_deref()),
+            self.function_body(&name.lexeme, &params, &returns, &body)
+        ))
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    ) -> Result<String, Self::E> {
+        let mut source = format!(
+            "if ({}) {}",
+            self.print_expr(condition),
+            self.print_stmt(*then_branch)
+        );
+
+        if let Some(else_branch) = else_branch {
+            source.push_str(&format!(" else {}", self.print_stmt(*else_branch)));
+        }
+
+        Ok(source)
+    }
+
+    fn visit_import_stmt(&mut self, _keyword: Token, path: String) -> Result<String, Self::E> {
+        Ok(format!("import \"{path}\";"))
+    }
+
+    fn visit_print_stmt(&mut self, expr: Expr) -> Result<String, Self::E> {
+        Ok(format!("print {};", self.print_expr(expr)))
+    }
+
+    fn visit_break_stmt(&mut self, _keyword: Token) -> Result<String, Self::E> {
+        Ok("break;".to_string())
+    }
+
+    fn visit_continue_stmt(&mut self, _keyword: Token) -> Result<String, Self::E> {
+        Ok("continue;".to_string())
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        _keyword: Token,
+        value: Option<Expr>,
+    ) -> Result<String, Self::E> {
+        Ok(match value {
+            Some(value) => format!("return {};", self.print_expr(value)),
+            None => "return;".to_string(),
+        })
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: Token,
+        annotation: Option<Token>,
+        initializer: Option<Expr>,
+    ) -> Result<String, Self::E> {
+        let name = match annotation {
+            Some(annotation) => format!("{}: {}", name.lexeme, annotation.lexeme),
+            None => name.lexeme,
+        };
+        Ok(match initializer {
+            Some(init) => format!("var {name} = {};", self.print_expr(init)),
+            None => format!("var {name};"),
+        })
+    }
+
+    fn visit_destructure_stmt(
+        &mut self,
+        _paren: Token,
+        names: Vec<Token>,
+        initializer: Expr,
+    ) -> Result<String, Self::E> {
+        let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+        Ok(format!(
+            "var ({}) = {};",
+            names.join(", "),
+            self.print_expr(initializer)
+        ))
+    }
+
+    fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Result<String, Self::E> {
+        Ok(format!(
+            "while ({}) {}",
+            self.print_expr(condition),
+            self.print_stmt(*body)
+        ))
+    }
+}
+
+/// A function's parameters as written, `name: Type` where annotated, and
+/// the ` -> Type` after them.
+fn annotated(params: Vec<Token>, signature: Option<Box<Signature>>) -> (Vec<String>, String) {
+    let Some(signature) = signature else {
+        return (
+            params.into_iter().map(|p| p.lexeme).collect(),
+            String::new(),
+        );
+    };
+    let params = params
+        .into_iter()
+        .zip(signature.params)
+        .map(|(param, annotation)| match annotation {
+            Some(annotation) => format!("{}: {}", param.lexeme, annotation.lexeme),
+            None => param.lexeme,
+        })
+        .collect();
+    let returns = signature
+        .returns
+        .map_or(String::new(), |returns| format!(" -> {}", returns.lexeme));
+    (params, returns)
+}
@@ -0,0 +1,511 @@
+//! Multi-file programs.
+//!
+//! A [`Program`] starts from one or more entry files and follows their
+//! `import "path";` statements, keeping the tokens and statements of every
+//! file it reaches. Problems in any file (unreadable, unparsable, import
+//! cycles, resolution errors) are collected into one [`Report`] instead of
+//! stopping at the first one. Imported paths are relative to the importing
+//! file, and each file runs once, after everything it imports.
+//!
+//! Scripts may only import with the `read` capability, as importing reads
+//! a file. Errors in imported files never quote their source, so an import
+//! can't be used to show the contents of a file that isn't Lox.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::Stmt,
+    dialect::Dialect,
+    interpreter::Interpreter,
+    parser::{self, Parser},
+    permissions::{Capability, Permissions},
+    resolver::Resolver,
+    scanner::Scanner,
+    token::Token,
+    typecheck::Checker,
+    types::{Gc, GcCell},
+};
+
+/// A problem found in one file of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.path.display(), self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Every diagnostic of a program, in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One file of a program with its cached front-end artifacts.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    path: PathBuf,
+    source: String,
+    tokens: Vec<Token>,
+    statements: Result<Vec<Stmt>, Diagnostic>,
+    imports: Vec<PathBuf>,
+    imported: bool,
+}
+
+impl SourceFile {
+    fn new(path: PathBuf, source: String, dialect: &Dialect, imported: bool) -> Self {
+        let tokens = Scanner::new(&source).with_dialect(dialect).scan_tokens();
+        let statements = Parser::new(tokens.clone())
+            .with_dialect(dialect)
+            .parse()
+            .map_err(|error| parse_diagnostic(&path, &error, !imported));
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let imports = statements
+            .iter()
+            .flatten()
+            .filter_map(|stmt| match stmt {
+                Stmt::Import { path, .. } => Some(normalize(&dir.join(path))),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            path,
+            source,
+            tokens,
+            statements,
+            imports,
+            imported,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The parsed statements, or `None` if the file doesn't parse.
+    pub fn statements(&self) -> Option<&[Stmt]> {
+        self.statements.as_deref().ok()
+    }
+
+    /// Files this one imports, as paths relative to the working directory.
+    pub fn imports(&self) -> &[PathBuf] {
+        &self.imports
+    }
+}
+
+/// Names the offending token only if `quote` is set: imported files may
+/// not be Lox at all.
+pub(crate) fn parse_diagnostic(path: &Path, error: &parser::Error, quote: bool) -> Diagnostic {
+    let at = |token: &Token| match quote {
+        true => format!(" at '{}'.", token.lexeme),
+        false => ".".to_string(),
+    };
+    let (line, message) = match error {
+        parser::Error::Bad { token, msg } if quote => {
+            (Some(token.line()), format!("{msg}{}", at(token)))
+        }
+        parser::Error::Bad { token, msg } => (Some(token.line()), msg.clone()),
+        parser::Error::UnexpectedEof { line, msg } => (Some(*line), format!("{msg} at end.")),
+        parser::Error::InvalidAssignment { token } => {
+            (Some(token.line()), "Invalid assignment target.".to_string())
+        }
+        parser::Error::TooMany { token, what, limit } => (
+            Some(token.line()),
+            format!("Can't have more than {limit} {what}{}", at(token)),
+        ),
+        parser::Error::TooDeep { token, what, limit } => (
+            Some(token.line()),
+            format!(
+                "{what} nested too deeply (more than {limit} levels){}",
+                at(token)
+            ),
+        ),
+    };
+
+    Diagnostic {
+        path: path.to_path_buf(),
+        line,
+        message,
+    }
+}
+
+/// Collapses `.` and `..` components so one file always has one key.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir if normal.file_name().is_some() => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    entries: Vec<PathBuf>,
+    files: HashMap<PathBuf, SourceFile>,
+    /// Files in the order they run: every file after its imports.
+    order: Vec<PathBuf>,
+    report: Report,
+    dialect: Dialect,
+    /// Globals declared at the top level of any file.
+    globals: HashSet<String>,
+    /// References to globals, by file.
+    unresolved: Vec<(PathBuf, Token)>,
+    /// Whether imported files are read, or reported as denied.
+    can_import: bool,
+    /// Whether the entries were themselves imported by a script.
+    entries_imported: bool,
+}
+
+impl Program {
+    /// Reads `entries` and everything they import.
+    pub fn load(entries: impl IntoIterator<Item = impl AsRef<Path>>) -> Self {
+        Self::load_as(entries, Dialect::default())
+    }
+
+    /// Reads `entries` and everything they import, written in `dialect`.
+    pub fn load_as(entries: impl IntoIterator<Item = impl AsRef<Path>>, dialect: Dialect) -> Self {
+        Self::load_with(entries, dialect, &Permissions::all())
+    }
+
+    /// Like [`Program::load_as`] for a script running with `permissions`:
+    /// without [`Capability::Read`], every import is a diagnostic and the
+    /// imported file is never opened.
+    pub fn load_with(
+        entries: impl IntoIterator<Item = impl AsRef<Path>>,
+        dialect: Dialect,
+        permissions: &Permissions,
+    ) -> Self {
+        Self::build_new(entries, dialect, permissions, false)
+    }
+
+    /// Like [`Program::load_with`] for the files a script imports outside
+    /// of any file, so the entries' errors don't quote them either.
+    pub(crate) fn load_imports(
+        imports: impl IntoIterator<Item = impl AsRef<Path>>,
+        dialect: Dialect,
+        permissions: &Permissions,
+    ) -> Self {
+        Self::build_new(imports, dialect, permissions, true)
+    }
+
+    fn build_new(
+        entries: impl IntoIterator<Item = impl AsRef<Path>>,
+        dialect: Dialect,
+        permissions: &Permissions,
+        entries_imported: bool,
+    ) -> Self {
+        let mut program = Self {
+            entries: entries
+                .into_iter()
+                .map(|path| normalize(path.as_ref()))
+                .collect(),
+            dialect,
+            can_import: permissions.is_granted(Capability::Read),
+            entries_imported,
+            ..Self::default()
+        };
+        program.build(&HashMap::new());
+        program
+    }
+
+    /// Replaces the contents of `path` (e.g. an unsaved editor buffer) and
+    /// rebuilds the import graph. Other files keep their cached artifacts.
+    pub fn update(&mut self, path: impl AsRef<Path>, source: String) {
+        let path = normalize(path.as_ref());
+        let mut overrides = HashMap::new();
+        overrides.insert(path.clone(), source);
+
+        // Keep every other file that is already loaded as is.
+        for (other, file) in &self.files {
+            if *other != path {
+                overrides.insert(other.clone(), file.source.clone());
+            }
+        }
+        self.build(&overrides);
+    }
+
+    fn build(&mut self, sources: &HashMap<PathBuf, String>) {
+        let _span = tracing::debug_span!("load", entries = ?self.entries).entered();
+        let mut previous = std::mem::take(&mut self.files);
+        self.order.clear();
+        self.report = Report::default();
+
+        let mut visiting = Vec::new();
+        let mut done = HashSet::new();
+        for entry in self.entries.clone() {
+            self.visit(
+                entry,
+                None,
+                sources,
+                &mut previous,
+                &mut visiting,
+                &mut done,
+            );
+        }
+
+        self.resolve();
+    }
+
+    fn visit(
+        &mut self,
+        path: PathBuf,
+        importer: Option<&Path>,
+        sources: &HashMap<PathBuf, String>,
+        previous: &mut HashMap<PathBuf, SourceFile>,
+        visiting: &mut Vec<PathBuf>,
+        done: &mut HashSet<PathBuf>,
+    ) {
+        if done.contains(&path) {
+            return;
+        }
+
+        if let Some(start) = visiting.iter().position(|p| *p == path) {
+            let cycle: Vec<String> = visiting[start..]
+                .iter()
+                .chain([&path])
+                .map(|p| p.display().to_string())
+                .collect();
+            self.diagnose(
+                &path,
+                None,
+                format!("Import cycle: {}.", cycle.join(" -> ")),
+            );
+            return;
+        }
+
+        let imported = importer.is_some() || self.entries_imported;
+        if let Some(importer) = importer.filter(|_| !self.can_import) {
+            let capability = Capability::Read;
+            let message = format!(
+                "Can't import {}: requires the '{capability}' capability (run with --allow-{capability}).",
+                path.display()
+            );
+            self.diagnose(importer, None, message);
+            done.insert(path);
+            return;
+        }
+
+        let file = match sources.get(&path) {
+            Some(source) => match previous.remove(&path) {
+                Some(file) if file.source == *source && file.imported == imported => file,
+                _ => SourceFile::new(path.clone(), source.clone(), &self.dialect, imported),
+            },
+            None => match fs::read_to_string(&path) {
+                Ok(source) => SourceFile::new(path.clone(), source, &self.dialect, imported),
+                Err(error) => {
+                    let message = match importer {
+                        Some(importer) => format!(
+                            "Can't read module imported by {}: {error}.",
+                            importer.display()
+                        ),
+                        None => format!("Can't read file: {error}."),
+                    };
+                    self.diagnose(&path, None, message);
+                    done.insert(path);
+                    return;
+                }
+            },
+        };
+
+        if let Err(diagnostic) = &file.statements {
+            self.report.diagnostics.push(diagnostic.clone());
+        }
+
+        visiting.push(path.clone());
+        for import in file.imports.clone() {
+            self.visit(import, Some(&path), sources, previous, visiting, done);
+        }
+        visiting.pop();
+
+        done.insert(path.clone());
+        self.order.push(path.clone());
+        self.files.insert(path, file);
+    }
+
+    /// Resolves every parsed file in a scratch interpreter to report scope
+    /// errors without running anything.
+    fn resolve(&mut self) {
+        let interpreter = Gc::new(GcCell::new(Interpreter::new()));
+        self.globals.clear();
+        self.unresolved.clear();
+
+        for path in self.order.clone() {
+            let Some(statements) = self.files[&path].statements() else {
+                continue;
+            };
+
+            let _span = tracing::debug_span!("resolve", path = %path.display()).entered();
+            let mut resolver = Resolver::new(interpreter.clone());
+            if let Err(error) = resolver.resolve(statements) {
+                self.diagnose(&path, None, error.to_string());
+            }
+            self.globals
+                .extend(resolver.declared_globals().iter().cloned());
+            let unresolved = resolver.unresolved_globals().iter();
+            self.unresolved
+                .extend(unresolved.map(|name| (path.clone(), name.clone())));
+        }
+    }
+
+    /// Values that don't match their type annotations, checking each file
+    /// after the files it imports. See [`typecheck`](crate::typecheck).
+    pub fn type_mismatches(&self) -> Vec<Diagnostic> {
+        let mut checker = Checker::default();
+        let mut diagnostics = Vec::new();
+        for file in self.files() {
+            let Some(statements) = file.statements() else {
+                continue;
+            };
+            diagnostics.extend(
+                checker
+                    .check(statements)
+                    .into_iter()
+                    .map(|mismatch| Diagnostic {
+                        path: file.path.clone(),
+                        line: Some(mismatch.line),
+                        message: mismatch.message,
+                    }),
+            );
+        }
+        diagnostics
+    }
+
+    /// References to globals that no file declares at its top level and
+    /// that `interpreter` doesn't define, such as misspelled names. They only
+    /// fail at runtime, and only if the code reaching them runs.
+    pub fn undefined_globals(&self, interpreter: &Interpreter) -> Vec<Diagnostic> {
+        self.unresolved
+            .iter()
+            .filter(|(_, name)| {
+                !self.globals.contains(&name.lexeme)
+                    && interpreter.get_global(&name.lexeme).is_none()
+            })
+            .map(|(path, name)| Diagnostic {
+                path: path.clone(),
+                line: Some(name.line()),
+                message: format!("Undefined variable '{}'.", name.lexeme),
+            })
+            .collect()
+    }
+
+    /// What the `--strict` checks of `interpreter`'s settings forbid, such
+    /// as shadowing, found by resolving each file after the files it imports
+    /// without running anything. Globals `interpreter` defines count as
+    /// declared.
+    pub fn strict_errors(&self, interpreter: &Interpreter) -> Vec<Diagnostic> {
+        let settings = interpreter.settings();
+        if !(settings.forbid_shadowing
+            || settings.forbid_undefined_globals
+            || settings.forbid_print_results)
+        {
+            return Vec::new();
+        }
+
+        let mut scratch = Interpreter::new();
+        scratch.set_settings(settings);
+        for (name, value) in &interpreter.copy_globals().borrow().values {
+            scratch.set_global(name, value.clone());
+        }
+
+        let scratch = Gc::new(GcCell::new(scratch));
+        let mut diagnostics = Vec::new();
+        for file in self.files() {
+            let Some(statements) = file.statements() else {
+                continue;
+            };
+            let mut resolver = Resolver::new(scratch.clone());
+            if let Some((line, message)) = resolver
+                .resolve(statements)
+                .err()
+                .and_then(|error| error.strict())
+            {
+                diagnostics.push(Diagnostic {
+                    path: file.path.clone(),
+                    line: Some(line),
+                    message,
+                });
+            }
+
+            // Later files see the globals this one declares, as when it runs.
+            let mut scratch = scratch.borrow_mut();
+            let nil = scratch.nil();
+            for name in resolver.declared_globals() {
+                scratch.set_global(name, nil.clone());
+            }
+        }
+        diagnostics
+    }
+
+    fn diagnose(&mut self, path: &Path, line: Option<usize>, message: String) {
+        self.report.diagnostics.push(Diagnostic {
+            path: path.to_path_buf(),
+            line,
+            message,
+        });
+    }
+
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<&SourceFile> {
+        self.files.get(&normalize(path.as_ref()))
+    }
+
+    /// Files in execution order: each one after the files it imports.
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile> {
+        self.order.iter().map(|path| &self.files[path])
+    }
+
+    /// Problems found in any file. A program only runs when this is empty.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+}
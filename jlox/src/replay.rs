@@ -0,0 +1,165 @@
+//! Recording and replaying the external inputs of a session.
+//!
+//! Natives that observe the outside world (the clock, file and socket reads)
+//! fetch their result through [`Inputs::provide`]. While recording, every
+//! result is appended to a trace; while replaying, results come from the trace
+//! instead, so a run can be reproduced exactly. Traces are written in the same
+//! line format as snapshots. Side effects such as connecting or
+//! sending still happen, and spawned tasks always run live.
+
+use std::{collections::VecDeque, fmt::Display};
+
+use thiserror::Error;
+
+use crate::{
+    codec::{self, Malformed},
+    interpreter,
+    snapshot::Entry,
+    tasks::Message,
+};
+
+const HEADER: &str = "jlox-trace 1";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Trace I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed trace at line {line}: {msg}")]
+    Malformed { line: usize, msg: String },
+
+    #[error("No session is being recorded.")]
+    NotRecording,
+}
+
+impl From<Malformed> for Error {
+    fn from(Malformed { line, msg }: Malformed) -> Self {
+        Self::Malformed { line, msg }
+    }
+}
+
+/// The inputs observed during a session, in order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Trace {
+    inputs: Vec<(String, Message)>,
+}
+
+impl Trace {
+    pub fn inputs(&self) -> &[(String, Message)] {
+        &self.inputs
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut trace = Self::default();
+        for (line, native, entry) in codec::parse(source, HEADER)? {
+            let input = match entry {
+                Entry::Nil => Message::Nil,
+                Entry::Bool(b) => Message::Bool(b),
+                Entry::Number(n) => Message::Number(n),
+                Entry::String(s) => Message::String(s),
+                Entry::Bytes(b) => Message::Bytes(b),
+                entry => {
+                    return Err(Error::Malformed {
+                        line,
+                        msg: format!("unknown input kind '{}'", entry.kind()),
+                    })
+                }
+            };
+            trace.inputs.push((native, input));
+        }
+
+        Ok(trace)
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries: Vec<(String, Entry)> = self
+            .inputs
+            .iter()
+            .map(|(native, input)| {
+                let entry = match input.clone() {
+                    Message::Nil => Entry::Nil,
+                    Message::Bool(b) => Entry::Bool(b),
+                    Message::Number(n) => Entry::Number(n),
+                    Message::String(s) => Entry::String(s),
+                    Message::Bytes(b) => Entry::Bytes(b),
+                };
+                (native.clone(), entry)
+            })
+            .collect();
+
+        codec::write(f, HEADER, &entries)
+    }
+}
+
+#[derive(Debug, Default)]
+enum Mode {
+    #[default]
+    Live,
+    Record(Trace),
+    Replay(VecDeque<(String, Message)>),
+}
+
+/// Where nondeterministic natives get their results from.
+#[derive(Debug, Default)]
+pub struct Inputs {
+    mode: Mode,
+}
+
+impl Inputs {
+    pub fn live() -> Self {
+        Self::default()
+    }
+
+    pub fn record() -> Self {
+        Self {
+            mode: Mode::Record(Trace::default()),
+        }
+    }
+
+    pub fn replay(trace: Trace) -> Self {
+        Self {
+            mode: Mode::Replay(trace.inputs.into()),
+        }
+    }
+
+    /// The inputs recorded so far, if recording.
+    pub fn trace(&self) -> Option<&Trace> {
+        match &self.mode {
+            Mode::Record(trace) => Some(trace),
+            _ => None,
+        }
+    }
+
+    /// Returns the next input for `native`: from the trace when replaying,
+    /// from `live` otherwise.
+    pub fn provide(
+        &mut self,
+        native: &str,
+        live: impl FnOnce() -> Result<Message, interpreter::Error>,
+    ) -> Result<Message, interpreter::Error> {
+        let replay_error = |msg: String| interpreter::Error::NativeError {
+            name: native.to_string(),
+            msg,
+        };
+
+        match &mut self.mode {
+            Mode::Live => live(),
+            Mode::Record(trace) => {
+                let input = live()?;
+                trace.inputs.push((native.to_string(), input.clone()));
+                Ok(input)
+            }
+            Mode::Replay(inputs) => match inputs.pop_front() {
+                Some((recorded, input)) if recorded == native => Ok(input),
+                Some((recorded, _)) => Err(replay_error(format!(
+                    "replay diverged, the recorded session called {recorded} here."
+                ))),
+                None => Err(replay_error(
+                    "replay ran past the end of the trace.".to_string(),
+                )),
+            },
+        }
+    }
+}
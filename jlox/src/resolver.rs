@@ -1,12 +1,18 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::collections::{HashMap, HashSet};
 
 use thiserror::Error;
 
 use crate::{
-    ast::{Expr, ExprVisitor, Literal, Stmt, StmtVisitor},
-    interpreter::Interpreter,
+    ast::{
+        Boundary, Expr, ExprVisitor, Literal, MatchArm, NodeId, Signature, Stmt, StmtId,
+        StmtVisitor, SymbolId,
+    },
+    interpreter::{Interpreter, MAX_SLOTS},
     object::Object,
+    settings::Settings,
+    stack,
     token::Token,
+    types::{Gc, GcCell},
 };
 
 #[derive(Error, Debug)]
@@ -21,7 +27,7 @@ pub enum Error {
     BadReturn { keyword: Token },
 
     #[error("{stmt:?}: Method statement is not a function.")]
-    MethodStmtNotFunction { stmt: Stmt },
+    MethodStmtNotFunction { stmt: Box<Stmt> },
 
     #[error("{keyword}: Can't use 'this' outside of a class.")]
     ThisOutsideClass { keyword: Token },
@@ -37,6 +43,61 @@ pub enum Error {
 
     #[error("{keyword}: Can't use 'super' in a class with no superclass.")]
     SuperNoSubClass { keyword: Token },
+
+    #[error("{keyword}: Can't use '{}' outside of a loop.", keyword.lexeme)]
+    OutsideLoop { keyword: Token },
+
+    #[error(
+        "'{}' shadows a variable of an enclosing scope [line {}].",
+        name.lexeme,
+        name.line()
+    )]
+    Shadowing { name: Token },
+
+    #[error("Undefined variable '{}' [line {}].", name.lexeme, name.line())]
+    UndefinedGlobal { name: Token },
+
+    #[error("Can't use the result of 'print', it is always nil [line {}].", name.line())]
+    PrintResult { name: Token },
+
+    #[error("Nested too deeply to resolve.")]
+    StackOverflow,
+}
+
+/// The names `statements` declare in the scope they run in.
+fn declared_names(statements: &[Stmt]) -> impl Iterator<Item = String> + '_ {
+    statements.iter().flat_map(|stmt| match stmt {
+        Stmt::Var { name, .. } | Stmt::Function { name, .. } | Stmt::Class { name, .. } => {
+            vec![name.lexeme.clone()]
+        }
+        Stmt::Destructure { names, .. } => names.iter().map(|name| name.lexeme.clone()).collect(),
+        _ => vec![],
+    })
+}
+
+impl Error {
+    /// The line and message of the errors `--strict` adds, which programs
+    /// report as diagnostics of the file they're found in.
+    pub fn strict(&self) -> Option<(usize, String)> {
+        let (name, message) = match self {
+            Self::Shadowing { name } => (
+                name,
+                format!(
+                    "'{}' shadows a variable of an enclosing scope.",
+                    name.lexeme
+                ),
+            ),
+            Self::UndefinedGlobal { name } => {
+                (name, format!("Undefined variable '{}'.", name.lexeme))
+            }
+            Self::PrintResult { name } => (
+                name,
+                "Can't use the result of 'print', it is always nil.".to_owned(),
+            ),
+            _ => return None,
+        };
+        Some((name.line(), message))
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -54,32 +115,84 @@ enum ClassType {
     SubClass,
 }
 
+/// What is learned about a function while its body is resolved, to tell
+/// whether its parameters can be kept in slots rather than a scope.
+struct SlotFrame {
+    /// The declaration, until something rules the slots out: a capture, or
+    /// a declaration of the function's own.
+    owner: Option<StmtId>,
+    /// Where the scope of the parameters is in `scopes`.
+    base: usize,
+    params: Vec<SymbolId>,
+    /// The references to the parameters, with their slots.
+    references: Vec<(NodeId, usize)>,
+}
+
 pub struct Resolver {
-    interpreter: Rc<RefCell<Interpreter>>,
-    scopes: Vec<HashMap<String, bool>>,
+    interpreter: Gc<GcCell<Interpreter>>,
+    scopes: Vec<HashMap<SymbolId, bool>>,
+    /// One per function being resolved, innermost last.
+    slot_frames: Vec<SlotFrame>,
+    /// The statement being resolved.
+    statement: StmtId,
     current_fn: FunctionType,
     current_class: ClassType,
+    /// Loops enclosing the current statement within the current function.
+    loops: usize,
+    settings: Settings,
+    /// Globals the top level of the resolved statements declares.
+    globals: HashSet<String>,
+    /// References found in no scope, in source order.
+    unresolved: Vec<Token>,
 }
 
 impl Resolver {
-    pub fn new(interpreter: Rc<RefCell<Interpreter>>) -> Self {
+    pub fn new(interpreter: Gc<GcCell<Interpreter>>) -> Self {
+        let settings = interpreter.borrow().settings();
         Self {
             interpreter,
             scopes: Vec::new(),
+            slot_frames: Vec::new(),
+            statement: Boundary::NONE.id,
             current_fn: FunctionType::None,
             current_class: ClassType::None,
+            loops: 0,
+            settings,
+            globals: HashSet::new(),
+            unresolved: Vec::new(),
         }
     }
 
-    pub fn resolve(&mut self, statements: &Vec<Stmt>) -> Result<(), Error> {
-        for statement in statements.into_iter() {
+    /// The globals declared at the top level of what was resolved.
+    pub fn declared_globals(&self) -> &HashSet<String> {
+        &self.globals
+    }
+
+    /// The references to globals in what was resolved, which may be defined
+    /// elsewhere or nowhere at all.
+    pub fn unresolved_globals(&self) -> &[Token] {
+        &self.unresolved
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        if self.scopes.is_empty() {
+            self.globals.extend(declared_names(statements));
+        }
+
+        for statement in statements.iter() {
             self.resolve_stmt(statement)?;
         }
 
         Ok(())
     }
 
+    /// Resolves a lone expression evaluated in the global scope.
+    pub fn resolve_expression(&mut self, expr: &Expr) -> Result<(), Error> {
+        self.resolve_expr(expr.clone())
+    }
+
     fn resolve_stmt(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        self.statement = stmt.boundary().id;
         self.execute(stmt.clone())?;
         Ok(())
     }
@@ -107,13 +220,27 @@ impl Resolver {
             .last_mut()
             .expect("Scopes stack is empty when peeking");
 
-        if scope.contains_key(&name.lexeme) {
+        let symbol = SymbolId::intern(&name.lexeme);
+        if scope.contains_key(&symbol) {
             return Err(Error::DoubleVariable {
                 name: name.lexeme.clone(),
             });
         }
 
-        scope.insert(name.lexeme.to_owned(), false);
+        scope.insert(symbol, false);
+        // Anything but a parameter needs a scope to live in.
+        if let Some(frame) = self.slot_frames.last_mut() {
+            if self.scopes.len() - 1 > frame.base || !frame.params.contains(&symbol) {
+                frame.owner = None;
+            }
+        }
+
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+        if self.settings.forbid_shadowing
+            && enclosing.iter().any(|scope| scope.contains_key(&symbol))
+        {
+            return Err(Error::Shadowing { name: name.clone() });
+        }
         Ok(())
     }
 
@@ -126,62 +253,171 @@ impl Resolver {
             .scopes
             .last_mut()
             .expect("Scopes stack is empty when peeking (2)");
-        scope.insert(name.lexeme.to_owned(), true);
+        scope.insert(SymbolId::intern(&name.lexeme), true);
+    }
+
+    /// Records how many scopes out `name` was declared, innermost first.
+    /// Names found in no scope are left unresolved and looked up as globals
+    /// at runtime, so functions may refer to globals defined after them.
+    fn resolve_local(&mut self, id: NodeId, name: &Token) -> Result<(), Error> {
+        let symbol = SymbolId::intern(&name.lexeme);
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&symbol) {
+                let index = self.scopes.len() - 1 - depth;
+                if let Some(frame) = self.slot_frames.last_mut() {
+                    if index < frame.base {
+                        frame.owner = None;
+                    } else if index == frame.base {
+                        if let Some(slot) = frame.params.iter().position(|p| *p == symbol) {
+                            frame.references.push((id, slot));
+                        }
+                    }
+                }
+                self.interpreter.borrow_mut().resolve(id, depth);
+                return Ok(());
+            }
+        }
+
+        if self.settings.forbid_undefined_globals && !self.is_global(&name.lexeme) {
+            return Err(Error::UndefinedGlobal { name: name.clone() });
+        }
+        self.unresolved.push(name.clone());
+        Ok(())
     }
 
-    fn resolve_local(&mut self, name: &Token) {
-        for (i, scope) in self.scopes.iter().enumerate() {
-            if scope.contains_key(&name.lexeme) {
-                self.interpreter
-                    .borrow_mut()
-                    .resolve(name, self.scopes.len() - 1 - i);
-                return;
+    /// Whether `name` is declared at the top level or already defined, by
+    /// the host or by code run before.
+    fn is_global(&self, name: &str) -> bool {
+        self.globals.contains(name) || self.interpreter.borrow().get_global(name).is_some()
+    }
+
+    /// The global `print` function, when `expr` names it.
+    fn print_function(&self, expr: &Expr) -> Option<Token> {
+        match expr {
+            Expr::Variable { name, .. }
+                if name.lexeme == "print"
+                    && !self
+                        .scopes
+                        .iter()
+                        .any(|scope| scope.contains_key(&SymbolId::intern("print"))) =>
+            {
+                Some(name.clone())
             }
+            _ => None,
         }
     }
 
+    /// Resolves a function's body; `name` is `None` for methods.
     fn resolve_function(
         &mut self,
+        name: Option<&str>,
         params: Vec<Token>,
         body: Vec<Stmt>,
         fn_type: FunctionType,
     ) -> Result<(), Error> {
+        let owner = self.statement;
+        let slotted = fn_type == FunctionType::Function
+            && params.len() <= MAX_SLOTS
+            && owner != Boundary::NONE.id;
         let enclosing_function = self.current_fn;
         self.current_fn = fn_type;
+        // A loop around the declaration doesn't enclose the body.
+        let enclosing_loops = std::mem::take(&mut self.loops);
 
         self.begin_scope();
-
-        for param in params {
-            self.declare(&param)?;
-            self.define(&param);
+        self.slot_frames.push(SlotFrame {
+            owner: slotted.then_some(owner),
+            base: self.scopes.len() - 1,
+            params: params
+                .iter()
+                .map(|param| SymbolId::intern(&param.lexeme))
+                .collect(),
+            references: Vec::new(),
+        });
+
+        for param in &params {
+            self.declare(param)?;
+            self.define(param);
         }
 
         self.resolve(&body)?;
         self.end_scope();
+        let frame = self
+            .slot_frames
+            .pop()
+            .expect("Popped an empty slot frames stack");
+        if let (Some(owner), Some(name)) = (frame.owner, name) {
+            self.interpreter
+                .borrow_mut()
+                .resolve_slots(owner, name, frame.references);
+        }
         self.current_fn = enclosing_function;
+        self.loops = enclosing_loops;
+
+        Ok(())
+    }
+
+    fn resolve_call(&mut self, callee: Expr, arguments: Vec<Expr>) -> Result<(), Error> {
+        self.resolve_expr(callee)?;
+
+        for argument in arguments {
+            self.resolve_expr(argument)?;
+        }
 
         Ok(())
     }
+
+    fn resolve_loop_body(&mut self, body: &Stmt) -> Result<(), Error> {
+        let enclosing_loops = self.loops;
+        self.loops += 1;
+        let result = self.resolve_stmt(body);
+        self.loops = enclosing_loops;
+        result
+    }
+
+    fn check_in_loop(&self, keyword: Token) -> Result<Object, Error> {
+        if self.loops == 0 {
+            return Err(Error::OutsideLoop { keyword });
+        }
+
+        Ok(Object::Nil)
+    }
 }
 
 impl ExprVisitor<Object> for Resolver {
     type E = Error;
 
-    fn visit_variable_expr(&mut self, name: Token) -> Result<Rc<Object>, Self::E> {
-        if !self.scopes.is_empty() && !(self.scopes.last().unwrap().get(&name.lexeme).unwrap()) {
+    fn enter_expr(&mut self) -> Result<(), Self::E> {
+        match stack::exhausted() {
+            true => Err(Error::StackOverflow),
+            false => Ok(()),
+        }
+    }
+
+    /// Only a local read in its own initializer, in the scope declaring it,
+    /// is an error; anything else resolves or falls back to a global.
+    fn visit_variable_expr(&mut self, id: NodeId, name: Token) -> Result<Gc<Object>, Self::E> {
+        let scope = self.scopes.last();
+        let symbol = SymbolId::intern(&name.lexeme);
+        if scope.and_then(|scope| scope.get(&symbol)) == Some(&false) {
             return Err(Error::ReadInitializer { expr: name });
         }
 
-        self.resolve_local(&name);
+        self.resolve_local(id, &name)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_assign_expr(&mut self, name: Token, value: Box<Expr>) -> Result<Rc<Object>, Self::E> {
+    fn visit_assign_expr(
+        &mut self,
+        id: NodeId,
+        name: Token,
+        value: Box<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*value)?;
-        self.resolve_local(&name);
+        self.resolve_local(id, &name)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
     fn visit_binary_expr(
@@ -189,42 +425,70 @@ impl ExprVisitor<Object> for Resolver {
         left: Box<Expr>,
         _op: Token,
         right: Box<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
+    ) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*left)?;
         self.resolve_expr(*right)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
+    /// Calls of `print` only get here when their result is used; statements
+    /// calling it are resolved by [`Resolver::resolve_call`].
     fn visit_call_expr(
         &mut self,
         callee: Box<Expr>,
         _paren: Token,
         arguments: Vec<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
-        self.resolve_expr(*callee)?;
-
-        for argument in arguments {
-            self.resolve_expr(argument)?;
+    ) -> Result<Gc<Object>, Self::E> {
+        if let Some(name) = self.print_function(&callee) {
+            if self.settings.forbid_print_results {
+                return Err(Error::PrintResult { name });
+            }
         }
 
-        Ok(Rc::new(Object::Nil))
+        self.resolve_call(*callee, arguments)?;
+
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_get_expr(&mut self, object: Box<Expr>, _name: Token) -> Result<Rc<Object>, Self::E> {
+    fn visit_get_expr(&mut self, object: Box<Expr>, _name: Token) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*object)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Rc<Object>, Self::E> {
+    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*expr)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        _bracket: Token,
+        index: Box<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
+        self.resolve_expr(*object)?;
+        self.resolve_expr(*index)?;
+
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_literal_expr(&mut self, _literal: Literal) -> Result<Rc<Object>, Self::E> {
-        Ok(Rc::new(Object::Nil))
+    fn visit_list_expr(
+        &mut self,
+        _bracket: Token,
+        elements: Vec<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
+        for element in elements {
+            self.resolve_expr(element)?;
+        }
+
+        Ok(Gc::new(Object::Nil))
+    }
+
+    fn visit_literal_expr(&mut self, _literal: Literal) -> Result<Gc<Object>, Self::E> {
+        Ok(Gc::new(Object::Nil))
     }
 
     fn visit_logical_expr(
@@ -232,11 +496,11 @@ impl ExprVisitor<Object> for Resolver {
         left: Box<Expr>,
         _op: Token,
         right: Box<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
+    ) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*left)?;
         self.resolve_expr(*right)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
     fn visit_set_expr(
@@ -244,45 +508,115 @@ impl ExprVisitor<Object> for Resolver {
         object: Box<Expr>,
         _name: Token,
         value: Box<Expr>,
-    ) -> Result<Rc<Object>, Self::E> {
+    ) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*value)?;
         self.resolve_expr(*object)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: Box<Expr>,
+        _bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    ) -> Result<Gc<Object>, Self::E> {
+        self.resolve_expr(*object)?;
+        self.resolve_expr(*index)?;
+        self.resolve_expr(*value)?;
+
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_super_expr(&mut self, keyword: Token, _method: Token) -> Result<Rc<Object>, Self::E> {
+    fn visit_super_expr(
+        &mut self,
+        id: NodeId,
+        keyword: Token,
+        _ancestor: Option<Token>,
+        _method: Token,
+    ) -> Result<Gc<Object>, Self::E> {
         if self.current_class == ClassType::None {
             return Err(Error::SuperOutsideClass { keyword });
         } else if self.current_class != ClassType::SubClass {
             return Err(Error::SuperNoSubClass { keyword });
         }
 
-        self.resolve_local(&keyword);
+        self.resolve_local(id, &keyword)?;
+
+        Ok(Gc::new(Object::Nil))
+    }
+
+    /// Each arm is a scope of its own, holding the names its pattern binds.
+    fn visit_map_expr(
+        &mut self,
+        _brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    ) -> Result<Gc<Object>, Self::E> {
+        for (key, value) in entries {
+            self.resolve_expr(key)?;
+            self.resolve_expr(value)?;
+        }
+
+        Ok(Gc::new(Object::Nil))
+    }
+
+    fn visit_match_expr(
+        &mut self,
+        _keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    ) -> Result<Gc<Object>, Self::E> {
+        self.resolve_expr(*subject)?;
+
+        for arm in arms {
+            self.begin_scope();
+            for name in arm.pattern.bindings() {
+                self.declare(name)?;
+                self.define(name);
+            }
+            if let Some(guard) = arm.guard {
+                self.resolve_expr(guard)?;
+            }
+            self.resolve_expr(arm.body)?;
+            self.end_scope();
+        }
+
+        Ok(Gc::new(Object::Nil))
+    }
+
+    fn visit_tuple_expr(&mut self, elements: Vec<Expr>) -> Result<Gc<Object>, Self::E> {
+        for element in elements {
+            self.resolve_expr(element)?;
+        }
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_this_expr(&mut self, keyword: Token) -> Result<Rc<Object>, Self::E> {
+    fn visit_this_expr(&mut self, id: NodeId, keyword: Token) -> Result<Gc<Object>, Self::E> {
         if self.current_class == ClassType::None {
             return Err(Error::ThisOutsideClass { keyword });
         }
 
-        self.resolve_local(&keyword);
+        self.resolve_local(id, &keyword)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 
-    fn visit_unary_expr(&mut self, _op: Token, right: Box<Expr>) -> Result<Rc<Object>, Self::E> {
+    fn visit_unary_expr(&mut self, _op: Token, right: Box<Expr>) -> Result<Gc<Object>, Self::E> {
         self.resolve_expr(*right)?;
 
-        Ok(Rc::new(Object::Nil))
+        Ok(Gc::new(Object::Nil))
     }
 }
 
 impl StmtVisitor<Object> for Resolver {
     type E = Error;
 
+    fn enter_stmt(&mut self) -> Result<(), Self::E> {
+        self.enter_expr()
+    }
+
     fn visit_block_stmt(&mut self, statements: Vec<Stmt>) -> Result<Object, Self::E> {
         self.begin_scope();
         self.resolve(&statements)?;
@@ -296,6 +630,7 @@ impl StmtVisitor<Object> for Resolver {
         name: Token,
         superclass: Option<Expr>,
         methods: Vec<Stmt>,
+        _doc: Option<String>,
     ) -> Result<Object, Self::E> {
         let enclosing_class = self.current_class;
         self.current_class = ClassType::Class;
@@ -305,8 +640,8 @@ impl StmtVisitor<Object> for Resolver {
 
         let there_is_superclass = superclass.is_some();
         if let Some(sclass) = superclass {
-            if let Expr::Variable { name: sname } = &sclass {
-                if &sname.lexeme == &name.lexeme {
+            if let Expr::Variable { name: sname, .. } = &sclass {
+                if sname.lexeme == name.lexeme {
                     return Err(Error::ClassBootstrap { keyword: name });
                 }
             }
@@ -318,29 +653,34 @@ impl StmtVisitor<Object> for Resolver {
             self.scopes
                 .last_mut()
                 .unwrap()
-                .insert("super".to_string(), true);
+                .insert(SymbolId::intern("super"), true);
         }
 
         self.begin_scope();
         self.scopes
             .last_mut()
             .unwrap()
-            .insert("this".to_string(), true);
+            .insert(SymbolId::intern("this"), true);
 
         for method in methods {
-            let declaration = if &name.lexeme == "init" {
-                FunctionType::Initializer
-            } else {
-                FunctionType::Method
-            };
-
             match method {
                 Stmt::Function {
-                    name: _,
-                    params,
-                    body,
-                } => self.resolve_function(params, body, declaration)?,
-                _ => return Err(Error::MethodStmtNotFunction { stmt: method }),
+                    name, params, body, ..
+                } => {
+                    // Only a method named `init` is an initializer, whatever
+                    // the class is called.
+                    let declaration = if name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(None, params, body, declaration)?
+                }
+                _ => {
+                    return Err(Error::MethodStmtNotFunction {
+                        stmt: Box::new(method),
+                    })
+                }
             };
         }
 
@@ -358,6 +698,7 @@ impl StmtVisitor<Object> for Resolver {
     fn visit_var_stmt(
         &mut self,
         name: Token,
+        _annotation: Option<Token>,
         initializer: Option<Expr>,
     ) -> Result<Object, Self::E> {
         self.declare(&name)?;
@@ -369,22 +710,55 @@ impl StmtVisitor<Object> for Resolver {
         Ok(Object::Nil)
     }
 
+    fn visit_destructure_stmt(
+        &mut self,
+        _paren: Token,
+        names: Vec<Token>,
+        initializer: Expr,
+    ) -> Result<Object, Self::E> {
+        for (i, name) in names.iter().enumerate() {
+            // Checked here too, as `declare` lets globals be redeclared.
+            if names[..i]
+                .iter()
+                .any(|earlier| earlier.lexeme == name.lexeme)
+            {
+                return Err(Error::DoubleVariable {
+                    name: name.lexeme.clone(),
+                });
+            }
+            self.declare(name)?;
+        }
+        self.evaluate(initializer)?;
+        for name in &names {
+            self.define(name);
+        }
+
+        Ok(Object::Nil)
+    }
+
     fn visit_function_stmt(
         &mut self,
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
+        _doc: Option<String>,
+        _signature: Option<Box<Signature>>,
     ) -> Result<Object, Self::E> {
         self.declare(&name)?;
         self.define(&name);
 
-        self.resolve_function(params, body, FunctionType::Function)?;
+        self.resolve_function(Some(&name.lexeme), params, body, FunctionType::Function)?;
 
         Ok(Object::Nil)
     }
 
-    fn visit_expression_stmt(&mut self, expr: Expr) -> Result<Object, Self::E> {
-        self.resolve_expr(expr)?;
+    fn visit_expression_stmt(&mut self, mut expr: Expr) -> Result<Object, Self::E> {
+        match &mut expr {
+            Expr::Call {
+                callee, arguments, ..
+            } => self.resolve_call(std::mem::take(callee), std::mem::take(arguments))?,
+            _ => self.resolve_expr(expr)?,
+        };
 
         Ok(Object::Nil)
     }
@@ -396,15 +770,19 @@ impl StmtVisitor<Object> for Resolver {
         else_branch: Option<Box<Stmt>>,
     ) -> Result<Object, Self::E> {
         self.resolve_expr(condition)?;
-        self.resolve_stmt(&*then_branch)?;
+        self.resolve_stmt(&then_branch)?;
 
         if let Some(else_part) = else_branch {
-            self.resolve_stmt(&*else_part)?;
+            self.resolve_stmt(&else_part)?;
         }
 
         Ok(Object::Nil)
     }
 
+    fn visit_import_stmt(&mut self, _keyword: Token, _path: String) -> Result<Object, Self::E> {
+        Ok(Object::Nil)
+    }
+
     fn visit_print_stmt(&mut self, expr: Expr) -> Result<Object, Self::E> {
         self.resolve_expr(expr)?;
 
@@ -430,9 +808,42 @@ impl StmtVisitor<Object> for Resolver {
         Ok(Object::Nil)
     }
 
+    /// The loop variables live in a scope of their own, which the
+    /// interpreter replaces with a fresh copy on every iteration.
+    fn visit_for_stmt(
+        &mut self,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+    ) -> Result<Object, Self::E> {
+        self.begin_scope();
+        if let Some(initializer) = initializer {
+            self.resolve_stmt(&initializer)?;
+        }
+        if let Some(condition) = condition {
+            self.resolve_expr(condition)?;
+        }
+        self.resolve_loop_body(&body)?;
+        if let Some(increment) = increment {
+            self.resolve_expr(increment)?;
+        }
+        self.end_scope();
+
+        Ok(Object::Nil)
+    }
+
+    fn visit_break_stmt(&mut self, keyword: Token) -> Result<Object, Self::E> {
+        self.check_in_loop(keyword)
+    }
+
+    fn visit_continue_stmt(&mut self, keyword: Token) -> Result<Object, Self::E> {
+        self.check_in_loop(keyword)
+    }
+
     fn visit_while_stmt(&mut self, condition: Expr, body: Box<Stmt>) -> Result<Object, Self::E> {
         self.resolve_expr(condition)?;
-        self.resolve_stmt(&*body)?;
+        self.resolve_loop_body(&body)?;
 
         Ok(Object::Nil)
     }
@@ -0,0 +1,276 @@
+//! What the Rust that `jlox transpile` writes runs on: a dynamically typed
+//! [`Value`], the operators on it, the variables closures and globals
+//! share, and the natives a transpiled script can call.
+//!
+//! Every operation checks its operands like the interpreter and fails with
+//! the same message, and values print like `print` prints them, so a
+//! transpiled script behaves like the original.
+//!
+//! ```
+//! use jlox::runtime::{Result, Value};
+//!
+//! fn square(x: Value) -> Result<Value> {
+//!     x.mul(&x)
+//! }
+//!
+//! let square = Value::function("square", 1, |args| square(args[0].clone()));
+//! assert_eq!(square.call([Value::Number(3.0)]), Ok(Value::Number(9.0)));
+//! assert!(square.call([]).is_err());
+//! ```
+
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Display},
+    process::ExitCode,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A runtime error, with the interpreter's message for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(pub String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A Lox value. `{:?}` prints it the way `print` does.
+#[derive(Debug, Clone, Default)]
+pub enum Value {
+    #[default]
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Function),
+}
+
+type Code = dyn Fn(&[Value]) -> Result<Value>;
+
+/// A function value: a transpiled function or closure, or a native.
+#[derive(Clone)]
+pub struct Function {
+    name: Rc<str>,
+    arity: usize,
+    code: Rc<Code>,
+}
+
+impl Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<lox function>")
+    }
+}
+
+/// How a value reads in an error message.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nil => f.write_str("nil"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::String(s) => f.write_str(s),
+            Self::Function(function) => write!(f, "{function:?}"),
+        }
+    }
+}
+
+/// Lox's `==`: values of different types are never equal, and neither are
+/// functions.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::String(s.into())
+    }
+}
+
+impl Value {
+    /// A function called with its arguments once [`Value::call`] has
+    /// checked there are `arity` of them.
+    pub fn function(
+        name: &str,
+        arity: usize,
+        code: impl Fn(&[Value]) -> Result<Value> + 'static,
+    ) -> Self {
+        Self::Function(Function {
+            name: name.into(),
+            arity,
+            code: Rc::new(code),
+        })
+    }
+
+    pub fn truthy(&self) -> bool {
+        match self {
+            Self::Nil => false,
+            Self::Bool(b) => *b,
+            _ => true,
+        }
+    }
+
+    pub fn number(&self) -> Result<f64> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            _ => Err(Error(format!(
+                "Cast conversion failed: {self} is not a number"
+            ))),
+        }
+    }
+
+    /// `+`, adding numbers or joining strings.
+    pub fn add(&self, other: &Value) -> Result<Value> {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => Ok(Self::Number(a + b)),
+            (Self::String(a), Self::String(b)) => Ok(Self::String(format!("{a}{b}").into())),
+            _ => Err(Error(format!(
+                "Unsupported addition between {self:?} and {other:?}"
+            ))),
+        }
+    }
+
+    pub fn sub(&self, other: &Value) -> Result<Value> {
+        Ok(Self::Number(self.number()? - other.number()?))
+    }
+
+    pub fn mul(&self, other: &Value) -> Result<Value> {
+        Ok(Self::Number(self.number()? * other.number()?))
+    }
+
+    pub fn div(&self, other: &Value) -> Result<Value> {
+        let (a, b) = (self.number()?, other.number()?);
+        if b == 0.0 {
+            return Err(Error("Division by zero".to_owned()));
+        }
+        Ok(Self::Number(a / b))
+    }
+
+    pub fn negate(&self) -> Result<Value> {
+        Ok(Self::Number(-self.number()?))
+    }
+
+    pub fn less(&self, other: &Value) -> Result<bool> {
+        Ok(self.number()? < other.number()?)
+    }
+
+    pub fn less_equal(&self, other: &Value) -> Result<bool> {
+        Ok(self.number()? <= other.number()?)
+    }
+
+    pub fn greater(&self, other: &Value) -> Result<bool> {
+        Ok(self.number()? > other.number()?)
+    }
+
+    pub fn greater_equal(&self, other: &Value) -> Result<bool> {
+        Ok(self.number()? >= other.number()?)
+    }
+
+    pub fn call<const N: usize>(&self, args: [Value; N]) -> Result<Value> {
+        let Self::Function(function) = self else {
+            return Err(Error(format!("Object is not callable: {self:?}")));
+        };
+        if args.len() != function.arity {
+            return Err(Error(format!(
+                "Expected {} arguments but got {} in call to '{}'",
+                function.arity,
+                args.len(),
+                function.name
+            )));
+        }
+        (function.code)(&args)
+    }
+}
+
+/// A local that closures capture, shared between them and the function
+/// that declared it.
+#[derive(Debug, Clone, Default)]
+pub struct Var(Rc<RefCell<Value>>);
+
+impl Var {
+    pub fn new(value: Value) -> Self {
+        Self(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn get(&self) -> Value {
+        self.0.borrow().clone()
+    }
+
+    pub fn set(&self, value: Value) {
+        *self.0.borrow_mut() = value;
+    }
+}
+
+/// A global that functions use. It exists from the start of the script,
+/// but reading or assigning it fails until its declaration has run.
+#[derive(Debug, Clone)]
+pub struct Global {
+    name: &'static str,
+    value: Rc<RefCell<Option<Value>>>,
+}
+
+impl Global {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: Rc::default(),
+        }
+    }
+
+    pub fn define(&self, value: Value) {
+        *self.value.borrow_mut() = Some(value);
+    }
+
+    pub fn get(&self) -> Result<Value> {
+        self.value.borrow().clone().ok_or_else(|| self.undefined())
+    }
+
+    pub fn set(&self, value: Value) -> Result<()> {
+        match &mut *self.value.borrow_mut() {
+            Some(old) => *old = value,
+            None => return Err(self.undefined()),
+        }
+        Ok(())
+    }
+
+    fn undefined(&self) -> Error {
+        Error(format!(
+            "Environment error: UndefinedVariable {{ name: {:?} }}",
+            self.name
+        ))
+    }
+}
+
+/// `clock()`: milliseconds since the Unix epoch.
+pub fn clock() -> Result<Value> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    Ok(Value::Number(now as f64))
+}
+
+/// Runs a transpiled script, reporting an error the way `jlox` does and
+/// exiting with its status.
+pub fn run(script: impl FnOnce() -> Result<()>) -> ExitCode {
+    match script() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::from(70)
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use phf::phf_map;
 use thiserror::Error;
 
 use crate::{
     ast::Literal,
-    token::{Token, TokenType},
+    dialect::Dialect,
+    token::{Token, TokenType, Trivia},
+    types::Number,
 };
 
 #[derive(Error, Debug)]
@@ -11,43 +12,65 @@ enum Error {
     #[error("Unexpected character.")]
     UnexpectedChar,
 
-    #[error("Undetermined String")]
-    UndeterminedString,
+    #[error("Unterminated string.")]
+    UnterminatedString,
+
+    #[error("Invalid escape in bytes literal.")]
+    InvalidEscape,
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 type TT = TokenType;
 
-static KEYWORDS: phf::Map<&'static str, TT> = phf_map! {
-    "and" => TT::And,
-    "class" => TT::Class,
-    "else" => TT::Else,
-    "false" => TT::False,
-    "for" => TT::For,
-    "fun" => TT::Fun,
-    "if" => TT::If,
-    "nil" => TT::Nil,
-    "or" => TT::Or,
-    "print" => TT::Print,
-    "return" => TT::Return,
-    "super" => TT::Super,
-    "this" => TT::This,
-    "true" => TT::True,
-    "var" => TT::Var,
-    "while" => TT::While,
-};
-
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
+}
+
+// Only ASCII starts or continues an identifier, so lexemes never split a
+// multi-byte character.
+fn is_alpha(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
 }
 
+fn is_alphanumeric(c: char) -> bool {
+    is_alpha(c) || is_digit(c)
+}
+
+/// Turns source into tokens. It is an iterator yielding one token at a time,
+/// ending with EOF. The parser pulls them one at a time as it goes, keeping
+/// only the token it is looking at, so a token stream is never materialized.
+///
+/// Scanning never fails: text that isn't a token, such as a stray `@` or a
+/// string missing its closing quote, becomes a [`TokenType::Error`] token
+/// whose literal says what's wrong. The parser reports it where it meets
+/// it, and tools that only want the tokens get all of them.
+///
+/// ```
+/// use jlox::{ast::Literal, scanner::Scanner, token::TokenType};
+///
+/// let tokens: Vec<_> = Scanner::new("1 @ 2").collect();
+/// assert_eq!(tokens[1].token_type, TokenType::Error);
+/// assert_eq!(tokens[1].literal, Some(Literal::String("Unexpected character.".into())));
+/// assert_eq!(tokens[2].lexeme, "2");
+/// ```
 pub struct Scanner<'a> {
     source: &'a [u8],
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    finished: bool,
+    dialect: &'a Dialect,
+    /// Trivia waiting for the next token, when scanning with trivia.
+    trivia: Option<Vec<Trivia>>,
+    /// The trivia before the token most recently scanned.
+    attached: Vec<Trivia>,
+    /// Newlines since the last token or comment, counting the start of the
+    /// source as one.
+    newlines: usize,
+    /// Where the token most recently scanned ends.
+    token_end: usize,
 }
 
 impl<'a> Scanner<'a> {
@@ -58,18 +81,35 @@ impl<'a> Scanner<'a> {
             start: 0,
             current: 0,
             line: 1,
+            finished: false,
+            dialect: Dialect::standard(),
+            trivia: None,
+            attached: Vec::new(),
+            newlines: 1,
+            token_end: 0,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            let _ = self.scan_token();
-        }
+    /// Yields each token with the comments and blank lines before it, for
+    /// tools that reprint the source.
+    pub fn with_trivia(mut self) -> WithTrivia<'a> {
+        self.trivia = Some(Vec::new());
+        WithTrivia(self)
+    }
 
-        self.tokens.push(Token::new(TT::EOF, "", None, self.line));
+    /// Scans the keywords of `dialect` instead of standard Lox's.
+    pub fn with_dialect(mut self, dialect: &'a Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
 
-        self.tokens.clone()
+    pub fn scan_tokens(&mut self) -> Vec<Token> {
+        self.collect()
+    }
+
+    /// Byte range in the source of the token most recently returned.
+    pub fn span(&self) -> std::ops::Range<usize> {
+        self.start..self.current
     }
 
     fn is_at_end(&self) -> bool {
@@ -83,34 +123,75 @@ impl<'a> Scanner<'a> {
             ')' => self.add_token(TT::RightParen, None),
             '{' => self.add_token(TT::LeftBrace, None),
             '}' => self.add_token(TT::RightBrace, None),
+            '[' => self.add_token(TT::LeftBracket, None),
+            ']' => self.add_token(TT::RightBracket, None),
+            ':' => self.add_token(TT::Colon, None),
             ',' => self.add_token(TT::Comma, None),
-            '.' => self.add_token(TT::Dot, None),
+            '.' => self.check_next('.', TT::DotDot, TT::Dot),
+            '-' if self.match_next('>') => self.add_token(TT::ThinArrow, None),
             '-' => self.add_token(TT::Minus, None),
             '+' => self.add_token(TT::Plus, None),
+            '#' => self.add_token(TT::Pound, None),
             ';' => self.add_token(TT::Semicolon, None),
             '*' => self.add_token(TT::Star, None),
             '!' => self.check_next('=', TT::BangEqual, TT::Bang),
+            '=' if self.match_next('>') => self.add_token(TT::Arrow, None),
             '=' => self.check_next('=', TT::EqualEqual, TT::Equal),
             '<' => self.check_next('=', TT::LessEqual, TT::Less),
             '>' => self.check_next('=', TT::GreaterEqual, TT::Greater),
+            '|' if self.match_next('>') => self.add_token(TT::Pipe, None),
             '/' => {
                 if self.match_next('/') {
+                    // `///` documents the next declaration, `////` is a plain comment.
+                    let is_doc = self.peek() == '/' && self.peek_next() != '/';
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+
+                    if is_doc {
+                        let text = self.text(self.start + 3, self.current);
+                        let text = text.strip_prefix(' ').unwrap_or(&text).trim_end();
+                        self.add_token(TT::DocComment, Some(Literal::String(text.to_owned())));
+                    } else if self.trivia.is_some() {
+                        let text = self.text(self.start, self.current).trim_end().to_owned();
+                        let trailing = self.newlines == 0;
+                        let line = self.line;
+                        let after = self.token_end;
+                        self.blank_lines();
+                        if let Some(trivia) = &mut self.trivia {
+                            trivia.push(Trivia::Comment {
+                                text,
+                                line,
+                                trailing,
+                                after,
+                            });
+                        }
+                    }
                 } else {
                     self.add_token(TT::Slash, None);
                 }
             }
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            '\n' => {
+                self.line += 1;
+                self.newlines += 1;
+            }
             '"' => self.string()?,
+            'b' if self.match_next('"') => self.bytes()?,
             c => {
                 if is_digit(c) {
                     self.number();
-                } else if c.is_alphabetic() {
+                } else if is_alpha(c) {
                     self.identifier();
                 } else {
+                    // All of the character, not just its first byte.
+                    while self
+                        .source
+                        .get(self.current)
+                        .is_some_and(|b| b & 0xC0 == 0x80)
+                    {
+                        self.advance();
+                    }
                     return Err(Error::UnexpectedChar);
                 }
             }
@@ -120,16 +201,16 @@ impl<'a> Scanner<'a> {
     }
 
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while is_alphanumeric(self.peek()) {
             self.advance();
         }
 
-        let text = std::str::from_utf8(&self.source[self.start..self.current]).unwrap();
+        let text = self.text(self.start, self.current);
 
-        if let Some(ttype) = KEYWORDS.get(text).cloned() {
+        if let Some(ttype) = self.dialect.keyword(&text) {
             self.add_token(ttype, None);
         } else {
-            self.add_token(TT::Identifier, Some(Literal::String(text.to_owned())));
+            self.add_token(TT::Identifier, Some(Literal::String(text)));
         };
     }
 
@@ -147,14 +228,28 @@ impl<'a> Scanner<'a> {
             }
         }
 
+        // And an exponent, as in `1e21` or `2.5e-7`.
+        if matches!(self.peek(), 'e' | 'E') {
+            let sign = matches!(self.peek_next(), '+' | '-');
+            let digit = self.source.get(self.current + 1 + usize::from(sign));
+            if digit.is_some_and(|&c| is_digit(c as char)) {
+                self.advance();
+                if sign {
+                    self.advance();
+                }
+                while is_digit(self.peek()) {
+                    self.advance();
+                }
+            }
+        }
+
         self.add_token(
             TokenType::Number,
-            Some(Literal::Number(
-                std::str::from_utf8(&self.source[self.start..self.current])
-                    .unwrap()
+            Some(Literal::Number(Number(
+                self.text(self.start, self.current)
                     .parse()
-                    .unwrap(),
-            )),
+                    .unwrap_or_default(),
+            ))),
         )
     }
 
@@ -167,20 +262,41 @@ impl<'a> Scanner<'a> {
         }
 
         if self.is_at_end() {
-            return Err(Error::UndeterminedString);
+            return Err(Error::UnterminatedString);
         }
 
         // The closing "
         self.advance();
 
         // Trim the surrounding quotes
-        let value = &self.source[self.start + 1..self.current - 1];
-        self.add_token(
-            TT::String,
-            Some(Literal::String(
-                String::from_utf8(value.to_owned()).unwrap(),
-            )),
-        );
+        let value = self.text(self.start + 1, self.current - 1);
+        self.add_token(TT::String, Some(Literal::String(value)));
+
+        Ok(())
+    }
+
+    /// `b"..."`, where `\xNN`, `\"` and `\\` escape a byte.
+    fn bytes(&mut self) -> Result<()> {
+        while self.peek() != '"' && !self.is_at_end() {
+            match self.advance() {
+                '\n' => self.line += 1,
+                '\\' if !self.is_at_end() => {
+                    self.advance();
+                }
+                _ => (),
+            }
+        }
+
+        if self.is_at_end() {
+            return Err(Error::UnterminatedString);
+        }
+
+        // The closing "
+        self.advance();
+
+        let value = crate::bytes::unescape(&self.source[self.start + 2..self.current - 1])
+            .ok_or(Error::InvalidEscape)?;
+        self.add_token(TT::Bytes, Some(Literal::Bytes(value.into())));
 
         Ok(())
     }
@@ -190,20 +306,35 @@ impl<'a> Scanner<'a> {
         self.source[self.current - 1] as char
     }
 
+    fn text(&self, start: usize, end: usize) -> String {
+        String::from_utf8_lossy(&self.source[start..end]).into_owned()
+    }
+
     fn add_token(&mut self, token_type: TT, literal: Option<Literal>) {
-        let text = &self.source[self.start..self.current];
-        self.tokens.push(Token::new(
-            token_type,
-            std::str::from_utf8(text).unwrap(),
-            literal,
-            self.line,
-        ));
+        let text = self.text(self.start, self.current);
+        self.attached = self.take_trivia();
+        self.token_end = self.current;
+        self.tokens
+            .push(Token::new(token_type, &text, literal, self.line).at(self.start));
+    }
+
+    /// Records the blank lines, if any, before what was just scanned.
+    fn blank_lines(&mut self) {
+        if let Some(trivia) = &mut self.trivia {
+            if self.newlines > 1 {
+                trivia.push(Trivia::Blank { line: self.line });
+            }
+        }
+        self.newlines = 0;
+    }
+
+    fn take_trivia(&mut self) -> Vec<Trivia> {
+        self.blank_lines();
+        self.trivia.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
     fn check_next(&mut self, c: char, left: TT, right: TT) {
-        if self.is_at_end() {
-            self.add_token(right, None);
-        } else if self.source[self.current] as char != c {
+        if self.is_at_end() || self.source[self.current] as char != c {
             self.add_token(right, None);
         } else {
             self.current += 1;
@@ -239,3 +370,43 @@ impl<'a> Scanner<'a> {
         self.source[self.current + 1] as char
     }
 }
+
+impl Iterator for Scanner<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        // Each scan_token call adds at most one token.
+        while self.tokens.is_empty() {
+            if self.is_at_end() {
+                if self.finished {
+                    return None;
+                }
+
+                self.finished = true;
+                self.start = self.current;
+                self.attached = self.take_trivia();
+                return Some(Token::new(TT::EOF, "", None, self.line).at(self.start));
+            }
+
+            self.start = self.current;
+            if let Err(error) = self.scan_token() {
+                self.add_token(TT::Error, Some(Literal::String(error.to_string())));
+            }
+        }
+
+        debug_assert!(self.tokens.len() <= 1);
+        self.tokens.pop()
+    }
+}
+
+/// A [`Scanner`] yielding tokens with the trivia before them.
+pub struct WithTrivia<'a>(Scanner<'a>);
+
+impl Iterator for WithTrivia<'_> {
+    type Item = (Token, Vec<Trivia>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.0.next()?;
+        Some((token, std::mem::take(&mut self.0.attached)))
+    }
+}
@@ -0,0 +1,401 @@
+//! `jlox selftest`: differential testing against the bytecode VM.
+//!
+//! Every program, whether read from a corpus or generated from a seed, runs
+//! through this interpreter and through the VM as separate processes. The
+//! two must print the same output and fail (or not) in the same way; error
+//! messages are not compared since the two backends word them differently.
+//! This interpreter runs with `--print-display`, so it prints values the
+//! way the VM does, `3` rather than `Number(3.0)`.
+//!
+//! Programs are written for this interpreter. Where the VM spells something
+//! differently, a [`Divergence`] rewrites it in the copy the VM runs.
+
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// How a run ended, from its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Exit code 65: the program didn't scan, parse or resolve.
+    CompileError,
+    /// Exit code 70: the program failed while running.
+    RuntimeError,
+    /// Any other exit code, or `None` if killed by a signal.
+    Crashed(Option<i32>),
+}
+
+impl Status {
+    fn from_code(code: Option<i32>) -> Self {
+        match code {
+            Some(0) => Self::Ok,
+            Some(65) => Self::CompileError,
+            Some(70) => Self::RuntimeError,
+            code => Self::Crashed(code),
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::CompileError => write!(f, "compile error"),
+            Self::RuntimeError => write!(f, "runtime error"),
+            Self::Crashed(Some(code)) => write!(f, "crashed with exit code {code}"),
+            Self::Crashed(None) => write!(f, "killed by a signal"),
+        }
+    }
+}
+
+/// What one backend did with one program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub status: Status,
+}
+
+/// Runs `backend flags... script` and records what it printed and how it
+/// exited.
+pub fn run(backend: &Path, flags: &[&str], script: &Path) -> io::Result<Outcome> {
+    let output = Command::new(backend).args(flags).arg(script).output()?;
+
+    Ok(Outcome {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        status: Status::from_code(output.status.code()),
+    })
+}
+
+/// A program the two backends disagree on.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub program: PathBuf,
+    pub tree_walk: Outcome,
+    pub vm: Outcome,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}:", self.program.display())?;
+        if self.tree_walk.status != self.vm.status {
+            writeln!(f, "  jlox: {}", self.tree_walk.status)?;
+            writeln!(f, "  vm:   {}", self.vm.status)?;
+        }
+
+        let mut jlox = self.tree_walk.stdout.lines();
+        let mut vm = self.vm.stdout.lines();
+        for line in 1.. {
+            match (jlox.next(), vm.next()) {
+                (None, None) => break,
+                (a, b) if a == b => continue,
+                (a, b) => {
+                    writeln!(f, "  output line {line} differs:")?;
+                    writeln!(f, "    jlox: {}", a.unwrap_or("<end of output>"))?;
+                    write!(f, "    vm:   {}", b.unwrap_or("<end of output>"))?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A construct the backends are known to spell differently.
+pub struct Divergence {
+    pub what: &'static str,
+    /// One line of a program as the VM spells it, if it uses the construct.
+    pub to_vm: fn(&str) -> Option<String>,
+}
+
+pub const KNOWN_DIVERGENCES: &[Divergence] = &[Divergence {
+    what: "subclasses are declared with `class Sub > Base`, in the VM `class Sub < Base`",
+    to_vm: |line| {
+        let (class, superclass) = line.split_once(" > ")?;
+        let name = class.trim_start().strip_prefix("class ")?;
+        let is_identifier =
+            |name: &str| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        is_identifier(name).then(|| format!("{class} < {superclass}"))
+    },
+}];
+
+/// `source` with every known divergence rewritten the way the VM spells it.
+/// Lines stay where they are, so errors point at the same ones.
+pub fn for_vm(source: &str) -> String {
+    source
+        .split_inclusive('\n')
+        .map(|line| {
+            KNOWN_DIVERGENCES
+                .iter()
+                .find_map(|divergence| (divergence.to_vm)(line))
+                .unwrap_or_else(|| line.to_string())
+        })
+        .collect()
+}
+
+/// Runs `program` through both backends, returning how they disagree.
+pub fn compare(jlox: &Path, vm: &Path, program: &Path) -> io::Result<Option<Mismatch>> {
+    let tree_walk = run(jlox, &["--print-display"], program)?;
+
+    let source = fs::read_to_string(program)?;
+    let translated = for_vm(&source);
+    let vm = if translated == source {
+        run(vm, &[], program)?
+    } else {
+        let file_name = program.file_name().unwrap_or_default().to_string_lossy();
+        let copy = std::env::temp_dir().join(format!(
+            "jlox-selftest-vm-{}-{file_name}",
+            std::process::id()
+        ));
+        fs::write(&copy, translated)?;
+        let outcome = run(vm, &[], &copy);
+        fs::remove_file(&copy)?;
+        outcome?
+    };
+
+    Ok((tree_walk != vm).then(|| Mismatch {
+        program: program.to_path_buf(),
+        tree_walk,
+        vm,
+    }))
+}
+
+/// The `.lox` files under `root` (or `root` itself), sorted by path.
+pub fn corpus(root: &Path) -> io::Result<Vec<PathBuf>> {
+    if !root.is_dir() {
+        return Ok(vec![root.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(corpus(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// A small xorshift generator, so the same seed always gives the same
+/// program without pulling in a dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+/// Builds a random program from `seed`. Programs always parse and are
+/// mostly well typed, but now and then use a value of the wrong type (e.g.
+/// adding a string to a number), a failure both backends must agree on too.
+pub fn generate(seed: u64) -> String {
+    let mut generator = Generator {
+        rng: Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1),
+        source: String::new(),
+        variables: Vec::new(),
+        functions: Vec::new(),
+        counters: 0,
+    };
+
+    for _ in 0..3 + generator.rng.below(10) {
+        generator.statement(0, true);
+    }
+    generator.source
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Bool,
+    String,
+}
+
+const KINDS: [Kind; 3] = [Kind::Number, Kind::Bool, Kind::String];
+
+struct Generator {
+    rng: Rng,
+    source: String,
+    /// Variables in scope and the kind of value they hold, innermost last.
+    variables: Vec<(String, Kind)>,
+    /// Top-level functions, all taking and returning numbers, and their arity.
+    functions: Vec<(String, usize)>,
+    /// Loop counters made so far, used to name the next one.
+    counters: usize,
+}
+
+impl Generator {
+    fn line(&mut self, depth: usize, text: &str) {
+        self.source.push_str(&"  ".repeat(depth));
+        self.source.push_str(text);
+        self.source.push('\n');
+    }
+
+    fn kind(&mut self) -> Kind {
+        KINDS[self.rng.below(KINDS.len())]
+    }
+
+    fn statement(&mut self, depth: usize, top_level: bool) {
+        let nested = depth < 3;
+        match self.rng.below(if top_level { 7 } else { 6 }) {
+            2 => {
+                let name = format!("v{}", self.variables.len());
+                let kind = self.kind();
+                let expr = self.expression(kind, 0);
+                self.line(depth, &format!("var {name} = {expr};"));
+                self.variables.push((name, kind));
+            }
+            3 if !self.variables.is_empty() => {
+                let (name, kind) = self.variables[self.rng.below(self.variables.len())].clone();
+                let expr = self.expression(kind, 0);
+                self.line(depth, &format!("{name} = {expr};"));
+            }
+            4 if nested => {
+                // Mostly booleans, sometimes other values for their truthiness.
+                let kind = if self.rng.below(4) == 0 {
+                    self.kind()
+                } else {
+                    Kind::Bool
+                };
+                let condition = self.expression(kind, 0);
+                self.line(depth, &format!("if ({condition}) {{"));
+                self.block(depth);
+                self.line(depth, "} else {");
+                self.block(depth);
+                self.line(depth, "}");
+            }
+            5 if nested => {
+                let counter = format!("i{}", self.counters);
+                self.counters += 1;
+                let times = self.rng.below(4);
+                self.line(
+                    depth,
+                    &format!(
+                        "for (var {counter} = 0; {counter} < {times}; {counter} = {counter} + 1) {{"
+                    ),
+                );
+                self.block(depth);
+                self.line(depth, "}");
+            }
+            6 => self.function(depth),
+            _ => {
+                let kind = self.kind();
+                let expr = self.expression(kind, 0);
+                self.line(depth, &format!("print {expr};"));
+            }
+        }
+    }
+
+    fn block(&mut self, depth: usize) {
+        let scope = self.variables.len();
+        for _ in 0..1 + self.rng.below(3) {
+            self.statement(depth + 1, false);
+        }
+        self.variables.truncate(scope);
+    }
+
+    /// A function of its parameters only, so it can be called from anywhere.
+    fn function(&mut self, depth: usize) {
+        let name = format!("f{}", self.functions.len());
+        let arity = self.rng.below(3);
+        let params: Vec<String> = (0..arity).map(|i| format!("p{i}")).collect();
+
+        let scope = params.iter().map(|p| (p.clone(), Kind::Number)).collect();
+        let outer = std::mem::replace(&mut self.variables, scope);
+        let value = self.expression(Kind::Number, 0);
+        self.variables = outer;
+
+        self.line(
+            depth,
+            &format!("fun {name}({}) {{ return {value}; }}", params.join(", ")),
+        );
+        self.functions.push((name, arity));
+    }
+
+    fn expression(&mut self, kind: Kind, depth: usize) -> String {
+        if depth >= 3 || self.rng.below(3) == 0 {
+            return self.atom(kind);
+        }
+
+        let sub = |generator: &mut Self, kind| generator.expression(kind, depth + 1);
+        match (kind, self.rng.below(4)) {
+            (_, 0) => format!("({})", sub(self, kind)),
+            (Kind::Number, 1) => format!("-{}", sub(self, kind)),
+            (Kind::Number, 2) if !self.functions.is_empty() => {
+                let (name, arity) = self.functions[self.rng.below(self.functions.len())].clone();
+                let arguments: Vec<String> = (0..arity).map(|_| sub(self, kind)).collect();
+                format!("{name}({})", arguments.join(", "))
+            }
+            // jlox reports division by zero where the VM yields infinity, so
+            // only divide by a literal that cannot be zero.
+            (Kind::Number, 3) if self.rng.below(4) == 0 => {
+                format!("{} / {}", sub(self, kind), 1 + self.rng.below(9))
+            }
+            (Kind::Number, _) => {
+                let op = self.rng.pick(&["+", "-", "*"]);
+                format!("{} {op} {}", sub(self, kind), sub(self, kind))
+            }
+            (Kind::Bool, 1) => {
+                let operand = self.kind();
+                format!("!{}", sub(self, operand))
+            }
+            (Kind::Bool, 2) => {
+                let op = self.rng.pick(&["<", "<=", ">", ">="]);
+                format!(
+                    "{} {op} {}",
+                    sub(self, Kind::Number),
+                    sub(self, Kind::Number)
+                )
+            }
+            (Kind::Bool, _) => {
+                let op = self.rng.pick(&["==", "!=", "and", "or"]);
+                let operands = if matches!(op, "==" | "!=") {
+                    self.kind()
+                } else {
+                    kind
+                };
+                format!("{} {op} {}", sub(self, operands), sub(self, operands))
+            }
+            (Kind::String, _) => format!("{} + {}", sub(self, kind), sub(self, kind)),
+        }
+    }
+
+    fn atom(&mut self, kind: Kind) -> String {
+        // Now and then, a value of the wrong kind.
+        let kind = if self.rng.below(25) == 0 {
+            self.kind()
+        } else {
+            kind
+        };
+
+        let variables: Vec<String> = self
+            .variables
+            .iter()
+            .filter(|(_, k)| *k == kind)
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !variables.is_empty() && self.rng.below(2) == 0 {
+            return variables[self.rng.below(variables.len())].clone();
+        }
+
+        match kind {
+            Kind::Number if self.rng.below(4) == 0 => format!("{}.5", self.rng.below(10)),
+            Kind::Number => self.rng.below(10).to_string(),
+            Kind::Bool => self.rng.pick(&["true", "false", "nil"]).to_string(),
+            Kind::String => format!("\"{}\"", self.rng.pick(&["lox", "a", ""])),
+        }
+    }
+}
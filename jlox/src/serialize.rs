@@ -0,0 +1,142 @@
+//! `serde` support for the plain data subset of [`Object`]: nil, booleans,
+//! numbers, strings, bytes, and tuples, lists and maps of those. Lists map to
+//! sequences and maps to maps. A tuple is tagged as a map with the single key
+//! `"$tuple"` holding its elements, so both round-trip: `(1, 2)` is
+//! `{"$tuple":[1.0,2.0]}` in JSON. Functions, classes, instances and host
+//! handles have no data representation and fail to serialize.
+
+use std::fmt;
+
+use serde::{
+    de::{self, Visitor},
+    ser::{self, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    collections::Map,
+    object::Object,
+    types::{Gc, Number},
+};
+
+/// The key of the one-entry map a tuple is written as.
+const TUPLE_TAG: &str = "$tuple";
+
+/// Elements written as a sequence.
+struct Elements<'a>(&'a [Gc<Object>]);
+
+impl Serialize for Elements<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|e| &**e))
+    }
+}
+
+impl Serialize for Object {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Nil => serializer.serialize_unit(),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::Number(n) => serializer.serialize_f64(n.0),
+            Self::String(s) => serializer.serialize_str(s),
+            Self::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            Self::Tuple(elements) => {
+                let mut tagged = serializer.serialize_map(Some(1))?;
+                tagged.serialize_entry(TUPLE_TAG, &Elements(elements))?;
+                tagged.end()
+            }
+            Self::List(elements) => Elements(elements).serialize(serializer),
+            Self::Map(map) => {
+                let mut entries = serializer.serialize_map(Some(map.len()))?;
+                for (key, value) in map.iter() {
+                    entries.serialize_entry(&**key, &**value)?;
+                }
+                entries.end()
+            }
+            other => Err(ser::Error::custom(format!(
+                "{other} is not plain data and can't be serialized"
+            ))),
+        }
+    }
+}
+
+struct ObjectVisitor;
+
+impl<'de> Visitor<'de> for ObjectVisitor {
+    type Value = Object;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("nil, a boolean, a number, a string, bytes, a sequence or a map")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Object, E> {
+        Ok(Object::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Object, E> {
+        Ok(Object::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Object, D::Error> {
+        Object::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Object, E> {
+        Ok(Object::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Object, E> {
+        Ok(Object::Number(Number(v as f64)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Object, E> {
+        Ok(Object::Number(Number(v as f64)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Object, E> {
+        Ok(Object::Number(Number(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Object, E> {
+        Ok(Object::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Object, E> {
+        Ok(Object::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Object, E> {
+        Ok(Object::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Object, E> {
+        Ok(Object::Bytes(v))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Object, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element::<Object>()? {
+            elements.push(Gc::new(element));
+        }
+        Ok(Object::List(elements))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut entries: A) -> Result<Object, A::Error> {
+        let mut map = Map::new();
+        while let Some((key, value)) = entries.next_entry::<Object, Object>()? {
+            map.insert(Gc::new(key), Gc::new(value))
+                .map_err(|key| de::Error::custom(format!("{key} can't be a map key")))?;
+        }
+
+        let tag = Object::String(TUPLE_TAG.to_owned());
+        match map.get(&tag).map(|value| &**value) {
+            Some(Object::List(elements)) if map.len() == 1 => Ok(Object::Tuple(elements.clone())),
+            _ => Ok(Object::Map(map)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ObjectVisitor)
+    }
+}
@@ -0,0 +1,257 @@
+//! `jlox serve`: runs scripts sent over HTTP, e.g. to grade exercises or back
+//! a web playground.
+//!
+//! `POST /run` with the source as the body answers with a JSON object:
+//!
+//! ```json
+//! {"ok": false, "exit_status": 70, "stdout": "Number(1.0)\n", "stderr": "", "diagnostics": ["Error: ..."]}
+//! ```
+//!
+//! Every request runs through [`run_captured`] with empty input and the
+//! server's [`Limits`], which the tasks a script spawns share, so scripts
+//! can't touch the machine or run away with it. Scripts that nest too deeply
+//! for their thread's stack fail with an error, and connections past
+//! [`Config::max_connections`] are turned away.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{capture::run_captured, limits::Limits};
+
+/// Matches the main thread, so requests can recurse as deep as scripts.
+const REQUEST_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// How a server listens and what each request may use.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub limits: Limits,
+    /// Largest source accepted, in bytes.
+    pub max_source_length: usize,
+    /// Most connections handled at once. Others are answered with `503
+    /// Service Unavailable` straight away.
+    pub max_connections: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            limits: Limits {
+                max_duration: Some(Duration::from_secs(5)),
+                max_memory: Some(64 << 20),
+                max_string_length: 1 << 20,
+                ..Limits::default()
+            },
+            max_source_length: 1 << 20,
+            max_connections: 16,
+        }
+    }
+}
+
+/// Answers requests until the process is stopped.
+pub fn serve(config: Config) -> io::Result<()> {
+    let listener = TcpListener::bind((config.host.as_str(), config.port))?;
+    let config = Arc::new(config);
+    let open = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        if open.load(Ordering::Relaxed) >= config.max_connections {
+            if let Err(err) = turn_away(stream) {
+                tracing::debug!(%err, "turning a connection away failed");
+            }
+            continue;
+        }
+
+        let connection = Connection::open(&open);
+        let config = config.clone();
+        let spawned = thread::Builder::new()
+            .stack_size(REQUEST_STACK_SIZE)
+            .spawn(move || {
+                let _connection = connection;
+                if let Err(err) = handle(stream, &config) {
+                    tracing::debug!(%err, "request failed");
+                }
+            });
+        if let Err(err) = spawned {
+            tracing::debug!(%err, "no thread for a request");
+        }
+    }
+    Ok(())
+}
+
+/// Answers `503` without reading the request. What the client sends is
+/// drained for a moment after, as closing with it unread would reset the
+/// connection and could lose the answer.
+fn turn_away(stream: TcpStream) -> io::Result<()> {
+    let busy = Response::error("503 Service Unavailable", "Too many requests at once.");
+    respond(stream.try_clone()?, &busy)?;
+    stream.shutdown(Shutdown::Write)?;
+    stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+    io::copy(&mut stream.take(1 << 16), &mut io::sink())?;
+    Ok(())
+}
+
+/// Counts a connection as open until it's dropped.
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    fn open(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count.clone())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// An HTTP response: its status line and JSON body.
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            body: format!("{{\"error\": {}}}", json_string(message)),
+        }
+    }
+}
+
+fn handle(stream: TcpStream, config: &Config) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader, config)? {
+        Ok(request) => match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/run") => match String::from_utf8(request.body) {
+                Ok(source) => Response {
+                    status: "200 OK",
+                    body: run(&source, config.limits),
+                },
+                Err(_) => Response::error("400 Bad Request", "The source must be utf-8."),
+            },
+            (_, "/run") => Response::error("405 Method Not Allowed", "Use POST."),
+            _ => Response::error("404 Not Found", "Only /run is served."),
+        },
+        Err(response) => response,
+    };
+    respond(stream, &response)
+}
+
+fn respond(mut stream: TcpStream, response: &Response) -> io::Result<()> {
+    stream.set_write_timeout(Some(Duration::from_secs(30)))?;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// The next request, or the response rejecting it.
+fn read_request(
+    reader: &mut impl BufRead,
+    config: &Config,
+) -> io::Result<Result<Request, Response>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(Err(Response::error(
+            "400 Bad Request",
+            "Malformed request.",
+        )));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = match value.trim().parse() {
+                    Ok(length) => length,
+                    Err(_) => {
+                        return Ok(Err(Response::error(
+                            "400 Bad Request",
+                            "Malformed Content-Length.",
+                        )))
+                    }
+                };
+            }
+        }
+    }
+
+    if length > config.max_source_length {
+        let message = format!(
+            "Sources can't be longer than {} bytes.",
+            config.max_source_length
+        );
+        return Ok(Err(Response::error("413 Payload Too Large", &message)));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(Request { method, path, body }))
+}
+
+/// Runs `source` in a sandbox and describes the outcome as JSON.
+fn run(source: &str, limits: Limits) -> String {
+    let report = run_captured(source, "", limits);
+    let diagnostics: Vec<String> = report.diagnostics.iter().map(|d| json_string(d)).collect();
+    format!(
+        "{{\"ok\": {}, \"exit_status\": {}, \"stdout\": {}, \"stderr\": {}, \"diagnostics\": [{}]}}",
+        report.exit_status == 0,
+        report.exit_status,
+        json_string(&report.stdout),
+        json_string(&report.stderr),
+        diagnostics.join(", ")
+    )
+}
+
+/// `text` as a JSON string literal.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if u32::from(c) < 0x20 => json.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,44 @@
+//! `--strict`: checks that turn likely mistakes into errors, and warnings
+//! that point them out.
+//!
+//! The resolver and the interpreter both read the session's [`Settings`],
+//! so a check is one field here rather than a flag threaded through each.
+//! `+` already refuses to mix strings and numbers, so that needs no setting.
+
+/// Which optional checks are on. All are off by default, as in the book.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Reading a variable declared without an initializer before anything
+    /// is assigned to it is a runtime error instead of `nil`.
+    pub forbid_uninitialized_reads: bool,
+    /// Declaring a local with the name of one in an enclosing scope is a
+    /// resolve error.
+    pub forbid_shadowing: bool,
+    /// Referring to a global that no top-level declaration of the script
+    /// defines, and that isn't a native or already defined, is a resolve
+    /// error.
+    pub forbid_undefined_globals: bool,
+    /// Using the result of calling the `print` function, in dialects that
+    /// have one, is a resolve error: it is always `nil`.
+    pub forbid_print_results: bool,
+    /// Before a program runs, warn about references to globals that no file
+    /// of it declares and that aren't natives or already defined. Unlike
+    /// [`Settings::forbid_undefined_globals`] this sees the whole program.
+    pub warn_undefined_globals: bool,
+    /// Values that don't match their type annotations stop a program
+    /// running, instead of being warned about, as `--typed` turns on.
+    pub forbid_type_mismatches: bool,
+}
+
+impl Settings {
+    /// Every error, as `--strict` turns on.
+    pub fn strict() -> Self {
+        Self {
+            forbid_uninitialized_reads: true,
+            forbid_shadowing: true,
+            forbid_undefined_globals: true,
+            forbid_print_results: true,
+            ..Self::default()
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::{collections::HashMap, fmt::Display};
+
+use thiserror::Error;
+
+use crate::{
+    ast::{Boundary, Stmt},
+    class::Class,
+    codec::{self, Malformed},
+    collections::Map,
+    dialect::Dialect,
+    environment::Environment,
+    functions::Callable,
+    interpreter::Interpreter,
+    object::Object,
+    parser::Parser,
+    printer::Printer,
+    resolver::Resolver,
+    scanner::Scanner,
+    token::{Token, TokenType},
+    types::{Gc, GcCell, Number},
+};
+
+const HEADER: &str = "jlox-snapshot 1";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Snapshot I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed snapshot at line {line}: {msg}")]
+    Malformed { line: usize, msg: String },
+
+    #[error("Snapshot entry '{name}' does not contain a single {kind} declaration.")]
+    BadSource { name: String, kind: &'static str },
+
+    #[error("Snapshot entry '{name}' failed to parse: {error}")]
+    Parse {
+        name: String,
+        error: crate::parser::Error,
+    },
+
+    #[error("Snapshot entry '{name}' failed to resolve: {error}")]
+    Resolve {
+        name: String,
+        error: crate::resolver::Error,
+    },
+
+    #[error("Snapshot entry '{name}' failed to run: {error}")]
+    Runtime {
+        name: String,
+        error: crate::interpreter::Error,
+    },
+
+    #[error("Snapshot entry '{name}' can't be restored: {msg}.")]
+    BadValue { name: String, msg: String },
+}
+
+impl From<Malformed> for Error {
+    fn from(Malformed { line, msg }: Malformed) -> Self {
+        Self::Malformed { line, msg }
+    }
+}
+
+/// A persisted global. Functions and classes are stored as Lox source and
+/// re-declared on restore; everything else is a plain value. Tuples, lists
+/// and maps hold plain values only.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Tuple(Vec<Entry>),
+    List(Vec<Entry>),
+    /// Keys and values, in the map's order.
+    Map(Vec<(Entry, Entry)>),
+    Function(String),
+    Class(String),
+}
+
+impl Entry {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Nil => "nil",
+            Self::Bool(_) => "bool",
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Bytes(_) => "bytes",
+            Self::Tuple(_) => "tuple",
+            Self::List(_) => "list",
+            Self::Map(_) => "map",
+            Self::Function(_) => "fun",
+            Self::Class(_) => "class",
+        }
+    }
+
+    /// The entry of a value that is plain data all the way down.
+    fn plain(value: &Object) -> Option<Self> {
+        let all = |elements: &[Gc<Object>]| -> Option<Vec<Self>> {
+            elements
+                .iter()
+                .map(|element| Self::plain(element))
+                .collect()
+        };
+
+        Some(match value {
+            Object::Nil => Self::Nil,
+            Object::Bool(b) => Self::Bool(*b),
+            Object::Number(n) => Self::Number(n.0),
+            Object::String(s) => Self::String(s.clone()),
+            Object::Bytes(b) => Self::Bytes(b.clone()),
+            Object::Tuple(elements) => Self::Tuple(all(elements)?),
+            Object::List(elements) => Self::List(all(elements)?),
+            Object::Map(map) => Self::Map(
+                map.iter()
+                    .map(|(key, value)| Some((Self::plain(key)?, Self::plain(value)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            Object::Function(_) | Object::Class(_) | Object::Instance(_) | Object::UserData(_) => {
+                return None
+            }
+        })
+    }
+
+    /// The value of a plain data entry.
+    fn value(&self) -> Result<Object, String> {
+        let all = |elements: &[Self]| -> Result<Vec<Gc<Object>>, String> {
+            elements
+                .iter()
+                .map(|element| element.value().map(Gc::new))
+                .collect()
+        };
+
+        Ok(match self {
+            Self::Nil => Object::Nil,
+            Self::Bool(b) => Object::Bool(*b),
+            Self::Number(n) => Object::Number(Number(*n)),
+            Self::String(s) => Object::String(s.clone()),
+            Self::Bytes(b) => Object::Bytes(b.clone()),
+            Self::Tuple(elements) => Object::Tuple(all(elements)?),
+            Self::List(elements) => Object::List(all(elements)?),
+            Self::Map(entries) => {
+                let mut map = Map::new();
+                for (key, value) in entries {
+                    let (key, value) = (Gc::new(key.value()?), Gc::new(value.value()?));
+                    map.insert(key, value)
+                        .map_err(|key| format!("{key} can't be a map key"))?;
+                }
+                Object::Map(map)
+            }
+            Self::Function(_) | Self::Class(_) => {
+                return Err(format!("a {} can only be a global", self.kind()))
+            }
+        })
+    }
+}
+
+/// The global environment of an interpreter, minus natives and anything that
+/// can't be rebuilt from source (instances, closures over local scopes,
+/// sockets, and collections holding any of them).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Snapshot {
+    entries: Vec<(String, Entry)>,
+}
+
+fn identifier(name: &str) -> Token {
+    Token::new(TokenType::Identifier, name, None, 0)
+}
+
+fn is_global_closure(closure: &Gc<GcCell<Environment>>, globals: &Gc<GcCell<Environment>>) -> bool {
+    Gc::ptr_eq(closure, globals)
+}
+
+fn class_source(klass: &Class, globals: &Gc<GcCell<Environment>>) -> Option<String> {
+    let mut names: Vec<&String> = klass.methods().keys().collect();
+    names.sort();
+
+    let mut methods = Vec::new();
+    for name in names {
+        let method = &klass.methods()[name];
+
+        // Methods of a subclass close over the environment holding "super".
+        let closure = method.closure();
+        let enclosing = closure.borrow().enclosing.clone();
+        let top_level = is_global_closure(closure, globals)
+            || (klass.superclass().is_some()
+                && enclosing.is_some_and(|enclosing| is_global_closure(&enclosing, globals)));
+        if !top_level {
+            return None;
+        }
+
+        methods.push(Stmt::Function {
+            name: identifier(name),
+            params: method.params().iter().map(|p| identifier(p)).collect(),
+            body: method.body().to_vec(),
+            doc: method.doc().map(str::to_owned),
+            signature: None,
+            boundary: Boundary::NONE,
+        });
+    }
+
+    let superclass = klass
+        .superclass()
+        .map(|superclass| crate::ast::Expr::Variable {
+            id: crate::ast::NodeId::NONE,
+            name: identifier(superclass.borrow().name()),
+        });
+
+    Some(Printer::new().print_stmt(Stmt::Class {
+        name: identifier(klass.name()),
+        superclass,
+        methods,
+        doc: klass.doc().map(str::to_owned),
+        boundary: Boundary::NONE,
+    }))
+}
+
+impl Snapshot {
+    pub fn capture(interpreter: &mut Interpreter) -> Self {
+        let globals = interpreter.copy_globals();
+        let values = globals.borrow().values.clone();
+
+        let mut names: Vec<&String> = values.keys().collect();
+        names.sort();
+
+        let mut snapshot = Self::default();
+        for name in names {
+            // Every interpreter defines the builtins itself.
+            if interpreter.is_builtin(name, &values[name]) {
+                continue;
+            }
+            snapshot.capture_global(name, &values, &globals);
+        }
+
+        snapshot
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|(entry, _)| entry == name)
+    }
+
+    fn capture_global(
+        &mut self,
+        name: &str,
+        values: &HashMap<String, Gc<Object>>,
+        globals: &Gc<GcCell<Environment>>,
+    ) {
+        if self.contains(name) {
+            return;
+        }
+
+        let entry = match &*values[name] {
+            Object::Function(function) => {
+                let Some(function) = function.as_lox_function() else {
+                    return;
+                };
+                if !is_global_closure(function.closure(), globals) {
+                    return;
+                }
+
+                Entry::Function(Printer::new().print_function(
+                    function.name(),
+                    function.params(),
+                    function.body(),
+                    function.doc(),
+                ))
+            }
+            Object::Class(klass) => {
+                // Superclasses have to be declared before their subclasses.
+                if let Some(superclass) = klass.borrow().superclass() {
+                    let parent = values.iter().find(|(_, value)| {
+                        matches!(&***value, Object::Class(k) if Gc::ptr_eq(k, superclass))
+                    });
+                    if let Some((parent, _)) = parent {
+                        self.capture_global(parent, values, globals);
+                    }
+                }
+
+                let Some(source) = class_source(&klass.borrow(), globals) else {
+                    return;
+                };
+                Entry::Class(source)
+            }
+            value => match Entry::plain(value) {
+                Some(entry) => entry,
+                None => return,
+            },
+        };
+
+        self.entries.push((name.to_string(), entry));
+    }
+
+    pub fn entries(&self) -> &[(String, Entry)] {
+        &self.entries
+    }
+
+    /// Appends an entry, restored after everything captured before it.
+    pub fn push(&mut self, name: String, entry: Entry) {
+        self.entries.push((name, entry));
+    }
+
+    /// Re-declares every entry in the interpreter's global scope, in order.
+    pub fn restore(&self, interpreter: &Gc<GcCell<Interpreter>>) -> Result<(), Error> {
+        let globals = interpreter.borrow_mut().copy_globals();
+
+        for (name, entry) in &self.entries {
+            let value = match entry {
+                Entry::Function(source) | Entry::Class(source) => {
+                    declare(interpreter, &globals, name, entry.kind(), source)?;
+                    continue;
+                }
+                entry => entry.value().map_err(|msg| Error::BadValue {
+                    name: name.clone(),
+                    msg,
+                })?,
+            };
+
+            globals.borrow_mut().define(name.clone(), Gc::new(value));
+        }
+
+        Ok(())
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let entries = codec::parse(source, HEADER)?;
+        Ok(Self {
+            entries: entries
+                .into_iter()
+                .map(|(_, name, entry)| (name, entry))
+                .collect(),
+        })
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        codec::write(f, HEADER, &self.entries)
+    }
+}
+
+/// Runs a single function or class declaration at the top level and stores
+/// the result under `name`, which may differ from the declared name when the
+/// value was reassigned to another global.
+fn declare(
+    interpreter: &Gc<GcCell<Interpreter>>,
+    globals: &Gc<GcCell<Environment>>,
+    name: &str,
+    kind: &'static str,
+    source: &str,
+) -> Result<(), Error> {
+    // Entries are printed with standard spellings, but `print` stays a
+    // function in sessions whose dialect has one.
+    let dialect = match interpreter.borrow().dialect().print_function() {
+        true => Dialect::standard().clone().with_print_function(),
+        false => Dialect::standard().clone(),
+    };
+    let statements = Parser::new(Scanner::new(source).with_dialect(&dialect))
+        .with_dialect(&dialect)
+        .parse()
+        .map_err(|error| Error::Parse {
+            name: name.to_string(),
+            error,
+        })?;
+
+    let declared = match statements.as_slice() {
+        [Stmt::Function { name, .. }] if kind == "fun" => name.lexeme.clone(),
+        [Stmt::Class { name, .. }] if kind == "class" => name.lexeme.clone(),
+        _ => {
+            return Err(Error::BadSource {
+                name: name.to_string(),
+                kind,
+            })
+        }
+    };
+
+    Resolver::new(interpreter.clone())
+        .resolve(&statements)
+        .map_err(|error| Error::Resolve {
+            name: name.to_string(),
+            error,
+        })?;
+
+    let shadowed = globals.borrow().values.get(&declared).cloned();
+
+    interpreter
+        .borrow_mut()
+        .interpret(statements)
+        .map_err(|error| Error::Runtime {
+            name: name.to_string(),
+            error,
+        })?;
+
+    if declared != name {
+        let mut globals = globals.borrow_mut();
+        let value = globals.values.remove(&declared).unwrap_or_default();
+        globals.define(name.to_string(), value);
+
+        if let Some(shadowed) = shadowed {
+            globals.define(declared, shadowed);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! How much native stack the current thread has left.
+//!
+//! The tree-walker, the resolver and the passes over the tree recurse on the
+//! Rust stack, so a script can nest calls, blocks and expressions until the
+//! process aborts. They check [`exhausted`] as they go down instead and fail
+//! with an error while there is still room to report it.
+
+use std::cell::Cell;
+
+/// What has to stay free when a check passes: the frames until the next
+/// check, natives, error reporting and dropping what the run built. The
+/// interpreter clones each statement it runs without checking, so this also
+/// covers cloning the deepest tree the parser allows, which takes far more
+/// stack in unoptimized builds.
+pub const RED_ZONE: usize = if cfg!(debug_assertions) {
+    1024 * 1024
+} else {
+    256 * 1024
+};
+
+thread_local! {
+    /// The lowest address of this thread's stack, looked up on first use.
+    static LIMIT: Cell<Option<Option<usize>>> = const { Cell::new(None) };
+}
+
+/// Bytes left on the current thread's stack, or `None` on platforms where
+/// its bounds can't be found.
+pub fn remaining() -> Option<usize> {
+    let marker = 0u8;
+    let here = std::ptr::addr_of!(marker) as usize;
+    let limit = LIMIT.with(|limit| {
+        let lowest = limit.get().unwrap_or_else(lowest_address);
+        limit.set(Some(lowest));
+        lowest
+    })?;
+    Some(here.saturating_sub(limit))
+}
+
+/// Whether the current thread is down to its [`RED_ZONE`].
+pub fn exhausted() -> bool {
+    remaining().is_some_and(|left| left < RED_ZONE)
+}
+
+/// How far above the bottom the main thread's stack stops growing on Linux:
+/// the kernel keeps a gap (`stack_guard_gap`, 1 MiB by default) between it
+/// and the mappings below.
+#[cfg(target_os = "linux")]
+const MAIN_THREAD_GAP: usize = 1 << 20;
+
+#[cfg(target_os = "linux")]
+fn lowest_address() -> Option<usize> {
+    // SAFETY: `attr` is initialized by `pthread_getattr_np` before it is
+    // read and destroyed once, and the out-pointers are valid locals.
+    unsafe {
+        let mut attr: libc::pthread_attr_t = std::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut address = std::ptr::null_mut();
+        let mut size = 0;
+        let found = libc::pthread_attr_getstack(&attr, &mut address, &mut size) == 0;
+        libc::pthread_attr_destroy(&mut attr);
+        let main = libc::syscall(libc::SYS_gettid) == libc::getpid() as libc::c_long;
+        let gap = if main { MAIN_THREAD_GAP } else { 0 };
+        found.then_some(address as usize + gap)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn lowest_address() -> Option<usize> {
+    // SAFETY: both only read the attributes of the calling thread.
+    unsafe {
+        let thread = libc::pthread_self();
+        let top = libc::pthread_get_stackaddr_np(thread) as usize;
+        Some(top - libc::pthread_get_stacksize_np(thread))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn lowest_address() -> Option<usize> {
+    None
+}
@@ -0,0 +1,118 @@
+//! Execution statistics, printed by `jlox --stats`.
+//!
+//! The interpreter counts statements and calls, [`Environment`] counts the
+//! scopes it creates, and [`CountingAllocator`] counts heap allocations when
+//! a binary installs it as its global allocator.
+//!
+//! [`Environment`]: crate::environment::Environment
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    fmt::Display,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Counters gathered while running scripts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub statements: usize,
+    pub calls: usize,
+    pub environments: usize,
+    /// Most heap blocks alive at once. Zero without [`CountingAllocator`].
+    pub peak_live_objects: usize,
+    /// Heap blocks allocated in total. Zero without [`CountingAllocator`].
+    pub allocations: usize,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "statements executed: {}", self.statements)?;
+        writeln!(f, "function calls:      {}", self.calls)?;
+        writeln!(f, "environments:        {}", self.environments)?;
+        writeln!(f, "peak live objects:   {}", self.peak_live_objects)?;
+        write!(f, "total allocations:   {}", self.allocations)
+    }
+}
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK_LIVE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Bytes the current thread allocated minus the bytes it freed.
+    static THREAD_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting allocations for [`Stats`]:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: jlox::stats::CountingAllocator = jlox::stats::CountingAllocator;
+/// ```
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    /// Heap blocks allocated since the program started.
+    pub fn allocations() -> usize {
+        ALLOCATIONS.load(Ordering::Relaxed)
+    }
+
+    /// Most heap blocks alive at once since the program started.
+    pub fn peak_live() -> usize {
+        PEAK_LIVE.load(Ordering::Relaxed)
+    }
+
+    /// Bytes the current thread allocated and didn't free since it started.
+    /// Memory one thread allocates and another frees makes it drift, so only
+    /// differences over a stretch of work on one thread mean much.
+    pub fn thread_bytes() -> isize {
+        THREAD_BYTES.with(Cell::get)
+    }
+}
+
+fn record_allocation(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    let live = LIVE.fetch_add(1, Ordering::Relaxed) + 1;
+    PEAK_LIVE.fetch_max(live, Ordering::Relaxed);
+    record_bytes(size as isize);
+}
+
+fn record_bytes(delta: isize) {
+    // Fails only while the thread is being torn down.
+    let _ = THREAD_BYTES.try_with(|bytes| bytes.set(bytes.get() + delta));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(1, Ordering::Relaxed);
+        record_bytes(-(layout.size() as isize));
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    // A reallocation moves one block, so it counts as neither, only as the
+    // bytes it grows or shrinks by.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record_bytes(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
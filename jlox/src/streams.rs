@@ -0,0 +1,254 @@
+//! The standard streams as the globals `stdin`, `stdout` and `stderr`, so a
+//! script can work as a filter: `jlox tool.lox < in.txt > out.txt`.
+//!
+//! `stdin.readLine()` returns a line without its line ending, or nil at the
+//! end of input, and `stdin.read()` the rest of the input. Both are recorded
+//! and replayed like other external inputs.
+//!
+//! A stream's `write(x)` adds the text of `x` (bytes as they are) without a
+//! newline. `stdout` is line buffered, so a partial line such as a prompt or progress
+//! bar only shows up after `stdout.flush()`; `stderr` isn't buffered. The
+//! global `write(x)` is a shorthand for `stdout.write(x); stdout.flush();`.
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{
+    functions::Callable,
+    host::{ClassBuilder, HostClass, IntoLox},
+    interpreter::{Error, Interpreter},
+    object::Object,
+    tasks::Message,
+    types::{host_fn, Gc},
+};
+
+host_fn!(Reader = BufRead);
+host_fn!(Writer = Write);
+
+fn io_error(name: &str, error: io::Error) -> Error {
+    Error::NativeError {
+        name: name.to_string(),
+        msg: error.to_string(),
+    }
+}
+
+/// The value behind `stdin`.
+#[derive(Debug)]
+pub struct InputStream;
+
+impl InputStream {
+    pub fn class() -> Gc<HostClass> {
+        ClassBuilder::<InputStream>::new("InputStream")
+            .native_method("readLine", 0, |interpreter, _, _| {
+                let line = interpreter.read_input("readLine", |streams| {
+                    let mut line = String::new();
+                    let read = streams
+                        .read_line(&mut line)
+                        .map_err(|e| io_error("stdin.readLine", e))?;
+                    if read == 0 {
+                        return Ok(Message::Nil);
+                    }
+
+                    let end = line.trim_end_matches(['\n', '\r']).len();
+                    line.truncate(end);
+                    Ok(Message::String(line))
+                })?;
+                if let Message::String(line) = &line {
+                    interpreter.check_string_length("stdin.readLine", line.len())?;
+                }
+                Ok(Gc::new(line.into_lox()))
+            })
+            .native_method("read", 0, |interpreter, _, _| {
+                let rest = interpreter.read_input("read", |streams| {
+                    let mut rest = String::new();
+                    streams
+                        .read_to_string(&mut rest)
+                        .map_err(|e| io_error("stdin.read", e))?;
+                    Ok(Message::String(rest))
+                })?;
+                if let Message::String(rest) = &rest {
+                    interpreter.check_string_length("stdin.read", rest.len())?;
+                }
+                Ok(Gc::new(rest.into_lox()))
+            })
+            .build()
+    }
+}
+
+/// The value behind `stdout` and `stderr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+
+    pub fn class() -> Gc<HostClass> {
+        ClassBuilder::<OutputStream>::new("OutputStream")
+            .native_method("write", 1, |interpreter, stream, arguments| {
+                interpreter
+                    .streams_mut()
+                    .write_value(*stream, &arguments[0])
+                    .map_err(|e| io_error(&format!("{}.write", stream.name()), e))?;
+                Ok(interpreter.nil())
+            })
+            .native_method("flush", 0, |interpreter, stream, _| {
+                interpreter
+                    .streams_mut()
+                    .flush(*stream)
+                    .map_err(|e| io_error(&format!("{}.flush", stream.name()), e))?;
+                Ok(interpreter.nil())
+            })
+            .build()
+    }
+}
+
+/// What `stdin`, `stdout` and `stderr` are connected to: the process's own
+/// streams, unless the host redirected them, e.g. to feed a script input and
+/// capture what it prints.
+#[derive(Default)]
+pub struct Streams {
+    stdin: Option<Reader>,
+    stdout: Option<Writer>,
+    stderr: Option<Writer>,
+}
+
+impl Streams {
+    /// Reads from `reader`, or from the console again with `None`.
+    pub(crate) fn set_input(&mut self, reader: Option<Reader>) {
+        self.stdin = reader;
+    }
+
+    fn read_line(&mut self, line: &mut String) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(reader) => reader.read_line(line),
+            None => io::stdin().lock().read_line(line),
+        }
+    }
+
+    fn read_to_string(&mut self, rest: &mut String) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(reader) => reader.read_to_string(rest),
+            None => io::stdin().lock().read_to_string(rest),
+        }
+    }
+
+    fn redirect(&mut self, stream: OutputStream) -> &mut Option<Writer> {
+        match stream {
+            OutputStream::Stdout => &mut self.stdout,
+            OutputStream::Stderr => &mut self.stderr,
+        }
+    }
+
+    /// Whether any of the streams was redirected away from the console.
+    pub(crate) fn is_redirected(&self) -> bool {
+        self.stdin.is_some() || self.stdout.is_some() || self.stderr.is_some()
+    }
+
+    /// Sends `stream` to `writer`, or back to the console with `None`.
+    pub(crate) fn set(&mut self, stream: OutputStream, writer: Option<Writer>) {
+        *self.redirect(stream) = writer;
+    }
+
+    pub(crate) fn write_all(&mut self, stream: OutputStream, data: &[u8]) -> io::Result<()> {
+        match (stream, self.redirect(stream)) {
+            (_, Some(writer)) => writer.write_all(data),
+            (OutputStream::Stdout, None) => io::stdout().write_all(data),
+            (OutputStream::Stderr, None) => io::stderr().write_all(data),
+        }
+    }
+
+    /// The text of `value`, or its bytes as they are.
+    fn write_value(&mut self, stream: OutputStream, value: &Object) -> io::Result<()> {
+        match value {
+            Object::Bytes(bytes) => self.write_all(stream, bytes),
+            other => self.write_all(stream, other.to_string().as_bytes()),
+        }
+    }
+
+    pub(crate) fn flush(&mut self, stream: OutputStream) -> io::Result<()> {
+        match (stream, self.redirect(stream)) {
+            (_, Some(writer)) => writer.flush(),
+            (OutputStream::Stdout, None) => io::stdout().flush(),
+            (OutputStream::Stderr, None) => io::stderr().flush(),
+        }
+    }
+}
+
+/// `write(x)`: prints the text of `x` without a newline, unlike `print`, and
+/// flushes it right away, so prompts and progress bars show up at once.
+pub struct WriteOut;
+
+impl Callable for WriteOut {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let streams = interpreter.streams_mut();
+        streams
+            .write_value(OutputStream::Stdout, &arguments[0])
+            .and_then(|()| streams.flush(OutputStream::Stdout))
+            .map_err(|e| io_error("write", e))?;
+        Ok(interpreter.nil())
+    }
+}
+
+/// `print(a, b, ...)`: the global that replaces the `print` statement in
+/// dialects where `print` is a function. Writes its arguments as the
+/// statement writes its value, separated by spaces, then a newline.
+pub struct Print;
+
+impl Callable for Print {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let line: Vec<String> = arguments.iter().map(|value| format!("{value:?}")).collect();
+        interpreter
+            .streams_mut()
+            .write_all(
+                OutputStream::Stdout,
+                format!("{}\n", line.join(" ")).as_bytes(),
+            )
+            .map_err(|e| io_error("print", e))?;
+        Ok(interpreter.nil())
+    }
+}
+
+/// `stdin`, `stdout` and `stderr`, for every interpreter to define.
+pub fn globals() -> [(&'static str, Object); 3] {
+    let output = OutputStream::class();
+    [
+        ("stdin", InputStream::class().instantiate(InputStream)),
+        ("stdout", output.instantiate(OutputStream::Stdout)),
+        ("stderr", output.instantiate(OutputStream::Stderr)),
+    ]
+    .map(|(name, stream)| {
+        let stream = stream.expect("Expect each stream class to wrap its own type.");
+        (name, stream)
+    })
+}
@@ -0,0 +1,45 @@
+//! Building strings without quadratic copying.
+//!
+//! `s = s + piece;` copies `s` on every iteration. A `StringBuilder` keeps one
+//! growing buffer instead:
+//!
+//! ```lox
+//! var sb = StringBuilder();
+//! for (var i = 0; i < 3; i = i + 1) sb.append(i);
+//! print sb.toString();
+//! ```
+
+use crate::{
+    host::{ClassBuilder, HostClass},
+    types::Gc,
+};
+
+/// The value behind a script's `StringBuilder` instance.
+#[derive(Debug, Default)]
+pub struct StringBuilder {
+    buffer: String,
+}
+
+impl StringBuilder {
+    /// The `StringBuilder` class: `append(x)` adds the text of `x`
+    /// (strings without quotes), `toString()` returns the text so far and
+    /// `length` is its size in bytes.
+    pub fn class() -> Gc<HostClass> {
+        ClassBuilder::<StringBuilder>::new("StringBuilder")
+            .constructor(|(): ()| StringBuilder::default())
+            .native_method("append", 1, |interpreter, builder, arguments| {
+                let piece = arguments[0].to_string();
+                interpreter.check_string_length(
+                    "StringBuilder.append",
+                    builder.buffer.len() + piece.len(),
+                )?;
+
+                builder.buffer.push_str(&piece);
+                Ok(interpreter.nil())
+            })
+            .method("toString", |builder, (): ()| builder.buffer.clone())
+            .method("clear", |builder, (): ()| builder.buffer.clear())
+            .getter("length", |builder| builder.buffer.len() as f64)
+            .build()
+    }
+}
@@ -0,0 +1,632 @@
+//! Share-nothing concurrency: `spawn(fn)` runs a top-level function on its
+//! own OS thread and interpreter, and `channel()` connects tasks.
+//!
+//! A task starts from a snapshot of the spawner's globals, so it sees the same
+//! functions, classes and plain values but none of its later changes. The only
+//! state shared between tasks are channels held in globals; everything sent
+//! through them is copied and has to be nil, a boolean, a number, a string
+//! or bytes.
+//!
+//! Tasks run with the spawner's permissions, limits, settings and dialect.
+//! Natives and classes the host registered stay on the spawner's thread: in a
+//! task, using one is a runtime error naming it.
+//!
+//! Tasks share the run's budget: they stop at the spawner's deadline, and the
+//! heap they hold counts towards its memory limit. When the spawner's output
+//! is redirected, theirs is sent back for it to write, and their input is
+//! empty, so they never reach the process's own streams.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicIsize, Ordering},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dialect::Dialect,
+    functions::Callable,
+    host::{ClassBuilder, FromLox, HostClass, IntoLox},
+    interpreter::{Error, Interpreter, TopLevel},
+    limits::Limits,
+    math::MathMode,
+    object::Object,
+    permissions::Permissions,
+    printer::Printer,
+    settings::Settings,
+    snapshot::{Entry, Snapshot},
+    streams::OutputStream,
+    types::{Gc, GcCell, Number},
+};
+
+/// The global a spawned function is declared under in its task.
+const TASK_ENTRY: &str = "spawned task";
+
+/// Matches the main thread, so tasks can recurse as deep as scripts.
+const TASK_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// How long `recv()` and `join()` wait between checks of the run's budget.
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A value copied between tasks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl FromLox for Message {
+    fn from_lox(value: &Gc<Object>) -> Result<Self, String> {
+        match &**value {
+            Object::Nil => Ok(Self::Nil),
+            Object::Bool(b) => Ok(Self::Bool(*b)),
+            Object::Number(n) => Ok(Self::Number(n.0)),
+            Object::String(s) => Ok(Self::String(s.clone())),
+            Object::Bytes(b) => Ok(Self::Bytes(b.clone())),
+            other => Err(format!(
+                "only nil, booleans, numbers, strings and bytes can cross tasks, got {other}"
+            )),
+        }
+    }
+}
+
+impl IntoLox for Message {
+    fn into_lox(self) -> Object {
+        match self {
+            Self::Nil => Object::Nil,
+            Self::Bool(b) => Object::Bool(b),
+            Self::Number(n) => Object::Number(Number(n)),
+            Self::String(s) => Object::String(s),
+            Self::Bytes(b) => Object::Bytes(b),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<Message>,
+    closed: bool,
+}
+
+/// An unbounded multi-producer, multi-consumer queue of messages.
+#[derive(Clone, Default)]
+pub struct Channel {
+    shared: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.shared.0.lock().expect("channel lock poisoned.")
+    }
+
+    pub fn send(&self, message: Message) -> Result<(), String> {
+        let mut queue = self.queue();
+        if queue.closed {
+            return Err("channel is closed".to_string());
+        }
+
+        queue.messages.push_back(message);
+        self.shared.1.notify_one();
+        Ok(())
+    }
+
+    /// Blocks until a message arrives. Returns `None` once the channel is
+    /// closed and drained.
+    pub fn recv(&self) -> Option<Message> {
+        let mut queue = self.queue();
+        loop {
+            if let Some(message) = queue.messages.pop_front() {
+                return Some(message);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self.shared.1.wait(queue).expect("channel lock poisoned.");
+        }
+    }
+
+    /// Like [`Channel::recv`], but fails once `interpreter`'s run is over
+    /// its budget instead of waiting past it.
+    fn recv_within_budget(&self, interpreter: &mut Interpreter) -> Result<Option<Message>, Error> {
+        let mut queue = self.queue();
+        loop {
+            if let Some(message) = queue.messages.pop_front() {
+                return Ok(Some(message));
+            }
+            if queue.closed {
+                return Ok(None);
+            }
+            interpreter.check_budget()?;
+            queue = self
+                .shared
+                .1
+                .wait_timeout(queue, BUDGET_CHECK_INTERVAL)
+                .expect("channel lock poisoned.")
+                .0;
+        }
+    }
+
+    pub fn close(&self) {
+        self.queue().closed = true;
+        self.shared.1.notify_all();
+    }
+
+    pub fn class() -> Gc<HostClass> {
+        ClassBuilder::<Channel>::new("Channel")
+            .try_method("send", |channel, (message,): (Message,)| {
+                channel.send(message)
+            })
+            .native_method("recv", 0, |interpreter, channel, _| {
+                let message = channel.recv_within_budget(interpreter)?;
+                Ok(Gc::new(message.into_lox()))
+            })
+            .method("close", |channel, (): ()| channel.close())
+            .build()
+    }
+}
+
+/// `channel()`: creates a channel with `send(value)`, `recv()` and `close()`
+/// methods. `recv()` returns nil once the channel is closed and empty.
+pub struct NewChannel {
+    class: Gc<HostClass>,
+}
+
+impl NewChannel {
+    pub fn new() -> Self {
+        Self {
+            class: Channel::class(),
+        }
+    }
+}
+
+impl Default for NewChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Callable for NewChannel {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        Ok(Gc::new(self.class.instantiate(Channel::new())?))
+    }
+}
+
+/// A running task; `join()` waits for it and returns the function's result.
+pub struct Task {
+    /// Where the task's thread sends its result. Dropped unsent if it panics.
+    result: Option<Mutex<mpsc::Receiver<Result<Message, String>>>>,
+}
+
+impl Task {
+    pub fn join(&mut self) -> Result<Message, String> {
+        let result = self.result.take().ok_or("task was already joined")?;
+
+        result
+            .into_inner()
+            .expect("task lock poisoned.")
+            .recv()
+            .map_err(|_| "task panicked".to_string())
+            .and_then(|result| result)
+    }
+
+    /// Like [`Task::join`], but fails once `interpreter`'s run is over its
+    /// budget instead of waiting past it. Writes out what the task printed.
+    fn join_within_budget(&mut self, interpreter: &mut Interpreter) -> Result<Message, Error> {
+        let result = self
+            .result
+            .as_ref()
+            .ok_or_else(|| join_error("task was already joined"))?
+            .lock()
+            .expect("task lock poisoned.");
+        let outcome = loop {
+            match result.recv_timeout(BUDGET_CHECK_INTERVAL) {
+                Ok(outcome) => break outcome,
+                Err(mpsc::RecvTimeoutError::Timeout) => interpreter.check_budget()?,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break Err("task panicked".to_string()),
+            }
+        };
+        drop(result);
+        self.result = None;
+        interpreter.forward_task_output();
+        outcome.map_err(join_error)
+    }
+
+    pub fn class() -> Gc<HostClass> {
+        ClassBuilder::<Task>::new("Task")
+            .native_method("join", 0, |interpreter, task, _| {
+                let message = task.join_within_budget(interpreter)?;
+                Ok(Gc::new(message.into_lox()))
+            })
+            .build()
+    }
+}
+
+fn join_error(msg: impl Into<String>) -> Error {
+    Error::NativeError {
+        name: "Task.join".to_string(),
+        msg: msg.into(),
+    }
+}
+
+/// What one run shares with every task spawned from it, directly or not.
+#[derive(Clone, Default)]
+pub(crate) struct TaskGroup {
+    shared: Arc<GroupState>,
+}
+
+#[derive(Default)]
+struct GroupState {
+    /// The heap bytes held by the run and its tasks, as each last reported.
+    bytes: AtomicIsize,
+    /// How many tasks haven't finished yet.
+    running: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl TaskGroup {
+    /// Adds `change` to the bytes the group holds and returns the total.
+    pub(crate) fn report(&self, change: isize) -> isize {
+        self.shared.bytes.fetch_add(change, Ordering::Relaxed) + change
+    }
+
+    fn running(&self) -> std::sync::MutexGuard<'_, usize> {
+        self.shared
+            .running
+            .lock()
+            .expect("task group lock poisoned.")
+    }
+
+    /// Counts a task as running until the returned guard is dropped.
+    fn start(&self) -> Running {
+        *self.running() += 1;
+        Running(self.clone())
+    }
+
+    /// Waits until every task has finished, or `deadline` passes. Returns
+    /// whether they all did.
+    pub(crate) fn wait(&self, deadline: Option<Instant>) -> bool {
+        let mut running = self.running();
+        while *running > 0 {
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) => left,
+                    None => return false,
+                },
+                None => Duration::MAX,
+            };
+            running = self
+                .shared
+                .finished
+                .wait_timeout(running, timeout)
+                .expect("task group lock poisoned.")
+                .0;
+        }
+        true
+    }
+}
+
+/// A task of a [`TaskGroup`] that is still running.
+struct Running(TaskGroup);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        *self.0.running() -= 1;
+        self.0.shared.finished.notify_all();
+    }
+}
+
+/// Something a task printed, and to which stream.
+pub(crate) type Output = (OutputStream, Vec<u8>);
+
+/// Where the output of tasks goes when their spawner's is redirected: back
+/// to the interpreter that spawned the first of them, which writes it out.
+#[derive(Default)]
+pub(crate) struct TaskOutput {
+    sender: Option<mpsc::Sender<Output>>,
+    /// Only on the interpreter the output is sent back to, not in tasks.
+    receiver: Option<Mutex<mpsc::Receiver<Output>>>,
+}
+
+impl TaskOutput {
+    /// For a task, passing its output on with `sender`.
+    fn forwarding(sender: mpsc::Sender<Output>) -> Self {
+        Self {
+            sender: Some(sender),
+            receiver: None,
+        }
+    }
+
+    /// Where tasks spawned from here send their output.
+    fn sender(&mut self) -> mpsc::Sender<Output> {
+        if let Some(sender) = &self.sender {
+            return sender.clone();
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.sender = Some(sender.clone());
+        self.receiver = Some(Mutex::new(receiver));
+        sender
+    }
+
+    /// What tasks sent back since last time.
+    pub(crate) fn received(&self) -> Vec<Output> {
+        match &self.receiver {
+            Some(receiver) => receiver
+                .lock()
+                .expect("task output lock poisoned.")
+                .try_iter()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// A task's `stdout` or `stderr`, sent back to the spawner.
+struct Forward {
+    stream: OutputStream,
+    sender: mpsc::Sender<Output>,
+}
+
+impl Write for Forward {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // Once the spawner is gone there is nowhere left to write to.
+        let _ = self.sender.send((self.stream, data.to_vec()));
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stands in for a global the host defined, which can't follow a task onto
+/// its thread.
+struct HostOnly {
+    name: String,
+}
+
+impl Callable for HostOnly {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn variadic(&self) -> bool {
+        true
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        Err(task_error(format!(
+            "'{}' is defined by the host and isn't available in spawned tasks.",
+            self.name
+        )))
+    }
+}
+
+/// What a task takes from the session that spawned it, besides its globals.
+struct Inherited {
+    permissions: Permissions,
+    limits: Limits,
+    settings: Settings,
+    math: MathMode,
+    top_level: TopLevel,
+    print_display: bool,
+    dialect: Dialect,
+    host_globals: Vec<String>,
+    run_started: Instant,
+    group: TaskGroup,
+    /// Set when the spawner's streams are redirected.
+    output: Option<mpsc::Sender<Output>>,
+}
+
+impl Inherited {
+    fn capture(interpreter: &mut Interpreter) -> Self {
+        let output = interpreter
+            .streams_mut()
+            .is_redirected()
+            .then(|| interpreter.task_output_mut().sender());
+        Self {
+            permissions: interpreter.permissions().clone(),
+            limits: interpreter.limits(),
+            settings: interpreter.settings(),
+            math: interpreter.math_mode(),
+            top_level: interpreter.top_level(),
+            print_display: interpreter.print_display(),
+            dialect: interpreter.dialect().clone(),
+            host_globals: interpreter.host_globals().map(str::to_owned).collect(),
+            run_started: interpreter.run_started(),
+            group: interpreter.task_group().clone(),
+            output,
+        }
+    }
+
+    /// A fresh interpreter set up like the spawner's. Host globals that it
+    /// doesn't define itself, as it does builtins, fail when used.
+    fn interpreter(self) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        *interpreter.permissions_mut() = self.permissions;
+        interpreter.set_limits(self.limits);
+        interpreter.set_settings(self.settings);
+        interpreter.set_math_mode(self.math);
+        interpreter.set_top_level(self.top_level);
+        interpreter.set_print_display(self.print_display);
+        interpreter.set_dialect(self.dialect);
+        interpreter.join_run(self.run_started, self.group);
+        if let Some(sender) = self.output {
+            for stream in [OutputStream::Stdout, OutputStream::Stderr] {
+                let sender = sender.clone();
+                interpreter.redirect_output(stream, Forward { stream, sender });
+            }
+            interpreter.redirect_input(io::empty());
+            *interpreter.task_output_mut() = TaskOutput::forwarding(sender);
+        }
+
+        for name in self.host_globals {
+            if interpreter.get_global(&name).is_none() {
+                let stub = HostOnly { name: name.clone() };
+                interpreter.set_global(&name, Gc::new(Object::Function(Gc::new(stub))));
+            }
+        }
+        interpreter
+    }
+}
+
+fn task_error(msg: impl Into<String>) -> Error {
+    Error::NativeError {
+        name: "spawn".to_string(),
+        msg: msg.into(),
+    }
+}
+
+/// Runs the function declared as [`TASK_ENTRY`] in a fresh interpreter.
+fn run_task(
+    snapshot: Snapshot,
+    channels: Vec<(String, Channel)>,
+    interpreter: &Gc<GcCell<Interpreter>>,
+) -> Result<Message, String> {
+    snapshot
+        .restore(interpreter)
+        .map_err(|error| error.to_string())?;
+
+    let mut interpreter = interpreter.borrow_mut();
+    let globals = interpreter.copy_globals();
+    let class = Channel::class();
+    for (name, channel) in channels {
+        let channel = class
+            .instantiate(channel)
+            .map_err(|error| error.to_string())?;
+        globals.borrow_mut().define(name, Gc::new(channel));
+    }
+
+    let entry = globals
+        .borrow()
+        .get(TASK_ENTRY)
+        .map_err(|error| error.to_string())?;
+    let result = interpreter
+        .call_value(entry, Vec::new())
+        .map_err(|error| error.to_string())?;
+    interpreter
+        .run_events(true)
+        .map_err(|error| error.to_string())?;
+
+    Message::from_lox(&result)
+}
+
+/// `spawn(fn)`: runs a top-level function without arguments on a new thread
+/// and returns its task.
+pub struct Spawn {
+    class: Gc<HostClass>,
+}
+
+impl Spawn {
+    pub fn new() -> Self {
+        Self {
+            class: Task::class(),
+        }
+    }
+}
+
+impl Default for Spawn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Callable for Spawn {
+    type E = Error;
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let globals = interpreter.copy_globals();
+
+        let function = match &*arguments[0] {
+            Object::Function(function) => function.as_lox_function(),
+            _ => None,
+        };
+        let Some(function) = function else {
+            return Err(task_error(format!(
+                "expected a function, got {}.",
+                arguments[0]
+            )));
+        };
+        if !Gc::ptr_eq(function.closure(), &globals) {
+            return Err(task_error("only top-level functions can be spawned."));
+        }
+        if !function.params().is_empty() {
+            return Err(task_error("spawned functions can't take parameters."));
+        }
+
+        let mut snapshot = Snapshot::capture(interpreter);
+        snapshot.push(
+            TASK_ENTRY.to_string(),
+            Entry::Function(Printer::new().print_function(
+                function.name(),
+                function.params(),
+                function.body(),
+                function.doc(),
+            )),
+        );
+
+        let channels: Vec<(String, Channel)> = globals
+            .borrow()
+            .values
+            .iter()
+            .filter_map(|(name, value)| match &**value {
+                Object::UserData(data) => {
+                    data.with(|channel: &Channel| (name.clone(), channel.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let inherited = Inherited::capture(interpreter);
+        let running = inherited.group.start();
+        let (sender, result) = mpsc::channel();
+        thread::Builder::new()
+            .stack_size(TASK_STACK_SIZE)
+            .spawn(move || {
+                let _running = running;
+                let interpreter = Gc::new(GcCell::new(inherited.interpreter()));
+                let outcome = run_task(snapshot, channels, &interpreter);
+                interpreter.borrow_mut().leave_run();
+                let _ = sender.send(outcome);
+            })
+            .map_err(|error| task_error(error.to_string()))?;
+
+        Ok(Gc::new(self.class.instantiate(Task {
+            result: Some(Mutex::new(result)),
+        })?))
+    }
+}
@@ -1,45 +1,64 @@
-use std::{fmt::Display, hash::Hash};
+use std::{fmt::Display, hash::Hash, ops::Range};
 
 use crate::ast::Literal;
 
-#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, Default)]
 pub enum TokenType {
     //Single-character tokens
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
     Plus,
+    Pound,
     Semicolon,
     Slash,
     Star,
 
     // One or two character tokens
+    Arrow,
+    ThinArrow,
     Bang,
     BangEqual,
+    DotDot,
     Equal,
     EqualEqual,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
+    Pipe,
 
     // Literals
     Identifier,
     String,
+    Bytes,
     Number,
 
+    // A `///` comment, kept so declarations can carry documentation
+    DocComment,
+
+    // Text the scanner couldn't make a token of, with the reason as its literal
+    Error,
+
     // Keywords
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    Import,
+    Match,
     Nil,
     Or,
     Print,
@@ -50,15 +69,30 @@ pub enum TokenType {
     Var,
     While,
 
+    #[default]
     EOF,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The default is an empty `EOF`, left behind by `std::mem::take`.
+#[derive(Debug, Clone, Default)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: Option<Literal>,
     line: usize,
+    /// Byte offset in the source it was scanned from. A `u32` fits beside
+    /// the token type, so tokens are no bigger for having it.
+    offset: u32,
+}
+
+/// Offsets don't take part, like the ids of
+/// [`Boundary`](crate::ast::Boundary): the same code moved within a file
+/// parses to the same tokens.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        (self.token_type, &self.lexeme, &self.literal, self.line)
+            == (other.token_type, &other.lexeme, &other.literal, other.line)
+    }
 }
 
 impl Eq for Token {}
@@ -70,17 +104,32 @@ impl Token {
             lexeme: lexeme.to_string(),
             literal,
             line,
+            offset: 0,
         }
     }
 
-    fn to_string(&self) -> String {
-        format!("{:?} {}", self.token_type, self.lexeme)
+    /// The token found at byte `offset` of its source.
+    pub(crate) fn at(mut self, offset: usize) -> Self {
+        self.offset = offset as u32;
+        self
     }
 
     pub fn line(&self) -> usize {
         self.line
     }
 
+    /// The bytes of the source the token was scanned from that it spans.
+    /// Tokens the host makes up start at 0.
+    pub fn span(&self) -> Range<usize> {
+        let start = self.offset as usize;
+        start..start + self.lexeme.len()
+    }
+
+    /// Moves the token `lines` lines further down its source.
+    pub(crate) fn shift(&mut self, lines: usize) {
+        self.line += lines;
+    }
+
     pub fn lexeme(&self) -> &str {
         &self.lexeme
     }
@@ -91,33 +140,48 @@ impl Display for TokenType {
         match &self {
             Self::LeftParen => f.write_str("("),
             Self::RightParen => f.write_str(")"),
-            Self::LeftBrace => f.write_str("["),
-            Self::RightBrace => f.write_str("]"),
+            Self::LeftBrace => f.write_str("{"),
+            Self::RightBrace => f.write_str("}"),
+            Self::LeftBracket => f.write_str("["),
+            Self::RightBracket => f.write_str("]"),
+            Self::Colon => f.write_str(":"),
             Self::Comma => f.write_str(","),
             Self::Dot => f.write_str("."),
             Self::Minus => f.write_str("-"),
             Self::Plus => f.write_str("+"),
+            Self::Pound => f.write_str("#"),
             Self::Semicolon => f.write_str(";"),
             Self::Slash => f.write_str("/"),
             Self::Star => f.write_str("*"),
+            Self::Arrow => f.write_str("=>"),
             Self::Bang => f.write_str("!"),
             Self::BangEqual => f.write_str("!="),
+            Self::DotDot => f.write_str(".."),
             Self::Equal => f.write_str("="),
             Self::EqualEqual => f.write_str("=="),
             Self::Greater => f.write_str(">"),
             Self::GreaterEqual => f.write_str(">="),
             Self::Less => f.write_str("<"),
             Self::LessEqual => f.write_str("<="),
+            Self::Pipe => f.write_str("|>"),
+            Self::ThinArrow => f.write_str("->"),
             Self::Identifier => f.write_str("IDENT"),
             Self::String => f.write_str("STR"),
+            Self::Bytes => f.write_str("BYTES"),
             Self::Number => f.write_str("NUM"),
+            Self::DocComment => f.write_str("DOC"),
+            Self::Error => f.write_str("ERROR"),
             Self::And => f.write_str("and"),
+            Self::Break => f.write_str("break"),
             Self::Class => f.write_str("class"),
+            Self::Continue => f.write_str("continue"),
             Self::Else => f.write_str("else"),
             Self::False => f.write_str("false"),
             Self::Fun => f.write_str("fun"),
             Self::For => f.write_str("for"),
             Self::If => f.write_str("if"),
+            Self::Import => f.write_str("import"),
+            Self::Match => f.write_str("match"),
             Self::Nil => f.write_str("nil"),
             Self::Or => f.write_str("or"),
             Self::Print => f.write_str("print"),
@@ -148,3 +212,26 @@ impl Hash for Token {
         self.lexeme.hash(state);
     }
 }
+
+/// Source that doesn't change the program but that a formatter keeps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivia {
+    /// A `//` comment on `line`, as written. It's trailing if code comes
+    /// before it on the line; that code ends at byte `after`.
+    Comment {
+        text: String,
+        line: usize,
+        trailing: bool,
+        after: usize,
+    },
+    /// One or more blank lines before `line`.
+    Blank { line: usize },
+}
+
+impl Trivia {
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Comment { line, .. } | Self::Blank { line } => *line,
+        }
+    }
+}
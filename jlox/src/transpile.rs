@@ -0,0 +1,1199 @@
+//! `jlox transpile`: a script as a Rust program that runs without the
+//! interpreter, on the small runtime in [`crate::runtime`].
+//!
+//! It takes the part of Lox that needs nothing looked up by name at
+//! runtime: functions and closures, variables, `if`, `while` and `for`,
+//! `print` and the native `clock`. Classes, lists, maps, tuples, `match`,
+//! imports and destructuring are refused, with the line they're on.
+//!
+//! The output follows the source statement by statement. Every Lox value
+//! is a [`Value`](crate::runtime::Value), and:
+//!
+//! - a top-level function that only uses its own variables, natives and
+//!   other such functions becomes a Rust `fn`, called directly; any other
+//!   function becomes a closure;
+//! - a local that closures capture lives in a [`Var`](crate::runtime::Var),
+//!   and a global that functions use in a [`Global`](crate::runtime::Global);
+//!   every other variable is a plain `let`;
+//! - names are converted to snake case.
+//!
+//! Deep recursion overflows the Rust stack instead of failing with
+//! "Stack overflow.".
+//!
+//! ```
+//! use jlox::{parse_source, transpile};
+//!
+//! let statements = parse_source("fun square(x) { return x * x; } print square(3);").unwrap();
+//! let rust = transpile::to_rust(&statements, "square.lox").unwrap();
+//! assert!(rust.contains("fn square(x: Value) -> Result<Value> {\n    x.mul(&x)\n}"));
+//! assert!(rust.contains("println!(\"{:?}\", square(Value::Number(3.0))?);"));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{
+    ast::{Expr, Literal, Stmt},
+    token::{Token, TokenType},
+};
+
+/// Why a script can't be transpiled.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message} [line {line}]")]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+/// The natives [`crate::runtime`] provides, with their arities.
+const NATIVES: &[(&str, usize)] = &[("clock", 0)];
+
+/// Names the generated code uses for itself.
+const RESERVED: &[&str] = &["args", "left", "main", "run"];
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// `fibSlow` as `fib_slow`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let after_lower = chars[i - 1].is_ascii_lowercase() || chars[i - 1].is_ascii_digit();
+            let before_lower = chars.get(i + 1).is_some_and(|c| c.is_ascii_lowercase());
+            if after_lower || (chars[i - 1].is_ascii_uppercase() && before_lower) {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Where a variable lives in the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Storage {
+    Plain,
+    Var,
+    Global,
+    Item,
+}
+
+struct Binding {
+    name: String,
+    rust: String,
+    global: bool,
+    /// The function declaring it, 0 for top-level code.
+    function: usize,
+    storage: Storage,
+    read: bool,
+    assigned: bool,
+    /// Whether a function other than the declaring one uses it.
+    captured: bool,
+    /// Whether top-level code uses the global before declaring it.
+    early: bool,
+    /// How often top-level code declares the global.
+    declarations: usize,
+    /// The declaration, if the binding is a top-level function declared
+    /// once.
+    function_stmt: Option<*const Stmt>,
+    arity: usize,
+    /// Whether it's declared by a `for` loop, which gives each iteration a
+    /// fresh copy of it.
+    per_iteration: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Resolved {
+    Binding(usize),
+    Native(&'static str, usize),
+}
+
+/// What the generated code needs to know about the variables of a script.
+#[derive(Default)]
+struct Analysis {
+    bindings: Vec<Binding>,
+    /// Keyed by the address of the declaring token.
+    declarations: HashMap<*const Token, usize>,
+    /// Keyed by the address of the token naming the variable.
+    references: HashMap<*const Token, Resolved>,
+    /// For each function declaration, the bindings of enclosing functions
+    /// it uses, in order of first use.
+    free: HashMap<*const Stmt, Vec<usize>>,
+}
+
+impl Analysis {
+    fn declared(&self, name: &Token) -> &Binding {
+        &self.bindings[self.declarations[&(name as *const Token)]]
+    }
+
+    fn resolved(&self, name: &Token) -> Resolved {
+        self.references[&(name as *const Token)]
+    }
+}
+
+struct Analyzer {
+    analysis: Analysis,
+    globals: HashMap<String, usize>,
+    /// Local scopes, innermost last, each with the bindings it declares.
+    scopes: Vec<Vec<(String, usize)>>,
+    /// The functions being analyzed, innermost last.
+    functions: Vec<(usize, *const Stmt)>,
+    next_function: usize,
+    /// Globals declared so far, in the order top-level code runs.
+    defined: HashSet<String>,
+    /// Loops enclosing the current statement within the current function.
+    loops: usize,
+    line: usize,
+    rust_names: HashMap<String, String>,
+    taken: HashSet<String>,
+}
+
+impl Analyzer {
+    fn analyze(statements: &[Stmt]) -> Result<Analysis, Error> {
+        let mut analyzer = Self {
+            analysis: Analysis::default(),
+            globals: HashMap::new(),
+            scopes: Vec::new(),
+            functions: Vec::new(),
+            next_function: 1,
+            defined: HashSet::new(),
+            loops: 0,
+            line: 1,
+            rust_names: HashMap::new(),
+            taken: RESERVED.iter().map(|name| name.to_string()).collect(),
+        };
+
+        for stmt in statements {
+            if let Stmt::Var { name, .. } | Stmt::Function { name, .. } = stmt {
+                if !analyzer.globals.contains_key(&name.lexeme) {
+                    let binding = analyzer.bind(&name.lexeme, true);
+                    analyzer.globals.insert(name.lexeme.clone(), binding);
+                }
+            }
+        }
+        for stmt in statements {
+            analyzer.statement(stmt)?;
+        }
+
+        analyzer.assign_storage();
+        Ok(analyzer.analysis)
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn unsupported(&self, what: &str) -> Error {
+        self.error(format!("Can't transpile {what}."))
+    }
+
+    /// The Rust name of `name`, the same for every variable called that.
+    fn rust_name(&mut self, name: &str) -> String {
+        if let Some(rust) = self.rust_names.get(name) {
+            return rust.clone();
+        }
+
+        let mut snake = snake_case(name);
+        if snake.chars().all(|c| c == '_') {
+            snake.push('v');
+        }
+        while self.taken.contains(&snake) {
+            snake.push('_');
+        }
+        self.taken.insert(snake.clone());
+
+        let rust = match snake.as_str() {
+            "self" | "super" | "crate" => format!("{snake}_"),
+            _ if KEYWORDS.contains(&snake.as_str()) => format!("r#{snake}"),
+            _ => snake,
+        };
+        self.rust_names.insert(name.to_owned(), rust.clone());
+        rust
+    }
+
+    fn current_function(&self) -> usize {
+        self.functions.last().map_or(0, |&(id, _)| id)
+    }
+
+    fn bind(&mut self, name: &str, global: bool) -> usize {
+        let rust = self.rust_name(name);
+        self.analysis.bindings.push(Binding {
+            name: name.to_owned(),
+            rust,
+            global,
+            function: if global { 0 } else { self.current_function() },
+            storage: Storage::Plain,
+            read: false,
+            assigned: false,
+            captured: false,
+            early: false,
+            declarations: 0,
+            function_stmt: None,
+            arity: 0,
+            per_iteration: false,
+        });
+        self.analysis.bindings.len() - 1
+    }
+
+    /// Declares `name` in the current scope, or defines the global if there
+    /// is none.
+    fn declare(&mut self, name: &Token) -> usize {
+        let binding = match self.scopes.last() {
+            None => {
+                let binding = self.globals[&name.lexeme];
+                self.analysis.bindings[binding].declarations += 1;
+                self.defined.insert(name.lexeme.clone());
+                binding
+            }
+            Some(_) => {
+                let binding = self.bind(&name.lexeme, false);
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .push((name.lexeme.clone(), binding));
+                binding
+            }
+        };
+        self.analysis
+            .declarations
+            .insert(name as *const Token, binding);
+        binding
+    }
+
+    fn reference(&mut self, name: &Token, assign: bool) -> Result<(), Error> {
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(declared, _)| *declared == name.lexeme)
+            .map(|&(_, binding)| binding);
+        let resolved = match local.or_else(|| self.globals.get(&name.lexeme).copied()) {
+            Some(binding) => Resolved::Binding(binding),
+            None => match NATIVES.iter().find(|(native, _)| *native == name.lexeme) {
+                Some(_) if assign => {
+                    return Err(self.error(format!("Can't assign to the native '{}'.", name.lexeme)))
+                }
+                Some(&(native, arity)) => Resolved::Native(native, arity),
+                None => return Err(self.error(format!("Undefined variable '{}'.", name.lexeme))),
+            },
+        };
+        self.analysis
+            .references
+            .insert(name as *const Token, resolved);
+
+        let Resolved::Binding(index) = resolved else {
+            return Ok(());
+        };
+        let top_level = self.functions.is_empty();
+        let defined = self.defined.contains(&name.lexeme);
+        let binding = &mut self.analysis.bindings[index];
+        if assign {
+            binding.assigned = true;
+        } else {
+            binding.read = true;
+        }
+        if binding.global && top_level && !defined {
+            binding.early = true;
+        }
+
+        let declared_in = binding.function;
+        for &(function, stmt) in self.functions.iter().rev() {
+            if function == declared_in {
+                break;
+            }
+            self.analysis.bindings[index].captured = true;
+            let free = self.analysis.free.entry(stmt).or_default();
+            if !free.contains(&index) {
+                free.push(index);
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        self.line = stmt.boundary().line;
+        match stmt {
+            Stmt::Block { statements, .. } => {
+                self.scopes.push(Vec::new());
+                for stmt in statements {
+                    self.statement(stmt)?;
+                }
+                self.scopes.pop();
+            }
+            Stmt::Break { keyword, .. } | Stmt::Continue { keyword, .. } if self.loops == 0 => {
+                return Err(self.error(format!("Can't use '{}' outside of a loop.", keyword.lexeme)))
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Class { .. } => return Err(self.unsupported("classes")),
+            Stmt::Destructure { .. } => return Err(self.unsupported("destructuring")),
+            Stmt::Expression { expr, .. } | Stmt::Print { expr, .. } => self.expression(expr)?,
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(Vec::new());
+                if let Some(initializer) = initializer {
+                    self.statement(initializer)?;
+                    if let Stmt::Var { name, .. } = &**initializer {
+                        let binding = self.analysis.declarations[&(name as *const Token)];
+                        self.analysis.bindings[binding].per_iteration = true;
+                    }
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition)?;
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                }
+                self.loops += 1;
+                self.statement(body)?;
+                self.loops -= 1;
+                self.scopes.pop();
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                let binding = self.declare(name);
+                if self.scopes.is_empty() {
+                    let binding = &mut self.analysis.bindings[binding];
+                    binding.function_stmt = Some(stmt as *const Stmt);
+                    binding.arity = params.len();
+                }
+
+                self.functions
+                    .push((self.next_function, stmt as *const Stmt));
+                self.next_function += 1;
+                let loops = std::mem::take(&mut self.loops);
+                self.scopes.push(Vec::new());
+                for param in params {
+                    self.declare(param);
+                }
+                for stmt in body {
+                    self.statement(stmt)?;
+                }
+                self.scopes.pop();
+                self.loops = loops;
+                self.functions.pop();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expression(condition)?;
+                self.statement(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+            }
+            Stmt::Import { .. } => return Err(self.unsupported("imports")),
+            Stmt::Return { .. } if self.functions.is_empty() => {
+                return Err(self.error("Can't return from top-level code."))
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value)?;
+                }
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer)?;
+                }
+                self.declare(name);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.expression(condition)?;
+                self.loops += 1;
+                self.statement(body)?;
+                self.loops -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), Error> {
+        match expr {
+            Expr::Assign { name, value, .. } => {
+                self.expression(value)?;
+                self.reference(name, true)
+            }
+            Expr::Variable { name, .. } => self.reference(name, false),
+            Expr::Literal(Literal::Bytes(_)) => Err(self.unsupported("byte strings")),
+            Expr::Get { .. } | Expr::Set { .. } => Err(self.unsupported("properties")),
+            Expr::Index { .. } | Expr::SetIndex { .. } => Err(self.unsupported("indexing")),
+            Expr::List { .. } => Err(self.unsupported("lists")),
+            Expr::Map { .. } => Err(self.unsupported("maps")),
+            Expr::Match { .. } => Err(self.unsupported("'match'")),
+            Expr::Super { .. } => Err(self.unsupported("'super'")),
+            Expr::This { .. } => Err(self.unsupported("'this'")),
+            Expr::Tuple { .. } => Err(self.unsupported("tuples")),
+            _ => expr
+                .children()
+                .into_iter()
+                .try_for_each(|child| self.expression(child)),
+        }
+    }
+
+    /// Decides which top-level functions become Rust functions and where
+    /// every other variable lives.
+    fn assign_storage(&mut self) {
+        let bindings = &mut self.analysis.bindings;
+        let mut items: HashSet<usize> = (0..bindings.len())
+            .filter(|&i| {
+                let binding = &bindings[i];
+                binding.function_stmt.is_some()
+                    && binding.declarations == 1
+                    && !binding.assigned
+                    && !binding.early
+            })
+            .collect();
+        // An item can only use other items, so drop functions using
+        // anything else until nothing changes.
+        loop {
+            let dropped: Vec<usize> = items
+                .iter()
+                .copied()
+                .filter(|&i| {
+                    let stmt = bindings[i].function_stmt.unwrap();
+                    let free = self.analysis.free.get(&stmt).map_or(&[][..], Vec::as_slice);
+                    free.iter().any(|binding| !items.contains(binding))
+                })
+                .collect();
+            if dropped.is_empty() {
+                break;
+            }
+            for i in dropped {
+                items.remove(&i);
+            }
+        }
+
+        for (i, binding) in bindings.iter_mut().enumerate() {
+            binding.storage = if items.contains(&i) {
+                Storage::Item
+            } else if binding.global && (binding.captured || binding.early) {
+                Storage::Global
+            } else if !binding.global && binding.captured {
+                Storage::Var
+            } else {
+                Storage::Plain
+            };
+            if !binding.read && matches!(binding.storage, Storage::Plain | Storage::Var) {
+                binding.rust = format!("_{}", binding.rust.trim_start_matches("r#"));
+            }
+        }
+    }
+}
+
+/// A loop of the function being written.
+struct Loop {
+    /// The labels of the loop, if it's broken out of, and of its body, when
+    /// `continue` has to jump past the rest of the body to the code after
+    /// it.
+    labels: Option<(Option<String>, String)>,
+}
+
+struct Emitter<'a> {
+    analysis: &'a Analysis,
+    out: String,
+    indent: usize,
+    loops: Vec<Loop>,
+    labels: usize,
+}
+
+/// Whether `stmt` breaks out of the loop it's the body of, or with
+/// `continues` continues it.
+fn jumps(stmt: &Stmt, continues: bool) -> bool {
+    match stmt {
+        Stmt::Break { .. } => !continues,
+        Stmt::Continue { .. } => continues,
+        Stmt::For { .. } | Stmt::While { .. } | Stmt::Function { .. } => false,
+        _ => stmt
+            .children()
+            .into_iter()
+            .any(|stmt| jumps(stmt, continues)),
+    }
+}
+
+/// The statements of a branch or loop body, without the braces of a block.
+fn body(stmt: &Stmt) -> &[Stmt] {
+    match stmt {
+        Stmt::Block { statements, .. } => statements,
+        _ => std::slice::from_ref(stmt),
+    }
+}
+
+fn ungrouped(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Grouping { ex } => ungrouped(ex),
+        _ => expr,
+    }
+}
+
+/// The [`crate::runtime::Value`] comparison method for `op`.
+fn comparison(op: TokenType) -> Option<&'static str> {
+    match op {
+        TokenType::Less => Some("less"),
+        TokenType::LessEqual => Some("less_equal"),
+        TokenType::Greater => Some("greater"),
+        TokenType::GreaterEqual => Some("greater_equal"),
+        _ => None,
+    }
+}
+
+/// `script` as a Rust program. `source` names the script in the header
+/// comment.
+pub fn to_rust(statements: &[Stmt], source: &str) -> Result<String, Error> {
+    let analysis = Analyzer::analyze(statements)?;
+    let mut emitter = Emitter {
+        analysis: &analysis,
+        out: String::new(),
+        indent: 0,
+        loops: Vec::new(),
+        labels: 0,
+    };
+
+    emitter.line(&format!("// Transpiled from {source} by `jlox transpile`."));
+    emitter.line("");
+    emitter.line("use jlox::runtime::*;");
+
+    for stmt in statements {
+        if let Stmt::Function {
+            name,
+            params,
+            body,
+            doc,
+            ..
+        } = stmt
+        {
+            if analysis.declared(name).storage == Storage::Item {
+                emitter.line("");
+                emitter.item(name, params, body, doc.as_deref());
+            }
+        }
+    }
+
+    emitter.line("");
+    emitter.line("fn main() -> std::process::ExitCode {");
+    emitter.indent += 1;
+    emitter.line("run(|| {");
+    emitter.indent += 1;
+    for binding in &analysis.bindings {
+        if binding.storage == Storage::Global {
+            emitter.line(&format!(
+                "let {} = Global::new({:?});",
+                binding.rust, binding.name
+            ));
+        }
+    }
+    for stmt in statements {
+        emitter.statement(stmt);
+    }
+    emitter.line("Ok(())");
+    emitter.indent -= 1;
+    emitter.line("})");
+    emitter.indent -= 1;
+    emitter.line("}");
+    Ok(emitter.out)
+}
+
+impl Emitter<'_> {
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&"    ".repeat(self.indent));
+            self.out.push_str(text);
+        }
+        self.out.push('\n');
+    }
+
+    fn item(&mut self, name: &Token, params: &[Token], body: &[Stmt], doc: Option<&str>) {
+        for line in doc.into_iter().flat_map(str::lines) {
+            self.line(format!("/// {line}").trim_end());
+        }
+        let signature: Vec<String> = params
+            .iter()
+            .map(|param| {
+                let binding = self.analysis.declared(param);
+                match binding.storage {
+                    Storage::Plain if binding.assigned => format!("mut {}: Value", binding.rust),
+                    _ => format!("{}: Value", binding.rust),
+                }
+            })
+            .collect();
+        self.line(&format!(
+            "fn {}({}) -> Result<Value> {{",
+            self.analysis.declared(name).rust,
+            signature.join(", ")
+        ));
+        self.indent += 1;
+        for param in params {
+            let binding = self.analysis.declared(param);
+            if binding.storage == Storage::Var {
+                self.line(&format!("let {0} = Var::new({0});", binding.rust));
+            }
+        }
+        self.function_body(body);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// A function that isn't an item, as a closure `prefix` and `suffix`
+    /// go around.
+    fn closure(&mut self, stmt: &Stmt, prefix: &str, suffix: &str) {
+        let Stmt::Function {
+            name, params, body, ..
+        } = stmt
+        else {
+            unreachable!("not a function");
+        };
+        let captures: Vec<&str> = self
+            .analysis
+            .free
+            .get(&(stmt as *const Stmt))
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.analysis.bindings[i])
+            .filter(|binding| matches!(binding.storage, Storage::Var | Storage::Global))
+            .map(|binding| binding.rust.as_str())
+            .collect();
+        let args = if params.is_empty() { "_" } else { "args" };
+        let header = format!(
+            "{prefix}Value::function({:?}, {}, ",
+            name.lexeme,
+            params.len()
+        );
+
+        let capturing = !captures.is_empty();
+        if !capturing {
+            self.line(&format!("{header}|{args}| {{"));
+        } else {
+            self.line(&format!("{header}{{"));
+            self.indent += 1;
+            for capture in captures {
+                self.line(&format!("let {capture} = {capture}.clone();"));
+            }
+            self.line(&format!("move |{args}| {{"));
+        }
+        self.indent += 1;
+        for (i, param) in params.iter().enumerate() {
+            let binding = self.analysis.declared(param);
+            let line = match binding.storage {
+                Storage::Var => format!("let {} = Var::new(args[{i}].clone());", binding.rust),
+                _ if binding.assigned => format!("let mut {} = args[{i}].clone();", binding.rust),
+                _ => format!("let {} = args[{i}].clone();", binding.rust),
+            };
+            self.line(&line);
+        }
+        self.function_body(body);
+        self.indent -= 1;
+        if capturing {
+            self.line("}");
+            self.indent -= 1;
+        }
+        self.line(&format!("}}){suffix}"));
+    }
+
+    /// The statements of a function, ending with its result.
+    fn function_body(&mut self, body: &[Stmt]) {
+        let loops = std::mem::take(&mut self.loops);
+        match body.split_last() {
+            Some((Stmt::Return { value, .. }, rest)) => {
+                for stmt in rest {
+                    self.statement(stmt);
+                }
+                let result = self.result(value.as_ref());
+                self.line(&result);
+            }
+            _ => {
+                for stmt in body {
+                    self.statement(stmt);
+                }
+                self.line("Ok(Value::Nil)");
+            }
+        }
+        self.loops = loops;
+    }
+
+    /// What a function returns for `return value;`.
+    fn result(&self, value: Option<&Expr>) -> String {
+        let Some(value) = value else {
+            return "Ok(Value::Nil)".to_owned();
+        };
+        // A local is moved out rather than cloned, and `Ok(x?)` is `x`.
+        let value = self.borrowed(value);
+        match value.strip_suffix('?') {
+            Some(result) => result.to_owned(),
+            None => format!("Ok({value})"),
+        }
+    }
+
+    /// The statements of a branch or loop, indented.
+    fn block(&mut self, stmt: &Stmt) {
+        self.indent += 1;
+        for stmt in body(stmt) {
+            self.statement(stmt);
+        }
+        self.indent -= 1;
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements, .. } => {
+                self.line("{");
+                self.indent += 1;
+                for stmt in statements {
+                    self.statement(stmt);
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::Break { .. } => match &self.loops.last().unwrap().labels {
+                Some((Some(outer), _)) => {
+                    let line = format!("break {outer};");
+                    self.line(&line);
+                }
+                _ => self.line("break;"),
+            },
+            Stmt::Continue { .. } => match &self.loops.last().unwrap().labels {
+                Some((_, body)) => {
+                    let line = format!("break {body};");
+                    self.line(&line);
+                }
+                None => self.line("continue;"),
+            },
+            Stmt::Expression { expr, .. } => {
+                let line = match expr {
+                    Expr::Assign { name, value, .. } => self.assignment(name, value),
+                    Expr::Call { .. } => format!("{};", self.value(expr)),
+                    _ => format!("let _ = {};", self.value(expr)),
+                };
+                self.line(&line);
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => self.for_loop(
+                initializer.as_deref(),
+                condition.as_ref(),
+                increment.as_ref(),
+                body,
+            ),
+            Stmt::Function { name, .. } => {
+                let binding = self.analysis.declared(name);
+                let rust = binding.rust.clone();
+                let recursive =
+                    self.analysis
+                        .free
+                        .get(&(stmt as *const Stmt))
+                        .is_some_and(|free| {
+                            free.contains(&self.analysis.declarations[&(name as *const Token)])
+                        });
+                match binding.storage {
+                    Storage::Item => {}
+                    Storage::Plain if binding.assigned => {
+                        self.closure(stmt, &format!("let mut {rust} = "), ";")
+                    }
+                    Storage::Plain => self.closure(stmt, &format!("let {rust} = "), ";"),
+                    Storage::Var if recursive => {
+                        self.line(&format!("let {rust} = Var::default();"));
+                        self.closure(stmt, &format!("{rust}.set("), ");");
+                    }
+                    Storage::Var => self.closure(stmt, &format!("let {rust} = Var::new("), ");"),
+                    Storage::Global => self.closure(stmt, &format!("{rust}.define("), ");"),
+                }
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let line = format!("if {} {{", self.condition(condition));
+                self.line(&line);
+                self.block(then_branch);
+                let mut else_branch = else_branch.as_deref();
+                while let Some(branch) = else_branch {
+                    match branch {
+                        Stmt::If {
+                            condition,
+                            then_branch,
+                            else_branch: next,
+                            ..
+                        } => {
+                            let line = format!("}} else if {} {{", self.condition(condition));
+                            self.line(&line);
+                            self.block(then_branch);
+                            else_branch = next.as_deref();
+                        }
+                        _ => {
+                            self.line("} else {");
+                            self.block(branch);
+                            else_branch = None;
+                        }
+                    }
+                }
+                self.line("}");
+            }
+            Stmt::Print { expr, .. } => {
+                let value = self.borrowed(expr);
+                let line = if value.chars().all(|c| c == '_' || c.is_ascii_alphanumeric()) {
+                    format!("println!(\"{{{value}:?}}\");")
+                } else {
+                    format!("println!(\"{{:?}}\", {value});")
+                };
+                self.line(&line);
+            }
+            Stmt::Return { value, .. } => {
+                let line = format!("return {};", self.result(value.as_ref()));
+                self.line(&line);
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(initializer) => self.value(initializer),
+                    None => "Value::Nil".to_owned(),
+                };
+                let binding = self.analysis.declared(name);
+                let rust = &binding.rust;
+                let line = match binding.storage {
+                    Storage::Plain if binding.assigned => format!("let mut {rust} = {value};"),
+                    Storage::Plain => format!("let {rust} = {value};"),
+                    Storage::Var if binding.per_iteration => {
+                        format!("let mut {rust} = Var::new({value});")
+                    }
+                    Storage::Var => format!("let {rust} = Var::new({value});"),
+                    Storage::Global => format!("{rust}.define({value});"),
+                    Storage::Item => unreachable!("variables aren't items"),
+                };
+                self.line(&line);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let line = match ungrouped(condition) {
+                    Expr::Literal(Literal::True) => "loop {".to_owned(),
+                    _ => format!("while {} {{", self.condition(condition)),
+                };
+                self.line(&line);
+                self.loops.push(Loop { labels: None });
+                self.block(body);
+                self.loops.pop();
+                self.line("}");
+            }
+            Stmt::Class { .. } | Stmt::Destructure { .. } | Stmt::Import { .. } => {
+                unreachable!("refused by the analysis")
+            }
+        }
+    }
+
+    fn for_loop(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) {
+        let scoped = matches!(initializer, Some(Stmt::Var { .. }));
+        if scoped {
+            self.line("{");
+            self.indent += 1;
+        }
+        if let Some(initializer) = initializer {
+            self.statement(initializer);
+        }
+
+        // Each iteration gets its own copy of a variable closures capture.
+        let copied = match initializer {
+            Some(Stmt::Var { name, .. }) => {
+                let binding = self.analysis.declared(name);
+                (binding.storage == Storage::Var).then(|| binding.rust.clone())
+            }
+            _ => None,
+        };
+        let labels = ((copied.is_some() || increment.is_some()) && jumps(body, true)).then(|| {
+            self.labels += 1;
+            let n = if self.labels == 1 {
+                String::new()
+            } else {
+                self.labels.to_string()
+            };
+            let outer = jumps(body, false).then(|| format!("'outer{n}"));
+            (outer, format!("'body{n}"))
+        });
+
+        let mut header = match condition.map(ungrouped) {
+            None | Some(Expr::Literal(Literal::True)) => "loop {".to_owned(),
+            Some(condition) => format!("while {} {{", self.condition(condition)),
+        };
+        if let Some((Some(outer), _)) = &labels {
+            header = format!("{outer}: {header}");
+        }
+        self.line(&header);
+        self.indent += 1;
+        match &labels {
+            Some((_, label)) => {
+                self.line(&format!("{label}: {{"));
+                self.loops.push(Loop {
+                    labels: labels.clone(),
+                });
+                self.block(body);
+                self.loops.pop();
+                self.line("}");
+                self.labels -= 1;
+            }
+            None => {
+                self.loops.push(Loop { labels: None });
+                self.indent -= 1;
+                self.block(body);
+                self.indent += 1;
+                self.loops.pop();
+            }
+        }
+        if let Some(rust) = copied {
+            self.line(&format!("{rust} = Var::new({rust}.get());"));
+        }
+        if let Some(increment) = increment {
+            let line = match increment {
+                Expr::Assign { name, value, .. } => self.assignment(name, value),
+                _ => format!("let _ = {};", self.value(increment)),
+            };
+            self.line(&line);
+        }
+        self.indent -= 1;
+        self.line("}");
+
+        if scoped {
+            self.indent -= 1;
+            self.line("}");
+        }
+    }
+
+    /// `name = value;` as a statement.
+    fn assignment(&self, name: &Token, value: &Expr) -> String {
+        let value = self.value(value);
+        let Resolved::Binding(binding) = self.analysis.resolved(name) else {
+            unreachable!("natives can't be assigned");
+        };
+        let binding = &self.analysis.bindings[binding];
+        match binding.storage {
+            Storage::Plain => format!("{} = {value};", binding.rust),
+            Storage::Var => format!("{}.set({value});", binding.rust),
+            Storage::Global => format!("{}.set({value})?;", binding.rust),
+            Storage::Item => unreachable!("items aren't assigned"),
+        }
+    }
+
+    /// A variable's value, or with `borrowed` something a reference can be
+    /// taken of.
+    fn variable(&self, name: &Token, borrowed: bool) -> String {
+        let (rust, storage, arity) = match self.analysis.resolved(name) {
+            Resolved::Binding(binding) => {
+                let binding = &self.analysis.bindings[binding];
+                (binding.rust.as_str(), binding.storage, binding.arity)
+            }
+            Resolved::Native(native, arity) => (native, Storage::Item, arity),
+        };
+        match storage {
+            Storage::Plain if borrowed => rust.to_owned(),
+            Storage::Plain => format!("{rust}.clone()"),
+            Storage::Var => format!("{rust}.get()"),
+            Storage::Global => format!("{rust}.get()?"),
+            Storage::Item => {
+                let args: Vec<String> = (0..arity).map(|i| format!("args[{i}].clone()")).collect();
+                let params = if arity == 0 { "_" } else { "args" };
+                format!(
+                    "Value::function({:?}, {arity}, |{params}| {rust}({}))",
+                    name.lexeme,
+                    args.join(", ")
+                )
+            }
+        }
+    }
+
+    /// `expr` as an owned `Value`.
+    fn value(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Variable { name, .. } => self.variable(name, false),
+            _ => self.borrowed(expr),
+        }
+    }
+
+    /// `expr` as a `Value` that may be a local variable itself, to borrow
+    /// or to move out of for the last time.
+    fn borrowed(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Assign { name, value, .. } => {
+                let read = match self.analysis.resolved(name) {
+                    Resolved::Binding(binding) => {
+                        let binding = &self.analysis.bindings[binding];
+                        match binding.storage {
+                            Storage::Plain => format!("{}.clone()", binding.rust),
+                            Storage::Var => format!("{}.get()", binding.rust),
+                            _ => format!("{}.get()?", binding.rust),
+                        }
+                    }
+                    Resolved::Native(..) => unreachable!("natives can't be assigned"),
+                };
+                format!("{{ {} {read} }}", self.assignment(name, value))
+            }
+            Expr::Binary { left, op, right } => match op.token_type {
+                TokenType::EqualEqual | TokenType::BangEqual => {
+                    format!("Value::Bool({})", self.condition(expr))
+                }
+                TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+                    let method = match op.token_type {
+                        TokenType::Plus => "add",
+                        TokenType::Minus => "sub",
+                        TokenType::Star => "mul",
+                        _ => "div",
+                    };
+                    format!(
+                        "{}.{method}(&{})?",
+                        self.receiver(left),
+                        self.borrowed(right)
+                    )
+                }
+                _ => format!("Value::Bool({})", self.condition(expr)),
+            },
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let count = arguments.len();
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| self.value(argument))
+                    .collect();
+                let arguments = arguments.join(", ");
+                if let Expr::Variable { name, .. } = &**callee {
+                    let direct = match self.analysis.resolved(name) {
+                        Resolved::Binding(binding) => {
+                            let binding = &self.analysis.bindings[binding];
+                            (binding.storage == Storage::Item)
+                                .then_some((binding.rust.as_str(), binding.arity))
+                        }
+                        Resolved::Native(native, arity) => Some((native, arity)),
+                    };
+                    if let Some((function, arity)) = direct {
+                        if arity == count {
+                            return format!("{function}({arguments})?");
+                        }
+                    }
+                }
+                format!("{}.call([{arguments}])?", self.receiver(callee))
+            }
+            Expr::Grouping { ex } => self.borrowed(ex),
+            Expr::Literal(literal) => match literal {
+                Literal::Number(n) if n.0.is_infinite() => {
+                    "Value::Number(f64::INFINITY)".to_owned()
+                }
+                Literal::Number(n) => format!("Value::Number({:?})", n.0),
+                Literal::String(s) => format!("Value::from({s:?})"),
+                Literal::True => "Value::Bool(true)".to_owned(),
+                Literal::False => "Value::Bool(false)".to_owned(),
+                Literal::Nil => "Value::Nil".to_owned(),
+                Literal::Bytes(_) => unreachable!("refused by the analysis"),
+            },
+            Expr::Logical { left, op, right } => {
+                let right = self.value(right);
+                let right = if right.starts_with('{') {
+                    right
+                } else {
+                    format!("{{ {right} }}")
+                };
+                let (truthy, falsy) = match op.token_type {
+                    TokenType::Or => ("{ left }".to_owned(), right),
+                    _ => (right, "{ left }".to_owned()),
+                };
+                format!(
+                    "{{ let left = {}; if left.truthy() {truthy} else {falsy} }}",
+                    self.value(left)
+                )
+            }
+            Expr::Unary { op, right } => match op.token_type {
+                TokenType::Minus => format!("{}.negate()?", self.receiver(right)),
+                _ => format!("Value::Bool({})", self.condition(expr)),
+            },
+            Expr::Variable { name, .. } => self.variable(name, true),
+            _ => unreachable!("refused by the analysis"),
+        }
+    }
+
+    /// `expr` with a method called on it.
+    fn receiver(&self, expr: &Expr) -> String {
+        let value = self.borrowed(expr);
+        if value.starts_with('{') {
+            format!("({value})")
+        } else {
+            value
+        }
+    }
+
+    /// Whether `expr` is truthy, as a Rust `bool`.
+    fn condition(&self, expr: &Expr) -> String {
+        match ungrouped(expr) {
+            Expr::Binary { left, op, right } => {
+                let (left, right) = (self.receiver(left), self.borrowed(right));
+                match op.token_type {
+                    TokenType::EqualEqual => format!("{left} == {right}"),
+                    TokenType::BangEqual => format!("{left} != {right}"),
+                    token_type => match comparison(token_type) {
+                        Some(method) => format!("{left}.{method}(&{right})?"),
+                        None => format!("{}.truthy()", self.receiver(expr)),
+                    },
+                }
+            }
+            Expr::Logical { left, op, right } => {
+                let operator = if op.token_type == TokenType::Or {
+                    "||"
+                } else {
+                    "&&"
+                };
+                let operand = |expr: &Expr| match ungrouped(expr) {
+                    Expr::Logical { op: inner, .. } if inner.token_type != op.token_type => {
+                        format!("({})", self.condition(expr))
+                    }
+                    _ => self.condition(expr),
+                };
+                format!("{} {operator} {}", operand(left), operand(right))
+            }
+            Expr::Unary { op, right } if op.token_type == TokenType::Bang => {
+                match ungrouped(right) {
+                    Expr::Logical { .. } => format!("!({})", self.condition(right)),
+                    Expr::Binary { left, op, right } if op.token_type == TokenType::EqualEqual => {
+                        format!("{} != {}", self.receiver(left), self.borrowed(right))
+                    }
+                    Expr::Binary { left, op, right } if op.token_type == TokenType::BangEqual => {
+                        format!("{} == {}", self.receiver(left), self.borrowed(right))
+                    }
+                    _ => format!("!{}", self.condition(right)),
+                }
+            }
+            Expr::Literal(Literal::True) => "true".to_owned(),
+            Expr::Literal(Literal::False | Literal::Nil) => "false".to_owned(),
+            expr => format!("{}.truthy()", self.receiver(expr)),
+        }
+    }
+}
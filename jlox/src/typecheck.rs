@@ -0,0 +1,926 @@
+//! Gradual typing: a best-effort check of the optional annotations in
+//! `fun add(a: Number, b: Number) -> Number` and `var n: Number = 0;`.
+//!
+//! A type is `Number`, `String`, `Bool`, `Nil`, `Bytes`, `List`, `Map`,
+//! `Tuple`, `Function`, a class, whose instances it means, or `Any`. What
+//! isn't annotated is `Any`, which goes with everything, so the checker
+//! only speaks up where an annotation says what a value should be and an
+//! expression plainly isn't that. An instance of a subclass goes where its
+//! superclass is expected.
+//!
+//! It checks the initializers of and assignments to annotated variables,
+//! the arguments of calls to functions, classes and methods it can see the
+//! declaration of, and returned values. Running a program prints what it
+//! finds as warnings; `--typed` makes them errors.
+//!
+//! [`infer`] goes further for `jlox types`: an unannotated variable has the
+//! type of what it last held and an unannotated function returns the type
+//! its `return`s agree on, and values used as types they can't be, like a
+//! `String` operand of `-`, are flagged too.
+//!
+//! ```
+//! use jlox::{parse_source, typecheck::check};
+//!
+//! let statements = parse_source("fun half(n: Number) -> Number { return n / 2; } half(\"4\");").unwrap();
+//! let mismatches = check(&statements);
+//! assert_eq!(mismatches[0].message, "Argument 1 to 'half' should be Number, not String.");
+//! ```
+
+use std::{collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{
+    ast::{Expr, Literal, Signature, Stmt},
+    token::{Token, TokenType},
+};
+
+/// What the checker knows about a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Nil,
+    Bool,
+    Number,
+    String,
+    Bytes,
+    List,
+    Map,
+    Tuple,
+    Function,
+    /// An instance of the class with this name.
+    Instance(String),
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => f.write_str("Any"),
+            Self::Nil => f.write_str("Nil"),
+            Self::Bool => f.write_str("Bool"),
+            Self::Number => f.write_str("Number"),
+            Self::String => f.write_str("String"),
+            Self::Bytes => f.write_str("Bytes"),
+            Self::List => f.write_str("List"),
+            Self::Map => f.write_str("Map"),
+            Self::Tuple => f.write_str("Tuple"),
+            Self::Function => f.write_str("Function"),
+            Self::Instance(class) => f.write_str(class),
+        }
+    }
+}
+
+/// A value whose type doesn't match its annotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub line: usize,
+    pub message: String,
+}
+
+/// The mismatches in `statements`, in source order.
+pub fn check(statements: &[Stmt]) -> Vec<Mismatch> {
+    let mut checker = Checker::default();
+    checker.check(statements);
+    checker.mismatches
+}
+
+/// A top-level declaration and the types inferred for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Declaration {
+    Variable {
+        line: usize,
+        name: String,
+        ty: Type,
+    },
+    Function {
+        line: usize,
+        name: String,
+        params: Vec<(String, Type)>,
+        returns: Type,
+    },
+}
+
+impl Declaration {
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Variable { line, .. } | Self::Function { line, .. } => *line,
+        }
+    }
+}
+
+impl Display for Declaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Variable { name, ty, .. } => write!(f, "var {name}: {ty}"),
+            Self::Function {
+                name,
+                params,
+                returns,
+                ..
+            } => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|(name, ty)| format!("{name}: {ty}"))
+                    .collect();
+                write!(f, "fun {name}({}) -> {returns}", params.join(", "))
+            }
+        }
+    }
+}
+
+/// What [`infer`] found in a file.
+#[derive(Debug, Default)]
+pub struct Inference {
+    /// The top-level variables and functions, in source order.
+    pub declarations: Vec<Declaration>,
+    /// Values used as types they can't be, along with annotation
+    /// mismatches, in source order.
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Infers the types of the top-level variables and functions of
+/// `statements`, flagging incompatible uses of values.
+///
+/// ```
+/// use jlox::{parse_source, typecheck::infer};
+///
+/// let statements = parse_source("var n = 1; fun twice(x) { return x * 2; } print -\"one\";").unwrap();
+/// let inference = infer(&statements);
+/// assert_eq!(inference.declarations[0].to_string(), "var n: Number");
+/// assert_eq!(inference.declarations[1].to_string(), "fun twice(x: Any) -> Number");
+/// assert_eq!(inference.mismatches[0].message, "Operand of '-' must be a number, not a String.");
+/// ```
+pub fn infer(statements: &[Stmt]) -> Inference {
+    let mut checker = Checker {
+        infer: true,
+        ..Checker::default()
+    };
+    checker.check(statements);
+
+    let declarations = statements
+        .iter()
+        .flat_map(|stmt| match stmt {
+            Stmt::Var { name, .. } => vec![name],
+            Stmt::Destructure { names, .. } => names.iter().collect(),
+            Stmt::Function { name, .. } => vec![name],
+            _ => Vec::new(),
+        })
+        .filter_map(|name| {
+            let line = name.line();
+            let declaration = match checker.lookup(&name.lexeme)? {
+                Binding::Variable(ty) | Binding::Inferred(ty) => Declaration::Variable {
+                    line,
+                    name: name.lexeme.clone(),
+                    ty,
+                },
+                Binding::Function(function) => {
+                    let Some(Stmt::Function { params, .. }) = statements.iter().find(
+                        |stmt| matches!(stmt, Stmt::Function { name: declared, .. } if declared.lexeme == name.lexeme),
+                    ) else {
+                        return None;
+                    };
+                    Declaration::Function {
+                        line,
+                        name: name.lexeme.clone(),
+                        params: params
+                            .iter()
+                            .map(|param| param.lexeme.clone())
+                            .zip(function.params.iter().cloned())
+                            .collect(),
+                        returns: function.returns.clone(),
+                    }
+                }
+                Binding::Class(_) => return None,
+            };
+            Some(declaration)
+        })
+        .collect();
+
+    Inference {
+        declarations,
+        mismatches: checker.mismatches,
+    }
+}
+
+/// A function's types as annotated, `Any` where they aren't.
+#[derive(Debug)]
+struct FunctionType {
+    params: Vec<Type>,
+    returns: Type,
+}
+
+#[derive(Debug, Default)]
+struct Class {
+    superclass: Option<String>,
+    methods: HashMap<String, Rc<FunctionType>>,
+}
+
+/// What a name in scope is.
+#[derive(Debug, Clone)]
+enum Binding {
+    Variable(Type),
+    /// An unannotated variable, when inferring: the type of what it last
+    /// held, `Nil` until it holds anything else.
+    Inferred(Type),
+    Function(Rc<FunctionType>),
+    Class(String),
+}
+
+/// Checks one file after another, each seeing the globals of those
+/// before it, as a program's files run.
+#[derive(Default)]
+pub(crate) struct Checker {
+    /// Innermost last; the first is the global scope.
+    scopes: Vec<HashMap<String, Binding>>,
+    classes: HashMap<String, Class>,
+    /// The functions being checked, innermost last.
+    returns: Vec<Returning>,
+    /// The class whose methods are being checked, for `this`.
+    class: Option<String>,
+    /// Whether unannotated variables and returns get inferred types, and
+    /// incompatible uses of values are flagged.
+    infer: bool,
+    mismatches: Vec<Mismatch>,
+}
+
+/// A function being checked.
+struct Returning {
+    name: String,
+    declared: Type,
+    /// The types of the values its `return`s give, when inferring.
+    returned: Vec<Type>,
+}
+
+impl Checker {
+    /// Checks `statements`, returning the mismatches found in them.
+    pub fn check(&mut self, statements: &[Stmt]) -> Vec<Mismatch> {
+        if self.scopes.is_empty() {
+            self.scopes.push(HashMap::new());
+        }
+        let found = self.mismatches.len();
+        // Functions and classes can be used above their declaration.
+        self.hoist(statements);
+        for stmt in statements {
+            self.statement(stmt);
+        }
+        self.mismatches[found..].to_vec()
+    }
+
+    /// Binds the classes and then the functions declared in `statements`,
+    /// so annotations can name classes declared below them.
+    fn hoist(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            if let Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } = stmt
+            {
+                self.declare_class(name, superclass.as_ref(), methods);
+            }
+        }
+        for stmt in statements {
+            if let Stmt::Function {
+                name,
+                params,
+                signature,
+                ..
+            } = stmt
+            {
+                let function = self.function_type(params.len(), signature.as_deref());
+                self.bind(name, Binding::Function(function));
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements, .. } => {
+                self.scopes.push(HashMap::new());
+                self.hoist(statements);
+                for stmt in statements {
+                    self.statement(stmt);
+                }
+                self.scopes.pop();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                if !self.classes.contains_key(&name.lexeme) {
+                    self.declare_class(name, superclass.as_ref(), methods);
+                }
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                let enclosing = self.class.replace(name.lexeme.clone());
+                for method in methods {
+                    let Stmt::Function {
+                        name: method_name, ..
+                    } = method
+                    else {
+                        continue;
+                    };
+                    let returns = self.function(method, method_name.lexeme == "init");
+                    let function = self
+                        .classes
+                        .get_mut(&name.lexeme)
+                        .and_then(|class| class.methods.get_mut(&method_name.lexeme));
+                    if let Some(function) = function.filter(|function| function.returns != returns)
+                    {
+                        *function = Rc::new(FunctionType {
+                            params: function.params.clone(),
+                            returns,
+                        });
+                    }
+                }
+                self.class = enclosing;
+            }
+            Stmt::Function {
+                name,
+                params,
+                signature,
+                ..
+            } => {
+                let function = self.function_type(params.len(), signature.as_deref());
+                self.bind(name, Binding::Function(function.clone()));
+                let returns = self.function(stmt, false);
+                if returns != function.returns {
+                    let function = FunctionType {
+                        params: function.params.clone(),
+                        returns,
+                    };
+                    self.bind(name, Binding::Function(Rc::new(function)));
+                }
+            }
+            Stmt::Var {
+                name,
+                annotation,
+                initializer,
+                ..
+            } => {
+                let declared = annotation
+                    .as_ref()
+                    .map_or(Type::Any, |annotation| self.annotated(annotation));
+                let actual = initializer
+                    .as_ref()
+                    .map(|initializer| self.expression(initializer));
+                if let Some(actual) = &actual {
+                    if !self.accepts(&declared, actual) {
+                        self.mismatch(
+                            name,
+                            format!(
+                                "Can't initialize '{}' of type {declared} with {}.",
+                                name.lexeme,
+                                article(actual)
+                            ),
+                        );
+                    }
+                }
+                let binding = match annotation {
+                    None if self.infer => Binding::Inferred(actual.unwrap_or(Type::Nil)),
+                    _ => Binding::Variable(declared),
+                };
+                self.bind(name, binding);
+            }
+            Stmt::Destructure {
+                names, initializer, ..
+            } => {
+                self.expression(initializer);
+                for name in names {
+                    self.bind(name, Binding::Variable(Type::Any));
+                }
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(HashMap::new());
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                condition.iter().chain(increment).for_each(|expr| {
+                    self.expression(expr);
+                });
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Stmt::Return { keyword, value, .. } => {
+                let actual = value
+                    .as_ref()
+                    .map_or(Type::Nil, |value| self.expression(value));
+                let Some(function) = self.returns.last_mut() else {
+                    return;
+                };
+                function.returned.push(actual.clone());
+                let (name, declared) = (function.name.clone(), function.declared.clone());
+                if !self.accepts(&declared, &actual) {
+                    self.mismatch(
+                        keyword,
+                        format!("'{name}' should return {declared}, not {actual}."),
+                    );
+                }
+            }
+            _ => {
+                for expr in stmt.expressions() {
+                    self.expression(expr);
+                }
+                for child in stmt.children() {
+                    self.statement(child);
+                }
+            }
+        }
+    }
+
+    /// Checks the annotations and body of the function or method `stmt`,
+    /// giving its return type: as annotated, or when inferring, the type
+    /// its returns agree on. An initializer returns its instance, whatever
+    /// it's annotated with.
+    fn function(&mut self, stmt: &Stmt, initializer: bool) -> Type {
+        let Stmt::Function {
+            name,
+            params,
+            body,
+            signature,
+            ..
+        } = stmt
+        else {
+            return Type::Any;
+        };
+        let (types, returns) = match signature {
+            Some(signature) => (
+                signature
+                    .params
+                    .iter()
+                    .map(|param| param.as_ref().map_or(Type::Any, |ty| self.annotated(ty)))
+                    .collect(),
+                signature
+                    .returns
+                    .as_ref()
+                    .map_or(Type::Any, |ty| self.annotated(ty)),
+            ),
+            None => (vec![Type::Any; params.len()], Type::Any),
+        };
+
+        self.scopes.push(HashMap::new());
+        for (param, ty) in params.iter().zip(types) {
+            self.bind(param, Binding::Variable(ty));
+        }
+        let returns = if initializer { Type::Any } else { returns };
+        self.returns.push(Returning {
+            name: name.lexeme.clone(),
+            declared: returns.clone(),
+            returned: Vec::new(),
+        });
+        self.hoist(body);
+        for stmt in body {
+            self.statement(stmt);
+        }
+        let returned = self.returns.pop().map(|function| function.returned);
+        self.scopes.pop();
+
+        let annotated = signature.as_ref().is_some_and(|s| s.returns.is_some());
+        if !self.infer || annotated || initializer {
+            return returns;
+        }
+        let mut returned = returned.unwrap_or_default();
+        if !always_returns(body) {
+            returned.push(Type::Nil);
+        }
+        match returned.split_first() {
+            Some((first, rest)) if rest.iter().all(|ty| ty == first) => first.clone(),
+            _ => Type::Any,
+        }
+    }
+
+    /// The type of `expr`, checking what's in it along the way.
+    fn expression(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal(literal) => match literal {
+                Literal::Number(_) => Type::Number,
+                Literal::String(_) => Type::String,
+                Literal::Bytes(_) => Type::Bytes,
+                Literal::True | Literal::False => Type::Bool,
+                Literal::Nil => Type::Nil,
+            },
+            Expr::Grouping { ex } => self.expression(ex),
+            Expr::Unary { op, right } => {
+                let right = self.expression(right);
+                if self.infer && op.token_type == TokenType::Minus && !number(&right) {
+                    self.mismatch(
+                        op,
+                        format!("Operand of '-' must be a number, not {}.", article(&right)),
+                    );
+                }
+                match op.token_type {
+                    TokenType::Minus => Type::Number,
+                    _ => Type::Bool,
+                }
+            }
+            Expr::Binary { left, op, right } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                if self.infer {
+                    self.operands(op, &left, &right);
+                }
+                match op.token_type {
+                    TokenType::Minus | TokenType::Star | TokenType::Slash => Type::Number,
+                    TokenType::Plus if left == right && left != Type::Any => left,
+                    TokenType::Plus => Type::Any,
+                    _ => Type::Bool,
+                }
+            }
+            Expr::Logical { left, right, .. } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                if left == right {
+                    left
+                } else {
+                    Type::Any
+                }
+            }
+            Expr::Variable { name, .. } => match self.lookup(&name.lexeme) {
+                Some(Binding::Inferred(Type::Nil)) => Type::Any,
+                Some(Binding::Variable(ty) | Binding::Inferred(ty)) => ty,
+                Some(Binding::Function(_)) => Type::Function,
+                Some(Binding::Class(_)) | None => Type::Any,
+            },
+            Expr::Assign { name, value, .. } => {
+                let actual = self.expression(value);
+                match self.lookup(&name.lexeme) {
+                    Some(Binding::Variable(declared)) if !self.accepts(&declared, &actual) => {
+                        self.mismatch(
+                            name,
+                            format!(
+                                "Can't assign {} to '{}' of type {declared}.",
+                                article(&actual),
+                                name.lexeme
+                            ),
+                        );
+                    }
+                    Some(Binding::Inferred(held)) => {
+                        let now = match (&held, &actual) {
+                            (Type::Nil, _) => actual.clone(),
+                            (_, Type::Nil) => held,
+                            _ if self.accepts(&held, &actual) => held,
+                            _ => {
+                                self.mismatch(
+                                    name,
+                                    format!(
+                                        "Can't assign {} to '{}', which holds {}.",
+                                        article(&actual),
+                                        name.lexeme,
+                                        article(&held)
+                                    ),
+                                );
+                                Type::Any
+                            }
+                        };
+                        self.rebind(&name.lexeme, Binding::Inferred(now));
+                    }
+                    _ => {}
+                }
+                actual
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => self.call(callee, paren, arguments),
+            Expr::This { .. } => self.class.clone().map_or(Type::Any, Type::Instance),
+            Expr::List { elements, .. } => {
+                elements.iter().for_each(|element| {
+                    self.expression(element);
+                });
+                Type::List
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                Type::Map
+            }
+            Expr::Tuple { elements } => {
+                elements.iter().for_each(|element| {
+                    self.expression(element);
+                });
+                Type::Tuple
+            }
+            Expr::Set { object, value, .. } => {
+                self.expression(object);
+                self.expression(value)
+            }
+            Expr::Match { subject, arms, .. } => {
+                self.expression(subject);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    for name in arm.pattern.bindings() {
+                        self.bind(name, Binding::Variable(Type::Any));
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    self.expression(&arm.body);
+                    self.scopes.pop();
+                }
+                Type::Any
+            }
+            _ => {
+                for child in expr.children() {
+                    self.expression(child);
+                }
+                Type::Any
+            }
+        }
+    }
+
+    /// Checks the arguments of a call to a function, class or method whose
+    /// declaration is known, giving what it returns.
+    fn call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Type {
+        let arguments: Vec<Type> = arguments.iter().map(|arg| self.expression(arg)).collect();
+        let (function, returns) = match callee {
+            Expr::Variable { name, .. } => match self.lookup(&name.lexeme) {
+                Some(Binding::Function(function)) => {
+                    let returns = function.returns.clone();
+                    (Some((function, name)), returns)
+                }
+                Some(Binding::Class(class)) => (
+                    self.method(&class, "init").map(|init| (init, name)),
+                    Type::Instance(class),
+                ),
+                _ => {
+                    let ty = self.expression(callee);
+                    self.callable(paren, &ty);
+                    (None, Type::Any)
+                }
+            },
+            Expr::Get { object, name } => match self.expression(object) {
+                Type::Instance(class) => {
+                    let method = self.method(&class, &name.lexeme);
+                    let returns = method.as_ref().map_or(Type::Any, |m| m.returns.clone());
+                    (method.map(|method| (method, name)), returns)
+                }
+                _ => (None, Type::Any),
+            },
+            callee => {
+                let ty = self.expression(callee);
+                self.callable(paren, &ty);
+                (None, Type::Any)
+            }
+        };
+
+        if let Some((function, name)) = function {
+            for (i, (expected, actual)) in function.params.iter().zip(&arguments).enumerate() {
+                if !self.accepts(expected, actual) {
+                    self.mismatch(
+                        paren,
+                        format!(
+                            "Argument {} to '{}' should be {expected}, not {actual}.",
+                            i + 1,
+                            name.lexeme
+                        ),
+                    );
+                }
+            }
+        }
+        returns
+    }
+
+    /// Flags operands `op` can't take, as far as their types are known.
+    fn operands(&mut self, op: &Token, left: &Type, right: &Type) {
+        match op.token_type {
+            TokenType::Minus
+            | TokenType::Star
+            | TokenType::Slash
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                if let Some(operand) = [left, right].into_iter().find(|ty| !number(ty)) {
+                    self.mismatch(
+                        op,
+                        format!(
+                            "Operands of '{}' must be numbers, not {}.",
+                            op.lexeme,
+                            article(operand)
+                        ),
+                    );
+                }
+            }
+            TokenType::Plus
+                if known(left)
+                    && known(right)
+                    && (left != right || !matches!(left, Type::Number | Type::String)) =>
+            {
+                self.mismatch(
+                    op,
+                    format!("Can't add {} and {}.", article(left), article(right)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Flags calling a value of type `ty`, when inferring, if it plainly
+    /// isn't a function or class.
+    fn callable(&mut self, paren: &Token, ty: &Type) {
+        if self.infer && *ty != Type::Function && known(ty) {
+            self.mismatch(paren, format!("Can't call {}.", article(ty)));
+        }
+    }
+
+    /// `class`'s method `name`, or the one it inherits.
+    fn method(&self, class: &str, name: &str) -> Option<Rc<FunctionType>> {
+        let mut class = self.classes.get(class);
+        // A class can't inherit from itself, but a chain could still loop
+        // back if two classes share a name.
+        for _ in 0..self.classes.len() {
+            let current = class?;
+            if let Some(method) = current.methods.get(name) {
+                return Some(method.clone());
+            }
+            class = self.classes.get(current.superclass.as_deref()?);
+        }
+        None
+    }
+
+    fn declare_class(&mut self, name: &Token, superclass: Option<&Expr>, methods: &[Stmt]) {
+        let superclass = match superclass {
+            Some(Expr::Variable { name, .. }) => Some(name.lexeme.clone()),
+            _ => None,
+        };
+        // Bound first, so methods can name their own class.
+        self.bind(name, Binding::Class(name.lexeme.clone()));
+        let methods = methods
+            .iter()
+            .filter_map(|method| match method {
+                Stmt::Function {
+                    name,
+                    params,
+                    signature,
+                    ..
+                } => Some((
+                    name.lexeme.clone(),
+                    self.function_type(params.len(), signature.as_deref()),
+                )),
+                _ => None,
+            })
+            .collect();
+        self.classes.insert(
+            name.lexeme.clone(),
+            Class {
+                superclass,
+                methods,
+            },
+        );
+    }
+
+    fn function_type(&self, arity: usize, signature: Option<&Signature>) -> Rc<FunctionType> {
+        let Some(signature) = signature else {
+            return Rc::new(FunctionType {
+                params: vec![Type::Any; arity],
+                returns: Type::Any,
+            });
+        };
+        let params = signature
+            .params
+            .iter()
+            .map(|param| param.as_ref().and_then(|ty| self.resolve_type(ty)))
+            .map(|ty| ty.unwrap_or(Type::Any))
+            .collect();
+        let returns = signature
+            .returns
+            .as_ref()
+            .and_then(|ty| self.resolve_type(ty))
+            .unwrap_or(Type::Any);
+        Rc::new(FunctionType { params, returns })
+    }
+
+    /// The type an annotation names, if it names one.
+    fn resolve_type(&self, annotation: &Token) -> Option<Type> {
+        Some(match annotation.lexeme.as_str() {
+            "Any" => Type::Any,
+            "Nil" => Type::Nil,
+            "Bool" => Type::Bool,
+            "Number" => Type::Number,
+            "String" => Type::String,
+            "Bytes" => Type::Bytes,
+            "List" => Type::List,
+            "Map" => Type::Map,
+            "Tuple" => Type::Tuple,
+            "Function" => Type::Function,
+            class => match self.lookup(class)? {
+                Binding::Class(class) => Type::Instance(class),
+                _ => return None,
+            },
+        })
+    }
+
+    /// [`Checker::resolve_type`], reporting a name that isn't a type and
+    /// taking it as `Any`.
+    fn annotated(&mut self, annotation: &Token) -> Type {
+        self.resolve_type(annotation).unwrap_or_else(|| {
+            self.mismatch(annotation, format!("Unknown type '{}'.", annotation.lexeme));
+            Type::Any
+        })
+    }
+
+    /// Whether a value of type `actual` can go where `expected` is wanted.
+    fn accepts(&self, expected: &Type, actual: &Type) -> bool {
+        match (expected, actual) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Instance(expected), Type::Instance(actual)) => {
+                let mut class = Some(actual.as_str());
+                for _ in 0..=self.classes.len() {
+                    match class {
+                        Some(class) if class == expected => return true,
+                        Some(name) => {
+                            class = self
+                                .classes
+                                .get(name)
+                                .and_then(|class| class.superclass.as_deref());
+                        }
+                        None => return false,
+                    }
+                }
+                false
+            }
+            (expected, actual) => expected == actual,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    /// Replaces what the innermost `name` in scope is bound to.
+    fn rebind(&mut self, name: &str, binding: Binding) {
+        if let Some(bound) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+        {
+            *bound = binding;
+        }
+    }
+
+    fn bind(&mut self, name: &Token, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), binding);
+        }
+    }
+
+    fn mismatch(&mut self, token: &Token, message: String) {
+        self.mismatches.push(Mismatch {
+            line: token.line(),
+            message,
+        });
+    }
+}
+
+/// Whether anything is known about a value of type `ty`.
+fn known(ty: &Type) -> bool {
+    *ty != Type::Any
+}
+
+/// Whether a value of type `ty` could be a number.
+fn number(ty: &Type) -> bool {
+    !known(ty) || *ty == Type::Number
+}
+
+/// Whether running `statements` always ends in a `return`.
+fn always_returns(statements: &[Stmt]) -> bool {
+    match statements.last() {
+        Some(Stmt::Return { .. }) => true,
+        Some(Stmt::Block { statements, .. }) => always_returns(statements),
+        Some(Stmt::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        }) => {
+            always_returns(std::slice::from_ref(then_branch))
+                && always_returns(std::slice::from_ref(else_branch))
+        }
+        _ => false,
+    }
+}
+
+/// `a Number`, `an Instance`, for messages.
+fn article(ty: &Type) -> String {
+    let name = ty.to_string();
+    match name.chars().next() {
+        Some('A' | 'E' | 'I' | 'O' | 'U') => format!("an {name}"),
+        _ => format!("a {name}"),
+    }
+}
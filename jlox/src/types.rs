@@ -1,3 +1,244 @@
-pub type Number = f64;
+use std::{
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
 
-pub struct Value {}
+/// A Lox number. Scripts only have one numeric type, a double, but every
+/// place that stores, compares or prints one goes through this type.
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Number(pub f64);
+
+impl Number {
+    /// Largest difference [`Number::approx_eq`] still treats as equal.
+    pub const EPSILON: f64 = 1e-9;
+
+    /// The value as an integer, if it is a whole number that fits in an
+    /// `i64` exactly.
+    pub fn as_int(self) -> Option<i64> {
+        const LIMIT: f64 = 9_007_199_254_740_992.0; // 2^53
+        (self.0.fract() == 0.0 && self.0.abs() <= LIMIT).then_some(self.0 as i64)
+    }
+
+    /// Equality within [`Number::EPSILON`], relative for large values.
+    pub fn approx_eq(self, other: Self) -> bool {
+        let scale = self.0.abs().max(other.0.abs()).max(1.0);
+        self == other || (self.0 - other.0).abs() <= Self::EPSILON * scale
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.0.is_nan()
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.0.is_finite()
+    }
+
+    pub fn is_infinite(self) -> bool {
+        self.0.is_infinite()
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Number> for f64 {
+    fn from(value: Number) -> Self {
+        value.0
+    }
+}
+
+/// Debugs as the bare `f64`, so `Object::Number` still shows as `Number(1.0)`.
+impl std::fmt::Debug for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// Numbers print in the shortest form that scans back to the same double,
+/// found with the Ryū algorithm: `0.1 + 0.2` prints `0.30000000000000004`.
+/// Magnitudes from `1e16` up and below `1e-5` use an exponent, as in `1e21`
+/// and `2.5e-7`; whole numbers below that print without a fractional part,
+/// e.g. `3` rather than `3.0`. NaN and the infinities print as `NaN`, `inf`
+/// and `-inf`. The output never depends on the system's locale.
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.0.is_finite() {
+            return write!(f, "{}", self.0);
+        }
+
+        let mut buffer = ryu::Buffer::new();
+        let text = buffer.format_finite(self.0);
+        f.write_str(text.strip_suffix(".0").unwrap_or(text))
+    }
+}
+
+macro_rules! number_op {
+    ($($trait:ident $method:ident $op:tt),*) => {
+        $(impl $trait for Number {
+            type Output = Number;
+
+            fn $method(self, rhs: Self) -> Self {
+                Self(self.0 $op rhs.0)
+            }
+        })*
+    };
+}
+
+number_op!(Add add +, Sub sub -, Mul mul *, Div div /);
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+/// An owned copy of a runtime value, detached from the interpreter's shared
+/// objects so embedders and tests can keep, clone and compare it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    /// A function, method or native. Natives have no name.
+    Function {
+        name: Option<String>,
+        arity: usize,
+    },
+    Class {
+        name: String,
+    },
+    Instance {
+        class: String,
+    },
+    /// A Rust value exposed to scripts, by its type or class name.
+    Host {
+        name: String,
+    },
+    Tuple(Vec<Value>),
+    List(Vec<Value>),
+    /// A frozen map's entries, in insertion order.
+    Map(Vec<(Value, Value)>),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nil => write!(f, "nil"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::String(s) => write!(f, "{s}"),
+            Self::Bytes(bytes) => write!(f, "b\"{}\"", crate::bytes::escape(bytes)),
+            Self::Function {
+                name: Some(name), ..
+            } => write!(f, "<fn {name}>"),
+            Self::Function { name: None, .. } => write!(f, "<native fn>"),
+            Self::Class { name } => write!(f, "{name}"),
+            Self::Instance { class } => write!(f, "{class} instance"),
+            Self::Host { name } => write!(f, "<{name}>"),
+            Self::Tuple(elements) => crate::object::write_tuple(f, elements),
+            Self::List(elements) => crate::object::write_list(f, elements),
+            Self::Map(entries) => crate::object::write_map(f, entries.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+// Shared ownership of runtime values. By default the interpreter uses `Rc`
+// and `RefCell`; with the `sync` feature these become `Arc` and `RwLock` so
+// an interpreter can be moved to (and shared with) other threads.
+
+/// A reference-counted pointer to a runtime value.
+#[cfg(not(feature = "sync"))]
+pub type Gc<T> = std::rc::Rc<T>;
+
+/// A reference-counted pointer to a runtime value.
+#[cfg(feature = "sync")]
+pub type Gc<T> = std::sync::Arc<T>;
+
+/// A [`Gc`] that doesn't keep its value alive.
+#[cfg(not(feature = "sync"))]
+pub type GcWeak<T> = std::rc::Weak<T>;
+
+/// A [`Gc`] that doesn't keep its value alive.
+#[cfg(feature = "sync")]
+pub type GcWeak<T> = std::sync::Weak<T>;
+
+/// Interior mutability for values behind a [`Gc`].
+#[cfg(not(feature = "sync"))]
+pub type GcCell<T> = std::cell::RefCell<T>;
+
+/// Interior mutability for values behind a [`Gc`], with the same
+/// `borrow`/`borrow_mut` interface as `RefCell`.
+#[cfg(feature = "sync")]
+#[derive(Debug, Default)]
+pub struct GcCell<T>(std::sync::RwLock<T>);
+
+#[cfg(feature = "sync")]
+impl<T> GcCell<T> {
+    pub fn new(value: T) -> Self {
+        Self(std::sync::RwLock::new(value))
+    }
+
+    pub fn borrow(&self) -> std::sync::RwLockReadGuard<'_, T> {
+        self.0.read().expect("GcCell lock poisoned.")
+    }
+
+    pub fn borrow_mut(&self) -> std::sync::RwLockWriteGuard<'_, T> {
+        self.0.write().expect("GcCell lock poisoned.")
+    }
+
+    pub fn try_borrow(&self) -> std::sync::TryLockResult<std::sync::RwLockReadGuard<'_, T>> {
+        self.0.try_read()
+    }
+
+    pub fn try_borrow_mut(&self) -> std::sync::TryLockResult<std::sync::RwLockWriteGuard<'_, T>> {
+        self.0.try_write()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0.into_inner().expect("GcCell lock poisoned.")
+    }
+}
+
+/// Bound on anything stored inside runtime values (natives, host data).
+/// It only requires `Send + Sync` with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSync {}
+
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Bound on anything stored inside runtime values (natives, host data).
+/// It only requires `Send + Sync` with the `sync` feature.
+#[cfg(feature = "sync")]
+pub trait MaybeSync: Send + Sync {}
+
+#[cfg(feature = "sync")]
+impl<T: ?Sized + Send + Sync> MaybeSync for T {}
+
+/// The type-erased value wrapped by host data.
+#[cfg(not(feature = "sync"))]
+pub type AnyValue = dyn std::any::Any;
+
+/// The type-erased value wrapped by host data.
+#[cfg(feature = "sync")]
+pub type AnyValue = dyn std::any::Any + Send + Sync;
+
+/// Declares a boxed closure type that is `Send + Sync` when the interpreter
+/// is.
+macro_rules! host_fn {
+    ($name:ident = $($signature:tt)*) => {
+        #[cfg(not(feature = "sync"))]
+        type $name = Box<dyn $($signature)*>;
+        #[cfg(feature = "sync")]
+        type $name = Box<dyn $($signature)* + Send + Sync>;
+    };
+}
+
+pub(crate) use host_fn;
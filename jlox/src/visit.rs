@@ -0,0 +1,218 @@
+//! [`ValueVisitor`]: one walk over a value and everything it holds, for
+//! host tools that need to see a whole object graph, like serializers,
+//! deep comparisons and tracers.
+//!
+//! [`Object::visit`] reaches list, tuple and map elements, instances'
+//! classes and fields, classes' superclasses and methods and the variables
+//! Lox functions capture, calling the visitor on
+//! the way into and out of each value. Instances, functions, classes and
+//! collections are entered only the first time they are reached; after
+//! that, including when a value holds itself, the visitor is told it was
+//! reached again instead, so the walk always ends.
+//!
+//! A function's captures are the variables of the scopes it closes over,
+//! innermost first, stopping short of the globals. Host values are leaves.
+//!
+//! ```
+//! use jlox::{object::Object, types::Gc, visit::{Edge, ValueVisitor}};
+//!
+//! struct Count(usize);
+//!
+//! impl ValueVisitor for Count {
+//!     fn enter(&mut self, _edge: Edge<'_>, _value: &Object) -> bool {
+//!         self.0 += 1;
+//!         true
+//!     }
+//! }
+//!
+//! let list = Object::List(vec![Gc::new(Object::Nil), Gc::new(Object::Bool(true))]);
+//! let mut count = Count(0);
+//! list.visit(&mut count);
+//! assert_eq!(count.0, 3);
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{
+    environment::Environment,
+    functions::Callable,
+    interpreter::Error,
+    object::Object,
+    types::{Gc, GcCell},
+};
+
+/// How a value was reached from the one holding it.
+#[derive(Debug, Clone, Copy)]
+pub enum Edge<'a> {
+    /// The value the walk started from.
+    Root,
+    /// An element of a list or tuple, by index.
+    Element(usize),
+    /// A key of a map.
+    Key,
+    /// The value of a map entry, with its key.
+    Entry(&'a Object),
+    /// The class of an instance.
+    Class,
+    /// A field of an instance.
+    Field(&'a str),
+    /// The superclass of a class.
+    Superclass,
+    /// A method of a class, as a function.
+    Method(&'a str),
+    /// A variable a function captures.
+    Capture(&'a str),
+}
+
+/// Called by [`Object::visit`] for every value it reaches. Each method
+/// has a default doing nothing, so a visitor implements what it needs.
+pub trait ValueVisitor {
+    /// `value` is reached through `edge` for the first time. Returning
+    /// `false` skips what it holds, and the matching [`Self::leave`].
+    fn enter(&mut self, edge: Edge<'_>, value: &Object) -> bool {
+        let _ = (edge, value);
+        true
+    }
+
+    /// Everything `value` holds has been visited.
+    fn leave(&mut self, value: &Object) {
+        let _ = value;
+    }
+
+    /// `value` was already entered and is reached again through `edge`:
+    /// it's shared, or it holds itself if it hasn't been left yet.
+    fn revisit(&mut self, edge: Edge<'_>, value: &Object) {
+        let _ = (edge, value);
+    }
+}
+
+impl Object {
+    /// Walks this value and everything it holds with `visitor`, each value
+    /// before what it holds.
+    pub fn visit<V: ValueVisitor + ?Sized>(&self, visitor: &mut V) {
+        Walk {
+            visitor,
+            entered: HashSet::new(),
+            methods: Vec::new(),
+        }
+        .value(Edge::Root, self);
+    }
+}
+
+struct Walk<'v, V: ?Sized> {
+    visitor: &'v mut V,
+    /// The identities of the values entered so far.
+    entered: HashSet<usize>,
+    /// The methods made into functions to be visited, kept for the rest of
+    /// the walk so nothing else gets their addresses.
+    methods: Vec<Gc<Object>>,
+}
+
+impl<V: ValueVisitor + ?Sized> Walk<'_, V> {
+    fn value(&mut self, edge: Edge<'_>, value: &Object) {
+        if let Some(identity) = identity(value) {
+            if !self.entered.insert(identity) {
+                self.visitor.revisit(edge, value);
+                return;
+            }
+        }
+        if !self.visitor.enter(edge, value) {
+            return;
+        }
+
+        match value {
+            Object::List(elements) | Object::Tuple(elements) => {
+                for (i, element) in elements.iter().enumerate() {
+                    self.value(Edge::Element(i), element);
+                }
+            }
+            Object::Map(map) => {
+                for (key, entry) in map.iter() {
+                    self.value(Edge::Key, key);
+                    self.value(Edge::Entry(key), entry);
+                }
+            }
+            Object::Instance(instance) => {
+                let class = Object::Class(instance.borrow().class().clone());
+                self.value(Edge::Class, &class);
+                // Copied out, so the visitor can look at the instance.
+                let mut fields: Vec<(String, Gc<Object>)> = instance
+                    .borrow()
+                    .fields()
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                for (name, field) in &fields {
+                    self.value(Edge::Field(name), field);
+                }
+            }
+            Object::Class(klass) => {
+                let superclass = klass.borrow().superclass().cloned();
+                if let Some(superclass) = superclass {
+                    self.value(Edge::Superclass, &Object::Class(superclass));
+                }
+                let mut methods: Vec<(String, Gc<dyn Callable<E = Error>>)> = klass
+                    .borrow()
+                    .methods()
+                    .iter()
+                    .map(|(name, method)| (name.clone(), Gc::new(method.clone()) as _))
+                    .collect();
+                methods.sort_by(|(a, _), (b, _)| a.cmp(b));
+                for (name, method) in methods {
+                    let method = Gc::new(Object::Function(method));
+                    self.value(Edge::Method(&name), &method);
+                    self.methods.push(method);
+                }
+            }
+            Object::Function(function) => {
+                if let Some(function) = function.as_lox_function() {
+                    for (name, captured) in captures(function.closure()) {
+                        self.value(Edge::Capture(&name), &captured);
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.visitor.leave(value);
+    }
+}
+
+/// What tells shared values apart: the address of the state they share.
+/// Plain values have none and are visited wherever they are reached.
+fn identity(value: &Object) -> Option<usize> {
+    match value {
+        Object::Instance(instance) => Some(Gc::as_ptr(instance) as usize),
+        Object::Class(klass) => Some(Gc::as_ptr(klass) as usize),
+        Object::Function(function) => Some(Gc::as_ptr(function) as *const () as usize),
+        Object::List(_) | Object::Tuple(_) | Object::Map(_) => {
+            Some(value as *const Object as usize)
+        }
+        _ => None,
+    }
+}
+
+/// The variables of the scopes from `closure` out to the globals, the
+/// innermost of any that share a name, sorted within each scope.
+fn captures(closure: &Gc<GcCell<Environment>>) -> Vec<(String, Gc<Object>)> {
+    let mut captures = Vec::new();
+    let mut names = HashSet::new();
+    let mut scope = Some(closure.clone());
+    while let Some(current) = scope {
+        let current = current.borrow();
+        // The global scope is the only one without an enclosing scope.
+        if current.enclosing.is_none() {
+            break;
+        }
+        let mut values: Vec<(String, Gc<Object>)> = current
+            .values
+            .iter()
+            .filter(|(name, _)| names.insert((*name).clone()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        captures.extend(values);
+        scope = current.enclosing.clone();
+    }
+    captures
+}
@@ -0,0 +1,309 @@
+//! `jlox ast`: the parse tree of a script drawn as a Graphviz or D2 graph,
+//! or as an HTML page of collapsible nodes.
+//!
+//! Each node is labeled with the head of its [`Display`] dump, `+` for
+//! `(+ a b)` or `fun area (w h)` for a function, and carries the whole dump
+//! as a tooltip. Edges into the branches of an `if`, the clauses of a `for`
+//! and the arms of a `match` are labeled with their role.
+//!
+//! ```
+//! use jlox::{parse_source, visualize::Tree};
+//!
+//! let tree = Tree::build(&parse_source("print 1 + 2;").unwrap());
+//! let labels: Vec<_> = tree.nodes.iter().map(|node| node.label.as_str()).collect();
+//! assert_eq!(labels, ["print", "+", "1", "2"]);
+//! ```
+//!
+//! [`Display`]: std::fmt::Display
+
+use crate::{
+    ast::{Expr, Stmt},
+    docs::escape,
+};
+
+/// One statement or expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub label: String,
+    /// The node's whole dump.
+    pub detail: String,
+    /// The line of a statement.
+    pub line: Option<usize>,
+    /// Indices of the children in [`Tree::nodes`], each with the label of
+    /// the edge to it, in source order.
+    pub children: Vec<(Option<String>, usize)>,
+}
+
+/// A parse tree, its nodes numbered in depth-first order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+    /// The top-level statements.
+    pub roots: Vec<usize>,
+}
+
+enum Child<'a> {
+    Stmt(&'a Stmt),
+    Expr(&'a Expr),
+}
+
+impl Tree {
+    pub fn build(statements: &[Stmt]) -> Self {
+        let mut tree = Self::default();
+        for stmt in statements {
+            let root = tree.add(Child::Stmt(stmt));
+            tree.roots.push(root);
+        }
+        tree
+    }
+
+    fn add(&mut self, node: Child<'_>) -> usize {
+        let (label, detail, line, children) = match node {
+            Child::Stmt(stmt) => (
+                stmt_label(stmt),
+                stmt.to_string(),
+                Some(stmt.boundary().line),
+                stmt_children(stmt),
+            ),
+            Child::Expr(expr) => (
+                expr_label(expr),
+                expr.to_string(),
+                None,
+                expr_children(expr),
+            ),
+        };
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            label,
+            detail,
+            line,
+            children: Vec::new(),
+        });
+        let children = children
+            .into_iter()
+            .map(|(role, child)| (role, self.add(child)))
+            .collect();
+        self.nodes[index].children = children;
+        index
+    }
+
+    /// The tree in Graphviz's DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph ast {\n  node [shape=box];\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            dot.push_str(&format!(
+                "  n{i} [label={}, tooltip={}];\n",
+                quote(&node.label),
+                quote(&node.detail)
+            ));
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            for (role, child) in &node.children {
+                match role {
+                    Some(role) => {
+                        dot.push_str(&format!("  n{i} -> n{child} [label={}];\n", quote(role)))
+                    }
+                    None => dot.push_str(&format!("  n{i} -> n{child};\n")),
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The tree in D2.
+    pub fn to_d2(&self) -> String {
+        let mut d2 = String::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            d2.push_str(&format!(
+                "n{i}: {} {{tooltip: {}}}\n",
+                quote(&node.label),
+                quote(&node.detail)
+            ));
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            for (role, child) in &node.children {
+                match role {
+                    Some(role) => d2.push_str(&format!("n{i} -> n{child}: {}\n", quote(role))),
+                    None => d2.push_str(&format!("n{i} -> n{child}\n")),
+                }
+            }
+        }
+        d2
+    }
+
+    /// A page showing the tree as nested lists whose nodes open and close
+    /// on a click. `title` heads the page.
+    pub fn to_html(&self, title: &str) -> String {
+        let title = escape(title);
+        let mut page = format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title>\n\
+             <style>ul {{ list-style: none; padding-left: 1.5em; }} em {{ color: gray; }}</style>\n\
+             </head>\n<body>\n<h1>{title}</h1>\n<ul>\n"
+        );
+        for &root in &self.roots {
+            self.html_node(&mut page, None, root);
+        }
+        page.push_str("</ul>\n</body>\n</html>\n");
+        page
+    }
+
+    fn html_node(&self, page: &mut String, role: Option<&str>, index: usize) {
+        let node = &self.nodes[index];
+        let mut summary = String::new();
+        if let Some(role) = role {
+            summary.push_str(&format!("<em>{}</em> ", escape(role)));
+        }
+        summary.push_str(&format!(
+            "<code title=\"{}\">{}</code>",
+            escape(&node.detail),
+            escape(&node.label)
+        ));
+        if let Some(line) = node.line {
+            summary.push_str(&format!(" <em>line {line}</em>"));
+        }
+
+        if node.children.is_empty() {
+            page.push_str(&format!("<li>{summary}</li>\n"));
+            return;
+        }
+        page.push_str(&format!(
+            "<li><details open><summary>{summary}</summary>\n<ul>\n"
+        ));
+        for (role, child) in &node.children {
+            self.html_node(page, role.as_deref(), *child);
+        }
+        page.push_str("</ul>\n</details></li>\n");
+    }
+}
+
+/// `text` as a quoted string on one line, which DOT and D2 both read.
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+/// The head of the statement's dump, without its nested statements and
+/// expressions.
+fn stmt_label(stmt: &Stmt) -> String {
+    let names = |tokens: &[crate::token::Token]| {
+        let names: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        names.join(" ")
+    };
+
+    match stmt {
+        Stmt::Block { .. } => "block".to_owned(),
+        Stmt::Break { .. } => "break".to_owned(),
+        Stmt::Class { name, .. } => format!("class {}", name.lexeme),
+        Stmt::Continue { .. } => "continue".to_owned(),
+        Stmt::Destructure { names: vars, .. } => format!("var ({})", names(vars)),
+        Stmt::Expression { .. } => ";".to_owned(),
+        Stmt::For { .. } => "for".to_owned(),
+        Stmt::Function { name, params, .. } => {
+            format!("fun {} ({})", name.lexeme, names(params))
+        }
+        Stmt::If { .. } => "if".to_owned(),
+        Stmt::Import { path, .. } => format!("import \"{path}\""),
+        Stmt::Print { .. } => "print".to_owned(),
+        Stmt::Return { .. } => "return".to_owned(),
+        Stmt::Var { name, .. } => format!("var {}", name.lexeme),
+        Stmt::While { .. } => "while".to_owned(),
+    }
+}
+
+/// The head of the expression's dump, without its nested expressions.
+fn expr_label(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign { name, .. } => format!("= {}", name.lexeme),
+        Expr::Binary { op, .. } | Expr::Logical { op, .. } | Expr::Unary { op, .. } => {
+            op.lexeme.clone()
+        }
+        Expr::Call { .. } => "call".to_owned(),
+        Expr::Get { name, .. } => format!(". {}", name.lexeme),
+        Expr::Grouping { .. } => "group".to_owned(),
+        Expr::Index { .. } => "[]".to_owned(),
+        Expr::List { .. } => "list".to_owned(),
+        Expr::Map { .. } => "map".to_owned(),
+        Expr::Match { .. } => "match".to_owned(),
+        Expr::Set { name, .. } => format!(".= {}", name.lexeme),
+        Expr::SetIndex { .. } => "[]=".to_owned(),
+        Expr::Tuple { .. } => "tuple".to_owned(),
+        Expr::Literal(_) | Expr::Super { .. } | Expr::This { .. } | Expr::Variable { .. } => {
+            expr.to_string()
+        }
+    }
+}
+
+fn stmt_children(stmt: &Stmt) -> Vec<(Option<String>, Child<'_>)> {
+    let role = |role: &str| Some(role.to_owned());
+
+    match stmt {
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => [
+            (None, Child::Expr(condition)),
+            (role("then"), Child::Stmt(then_branch)),
+        ]
+        .into_iter()
+        .chain(else_branch.iter().map(|e| (role("else"), Child::Stmt(e))))
+        .collect(),
+        Stmt::For {
+            initializer,
+            condition,
+            increment,
+            body,
+            ..
+        } => initializer
+            .iter()
+            .map(|i| (role("init"), Child::Stmt(i)))
+            .chain(
+                condition
+                    .iter()
+                    .map(|c| (role("condition"), Child::Expr(c))),
+            )
+            .chain(
+                increment
+                    .iter()
+                    .map(|i| (role("increment"), Child::Expr(i))),
+            )
+            .chain([(None, Child::Stmt(body))])
+            .collect(),
+        _ => stmt
+            .expressions()
+            .into_iter()
+            .map(|expr| (None, Child::Expr(expr)))
+            .chain(
+                stmt.children()
+                    .into_iter()
+                    .map(|stmt| (None, Child::Stmt(stmt))),
+            )
+            .collect(),
+    }
+}
+
+fn expr_children(expr: &Expr) -> Vec<(Option<String>, Child<'_>)> {
+    match expr {
+        Expr::Match { subject, arms, .. } => {
+            let mut children = vec![(None, Child::Expr(subject))];
+            for arm in arms {
+                if let Some(guard) = &arm.guard {
+                    children.push((Some(format!("{} if", arm.pattern)), Child::Expr(guard)));
+                }
+                children.push((Some(format!("{} =>", arm.pattern)), Child::Expr(&arm.body)));
+            }
+            children
+        }
+        _ => expr
+            .children()
+            .into_iter()
+            .map(|expr| (None, Child::Expr(expr)))
+            .collect(),
+    }
+}
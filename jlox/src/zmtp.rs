@@ -0,0 +1,291 @@
+//! Just enough of ZMTP 3.0, the ZeroMQ wire protocol, for the Jupyter kernel:
+//! the NULL security mechanism over TCP and the socket types a kernel and its
+//! clients use. A bound [`Socket`] accepts any number of peers, each read on a
+//! thread of its own.
+//!
+//! Every message is a list of frames. A frame starts with a flags byte (bit 0:
+//! more frames follow, bit 1: the size takes 8 bytes instead of 1, bit 2: it
+//! is a command) and its size in big-endian.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+const MORE: u8 = 0x01;
+const LONG: u8 = 0x02;
+const COMMAND: u8 = 0x04;
+
+/// A message: its frames in order.
+pub type Multipart = Vec<Vec<u8>>;
+
+/// The ZeroMQ socket types, named in the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Router,
+    Dealer,
+    Pub,
+    Sub,
+    Rep,
+    Req,
+}
+
+impl SocketType {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Router => "ROUTER",
+            Self::Dealer => "DEALER",
+            Self::Pub => "PUB",
+            Self::Sub => "SUB",
+            Self::Rep => "REP",
+            Self::Req => "REQ",
+        }
+    }
+}
+
+fn protocol_error(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// One end of a TCP connection after the handshake.
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+    /// The identity the peer announced, empty if it didn't.
+    identity: Vec<u8>,
+}
+
+impl Connection {
+    /// Connects to a bound socket as a client of type `kind`.
+    pub fn connect(address: impl ToSocketAddrs, kind: SocketType) -> io::Result<Self> {
+        Self::handshake(TcpStream::connect(address)?, kind, false)
+    }
+
+    fn handshake(mut stream: TcpStream, kind: SocketType, as_server: bool) -> io::Result<Self> {
+        let mut greeting = [0; 64];
+        greeting[0] = 0xff;
+        greeting[9] = 0x7f;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        greeting[32] = as_server.into();
+        stream.write_all(&greeting)?;
+
+        let mut peer = [0; 64];
+        stream.read_exact(&mut peer)?;
+        if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 {
+            return Err(protocol_error("the peer doesn't speak ZMTP 3"));
+        }
+        if &peer[12..16] != b"NULL" {
+            return Err(protocol_error("the peer wants a security mechanism"));
+        }
+
+        let mut ready = b"\x05READY".to_vec();
+        property(&mut ready, "Socket-Type", kind.name().as_bytes());
+        let mut connection = Self {
+            stream,
+            identity: Vec::new(),
+        };
+        connection.write_frame(COMMAND, &ready)?;
+
+        let (_, ready) = connection.read_frame()?;
+        let ready = ready
+            .strip_prefix(b"\x05READY")
+            .ok_or_else(|| protocol_error("expected a READY command"))?;
+        connection.identity = properties(ready)?
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("identity"))
+            .map(|(_, value)| value)
+            .unwrap_or_default();
+        Ok(connection)
+    }
+
+    fn write_frame(&mut self, flags: u8, body: &[u8]) -> io::Result<()> {
+        match u8::try_from(body.len()) {
+            Ok(size) => self.stream.write_all(&[flags, size])?,
+            Err(_) => {
+                self.stream.write_all(&[flags | LONG])?;
+                self.stream.write_all(&(body.len() as u64).to_be_bytes())?;
+            }
+        }
+        self.stream.write_all(body)
+    }
+
+    fn read_frame(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut flags = [0];
+        self.stream.read_exact(&mut flags)?;
+        let size = if flags[0] & LONG == 0 {
+            let mut size = [0];
+            self.stream.read_exact(&mut size)?;
+            u64::from(size[0])
+        } else {
+            let mut size = [0; 8];
+            self.stream.read_exact(&mut size)?;
+            u64::from_be_bytes(size)
+        };
+
+        let mut body = Vec::new();
+        (&mut self.stream).take(size).read_to_end(&mut body)?;
+        if body.len() as u64 != size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok((flags[0], body))
+    }
+
+    pub fn send(&mut self, message: &[Vec<u8>]) -> io::Result<()> {
+        for (i, frame) in message.iter().enumerate() {
+            let flags = if i + 1 < message.len() { MORE } else { 0 };
+            self.write_frame(flags, frame)?;
+        }
+        self.stream.flush()
+    }
+
+    /// The next message. Commands in between, such as subscriptions, are
+    /// skipped.
+    pub fn recv(&mut self) -> io::Result<Multipart> {
+        let mut message = Vec::new();
+        loop {
+            let (flags, body) = self.read_frame()?;
+            if flags & COMMAND != 0 {
+                continue;
+            }
+            message.push(body);
+            if flags & MORE == 0 {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Makes [`Connection::recv`] fail after `timeout` without a message.
+    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            stream: self.stream.try_clone()?,
+            identity: self.identity.clone(),
+        })
+    }
+}
+
+/// Appends a READY property: a 1-byte name size, the name, a 4-byte value
+/// size and the value.
+fn property(body: &mut Vec<u8>, name: &str, value: &[u8]) {
+    body.push(name.len() as u8);
+    body.extend_from_slice(name.as_bytes());
+    body.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    body.extend_from_slice(value);
+}
+
+fn properties(mut body: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let truncated = || protocol_error("truncated READY command");
+    let mut properties = Vec::new();
+    while let Some((&size, rest)) = body.split_first() {
+        let (name, rest) = rest.split_at_checked(size.into()).ok_or_else(truncated)?;
+        let (size, rest) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+        let (value, rest) = rest
+            .split_at_checked(u32::from_be_bytes(*size) as usize)
+            .ok_or_else(truncated)?;
+        properties.push((String::from_utf8_lossy(name).into_owned(), value.to_vec()));
+        body = rest;
+    }
+    Ok(properties)
+}
+
+/// What a bound socket does with each message a peer sends: a ROUTER's
+/// start with the peer's identity, and a reply returned goes back to the
+/// same peer.
+pub type Handler = dyn Fn(Multipart) -> Option<Multipart> + Send + Sync;
+
+/// A socket listening for peers.
+#[derive(Clone)]
+pub struct Socket {
+    kind: SocketType,
+    port: u16,
+    peers: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl Socket {
+    /// Listens on `address`, calling `handler` with every message received.
+    pub fn bind(
+        address: impl ToSocketAddrs,
+        kind: SocketType,
+        handler: impl Fn(Multipart) -> Option<Multipart> + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let socket = Self {
+            kind,
+            port: listener.local_addr()?.port(),
+            peers: Arc::default(),
+        };
+
+        let handler: Arc<Handler> = Arc::new(handler);
+        let accepting = socket.clone();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { continue };
+                let socket = accepting.clone();
+                let handler = handler.clone();
+                thread::spawn(move || socket.serve(stream, id as u32, &*handler));
+            }
+        });
+        Ok(socket)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Reads a peer's messages until it goes away.
+    fn serve(&self, stream: TcpStream, id: u32, handler: &Handler) -> io::Result<()> {
+        let mut connection = Connection::handshake(stream, self.kind, true)?;
+        if connection.identity.is_empty() {
+            // Made up like ZeroMQ does: a zero byte, then a counter.
+            connection.identity = [0].into_iter().chain(id.to_be_bytes()).collect();
+        }
+        let identity = connection.identity.clone();
+        self.peers.lock().unwrap().push(connection.try_clone()?);
+
+        let result = (|| loop {
+            let mut message = connection.recv()?;
+            if self.kind == SocketType::Router {
+                message.insert(0, identity.clone());
+            }
+            if let Some(reply) = handler(message) {
+                connection.send(&reply)?;
+            }
+        })();
+
+        self.peers
+            .lock()
+            .unwrap()
+            .retain(|peer| peer.identity != identity);
+        result
+    }
+
+    /// Sends `message`: a ROUTER to the peer its first frame names, a PUB to
+    /// every peer and other types to the first one.
+    pub fn send(&self, mut message: Multipart) -> io::Result<()> {
+        let mut peers = self.peers.lock().unwrap();
+        match self.kind {
+            SocketType::Router => {
+                let identity = message.remove(0);
+                match peers.iter_mut().find(|peer| peer.identity == identity) {
+                    Some(peer) => peer.send(&message),
+                    // ZeroMQ drops messages for peers that went away.
+                    None => Ok(()),
+                }
+            }
+            SocketType::Pub => {
+                peers.retain_mut(|peer| peer.send(&message).is_ok());
+                Ok(())
+            }
+            _ => match peers.first_mut() {
+                Some(peer) => peer.send(&message),
+                None => Err(io::ErrorKind::NotConnected.into()),
+            },
+        }
+    }
+}
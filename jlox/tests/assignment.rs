@@ -0,0 +1,60 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn chained_assignment_is_right_associative() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Box {}
+         var o = Box();
+         var a; var b; var c;
+         a = b = c = 1;
+         o.x = o.y = 2;
+         var d = o.z = a = 3;
+         var e; var f;
+         { var local; e = local = o.w = f = 4; }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("b"), Some(Value::Number(1.0)));
+    assert_eq!(lox.get_global("c"), Some(Value::Number(1.0)));
+    assert_eq!(lox.eval_expression("o.x").unwrap(), Value::Number(2.0));
+    assert_eq!(lox.eval_expression("o.y").unwrap(), Value::Number(2.0));
+    assert_eq!(lox.get_global("a"), Some(Value::Number(3.0)));
+    assert_eq!(lox.get_global("d"), Some(Value::Number(3.0)));
+    assert_eq!(lox.eval_expression("o.z").unwrap(), Value::Number(3.0));
+    assert_eq!(lox.get_global("e"), Some(Value::Number(4.0)));
+    assert_eq!(lox.eval_expression("o.w").unwrap(), Value::Number(4.0));
+    assert_eq!(lox.get_global("f"), Some(Value::Number(4.0)));
+}
+
+#[test]
+fn targets_are_evaluated_left_to_right_before_the_value() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Box {}
+         var first = Box();
+         var second = Box();
+         var log = \"\";
+         fun note(name, value) { log = log + name; return value; }
+         note(\"a\", first).x = note(\"b\", second).y = note(\"c\", 5);",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("log"), Some(Value::String("abc".into())));
+    assert_eq!(lox.eval_expression("first.x").unwrap(), Value::Number(5.0));
+    assert_eq!(lox.eval_expression("second.y").unwrap(), Value::Number(5.0));
+}
+
+#[test]
+fn a_non_instance_target_fails_before_its_value_runs() {
+    let mut lox = Lox::new();
+    let result = lox.execute(
+        "var log = \"\";
+         fun note(value) { log = log + \"value\"; return value; }
+         var n = 1;
+         n.x = note(2);",
+    );
+
+    assert!(matches!(result, Err(LoxError::Runtime(_))));
+    assert_eq!(lox.get_global("log"), Some(Value::String(String::new())));
+}
@@ -0,0 +1,56 @@
+use jlox::{permissions::Capability, Lox, Value};
+
+#[test]
+fn bytes_literals_index_slice_and_decode() {
+    let mut lox = Lox::new();
+    lox.execute("var gif = b\"GIF89a\\x00\\xff\";").unwrap();
+
+    assert_eq!(lox.eval_expression("gif[6]").unwrap(), Value::Number(0.0));
+    assert_eq!(lox.eval_expression("gif[7]").unwrap(), Value::Number(255.0));
+    assert_eq!(
+        lox.eval_expression("decode(slice(gif, 0, 3))").unwrap(),
+        Value::String("GIF".into())
+    );
+    assert_eq!(
+        lox.eval_expression("slice(gif, 6)").unwrap().to_string(),
+        "b\"\\x00\\xff\""
+    );
+    assert_eq!(
+        lox.eval_expression("encode(\"café\", \"latin-1\")")
+            .unwrap(),
+        Value::Bytes(b"caf\xe9".to_vec())
+    );
+    assert_eq!(
+        lox.eval_expression("encode(\"café\", \"ascii\")")
+            .unwrap_err()
+            .to_string(),
+        "Error: encode: 'é' can't be encoded as ascii."
+    );
+    assert_eq!(
+        lox.eval_expression("decode(gif)").unwrap_err().to_string(),
+        "Error: decode: invalid utf-8 at byte 7."
+    );
+}
+
+#[test]
+fn files_are_read_and_written_as_bytes() {
+    let path = std::env::temp_dir().join("jlox-bytes-test.bin");
+    let path = path.to_str().unwrap().replace('\\', "/");
+
+    let mut lox = Lox::new();
+    lox.grant(Capability::Read);
+    let error = lox
+        .execute(&format!("writeBytes(\"{path}\", b\"\\x00\\x01\");"))
+        .unwrap_err();
+    assert!(error.to_string().contains("'write' capability"), "{error}");
+
+    lox.grant(Capability::Write);
+    lox.execute(&format!("writeBytes(\"{path}\", b\"\\x00\\x01\");"))
+        .unwrap();
+    assert_eq!(
+        lox.eval_expression(&format!("readBytes(\"{path}\")"))
+            .unwrap(),
+        Value::Bytes(vec![0, 1])
+    );
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,50 @@
+use jlox::{callgraph::CallGraph, parse_source};
+
+#[test]
+fn method_calls_follow_this_super_and_names() {
+    let source = "class Shape {
+  init() { this.reset(); }
+  reset() {}
+  area() { return 0; }
+}
+class Square > Shape {
+  init(side) { super.init(); this.side = side; }
+  area() { return this.side * this.side; }
+}
+fun total(shapes) {
+  fun one(shape) { return shape.area(); }
+  return one(shapes[0]);
+}
+total(#[Square(2)]);";
+
+    let graph = CallGraph::extract(&parse_source(source).unwrap());
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|(caller, callee)| format!("{caller} -> {callee}"))
+        .collect();
+    assert_eq!(
+        edges,
+        [
+            "Shape.init -> Shape.reset",
+            "Square.init -> Shape.init",
+            "one -> Shape.area",
+            "one -> Square.area",
+            "total -> one",
+            "<script> -> total",
+            "<script> -> Square.init",
+        ]
+    );
+    assert_eq!(
+        graph.functions,
+        [
+            "Shape.init",
+            "Shape.reset",
+            "Shape.area",
+            "Square.init",
+            "Square.area",
+            "total",
+            "one"
+        ]
+    );
+}
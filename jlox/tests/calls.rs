@@ -0,0 +1,90 @@
+use jlox::{Lox, LoxError};
+
+mod common;
+use common::runtime_error;
+
+#[test]
+fn arity_errors_name_the_callee_and_the_call_line() {
+    assert_eq!(
+        runtime_error(
+            &mut Lox::new(),
+            "fun distance(a, b) {\n  return a - b;\n}\n\ndistance(1, 2, 3);"
+        ),
+        "Expected 2 arguments but got 3 in call to 'distance' [line 5]"
+    );
+    assert_eq!(
+        runtime_error(
+            &mut Lox::new(),
+            "class Point { init(x, y) {} }\nvar p = Point(\n  1\n);"
+        ),
+        "Expected 2 arguments but got 1 in call to 'Point' [line 4]"
+    );
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "var now = clock;\nnow(1);"),
+        "Expected 0 arguments but got 1 in call to 'clock' [line 2]"
+    );
+}
+
+#[test]
+fn reused_call_scopes_start_empty_and_spare_closures() {
+    let mut lox = jlox::Lox::new();
+    lox.execute(
+        "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+fun make(start) { var count = start; fun get() { return count; } return get; }
+fun probe() { var seen = nil; return seen; }
+var keep = make(7);
+var total = fib(15) + keep();
+fib(10);
+var after = keep();
+var fresh = probe();",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("total"), Some(jlox::Value::Number(617.0)));
+    assert_eq!(lox.get_global("after"), Some(jlox::Value::Number(7.0)));
+    assert_eq!(lox.get_global("fresh"), Some(jlox::Value::Nil));
+}
+
+#[test]
+fn functions_that_capture_nothing_keep_parameters_out_of_scopes() {
+    let mut lox = jlox::Lox::new();
+    lox.execute(
+        "fun add(a, b) { if (a > 0) a = a - 1; return a + b; }
+fun sum(n) { if (n == 0) return 0; return add(n, sum(n - 1)); }
+fun outer(x) { var y = x * 2; return add(x, y) + x; }
+var total = sum(4);
+var mixed = outer(3);",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("total"), Some(jlox::Value::Number(6.0)));
+    assert_eq!(lox.get_global("mixed"), Some(jlox::Value::Number(11.0)));
+    // The globals and the call to `outer`, which declares a variable.
+    assert_eq!(lox.stats().environments, 2);
+}
+
+#[test]
+fn call_sites_see_globals_reassigned_or_redeclared_after_a_call() {
+    let mut lox = jlox::Lox::new();
+    lox.execute(
+        "fun one() { return 1; }
+fun two() { return 2; }
+var pick = one;
+fun call() { return pick(); }
+var first = call();
+pick = two;
+var second = call();",
+    )
+    .unwrap();
+    lox.execute("fun two() { return 3; } pick = two; var third = call();")
+        .unwrap();
+
+    assert_eq!(lox.get_global("first"), Some(jlox::Value::Number(1.0)));
+    assert_eq!(lox.get_global("second"), Some(jlox::Value::Number(2.0)));
+    assert_eq!(lox.get_global("third"), Some(jlox::Value::Number(3.0)));
+    lox.execute("pick = nil;").unwrap();
+    assert!(matches!(
+        lox.eval_expression("call()"),
+        Err(LoxError::Runtime(_))
+    ));
+}
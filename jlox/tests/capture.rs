@@ -0,0 +1,41 @@
+use jlox::{limits::Limits, run_captured};
+
+#[test]
+fn captured_runs_report_output_and_status() {
+    let report = run_captured(
+        "var line = stdin.readLine();
+         while (line != nil) {
+           write(line + \"!\");
+           line = stdin.readLine();
+         }
+         stderr.write(\"done\");",
+        "a\nb\n",
+        Limits::default(),
+    );
+    assert_eq!(report.stdout, "a!b!");
+    assert_eq!(report.stderr, "done");
+    assert_eq!(report.exit_status, 0);
+    assert!(report.diagnostics.is_empty());
+    assert!(report.stats.statements > 0);
+
+    let report = run_captured("write(1);\nnil();", "", Limits::default());
+    assert_eq!(report.stdout, "1");
+    assert_eq!(report.exit_status, 70);
+    assert_eq!(report.diagnostics.len(), 1);
+
+    let report = run_captured("write(1);\nvar;", "", Limits::default());
+    assert_eq!(report.stdout, "");
+    assert_eq!(report.exit_status, 65);
+}
+
+#[test]
+fn captured_runs_collect_what_their_tasks_print() {
+    let report = run_captured(
+        "fun greet() { write(\"task\"); stderr.write(\"!\"); }\nspawn(greet);",
+        "",
+        Limits::default(),
+    );
+    assert_eq!(report.stdout, "task");
+    assert_eq!(report.stderr, "!");
+    assert_eq!(report.exit_status, 0);
+}
@@ -0,0 +1,46 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn closure_info_shows_what_a_function_uses_from_enclosing_scopes() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var step = 10;
+fun counter(start) {
+  var count = start;
+  var unused = \"no\";
+  fun bump(by) {
+    var count2 = count + by + step;
+    count = count2;
+    return count;
+  }
+  return bump;
+}
+var bump = counter(1);
+bump(1);
+class Box {
+  init(value) { this.value = value; }
+  get() { return this.value; }
+}
+var get = Box(7).get;",
+    )
+    .unwrap();
+
+    // `step` is a global, and `by` and `count2` are bump's own.
+    assert_eq!(
+        lox.eval_expression("closureInfo(bump)").unwrap(),
+        Value::Map(vec![(Value::String("count".into()), Value::Number(12.0))])
+    );
+    assert_eq!(
+        lox.eval_expression("closureInfo(counter)").unwrap(),
+        Value::Map(Vec::new())
+    );
+    assert!(matches!(
+        lox.eval_expression("closureInfo(get)").unwrap(),
+        Value::Map(entries) if matches!(&entries[..], [(Value::String(name), Value::Instance { .. })] if name == "this")
+    ));
+
+    assert!(matches!(
+        lox.eval_expression("closureInfo(clock)"),
+        Err(LoxError::Runtime(_))
+    ));
+}
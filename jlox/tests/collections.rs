@@ -0,0 +1,81 @@
+use jlox::{Lox, Value};
+
+mod common;
+use common::runtime_error;
+
+#[test]
+fn frozen_lists_and_maps_are_read_by_index() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var xs = #[1, \"two\", #[3],];
+         var m = #{\"a\": 1, (1, 2): \"pair\", xs: \"list\", \"a\": 2};",
+    )
+    .unwrap();
+
+    assert_eq!(lox.eval_expression("xs[2][0]").unwrap(), Value::Number(3.0));
+    assert_eq!(lox.eval_expression("m[\"a\"]").unwrap(), Value::Number(2.0));
+    assert_eq!(
+        lox.eval_expression("m[(1, 2)] + m[#[1, \"two\", #[3]]]")
+            .unwrap(),
+        Value::String("pairlist".into())
+    );
+    assert_eq!(
+        lox.eval_expression("len(m) + len(xs)").unwrap(),
+        Value::Number(6.0)
+    );
+    assert_eq!(
+        lox.get_global("m"),
+        Some(Value::Map(vec![
+            (Value::String("a".into()), Value::Number(2.0)),
+            (
+                Value::Tuple(vec![Value::Number(1.0), Value::Number(2.0)]),
+                Value::String("pair".into())
+            ),
+            (
+                Value::List(vec![
+                    Value::Number(1.0),
+                    Value::String("two".into()),
+                    Value::List(vec![Value::Number(3.0)]),
+                ]),
+                Value::String("list".into())
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn maps_compare_and_hash_regardless_of_order() {
+    let mut lox = Lox::new();
+    lox.execute("var m = #{#{\"x\": 1, \"y\": 2}: true};")
+        .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("m[#{\"y\": 2, \"x\": 1}]").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        lox.eval_expression("has(m, #{\"x\": 1}) or #[0] == #[-0]")
+            .unwrap(),
+        Value::Bool(true)
+    );
+}
+
+#[test]
+fn mutating_or_misusing_collections_is_a_runtime_error() {
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "var xs = #[1];\nxs[0] = 2;"),
+        "Can't modify #[1], lists, maps and bytes are frozen [line 2]."
+    );
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "print #[1][1];"),
+        "List index must be a whole number below 1, got 1 [line 1]."
+    );
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "print #{\"a\": 1}[\"b\"];"),
+        "Key b is not in the map [line 1]."
+    );
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "class A {}\nprint #{A: 1};"),
+        "A can't be a map key [line 2]."
+    );
+}
@@ -0,0 +1,9 @@
+use jlox::{Lox, LoxError};
+
+/// Runs `source` in `lox` and returns the runtime error it stops with.
+pub fn runtime_error(lox: &mut Lox, source: &str) -> String {
+    match lox.execute(source) {
+        Err(LoxError::Runtime(error)) => error.to_string(),
+        result => panic!("{source:?} gave {result:?}"),
+    }
+}
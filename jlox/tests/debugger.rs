@@ -0,0 +1,151 @@
+use std::{
+    io::{Cursor, Write},
+    sync::{Arc, Mutex},
+};
+
+use jlox::{ast::boundary_table, debugger::Debugger, parse_source, Lox};
+
+const SCRIPT: &str = "class Counter {
+  bump(n) {
+    return n + 1;
+  }
+}
+var c = Counter();
+var total = 0;
+for (var i = 0; i < 2; i = i + 1) {
+  total = c.bump(total);
+}
+print total;";
+
+/// Collects what the debugger reports, shared with the test.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What the debugger reported while debugging `script` with `commands`.
+fn transcript(script: &str, commands: &str) -> String {
+    let output = Capture::default();
+    let mut lox = Lox::new();
+    lox.redirect_output(jlox::streams::OutputStream::Stdout, std::io::sink());
+    lox.set_debug_hook(Debugger::new(
+        script,
+        Cursor::new(commands.to_owned()),
+        output.clone(),
+    ));
+    let _ = lox.execute(script);
+
+    let output = output.0.lock().unwrap().clone();
+    String::from_utf8(output).unwrap()
+}
+
+/// The lines execution stopped at when debugging [`SCRIPT`] with `commands`.
+fn stops(commands: &str) -> Vec<usize> {
+    transcript(SCRIPT, commands)
+        .split("[line ")
+        .skip(1)
+        .map(|stop| stop.split(']').next().unwrap().parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn boundary_table_lists_statements_in_source_order() {
+    let table = boundary_table(&parse_source(SCRIPT).unwrap());
+    let lines: Vec<_> = table.iter().map(|b| (b.line, b.end_line)).collect();
+    assert_eq!(
+        lines,
+        [
+            (1, 5),
+            (2, 4),
+            (3, 3),
+            (6, 6),
+            (7, 7),
+            (8, 10),
+            (8, 8),
+            (8, 10),
+            (9, 9),
+            (11, 11)
+        ]
+    );
+    assert!(table.windows(2).all(|pair| pair[0].id < pair[1].id));
+}
+
+#[test]
+fn stepping_follows_loops_and_calls() {
+    // Into the method, out of it to the loop header, then over the call.
+    assert_eq!(stops("b 9\nc\ns\nf\nn\nn\nn\n"), [1, 9, 3, 8, 9, 8, 11]);
+    // Over the call from the start, without a breakpoint.
+    assert_eq!(
+        stops("n\nn\nn\nn\nn\nn\nn\nn\n"),
+        [1, 6, 7, 8, 9, 8, 9, 8, 11]
+    );
+}
+
+#[test]
+fn breakpoints_take_conditions_and_watches_report_changes() {
+    let output = transcript(SCRIPT, "b 9 if i == 1\nc\np total\np n\nw total\nc\nc\n");
+    assert_eq!(
+        output,
+        "[line 1] class Counter {\n(debug) Breakpoint at line 9.\n\
+         (debug) [line 9] total = c.bump(total);\n(debug) 1\n\
+         (debug) Error: Environment error: UndefinedVariable { name: \"n\" }\n\
+         (debug) Watch 1: total = 1\n\
+         (debug) Watch 1: total changed from 1 to 2.\n\
+         [line 8] for (var i = 0; i < 2; i = i + 1) {\n(debug) "
+    );
+}
+
+#[test]
+fn runtime_errors_stop_for_a_post_mortem() {
+    let script = "fun half(n) {
+  var doubled = n * 2;
+  return doubled / nil;
+}
+var result = half(21);";
+
+    let output = transcript(script, "c\nbt\np doubled\nup\np n\nq\n");
+    assert_eq!(
+        output,
+        "[line 1] fun half(n) {\n\
+         (debug) Error: Cast conversion failed: nil is not a number\n\
+         [line 3] return doubled / nil;\n\
+         (debug) -> #0 half at line 3\n   #1 script at line 5\n\
+         (debug) 42\n\
+         (debug) [line 5] var result = half(21);\n\
+         (debug) Error: Environment error: UndefinedVariable { name: \"n\" }\n\
+         (debug) "
+    );
+}
+
+#[test]
+fn post_mortems_show_what_a_frame_captures() {
+    let script = "fun make(limit) {
+  fun check(n) {
+    return n / limit;
+  }
+  return check;
+}
+var check = make(nil);
+check(1);";
+
+    let output = transcript(script, "c\ncaptures\nup\ncaptures\nq\n");
+    assert_eq!(
+        output,
+        "[line 1] fun make(limit) {\n\
+         (debug) Error: Cast conversion failed: nil is not a number\n\
+         [line 3] return n / limit;\n\
+         (debug) limit = nil\n\
+         (debug) [line 8] check(1);\n\
+         (debug) No captured variables.\n\
+         (debug) "
+    );
+}
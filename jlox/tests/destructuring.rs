@@ -0,0 +1,70 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+mod common;
+use common::runtime_error;
+
+#[test]
+fn var_destructures_tuples_returned_from_functions() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun divmod(a, b) {
+           var q = 0;
+           while (a >= b) {
+             a = a - b;
+             q = q + 1;
+           }
+           return (q, a);
+         }
+         var (q, r) = divmod(17, 5);
+         fun swap() {
+           var (first, second) = (\"a\", \"b\");
+           var (x, y) = (second, first);
+           return x + y;
+         }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("q"), Some(Value::Number(3.0)));
+    assert_eq!(lox.get_global("r"), Some(Value::Number(2.0)));
+    assert_eq!(
+        lox.eval_expression("swap()").unwrap(),
+        Value::String("ba".into())
+    );
+}
+
+#[test]
+fn var_destructures_lists() {
+    let mut lox = Lox::new();
+    lox.execute("var (a, b) = #[1, 2];").unwrap();
+
+    assert_eq!(lox.get_global("a"), Some(Value::Number(1.0)));
+    assert_eq!(lox.get_global("b"), Some(Value::Number(2.0)));
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "var (a, b) = #[1];"),
+        "Expected 2 values to destructure but got 1 [line 1]."
+    );
+}
+
+#[test]
+fn destructuring_mismatches_are_runtime_errors() {
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "var t = (1, 2, 3);\nvar (a, b) = t;"),
+        "Expected 2 values to destructure but got 3 [line 2]."
+    );
+    assert_eq!(
+        runtime_error(&mut Lox::new(), "var (a, b) = 1;"),
+        "Only tuples and lists can be destructured, got 1 [line 1]."
+    );
+    for source in [
+        "var (a, a) = (1, 2);",
+        "{ var (a, a) = (1, 2); }",
+        "fun f() { var (a, b, a) = (1, 2, 3); }",
+    ] {
+        assert!(
+            matches!(run_source(source), Err(LoxError::Resolve(_))),
+            "{source}"
+        );
+    }
+    // Redeclaring a global in a later statement is still fine.
+    assert!(run_source("var a = 1;\nvar (a, b) = (2, 3);").is_ok());
+}
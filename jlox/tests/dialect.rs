@@ -0,0 +1,114 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use jlox::{dialect::Dialect, streams::OutputStream, token::TokenType, Lox, Value};
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn dialects_respell_keywords() {
+    let mut lox = Lox::new();
+    lox.set_dialect(
+        Dialect::default()
+            .with_keyword(TokenType::Fun, "fn")
+            .with_keyword(TokenType::Else, "otherwise"),
+    );
+
+    lox.execute(
+        "fn sign(n) { if (n < 0) return -1; otherwise return 1; }
+         var fun = sign(-5);",
+    )
+    .unwrap();
+    assert_eq!(lox.eval_expression("fun").unwrap(), Value::Number(-1.0));
+
+    // The standard spelling is an identifier now, so this is a call.
+    assert!(lox.execute("fun f() {}").is_err());
+}
+
+#[test]
+fn dropped_keywords_are_identifiers() {
+    let dialect = Dialect::default().without_keyword(TokenType::While);
+    assert_eq!(dialect.keyword("while"), None);
+    assert_eq!(dialect.keyword("for"), Some(TokenType::For));
+    assert_eq!(Dialect::standard().keyword("while"), Some(TokenType::While));
+}
+
+#[test]
+fn print_can_be_a_variadic_function() {
+    let output = Capture::default();
+    let mut lox = Lox::new();
+    lox.set_dialect(Dialect::default().with_print_function());
+    lox.redirect_output(OutputStream::Stdout, output.clone());
+
+    lox.execute("print(1, \"two\"); print(); var p = print; p(nil);")
+        .unwrap();
+    let output = output.0.lock().unwrap().clone();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Number(1.0) String(\"two\")\n\nNil\n"
+    );
+    let natives = lox.natives();
+    let print = natives.iter().find(|n| n.name() == "print").unwrap();
+    assert_eq!(print.signature(), "print(0+)  [streams]");
+}
+
+#[test]
+fn switching_back_removes_the_print_function() {
+    let output = Capture::default();
+    let mut lox = Lox::new();
+    lox.redirect_output(OutputStream::Stdout, output.clone());
+
+    lox.set_dialect(Dialect::default().with_print_function());
+    lox.execute("print(1);").unwrap();
+
+    lox.set_dialect(Dialect::default());
+    assert!(lox.natives().iter().all(|n| n.name() != "print"));
+    assert_eq!(lox.get_global("print"), None);
+    lox.execute("print 2;").unwrap();
+
+    lox.set_dialect(Dialect::default().with_print_function());
+    lox.execute("print(3);").unwrap();
+
+    let output = output.0.lock().unwrap().clone();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Number(1.0)\nNumber(2.0)\nNumber(3.0)\n"
+    );
+}
+
+#[test]
+fn line_breaks_can_end_statements() {
+    let mut lox = Lox::new();
+    lox.set_dialect(Dialect::default().with_semicolon_inference());
+
+    lox.execute(
+        "var total = 1
+         fun add(n) { total = total +
+             n }
+         add(2); add(3)
+         fun none() {
+           return
+         }",
+    )
+    .unwrap();
+    assert_eq!(lox.eval_expression("total").unwrap(), Value::Number(6.0));
+    assert_eq!(lox.eval_expression("none()").unwrap(), Value::Nil);
+
+    // Statements on one line still need their semicolons.
+    assert!(lox.execute("var a = 1 var b = 2").is_err());
+    assert!(Lox::new().execute("var c = 1\nvar d = 2").is_err());
+}
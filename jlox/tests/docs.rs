@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use jlox::{
+    docs::{self, Format, Item, Module},
+    parse_source,
+};
+
+#[test]
+fn collects_top_level_declarations_and_renders_markdown() {
+    let source = "/// Adds.\nfun add(a, b) {}\n{ /// Hidden.\n fun inner() {} }\n/// Shapes.\nclass Circle > Shape {\n  /// Area.\n  area() {}\n}\n";
+    let items = docs::collect(&parse_source(source).unwrap());
+
+    let [Item::Function(add), Item::Class(circle)] = items.as_slice() else {
+        panic!("unexpected items {items:?}");
+    };
+    assert_eq!(add.params, ["a", "b"]);
+    assert_eq!(circle.superclass.as_deref(), Some("Shape"));
+    assert_eq!(circle.methods[0].doc.as_deref(), Some("Area."));
+
+    let module = Module {
+        path: PathBuf::from("shapes.lox"),
+        items,
+    };
+    assert_eq!(
+        docs::render(&module, Format::Markdown),
+        "# shapes.lox\n\n## `fun add(a, b)`\n\nAdds.\n\n## `class Circle > Shape`\n\nShapes.\n\n### `Circle.area()`\n\nArea.\n"
+    );
+}
+
+#[test]
+fn parse_errors_name_the_file_and_line() {
+    let root = std::env::temp_dir().join(format!("jlox-docs-broken-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let path = root.join("v.lox");
+
+    std::fs::write(&path, "var ok = 1;\nvar v = ;\n").unwrap();
+    assert_eq!(
+        docs::scan(&root).unwrap_err().to_string(),
+        format!("{}:2: Expect expression. at ';'.", path.display())
+    );
+
+    // Like `jlox v.lox`, a string left open reports the line it runs to.
+    std::fs::write(&path, "print \"abc;\n").unwrap();
+    let error = docs::scan(&root).unwrap_err().to_string();
+    std::fs::remove_dir_all(&root).unwrap();
+    assert_eq!(
+        error,
+        format!("{}:2: Unterminated string. at '\"abc;\n'.", path.display())
+    );
+}
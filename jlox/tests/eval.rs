@@ -0,0 +1,53 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn expressions_see_the_session_globals() {
+    let mut lox = Lox::new();
+    lox.execute("var a = 20; fun add(x, y) { return x + y; } class Point {}")
+        .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("add(a, 22)").unwrap(),
+        Value::Number(42.0)
+    );
+    assert_eq!(lox.eval_expression("a > 1;").unwrap(), Value::Bool(true));
+    assert_eq!(
+        lox.eval_expression("\"a\" + \"b\"").unwrap(),
+        Value::String("ab".to_string())
+    );
+    assert_eq!(lox.eval_expression("nil").unwrap(), Value::Nil);
+    assert_eq!(
+        lox.eval_expression("add").unwrap(),
+        Value::Function {
+            name: Some("add".to_string()),
+            arity: 2
+        }
+    );
+    assert_eq!(
+        lox.eval_expression("Point()").unwrap(),
+        Value::Instance {
+            class: "Point".to_string()
+        }
+    );
+
+    // Assignments persist like any other evaluation.
+    lox.eval_expression("a = 1").unwrap();
+    assert_eq!(lox.eval_expression("a").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn only_a_single_expression_is_accepted() {
+    let mut lox = Lox::new();
+    assert!(matches!(
+        lox.eval_expression("1; 2"),
+        Err(LoxError::Parse(_))
+    ));
+    assert!(matches!(
+        lox.eval_expression("var a = 1;"),
+        Err(LoxError::Parse(_))
+    ));
+    assert!(matches!(
+        lox.eval_expression("missing"),
+        Err(LoxError::Runtime(_))
+    ));
+}
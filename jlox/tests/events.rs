@@ -0,0 +1,89 @@
+use std::thread;
+
+use jlox::object::Object;
+use jlox::{Lox, Value};
+
+fn lox_with_fetch() -> Lox {
+    let mut lox = Lox::new();
+    // Doubles its argument on another thread, or fails for negatives.
+    lox.define_async_native("fetch", 1, |_, arguments, completion| {
+        let Object::Number(n) = &*arguments[0] else {
+            drop(completion);
+            return Ok(());
+        };
+        let n = n.0;
+        thread::spawn(move || match n < 0.0 {
+            true => completion.reject(format!("{n} is negative")),
+            false => completion.resolve(n * 2.0),
+        });
+        Ok(())
+    });
+    lox.execute(
+        "var log = \"\";
+fun record(error, value) {
+  if (error != nil) log = log + \"error: \" + error + \";\";
+  else log = log + \"value;\";
+  last = value;
+}
+var last;",
+    )
+    .unwrap();
+    lox
+}
+
+#[test]
+fn deferred_calls_run_after_the_script_in_order() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var log = \"\";
+fun first() { log = log + \"first \"; defer(third); }
+fun second() { log = log + \"second \"; }
+fun third() { log = log + \"third\"; }
+defer(first);
+defer(second);
+log = log + \"script \";",
+    )
+    .unwrap();
+    lox.wait_for_events();
+
+    assert_eq!(
+        lox.get_global("log"),
+        Some(Value::String("script first second third".into()))
+    );
+    assert!(!lox.poll_events());
+}
+
+#[test]
+fn async_natives_call_back_with_a_value_or_an_error() {
+    let mut lox = lox_with_fetch();
+    lox.execute("fetch(21, record);").unwrap();
+    lox.wait_for_events();
+    assert_eq!(lox.get_global("last"), Some(Value::Number(42.0)));
+
+    lox.execute("fetch(-1, record);").unwrap();
+    lox.wait_for_events();
+    assert_eq!(lox.get_global("last"), Some(Value::Nil));
+
+    lox.execute("fetch(\"x\", record);").unwrap();
+    lox.wait_for_events();
+    assert_eq!(
+        lox.get_global("log"),
+        Some(Value::String(
+            "value;error: -1 is negative;error: native finished without a result.;".into()
+        ))
+    );
+}
+
+#[test]
+fn polling_reports_outstanding_natives_without_blocking() {
+    let mut lox = lox_with_fetch();
+    lox.execute("fetch(1, record); fetch(2, record);").unwrap();
+
+    while lox.poll_events() {
+        thread::yield_now();
+    }
+    assert_eq!(
+        lox.get_global("log"),
+        Some(Value::String("value;value;".into()))
+    );
+}
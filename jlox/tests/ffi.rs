@@ -0,0 +1,56 @@
+use std::ffi::{c_void, CStr};
+
+use jlox::ffi::*;
+
+/// `scale(x)`: `x` times the number `user_data` points to.
+unsafe extern "C" fn scale(
+    user_data: *mut c_void,
+    args: *const *const LoxValue,
+    count: usize,
+) -> *mut LoxValue {
+    let args = std::slice::from_raw_parts(args, count);
+    if lox_value_type(args[0]) != LoxType::Number {
+        return lox_value_error(c"expected a number".as_ptr());
+    }
+    lox_value_number(lox_value_as_number(args[0]) * *user_data.cast::<f64>())
+}
+
+#[test]
+fn c_callers_run_source_and_register_natives() {
+    let mut factor = 3.0;
+    unsafe {
+        let vm = lox_new();
+        let registered = lox_register_fn(vm, c"scale".as_ptr(), 1, scale, (&raw mut factor).cast());
+        assert_eq!(registered, LOX_OK);
+
+        assert_eq!(lox_run(vm, c"var x = scale(2) + 1;".as_ptr()), LOX_OK);
+        assert!(lox_get_error(vm).is_null());
+        let x = lox_get_global(vm, c"x".as_ptr());
+        assert_eq!(lox_value_as_number(x), 7.0);
+        lox_value_free(x);
+
+        let name = lox_value_string(c"lox".as_ptr());
+        lox_set_global(vm, c"name".as_ptr(), name);
+        lox_value_free(name);
+        assert_eq!(
+            lox_run(vm, c"var greeting = \"hi \" + name;".as_ptr()),
+            LOX_OK
+        );
+        let greeting = lox_get_global(vm, c"greeting".as_ptr());
+        assert_eq!(
+            CStr::from_ptr(lox_value_as_string(greeting)).to_str(),
+            Ok("hi lox")
+        );
+        lox_value_free(greeting);
+
+        assert_eq!(lox_run(vm, c"scale(\"a\");".as_ptr()), LOX_RUNTIME_ERROR);
+        assert_eq!(
+            CStr::from_ptr(lox_get_error(vm)).to_str(),
+            Ok("Error: scale: expected a number")
+        );
+        assert_eq!(lox_run(vm, c"var;".as_ptr()), LOX_COMPILE_ERROR);
+        assert!(lox_get_global(vm, c"missing".as_ptr()).is_null());
+
+        lox_free(vm);
+    }
+}
@@ -0,0 +1,116 @@
+use jlox::{
+    printer::format_source,
+    scanner::Scanner,
+    token::{TokenType, Trivia},
+};
+
+const MESSY: &str = "// Settings.
+var a=1;   // one
+
+
+/// Adds.
+fun add(x,y) { // why
+  return x+y;
+
+  // unreachable
+}
+class Point {
+  init(x) { this.x = x; }
+  // the rest
+}
+";
+
+#[test]
+fn formatting_keeps_comments_and_blank_lines() {
+    let formatted = format_source(MESSY).unwrap();
+    assert_eq!(
+        formatted,
+        "// Settings.
+var a = 1; // one
+
+/// Adds.
+fun add(x, y) {
+    // why
+    return x + y;
+
+    // unreachable
+}
+class Point {
+    init(x) {
+        this.x = x;
+    }
+    // the rest
+}
+"
+    );
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn scanner_attaches_trivia_to_the_next_token() {
+    let (_, trivia) = Scanner::new("var a; // note\n\n// next\nprint a;")
+        .with_trivia()
+        .find(|(token, _)| token.token_type == TokenType::Print)
+        .unwrap();
+    assert_eq!(
+        trivia,
+        [
+            Trivia::Comment {
+                text: "// note".to_owned(),
+                line: 1,
+                trailing: true,
+                after: 6,
+            },
+            Trivia::Blank { line: 3 },
+            Trivia::Comment {
+                text: "// next".to_owned(),
+                line: 3,
+                trailing: false,
+                after: 6,
+            },
+        ]
+    );
+
+    let (eof, trivia) = Scanner::new("print a;\n// last")
+        .with_trivia()
+        .last()
+        .unwrap();
+    assert_eq!(eof.token_type, TokenType::EOF);
+    assert_eq!(trivia.len(), 1);
+}
+
+#[test]
+fn trailing_comments_stay_after_the_statement_they_follow() {
+    let formatted = format_source(
+        "if (a) { print a; } else { print 2; } // end
+while (a) { a = a - 1; } // loop
+print a;
+",
+    )
+    .unwrap();
+    assert_eq!(
+        formatted,
+        "if (a) {
+    print a;
+} else {
+    print 2;
+} // end
+while (a) {
+    a = a - 1;
+} // loop
+print a;
+"
+    );
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn formatting_keeps_pipelines_as_written() {
+    let source = "print 1 |> f(2);
+print 1 + 2 |> g |> f(3) |> g();
+var x = (1 |> g) * 2 |> h;
+";
+    let formatted = format_source(source).unwrap();
+    assert_eq!(formatted, source);
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+}
@@ -0,0 +1,21 @@
+use jlox::{Lox, Value};
+
+#[test]
+fn hosts_pass_configuration_in_and_read_results_out() {
+    let mut lox = Lox::new();
+    lox.set_global("width", 6.0);
+    lox.set_global("name", "box");
+    lox.set_global("verbose", Some(true));
+
+    lox.execute("var area = width * 7; var label = name + \"!\"; width = nil;")
+        .unwrap();
+
+    assert_eq!(lox.get_global("area"), Some(Value::Number(42.0)));
+    assert_eq!(
+        lox.get_global("label"),
+        Some(Value::String("box!".to_string()))
+    );
+    assert_eq!(lox.get_global("verbose"), Some(Value::Bool(true)));
+    assert_eq!(lox.get_global("width"), Some(Value::Nil));
+    assert_eq!(lox.get_global("missing"), None);
+}
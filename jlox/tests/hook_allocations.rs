@@ -0,0 +1,28 @@
+//! A test binary of its own: the counting allocator sees the whole process.
+
+use jlox::{host::IntoLox, stats::CountingAllocator, Lox};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations(lox: &mut Lox, name: &str, arguments: usize) -> usize {
+    let before = lox.stats().allocations;
+    for _ in 0..1000 {
+        let arguments = (0..arguments).map(|_| 0.5.into_lox()).collect();
+        lox.call_if_exists(name, arguments).unwrap();
+    }
+    lox.stats().allocations - before
+}
+
+#[test]
+fn warm_hooks_allocate_only_their_arguments() {
+    let mut lox = Lox::new();
+    lox.execute("fun draw() {} fun update(dt) {}").unwrap();
+    allocations(&mut lox, "draw", 0);
+    allocations(&mut lox, "update", 1);
+
+    assert_eq!(allocations(&mut lox, "draw", 0), 0);
+    assert_eq!(allocations(&mut lox, "missing", 0), 0);
+    // The argument list and the number in it.
+    assert_eq!(allocations(&mut lox, "update", 1), 2 * 1000);
+}
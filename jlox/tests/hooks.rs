@@ -0,0 +1,26 @@
+use jlox::{host::IntoLox, Lox, LoxError, Value};
+
+#[test]
+fn hosts_call_optional_hooks_every_frame() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var elapsed = 0;
+         fun update(dt) {
+           elapsed = elapsed + dt;
+           return elapsed;
+         }",
+    )
+    .unwrap();
+
+    for _ in 0..999 {
+        lox.call_if_exists("update", vec![0.5.into_lox()]).unwrap();
+    }
+    assert_eq!(
+        lox.call_if_exists("update", vec![0.5.into_lox()]).unwrap(),
+        Some(Value::Number(500.0))
+    );
+    assert_eq!(lox.call_if_exists("draw", vec![]).unwrap(), None);
+
+    let error = lox.call_if_exists("update", vec![]).unwrap_err();
+    assert!(matches!(error, LoxError::Runtime(_)), "{error}");
+}
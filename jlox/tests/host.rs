@@ -0,0 +1,187 @@
+use jlox::host::{ClassBuilder, UserData};
+use jlox::object::Object;
+use jlox::types::Gc;
+use jlox::{native_fn, Lox, LoxError, Value};
+
+mod common;
+use common::runtime_error;
+
+struct Counter {
+    count: f64,
+}
+
+struct Account {
+    owner: String,
+    balance: f64,
+}
+
+fn account_class() -> Gc<jlox::host::HostClass> {
+    ClassBuilder::<Account>::new("Account")
+        .constructor(|(owner,): (String,)| Account {
+            owner,
+            balance: 0.0,
+        })
+        .getter("owner", |account| account.owner.clone())
+        .field(
+            "balance",
+            |account| account.balance,
+            |account, balance: f64| account.balance = balance,
+        )
+        .method("deposit", |account, (amount,): (f64,)| {
+            account.balance += amount;
+            account.balance
+        })
+        .try_method("withdraw", |account, (amount,): (f64,)| {
+            if amount > account.balance {
+                return Err(format!("only {} left", account.balance));
+            }
+            account.balance -= amount;
+            Ok(account.balance)
+        })
+        .build()
+}
+
+#[test]
+fn class_builder_exposes_constructor_properties_and_methods() {
+    let mut lox = Lox::new();
+    lox.register_class(account_class());
+
+    lox.execute(
+        "var account = Account(\"ada\");
+account.deposit(10);
+account.balance = account.balance + 5;
+var left = account.withdraw(3);
+var owner = account.owner;",
+    )
+    .unwrap();
+    assert_eq!(lox.get_global("left"), Some(Value::Number(12.0)));
+    assert_eq!(lox.get_global("owner"), Some(Value::String("ada".into())));
+    assert!(matches!(
+        lox.get_global("account"),
+        Some(Value::Host { .. })
+    ));
+
+    assert_eq!(
+        runtime_error(&mut lox, "account.withdraw(100);"),
+        "Account.withdraw: only 12 left"
+    );
+    assert_eq!(
+        runtime_error(&mut lox, "account.owner = \"bob\";"),
+        "Account.owner: property is read-only."
+    );
+    assert!(runtime_error(&mut lox, "account.balance = \"lots\";").contains("expected a number"));
+    assert!(runtime_error(&mut lox, "account.deposit(\"lots\");").contains("expected a number"));
+    assert!(runtime_error(&mut lox, "Account(1);").contains("expected a string"));
+    assert!(runtime_error(&mut lox, "account.missing;").contains("Undefined property 'missing'"));
+    assert!(runtime_error(&mut lox, "account.deposit(1, 2);").contains("Expected 1 arguments"));
+}
+
+#[test]
+fn classes_without_a_constructor_are_only_made_by_the_host() {
+    let class = ClassBuilder::<Account>::new("Vault")
+        .getter("owner", |account| account.owner.clone())
+        .build();
+    let mut lox = Lox::new();
+    lox.register_class(class.clone());
+    lox.set_global(
+        "vault",
+        class
+            .instantiate(Account {
+                owner: "host".into(),
+                balance: 1.0,
+            })
+            .unwrap(),
+    );
+
+    assert_eq!(
+        lox.eval_expression("vault.owner").unwrap(),
+        Value::String("host".into())
+    );
+    assert_eq!(
+        runtime_error(&mut lox, "Vault();"),
+        "Vault: class can't be constructed from Lox."
+    );
+}
+
+#[test]
+fn instances_must_hold_the_class_type() {
+    let error = account_class()
+        .instantiate(Counter { count: 1.0 })
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Account: instances hold host::Account, not host::Counter."
+    );
+
+    assert!(UserData::with_class(counter_class(), Counter { count: 1.0 }).is_ok());
+    let error = UserData::with_class(counter_class(), 1.0).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Counter: instances hold host::Counter, not f64."
+    );
+}
+
+fn counter_class() -> Gc<jlox::host::HostClass> {
+    ClassBuilder::<Counter>::new("Counter")
+        .constructor(|(count,): (f64,)| Counter { count })
+        .getter("count", |counter| counter.count)
+        .method("get", |counter, (): ()| counter.count)
+        .native_method("each", 1, |interpreter, counter, args| {
+            counter.count += 1.0;
+            interpreter.call_value(args[0].clone(), vec![])?;
+            Ok(Gc::new(Object::Nil))
+        })
+        .build()
+}
+
+#[test]
+fn reentering_an_object_from_its_method_is_a_runtime_error() {
+    let mut lox = Lox::new();
+    lox.register_class(counter_class());
+
+    lox.execute("var c = Counter(1); fun nothing() {} c.each(nothing);")
+        .unwrap();
+    assert_eq!(lox.eval_expression("c.get()").unwrap(), Value::Number(2.0));
+
+    for callback in ["fun f() { c.get(); }", "fun f() { c.count; }"] {
+        match lox.execute(&format!("{callback} c.each(f);")) {
+            Err(LoxError::Runtime(error)) => assert!(
+                error.to_string().contains("in use by one of its methods"),
+                "{error}"
+            ),
+            result => panic!("{callback} gave {result:?}"),
+        }
+    }
+
+    // The object is usable again once the method returns.
+    assert_eq!(lox.eval_expression("c.count").unwrap(), Value::Number(4.0));
+}
+
+struct Handle(u32);
+
+native_fn!(Open = "open", |_, (id: f64)| Ok(Object::UserData(Gc::new(
+    UserData::new(Handle(id as u32))
+))));
+
+native_fn!(HandleId = "handleId", |_, (handle: Gc<Object>)| {
+    UserData::expect::<Handle, _>(&handle, "handleId", |handle| f64::from(handle.0))
+        .map_err(|error| error.to_string())
+});
+
+#[test]
+fn opaque_user_data_only_goes_back_to_natives() {
+    let mut lox = Lox::new();
+    lox.define_native("open", Open);
+    lox.define_native("handleId", HandleId);
+
+    lox.execute("var handle = open(7);").unwrap();
+    assert_eq!(
+        lox.eval_expression("handleId(handle)").unwrap(),
+        Value::Number(7.0)
+    );
+    assert!(matches!(lox.get_global("handle"), Some(Value::Host { .. })));
+
+    assert!(runtime_error(&mut lox, "handle.id;").contains("Only instances have properties"));
+    assert!(runtime_error(&mut lox, "handle.id = 1;").contains("Only instances have fields"));
+    assert!(runtime_error(&mut lox, "handleId(1);").contains("expected"));
+}
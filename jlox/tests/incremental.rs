@@ -0,0 +1,67 @@
+use jlox::{incremental::ParseCache, parse_source};
+
+const PROGRAM: &str = "/// Adds.
+fun add(a, b) {
+    return a + b;
+}
+
+var total = 0;
+for (var i = 0; i < 3; i = i + 1) total = add(total, i);
+
+if (total > 2) {
+    print total;
+} else {
+    print \"small\";
+}
+
+class Point {
+    init(x) { this.x = x; }
+}
+print Point(1).x; // trailing comment
+";
+
+#[test]
+fn matches_a_full_parse() {
+    let mut cache = ParseCache::new();
+    assert_eq!(
+        cache.parse(PROGRAM).unwrap(),
+        parse_source(PROGRAM).unwrap()
+    );
+    assert_eq!(cache.reparsed(), 6);
+
+    let error = |source: &str| {
+        let full = parse_source(source).unwrap_err().to_string();
+        let incremental = ParseCache::new().parse(source).unwrap_err().to_string();
+        assert_eq!(incremental, full, "{source:?}");
+    };
+    error("var a = 1;\n\nprint a");
+    error("fun f() {\n}\nprint )");
+    error("{\nvar a = 1;\n");
+}
+
+#[test]
+fn only_edited_declarations_are_parsed_again() {
+    let mut cache = ParseCache::new();
+    cache.parse(PROGRAM).unwrap();
+
+    let edited = PROGRAM.replace("a + b", "a + b + 0");
+    assert_eq!(
+        cache.parse(&edited).unwrap(),
+        parse_source(&edited).unwrap()
+    );
+    assert_eq!(cache.reparsed(), 1);
+
+    // Declarations below inserted lines are reused at their new lines.
+    let shifted = format!("var x = 1;\n\n{edited}");
+    assert_eq!(
+        cache.parse(&shifted).unwrap(),
+        parse_source(&shifted).unwrap()
+    );
+    assert_eq!(cache.reparsed(), 1);
+
+    assert_eq!(
+        cache.parse(&shifted).unwrap(),
+        parse_source(&shifted).unwrap()
+    );
+    assert_eq!(cache.reparsed(), 0);
+}
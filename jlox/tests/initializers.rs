@@ -0,0 +1,58 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+#[test]
+fn returning_a_value_from_init_is_a_resolve_error() {
+    let result = run_source("class Point { init() { return 1; } }");
+    match result {
+        Err(LoxError::Resolve(error)) => assert!(
+            error
+                .to_string()
+                .ends_with("Can't return a value from an initializer."),
+            "{error}"
+        ),
+        result => panic!("expected a resolve error, got {result:?}"),
+    }
+}
+
+#[test]
+fn a_bare_return_in_init_still_yields_the_instance() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Point {
+           init(x) {
+             this.x = x;
+             if (x > 0) return;
+             this.x = 0;
+           }
+         }",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("Point(3).x").unwrap(),
+        Value::Number(3.0)
+    );
+    assert_eq!(
+        lox.eval_expression("Point(-1).x").unwrap(),
+        Value::Number(0.0)
+    );
+    assert_eq!(
+        lox.eval_expression("Point(3).init(5).x").unwrap(),
+        Value::Number(5.0)
+    );
+}
+
+#[test]
+fn classes_named_init_and_methods_named_like_their_class_are_ordinary() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class init { value() { return 1; } }
+         class Point { Point() { return 2; } }
+         var a = init().value();
+         var b = Point().Point();",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("a"), Some(Value::Number(1.0)));
+    assert_eq!(lox.get_global("b"), Some(Value::Number(2.0)));
+}
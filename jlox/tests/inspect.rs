@@ -0,0 +1,37 @@
+use jlox::{limits::Limits, run_captured};
+
+fn output(source: &str) -> String {
+    let report = run_captured(source, "", Limits::default());
+    assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+    report.stdout
+}
+
+#[test]
+fn inspect_indents_nested_values() {
+    assert_eq!(
+        output(
+            "class Point { init(x, y) { this.x = x; this.y = y; } }
+             write(inspect(#{\"origin\": Point(0, 0), \"tags\": #[\"a\", \"b\"], \"one\": (1,)}));"
+        ),
+        "#{\n  \"origin\": Point { x: 0, y: 0 },\n  \"tags\": #[\"a\", \"b\"],\n  \"one\": (1,),\n}"
+    );
+    assert_eq!(
+        output("write(inspect(#[#[#[1]], 2], 1));"),
+        "#[\n  #[...],\n  2,\n]"
+    );
+}
+
+#[test]
+fn inspect_stops_at_cycles() {
+    assert_eq!(
+        output(
+            "class Node { init(name) { this.name = name; } }
+             var a = Node(\"a\");
+             var b = Node(\"b\");
+             a.next = b;
+             b.next = a;
+             write(inspect(a));"
+        ),
+        "Node {\n  name: \"a\",\n  next: Node {\n    name: \"b\",\n    next: <circular>,\n  },\n}"
+    );
+}
@@ -0,0 +1,81 @@
+use jlox::{
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    types::{Gc, Number},
+    Lox, LoxError, Value,
+};
+
+#[test]
+fn reset_keeps_only_host_definitions() {
+    let mut lox = Lox::new();
+    lox.define_native("answer", Answer);
+    lox.execute("var a = 1; clock = nil; answer = nil;")
+        .unwrap();
+
+    lox.reset();
+
+    assert!(matches!(
+        lox.eval_expression("a"),
+        Err(LoxError::Runtime(_))
+    ));
+    assert!(matches!(
+        lox.eval_expression("clock").unwrap(),
+        Value::Function { arity: 0, .. }
+    ));
+    assert_eq!(
+        lox.eval_expression("answer()").unwrap(),
+        Value::Number(42.0)
+    );
+}
+
+#[test]
+fn reset_restores_globals_the_host_set() {
+    let mut lox = Lox::new();
+    lox.set_global("width", 6.0);
+    lox.execute("width = 1; var height = 2;").unwrap();
+
+    lox.reset();
+
+    assert_eq!(lox.get_global("width"), Some(Value::Number(6.0)));
+    assert_eq!(lox.get_global("height"), None);
+}
+
+#[test]
+fn isolated_runs_do_not_leak_declarations() {
+    let mut lox = Lox::new();
+    lox.execute("var counter = 0;").unwrap();
+
+    lox.run_isolated("var local = 5; fun bump() { counter = counter + local; } bump();")
+        .unwrap();
+    lox.run_isolated("var local = 1; counter = counter + local;")
+        .unwrap();
+
+    assert_eq!(lox.eval_expression("counter").unwrap(), Value::Number(6.0));
+    assert!(matches!(
+        lox.eval_expression("local"),
+        Err(LoxError::Runtime(_))
+    ));
+    assert!(matches!(
+        lox.eval_expression("bump"),
+        Err(LoxError::Runtime(_))
+    ));
+}
+
+struct Answer;
+
+impl Callable for Answer {
+    type E = Error;
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        Ok(Gc::new(Object::Number(Number(42.0))))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+}
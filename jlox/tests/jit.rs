@@ -0,0 +1,68 @@
+#![cfg(feature = "jit")]
+
+use jlox::{Lox, Value};
+
+const FUNCTIONS: &str = "
+fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+fun sum(n) {
+  var total = 0;
+  for (var i = 0; i < n; i = i + 1) {
+    if (i == 3) continue;
+    if (i > 50) break;
+    total = total + i;
+  }
+  return total;
+}
+fun nothing(n) { var x = n; }
+fun ratio(a, b) { return a / b; }
+";
+
+fn session(jit: bool) -> Lox {
+    let mut lox = Lox::new();
+    if jit {
+        lox.enable_jit().unwrap();
+    }
+    lox.execute(FUNCTIONS).unwrap();
+    lox
+}
+
+#[test]
+fn compiled_functions_return_what_the_interpreter_does() {
+    let mut interpreted = session(false);
+    let mut compiled = session(true);
+
+    // Enough calls to compile each function and then run the compiled code.
+    for _ in 0..200 {
+        for call in ["fib(12)", "sum(100)", "nothing(1)", "ratio(5, 2)"] {
+            assert_eq!(
+                compiled.eval_expression(call).unwrap(),
+                interpreted.eval_expression(call).unwrap()
+            );
+        }
+    }
+    assert_eq!(
+        compiled.eval_expression("sum(100)").unwrap(),
+        Value::Number(1272.0)
+    );
+    assert_eq!(compiled.eval_expression("nothing(1)").unwrap(), Value::Nil);
+}
+
+#[test]
+fn compiled_code_bails_to_the_interpreter_for_errors_and_other_values() {
+    let mut lox = session(true);
+    for _ in 0..200 {
+        lox.eval_expression("ratio(1, 2)").unwrap();
+    }
+
+    let err = lox.eval_expression("ratio(1, 0)").unwrap_err();
+    assert!(err.to_string().contains("Division by zero"), "{err}");
+    assert_eq!(
+        lox.eval_expression("ratio(\"a\", 2)")
+            .unwrap_err()
+            .to_string(),
+        session(false)
+            .eval_expression("ratio(\"a\", 2)")
+            .unwrap_err()
+            .to_string()
+    );
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "jupyter")]
+
+use std::{
+    net::TcpListener,
+    process::{Child, Command},
+    thread,
+    time::Duration,
+};
+
+use jlox::zmtp::{Connection, SocketType};
+use serde_json::{json, Value};
+
+/// A port nothing listens on right now.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn connect(port: u16, kind: SocketType) -> Connection {
+    for _ in 0..100 {
+        if let Ok(connection) = Connection::connect(("127.0.0.1", port), kind) {
+            connection
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            return connection;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("the kernel didn't listen on {port}");
+}
+
+/// Sends an unsigned request; the connection file has an empty key.
+fn request(shell: &mut Connection, msg_type: &str, content: Value) {
+    let header = json!({ "msg_id": msg_type, "session": "test", "msg_type": msg_type });
+    let parts = [header, json!({}), json!({}), content];
+    let mut message = vec![b"<IDS|MSG>".to_vec(), vec![]];
+    message.extend(parts.iter().map(|part| part.to_string().into_bytes()));
+    shell.send(&message).unwrap();
+}
+
+/// The message type and content of the next message, after the delimiter,
+/// signature and header.
+fn receive(connection: &mut Connection) -> (String, Value) {
+    let message = connection.recv().unwrap();
+    let at = message
+        .iter()
+        .position(|part| part == b"<IDS|MSG>")
+        .unwrap();
+    let header: Value = serde_json::from_slice(&message[at + 2]).unwrap();
+    let content = serde_json::from_slice(&message[at + 5]).unwrap();
+    (header["msg_type"].as_str().unwrap().to_string(), content)
+}
+
+/// IOPub messages up to the kernel going idle, without status messages.
+fn outputs(iopub: &mut Connection) -> Vec<(String, Value)> {
+    let mut outputs = Vec::new();
+    loop {
+        let (msg_type, content) = receive(iopub);
+        match (msg_type.as_str(), content["execution_state"].as_str()) {
+            ("status", Some("idle")) => return outputs,
+            ("status", _) => (),
+            _ => outputs.push((msg_type, content)),
+        }
+    }
+}
+
+struct Kernel(Child);
+
+impl Drop for Kernel {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+#[test]
+fn kernels_run_cells_in_one_session() {
+    let [shell_port, iopub_port, stdin_port, control_port, hb_port] = [(); 5].map(|()| free_port());
+    let path = std::env::temp_dir().join("jlox-kernel-connection.json");
+    let info = json!({
+        "transport": "tcp",
+        "ip": "127.0.0.1",
+        "shell_port": shell_port,
+        "iopub_port": iopub_port,
+        "stdin_port": stdin_port,
+        "control_port": control_port,
+        "hb_port": hb_port,
+        "key": "",
+        "signature_scheme": "hmac-sha256",
+    });
+    std::fs::write(&path, info.to_string()).unwrap();
+
+    let _kernel = Kernel(
+        Command::new(env!("CARGO_BIN_EXE_jlox"))
+            .args(["kernel", "-f"])
+            .arg(&path)
+            .spawn()
+            .unwrap(),
+    );
+    let mut iopub = connect(iopub_port, SocketType::Sub);
+    iopub.send(&[b"\x01".to_vec()]).unwrap();
+    let mut shell = connect(shell_port, SocketType::Dealer);
+
+    request(&mut shell, "kernel_info_request", json!({}));
+    let (reply, info) = receive(&mut shell);
+    assert_eq!(reply, "kernel_info_reply");
+    assert_eq!(info["language_info"]["name"], "lox");
+    outputs(&mut iopub);
+
+    request(
+        &mut shell,
+        "execute_request",
+        json!({ "code": "var x = 20;\nwrite(\"x is \");\nwrite(x);" }),
+    );
+    assert_eq!(receive(&mut shell).1["status"], "ok");
+    let texts: Vec<Value> = outputs(&mut iopub)
+        .into_iter()
+        .filter(|(msg_type, _)| msg_type == "stream")
+        .map(|(_, content)| content["text"].clone())
+        .collect();
+    assert_eq!(texts, ["x is ", "20"]);
+
+    request(&mut shell, "execute_request", json!({ "code": "x + 22" }));
+    assert_eq!(receive(&mut shell).1["execution_count"], 2);
+    let outputs = outputs(&mut iopub);
+    let (_, result) = outputs
+        .iter()
+        .find(|(msg_type, _)| msg_type == "execute_result")
+        .unwrap();
+    assert_eq!(result["data"]["text/plain"], "42");
+
+    request(&mut shell, "execute_request", json!({ "code": "y;" }));
+    let (_, reply) = receive(&mut shell);
+    assert_eq!(reply["status"], "error");
+    assert!(
+        reply["evalue"]
+            .as_str()
+            .unwrap()
+            .contains("UndefinedVariable"),
+        "{reply}"
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
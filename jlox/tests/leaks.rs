@@ -0,0 +1,50 @@
+use jlox::{leaks::Leak, Lox};
+
+fn leak(what: &str, line: usize) -> Leak {
+    Leak {
+        what: what.to_owned(),
+        line,
+    }
+}
+
+#[test]
+fn only_what_cycles_keep_alive_is_reported() {
+    let mut lox = Lox::new();
+    lox.enable_leak_check();
+    lox.execute(
+        "class Node {}
+var kept = Node();
+kept.self = kept;
+{
+  var lost = Node();
+  lost.self = lost;
+}
+fun make() {
+  var count = 0;
+  fun bump() { count = count + 1; return count; }
+  return bump;
+}
+var bump = make();
+make();
+fun plain(n) { return Node(); }
+plain(1);
+fun factory() {
+  class Local { get() { return 1; } }
+  return Local();
+}
+var local = factory();",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.leaks(),
+        [
+            leak("the scope of a call to 'make'", 14),
+            leak("a Node instance", 5),
+        ]
+    );
+    assert_eq!(
+        leak("a Node instance", 5).to_string(),
+        "Leak: a Node instance made at line 5 is kept alive only by a cycle."
+    );
+}
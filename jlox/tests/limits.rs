@@ -0,0 +1,177 @@
+use jlox::{limits::Limits, parser, Lox, LoxError};
+
+fn parse_error(lox: &mut Lox, source: &str) -> parser::Error {
+    match lox.execute(source) {
+        Err(LoxError::Parse(error)) => error,
+        result => panic!("{source:?} gave {result:?}"),
+    }
+}
+
+#[test]
+fn argument_limits_are_reported_at_the_first_extra_token() {
+    let mut lox = Lox::new();
+    let arguments = vec!["1"; 256].join(", ");
+    let error = parse_error(&mut lox, &format!("print 1;\nf({arguments});"));
+    assert!(matches!(
+        &error,
+        parser::Error::TooMany { token, what: "arguments", limit: 255 } if token.line() == 2
+    ));
+
+    lox.set_limits(Limits {
+        max_arguments: 2,
+        ..Limits::default()
+    });
+    lox.execute("fun pair(a, b) { return a + b; } pair(1, 2);")
+        .unwrap();
+    let error = parse_error(&mut lox, "fun triple(a, b, c) {}");
+    assert!(matches!(
+        &error,
+        parser::Error::TooMany { token, what: "parameters", limit: 2 } if token.lexeme == "c"
+    ));
+    assert!(matches!(
+        parse_error(&mut lox, "pair(1, 2, 3);"),
+        parser::Error::TooMany {
+            what: "arguments",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn runtime_limits_are_configurable() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_call_depth: 10,
+        max_string_length: 8,
+        ..Limits::default()
+    });
+
+    lox.execute("fun down(n) { if (n > 0) down(n - 1); } down(9);")
+        .unwrap();
+    let error = lox.execute("down(10);").unwrap_err();
+    assert_eq!(error.to_string(), "Error: Stack overflow.");
+
+    lox.execute("var s = \"abcd\" + \"efgh\";").unwrap();
+    let error = lox.execute("s = s + \"!\";").unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .ends_with("Strings can't be longer than 8 bytes."),
+        "{error}"
+    );
+}
+
+#[test]
+fn natives_that_build_strings_keep_to_the_string_limit() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_string_length: 8,
+        ..Limits::default()
+    });
+
+    lox.execute("var short = toFixed(1, 2);").unwrap();
+    for (source, native) in [
+        ("toFixed(1, 20);", "toFixed"),
+        ("toPrecision(1, 20);", "toPrecision"),
+        ("inspect(#[1, 2, 3, 4]);", "inspect"),
+        ("decode(encode(\"abcdefghi\"));", "decode"),
+    ] {
+        let error = lox.execute(source).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!("Error: {native}: strings can't be longer than 8 bytes."),
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn deep_nesting_is_a_parse_error() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_nesting: 20,
+        ..Limits::default()
+    });
+    let nested = |depth| format!("var x = {}1{};", "(".repeat(depth), ")".repeat(depth));
+    lox.execute(&nested(19)).unwrap();
+
+    let error = parse_error(&mut lox, &nested(100_000));
+    assert!(matches!(
+        &error,
+        parser::Error::TooDeep { what: "Expression", limit: 20, token } if token.lexeme == "("
+    ));
+    assert!(matches!(
+        parse_error(&mut lox, &format!("{}nil;", "!".repeat(100_000))),
+        parser::Error::TooDeep { .. }
+    ));
+    assert!(matches!(
+        parse_error(&mut lox, &"{".repeat(100_000)),
+        parser::Error::TooDeep {
+            what: "Statement",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn default_limits_hold_on_a_small_stack() {
+    // Test threads get 2 MiB, which unoptimized builds run low on before
+    // reaching `max_nesting`. The parser reports that as nesting too deeply.
+    let depth = 199;
+    for source in [
+        format!("var a = {}1{};", "(".repeat(depth), ")".repeat(depth)),
+        format!("{} print 1; {}", "{".repeat(depth), "}".repeat(depth)),
+        format!("{} print 1;", "if (true) ".repeat(depth)),
+    ] {
+        match Lox::new().execute(&source) {
+            Ok(()) | Err(LoxError::Parse(_)) => {}
+            Err(error) => panic!("{error}"),
+        }
+    }
+}
+
+#[test]
+fn chains_count_as_nesting() {
+    let mut lox = Lox::new();
+    for chain in [" + 1", ".b", "(1)", " |> f"] {
+        let error = parse_error(&mut lox, &format!("print a{};", chain.repeat(3000)));
+        assert!(
+            matches!(error, parser::Error::TooDeep { limit: 200, .. }),
+            "{chain}: {error}"
+        );
+    }
+
+    let a = on_a_main_sized_stack(|| {
+        let mut lox = Lox::new();
+        lox.execute(&format!("var a = 1{};", " + 1".repeat(150)))
+            .unwrap();
+        lox.get_global("a")
+    });
+    assert_eq!(a, Some(jlox::Value::Number(151.0)));
+}
+
+#[test]
+fn deep_recursion_stops_before_the_stack_runs_out() {
+    let nest = |open: &str, close: &str, depth| (open.repeat(depth), close.repeat(depth));
+    let (open, close) = nest("(", ")", 150);
+    let (begin, end) = nest("{", "}", 150);
+    for source in [
+        format!("fun f(n) {{ return {open} f(n + 1) {close}; }} f(0);"),
+        format!("fun f(n) {{ {begin} f(n + 1); {end} }} f(0);"),
+    ] {
+        let error =
+            on_a_main_sized_stack(move || Lox::new().execute(&source).unwrap_err().to_string());
+        assert_eq!(error, "Error: Stack overflow.");
+    }
+}
+
+/// Runs `f` with the stack the CLI's main thread gets, which scripts nested
+/// this deeply need in unoptimized builds.
+fn on_a_main_sized_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap()
+}
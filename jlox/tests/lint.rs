@@ -0,0 +1,94 @@
+use jlox::{
+    lint::{fix_source, lint, lint_source, Rule},
+    parse_source,
+};
+
+/// The rule and line of every warning about `source`.
+fn warnings(source: &str) -> Vec<(Rule, usize)> {
+    lint(&parse_source(source).unwrap())
+        .into_iter()
+        .map(|lint| (lint.rule, lint.line))
+        .collect()
+}
+
+#[test]
+fn stores_to_locals_nothing_reads_are_dead() {
+    let source = "var global = 1;
+fun f(a, b) {
+  var total = a;
+  total = total + 1;
+  var unused = 2;
+  unused = 3;
+  b = 4;
+  var (x, y) = (1, 2);
+  fun g() { return x; }
+  return g;
+}";
+    assert_eq!(
+        warnings(source),
+        [
+            (Rule::DeadStore, 5),
+            (Rule::DeadStore, 6),
+            (Rule::DeadStore, 7),
+            (Rule::DeadStore, 8)
+        ]
+    );
+}
+
+#[test]
+fn literal_conditions_and_self_comparisons_are_flagged() {
+    let source = "fun f(x) {
+  if (nil) return;
+  while ((false)) {}
+  while (true) { if (x == x) break; }
+  for (;1;) {}
+  return x < x or x == 1;
+}";
+    assert_eq!(
+        warnings(source),
+        [
+            (Rule::ConstantCondition, 2),
+            (Rule::ConstantCondition, 3),
+            (Rule::SelfComparison, 4),
+            (Rule::ConstantCondition, 5),
+            (Rule::SelfComparison, 6)
+        ]
+    );
+}
+
+#[test]
+fn assignments_in_conditions_are_flagged_unless_parenthesized() {
+    let source = "fun f(x) {
+  if (x = 1) return;
+  while ((x = 2)) print x;
+}";
+    let lints = lint_source(source).unwrap();
+    assert_eq!(lints.len(), 1);
+    assert_eq!(
+        (lints[0].rule, lints[0].line),
+        (Rule::AssignmentInCondition, 2)
+    );
+    assert_eq!(lints[0].fix.as_ref().unwrap().edits[0].span, 19..20);
+}
+
+#[test]
+fn fixes_rename_unused_locals_compare_and_add_semicolons() {
+    let source = "fun f(a) {
+  var unused = a;
+  unused = 2
+  var x = 1;
+  var _x = 2;
+  if (a = 1) print _x
+}";
+    assert_eq!(
+        fix_source(source).unwrap(),
+        "fun f(a) {
+  var _unused = a;
+  _unused = 2;
+  var x = 1;
+  var _x = 2;
+  if (a == 1) print _x;
+}"
+    );
+    assert!(fix_source("var = 1;").is_err());
+}
@@ -0,0 +1,91 @@
+use jlox::{Lox, Value};
+
+mod common;
+use common::runtime_error;
+
+#[test]
+fn list_natives_call_back_into_lox() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun double(x) { return x * 2; }
+         fun big(x) { return x > 2; }
+         fun add(a, b) { return a + b; }
+         fun byFirst(a, b) { return a.0 - b.0; }
+         fun second(pair) { return pair.1; }
+         var pairs = #[(2, \"a\"), (1, \"b\"), (2, \"c\"), (1, \"d\")];",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("#[3, 1, 2] |> map(double) |> filter(big) |> reduce(add, 0)")
+            .unwrap(),
+        Value::Number(10.0)
+    );
+    // Pairs with the same first element keep their order.
+    assert_eq!(
+        lox.eval_expression("sort(pairs, byFirst) |> map(second) |> reduce(add, \"\")")
+            .unwrap(),
+        Value::String("bdac".into())
+    );
+}
+
+#[test]
+fn callback_errors_propagate_unchanged() {
+    assert_eq!(
+        runtime_error(
+            &mut Lox::new(),
+            "fun first(x) {\n  return x[0];\n}\nprint map(#[#[1], #[]], first);"
+        ),
+        "List index must be a whole number below 0, got 0 [line 2]."
+    );
+    assert_eq!(
+        runtime_error(
+            &mut Lox::new(),
+            "fun same(a, b) { return nil; }\nprint sort(#[1, 2], same);"
+        ),
+        "sort: the comparator must return a number, got nil."
+    );
+}
+
+#[test]
+fn sort_without_a_comparator_uses_values_and_compare_to() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Version {
+           init(major, tag) {
+             this.major = major;
+             this.tag = tag;
+           }
+           compareTo(other) { return this.major - other.major; }
+         }
+         class Broken {
+           compareTo(other) { return nil; }
+         }
+         fun tag(v) { return v.tag; }
+         fun add(a, b) { return a + b; }",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("sort(#[\"b\", \"c\", \"a\"]) |> reduce(add, \"\")")
+            .unwrap(),
+        Value::String("abc".into())
+    );
+    assert_eq!(
+        lox.eval_expression(
+            "sort(#[Version(2, \"x\"), Version(1, \"y\"), Version(2, \"z\")]) |> map(tag) |> reduce(add, \"\")"
+        )
+        .unwrap(),
+        Value::String("yxz".into())
+    );
+
+    let mut error = |source: &str| lox.eval_expression(source).unwrap_err().to_string();
+    assert_eq!(
+        error("sort(#[1, \"a\"])"),
+        "Error: sort: can't compare 1 with a, pass a comparator to sort them."
+    );
+    assert_eq!(
+        error("sort(#[Broken(), Broken()])"),
+        "Error: sort: Broken.compareTo() must return a number, got nil."
+    );
+}
@@ -0,0 +1,84 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+#[test]
+fn closures_capture_their_own_iteration_of_the_loop_variable() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var first; var second; var third;
+         for (var i = 0; i < 3; i = i + 1) {
+           fun capture() {
+             return i;
+           }
+           if (i == 0) first = capture;
+           if (i == 1) second = capture;
+           if (i == 2) third = capture;
+         }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.eval_expression("first()").unwrap(), Value::Number(0.0));
+    assert_eq!(lox.eval_expression("second()").unwrap(), Value::Number(1.0));
+    assert_eq!(lox.eval_expression("third()").unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn body_assignments_carry_over_to_the_next_iteration() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var visited = 0;
+         for (var i = 0; i < 10; i = i + 1) {
+           i = i + 2;
+           visited = visited + 1;
+         }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("visited"), Some(Value::Number(4.0)));
+}
+
+#[test]
+fn break_and_continue_affect_the_innermost_loop() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var sum = 0;
+         for (var i = 0; i < 10; i = i + 1) {
+           if (i == 2) continue;
+           if (i == 5) break;
+           var j = 0;
+           while (true) {
+             j = j + 1;
+             if (j < 3) continue;
+             break;
+           }
+           sum = sum + i * j;
+         }
+         fun firstOver(limit) {
+           var n = 0;
+           while (true) {
+             n = n + 1;
+             if (n > limit) return n;
+           }
+         }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("sum"), Some(Value::Number(24.0)));
+    assert_eq!(
+        lox.eval_expression("firstOver(4)").unwrap(),
+        Value::Number(5.0)
+    );
+}
+
+#[test]
+fn break_and_continue_outside_loops_are_resolve_errors() {
+    for source in [
+        "break;",
+        "{ continue; }",
+        "while (true) { fun escape() { break; } }",
+    ] {
+        assert!(
+            matches!(run_source(source), Err(LoxError::Resolve(_))),
+            "{source}"
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+#[test]
+fn instances_are_keys_by_hash_code_and_equals_or_by_identity() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Point {
+           init(x, y) {
+             this.x = x;
+             this.y = y;
+           }
+           hashCode() { return this.x * 31 + this.y; }
+           equals(other) { return other.x == this.x and other.y == this.y; }
+         }
+         class Tag {}
+         var tag = Tag();
+         var m = #{Point(1, 2): \"a\", tag: \"tag\", (tag, 1): \"pair\", Point(1, 2): \"b\"};",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("m[Point(1, 2)] + m[tag] + m[(tag, 1)]")
+            .unwrap(),
+        Value::String("btagpair".into())
+    );
+    assert_eq!(lox.eval_expression("len(m)").unwrap(), Value::Number(3.0));
+    assert_eq!(
+        lox.eval_expression("has(m, Tag())").unwrap(),
+        Value::Bool(false)
+    );
+}
+
+#[test]
+fn hash_code_must_return_a_number() {
+    match run_source("class Bad { hashCode() { return nil; } }\nprint #{Bad(): 1};") {
+        Err(LoxError::Runtime(error)) => assert_eq!(
+            error.to_string(),
+            "Bad.hashCode() must return a number, got nil."
+        ),
+        result => panic!("gave {result:?}"),
+    }
+}
@@ -0,0 +1,51 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+#[test]
+fn match_tries_arms_in_order() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun describe(x) {
+           return match x {
+             0 => \"zero\",
+             1..10 => \"small\",
+             (a, 0) => \"ends in zero after \" + a,
+             (_, b) if b > 5 => \"big second\",
+             \"hi\" => \"greeting\",
+             _ => \"other\"
+           };
+         }",
+    )
+    .unwrap();
+
+    for (argument, expected) in [
+        ("0", "zero"),
+        ("9.5", "small"),
+        ("10", "other"),
+        ("(\"x\", 0)", "ends in zero after x"),
+        ("(1, 9)", "big second"),
+        ("(1, 2)", "other"),
+        ("\"hi\"", "greeting"),
+        ("nil", "other"),
+    ] {
+        assert_eq!(
+            lox.eval_expression(&format!("describe({argument})"))
+                .unwrap(),
+            Value::String(expected.into()),
+            "describe({argument})"
+        );
+    }
+}
+
+#[test]
+fn unmatched_values_and_repeated_bindings_are_errors() {
+    match run_source("print match 42 {\n  1 => 2\n};") {
+        Err(LoxError::Runtime(error)) => {
+            assert_eq!(error.to_string(), "No match arm for 42 [line 1].")
+        }
+        result => panic!("gave {result:?}"),
+    }
+    assert!(matches!(
+        run_source("print match (1, 2) { (a, a) => a };"),
+        Err(LoxError::Resolve(_))
+    ));
+}
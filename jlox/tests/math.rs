@@ -0,0 +1,20 @@
+use jlox::{Lox, Value};
+
+#[test]
+fn nan_and_infinity_follow_ieee_754() {
+    let mut lox = Lox::new();
+    let mut eval = |source: &str| lox.eval_expression(source).unwrap();
+
+    assert!(matches!(eval("nan"), Value::Number(n) if n.is_nan()));
+    assert_eq!(eval("-infinity"), Value::Number(f64::NEG_INFINITY));
+
+    assert_eq!(eval("nan == nan"), Value::Bool(false));
+    assert_eq!(eval("nan != nan"), Value::Bool(true));
+    assert_eq!(eval("isNaN(nan)"), Value::Bool(true));
+    assert_eq!(eval("isNaN(\"nan\")"), Value::Bool(false));
+
+    assert_eq!(eval("isFinite(1.5)"), Value::Bool(true));
+    assert_eq!(eval("isFinite(infinity)"), Value::Bool(false));
+    assert_eq!(eval("isFinite(nan)"), Value::Bool(false));
+    assert_eq!(eval("isFinite(nil)"), Value::Bool(false));
+}
@@ -0,0 +1,45 @@
+use jlox::{
+    metrics::{measure, render_json, FileMetrics},
+    parse_source,
+};
+
+const SOURCE: &str = "fun classify(n) {
+  if (n < 0 or n > 100) return \"out\";
+  var kind = match n { 0 => \"zero\", x if x < 10 => \"small\", _ => \"big\" };
+  while (n > 10) {
+    if (n > 50) { n = n / 2; }
+    n = n - 1;
+  }
+  fun helper() { return kind; }
+  return kind;
+}
+class Box {
+  get() { return this.value; }
+}";
+
+#[test]
+fn functions_and_methods_are_measured() {
+    let functions = measure(&parse_source(SOURCE).unwrap());
+    let summary: Vec<_> = functions.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        summary,
+        [
+            "classify: 9 statements, depth 2, complexity 8",
+            "helper: 1 statements, depth 0, complexity 1",
+            "Box.get: 1 statements, depth 0, complexity 1",
+        ]
+    );
+}
+
+#[test]
+fn json_lists_functions_per_file() {
+    let files = [FileMetrics {
+        path: "box.lox".into(),
+        functions: measure(&parse_source("class Box { get() { return 1; } }").unwrap()),
+    }];
+    assert_eq!(
+        render_json(&files),
+        "[{\"path\": \"box.lox\", \"functions\": [{\"name\": \"Box.get\", \"line\": 1, \
+         \"statements\": 1, \"depth\": 0, \"complexity\": 1}]}]\n"
+    );
+}
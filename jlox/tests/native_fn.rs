@@ -0,0 +1,62 @@
+use jlox::{native_fn, Lox, Value};
+
+mod common;
+use common::runtime_error;
+
+native_fn!(Clamp = "clamp", |_, (x: f64, low: f64, high: f64)| {
+    if low > high {
+        return Err(format!("{low} is above {high}"));
+    }
+    Ok(x.clamp(low, high))
+});
+
+native_fn!(Label = "label", |_, (name: &str, count: Option<f64>)| {
+    Ok(match count {
+        Some(count) => format!("{name} x{count}"),
+        None => name.to_owned(),
+    })
+});
+
+native_fn!(Calls = "calls", |interpreter, ()| Ok(
+    interpreter.stats().calls as f64
+));
+
+#[test]
+fn native_fn_converts_and_checks_arguments() {
+    let mut lox = Lox::new();
+    lox.define_native("clamp", Clamp);
+    lox.define_native("label", Label);
+    lox.define_native("calls", Calls);
+
+    assert_eq!(
+        lox.eval_expression("clamp(12, 0, 10)").unwrap(),
+        Value::Number(10.0)
+    );
+    assert_eq!(
+        lox.eval_expression("label(\"box\", 3)").unwrap(),
+        Value::String("box x3".to_owned())
+    );
+    assert_eq!(
+        lox.eval_expression("label(\"box\", nil)").unwrap(),
+        Value::String("box".to_owned())
+    );
+
+    assert_eq!(lox.eval_expression("calls()").unwrap(), Value::Number(4.0));
+
+    assert_eq!(
+        runtime_error(&mut lox, "clamp(1, 2);"),
+        "Expected 3 arguments but got 2 in call to 'clamp' [line 1]"
+    );
+    assert_eq!(
+        runtime_error(&mut lox, "clamp(1, \"2\", 3);"),
+        "clamp: argument 2: expected a number, got 2"
+    );
+    assert_eq!(
+        runtime_error(&mut lox, "clamp(1, 3, 2);"),
+        "clamp: 3 is above 2"
+    );
+    assert_eq!(
+        runtime_error(&mut lox, "label(1, nil);"),
+        "label: argument 1: expected a string, got 1"
+    );
+}
@@ -0,0 +1,62 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use jlox::permissions::Capability;
+use jlox::{Lox, Value};
+
+mod common;
+use common::runtime_error;
+
+/// A server on a free local port that answers one message with its
+/// upper-cased text.
+fn echo_server() -> (u16, thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = [0; 64];
+        let read = stream.read(&mut buffer).unwrap();
+        let reply = String::from_utf8_lossy(&buffer[..read]).to_uppercase();
+        stream.write_all(reply.as_bytes()).unwrap();
+    });
+    (port, server)
+}
+
+#[test]
+fn sockets_send_and_receive_with_the_net_capability() {
+    let (port, server) = echo_server();
+    let mut lox = Lox::new();
+    lox.grant(Capability::Net);
+
+    lox.execute(&format!(
+        "var socket = tcpConnect(\"127.0.0.1\", {port});
+var sent = socket.send(\"ping\");
+var reply = socket.recv();
+var peer = socket.peer;
+socket.close();"
+    ))
+    .unwrap();
+    server.join().unwrap();
+
+    assert_eq!(lox.get_global("sent"), Some(Value::Number(4.0)));
+    assert_eq!(lox.get_global("reply"), Some(Value::String("PING".into())));
+    assert_eq!(
+        lox.get_global("peer"),
+        Some(Value::String(format!("127.0.0.1:{port}")))
+    );
+    assert!(runtime_error(&mut lox, "socket.send(\"again\");").contains("is closed"));
+}
+
+#[test]
+fn connecting_needs_the_net_capability_and_a_valid_port() {
+    let mut lox = Lox::new();
+    assert!(runtime_error(&mut lox, "tcpConnect(\"127.0.0.1\", 1);")
+        .contains("requires the 'net' capability"));
+
+    lox.grant(Capability::Net);
+    assert!(
+        runtime_error(&mut lox, "tcpConnect(\"127.0.0.1\", 70000);").contains("not a valid port")
+    );
+    assert!(runtime_error(&mut lox, "tcpConnect(1, 80);").contains("host must be a string"));
+}
@@ -0,0 +1,44 @@
+use jlox::{limits::Limits, run_captured, types::Number};
+
+#[test]
+fn number_helpers() {
+    assert_eq!(Number(3.0).as_int(), Some(3));
+    assert_eq!(Number(-2.0).as_int(), Some(-2));
+    assert_eq!(Number(2.5).as_int(), None);
+    assert_eq!(Number(f64::NAN).as_int(), None);
+    assert_eq!(Number(1e300).as_int(), None);
+
+    assert!(Number(0.1 + 0.2).approx_eq(Number(0.3)));
+    assert!(!Number(0.1).approx_eq(Number(0.2)));
+    assert!(!Number(f64::NAN).approx_eq(Number(f64::NAN)));
+
+    assert_eq!(Number(3.0).to_string(), "3");
+    assert_eq!(Number(2.5).to_string(), "2.5");
+    assert_eq!(Number(1.0) + Number(2.0) * Number(3.0), Number(7.0));
+}
+
+#[test]
+fn numbers_print_in_the_shortest_form_that_reads_back() {
+    for (x, text) in [
+        (0.1 + 0.2, "0.30000000000000004"),
+        (1e21, "1e21"),
+        (2.5e-7, "2.5e-7"),
+        (1e15, "1000000000000000"),
+        (-0.0, "-0"),
+    ] {
+        assert_eq!(Number(x).to_string(), text);
+        let report = run_captured(&format!("write({text} == {x:e});"), "", Limits::default());
+        assert_eq!(report.stdout, "true", "{text}");
+    }
+}
+
+#[test]
+fn numbers_format_to_fixed_digits() {
+    let report = run_captured(
+        "write(toFixed(2.345, 1) + \" \" + toFixed(0.125, 2) + \" \" + toPrecision(123.456, 4)
+               + \" \" + toPrecision(123456, 2) + \" \" + toPrecision(0.0000001234, 2));",
+        "",
+        Limits::default(),
+    );
+    assert_eq!(report.stdout, "2.3 0.12 123.5 1.2e5 1.2e-7");
+}
@@ -0,0 +1,52 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn on_missing_answers_for_absent_properties_and_methods() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Mock {
+  onMissing(name, args) {
+    this.last = name;
+    if (args == nil) return name;
+    return args[0] + args[1];
+  }
+  real() { return \"real\"; }
+}
+class Sub > Mock {}
+var m = Mock();
+m.field = 1;
+var read = m.color;
+var sum = m.add(2, 3);
+var real = m.real();
+var field = m.field;
+var last = m.last;
+var inherited = Sub().anything;",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("read"), Some(Value::String("color".into())));
+    assert_eq!(lox.get_global("sum"), Some(Value::Number(5.0)));
+    assert_eq!(lox.get_global("real"), Some(Value::String("real".into())));
+    assert_eq!(lox.get_global("field"), Some(Value::Number(1.0)));
+    assert_eq!(lox.get_global("last"), Some(Value::String("add".into())));
+    assert_eq!(
+        lox.get_global("inherited"),
+        Some(Value::String("anything".into()))
+    );
+}
+
+#[test]
+fn without_on_missing_absent_properties_are_still_errors() {
+    let mut lox = Lox::new();
+    lox.execute("class Plain {} var p = Plain();").unwrap();
+
+    for (source, message) in [
+        ("p.color", "Undefined property 'color'"),
+        ("p.add(1)", "Undefined property 'add'"),
+    ] {
+        match lox.eval_expression(source) {
+            Err(LoxError::Runtime(error)) => assert_eq!(error.to_string(), message),
+            result => panic!("{source:?} gave {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use jlox::{Lox, Value};
+
+#[test]
+fn on_set_hears_of_every_field_write_after_it_happens() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var log = \"\";
+class Observed {
+  init() { this.count = 0; }
+  onSet(name, old, new) {
+    log = log + name + \":\";
+    if (old == nil) log = log + \"nil\"; else log = log + \"set\";
+    if (this.count == new) log = log + \" \";
+  }
+}
+class Child > Observed {}
+var o = Observed();
+var result = o.count = 2;
+var c = Child();
+c.other = 1;",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.get_global("log"),
+        Some(Value::String(
+            "count:nil count:set count:nil other:nil".into()
+        ))
+    );
+    assert_eq!(lox.get_global("result"), Some(Value::Number(2.0)));
+}
+
+#[test]
+fn writes_inside_on_set_do_not_call_it_again_for_that_instance() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var calls = 0;
+class Tracked {
+  init(peer) { this.peer = peer; }
+  onSet(name, old, new) {
+    calls = calls + 1;
+    this.dirty = true;
+    if (this.peer != nil and name != \"dirty\") this.peer.value = new;
+  }
+}
+var b = Tracked(nil);
+var a = Tracked(b);
+calls = 0;
+a.value = 1;",
+    )
+    .unwrap();
+
+    // `a.value` and the write it makes to `b`; neither `dirty` write counts.
+    assert_eq!(lox.get_global("calls"), Some(Value::Number(2.0)));
+    assert_eq!(lox.eval_expression("a.dirty").unwrap(), Value::Bool(true));
+    assert_eq!(lox.eval_expression("b.value").unwrap(), Value::Number(1.0));
+
+    lox.execute("a.value = 2;").unwrap();
+    assert_eq!(lox.get_global("calls"), Some(Value::Number(4.0)));
+}
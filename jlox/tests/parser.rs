@@ -0,0 +1,261 @@
+use jlox::{
+    ast::{Expr, Literal, Stmt},
+    parse_source,
+    parser::Error,
+    run_source,
+    scanner::Scanner,
+    token::{Token, TokenType},
+    types::Number,
+};
+
+fn parse_error(source: &str) -> Error {
+    match parse_source(source) {
+        Ok(statements) => panic!("{source:?} parsed to {statements:?}"),
+        Err(error) => error,
+    }
+}
+
+#[test]
+fn empty_input_parses_to_nothing() {
+    assert!(parse_source("").unwrap().is_empty());
+    assert!(parse_source("  // just a comment\n").unwrap().is_empty());
+}
+
+#[test]
+fn empty_token_stream_does_not_panic() {
+    let statements = jlox::parser::Parser::new(Vec::new()).parse().unwrap();
+    assert!(statements.is_empty());
+}
+
+#[test]
+fn the_parser_scans_one_token_ahead() {
+    let pulled = std::cell::Cell::new(0);
+    let tokens = Scanner::new("1 + 2; 3 4 5").inspect(|_| pulled.set(pulled.get() + 1));
+
+    assert!(jlox::parser::Parser::new(tokens)
+        .parse_expression()
+        .is_err());
+    // `1 + 2;` and the `3` that shouldn't follow it.
+    assert_eq!(pulled.get(), 5);
+}
+
+#[test]
+fn truncated_input_reports_unexpected_eof() {
+    let corpus = [
+        "(",
+        "print",
+        "print 1",
+        "var",
+        "var a =",
+        "1 +",
+        "-",
+        "!",
+        "a.",
+        "f(1,",
+        "super",
+        "super(",
+        "fun",
+        "fun f(",
+        "fun f(a,",
+        "fun f() {",
+        "class A {",
+        "class A > ",
+        "if (true)",
+        "while (",
+        "for (;;",
+        "{",
+        "return",
+    ];
+
+    for source in corpus {
+        assert!(
+            matches!(parse_error(source), Error::UnexpectedEof { .. }),
+            "{source:?}"
+        );
+    }
+}
+
+#[test]
+fn unexpected_eof_reports_the_last_line() {
+    let Error::UnexpectedEof { line, msg } = parse_error("var a = 1;\n\nprint a") else {
+        panic!("expected an end of input error");
+    };
+    assert_eq!(line, 3);
+    assert_eq!(msg, "Expect ';' after value.");
+}
+
+#[test]
+fn stray_tokens_report_the_offending_token() {
+    let Error::Bad { token, msg } = parse_error(")") else {
+        panic!("expected a token error");
+    };
+    assert_eq!(token.lexeme, ")");
+    assert_eq!(msg, "Expect expression.");
+
+    assert!(matches!(
+        parse_error("1 = 2;"),
+        Error::InvalidAssignment { .. }
+    ));
+    assert!(matches!(parse_error("var 1;"), Error::Bad { .. }));
+    assert!(matches!(parse_error("}"), Error::Bad { .. }));
+}
+
+#[test]
+fn scanner_errors_are_tokens_the_parser_reports() {
+    let tokens = Scanner::new("a @ é \"open").scan_tokens();
+    let kinds: Vec<_> = tokens.iter().map(|token| token.token_type).collect();
+    assert_eq!(
+        kinds,
+        [
+            TokenType::Identifier,
+            TokenType::Error,
+            TokenType::Error,
+            TokenType::Error,
+            TokenType::EOF
+        ]
+    );
+    assert_eq!(tokens[2].lexeme, "é");
+
+    let Error::Bad { token, msg } = parse_error("print 1 + @;") else {
+        panic!("expected a token error");
+    };
+    assert_eq!(token.lexeme, "@");
+    assert_eq!(msg, "Unexpected character.");
+    let Error::Bad { msg, .. } = parse_error("print \"open;") else {
+        panic!("expected a token error");
+    };
+    assert_eq!(msg, "Unterminated string.");
+}
+
+#[test]
+fn fuzz_corpus_never_panics() {
+    let corpus = [
+        "((((",
+        "(\u{5bb}\u{fffd}\u{fffd}",
+        ")))",
+        "\"unterminated",
+        "é",
+        "var é = 1;",
+        "print \"ü\";",
+        "class > {",
+        "fun (",
+        "this.",
+        "super.",
+        "1..2",
+        "= = =",
+        "}}}{{{",
+        "return return;",
+        "\0",
+        "print 1 print 2",
+        "class A > A {}",
+        "fun f() { return f(); } f();",
+        "var a = a;",
+        "{ var a = 1; var a = 2; }",
+        "this;",
+        "super.x;",
+        "super(A).x;",
+        "return 1;",
+    ];
+
+    // Runaway recursion is cut off by the interpreter's call depth limit,
+    // which assumes a main-thread sized stack.
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            for source in corpus {
+                let _ = parse_source(source);
+                let _ = run_source(source);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+/// Pseudo-random source text from Lox tokens and stray characters, fixed by
+/// `seed` so failures reproduce.
+fn garbage(seed: u64, len: usize) -> String {
+    const PIECES: &[&str] = &[
+        "var", "fun", "class", "if", "else", "while", "for", "return", "print", "this", "super",
+        "nil", "true", "(", ")", "{", "}", ";", ",", ".", "=", "+", "-", "*", "/", "!", "<", ">",
+        "==", "a", "b", "1", "2.5", "\"s\"", "\"", "é", "#", "|>", "\n",
+    ];
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            PIECES[(state >> 33) as usize % PIECES.len()]
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn generated_garbage_never_panics() {
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(|| {
+            for seed in 0..500 {
+                let source = garbage(seed, 40);
+                let _ = parse_source(&source);
+                let _ = run_source(&source);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn long_chains_never_overflow() {
+    let error = parse_error(&format!("print 1{};", " + 1".repeat(3000)));
+    assert!(matches!(error, Error::TooDeep { .. }), "{error}");
+
+    for terms in [1000, 10000] {
+        assert!(run_source(&format!("print 1{};", " + 1".repeat(terms))).is_err());
+    }
+}
+
+#[test]
+fn deep_trees_drop_without_overflowing() {
+    let one = || Expr::Literal(Literal::Number(Number(1.0)));
+    let mut expr = one();
+    for _ in 0..1_000_000 {
+        expr = Expr::Binary {
+            left: Box::new(expr),
+            op: Token::new(TokenType::Plus, "+", None, 1),
+            right: Box::new(one()),
+        };
+    }
+    drop(expr);
+}
+
+#[test]
+fn non_ascii_text_is_kept_inside_strings() {
+    let tokens = Scanner::new("\"héllo → wörld\"").scan_tokens();
+    assert_eq!(tokens[0].lexeme, "\"héllo → wörld\"");
+}
+
+#[test]
+fn doc_comments_attach_to_declarations() {
+    let source = "/// Adds.\n///\n/// Twice.\nfun add(a, b) {}\n//// plain\nclass A {\n  /// Method.\n  m() {}\n}\nprint 1; /// stray\n";
+    let statements = parse_source(source).unwrap();
+
+    let [Stmt::Function { doc, .. }, Stmt::Class {
+        doc: class_doc,
+        methods,
+        ..
+    }, Stmt::Print { .. }] = statements.as_slice()
+    else {
+        panic!("unexpected statements {statements:?}");
+    };
+    assert_eq!(doc.as_deref(), Some("Adds.\n\nTwice."));
+    assert_eq!(class_doc, &None);
+
+    let [Stmt::Function { doc, .. }] = methods.as_slice() else {
+        panic!("unexpected methods {methods:?}");
+    };
+    assert_eq!(doc.as_deref(), Some("Method."));
+}
@@ -0,0 +1,156 @@
+use std::process::Command;
+
+use jlox::natives::NativeInfo;
+use jlox::permissions::{Capability, Permissions};
+use jlox::{dialect::Dialect, native_fn, program::Program, Lox, LoxError, Value};
+
+#[test]
+fn flags_name_capabilities() {
+    assert_eq!(Capability::from_flag("--allow-net"), Some(Capability::Net));
+    assert_eq!(
+        Capability::from_flag("--allow-read"),
+        Some(Capability::Read)
+    );
+    assert_eq!(Capability::from_flag("--allow-everything"), None);
+    assert_eq!(Capability::from_flag("--net"), None);
+
+    let mut permissions = Permissions::new();
+    assert!(permissions.check(Capability::Env, "env").is_err());
+    permissions.grant(Capability::Env);
+    assert!(permissions.check(Capability::Env, "env").is_ok());
+    permissions.revoke(Capability::Env);
+    assert!(!permissions.is_granted(Capability::Env));
+    assert!(Capability::ALL
+        .into_iter()
+        .all(|capability| Permissions::all().is_granted(capability)));
+}
+
+#[test]
+fn privileged_natives_are_denied_until_granted() {
+    let path = std::env::temp_dir().join("jlox-permissions.txt");
+    std::fs::write(&path, "secret").unwrap();
+    let source = format!(
+        "var data = decode(readBytes({:?}));",
+        path.display().to_string()
+    );
+
+    let mut lox = Lox::new();
+    match lox.execute(&source) {
+        Err(LoxError::Runtime(error)) => assert_eq!(
+            error.to_string(),
+            "readBytes: requires the 'read' capability (run with --allow-read)."
+        ),
+        result => panic!("reading without permission gave {result:?}"),
+    }
+    assert_eq!(lox.get_global("data"), None);
+
+    lox.grant(Capability::Read);
+    lox.execute(&source).unwrap();
+    assert_eq!(lox.get_global("data"), Some(Value::String("secret".into())));
+
+    lox.set_permissions(Permissions::new());
+    assert!(lox.execute(&source).is_err());
+}
+
+native_fn!(Ping = "ping", |_, (host: String)| Ok(format!("pong from {host}")));
+
+#[test]
+fn host_natives_are_denied_their_registered_capability_until_granted() {
+    let mut lox = Lox::new();
+    lox.define_native_with(
+        NativeInfo::new("ping", "host").requires(Capability::Net),
+        Ping,
+    );
+    let source = "var reply = ping(\"example.com\");";
+
+    match lox.execute(source) {
+        Err(LoxError::Runtime(error)) => assert_eq!(
+            error.to_string(),
+            "ping: requires the 'net' capability (run with --allow-net)."
+        ),
+        result => panic!("pinging without permission gave {result:?}"),
+    }
+    assert_eq!(lox.get_global("reply"), None);
+
+    lox.grant(Capability::Net);
+    lox.execute(source).unwrap();
+    assert_eq!(
+        lox.get_global("reply"),
+        Some(Value::String("pong from example.com".into()))
+    );
+}
+
+#[test]
+fn allow_flags_grant_capabilities_to_scripts() {
+    let data = std::env::temp_dir().join("jlox-allow-flags.txt");
+    std::fs::write(&data, "flag").unwrap();
+    let script = std::env::temp_dir().join("jlox-allow-flags.lox");
+    std::fs::write(
+        &script,
+        format!("print decode(readBytes({:?}));", data.display().to_string()),
+    )
+    .unwrap();
+
+    let run = |flags: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_jlox"))
+            .args(flags)
+            .arg(&script)
+            .output()
+            .unwrap()
+    };
+
+    let denied = run(&[]);
+    assert!(!denied.status.success());
+    assert!(String::from_utf8_lossy(&denied.stderr).contains("--allow-read"));
+
+    for flags in [
+        &["--allow-read"][..],
+        &["--allow-all"],
+        &["--allow-net", "--allow-read"],
+    ] {
+        let allowed = run(flags);
+        assert!(allowed.status.success(), "{flags:?}");
+        assert!(String::from_utf8_lossy(&allowed.stdout).contains("flag"));
+    }
+}
+
+#[test]
+fn imports_need_the_read_capability() {
+    let root = std::env::temp_dir().join(format!("jlox-import-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let secret = root.join("secret.txt");
+    std::fs::write(&secret, "root:x:0:0:secret\n").unwrap();
+    let source = format!("import {:?};", secret.display().to_string());
+
+    let mut lox = Lox::new();
+    match lox.execute(&source) {
+        Err(LoxError::Runtime(error)) => assert_eq!(
+            error.to_string(),
+            "import: requires the 'read' capability (run with --allow-read)."
+        ),
+        result => panic!("importing without permission gave {result:?}"),
+    }
+
+    lox.grant(Capability::Read);
+    match lox.execute(&source) {
+        Err(LoxError::Program(report)) => {
+            assert_eq!(
+                report.to_string(),
+                format!("{}:1: Expect ';' after expression.", secret.display())
+            );
+        }
+        result => panic!("importing a file that isn't Lox gave {result:?}"),
+    }
+
+    let main = root.join("main.lox");
+    std::fs::write(&main, "import \"secret.txt\";\n").unwrap();
+    let program = Program::load_with([&main], Dialect::default(), &Permissions::new());
+    assert_eq!(
+        program.report().to_string(),
+        format!(
+            "{}: Can't import {}: requires the 'read' capability (run with --allow-read).",
+            main.display(),
+            secret.display()
+        )
+    );
+}
@@ -0,0 +1,44 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn pipeline_passes_the_value_as_the_first_argument() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun double(x) { return x * 2; }
+         fun sub(x, y) { return x - y; }
+         class Scaler {
+           init(k) { this.k = k; }
+           scale(x) { return x * this.k; }
+         }
+         var s = Scaler(3);",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("1 + 2 |> double |> sub(1) |> s.scale")
+            .unwrap(),
+        Value::Number(15.0)
+    );
+    assert_eq!(
+        lox.eval_expression("(10 |> Scaler).k").unwrap(),
+        Value::Number(10.0)
+    );
+    // The whole property chain is the stage, so this is `Scaler(2).scale(4)`.
+    assert_eq!(
+        lox.eval_expression("4 |> Scaler(2).scale").unwrap(),
+        Value::Number(8.0)
+    );
+}
+
+#[test]
+fn piping_into_a_full_call_respects_the_argument_limit() {
+    let mut lox = Lox::new();
+    let mut limits = lox.limits();
+    limits.max_arguments = 1;
+    lox.set_limits(limits);
+
+    assert!(matches!(
+        lox.execute("fun f(a) {}\n1 |> f(2);"),
+        Err(LoxError::Parse(_))
+    ));
+}
@@ -0,0 +1,126 @@
+use std::{fs, path::PathBuf};
+
+use jlox::{interpreter::Interpreter, program::Program, Lox, LoxError};
+
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("jlox-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for (path, source) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    root
+}
+
+#[test]
+fn imports_run_before_their_importers() {
+    let root = project(
+        "order",
+        &[
+            ("main.lox", "import \"lib/b.lox\";\nimport \"lib/a.lox\";\n"),
+            ("lib/a.lox", "fun a() {}\n"),
+            ("lib/b.lox", "import \"a.lox\";\nimport \"../lib/a.lox\";\n"),
+        ],
+    );
+
+    let program = Program::load([root.join("main.lox")]);
+    assert!(program.report().is_empty(), "{}", program.report());
+
+    let order: Vec<PathBuf> = program
+        .files()
+        .map(|file| file.path().strip_prefix(&root).unwrap().to_path_buf())
+        .collect();
+    assert_eq!(
+        order,
+        ["lib/a.lox", "lib/b.lox", "main.lox"].map(PathBuf::from)
+    );
+    assert!(!program
+        .file(root.join("lib/a.lox"))
+        .unwrap()
+        .tokens()
+        .is_empty());
+}
+
+#[test]
+fn diagnostics_from_every_file_are_collected() {
+    let root = project(
+        "report",
+        &[
+            (
+                "main.lox",
+                "import \"missing.lox\";\nimport \"broken.lox\";\nimport \"cycle.lox\";\n",
+            ),
+            ("broken.lox", "var a = 1;\nprint a\n"),
+            ("cycle.lox", "import \"main.lox\";\nreturn 1;\n"),
+        ],
+    );
+
+    let mut program = Program::load([root.join("main.lox")]);
+    let lines: Vec<String> = program
+        .report()
+        .diagnostics
+        .iter()
+        .map(|d| {
+            format!(
+                "{}:{:?}",
+                d.path.file_name().unwrap().to_string_lossy(),
+                d.line
+            )
+        })
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "missing.lox:None",
+            "broken.lox:Some(3)",
+            "main.lox:None",
+            "cycle.lox:None"
+        ]
+    );
+
+    program.update(
+        root.join("broken.lox"),
+        "var a = 1;\nprint a;\n".to_string(),
+    );
+    assert_eq!(program.report().diagnostics.len(), 3);
+}
+
+#[test]
+fn undefined_globals_are_found_across_files() {
+    let root = project(
+        "globals",
+        &[
+            (
+                "main.lox",
+                "import \"lib.lox\";\nfun run() { return helper() + clock(); }\n\
+                 fun rarely() { return helpr(); }\n",
+            ),
+            (
+                "lib.lox",
+                "fun helper() { return later; }\nvar later = 1;\n",
+            ),
+        ],
+    );
+
+    let program = Program::load([root.join("main.lox")]);
+    assert!(program.report().is_empty(), "{}", program.report());
+    let undefined: Vec<String> = program
+        .undefined_globals(&Interpreter::new())
+        .iter()
+        .map(|d| format!("{}:{}", d.line.unwrap(), d.message))
+        .collect();
+    assert_eq!(undefined, ["3:Undefined variable 'helpr'."]);
+}
+
+#[test]
+fn imports_that_were_never_loaded_are_runtime_errors() {
+    let mut lox = Lox::new();
+    match lox.run_isolated("import \"lib.lox\";") {
+        Err(LoxError::Runtime(error)) => assert_eq!(
+            error.to_string(),
+            "'import' is only supported when running a file or source through Lox [line 1]."
+        ),
+        result => panic!("an isolated import gave {result:?}"),
+    }
+}
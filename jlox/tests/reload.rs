@@ -0,0 +1,40 @@
+use jlox::{program::Program, Lox, Value};
+
+#[test]
+fn reload_replaces_functions_and_classes_only() {
+    let path = std::env::temp_dir().join("jlox-reload-test.lox");
+    let version = |n: u32| {
+        format!(
+            "var count = 0;
+             fun greet() {{
+               return \"v{n}\";
+             }}
+             class Counter {{
+               value() {{
+                 return {n};
+               }}
+             }}"
+        )
+    };
+
+    std::fs::write(&path, version(1)).unwrap();
+    let mut lox = Lox::new();
+    lox.run_program(&Program::load([&path])).unwrap();
+    lox.execute("count = 5;\nvar counter = Counter();").unwrap();
+
+    std::fs::write(&path, version(2)).unwrap();
+    let names = lox.reload(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(names, ["greet", "Counter"]);
+    assert_eq!(lox.get_global("count"), Some(Value::Number(5.0)));
+    assert_eq!(
+        lox.eval_expression("greet()").unwrap(),
+        Value::String("v2".into())
+    );
+    // Instances made before the reload see the new methods.
+    assert_eq!(
+        lox.eval_expression("counter.value()").unwrap(),
+        Value::Number(2.0)
+    );
+}
@@ -0,0 +1,31 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Types `lines` into the REPL and returns what it wrote to stdout, prompts
+/// included.
+fn repl(lines: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jlox"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(lines.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn repl_keeps_the_last_results() {
+    let output = repl("1 + 2\n_ * 10\nvar x = 4;\nnil\nx;\n_ + _2 + _3\n");
+    assert_eq!(output, "> 3\n> 30\n> > > 4\n> 37\n> ");
+}
@@ -0,0 +1,120 @@
+use std::io::Cursor;
+
+use jlox::permissions::Capability;
+use jlox::replay::{Error, Trace};
+use jlox::{Lox, Value};
+
+const SCRIPT: &str = "var started = clock();
+var first = stdin.readLine();
+var second = stdin.readLine();";
+
+fn globals(lox: &Lox) -> Vec<Option<Value>> {
+    ["started", "first", "second"]
+        .iter()
+        .map(|name| lox.get_global(name))
+        .collect()
+}
+
+#[test]
+fn replays_the_inputs_of_a_recorded_session() {
+    let path = std::env::temp_dir().join("jlox-replay-trace.txt");
+    let path = path.to_str().unwrap();
+
+    let mut recorded = Lox::new();
+    recorded.redirect_input(Cursor::new("first line\nsecond line\n"));
+    recorded.start_recording();
+    recorded.execute(SCRIPT).unwrap();
+    recorded.save_recording(path).unwrap();
+
+    let mut replayed = Lox::new();
+    replayed.redirect_input(Cursor::new("something else\n"));
+    replayed.replay(path).unwrap();
+    replayed.execute(SCRIPT).unwrap();
+
+    assert!(matches!(
+        replayed.get_global("first"),
+        Some(Value::String(line)) if line == "first line"
+    ));
+    assert_eq!(
+        format!("{:?}", globals(&replayed)),
+        format!("{:?}", globals(&recorded))
+    );
+
+    let error = replayed.execute("clock();").unwrap_err().to_string();
+    assert!(
+        error.contains("replay ran past the end of the trace."),
+        "{error}"
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn replay_stops_where_the_script_diverges() {
+    let path = std::env::temp_dir().join("jlox-replay-diverged.txt");
+    let path = path.to_str().unwrap();
+
+    let mut recorded = Lox::new();
+    recorded.start_recording();
+    recorded.execute("clock();").unwrap();
+    recorded.save_recording(path).unwrap();
+
+    let mut replayed = Lox::new();
+    replayed.replay(path).unwrap();
+    let error = replayed
+        .execute("stdin.readLine();")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("the recorded session called clock here."),
+        "{error}"
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn replays_the_files_a_session_read() {
+    let data = std::env::temp_dir().join("jlox-replay-data.bin");
+    std::fs::write(&data, b"GIF\x89 \"quoted\"\n\\").unwrap();
+    let script = format!("var data = readBytes({:?});", data.to_str().unwrap());
+    let path = std::env::temp_dir().join("jlox-replay-bytes.txt");
+    let path = path.to_str().unwrap();
+
+    let mut recorded = Lox::new();
+    recorded.grant(Capability::Read);
+    recorded.start_recording();
+    recorded.execute(&script).unwrap();
+    recorded.save_recording(path).unwrap();
+    std::fs::remove_file(&data).unwrap();
+
+    let mut replayed = Lox::new();
+    replayed.grant(Capability::Read);
+    replayed.replay(path).unwrap();
+    replayed.execute(&script).unwrap();
+    assert!(matches!(
+        replayed.get_global("data"),
+        Some(Value::Bytes(bytes)) if bytes == b"GIF\x89 \"quoted\"\n\\"
+    ));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn saving_requires_a_recording() {
+    let mut lox = Lox::new();
+    let path = std::env::temp_dir().join("jlox-replay-unused.txt");
+    assert!(matches!(
+        lox.save_recording(path.to_str().unwrap()),
+        Err(Error::NotRecording)
+    ));
+}
+
+#[test]
+fn traces_only_hold_inputs() {
+    assert!(matches!(
+        Trace::parse("jlox-snapshot 1\n"),
+        Err(Error::Malformed { line: 1, .. })
+    ));
+    assert!(matches!(
+        Trace::parse("jlox-trace 1\nnumber clock 1.5\nfun f 1\nfun f() {}\n"),
+        Err(Error::Malformed { line: 3, .. })
+    ));
+}
@@ -0,0 +1,76 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+#[test]
+fn functions_can_refer_to_globals_defined_after_them() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun isEven(n) {
+           if (n == 0) return true;
+           return isOdd(n - 1);
+         }
+         fun isOdd(n) {
+           if (n == 0) return false;
+           return isEven(n - 1);
+         }
+         fun describe() {
+           return label;
+         }
+         var label = \"late\";",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("isEven(10)").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(lox.eval_expression("isOdd(7)").unwrap(), Value::Bool(true));
+    assert_eq!(
+        lox.eval_expression("describe()").unwrap(),
+        Value::String("late".into())
+    );
+}
+
+#[test]
+fn locals_resolve_to_the_innermost_declaration() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var seen;
+         {
+           var a = \"outer\";
+           {
+             var a = \"inner\";
+             fun show() {
+               return a;
+             }
+             seen = show();
+           }
+         }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("seen"), Some(Value::String("inner".into())));
+}
+
+#[test]
+fn same_names_on_one_line_resolve_separately() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var seen; { var a = \"o\"; { fun f() { return a; } var a = \"i\"; seen = f() + a; } }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("seen"), Some(Value::String("oi".into())));
+}
+
+#[test]
+fn reading_a_local_in_its_own_initializer_is_an_error() {
+    assert!(matches!(
+        run_source("{ var a = 1; { var a = a; } }"),
+        Err(LoxError::Resolve(_))
+    ));
+    // At the top level the name is a global, which is checked at runtime.
+    assert!(matches!(
+        run_source("var b = b;"),
+        Err(LoxError::Runtime(_))
+    ));
+}
@@ -0,0 +1,45 @@
+use jlox::{Lox, Value};
+
+#[test]
+fn nested_functions_return_to_their_own_caller() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun outer() {
+           var log = \"\";
+           {
+             fun inner(n) {
+               while (true) {
+                 if (n > 2) return;
+                 n = n + 1;
+               }
+             }
+             log = log + \"a\";
+             if (inner(0) == nil) log = log + \"b\";
+           }
+           for (var i = 0; i < 10; i = i + 1) {
+             if (i == 1) return log + \"c\";
+           }
+           return \"unreachable\";
+         }
+         var result = outer();",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("result"), Some(Value::String("abc".into())));
+}
+
+#[test]
+fn bare_semicolons_are_empty_statements() {
+    let mut lox = Lox::new();
+    lox.execute(
+        ";
+         fun nothing() { ; return; ; }
+         var count = 0;
+         while (count < 3) count = count + 1;;
+         if (true) ; else count = 10;",
+    )
+    .unwrap();
+
+    assert_eq!(lox.eval_expression("nothing()").unwrap(), Value::Nil);
+    assert_eq!(lox.get_global("count"), Some(Value::Number(3.0)));
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use jlox::{parse_source, selftest};
+
+#[test]
+fn generated_programs_are_reproducible_and_parse() {
+    for seed in 0..200 {
+        let program = selftest::generate(seed);
+        assert_eq!(program, selftest::generate(seed));
+
+        if let Err(error) = parse_source(&program) {
+            panic!("seed {seed} doesn't parse: {error}\n{program}");
+        }
+    }
+}
+
+#[test]
+fn corpus_lists_lox_files_in_order() {
+    let corpus = selftest::corpus(Path::new("tests/corpus")).unwrap();
+    let names: Vec<_> = corpus
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap())
+        .collect();
+
+    assert_eq!(
+        names,
+        [
+            "arithmetic.lox",
+            "classes.lox",
+            "closures.lox",
+            "constants.lox",
+            "errors.lox"
+        ]
+    );
+}
+
+#[test]
+fn known_divergences_are_rewritten_for_the_vm() {
+    assert_eq!(
+        selftest::for_vm("class Sub > Base {\n  f() { return 1 > 2; }\n}\nprint class_ > 1;\n"),
+        "class Sub < Base {\n  f() { return 1 > 2; }\n}\nprint class_ > 1;\n"
+    );
+}
+
+/// Builds the VM from `../clox` so the backends can be compared, or returns
+/// `None` where there's no C compiler.
+fn build_vm() -> Option<PathBuf> {
+    let sources: Vec<PathBuf> = fs::read_dir("../clox/src")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+        .collect();
+    let vm = Path::new(env!("CARGO_TARGET_TMPDIR")).join("clox");
+
+    match Command::new("cc")
+        .args(["-O2", "-w", "-o"])
+        .arg(&vm)
+        .args(&sources)
+        .status()
+    {
+        Ok(status) => {
+            assert!(status.success(), "clox doesn't build");
+            Some(vm)
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            eprintln!("no C compiler, skipping");
+            None
+        }
+        Err(error) => panic!("{error}"),
+    }
+}
+
+#[test]
+fn backends_agree_on_corpus_and_generated_programs() {
+    let Some(vm) = build_vm() else { return };
+    let jlox = Path::new(env!("CARGO_BIN_EXE_jlox"));
+
+    for program in selftest::corpus(Path::new("tests/corpus")).unwrap() {
+        if let Some(mismatch) = selftest::compare(jlox, &vm, &program).unwrap() {
+            panic!("{}:\n{mismatch}", program.display());
+        }
+    }
+
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("selftest");
+    fs::create_dir_all(&directory).unwrap();
+    for seed in 0..50 {
+        let program = directory.join(format!("seed-{seed}.lox"));
+        fs::write(&program, selftest::generate(seed)).unwrap();
+        if let Some(mismatch) = selftest::compare(jlox, &vm, &program).unwrap() {
+            panic!("{}:\n{mismatch}", program.display());
+        }
+    }
+}
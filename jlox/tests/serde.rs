@@ -0,0 +1,52 @@
+#![cfg(feature = "serde")]
+
+use jlox::object::Object;
+use jlox::types::Gc;
+use jlox::{native_fn, Value};
+
+native_fn!(Noop = "noop", |_, ()| Ok(()));
+
+#[test]
+fn plain_data_round_trips_through_json() {
+    let json = r#"{"name":"lox","scores":[1.0,2.5],"ok":true,"none":null}"#;
+    let object: Object = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        object.to_value(),
+        Value::Map(vec![
+            (Value::String("name".into()), Value::String("lox".into())),
+            (
+                Value::String("scores".into()),
+                Value::List(vec![Value::Number(1.0), Value::Number(2.5)])
+            ),
+            (Value::String("ok".into()), Value::Bool(true)),
+            (Value::String("none".into()), Value::Nil),
+        ])
+    );
+    assert_eq!(serde_json::to_string(&object).unwrap(), json);
+}
+
+#[test]
+fn lists_and_tuples_round_trip() {
+    let number = |n| Gc::new(Object::Number(jlox::types::Number(n)));
+    let pair = Object::Tuple(vec![number(1.0), number(2.0)]);
+    let list = Object::List(vec![Gc::new(pair), number(3.0)]);
+
+    let json = serde_json::to_string(&list).unwrap();
+    assert_eq!(json, r#"[{"$tuple":[1.0,2.0]},3.0]"#);
+    let parsed: Object = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.to_value(), list.to_value());
+}
+
+#[test]
+fn lists_serialize_as_sequences_and_functions_not_at_all() {
+    let list = Object::List(vec![
+        Gc::new(Object::Number(jlox::types::Number(1.0))),
+        Gc::new(Object::String("a".into())),
+    ]);
+    assert_eq!(serde_json::to_string(&list).unwrap(), r#"[1.0,"a"]"#);
+
+    let function = Object::List(vec![Gc::new(Object::Function(Gc::new(Noop)))]);
+    let error = serde_json::to_string(&function).unwrap_err();
+    assert!(error.to_string().contains("can't be serialized"), "{error}");
+}
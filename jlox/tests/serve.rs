@@ -0,0 +1,167 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+    thread,
+    time::Duration,
+};
+
+struct Server(Child, u16);
+
+impl Server {
+    fn start(time_limit: &str) -> Self {
+        Self::start_with(&["--time-limit", time_limit, "--memory-limit", "16"])
+    }
+
+    fn start_with(args: &[&str]) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let child = Command::new(env!("CARGO_BIN_EXE_jlox"))
+            .args(["serve", "--port", &port.to_string()])
+            .args(args)
+            .spawn()
+            .unwrap();
+        Self(child, port)
+    }
+
+    fn connect(&self) -> TcpStream {
+        (0..100)
+            .find_map(|_| {
+                TcpStream::connect(("127.0.0.1", self.1))
+                    .inspect_err(|_| thread::sleep(Duration::from_millis(50)))
+                    .ok()
+            })
+            .expect("the server didn't listen")
+    }
+
+    /// The status line and body of the answer to `POST /run`.
+    fn run(&self, source: &str) -> (String, String) {
+        let mut stream = self.connect();
+        write!(
+            stream,
+            "POST /run HTTP/1.1\r\nContent-Length: {}\r\n\r\n{source}",
+            source.len()
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+#[test]
+fn servers_report_output_and_diagnostics() {
+    let server = Server::start("5000");
+
+    assert_eq!(
+        server.run("write(\"a\\b\");\nstderr.write(1);"),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            r#"{"ok": true, "exit_status": 0, "stdout": "a\\b", "stderr": "1", "diagnostics": []}"#
+                .to_string()
+        )
+    );
+
+    let (_, body) = server.run("write(1);\nnil();");
+    assert!(
+        body.starts_with(r#"{"ok": false, "exit_status": 70, "stdout": "1""#),
+        "{body}"
+    );
+    assert!(body.contains("Object is not callable: Nil"), "{body}");
+
+    let (_, body) = server.run("var x = ;");
+    assert!(body.contains(r#""exit_status": 65"#), "{body}");
+}
+
+#[test]
+fn servers_sandbox_scripts() {
+    let server = Server::start("200");
+
+    let (_, body) = server.run("readBytes(\"/etc/passwd\");");
+    assert!(body.contains("requires the 'read' capability"), "{body}");
+
+    let (_, body) = server.run("while (true) {}");
+    assert!(body.contains("Time limit of 200ms exceeded."), "{body}");
+
+    // Slow to fill in debug builds.
+    let server = Server::start("30000");
+    let (_, body) = server.run(
+        "class Node { init(next, data) { this.next = next; this.data = data; } }
+         var kilobyte = \"x\";
+         for (var i = 0; i < 10; i = i + 1) kilobyte = kilobyte + kilobyte;
+         var head = nil;
+         while (true) head = Node(head, kilobyte + \"!\");",
+    );
+    assert!(
+        body.contains("Memory limit of 16777216 bytes exceeded."),
+        "{body}"
+    );
+
+    let (_, body) = server.run("print stdin.readLine();");
+    assert!(body.contains(r#""stdout": "Nil\n""#), "{body}");
+}
+
+#[test]
+fn servers_stop_scripts_that_nest_too_deeply_for_the_stack() {
+    let server = Server::start("5000");
+    let (open, close) = ("(".repeat(190), ")".repeat(190));
+    let (_, body) = server.run(&format!(
+        "fun f(n) {{ return {open} f(n + 1) {close}; }} f(0);"
+    ));
+    assert!(body.contains("Stack overflow."), "{body}");
+
+    let (status, _) = server.run("print 1;");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+}
+
+#[test]
+fn servers_hold_tasks_to_the_request() {
+    let server = Server::start("200");
+
+    let (_, body) = server.run("fun hello() { write(\"hi\"); }\nspawn(hello).join();");
+    assert!(body.contains(r#""stdout": "hi""#), "{body}");
+
+    let (_, body) = server.run("fun spin() { while (true) {} }\nspawn(spin).join();");
+    assert!(body.contains("Time limit of 200ms exceeded."), "{body}");
+
+    let (_, body) = server
+        .run("var inbox = channel();\nfun wait() { return inbox.recv(); }\nspawn(wait).join();");
+    assert!(body.contains("Time limit of 200ms exceeded."), "{body}");
+
+    // Slow to fill in debug builds.
+    let server = Server::start("30000");
+    let (_, body) = server.run(
+        "fun grow() {
+           var kilobyte = \"x\";
+           for (var i = 0; i < 10; i = i + 1) kilobyte = kilobyte + kilobyte;
+           var list = nil;
+           while (true) list = (list, kilobyte + \"!\");
+         }
+         spawn(grow).join();",
+    );
+    assert!(
+        body.contains("Memory limit of 16777216 bytes exceeded."),
+        "{body}"
+    );
+}
+
+#[test]
+fn servers_turn_away_connections_past_the_limit() {
+    let server = Server::start_with(&["--max-connections", "1"]);
+    let _idle = server.connect();
+    // Accepted in order, so the idle connection holds the only slot.
+    let (status, body) = server.run("print 1;");
+    assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+    assert!(body.contains("Too many requests at once."), "{body}");
+}
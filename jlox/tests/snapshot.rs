@@ -0,0 +1,119 @@
+use jlox::snapshot::{Entry, Error, Snapshot};
+use jlox::{Lox, Value};
+
+#[test]
+fn snapshots_round_trip_globals_functions_and_classes() {
+    let path = std::env::temp_dir().join("jlox-snapshot-round-trip.snap");
+    let path = path.to_str().unwrap();
+
+    let mut lox = Lox::new();
+    lox.execute(
+        "var count = 3;
+var name = \"multi
+line\";
+var flag = true;
+var nothing = nil;
+var data = b\"GIF\\x89\";
+var table = #{\"xs\": #[1, (2, \"two\"), #[]], (1, 2): #{}};
+fun twice(x) { return x * 2; }
+class Shape {
+  init(sides) { this.sides = sides; }
+  corners() { return this.sides; }
+}
+class Square > Shape {
+  init() { super.init(4); }
+}
+var square = Square();
+var shapes = #[square];",
+    )
+    .unwrap();
+    lox.save_snapshot(path).unwrap();
+
+    let mut restored = Lox::new();
+    restored.load_snapshot(path).unwrap();
+
+    for name in ["count", "name", "flag", "nothing", "data", "table"] {
+        assert_eq!(restored.get_global(name), lox.get_global(name), "{name}");
+    }
+    assert_eq!(
+        restored.eval_expression("twice(count)").unwrap(),
+        Value::Number(6.0)
+    );
+    assert_eq!(
+        restored.eval_expression("Square().corners()").unwrap(),
+        Value::Number(4.0)
+    );
+    // Instances can't be rebuilt from source, so they're left out.
+    assert_eq!(restored.get_global("square"), None);
+    assert_eq!(restored.get_global("shapes"), None);
+}
+
+#[test]
+fn snapshots_parse_what_they_print() {
+    let mut snapshot = Snapshot::default();
+    snapshot.push("n".into(), Entry::Number(1.5));
+    snapshot.push("s".into(), Entry::String("a\nb".into()));
+    snapshot.push("f".into(), Entry::Function("fun f() {}".into()));
+    snapshot.push("b".into(), Entry::Bytes(b"a b\n\"".to_vec()));
+    snapshot.push(
+        "m".into(),
+        Entry::Map(vec![(
+            Entry::Tuple(vec![Entry::Nil, Entry::Bool(true)]),
+            Entry::List(vec![Entry::String("x\ny".into()), Entry::List(vec![])]),
+        )]),
+    );
+
+    let parsed = Snapshot::parse(&snapshot.to_string()).unwrap();
+    assert_eq!(parsed, snapshot);
+
+    assert!(matches!(
+        Snapshot::parse("not a snapshot\n"),
+        Err(Error::Malformed { line: 1, .. })
+    ));
+    assert!(matches!(
+        Snapshot::parse("jlox-snapshot 1\nstring s 3\nonly one line\n"),
+        Err(Error::Malformed { line: 2, .. })
+    ));
+}
+
+#[test]
+fn collections_only_restore_plain_data() {
+    let path = std::env::temp_dir().join("jlox-snapshot-nested-fun.snap");
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "jlox-snapshot 1\nlist fs 1\nfun 0 1\nfun f() {}\n").unwrap();
+
+    let error = Lox::new().load_snapshot(path).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Snapshot entry 'fs' can't be restored: a fun can only be a global."
+    );
+}
+
+#[test]
+fn builtin_constants_are_left_to_the_restoring_interpreter() {
+    let path = std::env::temp_dir().join("jlox-snapshot-builtins.snap");
+    let path = path.to_str().unwrap();
+
+    let mut lox = Lox::new();
+    lox.execute("var limit = infinity;").unwrap();
+    lox.save_snapshot(path).unwrap();
+
+    let snapshot = Snapshot::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let names: Vec<&str> = snapshot
+        .entries()
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(names, ["limit"]);
+
+    let mut restored = Lox::new();
+    restored.load_snapshot(path).unwrap();
+    assert_eq!(
+        restored.get_global("limit"),
+        Some(Value::Number(f64::INFINITY))
+    );
+    assert_eq!(
+        restored.eval_expression("nan == nan").unwrap(),
+        Value::Bool(false)
+    );
+}
@@ -0,0 +1,84 @@
+//! Golden-file tests for the parser and the pretty-printer.
+//!
+//! Every `tests/snapshots/<name>.lox` is parsed; its AST dump is compared
+//! against `<name>.ast` and its pretty-printed source against
+//! `<name>.printed`. Run with `UPDATE_SNAPSHOTS=1` to rewrite the snapshots
+//! after an intended change, then review the diff.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use jlox::{ast::Stmt, parse_source, printer::Printer};
+
+fn dump(statements: &[Stmt]) -> String {
+    statements.iter().map(|stmt| format!("{stmt}\n")).collect()
+}
+
+fn print(statements: &[Stmt]) -> String {
+    statements
+        .iter()
+        .map(|stmt| Printer::new().print_stmt(stmt.clone()) + "\n")
+        .collect()
+}
+
+/// Compares `actual` with the snapshot at `path`, or writes it when
+/// updating. Returns a description of the mismatch, if any.
+fn check(path: PathBuf, actual: &str, update: bool) -> Option<String> {
+    if update {
+        fs::write(&path, actual).unwrap();
+        return None;
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(expected) if expected == actual => None,
+        Ok(expected) => Some(format!(
+            "{} changed:\n--- expected\n{expected}--- actual\n{actual}",
+            path.display()
+        )),
+        Err(_) => Some(format!(
+            "{} is missing; run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )),
+    }
+}
+
+#[test]
+fn parser_and_printer_match_snapshots() {
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+
+    let mut inputs: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no inputs in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for input in inputs {
+        let source = fs::read_to_string(&input).unwrap();
+        let (ast, printed) = match parse_source(&source) {
+            Ok(statements) => (dump(&statements), Some(print(&statements))),
+            Err(error) => (format!("error: {error}\n"), None),
+        };
+
+        failures.extend(check(input.with_extension("ast"), &ast, update));
+
+        if let Some(printed) = printed {
+            // Printed source must parse back to the same tree.
+            match parse_source(&printed) {
+                Ok(reparsed) if dump(&reparsed) == ast => (),
+                _ => failures.push(format!(
+                    "{} doesn't round-trip through the printer:\n{printed}",
+                    input.display()
+                )),
+            }
+            failures.extend(check(input.with_extension("printed"), &printed, update));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,17 @@
+use jlox::Lox;
+
+#[test]
+fn counts_statements_calls_and_environments() {
+    let mut lox = Lox::new();
+    lox.execute("fun twice(n) { var m = n * 2; return m; } var a = twice(1); var b = twice(2);")
+        .unwrap();
+
+    let stats = lox.stats();
+    // Three top-level statements plus two per call.
+    assert_eq!(stats.statements, 7);
+    assert_eq!(stats.calls, 2);
+    // The globals and one environment per call.
+    assert_eq!(stats.environments, 3);
+    // The test binary doesn't install the counting allocator.
+    assert_eq!(stats.allocations, 0);
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use jlox::{streams::OutputStream, Lox};
+
+/// Runs `script` with `input` on stdin and returns what it wrote to stdout
+/// and stderr.
+fn run(name: &str, script: &str, input: &str) -> (String, String) {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, script).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jlox"))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn scripts_filter_stdin_to_stdout() {
+    let script = "
+        var line = stdin.readLine();
+        while (line != nil) {
+          stdout.write(len(line));
+          stdout.write(b\"\\x0a\");
+          line = stdin.readLine();
+        }
+        stderr.write(\"done\");
+        stdout.flush();
+    ";
+
+    assert_eq!(
+        run("jlox-streams-lines.lox", script, "one\r\nthree\n\nlast"),
+        ("3\n5\n0\n4\n".to_string(), "done".to_string())
+    );
+}
+
+#[test]
+fn write_prints_partial_lines() {
+    let script = "
+        for (var i = 1; i <= 3; i = i + 1) {
+          write(b\"\\x0d\");
+          write(i * 10);
+          write(\"%\");
+        }
+        write(b\"\\x0a\");
+    ";
+
+    assert_eq!(
+        run("jlox-streams-write.lox", script, "").0,
+        "\r10%\r20%\r30%\n"
+    );
+}
+
+#[test]
+fn read_takes_the_rest_of_stdin() {
+    let script = "
+        stdin.readLine();
+        stdout.write(stdin.read());
+        stdout.write(stdin.read() == \"\");
+    ";
+
+    assert_eq!(
+        run("jlox-streams-rest.lox", script, "skip\nkeep\nthis\n").0,
+        "keep\nthis\ntrue"
+    );
+}
+
+/// Collects what a script writes, shared with the test.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn hosts_redirect_output() {
+    let (out, err) = (Capture::default(), Capture::default());
+    let mut lox = Lox::new();
+    lox.redirect_output(OutputStream::Stdout, out.clone());
+    lox.redirect_output(OutputStream::Stderr, err.clone());
+
+    lox.execute("print 1;\nwrite(\"two\");\nstderr.write(3);")
+        .unwrap();
+    lox.restore_output(OutputStream::Stdout);
+    lox.execute("print 4;").unwrap();
+
+    assert_eq!(out.0.lock().unwrap().as_slice(), b"Number(1.0)\ntwo");
+    assert_eq!(err.0.lock().unwrap().as_slice(), b"3");
+}
@@ -0,0 +1,78 @@
+use jlox::{dialect::Dialect, settings::Settings, Lox};
+
+fn strict() -> Lox {
+    let mut lox = Lox::new();
+    lox.set_settings(Settings::strict());
+    lox
+}
+
+fn error(lox: &mut Lox, source: &str) -> String {
+    lox.execute(source).unwrap_err().to_string()
+}
+
+#[test]
+fn strict_mode_rejects_likely_mistakes() {
+    let mut lox = strict();
+    lox.execute("var total; fun reset() { total = 0; }")
+        .unwrap();
+    assert_eq!(
+        error(&mut lox, "var x = total;"),
+        "Error: Variable 'total' is read before it is assigned [line 1]."
+    );
+    lox.execute("reset(); var x = total;").unwrap();
+
+    assert_eq!(
+        error(&mut lox, "fun f(n) { { var n = 1; } }"),
+        "'n' shadows a variable of an enclosing scope [line 1]."
+    );
+
+    // Later top-level declarations, natives and earlier globals are fine.
+    lox.execute("fun g() { return later + clock() + x; } var later = 1;")
+        .unwrap();
+    assert_eq!(
+        error(&mut lox, "fun h() {\n  return totl;\n}"),
+        "Undefined variable 'totl' [line 2]."
+    );
+
+    // None of it applies outside strict mode.
+    Lox::new()
+        .execute("var y; var z = y; fun h(n) { { var n; } return totl; }")
+        .unwrap();
+}
+
+#[test]
+fn strict_mode_bans_using_print_results() {
+    let mut lox = strict();
+    lox.set_dialect(Dialect::default().with_print_function());
+    lox.redirect_output(jlox::streams::OutputStream::Stdout, std::io::sink());
+
+    lox.execute("print(1);").unwrap();
+    assert_eq!(
+        error(&mut lox, "var x = print(1);"),
+        "Can't use the result of 'print', it is always nil [line 1]."
+    );
+}
+
+#[test]
+fn strict_errors_in_files_are_static_diagnostics() {
+    let path = std::env::temp_dir().join(format!("jlox-strict-{}.lox", std::process::id()));
+    std::fs::write(&path, "print 1;\nfun f(n) {\n  { var n; }\n}\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_jlox"))
+        .arg("--strict")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Nothing runs: the error is found before the file does.
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "{}:3: 'n' shadows a variable of an enclosing scope.\n",
+            path.display()
+        )
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
@@ -0,0 +1,38 @@
+use jlox::{math::MathMode, Lox};
+
+fn run(mode: MathMode, source: &str) -> Result<(), String> {
+    let mut lox = Lox::new();
+    lox.set_math_mode(mode);
+    lox.redirect_output(jlox::streams::OutputStream::Stdout, std::io::sink());
+    lox.execute(source)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+#[test]
+fn strict_math_reports_where_infinities_and_nans_appear() {
+    let source = "var big = 1e308;\nvar huge = big * 10;";
+    assert_eq!(run(MathMode::Ieee, source), Ok(()));
+    assert_eq!(
+        run(MathMode::Strict, source),
+        Err("Error: 1e308 * 10 is inf [line 2]".to_string())
+    );
+
+    // Values that are already infinite or NaN pass through...
+    assert_eq!(run(MathMode::Strict, "var x = infinity + 1;"), Ok(()));
+    assert_eq!(run(MathMode::Strict, "var x = nan * 2;"), Ok(()));
+    // ...but NaN made from infinities is reported where it appears.
+    assert_eq!(run(MathMode::Ieee, "var x = infinity - infinity;"), Ok(()));
+    assert_eq!(
+        run(MathMode::Strict, "var x = infinity - infinity;"),
+        Err("Error: inf - inf is NaN [line 1]".to_string())
+    );
+    assert_eq!(
+        run(MathMode::Strict, "var x = 0 * infinity;"),
+        Err("Error: 0 * inf is NaN [line 1]".to_string())
+    );
+    assert_eq!(
+        run(MathMode::Strict, "var x = 1 / 0;"),
+        Err("Error: Division by zero".to_string())
+    );
+}
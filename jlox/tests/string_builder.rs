@@ -0,0 +1,52 @@
+use jlox::{limits::Limits, Lox, Value};
+
+#[test]
+fn string_builder_appends_the_text_of_values() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var sb = StringBuilder();
+         for (var i = 0; i < 3; i = i + 1) {
+           sb.append(i);
+           sb.append(\",\");
+         }
+         sb.append(nil);",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("sb.toString()").unwrap(),
+        Value::String("0,1,2,nil".into())
+    );
+    assert_eq!(
+        lox.eval_expression("sb.length").unwrap(),
+        Value::Number(9.0)
+    );
+}
+
+#[test]
+fn concatenation_chains_keep_their_operands() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_string_length: 8,
+        ..Limits::default()
+    });
+    lox.execute("var a = \"ab\"; var b = a + \"cd\" + \"ef\";")
+        .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("a").unwrap(),
+        Value::String("ab".into())
+    );
+    assert_eq!(
+        lox.eval_expression("b").unwrap(),
+        Value::String("abcdef".into())
+    );
+
+    let error = lox
+        .execute("var sb = StringBuilder(); sb.append(b); sb.append(b);")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Error: StringBuilder.append: strings can't be longer than 8 bytes."
+    );
+}
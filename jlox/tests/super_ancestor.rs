@@ -0,0 +1,39 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn super_with_an_ancestor_starts_the_lookup_there() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class A {
+  name() { return \"A\"; }
+  greet() { return \"A greets \" + this.name(); }
+}
+class B > A {
+  greet() { return \"B\"; }
+}
+class C > B {
+  name() { return \"C\"; }
+  greet() { return super(A).greet() + \", \" + super.greet() + \", \" + super(B).greet(); }
+  pick() { return super(A).name; }
+}
+var greeting = C().greet();
+var picked = C().pick()();",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.get_global("greeting"),
+        Some(Value::String("A greets C, B, B".into()))
+    );
+    assert_eq!(lox.get_global("picked"), Some(Value::String("A".into())));
+
+    lox.execute("class D > A { greet() { return super(C).greet(); } }")
+        .unwrap();
+    match lox.eval_expression("D().greet()") {
+        Err(LoxError::Runtime(error)) => assert_eq!(
+            error.to_string(),
+            "'C' is not an ancestor of this class [line 1]."
+        ),
+        result => panic!("expected a runtime error, got {result:?}"),
+    }
+}
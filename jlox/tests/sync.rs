@@ -0,0 +1,54 @@
+#![cfg(feature = "sync")]
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use jlox::host::ClassBuilder;
+use jlox::{native_fn, Lox, Value};
+
+native_fn!(Double = "double", |_, (x: f64)| Ok(x * 2.0));
+
+struct Tally(f64);
+
+#[test]
+fn sessions_move_between_threads_with_their_natives_and_classes() {
+    let mut lox = Lox::new();
+    lox.define_native("double", Double);
+    lox.register_class(
+        ClassBuilder::<Tally>::new("Tally")
+            .constructor(|(): ()| Tally(0.0))
+            .method("add", |tally, (n,): (f64,)| {
+                tally.0 += n;
+                tally.0
+            })
+            .build(),
+    );
+    lox.execute("var tally = Tally(); var total = 0;").unwrap();
+
+    let lox = Arc::new(Mutex::new(lox));
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let lox = Arc::clone(&lox);
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    lox.lock()
+                        .unwrap()
+                        .execute("total = total + double(1); tally.add(1);")
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    workers
+        .into_iter()
+        .for_each(|worker| worker.join().unwrap());
+
+    let mut lox = thread::spawn(move || Arc::into_inner(lox).unwrap().into_inner().unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(lox.get_global("total"), Some(Value::Number(200.0)));
+    assert_eq!(
+        lox.eval_expression("tally.add(0)").unwrap(),
+        Value::Number(100.0)
+    );
+}
@@ -0,0 +1,67 @@
+use jlox::dialect::Dialect;
+use jlox::settings::Settings;
+use jlox::{native_fn, Lox, Value};
+
+native_fn!(Secret = "secret", |_, ()| Ok(42.0));
+
+#[test]
+fn tasks_pass_messages_and_return_results() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "var inbox = channel();
+fun double() {
+  var total = 0;
+  var n = inbox.recv();
+  while (n != nil) {
+    total = total + n * 2;
+    n = inbox.recv();
+  }
+  return total;
+}
+var task = spawn(double);
+inbox.send(1);
+inbox.send(2);
+inbox.close();
+var result = task.join();",
+    )
+    .unwrap();
+    assert!(matches!(lox.get_global("result"), Some(Value::Number(n)) if n == 6.0));
+}
+
+#[test]
+fn host_natives_fail_in_tasks_with_their_name() {
+    let mut lox = Lox::new();
+    lox.define_native("secret", Secret);
+    lox.execute("fun peek() { return secret(); }").unwrap();
+
+    let error = lox.execute("spawn(peek).join();").unwrap_err().to_string();
+    assert!(
+        error.contains("'secret' is defined by the host and isn't available in spawned tasks."),
+        "{error}"
+    );
+}
+
+#[test]
+fn tasks_keep_the_session_settings() {
+    let mut lox = Lox::new();
+    lox.set_settings(Settings {
+        forbid_uninitialized_reads: true,
+        ..Settings::default()
+    });
+    lox.execute("fun read() { var x; return x; }").unwrap();
+
+    let error = lox.execute("spawn(read).join();").unwrap_err().to_string();
+    assert!(error.contains("read before it is assigned"), "{error}");
+}
+
+#[test]
+fn tasks_parse_with_the_session_dialect() {
+    let mut lox = Lox::new();
+    lox.set_dialect(Dialect::standard().clone().with_print_function());
+    lox.execute(
+        "fun quiet() { var shown = print(\"\"); return shown == nil; }
+var result = spawn(quiet).join();",
+    )
+    .unwrap();
+    assert!(matches!(lox.get_global("result"), Some(Value::Bool(true))));
+}
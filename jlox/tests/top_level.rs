@@ -0,0 +1,46 @@
+use jlox::{interpreter::TopLevel, Lox, LoxError, Value};
+
+#[test]
+fn script_top_level_declarations_are_locals() {
+    let mut lox = Lox::new();
+    lox.execute("var out;").unwrap();
+
+    lox.set_top_level(TopLevel::Script);
+    lox.execute(
+        "var base = 2;
+         fun twice(x) {
+           return x * base;
+         }
+         out = twice(3);",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("out"), Some(Value::Number(6.0)));
+    assert_eq!(lox.get_global("base"), None);
+    assert_eq!(lox.get_global("twice"), None);
+
+    lox.set_top_level(TopLevel::Interactive);
+    lox.execute("var base = 5;").unwrap();
+    assert_eq!(lox.get_global("base"), Some(Value::Number(5.0)));
+}
+
+#[test]
+fn scripts_only_see_functions_declared_before() {
+    let mut lox = Lox::new();
+    lox.set_top_level(TopLevel::Script);
+
+    let error = lox
+        .execute(
+            "fun even(n) {
+               if (n == 0) return true;
+               return odd(n - 1);
+             }
+             fun odd(n) {
+               if (n == 0) return false;
+               return even(n - 1);
+             }
+             even(2);",
+        )
+        .unwrap_err();
+    assert!(matches!(error, LoxError::Runtime(_)), "{error}");
+}
@@ -0,0 +1,66 @@
+use jlox::{javascript, parse_source, transpile};
+
+/// The checked-in translation, compiled into this test.
+mod closures {
+    include!("transpile/closures.rs");
+
+    #[test]
+    fn transpiled_program_runs() {
+        assert_eq!(fib(Value::Number(10.0)), Ok(Value::Number(55.0)));
+        assert_eq!(main(), std::process::ExitCode::SUCCESS);
+    }
+}
+
+#[test]
+fn translation_matches_the_checked_in_program() {
+    let source = include_str!("transpile/closures.lox");
+    let rust = transpile::to_rust(&parse_source(source).unwrap(), "closures.lox").unwrap();
+    assert_eq!(rust, include_str!("transpile/closures.rs"));
+}
+
+#[test]
+fn javascript_matches_the_checked_in_script() {
+    let source = include_str!("transpile/classes.lox");
+    let js = javascript::to_js(&parse_source(source).unwrap(), "classes.lox").unwrap();
+    assert_eq!(js.code, include_str!("transpile/classes.js"));
+}
+
+#[test]
+fn source_map_points_each_line_at_its_statement() {
+    let statements = parse_source("print 1;\n\nif (true)\n  print 2;").unwrap();
+    let js = javascript::to_js(&statements, "lines.lox").unwrap();
+    let code: Vec<&str> = js.code.lines().collect();
+
+    assert_eq!(
+        code[3..],
+        ["console.log(1);", "if (true) {", "  console.log(2);", "}"]
+    );
+    assert_eq!(js.lines[3..], [Some(1), Some(3), Some(4), Some(3)]);
+    assert_eq!(
+        js.source_map("lines.js"),
+        "{\"version\":3,\"file\":\"lines.js\",\"sources\":[\"lines.lox\"],\"names\":[],\"mappings\":\";;;AAAA;AAEA;AACA;AADA\"}\n"
+    );
+}
+
+#[test]
+fn dynamic_features_are_refused_with_their_line() {
+    let refusal = |source: &str| {
+        let statements = parse_source(source).unwrap();
+        transpile::to_rust(&statements, "script.lox")
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        refusal("print 1;\nclass Point {}"),
+        "Can't transpile classes. [line 2]"
+    );
+    assert_eq!(
+        refusal("fun f() {\n  return #[1, 2];\n}"),
+        "Can't transpile lists. [line 2]"
+    );
+    assert_eq!(
+        refusal("print missing;"),
+        "Undefined variable 'missing'. [line 1]"
+    );
+}
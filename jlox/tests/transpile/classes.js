@@ -0,0 +1,63 @@
+// Transpiled from classes.lox by `jlox transpile --js`.
+"use strict";
+
+const $truthy = (value) => value != null && value !== false;
+const $or = (left, right) => ($truthy(left) ? left : right());
+const $get = (object, name) => {
+  const value = object[name];
+  return typeof value === "function" && !Object.hasOwn(object, name)
+    ? value.bind(object)
+    : value;
+};
+
+/** A point on the plane. */
+class Point {
+  init(x, y) {
+    this.x = x;
+    this.y = y;
+    return this;
+  }
+
+  /** Distance from the origin, squared. */
+  norm() {
+    return this.x * this.x + this.y * this.y;
+  }
+
+  translate(dx, dy) {
+    let move = (p) => {
+      return new Point().init(p.x + dx, p.y + dy);
+    };
+
+    return move(this);
+  }
+}
+
+class Labeled extends Point {
+  init(x, y, label) {
+    super.init(x, y);
+    if (label == null) {
+      return this;
+    }
+    this.label = "<" + label + ">";
+    return this;
+  }
+
+  norm() {
+    return super.norm() * 2;
+  }
+}
+
+let p = new Labeled().init(3, 4, "p");
+console.log(p.label);
+let norm = $get(p, "norm");
+console.log(norm());
+console.log(p.translate(1, 1).norm());
+for (let i = 0; i < 3; i = i + 1) {
+  if (i === 0) {
+    continue;
+  } else if (!(i === 1)) {
+    console.log("two");
+  } else {
+    console.log($or(null, () => "one"));
+  }
+}
@@ -0,0 +1,68 @@
+// Transpiled from closures.lox by `jlox transpile`.
+
+use jlox::runtime::*;
+
+/// The nth Fibonacci number.
+fn fib(n: Value) -> Result<Value> {
+    if n.less(&Value::Number(2.0))? {
+        return Ok(n);
+    }
+    fib(n.sub(&Value::Number(1.0))?)?.add(&fib(n.sub(&Value::Number(2.0))?)?)
+}
+
+fn make_counter() -> Result<Value> {
+    let count = Var::new(Value::Number(0.0));
+    let increment = Value::function("increment", 0, {
+        let count = count.clone();
+        move |_| {
+            count.set(count.get().add(&Value::Number(1.0))?);
+            Ok(count.get())
+        }
+    });
+    Ok(increment)
+}
+
+fn main() -> std::process::ExitCode {
+    run(|| {
+        let total = Global::new("total");
+        total.define(Value::Number(0.0));
+        let add = Value::function("add", 1, {
+            let total = total.clone();
+            move |args| {
+                let x = args[0].clone();
+                total.set(total.get()?.add(&x)?)?;
+                Ok(Value::Nil)
+            }
+        });
+        let counter = make_counter()?;
+        counter.call([])?;
+        println!("{:?}", counter.call([])?);
+        let mut third = Value::Nil;
+        {
+            let mut i = Var::new(Value::Number(0.0));
+            while i.get().less(&Value::Number(5.0))? {
+                'body: {
+                    if i.get() == Value::Number(1.0) {
+                        break 'body;
+                    }
+                    let show = Value::function("show", 0, {
+                        let i = i.clone();
+                        move |_| {
+                            Ok(i.get())
+                        }
+                    });
+                    if i.get() == Value::Number(3.0) {
+                        third = show.clone();
+                    }
+                    add.call([fib(i.get())?])?;
+                }
+                i = Var::new(i.get());
+                i.set(i.get().add(&Value::Number(1.0))?);
+            }
+        }
+        println!("{:?}", third.call([])?);
+        println!("{:?}", total.get()?);
+        println!("{:?}", { let left = { let left = Value::Bool(total.get()?.greater(&Value::Number(5.0))?); if left.truthy() { Value::from("big") } else { left } }; if left.truthy() { left } else { Value::from("small") } });
+        Ok(())
+    })
+}
@@ -0,0 +1,44 @@
+use jlox::{Lox, LoxError, Value};
+
+#[test]
+fn instances_decide_their_truthiness_with_is_truthy() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "class Bag {
+           init() { this.size = 0; }
+           isTruthy() { return this.size > 0; }
+         }
+         class Plain {}
+         var bag = Bag();
+         var branch = \"\";
+         if (bag) branch = \"then\"; else branch = \"else\";
+         var loops = 0;
+         while (!bag) { bag.size = bag.size + 1; loops = loops + 1; }",
+    )
+    .unwrap();
+
+    assert_eq!(lox.get_global("branch"), Some(Value::String("else".into())));
+    assert_eq!(lox.get_global("loops"), Some(Value::Number(1.0)));
+    assert_eq!(lox.eval_expression("!bag").unwrap(), Value::Bool(false));
+    assert_eq!(
+        lox.eval_expression("bag and 1").unwrap(),
+        Value::Number(1.0)
+    );
+    assert_eq!(lox.eval_expression("!Bag()").unwrap(), Value::Bool(true));
+    assert_eq!(
+        lox.eval_expression("Bag() or 2").unwrap(),
+        Value::Number(2.0)
+    );
+    assert_eq!(lox.eval_expression("!Plain()").unwrap(), Value::Bool(false));
+}
+
+#[test]
+fn errors_in_is_truthy_propagate() {
+    let mut lox = Lox::new();
+    let result = lox.execute(
+        "class Broken { isTruthy() { return nil + 1; } }
+         if (Broken()) print 1;",
+    );
+
+    assert!(matches!(result, Err(LoxError::Runtime(_))));
+}
@@ -0,0 +1,56 @@
+use jlox::{run_source, Lox, LoxError, Value};
+
+#[test]
+fn tuples_are_built_with_commas_and_read_by_position() {
+    let mut lox = Lox::new();
+    lox.execute(
+        "fun divmod(a, b) {
+           var q = 0;
+           while (a >= b) {
+             a = a - b;
+             q = q + 1;
+           }
+           return (q, a);
+         }
+         var t = (1, \"a\", (true, nil));",
+    )
+    .unwrap();
+
+    assert_eq!(lox.eval_expression("t.0").unwrap(), Value::Number(1.0));
+    assert_eq!(lox.eval_expression("t.2.0").unwrap(), Value::Bool(true));
+    assert_eq!(
+        lox.eval_expression("divmod(7, 2)").unwrap(),
+        Value::Tuple(vec![Value::Number(3.0), Value::Number(1.0)])
+    );
+    assert_eq!(lox.eval_expression("(1,)").unwrap().to_string(), "(1,)");
+    assert_eq!(lox.eval_expression("(1)").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn tuples_compare_by_their_elements() {
+    let mut lox = Lox::new();
+    lox.execute("var t = (1, \"a\", (true, nil));").unwrap();
+
+    assert_eq!(
+        lox.eval_expression("t == (1, \"a\", (true, nil))").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        lox.eval_expression("t == (1, \"a\")").unwrap(),
+        Value::Bool(false)
+    );
+    assert_eq!(
+        lox.eval_expression("(1,) == 1").unwrap(),
+        Value::Bool(false)
+    );
+}
+
+#[test]
+fn tuples_are_immutable_and_bounds_checked() {
+    for source in ["var t = (1, 2); t.2;", "var t = (1, 2); t.0 = 3;"] {
+        assert!(
+            matches!(run_source(source), Err(LoxError::Runtime(_))),
+            "{source}"
+        );
+    }
+}
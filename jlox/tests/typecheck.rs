@@ -0,0 +1,85 @@
+use std::fs;
+
+use jlox::{
+    parse_source, printer::format_source, program::Program, settings::Settings, typecheck::check,
+    Lox, LoxError,
+};
+
+/// The line and message of every mismatch in `source`.
+fn mismatches(source: &str) -> Vec<(usize, String)> {
+    check(&parse_source(source).unwrap())
+        .into_iter()
+        .map(|mismatch| (mismatch.line, mismatch.message))
+        .collect()
+}
+
+#[test]
+fn annotated_values_are_checked_and_the_rest_is_any() {
+    let source = "class Shape {}
+class Square > Shape { init(side: Number) {} }
+fun area(s: Shape) -> Number { return \"big\"; }
+var n: Number = nil;
+n = 1;
+n = \"two\";
+area(Square(\"3\"));
+area(Shape());
+area(n);
+var x = \"free\";
+x = area;
+var bad: Nmber;";
+    assert_eq!(
+        mismatches(source),
+        [
+            (3, "'area' should return Number, not String.".to_string()),
+            (
+                4,
+                "Can't initialize 'n' of type Number with a Nil.".to_string()
+            ),
+            (
+                6,
+                "Can't assign a String to 'n' of type Number.".to_string()
+            ),
+            (
+                7,
+                "Argument 1 to 'Square' should be Number, not String.".to_string()
+            ),
+            (
+                9,
+                "Argument 1 to 'area' should be Shape, not Number.".to_string()
+            ),
+            (12, "Unknown type 'Nmber'.".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn annotations_survive_formatting() {
+    let source =
+        "fun add(a: Number, b) -> Number {\n    var total: Number = a + b;\n    return total;\n}\n";
+    assert_eq!(format_source(source).unwrap(), source);
+}
+
+#[test]
+fn typed_programs_refuse_to_run_with_mismatches() {
+    let path = std::env::temp_dir().join(format!("jlox-typed-{}.lox", std::process::id()));
+    fs::write(&path, "var n: Number = \"one\";\nvar ran = true;\n").unwrap();
+    let program = Program::load([&path]);
+
+    let mut lox = Lox::new();
+    lox.run_program(&program).unwrap();
+    assert!(lox.get_global("ran").is_some());
+
+    let mut lox = Lox::new();
+    lox.set_settings(Settings {
+        forbid_type_mismatches: true,
+        ..Settings::default()
+    });
+    let Err(LoxError::Program(report)) = lox.run_program(&program) else {
+        panic!("expected the mismatch to stop the program");
+    };
+    assert!(report
+        .to_string()
+        .ends_with(":1: Can't initialize 'n' of type Number with a String."));
+    assert!(lox.get_global("ran").is_none());
+    fs::remove_file(path).unwrap();
+}
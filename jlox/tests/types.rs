@@ -0,0 +1,93 @@
+use std::process::Command;
+
+use jlox::{parse_source, typecheck::infer};
+
+#[test]
+fn types_are_inferred_from_what_values_hold_and_return() {
+    let source = "var count = 0;
+var label;
+label = \"none\";
+fun sign(n) { if (n < 0) return -1; else return 1; }
+fun maybe(n) { if (n) return 1; }
+fun twice(s: String) { return s + s; }
+class Box { size() { return 2; } }
+var size = Box().size();
+var (a, b) = (1, 2);
+count = label;
+print -label;
+print label * 2;
+print true + 1;
+label();";
+    let inference = infer(&parse_source(source).unwrap());
+    let declarations: Vec<String> = inference
+        .declarations
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        declarations,
+        [
+            "var count: Any",
+            "var label: String",
+            "fun sign(n: Any) -> Number",
+            "fun maybe(n: Any) -> Any",
+            "fun twice(s: String) -> String",
+            "var size: Number",
+            "var a: Any",
+            "var b: Any",
+        ]
+    );
+    let mismatches: Vec<(usize, String)> = inference
+        .mismatches
+        .into_iter()
+        .map(|mismatch| (mismatch.line, mismatch.message))
+        .collect();
+    assert_eq!(
+        mismatches,
+        [
+            (
+                10,
+                "Can't assign a String to 'count', which holds a Number.".to_string()
+            ),
+            (
+                11,
+                "Operand of '-' must be a number, not a String.".to_string()
+            ),
+            (
+                12,
+                "Operands of '*' must be numbers, not a String.".to_string()
+            ),
+            (13, "Can't add a Bool and a Number.".to_string()),
+            (14, "Can't call a String.".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn types_command_reports_declarations_then_flags() {
+    let path = std::env::temp_dir().join("jlox_types_command.lox");
+    std::fs::write(
+        &path,
+        "var n = 1;\nfun half(x) { return x / 2; }\nn = \"one\";\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jlox"))
+        .arg("types")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let file = path.display();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{file}:1: var n: Any
+{file}:2: fun half(x: Any) -> Number
+{file}:3: Can't assign a String to 'n', which holds a Number.
+"
+        )
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
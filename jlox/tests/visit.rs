@@ -0,0 +1,113 @@
+use jlox::{
+    functions::Callable,
+    interpreter::{Error, Interpreter},
+    object::Object,
+    types::Gc,
+    visit::{Edge, ValueVisitor},
+    Lox, Value,
+};
+
+/// `walk(value)`: an outline of the walk over `value`, a line per value.
+struct Walk;
+
+impl Callable for Walk {
+    type E = Error;
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Gc<Object>>,
+    ) -> Result<Gc<Object>, Error> {
+        let mut outline = Outline::default();
+        arguments[0].visit(&mut outline);
+        Ok(Gc::new(Object::String(outline.lines.join("\n"))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Default)]
+struct Outline {
+    depth: usize,
+    lines: Vec<String>,
+}
+
+impl Outline {
+    fn line(&mut self, edge: Edge<'_>, value: &Object, again: bool) {
+        let edge = match edge {
+            Edge::Root => String::new(),
+            Edge::Element(i) => format!("[{i}] "),
+            Edge::Key => "key ".to_owned(),
+            Edge::Entry(key) => format!("{key}: "),
+            Edge::Class => "class ".to_owned(),
+            Edge::Field(name) => format!(".{name} "),
+            Edge::Superclass => "superclass ".to_owned(),
+            Edge::Method(name) => format!("method {name} "),
+            Edge::Capture(name) => format!("captures {name} "),
+        };
+        let value = match value {
+            Object::Function(_) => "<fn>".to_owned(),
+            value => value.to_string(),
+        };
+        let again = if again { " (again)" } else { "" };
+        self.lines
+            .push(format!("{}{edge}{value}{again}", "  ".repeat(self.depth)));
+    }
+}
+
+impl ValueVisitor for Outline {
+    fn enter(&mut self, edge: Edge<'_>, value: &Object) -> bool {
+        self.line(edge, value, false);
+        self.depth += 1;
+        true
+    }
+
+    fn leave(&mut self, _value: &Object) {
+        self.depth -= 1;
+    }
+
+    fn revisit(&mut self, edge: Edge<'_>, value: &Object) {
+        self.line(edge, value, true);
+    }
+}
+
+#[test]
+fn visits_fields_elements_and_captures_once_each() {
+    let mut lox = Lox::new();
+    lox.define_native("walk", Walk);
+    lox.execute(
+        "class Node {}
+var a = Node();
+a.name = \"a\";
+a.next = a;
+a.items = #[1, #{\"self\": a}];
+fun counter() {
+  var count = 0;
+  fun bump() { count = count + 1; return count; }
+  return bump;
+}
+a.bump = counter();",
+    )
+    .unwrap();
+
+    assert_eq!(
+        lox.eval_expression("walk(a)").unwrap(),
+        Value::String(
+            "Node instance
+  class Node
+  .bump <fn>
+    captures bump <fn> (again)
+    captures count 0
+  .items #[1, #{self: Node instance}]
+    [0] 1
+    [1] #{self: Node instance}
+      key self
+      self: Node instance (again)
+  .name a
+  .next Node instance (again)"
+                .to_owned()
+        )
+    );
+}
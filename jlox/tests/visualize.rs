@@ -0,0 +1,46 @@
+use jlox::{parse_source, visualize::Tree};
+
+const SOURCE: &str = "fun sign(n) {
+  if (n < 0) return -1; else return 1;
+}";
+
+#[test]
+fn dot_labels_nodes_with_the_heads_of_their_dumps() {
+    let tree = Tree::build(&parse_source(SOURCE).unwrap());
+    assert_eq!(
+        tree.to_dot(),
+        "digraph ast {
+  node [shape=box];
+  n0 [label=\"fun sign (n)\", tooltip=\"(fun sign (n)\\n  (if (< n 0)\\n    (return (- 1))\\n    (return 1)))\"];
+  n1 [label=\"if\", tooltip=\"(if (< n 0)\\n  (return (- 1))\\n  (return 1))\"];
+  n2 [label=\"<\", tooltip=\"(< n 0)\"];
+  n3 [label=\"n\", tooltip=\"n\"];
+  n4 [label=\"0\", tooltip=\"0\"];
+  n5 [label=\"return\", tooltip=\"(return (- 1))\"];
+  n6 [label=\"-\", tooltip=\"(- 1)\"];
+  n7 [label=\"1\", tooltip=\"1\"];
+  n8 [label=\"return\", tooltip=\"(return 1)\"];
+  n9 [label=\"1\", tooltip=\"1\"];
+  n0 -> n1;
+  n1 -> n2;
+  n1 -> n5 [label=\"then\"];
+  n1 -> n8 [label=\"else\"];
+  n2 -> n3;
+  n2 -> n4;
+  n5 -> n6;
+  n6 -> n7;
+  n8 -> n9;
+}
+"
+    );
+}
+
+#[test]
+fn html_nests_nodes_in_collapsible_lists() {
+    let html = Tree::build(&parse_source("var x = match 1 { _ => 2 };").unwrap()).to_html("x.lox");
+    assert!(html.contains("<h1>x.lox</h1>"));
+    assert!(html.contains(
+        "<li><details open><summary><code title=\"(var x (match 1 (=&gt; _ 2)))\">var x</code> <em>line 1</em></summary>"
+    ));
+    assert!(html.contains("<li><em>_ =&gt;</em> <code title=\"2\">2</code></li>"));
+}